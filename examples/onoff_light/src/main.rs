@@ -23,7 +23,7 @@ use embassy_futures::select::select3;
 
 use log::info;
 
-use rs_matter::core::{CommissioningData, Matter};
+use rs_matter::core::{CommissioningData, DefaultMatterStorage, Matter};
 use rs_matter::data_model::cluster_basic_information::BasicInfoConfig;
 use rs_matter::data_model::cluster_on_off;
 use rs_matter::data_model::device_types::DEV_TYPE_ON_OFF_LIGHT;
@@ -34,9 +34,11 @@ use rs_matter::error::Error;
 use rs_matter::mdns::MdnsService;
 use rs_matter::persist::Psm;
 use rs_matter::secure_channel::spake2p::VerifierData;
-use rs_matter::transport::core::{PacketBuffers, MATTER_SOCKET_BIND_ADDR};
+use rs_matter::transport::core::{
+    PacketBuffers, MATTER_SOCKET_BIND_ADDR, MATTER_SOCKET_BIND_ADDR_IPV4,
+};
 use rs_matter::utils::select::EitherUnwrap;
-use rs_matter::MATTER_PORT;
+use rs_matter::{node, MATTER_PORT};
 
 mod dev_att;
 
@@ -63,7 +65,7 @@ fn run() -> Result<(), Error> {
 
     info!(
         "Matter memory: Matter={}, PacketBuffers={}",
-        core::mem::size_of::<Matter>(),
+        core::mem::size_of::<Matter<DefaultMatterStorage>>(),
         core::mem::size_of::<PacketBuffers>(),
     );
 
@@ -103,13 +105,15 @@ fn run() -> Result<(), Error> {
     // NOTE:
     // When using a custom UDP stack (e.g. for `no_std` environments), replace with a UDP socket bind for your custom UDP stack
     // The returned socket should be splittable into two halves, where each half implements `UdpSend` and `UdpReceive` respectively
-    let socket = async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR)?;
+    // Fall back to an IPv4 socket on platforms without IPv6
+    let socket = async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR)
+        .or_else(|_| async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR_IPV4))?;
 
-    let mut packet_buffers = PacketBuffers::new();
+    let packet_buffers = PacketBuffers::new();
     let mut runner = pin!(matter.run(
         &socket,
         &socket,
-        &mut packet_buffers,
+        &packet_buffers,
         CommissioningData {
             // TODO: Hard-coded for now
             verifier: VerifierData::new_with_pw(123456, *matter.borrow()),
@@ -123,6 +127,10 @@ fn run() -> Result<(), Error> {
     // NOTE:
     // Replace with your own persister for e.g. `no_std` environments
     let mut psm = Psm::new(&matter, std::env::temp_dir().join("rs-matter"))?;
+
+    // Write the persisted values of the non-volatile attributes back to their clusters
+    futures_lite::future::block_on(matter.restore_nv_attributes(&handler))?;
+
     let mut psm_runner = pin!(psm.run());
 
     let runner = select3(&mut runner, &mut mdns_runner, &mut psm_runner);
@@ -134,32 +142,16 @@ fn run() -> Result<(), Error> {
     Ok(())
 }
 
-const NODE: Node<'static> = Node {
-    id: 0,
-    endpoints: &[
-        root_endpoint::endpoint(0),
-        Endpoint {
-            id: 1,
-            device_type: DEV_TYPE_ON_OFF_LIGHT,
-            clusters: &[descriptor::CLUSTER, cluster_on_off::CLUSTER],
-        },
-    ],
-};
-
 fn handler<'a>(matter: &'a Matter<'a>) -> impl Metadata + NonBlockingHandler + 'a {
-    (
-        NODE,
-        root_endpoint::handler(0, matter)
-            .chain(
-                1,
-                descriptor::ID,
-                descriptor::DescriptorCluster::new(*matter.borrow()),
-            )
-            .chain(
-                1,
-                cluster_on_off::ID,
-                cluster_on_off::OnOffCluster::new(*matter.borrow()),
-            ),
+    node!(
+        id: 0,
+        root: root_endpoint::endpoint(0) => root_endpoint::handler(0, matter),
+        endpoints: [
+            1, DEV_TYPE_ON_OFF_LIGHT => [
+                descriptor::CLUSTER => descriptor::DescriptorCluster::new(*matter.borrow()),
+                cluster_on_off::CLUSTER => cluster_on_off::OnOffCluster::new(*matter.borrow()),
+            ],
+        ],
     )
 }
 
@@ -181,7 +173,7 @@ async fn run_mdns(matter: &Matter<'_>) -> Result<(), Error> {
 
     // NOTE:
     // Replace with your own network initialization for e.g. `no_std` environments
-    fn initialize_network() -> Result<(Ipv4Addr, Ipv6Addr, u32), Error> {
+    fn initialize_network() -> Result<(Ipv4Addr, Option<Ipv6Addr>, u32), Error> {
         use log::error;
         use nix::{net::if_::InterfaceFlags, sys::socket::SockaddrIn6};
         use rs_matter::error::ErrorCode;
@@ -195,52 +187,75 @@ async fn run_mdns(matter: &Matter<'_>) -> Result<(), Error> {
             })
         };
 
+        let link_local_ipv6 = |iname: &str| {
+            interfaces()
+                .filter(|ia| ia.interface_name == iname)
+                .find_map(|ia| {
+                    ia.address
+                        .and_then(|addr| addr.as_sockaddr_in6().map(SockaddrIn6::ip))
+                        .filter(|ip| ip.octets()[..2] == [0xfe, 0x80])
+                })
+        };
+
         // A quick and dirty way to get a network interface that has a link-local IPv6 address assigned as well as a non-loopback IPv4
         // Most likely, this is the interface we need
         // (as opposed to all the docker and libvirt interfaces that might be assigned on the machine and which seem by default to be IPv4 only)
+        // On networks without IPv6, fall back to the first interface with a non-loopback IPv4
         let (iname, ip, ipv6) = interfaces()
             .filter_map(|ia| {
                 ia.address
-                    .and_then(|addr| addr.as_sockaddr_in6().map(SockaddrIn6::ip))
-                    .filter(|ip| ip.octets()[..2] == [0xfe, 0x80])
-                    .map(|ipv6| (ia.interface_name, ipv6))
+                    .and_then(|addr| addr.as_sockaddr_in().map(|addr| addr.ip().into()))
+                    .map(|ip: std::net::Ipv4Addr| (ia.interface_name, ip))
             })
-            .filter_map(|(iname, ipv6)| {
-                interfaces()
-                    .filter(|ia2| ia2.interface_name == iname)
-                    .find_map(|ia2| {
-                        ia2.address
-                            .and_then(|addr| addr.as_sockaddr_in().map(|addr| addr.ip().into()))
-                            .map(|ip: std::net::Ipv4Addr| (iname.clone(), ip, ipv6))
-                    })
+            .map(|(iname, ip)| {
+                let ipv6 = link_local_ipv6(&iname);
+                (iname, ip, ipv6)
             })
-            .next()
+            .min_by_key(|(_, _, ipv6)| ipv6.is_none())
             .ok_or_else(|| {
                 error!("Cannot find network interface suitable for mDNS broadcasting");
                 ErrorCode::StdIoError
             })?;
 
-        info!(
-            "Will use network interface {} with {}/{} for mDNS",
-            iname, ip, ipv6
-        );
-
-        Ok((ip.octets().into(), ipv6.octets().into(), 0 as _))
+        if let Some(ipv6) = ipv6 {
+            info!(
+                "Will use network interface {} with {}/{} for mDNS",
+                iname, ip, ipv6
+            );
+        } else {
+            info!(
+                "Will use network interface {} with {} (IPv4 only) for mDNS",
+                iname, ip
+            );
+        }
+
+        Ok((
+            ip.octets().into(),
+            ipv6.map(|ipv6| ipv6.octets().into()),
+            0 as _,
+        ))
     }
 
     let (ipv4_addr, ipv6_addr, interface) = initialize_network()?;
 
     use rs_matter::mdns::{
         Host, MDNS_IPV4_BROADCAST_ADDR, MDNS_IPV6_BROADCAST_ADDR, MDNS_SOCKET_BIND_ADDR,
+        MDNS_SOCKET_BIND_ADDR_IPV4,
     };
 
     // NOTE:
     // When using a custom UDP stack (e.g. for `no_std` environments), replace with a UDP socket bind + multicast join for your custom UDP stack
     // The returned socket should be splittable into two halves, where each half implements `UdpSend` and `UdpReceive` respectively
-    let socket = async_io::Async::<UdpSocket>::bind(MDNS_SOCKET_BIND_ADDR)?;
-    socket
-        .get_ref()
-        .join_multicast_v6(&MDNS_IPV6_BROADCAST_ADDR, interface)?;
+    let socket = if ipv6_addr.is_some() {
+        let socket = async_io::Async::<UdpSocket>::bind(MDNS_SOCKET_BIND_ADDR)?;
+        socket
+            .get_ref()
+            .join_multicast_v6(&MDNS_IPV6_BROADCAST_ADDR, interface)?;
+
+        socket
+    } else {
+        async_io::Async::<UdpSocket>::bind(MDNS_SOCKET_BIND_ADDR_IPV4)?
+    };
     socket
         .get_ref()
         .join_multicast_v4(&MDNS_IPV4_BROADCAST_ADDR, &ipv4_addr)?;
@@ -253,9 +268,9 @@ async fn run_mdns(matter: &Matter<'_>) -> Result<(), Error> {
                 id: 0,
                 hostname: "rs-matter-demo",
                 ip: ipv4_addr.octets(),
-                ipv6: Some(ipv6_addr.octets()),
+                ipv6: ipv6_addr.map(|ipv6| ipv6.octets()),
             },
-            Some(interface),
+            ipv6_addr.map(|_| interface),
         )
         .await
 }
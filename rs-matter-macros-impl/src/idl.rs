@@ -1,7 +1,10 @@
 use convert_case::{Case, Casing};
 use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::quote;
-use rs_matter_data_model::{Bitmap, Cluster, DataType, Enum, Struct, StructField};
+use rs_matter_data_model::{
+    AccessPrivilege, Attribute, Bitmap, Cluster, Command, DataType, Enum, Struct, StructField,
+    StructType,
+};
 
 /// Some context data for IDL generation
 ///
@@ -271,6 +274,189 @@ fn struct_definition(s: &Struct, context: &IdlGenerateContext) -> TokenStream {
     )
 }
 
+/// The first global attribute ID: `GeneratedCommandList`.
+///
+/// Global attributes are declared by the IDL of every cluster, yet their metadata is provided
+/// by rs-matter itself.
+const GLOBAL_ATTRIBUTES_START: u64 = 0xFFF8;
+
+/// Creates the token stream corresponding to an access privilege, i.e. one of
+/// the `Access::NEED_*` flags.
+fn privilege(privilege: AccessPrivilege, context: &IdlGenerateContext) -> TokenStream {
+    let krate = context.rs_matter_crate.clone();
+
+    match privilege {
+        AccessPrivilege::View => quote!(#krate::data_model::objects::Access::NEED_VIEW),
+        AccessPrivilege::Operate => quote!(#krate::data_model::objects::Access::NEED_OPERATE),
+        AccessPrivilege::Manage => quote!(#krate::data_model::objects::Access::NEED_MANAGE),
+        AccessPrivilege::Administer => quote!(#krate::data_model::objects::Access::NEED_ADMIN),
+    }
+}
+
+/// Creates the token stream corresponding to the metadata of an attribute.
+///
+/// Generates something like
+/// `Attribute::new(AttributeId::Foo as _, Access::READ.union(...), Quality::NONE)`
+/// with a length constraint for the strings of a maximum length.
+fn attribute_definition(
+    a: &Attribute,
+    cluster: &Cluster,
+    context: &IdlGenerateContext,
+) -> TokenStream {
+    let krate = context.rs_matter_crate.clone();
+    let name = Ident::new(&a.field.field.id.to_case(Case::Pascal), Span::call_site());
+
+    let mut access = vec![
+        quote!(#krate::data_model::objects::Access::READ),
+        privilege(a.read_acl, context),
+    ];
+
+    if !a.is_read_only {
+        access.push(quote!(#krate::data_model::objects::Access::WRITE));
+        access.push(privilege(a.write_acl, context));
+    }
+
+    if a.is_timed_write {
+        access.push(quote!(#krate::data_model::objects::Access::TIMED_ONLY));
+    }
+
+    if a.field.is_fabric_sensitive {
+        access.push(quote!(#krate::data_model::objects::Access::FAB_SENSITIVE));
+    }
+
+    let data_type = &a.field.field.data_type;
+
+    // Lists of fabric-scoped structs are fabric-scoped
+    let fabric_scoped = data_type.is_list
+        && cluster
+            .structs
+            .iter()
+            .any(|s| s.id == data_type.name && s.is_fabric_scoped);
+    if fabric_scoped {
+        access.push(quote!(#krate::data_model::objects::Access::FAB_SCOPED));
+    }
+
+    let first = access.remove(0);
+    let access = access
+        .into_iter()
+        .fold(first, |access, flag| quote!(#access.union(#flag)));
+
+    let quality = if a.field.is_nullable {
+        quote!(#krate::data_model::objects::Quality::X)
+    } else {
+        quote!(#krate::data_model::objects::Quality::NONE)
+    };
+
+    let is_string = matches!(
+        data_type.name.as_str(),
+        "char_string" | "long_char_string" | "octet_string" | "long_octet_string"
+    );
+
+    let constraint = match data_type.max_length {
+        Some(max) if is_string && !data_type.is_list => {
+            let max = Literal::u16_unsuffixed(max as u16);
+            quote!(.with_constraint(#krate::data_model::objects::Constraint::Length(0, #max)))
+        }
+        _ => quote!(),
+    };
+
+    quote!(
+        #krate::data_model::objects::Attribute::new(
+            AttributeId::#name as _,
+            #access,
+            #quality,
+        )#constraint
+    )
+}
+
+/// Creates the token stream corresponding to the metadata of an accepted command.
+fn command_definition(c: &Command, context: &IdlGenerateContext) -> TokenStream {
+    let krate = context.rs_matter_crate.clone();
+    let name = Ident::new(&c.id, Span::call_site());
+
+    // Commands are invoked with the WRITE operation, and need at least `Operate`
+    let access = match c.access {
+        AccessPrivilege::View | AccessPrivilege::Operate => {
+            quote!(#krate::data_model::objects::Access::WO)
+        }
+        AccessPrivilege::Manage => quote!(#krate::data_model::objects::Access::WM),
+        AccessPrivilege::Administer => quote!(#krate::data_model::objects::Access::WA),
+    };
+
+    if c.is_timed {
+        quote!(#krate::data_model::objects::Command::new_timed(Commands::#name as _, #access))
+    } else {
+        quote!(#krate::data_model::objects::Command::new(Commands::#name as _, #access))
+    }
+}
+
+/// Creates the token stream corresponding to the cluster metadata, i.e.
+/// `pub const CLUSTER: Cluster<'static> = ...`
+///
+/// The metadata lists all attributes of the cluster - the optional ones included - as well
+/// as all of its commands and events. Handlers implementing a subset of the cluster are
+/// expected to declare their own metadata, picking from the `AttributeId` and `Commands` ids.
+fn cluster_definition(cluster: &Cluster, context: &IdlGenerateContext) -> TokenStream {
+    let krate = context.rs_matter_crate.clone();
+
+    let attributes = cluster
+        .attributes
+        .iter()
+        .filter(|a| a.field.field.code < GLOBAL_ATTRIBUTES_START)
+        .map(|a| attribute_definition(a, cluster, context));
+
+    let commands = cluster
+        .commands
+        .iter()
+        .map(|c| command_definition(c, context));
+
+    let mut generated_commands = cluster
+        .commands
+        .iter()
+        .filter_map(|c| {
+            cluster.structs.iter().find_map(|s| match s.struct_type {
+                StructType::Response(code) if s.id == c.output => Some(code),
+                _ => None,
+            })
+        })
+        .collect::<Vec<_>>();
+    generated_commands.sort();
+    generated_commands.dedup();
+
+    let generated_commands = generated_commands
+        .into_iter()
+        .map(|code| Literal::u32_unsuffixed(code as u32));
+
+    let events = cluster
+        .events
+        .iter()
+        .map(|e| Literal::u32_unsuffixed(e.code as u32));
+
+    quote!(
+        pub const CLUSTER: #krate::data_model::objects::Cluster<'static> =
+            #krate::data_model::objects::Cluster {
+                id: ID as _,
+                feature_map: 0,
+                attributes: &[
+                    #krate::data_model::objects::FEATURE_MAP,
+                    #krate::data_model::objects::ATTRIBUTE_LIST,
+                    #krate::data_model::objects::ACCEPTED_COMMAND_LIST,
+                    #krate::data_model::objects::GENERATED_COMMAND_LIST,
+                    #(#attributes),*
+                ],
+                commands: &[
+                    #(#commands),*
+                ],
+                generated_commands: &[
+                    #(#generated_commands),*
+                ],
+                events: &[
+                    #(#events),*
+                ],
+            };
+    )
+}
+
 pub fn server_side_cluster_generate(
     cluster: &Cluster,
     context: &IdlGenerateContext,
@@ -287,6 +473,19 @@ pub fn server_side_cluster_generate(
         ));
     }
 
+    let attribute_ids = cluster
+        .attributes
+        .iter()
+        .filter(|a| a.field.field.code < GLOBAL_ATTRIBUTES_START)
+        .map(|a| {
+            let attribute_name =
+                Ident::new(&a.field.field.id.to_case(Case::Pascal), Span::call_site());
+            let attribute_code = Literal::i64_unsuffixed(a.field.field.code as i64);
+            quote!(
+                #attribute_name = #attribute_code
+            )
+        });
+
     let cluster_code = Literal::u32_unsuffixed(cluster.code as u32);
 
     let bitmap_declarations = cluster
@@ -298,6 +497,7 @@ pub fn server_side_cluster_generate(
         .structs
         .iter()
         .map(|s| struct_definition(s, context));
+    let cluster_declaration = cluster_definition(cluster, context);
 
     let krate = context.rs_matter_crate.clone();
 
@@ -320,6 +520,14 @@ pub fn server_side_cluster_generate(
             pub enum Commands {
                 #(#commands),*
             }
+
+            #[derive(strum::FromRepr, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+            #[repr(u16)]
+            pub enum AttributeId {
+                #(#attribute_ids),*
+            }
+
+            #cluster_declaration
         }
     )
 }
@@ -681,8 +889,201 @@ mod tests {
                         OnWithRecallGlobalScene = 65,
                         OnWithTimedOff = 66,
                     }
+
+                    #[derive(strum::FromRepr, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+                    #[repr(u16)]
+                    pub enum AttributeId {
+                        OnOff = 0,
+                        GlobalSceneControl = 16384,
+                        OnTime = 16385,
+                        OffWaitTime = 16386,
+                        StartUpOnOff = 16387,
+                    }
+
+                    pub const CLUSTER: rs_matter_crate::data_model::objects::Cluster<'static> =
+                        rs_matter_crate::data_model::objects::Cluster {
+                            id: ID as _,
+                            feature_map: 0,
+                            attributes: &[
+                                rs_matter_crate::data_model::objects::FEATURE_MAP,
+                                rs_matter_crate::data_model::objects::ATTRIBUTE_LIST,
+                                rs_matter_crate::data_model::objects::ACCEPTED_COMMAND_LIST,
+                                rs_matter_crate::data_model::objects::GENERATED_COMMAND_LIST,
+                                rs_matter_crate::data_model::objects::Attribute::new(
+                                    AttributeId::OnOff as _,
+                                    rs_matter_crate::data_model::objects::Access::READ
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW),
+                                    rs_matter_crate::data_model::objects::Quality::NONE,
+                                ),
+                                rs_matter_crate::data_model::objects::Attribute::new(
+                                    AttributeId::GlobalSceneControl as _,
+                                    rs_matter_crate::data_model::objects::Access::READ
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW),
+                                    rs_matter_crate::data_model::objects::Quality::NONE,
+                                ),
+                                rs_matter_crate::data_model::objects::Attribute::new(
+                                    AttributeId::OnTime as _,
+                                    rs_matter_crate::data_model::objects::Access::READ
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW)
+                                        .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_OPERATE),
+                                    rs_matter_crate::data_model::objects::Quality::NONE,
+                                ),
+                                rs_matter_crate::data_model::objects::Attribute::new(
+                                    AttributeId::OffWaitTime as _,
+                                    rs_matter_crate::data_model::objects::Access::READ
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW)
+                                        .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_OPERATE),
+                                    rs_matter_crate::data_model::objects::Quality::NONE,
+                                ),
+                                rs_matter_crate::data_model::objects::Attribute::new(
+                                    AttributeId::StartUpOnOff as _,
+                                    rs_matter_crate::data_model::objects::Access::READ
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW)
+                                        .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                        .union(rs_matter_crate::data_model::objects::Access::NEED_MANAGE),
+                                    rs_matter_crate::data_model::objects::Quality::X,
+                                ),
+                            ],
+                            commands: &[
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::Off as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                ),
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::On as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                ),
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::Toggle as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                ),
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::OffWithEffect as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                ),
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::OnWithRecallGlobalScene as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                ),
+                                rs_matter_crate::data_model::objects::Command::new(
+                                    Commands::OnWithTimedOff as _,
+                                    rs_matter_crate::data_model::objects::Access::WO
+                                )
+                            ],
+                            generated_commands: &[],
+                            events: &[],
+                        };
                 }
             )
         );
     }
+
+    #[test]
+    fn cluster_metadata_generation_works() {
+        let idl = parse_idl(
+            "
+              cluster TestForMetadata = 1 {
+                fabric_scoped struct EntryStruct {
+                  int8u value = 1;
+                  fabric_idx fabricIndex = 254;
+                }
+
+                info event StateChanged = 2 {
+                  int8u value = 0;
+                }
+
+                attribute access(write: administer) char_string<32> label = 0;
+                timedwrite attribute int8u timed = 1;
+                attribute access(read: manage, write: manage) EntryStruct entries[] = 2;
+                readonly attribute int16u clusterRevision = 65533;
+
+                request struct QueryRequest {
+                  int8u value = 0;
+                }
+
+                response struct QueryResponse = 3 {
+                  int8u value = 0;
+                }
+
+                command access(invoke: administer) Query(QueryRequest): QueryResponse = 0;
+                timed command Reset(): DefaultSuccess = 1;
+              }
+            ",
+        );
+
+        let cluster = get_cluster_named(&idl, "TestForMetadata").expect("Cluster exists");
+        let context = IdlGenerateContext::new("rs_matter_crate");
+
+        assert_tokenstreams_eq!(
+            &cluster_definition(cluster, &context),
+            &quote!(
+                pub const CLUSTER: rs_matter_crate::data_model::objects::Cluster<'static> =
+                    rs_matter_crate::data_model::objects::Cluster {
+                        id: ID as _,
+                        feature_map: 0,
+                        attributes: &[
+                            rs_matter_crate::data_model::objects::FEATURE_MAP,
+                            rs_matter_crate::data_model::objects::ATTRIBUTE_LIST,
+                            rs_matter_crate::data_model::objects::ACCEPTED_COMMAND_LIST,
+                            rs_matter_crate::data_model::objects::GENERATED_COMMAND_LIST,
+                            rs_matter_crate::data_model::objects::Attribute::new(
+                                AttributeId::Label as _,
+                                rs_matter_crate::data_model::objects::Access::READ
+                                    .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW)
+                                    .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::NEED_ADMIN,
+                                    ),
+                                rs_matter_crate::data_model::objects::Quality::NONE,
+                            )
+                            .with_constraint(
+                                rs_matter_crate::data_model::objects::Constraint::Length(0, 32),
+                            ),
+                            rs_matter_crate::data_model::objects::Attribute::new(
+                                AttributeId::Timed as _,
+                                rs_matter_crate::data_model::objects::Access::READ
+                                    .union(rs_matter_crate::data_model::objects::Access::NEED_VIEW)
+                                    .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::NEED_OPERATE,
+                                    )
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::TIMED_ONLY,
+                                    ),
+                                rs_matter_crate::data_model::objects::Quality::NONE,
+                            ),
+                            rs_matter_crate::data_model::objects::Attribute::new(
+                                AttributeId::Entries as _,
+                                rs_matter_crate::data_model::objects::Access::READ
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::NEED_MANAGE,
+                                    )
+                                    .union(rs_matter_crate::data_model::objects::Access::WRITE)
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::NEED_MANAGE,
+                                    )
+                                    .union(
+                                        rs_matter_crate::data_model::objects::Access::FAB_SCOPED,
+                                    ),
+                                rs_matter_crate::data_model::objects::Quality::NONE,
+                            ),
+                        ],
+                        commands: &[
+                            rs_matter_crate::data_model::objects::Command::new(
+                                Commands::Query as _,
+                                rs_matter_crate::data_model::objects::Access::WA,
+                            ),
+                            rs_matter_crate::data_model::objects::Command::new_timed(
+                                Commands::Reset as _,
+                                rs_matter_crate::data_model::objects::Access::WO,
+                            ),
+                        ],
+                        generated_commands: &[3],
+                        events: &[2],
+                    };
+            )
+        );
+    }
 }
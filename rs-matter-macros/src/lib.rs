@@ -132,6 +132,13 @@ impl Parse for MatterIdlImportArgs {
 /// at this time only "standard" clusters can be imported.
 ///
 /// `idl_import!(clusters=["OnOff"])` imports the OnOff cluster
+///
+/// For each cluster, a module named after it (e.g. `on_off`) is generated, containing:
+/// - `ID`, the ID of the cluster
+/// - the bitmaps, enums and structs of the cluster, with their TLV support
+/// - `Commands` and `AttributeId`, the IDs of its commands and attributes
+/// - `CLUSTER`, the metadata of the cluster, with all of its attributes (the access
+///   privileges, the nullability and the length of the strings included) and commands
 #[proc_macro]
 pub fn idl_import(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as MatterIdlImportArgs);
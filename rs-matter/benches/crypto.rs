@@ -0,0 +1,334 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Benchmarks for the crypto primitives used during session establishment
+//! (SPAKE2+ for PASE, ECDH/ECDSA and the Sigma2/Sigma3 processing for CASE) and for every
+//! secured message (AES-CCM).
+//!
+//! The backend under test is selected with the usual crate features, e.g.
+//! `cargo bench --bench crypto --no-default-features --features os,rustcrypto`
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use rs_matter::crypto::{self, KeyPair};
+use rs_matter::secure_channel::spake2p::{Spake2P, VerifierData};
+use rs_matter::tlv::{TLVWriter, TagType};
+use rs_matter::utils::rand::sys_rand;
+use rs_matter::utils::writebuf::WriteBuf;
+
+const PASSCODE: u32 = 20202021;
+
+const KEY: [u8; crypto::SYMM_KEY_LEN_BYTES] = [
+    0x44, 0xd4, 0x3c, 0x91, 0xd2, 0x27, 0xf3, 0xba, 0x08, 0x24, 0xc5, 0xd8, 0x7c, 0xb8, 0x1b, 0x33,
+];
+const NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [0; crypto::AEAD_NONCE_LEN_BYTES];
+const AAD: [u8; crypto::AEAD_AAD_LEN_BYTES] = [0x0, 0x11, 0x0, 0x0, 0x29, 0x0, 0x0, 0x0];
+
+/// The pA share of the prover from the first SPAKE2+ test vector of RFC 9383
+const PA: [u8; crypto::EC_POINT_LEN_BYTES] = [
+    0x04, 0xaf, 0x09, 0x98, 0x7a, 0x59, 0x3d, 0x3b, 0xac, 0x86, 0x94, 0xb1, 0x23, 0x83, 0x94, 0x22,
+    0xc3, 0xcc, 0x87, 0xe3, 0x7d, 0x6b, 0x41, 0xc1, 0xd6, 0x30, 0xf0, 0x00, 0xdd, 0x64, 0x98, 0x0e,
+    0x53, 0x7a, 0xe7, 0x04, 0xbc, 0xed, 0xe0, 0x4e, 0xa3, 0xbe, 0xc9, 0xb7, 0x47, 0x5b, 0x32, 0xfa,
+    0x2c, 0xa3, 0xb6, 0x84, 0xbe, 0x14, 0xd1, 0x16, 0x45, 0xe3, 0x8e, 0xa6, 0x60, 0x9e, 0xb3, 0x9e,
+    0x7e,
+];
+
+/// The size of a typical Matter operational certificate in its TLV encoding
+const CERT_LEN: usize = 250;
+
+const SIGMA2_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_Sigma2N";
+const SIGMA3_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_Sigma3N";
+
+#[allow(non_snake_case)]
+fn spake2p(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spake2p");
+
+    let verifier = VerifierData::new_with_pw(PASSCODE, sys_rand);
+
+    group.bench_function("start_verifier", |b| {
+        b.iter(|| {
+            let mut spake2p = Spake2P::new();
+            spake2p.start_verifier(black_box(&verifier)).unwrap();
+            spake2p
+        })
+    });
+
+    group.bench_function("handle_pA", |b| {
+        b.iter_batched(
+            || {
+                let mut spake2p = Spake2P::new();
+                spake2p.set_context(&[0; 32], &[0; 32]).unwrap();
+                spake2p.start_verifier(&verifier).unwrap();
+                spake2p
+            },
+            |mut spake2p| {
+                let mut pB = [0; crypto::EC_POINT_LEN_BYTES];
+                let mut cB = [0; crypto::SHA256_HASH_LEN_BYTES];
+                spake2p
+                    .handle_pA(black_box(&PA), &mut pB, &mut cB, sys_rand)
+                    .unwrap();
+                (pB, cB)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn case(c: &mut Criterion) {
+    let mut group = c.benchmark_group("case");
+
+    group.bench_function("keypair_new", |b| {
+        b.iter(|| KeyPair::new(sys_rand).unwrap())
+    });
+
+    let peer = KeyPair::new(sys_rand).unwrap();
+    let mut peer_pub_key = [0; crypto::EC_POINT_LEN_BYTES];
+    peer.get_public_key(&mut peer_pub_key).unwrap();
+
+    group.bench_function("ecdh_derive_secret", |b| {
+        b.iter_batched(
+            || KeyPair::new(sys_rand).unwrap(),
+            |key_pair| {
+                let mut secret = [0; crypto::ECDH_SHARED_SECRET_LEN_BYTES];
+                key_pair
+                    .derive_secret(black_box(&peer_pub_key), &mut secret)
+                    .unwrap();
+                secret
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let msg = [0x5a; 512];
+    let mut signature = [0; crypto::EC_SIGNATURE_LEN_BYTES];
+    peer.sign_msg(&msg, &mut signature).unwrap();
+
+    group.bench_function("ecdsa_sign", |b| {
+        b.iter(|| {
+            let mut signature = [0; crypto::EC_SIGNATURE_LEN_BYTES];
+            peer.sign_msg(black_box(&msg), &mut signature).unwrap();
+            signature
+        })
+    });
+
+    let verifier = KeyPair::new_from_public(&peer_pub_key).unwrap();
+    group.bench_function("ecdsa_verify", |b| {
+        b.iter(|| {
+            verifier
+                .verify_msg(black_box(&msg), black_box(&signature))
+                .unwrap()
+        })
+    });
+
+    group.bench_function("hkdf_sha256", |b| {
+        b.iter(|| {
+            let mut key = [0; 3 * crypto::SYMM_KEY_LEN_BYTES];
+            crypto::hkdf_sha256(
+                black_box(&[0; 32]),
+                black_box(&[0; crypto::ECDH_SHARED_SECRET_LEN_BYTES]),
+                b"SessionKeys",
+                &mut key,
+            )
+            .unwrap();
+            key
+        })
+    });
+
+    group.finish();
+}
+
+fn aes_ccm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aes_ccm");
+
+    for len in [64, 512, 1024] {
+        group.bench_function(format!("encrypt_{len}"), |b| {
+            let mut data = vec![0x5a; len + crypto::AEAD_MIC_LEN_BYTES];
+            b.iter(|| {
+                crypto::encrypt_in_place(&KEY, &NONCE, &AAD, black_box(&mut data), len).unwrap()
+            })
+        });
+
+        let mut cipher_text = vec![0x5a; len + crypto::AEAD_MIC_LEN_BYTES];
+        crypto::encrypt_in_place(&KEY, &NONCE, &AAD, &mut cipher_text, len).unwrap();
+
+        group.bench_function(format!("decrypt_{len}"), |b| {
+            b.iter_batched(
+                || cipher_text.clone(),
+                |mut data| crypto::decrypt_in_place(&KEY, &NONCE, &AAD, &mut data).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Encodes the to-be-signed data of a Sigma2 or Sigma3 message, i.e. the NOC and ICAC of the
+/// signer followed by the ephemeral public keys of both peers
+fn sigma_tbs(
+    noc: &[u8],
+    icac: &[u8],
+    pub_key: &[u8],
+    peer_pub_key: &[u8],
+    buf: &mut [u8],
+) -> usize {
+    let mut wb = WriteBuf::new(buf);
+    let mut tw = TLVWriter::new(&mut wb);
+    tw.start_struct(TagType::Anonymous).unwrap();
+    tw.str16(TagType::Context(1), noc).unwrap();
+    tw.str16(TagType::Context(2), icac).unwrap();
+    tw.str8(TagType::Context(3), pub_key).unwrap();
+    tw.str8(TagType::Context(4), peer_pub_key).unwrap();
+    tw.end_container().unwrap();
+    wb.as_slice().len()
+}
+
+/// Encodes and encrypts the TBEData of a Sigma2 or Sigma3 message with `key`, returning
+/// the length of the cipher text (including the MIC)
+fn sigma_tbe(
+    key: &[u8],
+    nonce: &[u8; crypto::AEAD_NONCE_LEN_BYTES],
+    noc: &[u8],
+    icac: &[u8],
+    signature: &[u8],
+    buf: &mut [u8],
+) -> usize {
+    let mut wb = WriteBuf::new(buf);
+    let mut tw = TLVWriter::new(&mut wb);
+    tw.start_struct(TagType::Anonymous).unwrap();
+    tw.str16(TagType::Context(1), noc).unwrap();
+    tw.str16(TagType::Context(2), icac).unwrap();
+    tw.str8(TagType::Context(3), signature).unwrap();
+    tw.end_container().unwrap();
+    wb.append(&[0; crypto::AEAD_MIC_LEN_BYTES]).unwrap();
+
+    let len = wb.as_slice().len();
+    crypto::encrypt_in_place(
+        key,
+        nonce,
+        &[],
+        wb.as_mut_slice(),
+        len - crypto::AEAD_MIC_LEN_BYTES,
+    )
+    .unwrap();
+
+    len
+}
+
+/// The work of a CASE responder for each handshake: building the Sigma2 message out of a
+/// received Sigma1, and validating the Sigma3 of the initiator
+fn case_sigma(c: &mut Criterion) {
+    let mut group = c.benchmark_group("case_sigma");
+
+    let noc = [0x5a; CERT_LEN];
+    let icac = [0xa5; CERT_LEN];
+    let ipk = [0x11; crypto::SYMM_KEY_LEN_BYTES];
+
+    let op_key = KeyPair::new(sys_rand).unwrap();
+
+    let initiator = KeyPair::new(sys_rand).unwrap();
+    let mut initiator_pub_key = [0; crypto::EC_POINT_LEN_BYTES];
+    initiator.get_public_key(&mut initiator_pub_key).unwrap();
+
+    // Sigma1 -> Sigma2: ephemeral key, shared secret, TBS signature, S2K derivation, TBE encryption
+    group.bench_function("sigma2", |b| {
+        b.iter(|| {
+            let key_pair = KeyPair::new(sys_rand).unwrap();
+            let mut our_pub_key = [0; crypto::EC_POINT_LEN_BYTES];
+            key_pair.get_public_key(&mut our_pub_key).unwrap();
+
+            let mut shared_secret = [0; crypto::ECDH_SHARED_SECRET_LEN_BYTES];
+            key_pair
+                .derive_secret(black_box(&initiator_pub_key), &mut shared_secret)
+                .unwrap();
+
+            let mut tbs = [0; 800];
+            let len = sigma_tbs(&noc, &icac, &our_pub_key, &initiator_pub_key, &mut tbs);
+            let mut signature = [0; crypto::EC_SIGNATURE_LEN_BYTES];
+            op_key.sign_msg(&tbs[..len], &mut signature).unwrap();
+
+            let mut s2k = [0; crypto::SYMM_KEY_LEN_BYTES];
+            crypto::hkdf_sha256(&ipk, &shared_secret, b"Sigma2", &mut s2k).unwrap();
+
+            let mut tbe = [0; 800];
+            let len = sigma_tbe(&s2k, &SIGMA2_NONCE, &noc, &icac, &signature, &mut tbe);
+            (tbe, len)
+        })
+    });
+
+    // A Sigma3 of the initiator, as encrypted for the benchmarked responder
+    let responder = KeyPair::new(sys_rand).unwrap();
+    let mut responder_pub_key = [0; crypto::EC_POINT_LEN_BYTES];
+    responder.get_public_key(&mut responder_pub_key).unwrap();
+
+    let mut shared_secret = [0; crypto::ECDH_SHARED_SECRET_LEN_BYTES];
+    responder
+        .derive_secret(&initiator_pub_key, &mut shared_secret)
+        .unwrap();
+
+    let mut tbs = [0; 800];
+    let len = sigma_tbs(
+        &noc,
+        &icac,
+        &initiator_pub_key,
+        &responder_pub_key,
+        &mut tbs,
+    );
+    let mut signature = [0; crypto::EC_SIGNATURE_LEN_BYTES];
+    initiator.sign_msg(&tbs[..len], &mut signature).unwrap();
+
+    let mut s3k = [0; crypto::SYMM_KEY_LEN_BYTES];
+    crypto::hkdf_sha256(&ipk, &shared_secret, b"Sigma3", &mut s3k).unwrap();
+
+    let mut sigma3 = [0; 800];
+    let len = sigma_tbe(&s3k, &SIGMA3_NONCE, &noc, &icac, &signature, &mut sigma3);
+    let sigma3 = &sigma3[..len];
+
+    // Sigma3: S3K derivation, TBE decryption, TBS signature verification
+    group.bench_function("sigma3", |b| {
+        b.iter_batched(
+            || sigma3.to_vec(),
+            |mut sigma3| {
+                let mut s3k = [0; crypto::SYMM_KEY_LEN_BYTES];
+                crypto::hkdf_sha256(&ipk, black_box(&shared_secret), b"Sigma3", &mut s3k).unwrap();
+
+                crypto::decrypt_in_place(&s3k, &SIGMA3_NONCE, &[], &mut sigma3).unwrap();
+
+                let mut tbs = [0; 800];
+                let len = sigma_tbs(
+                    &noc,
+                    &icac,
+                    &initiator_pub_key,
+                    &responder_pub_key,
+                    &mut tbs,
+                );
+                KeyPair::new_from_public(&initiator_pub_key)
+                    .unwrap()
+                    .verify_msg(&tbs[..len], &signature)
+                    .unwrap();
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, spake2p, case, case_sigma, aes_ccm);
+criterion_main!(benches);
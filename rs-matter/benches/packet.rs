@@ -0,0 +1,97 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Benchmarks for the message codec: plain and protocol header encoding/decoding,
+//! with and without AES-CCM encryption of the payload
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use rs_matter::transport::network::Address;
+use rs_matter::transport::packet::{Packet, MAX_RX_BUF_SIZE, MAX_TX_BUF_SIZE};
+
+const KEY: [u8; 16] = [
+    0x44, 0xd4, 0x3c, 0x91, 0xd2, 0x27, 0xf3, 0xba, 0x08, 0x24, 0xc5, 0xd8, 0x7c, 0xb8, 0x1b, 0x33,
+];
+const LOCAL_NODEID: u64 = 0x1122_3344;
+const PAYLOAD_LEN: usize = 512;
+
+fn encode(buf: &mut [u8], enc_key: Option<&[u8]>) -> usize {
+    let mut tx = Packet::new_tx(buf);
+
+    tx.plain.sess_id = if enc_key.is_some() { 1 } else { 0 };
+    tx.plain.ctr = 41;
+    tx.set_proto_id(0x0001);
+    tx.set_proto_opcode(0x02);
+    tx.proto.exch_id = 0x1234;
+    tx.proto.set_ack(40);
+    tx.get_writebuf()
+        .unwrap()
+        .append(&[0x5a; PAYLOAD_LEN])
+        .unwrap();
+
+    tx.proto_encode(
+        Address::default(),
+        None,
+        LOCAL_NODEID,
+        enc_key.is_none(),
+        enc_key,
+    )
+    .unwrap();
+
+    tx.as_slice().len()
+}
+
+fn decode(buf: &mut [u8], len: usize, dec_key: Option<&[u8]>) {
+    let mut rx = Packet::new_rx(buf);
+    rx.get_parsebuf().unwrap().set_len(len);
+
+    rx.plain_hdr_decode().unwrap();
+    rx.proto_decode(LOCAL_NODEID, dec_key).unwrap();
+}
+
+fn codec(c: &mut Criterion, name: &str, key: Option<&[u8]>) {
+    let mut group = c.benchmark_group(name);
+
+    group.bench_function("encode", |b| {
+        let mut buf = [0; MAX_TX_BUF_SIZE];
+        b.iter(|| encode(&mut buf, black_box(key)))
+    });
+
+    let mut encoded = [0; MAX_RX_BUF_SIZE];
+    let len = encode(&mut encoded, key);
+
+    group.bench_function("decode", |b| {
+        b.iter_batched_ref(
+            || encoded,
+            |buf| decode(buf, len, black_box(key)),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn plain_text(c: &mut Criterion) {
+    codec(c, "packet_plain_text", None);
+}
+
+fn encrypted(c: &mut Criterion) {
+    codec(c, "packet_encrypted", Some(&KEY));
+}
+
+criterion_group!(benches, plain_text, encrypted);
+criterion_main!(benches);
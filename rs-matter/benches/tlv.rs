@@ -0,0 +1,149 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Benchmarks for TLV encoding and decoding of representative Interaction Model messages
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use rs_matter::data_model::objects::EncodeValue;
+use rs_matter::interaction_model::messages::ib::{AttrData, AttrPath, CmdData, CmdPath};
+use rs_matter::interaction_model::messages::msg::{InvReq, ReadReq, WriteReq};
+use rs_matter::interaction_model::messages::GenericPath;
+use rs_matter::tlv::{get_root_node_struct, FromTLV, TLVArray, TLVWriter, TagType, ToTLV};
+use rs_matter::utils::writebuf::WriteBuf;
+
+const BUF_SIZE: usize = 1024;
+
+fn encode<T: ToTLV>(what: &T, buf: &mut [u8]) -> usize {
+    let mut wb = WriteBuf::new(buf);
+    let mut tw = TLVWriter::new(&mut wb);
+    what.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+    wb.as_slice().len()
+}
+
+fn attr_paths() -> [AttrPath; 8] {
+    core::array::from_fn(|i| {
+        AttrPath::new(&GenericPath::new(
+            Some(i as u16),
+            Some(0x0006),
+            Some(i as u32),
+        ))
+    })
+}
+
+fn read_req(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tlv_read_req");
+
+    let paths = attr_paths();
+    let req = ReadReq::new(true).set_attr_requests(&paths);
+
+    group.bench_function("encode", |b| {
+        let mut buf = [0; BUF_SIZE];
+        b.iter(|| encode(black_box(&req), &mut buf))
+    });
+
+    let mut buf = [0; BUF_SIZE];
+    let len = encode(&req, &mut buf);
+    let encoded = &buf[..len];
+
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let root = get_root_node_struct(black_box(encoded)).unwrap();
+            let req = ReadReq::from_tlv(&root).unwrap();
+            req.attr_requests.unwrap().iter().count()
+        })
+    });
+
+    group.finish();
+}
+
+fn write_req(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tlv_write_req");
+
+    let writer = |t: TagType, tw: &mut TLVWriter| {
+        tw.u32(t, 0xcafe).unwrap();
+    };
+
+    let paths = attr_paths();
+    let attrs: [AttrData; 8] = core::array::from_fn(|i| {
+        AttrData::new(Some(i as u32), paths[i].clone(), EncodeValue::Closure(&writer))
+    });
+    let req = WriteReq::new(false, &attrs);
+
+    group.bench_function("encode", |b| {
+        let mut buf = [0; BUF_SIZE];
+        b.iter(|| encode(black_box(&req), &mut buf))
+    });
+
+    let mut buf = [0; BUF_SIZE];
+    let len = encode(&req, &mut buf);
+    let encoded = &buf[..len];
+
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let root = get_root_node_struct(black_box(encoded)).unwrap();
+            let req = WriteReq::from_tlv(&root).unwrap();
+            req.write_requests.iter().count()
+        })
+    });
+
+    group.finish();
+}
+
+fn invoke_req(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tlv_invoke_req");
+
+    let writer = |t: TagType, tw: &mut TLVWriter| {
+        tw.start_struct(t).unwrap();
+        tw.u8(TagType::Context(0), 1).unwrap();
+        tw.end_container().unwrap();
+    };
+
+    let cmds: [CmdData; 4] = core::array::from_fn(|i| {
+        CmdData::new(
+            CmdPath::new(Some(i as u16), Some(0x0006), Some(1)),
+            EncodeValue::Closure(&writer),
+        )
+    });
+    let req = InvReq {
+        suppress_response: Some(false),
+        timed_request: Some(false),
+        inv_requests: Some(TLVArray::new(&cmds)),
+    };
+
+    group.bench_function("encode", |b| {
+        let mut buf = [0; BUF_SIZE];
+        b.iter(|| encode(black_box(&req), &mut buf))
+    });
+
+    let mut buf = [0; BUF_SIZE];
+    let len = encode(&req, &mut buf);
+    let encoded = &buf[..len];
+
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let root = get_root_node_struct(black_box(encoded)).unwrap();
+            let req = InvReq::from_tlv(&root).unwrap();
+            req.inv_requests.unwrap().iter().count()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, read_req, write_req, invoke_req);
+criterion_main!(benches);
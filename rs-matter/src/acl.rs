@@ -32,7 +32,7 @@ use num_derive::FromPrimitive;
 // Matter Minimum Requirements
 pub const SUBJECTS_PER_ENTRY: usize = 4;
 pub const TARGETS_PER_ENTRY: usize = 3;
-pub const ENTRIES_PER_FABRIC: usize = 3;
+pub const ENTRIES_PER_FABRIC: usize = crate::config::MAX_ACL_ENTRIES_PER_FABRIC;
 
 // TODO: Check if this and the SessionMode can be combined into some generic data structure
 #[derive(FromPrimitive, Copy, Clone, PartialEq, Debug)]
@@ -71,12 +71,13 @@ impl ToTLV for AuthMode {
 const MAX_ACCESSOR_SUBJECTS: usize = 1 + MAX_CAT_IDS_PER_NOC;
 /// The CAT Prefix used in Subjects
 pub const NOC_CAT_SUBJECT_PREFIX: u64 = 0xFFFF_FFFD_0000_0000;
+const NOC_CAT_SUBJECT_PREFIX_MASK: u64 = 0xFFFF_FFFF_0000_0000;
 const NOC_CAT_ID_MASK: u64 = 0xFFFF_0000;
 const NOC_CAT_VERSION_MASK: u64 = 0xFFFF;
 
 /// Is this identifier a NOC CAT
 fn is_noc_cat(id: u64) -> bool {
-    (id & NOC_CAT_SUBJECT_PREFIX) == NOC_CAT_SUBJECT_PREFIX
+    (id & NOC_CAT_SUBJECT_PREFIX_MASK) == NOC_CAT_SUBJECT_PREFIX
 }
 
 /// Get the 16-bit NOC CAT id from the identifier
@@ -146,7 +147,12 @@ impl Display for AccessorSubjects {
         write!(f, "[")?;
         for i in self.0 {
             if is_noc_cat(i) {
-                write!(f, "CAT({} - {})", get_noc_cat_id(i), get_noc_cat_version(i))?;
+                write!(
+                    f,
+                    "CAT({} - {}), ",
+                    get_noc_cat_id(i),
+                    get_noc_cat_version(i)
+                )?;
             } else if i != 0 {
                 write!(f, "{}, ", i)?;
             }
@@ -180,6 +186,12 @@ impl<'a> Accessor<'a> {
                 }
                 Accessor::new(c.fab_idx, subject, AuthMode::Case, acl_mgr)
             }
+            SessionMode::Group(g) => Accessor::new(
+                g.fab_idx,
+                AccessorSubjects::new(g.group_id as u64),
+                AuthMode::Group,
+                acl_mgr,
+            ),
             SessionMode::Pase => {
                 Accessor::new(0, AccessorSubjects::new(1), AuthMode::Pase, acl_mgr)
             }
@@ -316,6 +328,11 @@ impl AclEntry {
     }
 
     pub fn add_subject(&mut self, subject: u64) -> Result<(), Error> {
+        // Version 0 is reserved, so no NOC can carry such a CAT
+        if is_noc_cat(subject) && get_noc_cat_version(subject) == 0 {
+            Err(ErrorCode::Invalid)?;
+        }
+
         let index = self
             .subjects
             .iter()
@@ -377,7 +394,10 @@ impl AclEntry {
             Some(targets) => {
                 for t in targets.iter().flatten() {
                     entries_exist = true;
-                    if (t.endpoint.is_none() || t.endpoint == object.path.endpoint)
+                    // Device type targets are not supported yet: rather than granting access
+                    // to all endpoints and clusters, such targets never match
+                    if t.device_type.is_none()
+                        && (t.endpoint.is_none() || t.endpoint == object.path.endpoint)
                         && (t.cluster.is_none() || t.cluster == object.path.cluster)
                     {
                         allow = true
@@ -726,6 +746,29 @@ mod tests {
         assert_eq!(req.allow(), true);
     }
 
+    #[test]
+    fn test_cat_subject_prefix() {
+        let am = RefCell::new(AclMgr::new());
+        am.borrow_mut().erase_all().unwrap();
+
+        // A node id which only shares some of the bits of the CAT prefix is not a CAT
+        let subjects = AccessorSubjects::new(0xFFFF_FFFF_ABCD_0002);
+
+        let accessor = Accessor::new(2, subjects, AuthMode::Case, &am);
+        let path = GenericPath::new(Some(1), Some(1234), None);
+        let mut req = AccessReq::new(&accessor, path, Access::READ);
+        req.set_target_perms(Access::RWVA);
+
+        let mut new = AclEntry::new(2, Privilege::VIEW, AuthMode::Case);
+        new.add_subject_catid(gen_noc_cat(0xABCD, 1)).unwrap();
+        am.borrow_mut().add(new).unwrap();
+        assert_eq!(req.allow(), false);
+
+        // Version 0 of a CAT is reserved
+        let mut new = AclEntry::new(2, Privilege::VIEW, AuthMode::Case);
+        assert!(new.add_subject_catid(gen_noc_cat(0xABCD, 0)).is_err());
+    }
+
     #[test]
     fn test_target() {
         let am = RefCell::new(AclMgr::new());
@@ -746,6 +789,17 @@ mod tests {
         am.borrow_mut().add(new).unwrap();
         assert_eq!(req.allow(), false);
 
+        // Deny for a device type target
+        let mut new = AclEntry::new(2, Privilege::VIEW, AuthMode::Case);
+        new.add_target(Target {
+            cluster: None,
+            endpoint: None,
+            device_type: Some(0x0100),
+        })
+        .unwrap();
+        am.borrow_mut().add(new).unwrap();
+        assert_eq!(req.allow(), false);
+
         // Allow for cluster match - subject wildcard
         let mut new = AclEntry::new(2, Privilege::VIEW, AuthMode::Case);
         new.add_target(Target {
@@ -827,6 +881,32 @@ mod tests {
         assert_eq!(req.allow(), true);
     }
 
+    #[test]
+    fn test_command_privilege() {
+        let am = RefCell::new(AclMgr::new());
+        am.borrow_mut().erase_all().unwrap();
+        let accessor = Accessor::new(2, AccessorSubjects::new(112233), AuthMode::Case, &am);
+        let path = GenericPath::new(Some(1), Some(1234), Some(1));
+
+        let mut new = AclEntry::new(2, Privilege::OPERATE, AuthMode::Case);
+        new.add_subject(112233).unwrap();
+        am.borrow_mut().add(new).unwrap();
+
+        // Invoke of an Operate command with Operate privilege - allow
+        let mut req = AccessReq::new(&accessor, path.clone(), Access::WRITE);
+        req.set_target_perms(Access::WO);
+        assert_eq!(req.allow(), true);
+
+        // Invoke of a Manage or an Administer command with Operate privilege - deny
+        let mut req = AccessReq::new(&accessor, path.clone(), Access::WRITE);
+        req.set_target_perms(Access::WM);
+        assert_eq!(req.allow(), false);
+
+        let mut req = AccessReq::new(&accessor, path, Access::WRITE);
+        req.set_target_perms(Access::WA);
+        assert_eq!(req.allow(), false);
+    }
+
     #[test]
     fn test_delete_for_fabric() {
         let am = RefCell::new(AclMgr::new());
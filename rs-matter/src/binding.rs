@@ -0,0 +1,429 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The binding table of the node, i.e. the peers - nodes or groups - its endpoints act upon as
+//! clients, e.g. the lights a switch controls.
+//!
+//! The table is written by the administrators over the Binding cluster (see
+//! [`crate::data_model::cluster_binding`]) and persisted together with the fabrics and the ACLs
+//! (see [`crate::Matter::store_bindings`]). The application acts upon the bound peers by
+//! resolving the bindings of its client cluster (see [`crate::Matter::resolve_bindings`]),
+//! and - for the bound nodes - by interacting with them over an exchange opened with
+//! [`crate::Matter::initiate_binding`].
+
+use heapless::Vec;
+
+use crate::{
+    data_model::objects::{ClusterId, EndptId},
+    error::{Error, ErrorCode},
+    tlv::{self, FromTLV, TLVList, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+pub const MAX_BINDINGS_PER_FABRIC: usize = crate::config::MAX_BINDINGS_PER_FABRIC;
+pub const MAX_BINDINGS: usize = MAX_BINDINGS_PER_FABRIC * crate::config::MAX_FABRICS;
+
+/// An entry of the Binding attribute, i.e. the TargetStruct of the Binding cluster
+///
+/// A target is either a node - with the endpoint on it - or a group, optionally restricted
+/// to a single cluster.
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+#[tlvargs(start = 1)]
+pub struct Target {
+    pub node: Option<u64>,
+    pub group: Option<u16>,
+    pub endpoint: Option<EndptId>,
+    pub cluster: Option<ClusterId>,
+    #[tagval(0xFE)]
+    pub fab_idx: Option<u8>,
+}
+
+impl Target {
+    pub const fn node(
+        fab_idx: u8,
+        node_id: u64,
+        endpoint: EndptId,
+        cluster: Option<ClusterId>,
+    ) -> Self {
+        Self {
+            node: Some(node_id),
+            group: None,
+            endpoint: Some(endpoint),
+            cluster,
+            fab_idx: Some(fab_idx),
+        }
+    }
+
+    pub const fn group(fab_idx: u8, group_id: u16, cluster: Option<ClusterId>) -> Self {
+        Self {
+            node: None,
+            group: Some(group_id),
+            endpoint: None,
+            cluster,
+            fab_idx: Some(fab_idx),
+        }
+    }
+
+    /// Checks that the target is either a node with an endpoint, or a group without one
+    pub fn validate(&self) -> Result<(), Error> {
+        match (self.node, self.group, self.endpoint) {
+            (Some(_), None, Some(_)) | (None, Some(_), None) => Ok(()),
+            _ => Err(ErrorCode::ConstraintError.into()),
+        }
+    }
+
+    /// Resolves the target into the peer it binds to
+    pub fn resolve(&self) -> Option<Binding> {
+        let fab_idx = self.fab_idx?;
+
+        match (self.node, self.group, self.endpoint) {
+            (Some(node_id), None, Some(endpoint)) => Some(Binding::Node {
+                fab_idx,
+                node_id,
+                endpoint,
+                cluster: self.cluster,
+            }),
+            (None, Some(group_id), None) => Some(Binding::Group {
+                fab_idx,
+                group_id,
+                cluster: self.cluster,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A resolved binding, as handed to the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// The endpoint of a node, to be acted upon over a CASE session with the node
+    Node {
+        fab_idx: u8,
+        node_id: u64,
+        endpoint: EndptId,
+        cluster: Option<ClusterId>,
+    },
+    /// A group, to be acted upon with group messages
+    Group {
+        fab_idx: u8,
+        group_id: u16,
+        cluster: Option<ClusterId>,
+    },
+}
+
+impl Binding {
+    pub fn fab_idx(&self) -> u8 {
+        match self {
+            Self::Node { fab_idx, .. } | Self::Group { fab_idx, .. } => *fab_idx,
+        }
+    }
+
+    /// The cluster the binding is restricted to, if any
+    pub fn cluster(&self) -> Option<ClusterId> {
+        match self {
+            Self::Node { cluster, .. } | Self::Group { cluster, .. } => *cluster,
+        }
+    }
+}
+
+/// A target, together with the local endpoint it is bound to
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+struct BindingEntry {
+    endpoint: EndptId,
+    target: Target,
+}
+
+type BindingEntries = Vec<Option<BindingEntry>, MAX_BINDINGS>;
+
+pub struct BindingMgr {
+    entries: BindingEntries,
+    changed: bool,
+}
+
+impl BindingMgr {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            entries: BindingEntries::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut self.entries, &root)?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.entries
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Appends a target to the bindings of the local endpoint `endpoint`, on the fabric of
+    /// the target
+    pub fn add(&mut self, endpoint: EndptId, target: Target) -> Result<(), Error> {
+        target.validate()?;
+
+        let fab_idx = target.fab_idx.ok_or(ErrorCode::Invalid)?;
+
+        if self
+            .iter()
+            .filter(|e| e.target.fab_idx == Some(fab_idx))
+            .count()
+            >= MAX_BINDINGS_PER_FABRIC
+        {
+            Err(ErrorCode::ResourceExhausted)?;
+        }
+
+        let entry = BindingEntry { endpoint, target };
+
+        if let Some(slot) = self.entries.iter_mut().find(|e| e.is_none()) {
+            *slot = Some(entry);
+        } else {
+            self.entries
+                .push(Some(entry))
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    // Since the bindings are fabric-scoped, the index is only for the bindings of the local
+    // endpoint with the matching fabric index
+    pub fn edit(
+        &mut self,
+        endpoint: EndptId,
+        fab_idx: u8,
+        index: usize,
+        target: Target,
+    ) -> Result<(), Error> {
+        target.validate()?;
+
+        let slot = self.for_index(endpoint, fab_idx, index)?;
+        *slot = Some(BindingEntry { endpoint, target });
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, endpoint: EndptId, fab_idx: u8, index: usize) -> Result<(), Error> {
+        let slot = self.for_index(endpoint, fab_idx, index)?;
+        *slot = None;
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes all bindings of the local endpoint `endpoint` on the given fabric
+    pub fn delete_all(&mut self, endpoint: EndptId, fab_idx: u8) {
+        for slot in self.entries.iter_mut() {
+            if slot
+                .as_ref()
+                .map(|e| e.endpoint == endpoint && e.target.fab_idx == Some(fab_idx))
+                .unwrap_or(false)
+            {
+                *slot = None;
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Removes all bindings on the given fabric, e.g. when the fabric itself is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        for slot in self.entries.iter_mut() {
+            if slot
+                .as_ref()
+                .map(|e| e.target.fab_idx == Some(fab_idx))
+                .unwrap_or(false)
+            {
+                *slot = None;
+                self.changed = true;
+            }
+        }
+    }
+
+    /// The targets of the local endpoint `endpoint`, on all fabrics
+    pub fn targets(&self, endpoint: EndptId) -> impl Iterator<Item = &Target> {
+        self.iter()
+            .filter(move |e| e.endpoint == endpoint)
+            .map(|e| &e.target)
+    }
+
+    /// The resolved bindings of the local endpoint `endpoint` which apply to its client cluster
+    /// `cluster`, i.e. those restricted to that cluster, and those not restricted to any
+    pub fn resolve(
+        &self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+    ) -> impl Iterator<Item = Binding> + '_ {
+        self.targets(endpoint)
+            .filter_map(Target::resolve)
+            .filter(move |b| b.cluster().map(|c| c == cluster).unwrap_or(true))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &BindingEntry> {
+        self.entries.iter().flatten()
+    }
+
+    fn for_index(
+        &mut self,
+        endpoint: EndptId,
+        fab_idx: u8,
+        index: usize,
+    ) -> Result<&mut Option<BindingEntry>, Error> {
+        let slot = self
+            .entries
+            .iter_mut()
+            .filter(|e| {
+                e.as_ref()
+                    .map(|e| e.endpoint == endpoint && e.target.fab_idx == Some(fab_idx))
+                    .unwrap_or(false)
+            })
+            .nth(index)
+            .ok_or(ErrorCode::NotFound)?;
+
+        Ok(slot)
+    }
+}
+
+impl Default for BindingMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Binding, BindingMgr, Target, MAX_BINDINGS_PER_FABRIC};
+
+    #[test]
+    fn test_crud() {
+        let mut mgr = BindingMgr::new();
+
+        mgr.add(1, Target::node(1, 100, 1, Some(0x0006))).unwrap();
+        mgr.add(1, Target::group(1, 0x10, None)).unwrap();
+        mgr.add(1, Target::node(2, 200, 1, None)).unwrap();
+        mgr.add(2, Target::node(1, 101, 1, None)).unwrap();
+
+        // Neither a node without an endpoint, nor a group with one
+        let mut invalid = Target::node(1, 100, 1, None);
+        invalid.endpoint = None;
+        assert!(mgr.add(1, invalid).is_err());
+        let mut invalid = Target::group(1, 0x10, None);
+        invalid.endpoint = Some(1);
+        assert!(mgr.add(1, invalid).is_err());
+
+        assert_eq!(mgr.targets(1).count(), 3);
+
+        // The indexes are per endpoint and fabric
+        mgr.edit(1, 1, 1, Target::group(1, 0x20, None)).unwrap();
+        assert!(mgr.edit(1, 2, 1, Target::group(2, 0x20, None)).is_err());
+        assert!(mgr.targets(1).any(|t| t.group == Some(0x20)));
+
+        mgr.delete(1, 1, 0).unwrap();
+        assert!(mgr.targets(1).all(|t| t.node != Some(100)));
+
+        mgr.delete_all(1, 1);
+        assert!(mgr.targets(1).eq([&Target::node(2, 200, 1, None)]));
+
+        mgr.remove_fabric(2);
+        assert_eq!(mgr.targets(1).count(), 0);
+        assert_eq!(mgr.targets(2).count(), 1);
+    }
+
+    #[test]
+    fn test_per_fabric_limit() {
+        let mut mgr = BindingMgr::new();
+
+        for node_id in 0..MAX_BINDINGS_PER_FABRIC as u64 {
+            mgr.add(1, Target::node(1, node_id, 1, None)).unwrap();
+        }
+
+        assert!(mgr.add(1, Target::group(1, 0x10, None)).is_err());
+        mgr.add(1, Target::group(2, 0x10, None)).unwrap();
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut mgr = BindingMgr::new();
+
+        mgr.add(1, Target::node(1, 100, 2, Some(0x0006))).unwrap();
+        mgr.add(1, Target::node(1, 101, 3, Some(0x0008))).unwrap();
+        mgr.add(1, Target::group(1, 0x10, None)).unwrap();
+
+        assert!(mgr.resolve(1, 0x0006).eq([
+            Binding::Node {
+                fab_idx: 1,
+                node_id: 100,
+                endpoint: 2,
+                cluster: Some(0x0006),
+            },
+            Binding::Group {
+                fab_idx: 1,
+                group_id: 0x10,
+                cluster: None,
+            },
+        ]));
+        assert_eq!(mgr.resolve(2, 0x0006).count(), 0);
+    }
+
+    #[test]
+    fn test_store_load() {
+        let mut mgr = BindingMgr::new();
+        assert!(!mgr.is_changed());
+
+        mgr.add(1, Target::node(1, 100, 2, Some(0x0006))).unwrap();
+        mgr.add(1, Target::group(1, 0x10, None)).unwrap();
+        assert!(mgr.is_changed());
+
+        let mut buf = [0; 256];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+        assert!(!mgr.is_changed());
+
+        let mut loaded = BindingMgr::new();
+        loaded.load(data).unwrap();
+
+        assert!(loaded.targets(1).eq(mgr.targets(1)));
+    }
+}
@@ -0,0 +1,392 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Validation of the Device Attestation certificate chains (DAC -> PAI -> PAA), as done by a
+//! commissioner before it trusts a device, against a store of trusted Product Attestation
+//! Authorities.
+
+use log::error;
+
+use crate::error::{Error, ErrorCode};
+
+use super::x509::{
+    X509Cert, KEY_USAGE_CRL_SIGN, KEY_USAGE_DIGITAL_SIGNATURE, KEY_USAGE_KEY_CERT_SIGN,
+};
+
+/// A store of trusted Product Attestation Authority (PAA) certificates
+pub trait PaaStore {
+    /// Returns the DER encoding of the trusted PAA certificate with the Subject Key Identifier
+    /// `subject_key_id`, if any
+    fn get_paa(&self, subject_key_id: &[u8]) -> Option<&[u8]>;
+}
+
+impl<T> PaaStore for &T
+where
+    T: PaaStore + ?Sized,
+{
+    fn get_paa(&self, subject_key_id: &[u8]) -> Option<&[u8]> {
+        (**self).get_paa(subject_key_id)
+    }
+}
+
+/// A fixed list of trusted PAA certificates, in DER form
+impl PaaStore for [&[u8]] {
+    fn get_paa(&self, subject_key_id: &[u8]) -> Option<&[u8]> {
+        self.iter().copied().find(|paa| {
+            X509Cert::new(paa)
+                .map(|paa| paa.get_subject_key_id() == Some(subject_key_id))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// The identity of a device, as attested by its DAC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AttestedDevice {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+/// Validates Device Attestation certificate chains against a [`PaaStore`]
+pub struct DacVerifier<'a> {
+    paa_store: &'a dyn PaaStore,
+}
+
+impl<'a> DacVerifier<'a> {
+    pub const fn new(paa_store: &'a dyn PaaStore) -> Self {
+        Self { paa_store }
+    }
+
+    /// Validates the chain of a DAC and its PAI (both in DER form) up to a trusted PAA,
+    /// and returns the Vendor ID and the Product ID of the device.
+    ///
+    /// Besides the signatures, this checks the profile of each certificate and the scoping
+    /// rules of the Vendor and Product IDs along the chain. As the current time might not be
+    /// known, the validity periods are checked at the time the DAC was issued.
+    pub fn verify(&self, dac: &[u8], pai: &[u8]) -> Result<AttestedDevice, Error> {
+        let dac = X509Cert::new(dac)?;
+        let pai = X509Cert::new(pai)?;
+
+        let paa = pai
+            .get_authority_key_id()
+            .and_then(|akid| self.paa_store.get_paa(akid))
+            .ok_or_else(|| {
+                error!("The PAI was not issued by a trusted PAA");
+                ErrorCode::NotFound
+            })?;
+        let paa = X509Cert::new(paa)?;
+
+        // The PAA
+        Self::check_ca(&paa, "PAA")?;
+        if paa.get_product_id()?.is_some() {
+            error!("The PAA is scoped to a Product ID");
+            Err(ErrorCode::Invalid)?;
+        }
+        paa.verify_issued_by(&paa)?;
+
+        // The PAI
+        Self::check_ca(&pai, "PAI")?;
+        if pai.get_path_len() != Some(0) {
+            error!("The PAI can issue CA certificates");
+            Err(ErrorCode::Invalid)?;
+        }
+        let vendor_id = pai.get_vendor_id()?.ok_or_else(|| {
+            error!("The PAI is not scoped to a Vendor ID");
+            ErrorCode::Invalid
+        })?;
+        if paa
+            .get_vendor_id()?
+            .map(|id| id != vendor_id)
+            .unwrap_or(false)
+        {
+            error!("The Vendor ID of the PAI does not match the one of the PAA");
+            Err(ErrorCode::Invalid)?;
+        }
+        pai.verify_issued_by(&paa)?;
+
+        // The DAC
+        let key_usage = dac.get_key_usage().unwrap_or(0);
+        if dac.is_ca()
+            || key_usage & KEY_USAGE_DIGITAL_SIGNATURE == 0
+            || key_usage & (KEY_USAGE_KEY_CERT_SIGN | KEY_USAGE_CRL_SIGN) != 0
+            || dac.get_subject_key_id().is_none()
+            || dac.get_authority_key_id().is_none()
+        {
+            error!("The DAC does not have the profile of a DAC");
+            Err(ErrorCode::Invalid)?;
+        }
+        if dac.get_vendor_id()? != Some(vendor_id) {
+            error!("The Vendor ID of the DAC does not match the one of the PAI");
+            Err(ErrorCode::Invalid)?;
+        }
+        let product_id = dac.get_product_id()?.ok_or_else(|| {
+            error!("The DAC is not scoped to a Product ID");
+            ErrorCode::Invalid
+        })?;
+        if pai
+            .get_product_id()?
+            .map(|id| id != product_id)
+            .unwrap_or(false)
+        {
+            error!("The Product ID of the DAC does not match the one of the PAI");
+            Err(ErrorCode::Invalid)?;
+        }
+        dac.verify_issued_by(&pai)?;
+
+        // The whole chain has to be valid at the time the DAC was issued
+        let issued = dac.get_not_before();
+        if ![&dac, &pai, &paa]
+            .iter()
+            .all(|cert| cert.is_valid_at(issued))
+        {
+            error!("The DAC was issued outside of the validity period of its chain");
+            Err(ErrorCode::InvalidTime)?;
+        }
+
+        Ok(AttestedDevice {
+            vendor_id,
+            product_id,
+        })
+    }
+
+    fn check_ca(cert: &X509Cert, name: &str) -> Result<(), Error> {
+        let key_usage = cert.get_key_usage().unwrap_or(0);
+        let ca_key_usage = KEY_USAGE_KEY_CERT_SIGN | KEY_USAGE_CRL_SIGN;
+
+        if !cert.is_ca()
+            || key_usage & ca_key_usage != ca_key_usage
+            || key_usage & KEY_USAGE_DIGITAL_SIGNATURE != 0
+            || cert.get_subject_key_id().is_none()
+        {
+            error!("The {} does not have the profile of a CA certificate", name);
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cert::x509::X509Cert;
+    use crate::error::ErrorCode;
+
+    use super::{AttestedDevice, DacVerifier, PaaStore};
+
+    #[test]
+    fn test_dac_chain() {
+        let store: &[&[u8]] = &[&test_vectors::PAA];
+        let verifier = DacVerifier::new(&store);
+
+        assert_eq!(
+            verifier
+                .verify(&test_vectors::DAC, &test_vectors::PAI)
+                .unwrap(),
+            AttestedDevice {
+                vendor_id: 0xFFF1,
+                product_id: 0x8000,
+            }
+        );
+
+        // The PAI is not a DAC
+        assert!(verifier
+            .verify(&test_vectors::PAI, &test_vectors::PAI)
+            .is_err());
+
+        // The DAC is scoped to another vendor than its PAI
+        assert_eq!(
+            verifier
+                .verify(&test_vectors::DAC_OTHER_VENDOR, &test_vectors::PAI)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::Invalid)
+        );
+
+        // Tampered DAC
+        let mut dac = test_vectors::DAC;
+        let last = dac.len() - 1;
+        dac[last] ^= 0xff;
+        assert!(verifier.verify(&dac, &test_vectors::PAI).is_err());
+    }
+
+    #[test]
+    fn test_untrusted_paa() {
+        let store: &[&[u8]] = &[];
+        let verifier = DacVerifier::new(&store);
+
+        assert_eq!(
+            verifier
+                .verify(&test_vectors::DAC, &test_vectors::PAI)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_x509_fields() {
+        let paa = X509Cert::new(&test_vectors::PAA).unwrap();
+        assert!(paa.is_ca());
+        assert_eq!(paa.get_vendor_id().unwrap(), Some(0xFFF1));
+        assert_eq!(paa.get_product_id().unwrap(), None);
+        // 2026-10-16 15:53:39 UTC - 2126-09-22 15:53:39 UTC
+        assert_eq!(paa.get_not_before(), 845481219);
+        assert_eq!(paa.get_not_after(), 3999081219);
+
+        let store: &[&[u8]] = &[&test_vectors::PAA];
+        assert!(store.get_paa(paa.get_subject_key_id().unwrap()).is_some());
+    }
+
+    mod test_vectors {
+        pub const PAA: [u8; 448] = [
+            0x30, 0x82, 0x01, 0xbc, 0x30, 0x82, 0x01, 0x62, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02,
+            0x14, 0x53, 0x83, 0x8c, 0x2a, 0x27, 0xf4, 0x20, 0x5d, 0x03, 0x96, 0xac, 0xd1, 0x32,
+            0x31, 0x68, 0xc7, 0x00, 0x9c, 0xd4, 0x99, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48,
+            0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x29, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55,
+            0x04, 0x03, 0x0c, 0x08, 0x54, 0x65, 0x73, 0x74, 0x20, 0x50, 0x41, 0x41, 0x31, 0x14,
+            0x30, 0x12, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x01,
+            0x0c, 0x04, 0x46, 0x46, 0x46, 0x31, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x36, 0x31, 0x30,
+            0x31, 0x36, 0x31, 0x35, 0x35, 0x33, 0x33, 0x39, 0x5a, 0x18, 0x0f, 0x32, 0x31, 0x32,
+            0x36, 0x30, 0x39, 0x32, 0x32, 0x31, 0x35, 0x35, 0x33, 0x33, 0x39, 0x5a, 0x30, 0x29,
+            0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54, 0x65, 0x73,
+            0x74, 0x20, 0x50, 0x41, 0x41, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b, 0x06, 0x01,
+            0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46, 0x31, 0x30,
+            0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08,
+            0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0xd8, 0xfa,
+            0x49, 0x55, 0x80, 0x2a, 0x54, 0xb4, 0xdf, 0x92, 0x19, 0x00, 0x9e, 0x0f, 0xfe, 0x7a,
+            0x30, 0xdb, 0x91, 0xf5, 0xd2, 0xcd, 0xe4, 0x6c, 0x38, 0x98, 0x4c, 0xc9, 0xf8, 0x0f,
+            0x6a, 0xfe, 0x46, 0xdb, 0xa0, 0x4c, 0x0d, 0x7c, 0x22, 0x70, 0xe9, 0x22, 0xa5, 0x52,
+            0x76, 0x8b, 0x72, 0xb8, 0x11, 0xb2, 0x65, 0xd7, 0xc5, 0x42, 0x53, 0x4f, 0x59, 0x21,
+            0x12, 0x63, 0xff, 0x7d, 0xa5, 0xf0, 0xa3, 0x66, 0x30, 0x64, 0x30, 0x12, 0x06, 0x03,
+            0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x08, 0x30, 0x06, 0x01, 0x01, 0xff, 0x02,
+            0x01, 0x01, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04,
+            0x03, 0x02, 0x01, 0x06, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04,
+            0x14, 0x09, 0x1f, 0x3f, 0x6a, 0x8d, 0xe1, 0x7c, 0xf4, 0x14, 0x7c, 0x59, 0x31, 0xf6,
+            0x4e, 0x05, 0xc5, 0x1a, 0x41, 0x51, 0xe7, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23,
+            0x04, 0x18, 0x30, 0x16, 0x80, 0x14, 0x09, 0x1f, 0x3f, 0x6a, 0x8d, 0xe1, 0x7c, 0xf4,
+            0x14, 0x7c, 0x59, 0x31, 0xf6, 0x4e, 0x05, 0xc5, 0x1a, 0x41, 0x51, 0xe7, 0x30, 0x0a,
+            0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30,
+            0x45, 0x02, 0x20, 0x41, 0x59, 0xc0, 0xee, 0x49, 0x39, 0x1e, 0x30, 0x5c, 0x56, 0x52,
+            0xfe, 0x92, 0x83, 0x2d, 0xbe, 0xc3, 0x25, 0xc9, 0xb1, 0x96, 0x65, 0xa8, 0xa6, 0xb2,
+            0xfd, 0x4e, 0x6d, 0x61, 0x21, 0x74, 0xd3, 0x02, 0x21, 0x00, 0xdf, 0x87, 0xe9, 0xd6,
+            0x6b, 0x13, 0x65, 0xaa, 0x33, 0x3e, 0xd9, 0x2e, 0x41, 0x86, 0x5b, 0x2f, 0x27, 0xa2,
+            0xc0, 0x45, 0xbc, 0x47, 0x1c, 0xb5, 0xdc, 0x76, 0x18, 0x70, 0x50, 0x02, 0x08, 0x0a,
+        ];
+
+        pub const PAI: [u8; 428] = [
+            0x30, 0x82, 0x01, 0xa8, 0x30, 0x82, 0x01, 0x4f, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02,
+            0x01, 0x02, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+            0x30, 0x29, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54,
+            0x65, 0x73, 0x74, 0x20, 0x50, 0x41, 0x41, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b,
+            0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46,
+            0x31, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x36, 0x31, 0x35, 0x35,
+            0x33, 0x33, 0x39, 0x5a, 0x18, 0x0f, 0x32, 0x31, 0x32, 0x35, 0x30, 0x35, 0x31, 0x30,
+            0x31, 0x35, 0x35, 0x33, 0x33, 0x39, 0x5a, 0x30, 0x29, 0x31, 0x11, 0x30, 0x0f, 0x06,
+            0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54, 0x65, 0x73, 0x74, 0x20, 0x50, 0x41, 0x49,
+            0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c,
+            0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46, 0x31, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07,
+            0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+            0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x56, 0xd7, 0x13, 0x9f, 0x50, 0x37, 0xf0,
+            0xa8, 0x2a, 0xf0, 0x98, 0xaa, 0xde, 0x25, 0xbf, 0x30, 0xc0, 0xd8, 0x2e, 0xd8, 0x96,
+            0x44, 0x1f, 0xd5, 0xdc, 0x99, 0xcd, 0xe8, 0xbe, 0x49, 0x52, 0x78, 0x9f, 0xcf, 0x24,
+            0xc7, 0xce, 0xe0, 0x45, 0x85, 0x60, 0x25, 0xe5, 0x46, 0x9c, 0xd8, 0x7a, 0x2b, 0xea,
+            0xfe, 0x5a, 0x63, 0xab, 0x6c, 0x8d, 0x29, 0xc8, 0x8e, 0x09, 0x59, 0xa9, 0x20, 0xac,
+            0xbd, 0xa3, 0x66, 0x30, 0x64, 0x30, 0x12, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01,
+            0xff, 0x04, 0x08, 0x30, 0x06, 0x01, 0x01, 0xff, 0x02, 0x01, 0x00, 0x30, 0x0e, 0x06,
+            0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01, 0x06, 0x30,
+            0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0xf2, 0x42, 0x79, 0xb8,
+            0xd2, 0x2e, 0xda, 0xe7, 0xee, 0x6a, 0x3d, 0x86, 0x1f, 0x62, 0xa1, 0xac, 0xbd, 0xc1,
+            0x95, 0x05, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30, 0x16, 0x80,
+            0x14, 0x09, 0x1f, 0x3f, 0x6a, 0x8d, 0xe1, 0x7c, 0xf4, 0x14, 0x7c, 0x59, 0x31, 0xf6,
+            0x4e, 0x05, 0xc5, 0x1a, 0x41, 0x51, 0xe7, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48,
+            0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x47, 0x00, 0x30, 0x44, 0x02, 0x20, 0x38, 0x82,
+            0x4d, 0xe0, 0xb6, 0x2b, 0xea, 0x72, 0x87, 0xdf, 0x7c, 0xbe, 0x1d, 0xa8, 0x7d, 0xb3,
+            0xd0, 0x7b, 0x1e, 0xa6, 0x04, 0x97, 0xb9, 0x16, 0x25, 0xa4, 0x1d, 0x50, 0x57, 0xfe,
+            0x0e, 0xeb, 0x02, 0x20, 0x71, 0xa4, 0xdb, 0x7e, 0x85, 0xf2, 0x1c, 0x6d, 0x4f, 0x8d,
+            0x72, 0xa2, 0x4c, 0x2a, 0xcf, 0x52, 0x0b, 0xe8, 0x53, 0x66, 0x9e, 0x65, 0x80, 0x30,
+            0xce, 0x1d, 0x25, 0x4a, 0xa8, 0x4e, 0x64, 0x73,
+        ];
+
+        pub const DAC: [u8; 445] = [
+            0x30, 0x82, 0x01, 0xb9, 0x30, 0x82, 0x01, 0x5f, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02,
+            0x01, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+            0x30, 0x29, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54,
+            0x65, 0x73, 0x74, 0x20, 0x50, 0x41, 0x49, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b,
+            0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46,
+            0x31, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x36, 0x31, 0x35, 0x35,
+            0x33, 0x33, 0x39, 0x5a, 0x18, 0x0f, 0x32, 0x31, 0x32, 0x32, 0x30, 0x38, 0x31, 0x34,
+            0x31, 0x35, 0x35, 0x33, 0x33, 0x39, 0x5a, 0x30, 0x3f, 0x31, 0x11, 0x30, 0x0f, 0x06,
+            0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54, 0x65, 0x73, 0x74, 0x20, 0x44, 0x41, 0x43,
+            0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c,
+            0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46, 0x31, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a,
+            0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x02, 0x0c, 0x04, 0x38, 0x30,
+            0x30, 0x30, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02,
+            0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+            0x04, 0xb7, 0x4f, 0xe0, 0x4f, 0x8e, 0xef, 0x1a, 0xeb, 0x4c, 0x3d, 0xf4, 0xca, 0xe3,
+            0x07, 0xe7, 0x5b, 0xe0, 0xb2, 0xca, 0x59, 0x9f, 0xf4, 0x1c, 0x41, 0x7c, 0x77, 0xb5,
+            0x07, 0xc9, 0x6c, 0xe1, 0x1c, 0x48, 0xa7, 0x0d, 0xc4, 0x00, 0xab, 0xd9, 0xc1, 0xdd,
+            0x4a, 0xdd, 0x3f, 0xec, 0xdb, 0x48, 0x18, 0x6c, 0xb0, 0x10, 0x7a, 0x7b, 0x41, 0x3c,
+            0xbc, 0x31, 0x3c, 0xf1, 0x27, 0xea, 0x70, 0x1a, 0x52, 0xa3, 0x60, 0x30, 0x5e, 0x30,
+            0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30,
+            0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07,
+            0x80, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0x66, 0x71,
+            0x0a, 0x82, 0x12, 0xcf, 0xd2, 0x6f, 0x0f, 0xd6, 0xf7, 0xd3, 0x39, 0xcd, 0x71, 0x45,
+            0xd1, 0xbc, 0x3c, 0x1d, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30,
+            0x16, 0x80, 0x14, 0xf2, 0x42, 0x79, 0xb8, 0xd2, 0x2e, 0xda, 0xe7, 0xee, 0x6a, 0x3d,
+            0x86, 0x1f, 0x62, 0xa1, 0xac, 0xbd, 0xc1, 0x95, 0x05, 0x30, 0x0a, 0x06, 0x08, 0x2a,
+            0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x21,
+            0x00, 0xa3, 0x64, 0x42, 0xf3, 0x12, 0xf3, 0x76, 0x9b, 0x90, 0x2a, 0x44, 0x6f, 0x3e,
+            0xd9, 0x35, 0x46, 0xa0, 0xce, 0xec, 0x60, 0xc5, 0x93, 0x8d, 0x52, 0xf1, 0xa7, 0x4f,
+            0x33, 0xdf, 0xd0, 0xf5, 0x93, 0x02, 0x20, 0x18, 0x51, 0xaa, 0x02, 0x06, 0xb2, 0x26,
+            0x18, 0xf6, 0xa8, 0xcc, 0x49, 0x47, 0xeb, 0x6f, 0xd4, 0x6b, 0x4e, 0x49, 0xa2, 0xbd,
+            0x6a, 0x01, 0xdb, 0x30, 0x1b, 0xb0, 0x0c, 0xd8, 0x12, 0xfc, 0x4b,
+        ];
+
+        pub const DAC_OTHER_VENDOR: [u8; 444] = [
+            0x30, 0x82, 0x01, 0xb8, 0x30, 0x82, 0x01, 0x5f, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02,
+            0x01, 0x04, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+            0x30, 0x29, 0x31, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54,
+            0x65, 0x73, 0x74, 0x20, 0x50, 0x41, 0x49, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b,
+            0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46,
+            0x31, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x36, 0x31, 0x35, 0x35,
+            0x33, 0x33, 0x39, 0x5a, 0x18, 0x0f, 0x32, 0x31, 0x32, 0x32, 0x30, 0x38, 0x31, 0x34,
+            0x31, 0x35, 0x35, 0x33, 0x33, 0x39, 0x5a, 0x30, 0x3f, 0x31, 0x11, 0x30, 0x0f, 0x06,
+            0x03, 0x55, 0x04, 0x03, 0x0c, 0x08, 0x54, 0x65, 0x73, 0x74, 0x20, 0x44, 0x41, 0x43,
+            0x31, 0x14, 0x30, 0x12, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c,
+            0x02, 0x01, 0x0c, 0x04, 0x46, 0x46, 0x46, 0x32, 0x31, 0x14, 0x30, 0x12, 0x06, 0x0a,
+            0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xa2, 0x7c, 0x02, 0x02, 0x0c, 0x04, 0x38, 0x30,
+            0x30, 0x30, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02,
+            0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+            0x04, 0x5f, 0x88, 0x51, 0xd5, 0xd0, 0x15, 0x4d, 0x3d, 0xc7, 0x06, 0xfc, 0x73, 0xf9,
+            0x02, 0xa8, 0x88, 0xda, 0x79, 0x26, 0x94, 0x90, 0xe2, 0x6b, 0x9d, 0x7c, 0x16, 0xf4,
+            0xb5, 0x85, 0x8d, 0x2a, 0xf1, 0x49, 0xde, 0x24, 0xc0, 0x1e, 0x0a, 0xab, 0xb0, 0x18,
+            0xf8, 0x3a, 0xff, 0xe0, 0xf2, 0x5d, 0x55, 0x5f, 0x81, 0x79, 0x93, 0x84, 0xd2, 0x32,
+            0xd8, 0xc3, 0x8f, 0x5d, 0x23, 0x0d, 0xc5, 0xd5, 0x77, 0xa3, 0x60, 0x30, 0x5e, 0x30,
+            0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30,
+            0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07,
+            0x80, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0xc4, 0x02,
+            0xd0, 0x4d, 0xa5, 0x01, 0x5b, 0xf3, 0x7b, 0xb5, 0x59, 0xf3, 0x9c, 0x8e, 0x0f, 0xa3,
+            0x75, 0x42, 0x2e, 0x54, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30,
+            0x16, 0x80, 0x14, 0xf2, 0x42, 0x79, 0xb8, 0xd2, 0x2e, 0xda, 0xe7, 0xee, 0x6a, 0x3d,
+            0x86, 0x1f, 0x62, 0xa1, 0xac, 0xbd, 0xc1, 0x95, 0x05, 0x30, 0x0a, 0x06, 0x08, 0x2a,
+            0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x47, 0x00, 0x30, 0x44, 0x02, 0x20,
+            0x2c, 0x48, 0x02, 0x2b, 0x38, 0x55, 0xc5, 0x38, 0xaa, 0x2d, 0x3f, 0xb4, 0x16, 0x96,
+            0x50, 0xc3, 0x36, 0xa4, 0xec, 0xdb, 0x51, 0x62, 0x18, 0x82, 0x27, 0x40, 0x95, 0xb4,
+            0x1e, 0x73, 0x29, 0x4d, 0x02, 0x20, 0x52, 0x44, 0xab, 0x85, 0x53, 0x48, 0x64, 0xd2,
+            0x9b, 0xc6, 0xec, 0x01, 0x56, 0xb7, 0x37, 0xd5, 0x9b, 0xe0, 0x46, 0xca, 0xe1, 0x8e,
+            0x2d, 0x88, 0x1c, 0x86, 0x34, 0x21, 0x1f, 0xfb, 0x4c, 0xb4,
+        ];
+    }
+}
@@ -407,17 +407,21 @@ impl<'a> DistNames<'a> {
             })
     }
 
-    fn u32_arr(&self, match_id: DnTags, output: &mut [u32]) {
+    fn u32_arr(&self, match_id: DnTags, output: &mut [u32]) -> Result<usize, Error> {
         let mut out_index = 0;
         for (_, val) in self.dn.iter().filter(|(id, _)| *id == match_id as u8) {
             if let DistNameValue::Uint(a) = val {
-                if out_index < output.len() {
-                    // CatIds are actually just 32-bit
-                    output[out_index] = *a as u32;
-                    out_index += 1;
+                if out_index >= output.len() {
+                    Err(ErrorCode::NoSpace)?;
                 }
+
+                // CatIds are actually just 32-bit
+                output[out_index] = u32::try_from(*a).map_err(|_| ErrorCode::Invalid)?;
+                out_index += 1;
             }
         }
+
+        Ok(out_index)
     }
 }
 
@@ -654,8 +658,24 @@ impl<'a> Cert<'a> {
             .ok_or_else(|| Error::from(ErrorCode::NoNodeId))
     }
 
-    pub fn get_cat_ids(&self, output: &mut [u32]) {
-        self.subject.u32_arr(DnTags::NocCat, output)
+    /// Reads the CASE Authenticated Tags of the subject into `output`.
+    /// Fails if there are more of them than `output` can take, if any of them has a
+    /// (reserved) version of 0, or if the same tag is present with two different versions.
+    pub fn get_cat_ids(&self, output: &mut [u32]) -> Result<(), Error> {
+        let count = self.subject.u32_arr(DnTags::NocCat, output)?;
+        let cat_ids = &output[..count];
+
+        for (index, cat_id) in cat_ids.iter().enumerate() {
+            if cat_id & 0xffff == 0
+                || cat_ids[..index]
+                    .iter()
+                    .any(|other| other >> 16 == cat_id >> 16)
+            {
+                Err(ErrorCode::Invalid)?;
+            }
+        }
+
+        Ok(())
     }
 
     pub fn get_fabric_id(&self) -> Result<u64, Error> {
@@ -668,6 +688,17 @@ impl<'a> Cert<'a> {
         self.pubkey.0
     }
 
+    /// The start of the validity period, in seconds since the Matter epoch
+    pub fn get_not_before(&self) -> u32 {
+        self.not_before
+    }
+
+    /// The end of the validity period, in seconds since the Matter epoch;
+    /// 0 means that the certificate has no well-defined expiration date
+    pub fn get_not_after(&self) -> u32 {
+        self.not_after
+    }
+
     pub fn get_subject_key_id(&self) -> Result<&[u8], Error> {
         self.extensions
             .0
@@ -849,7 +880,9 @@ const MAX_DEPTH: usize = 10;
 const MAX_ASN1_CERT_SIZE: usize = 1000;
 
 mod asn1_writer;
+pub mod attestation;
 mod printer;
+pub mod x509;
 
 #[cfg(test)]
 mod tests {
@@ -934,6 +967,24 @@ mod tests {
         v.finalise().unwrap();
     }
 
+    #[test]
+    fn test_last_known_good_time() {
+        use crate::last_known_good_time::LastKnownGoodTime;
+
+        let noc = Cert::new(&test_vectors::NOC1_SUCCESS).unwrap();
+        let icac = Cert::new(&test_vectors::ICAC1_SUCCESS).unwrap();
+        let never_expires = Cert::new(&test_vectors::NOC_NOT_AFTER_ZERO).unwrap();
+
+        let mut lkgt = LastKnownGoodTime::new();
+        lkgt.update_from_certs(&[&noc, &icac]);
+        assert_eq!(lkgt.get(), noc.get_not_before().max(icac.get_not_before()));
+        lkgt.validate(&[&noc, &icac, &never_expires]).unwrap();
+
+        lkgt.update(noc.get_not_after() + 1);
+        assert!(lkgt.validate(&[&noc]).is_err());
+        lkgt.validate(&[&never_expires]).unwrap();
+    }
+
     #[test]
     fn test_cert_corrupted() {
         use crate::error::ErrorCode;
@@ -0,0 +1,502 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! A minimal reader of X.509 certificates in DER form.
+//!
+//! Unlike the operational certificates, the Device Attestation certificates (DAC, PAI and PAA)
+//! are not in the Matter TLV form. Only what the Matter spec allows for them is supported:
+//! ECDSA with SHA256 signatures over P-256 keys, and the Basic Constraints, Key Usage, Subject
+//! and Authority Key Identifier extensions.
+
+use log::error;
+
+use crate::{
+    crypto::{KeyPair, BIGNUM_LEN_BYTES, EC_POINT_LEN_BYTES, EC_SIGNATURE_LEN_BYTES},
+    error::{Error, ErrorCode},
+    utils::epoch::MATTER_EPOCH_SECS,
+};
+
+use super::{OID_ECDSA_WITH_SHA256, OID_EC_TYPE_PRIME256V1, OID_PUB_KEY_ECPUBKEY};
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+const TAG_KEY_IDENTIFIER: u8 = 0x80;
+
+const OID_COMMON_NAME: [u8; 3] = [0x55, 0x04, 0x03];
+const OID_MATTER_VID: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x02, 0x01];
+const OID_MATTER_PID: [u8; 10] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0xA2, 0x7C, 0x02, 0x02];
+
+const OID_BASIC_CONSTRAINTS: [u8; 3] = [0x55, 0x1D, 0x13];
+const OID_KEY_USAGE: [u8; 3] = [0x55, 0x1D, 0x0F];
+const OID_SUBJECT_KEY_ID: [u8; 3] = [0x55, 0x1D, 0x0E];
+const OID_AUTHORITY_KEY_ID: [u8; 3] = [0x55, 0x1D, 0x23];
+
+/// The bits of the Key Usage extension, as per RFC 5280
+pub const KEY_USAGE_DIGITAL_SIGNATURE: u16 = 0x0001;
+pub const KEY_USAGE_KEY_CERT_SIGN: u16 = 0x0020;
+pub const KEY_USAGE_CRL_SIGN: u16 = 0x0040;
+
+/// A cursor over a sequence of DER elements
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    /// Returns the tag, the content and the whole encoding of the next element
+    fn next(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), Error> {
+        let data = self.0;
+
+        let tag = *data.first().ok_or(ErrorCode::Invalid)?;
+        let first = *data.get(1).ok_or(ErrorCode::Invalid)? as usize;
+
+        let (len, offset) = if first < 0x80 {
+            (first, 2)
+        } else {
+            let count = first & 0x7f;
+            if count == 0 || count > 2 {
+                Err(ErrorCode::Invalid)?;
+            }
+
+            let bytes = data.get(2..2 + count).ok_or(ErrorCode::Invalid)?;
+            let len = bytes
+                .iter()
+                .fold(0, |len, byte| (len << 8) | *byte as usize);
+
+            (len, 2 + count)
+        };
+
+        let raw = data.get(..offset + len).ok_or(ErrorCode::Invalid)?;
+        self.0 = &data[offset + len..];
+
+        Ok((tag, &raw[offset..], raw))
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        let (actual, content, _) = self.next()?;
+        if actual != tag {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(content)
+    }
+
+    fn expect_raw(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        let (actual, _, raw) = self.next()?;
+        if actual != tag {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(raw)
+    }
+
+    fn next_if(&mut self, tag: u8) -> Result<Option<&'a [u8]>, Error> {
+        if self.peek_tag() == Some(tag) {
+            self.expect(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// An X.509 certificate, in DER form
+pub struct X509Cert<'a> {
+    tbs: &'a [u8],
+    signature: &'a [u8],
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    not_before: u64,
+    not_after: u64,
+    pubkey: &'a [u8],
+    is_ca: bool,
+    path_len: Option<u8>,
+    key_usage: Option<u16>,
+    subject_key_id: Option<&'a [u8]>,
+    authority_key_id: Option<&'a [u8]>,
+}
+
+impl<'a> X509Cert<'a> {
+    pub fn new(der: &'a [u8]) -> Result<Self, Error> {
+        let mut outer = Der(der);
+        let mut cert = Der(outer.expect(TAG_SEQUENCE)?);
+        if !outer.is_empty() {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let tbs = cert.expect_raw(TAG_SEQUENCE)?;
+        Self::check_sign_algo(cert.expect(TAG_SEQUENCE)?)?;
+        let signature = Self::bit_string(cert.expect(TAG_BIT_STRING)?)?;
+
+        let mut fields = Der(Der(tbs).expect(TAG_SEQUENCE)?);
+
+        // Only v3 certificates have extensions
+        let version = fields.expect(TAG_VERSION)?;
+        if version != [TAG_INTEGER, 0x01, 0x02] {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        fields.expect(TAG_INTEGER)?;
+        Self::check_sign_algo(fields.expect(TAG_SEQUENCE)?)?;
+
+        let issuer = fields.expect_raw(TAG_SEQUENCE)?;
+
+        let mut validity = Der(fields.expect(TAG_SEQUENCE)?);
+        let not_before = Self::time(&mut validity)?;
+        let not_after = Self::time(&mut validity)?;
+
+        let subject = fields.expect_raw(TAG_SEQUENCE)?;
+
+        let mut spki = Der(fields.expect(TAG_SEQUENCE)?);
+        let mut algo = Der(spki.expect(TAG_SEQUENCE)?);
+        if algo.expect(TAG_OID)? != OID_PUB_KEY_ECPUBKEY
+            || algo.expect(TAG_OID)? != OID_EC_TYPE_PRIME256V1
+        {
+            Err(ErrorCode::Invalid)?;
+        }
+        let pubkey = Self::bit_string(spki.expect(TAG_BIT_STRING)?)?;
+        if pubkey.len() != EC_POINT_LEN_BYTES {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let mut this = Self {
+            tbs,
+            signature,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            pubkey,
+            is_ca: false,
+            path_len: None,
+            key_usage: None,
+            subject_key_id: None,
+            authority_key_id: None,
+        };
+
+        // The optional unique identifiers are skipped
+        while !fields.is_empty() {
+            let (tag, content, _) = fields.next()?;
+            if tag == TAG_EXTENSIONS {
+                this.parse_extensions(Der(content).expect(TAG_SEQUENCE)?)?;
+            }
+        }
+
+        Ok(this)
+    }
+
+    /// The part of the certificate covered by its signature
+    pub fn get_tbs(&self) -> &[u8] {
+        self.tbs
+    }
+
+    /// The DER encoding of the issuer name
+    pub fn get_issuer(&self) -> &[u8] {
+        self.issuer
+    }
+
+    /// The DER encoding of the subject name
+    pub fn get_subject(&self) -> &[u8] {
+        self.subject
+    }
+
+    /// The start of the validity period, in seconds since the Matter epoch
+    pub fn get_not_before(&self) -> u64 {
+        self.not_before
+    }
+
+    /// The end of the validity period, in seconds since the Matter epoch
+    pub fn get_not_after(&self) -> u64 {
+        self.not_after
+    }
+
+    pub fn get_pubkey(&self) -> &[u8] {
+        self.pubkey
+    }
+
+    pub fn is_ca(&self) -> bool {
+        self.is_ca
+    }
+
+    pub fn get_path_len(&self) -> Option<u8> {
+        self.path_len
+    }
+
+    pub fn get_key_usage(&self) -> Option<u16> {
+        self.key_usage
+    }
+
+    pub fn get_subject_key_id(&self) -> Option<&[u8]> {
+        self.subject_key_id
+    }
+
+    pub fn get_authority_key_id(&self) -> Option<&[u8]> {
+        self.authority_key_id
+    }
+
+    /// The Vendor ID of the subject, if any
+    pub fn get_vendor_id(&self) -> Result<Option<u16>, Error> {
+        self.get_matter_id(&OID_MATTER_VID, b"Mvid:")
+    }
+
+    /// The Product ID of the subject, if any
+    pub fn get_product_id(&self) -> Result<Option<u16>, Error> {
+        self.get_matter_id(&OID_MATTER_PID, b"Mpid:")
+    }
+
+    /// Checks that the certificate was issued by `parent`, and that its signature is valid
+    pub fn verify_issued_by(&self, parent: &X509Cert) -> Result<(), Error> {
+        if self.issuer != parent.subject {
+            Err(ErrorCode::InvalidAuthKey)?;
+        }
+
+        if let (Some(akid), Some(skid)) = (self.authority_key_id, parent.subject_key_id) {
+            if akid != skid {
+                Err(ErrorCode::InvalidAuthKey)?;
+            }
+        }
+
+        let mut signature = [0; EC_SIGNATURE_LEN_BYTES];
+        Self::raw_signature(self.signature, &mut signature)?;
+
+        KeyPair::new_from_public(parent.pubkey)?
+            .verify_msg(self.tbs, &signature)
+            .inspect_err(|_| {
+                error!(
+                    "Error in signature verification of certificate: {:x?} by {:x?}",
+                    self.subject_key_id, parent.subject_key_id
+                );
+            })
+    }
+
+    /// Checks that `time` (in seconds since the Matter epoch) falls within the validity period
+    pub fn is_valid_at(&self, time: u64) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    fn parse_extensions(&mut self, extensions: &'a [u8]) -> Result<(), Error> {
+        let mut extensions = Der(extensions);
+
+        while !extensions.is_empty() {
+            let mut extension = Der(extensions.expect(TAG_SEQUENCE)?);
+
+            let oid = extension.expect(TAG_OID)?;
+            let critical = extension
+                .next_if(TAG_BOOLEAN)?
+                .map(|value| value != [0])
+                .unwrap_or(false);
+            let value = extension.expect(TAG_OCTET_STRING)?;
+
+            if oid == OID_BASIC_CONSTRAINTS {
+                let mut constraints = Der(Der(value).expect(TAG_SEQUENCE)?);
+                self.is_ca = constraints
+                    .next_if(TAG_BOOLEAN)?
+                    .map(|value| value != [0])
+                    .unwrap_or(false);
+                self.path_len = constraints
+                    .next_if(TAG_INTEGER)?
+                    .map(|value| match value {
+                        [len] => Ok(*len),
+                        _ => Err(ErrorCode::Invalid),
+                    })
+                    .transpose()?;
+            } else if oid == OID_KEY_USAGE {
+                let bits = Self::bit_string_any(Der(value).expect(TAG_BIT_STRING)?)?;
+
+                // Bit 0 is the most significant bit of the first byte
+                let mut key_usage = 0;
+                for bit in 0..16 {
+                    if bits.get(bit / 8).copied().unwrap_or(0) & (0x80 >> (bit % 8)) != 0 {
+                        key_usage |= 1 << bit;
+                    }
+                }
+
+                self.key_usage = Some(key_usage);
+            } else if oid == OID_SUBJECT_KEY_ID {
+                self.subject_key_id = Some(Der(value).expect(TAG_OCTET_STRING)?);
+            } else if oid == OID_AUTHORITY_KEY_ID {
+                let mut akid = Der(Der(value).expect(TAG_SEQUENCE)?);
+                self.authority_key_id = akid.next_if(TAG_KEY_IDENTIFIER)?;
+            } else if critical {
+                error!("Unsupported critical extension: {:x?}", oid);
+                Err(ErrorCode::Invalid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_matter_id(&self, oid: &[u8], legacy_prefix: &[u8]) -> Result<Option<u16>, Error> {
+        let mut legacy = None;
+
+        let mut name = Der(Der(self.subject).expect(TAG_SEQUENCE)?);
+        while !name.is_empty() {
+            let mut rdn = Der(name.expect(TAG_SET)?);
+            while !rdn.is_empty() {
+                let mut attr = Der(rdn.expect(TAG_SEQUENCE)?);
+                let attr_oid = attr.expect(TAG_OID)?;
+                let (tag, value, _) = attr.next()?;
+
+                if attr_oid == oid {
+                    if tag != TAG_UTF8_STRING {
+                        Err(ErrorCode::Invalid)?;
+                    }
+
+                    return Self::hex_u16(value).map(Some);
+                } else if attr_oid == OID_COMMON_NAME
+                    && (tag == TAG_UTF8_STRING || tag == TAG_PRINTABLE_STRING)
+                {
+                    // Older certificates might carry the IDs in the Common Name,
+                    // e.g. "ACME Matter Devel DAC 5CDA9899 Mvid:FFF1 Mpid:00B1"
+                    legacy = value
+                        .windows(legacy_prefix.len() + 4)
+                        .find(|window| window.starts_with(legacy_prefix))
+                        .map(|window| Self::hex_u16(&window[legacy_prefix.len()..]))
+                        .transpose()?;
+                }
+            }
+        }
+
+        Ok(legacy)
+    }
+
+    fn hex_u16(value: &[u8]) -> Result<u16, Error> {
+        if value.len() != 4 {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        value.iter().try_fold(0, |id, digit| {
+            let digit = match digit {
+                b'0'..=b'9' => digit - b'0',
+                b'A'..=b'F' => digit - b'A' + 10,
+                _ => Err(ErrorCode::Invalid)?,
+            };
+
+            Ok((id << 4) | digit as u16)
+        })
+    }
+
+    fn check_sign_algo(algo: &[u8]) -> Result<(), Error> {
+        if Der(algo).expect(TAG_OID)? != OID_ECDSA_WITH_SHA256 {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(())
+    }
+
+    /// The content of a BIT STRING without any unused bits
+    fn bit_string(value: &[u8]) -> Result<&[u8], Error> {
+        match value.split_first() {
+            Some((0, bits)) => Ok(bits),
+            _ => Err(ErrorCode::Invalid)?,
+        }
+    }
+
+    fn bit_string_any(value: &[u8]) -> Result<&[u8], Error> {
+        value
+            .split_first()
+            .map(|(_, bits)| bits)
+            .ok_or_else(|| ErrorCode::Invalid.into())
+    }
+
+    /// Converts an ECDSA-Sig-Value to the raw `r || s` form
+    fn raw_signature(der: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        let mut sig = Der(Der(der).expect(TAG_SEQUENCE)?);
+
+        for part in out.chunks_mut(BIGNUM_LEN_BYTES) {
+            let mut int = sig.expect(TAG_INTEGER)?;
+            while int.len() > BIGNUM_LEN_BYTES && int[0] == 0 {
+                int = &int[1..];
+            }
+
+            if int.len() > BIGNUM_LEN_BYTES {
+                Err(ErrorCode::InvalidSignature)?;
+            }
+
+            let offset = BIGNUM_LEN_BYTES - int.len();
+            part[..offset].fill(0);
+            part[offset..].copy_from_slice(int);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a UTCTime or a GeneralizedTime as seconds since the Matter epoch
+    fn time(der: &mut Der) -> Result<u64, Error> {
+        let (tag, value, _) = der.next()?;
+
+        let (year, rest) = match (tag, value.len()) {
+            (TAG_UTC_TIME, 13) => {
+                let year = Self::digits(&value[..2])?;
+                ((if year >= 50 { 1900 } else { 2000 }) + year, &value[2..])
+            }
+            (TAG_GENERALIZED_TIME, 15) => (Self::digits(&value[..4])?, &value[4..]),
+            _ => Err(ErrorCode::Invalid)?,
+        };
+
+        if rest[10] != b'Z' {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let month = Self::digits(&rest[..2])?;
+        let day = Self::digits(&rest[2..4])?;
+        let hour = Self::digits(&rest[4..6])?;
+        let minute = Self::digits(&rest[6..8])?;
+        let second = Self::digits(&rest[8..10])?;
+
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        // Days since the UNIX epoch, as per http://howardhinnant.github.io/date_algorithms.html
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year / 400;
+        let yoe = year - era * 400;
+        let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+
+        let secs = days * 86400 + hour * 3600 + minute * 60 + second - MATTER_EPOCH_SECS as i64;
+
+        Ok(secs.max(0) as u64)
+    }
+
+    fn digits(value: &[u8]) -> Result<i64, Error> {
+        value.iter().try_fold(0, |acc, digit| {
+            if digit.is_ascii_digit() {
+                Ok(acc * 10 + (digit - b'0') as i64)
+            } else {
+                Err(ErrorCode::Invalid.into())
+            }
+        })
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Compile-time capacities of the Matter stack.
+//!
+//! All fixed-size (`heapless`) containers which bound the resources used by the stack
+//! take their capacity from here. Each capacity can be overridden at build time by setting
+//! the environment variable of the same name, prefixed with `RS_MATTER_`. For example:
+//!
+//! ```text
+//! RS_MATTER_MAX_SESSIONS=4 RS_MATTER_MAX_EXCHANGES=2 cargo build
+//! ```
+//!
+//! The defaults satisfy the Matter minimum requirements for a single device.
+//!
+//! The capacities which differ the most between devices are chosen by the user of the stack
+//! instead, so that e.g. a thermostat on a small MCU can run with 2 exchanges while a bridge runs
+//! with 16, out of the same build:
+//! - the number of sessions, exchanges and fabrics are the const generics of the
+//!   [`crate::MatterStorage`] of [`crate::Matter::new_with_capacity`]; [`MAX_SESSIONS`],
+//!   [`MAX_EXCHANGES`] and [`MAX_FABRICS`] are only those of [`crate::Matter::new`];
+//! - the number of exchange handlers - and thus of the packet buffers they need - is the size of
+//!   the [`crate::transport::core::ExchangeBuffers`] passed to [`crate::Matter::run`].
+//!
+//! The per-fabric tables (ACL entries, bindings, group keys, ICD clients, ...) are still sized
+//! for [`MAX_FABRICS`] fabrics.
+
+/// Maximum number of concurrent sessions (PASE, CASE and unencrypted) of [`crate::Matter::new`]
+pub const MAX_SESSIONS: usize = parse_usize(option_env!("RS_MATTER_MAX_SESSIONS"), 16);
+
+/// Maximum number of concurrent exchanges of [`crate::Matter::new`]
+pub const MAX_EXCHANGES: usize = parse_usize(option_env!("RS_MATTER_MAX_EXCHANGES"), 8);
+
+/// Maximum number of fabrics the device can be commissioned into with [`crate::Matter::new`],
+/// and the number of fabrics the per-fabric tables are sized for
+pub const MAX_FABRICS: usize = parse_usize(option_env!("RS_MATTER_MAX_FABRICS"), 3);
+
+/// Maximum number of ACL entries per fabric
+pub const MAX_ACL_ENTRIES_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_ACL_ENTRIES_PER_FABRIC"), 3);
+
+/// Maximum number of services the built-in mDNS responder can advertise at the same time
+pub const MAX_MDNS_SERVICES: usize = parse_usize(option_env!("RS_MATTER_MAX_MDNS_SERVICES"), 4);
+
+/// Maximum number of nodes commissioned by this node, when operating in the controller role
+pub const MAX_PAIRED_NODES: usize = parse_usize(option_env!("RS_MATTER_MAX_PAIRED_NODES"), 4);
+
+/// Maximum number of operational group keys, across all fabrics
+pub const MAX_GROUP_KEYS: usize = parse_usize(option_env!("RS_MATTER_MAX_GROUP_KEYS"), 4);
+
+/// Maximum number of group key sets per fabric, including the one of the IPK
+pub const MAX_GROUP_KEY_SETS_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_GROUP_KEY_SETS_PER_FABRIC"), 3);
+
+/// Maximum number of groups per fabric
+pub const MAX_GROUPS_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_GROUPS_PER_FABRIC"), 4);
+
+/// Maximum number of group memberships of the endpoints - i.e. of (group, endpoint) pairs -
+/// per fabric
+pub const MAX_GROUP_MEMBERSHIPS_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_GROUP_MEMBERSHIPS_PER_FABRIC"), 8);
+
+/// Maximum number of group peers whose highest message counter is persisted,
+/// so that replays of their group messages are not accepted after a reboot
+pub const MAX_GROUP_PEERS: usize = parse_usize(option_env!("RS_MATTER_MAX_GROUP_PEERS"), 8);
+
+/// Number of group messages sent - or received from a peer - between two persisted updates
+/// of the corresponding message counter
+pub const MSG_COUNTER_WINDOW: u32 =
+    parse_usize(option_env!("RS_MATTER_MSG_COUNTER_WINDOW"), 1000) as u32;
+
+/// Maximum number of CASE sessions remembered, so that their peers can resume them
+/// without going through the full CASE handshake again
+pub const MAX_CASE_RESUMPTIONS: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_CASE_RESUMPTIONS"), 4);
+
+/// Maximum number of ICD clients registered per fabric, i.e. of the recipients of the
+/// Check-In messages of this node when it operates as an Intermittently Connected Device
+pub const MAX_ICD_CLIENTS_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_ICD_CLIENTS_PER_FABRIC"), 2);
+
+/// Maximum number of bindings per fabric, i.e. of the nodes and groups the endpoints of this
+/// node act upon as clients. The Matter specification requires at least 3 per fabric
+pub const MAX_BINDINGS_PER_FABRIC: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_BINDINGS_PER_FABRIC"), 3);
+
+/// Maximum number of subscriptions, across all fabrics. The Matter specification requires
+/// at least 3 per fabric
+pub const MAX_SUBSCRIPTIONS: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_SUBSCRIPTIONS"), 3 * MAX_FABRICS);
+
+/// Maximum size of a SubscribeRequest, which is kept for as long as the subscription is active,
+/// so that the reports can be generated from it
+pub const MAX_SUBSCRIPTION_REQ_SIZE: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_SUBSCRIPTION_REQ_SIZE"), 512);
+
+/// Number of cluster data versions tracked per subscription, so that the clusters which did not
+/// change since they were last reported are left out of its reports
+pub const MAX_SUBSCRIPTION_DATAVERS: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_SUBSCRIPTION_DATAVERS"), 16);
+
+/// Number of events of Debug priority kept for reporting; the oldest ones are dropped first
+pub const MAX_EVENTS_DEBUG: usize = parse_usize(option_env!("RS_MATTER_MAX_EVENTS_DEBUG"), 4);
+
+/// Number of events of Info priority kept for reporting; the oldest ones are dropped first
+pub const MAX_EVENTS_INFO: usize = parse_usize(option_env!("RS_MATTER_MAX_EVENTS_INFO"), 8);
+
+/// Number of events of Critical priority kept for reporting; the oldest ones are dropped first
+pub const MAX_EVENTS_CRITICAL: usize = parse_usize(option_env!("RS_MATTER_MAX_EVENTS_CRITICAL"), 4);
+
+/// Maximum size of the TLV-encoded payload of an event
+pub const MAX_EVENT_DATA_SIZE: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_EVENT_DATA_SIZE"), 64);
+
+/// Number of events emitted between two persisted updates of the upper bound of the event numbers
+pub const EVENT_NUMBER_WINDOW: u64 =
+    parse_usize(option_env!("RS_MATTER_EVENT_NUMBER_WINDOW"), 1000) as u64;
+
+/// Number of non-volatile attributes whose written values are persisted
+pub const MAX_NV_ATTRIBUTES: usize = parse_usize(option_env!("RS_MATTER_MAX_NV_ATTRIBUTES"), 8);
+
+/// Maximum size of the TLV-encoded value of a non-volatile attribute
+pub const MAX_NV_ATTRIBUTE_SIZE: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_NV_ATTRIBUTE_SIZE"), 48);
+
+/// How many command paths this node accepts in a single InvokeRequest. All responses to the
+/// commands of a request need to fit in a single InvokeResponse
+pub const MAX_PATHS_PER_INVOKE: usize =
+    parse_usize(option_env!("RS_MATTER_MAX_PATHS_PER_INVOKE"), 4);
+
+// Fabric indices are encoded as a non-zero `u8`
+const _: () = assert!(MAX_FABRICS > 0 && MAX_FABRICS < u8::MAX as usize);
+const _: () = assert!(MAX_SESSIONS > 0);
+const _: () = assert!(MAX_EXCHANGES > 0);
+// One key set for the IPK, and at least one for the groups
+const _: () = assert!(MAX_GROUP_KEY_SETS_PER_FABRIC > 1);
+const _: () = assert!(MAX_GROUP_PEERS > 0);
+const _: () = assert!(MAX_EVENTS_DEBUG > 0 && MAX_EVENTS_INFO > 0 && MAX_EVENTS_CRITICAL > 0);
+const _: () = assert!(MSG_COUNTER_WINDOW > 1);
+const _: () = assert!(EVENT_NUMBER_WINDOW > 1);
+const _: () = assert!(MAX_CASE_RESUMPTIONS > 0);
+const _: () = assert!(MAX_BINDINGS_PER_FABRIC > 0);
+
+/// Parses a decimal `usize` at compile time, falling back to `default` if `value` is `None`.
+/// An invalid value results in a compile-time error.
+const fn parse_usize(value: Option<&str>, default: usize) -> usize {
+    let Some(value) = value else {
+        return default;
+    };
+
+    let bytes = value.as_bytes();
+    assert!(!bytes.is_empty(), "Empty capacity value");
+
+    let mut result: usize = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let digit = bytes[index];
+        assert!(digit.is_ascii_digit(), "Capacity value is not a number");
+
+        result = result * 10 + (digit - b'0') as usize;
+        index += 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_usize;
+
+    #[test]
+    fn test_parse_usize() {
+        assert_eq!(parse_usize(None, 16), 16);
+        assert_eq!(parse_usize(Some("4"), 16), 4);
+        assert_eq!(parse_usize(Some("128"), 16), 128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parse_usize_invalid() {
+        parse_usize(Some("4k"), 16);
+    }
+}
@@ -15,32 +15,74 @@
  *    limitations under the License.
  */
 
-use core::{borrow::Borrow, cell::RefCell};
+use core::{
+    borrow::Borrow,
+    cell::{Cell, RefCell},
+    ops::Deref,
+    time::Duration,
+};
 
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
 
+use log::{info, warn};
+
 use crate::{
     acl::AclMgr,
+    binding::{Binding, BindingMgr, MAX_BINDINGS},
+    crypto::keystore::{OpKeyId, OpKeyStore},
     data_model::{
         cluster_basic_information::BasicInfoConfig,
-        sdm::{dev_att::DevAttDataFetcher, failsafe::FailSafe},
+        objects::{
+            AsyncMetadata, AttrData, AttrDetails, AttrId, ClusterId, DataModelHandler, EndptId,
+            MetadataGuard,
+        },
+        sdm::{
+            dev_att::DacProvider,
+            failsafe::{FailSafe, PendingChanges},
+            general_diagnostics::{TestEventTriggerHandler, TestEventTriggers},
+        },
+        system_model::descriptor,
     },
     error::*,
-    fabric::FabricMgr,
+    fabric::{FabricMgr, FabricSlot, MAX_SUPPORTED_FABRICS},
+    group_keys::{group_multicast_addr, GroupKey, GroupKeyMgr, GroupMembership, KeySet},
+    icd::{IcdClient, IcdClientMgr},
+    interaction_model::{
+        client::ReportHandler,
+        events::{EventId, EventNumber, EventPriority, Events},
+        messages::GenericPath,
+        subscriptions::Subscriptions,
+    },
+    last_known_good_time::LastKnownGoodTime,
     mdns::{Mdns, MdnsImpl, MdnsService},
+    nv_attributes::NvAttributes,
+    paired_nodes::PairedNodeMgr,
     pairing::{print_pairing_code_and_qr, DiscoveryCapabilities},
-    secure_channel::{pake::PaseMgr, spake2p::VerifierData},
+    secure_channel::{case::CaseResumptionStore, pake::PaseMgr, spake2p::VerifierData},
+    tlv::ToTLV,
     transport::{
+        core::{PacketObserver, TransportStats},
         exchange::{ExchangeCtx, MAX_EXCHANGES},
+        msg_ctr::MsgCounterMgr,
+        network::Ipv6Addr,
         packet::{MAX_RX_BUF_SIZE, MAX_TX_BUF_SIZE},
-        session::SessionMgr,
+        session::{EvictionPolicy, Session, SessionMgr, MAX_SESSIONS},
+    },
+    utils::{
+        buf::BufferAccessImpl,
+        epoch::{Epoch, MATTER_EPOCH_SECS},
+        rand::Rand,
+        select::Notification,
     },
-    utils::{buf::BufferAccessImpl, epoch::Epoch, rand::Rand, select::Notification},
 };
 
 /* The Matter Port */
 pub const MATTER_PORT: u16 = 5540;
 
+/// The number of data version bumps tracked for the clusters changed outside of an
+/// interaction (see [`Matter::attribute_changed`])
+const DATAVER_BUMPS: usize = 16;
+
 /// Device Commissioning Data
 pub struct CommissioningData {
     /// The data like password or verifier that is required to authenticate
@@ -49,34 +91,136 @@ pub struct CommissioningData {
     pub discriminator: u16,
 }
 
+/// The storage of the sessions, exchanges and fabrics of [`Matter`], with room for `S` sessions,
+/// `E` exchanges and `F` fabrics.
+///
+/// These are the capacities which differ the most between devices - e.g. a thermostat on a small
+/// MCU might do with 2 exchanges, while a bridge might need 16 - so they are chosen per
+/// [`Matter`] instance with [`Matter::new_with_capacity`], rather than at build time.
+pub struct MatterStorage<const S: usize, const E: usize, const F: usize> {
+    exchanges: RefCell<[Option<ExchangeCtx>; E]>,
+    session_mgr: RefCell<SessionMgr<[Option<Session>; S]>>,
+    fabric_mgr: RefCell<FabricMgr<[FabricSlot; F]>>,
+}
+
+impl<const S: usize, const E: usize, const F: usize> MatterStorage<S, E, F> {
+    // Only used as the array repeat operand, as `ExchangeCtx` is not `Copy`
+    const EMPTY: Option<ExchangeCtx> = None;
+
+    // Same constraints as those on the build-time capacities in `config`
+    const CAPACITY_CHECK: () = assert!(S > 0 && E > 0 && F > 0 && F < u8::MAX as usize);
+
+    #[inline(always)]
+    const fn new(epoch: Epoch, rand: Rand) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_CHECK;
+
+        Self {
+            exchanges: RefCell::new([Self::EMPTY; E]),
+            session_mgr: RefCell::new(SessionMgr::new(epoch, rand)),
+            fabric_mgr: RefCell::new(FabricMgr::new()),
+        }
+    }
+}
+
+/// The storage with the default - build-time - capacities of the [`crate::config`] module
+pub type DefaultMatterStorage = MatterStorage<MAX_SESSIONS, MAX_EXCHANGES, MAX_SUPPORTED_FABRICS>;
+
+/// A [`MatterStorage`] of any capacity, which [`Matter`] is type-erased over, so that the
+/// capacities do not show up in the type of the `&Matter` references passed around the stack.
+pub trait Storage: storage::Sealed {}
+
+impl<const S: usize, const E: usize, const F: usize> Storage for MatterStorage<S, E, F> {}
+
+mod storage {
+    use core::cell::RefCell;
+
+    use crate::{
+        fabric::FabricMgr,
+        transport::{exchange::ExchangeCtx, session::SessionMgr},
+    };
+
+    use super::MatterStorage;
+
+    // The exchanges are internal to the crate, yet not leaked, as the trait is sealed
+    #[allow(private_interfaces)]
+    pub trait Sealed {
+        fn exchanges(&self) -> &RefCell<[Option<ExchangeCtx>]>;
+        fn session_mgr(&self) -> &RefCell<SessionMgr>;
+        fn fabric_mgr(&self) -> &RefCell<FabricMgr>;
+    }
+
+    #[allow(private_interfaces)]
+    impl<const S: usize, const E: usize, const F: usize> Sealed for MatterStorage<S, E, F> {
+        fn exchanges(&self) -> &RefCell<[Option<ExchangeCtx>]> {
+            &self.exchanges
+        }
+
+        fn session_mgr(&self) -> &RefCell<SessionMgr> {
+            &self.session_mgr
+        }
+
+        fn fabric_mgr(&self) -> &RefCell<FabricMgr> {
+            &self.fabric_mgr
+        }
+    }
+}
+
 /// The primary Matter Object
-pub struct Matter<'a> {
-    pub(crate) fabric_mgr: RefCell<FabricMgr>,
+///
+/// `S` is the storage of its sessions, exchanges and fabrics: a [`MatterStorage`] when the object
+/// is created, which the object dereferences to the type-erased `Matter<'a, dyn Storage>` from, i.e.
+/// the `Matter<'a>` all methods are implemented on.
+pub struct Matter<'a, S: ?Sized = dyn Storage> {
     pub acl_mgr: RefCell<AclMgr>, // Public for tests
     pub(crate) pase_mgr: RefCell<PaseMgr>,
     pub(crate) failsafe: RefCell<FailSafe>,
+    pub(crate) paired_nodes: RefCell<PairedNodeMgr>,
+    pub(crate) icd_clients: RefCell<IcdClientMgr>,
+    pub(crate) bindings: RefCell<BindingMgr>,
+    pub(crate) group_key_mgr: RefCell<GroupKeyMgr>,
+    pub(crate) msg_ctrs: RefCell<MsgCounterMgr>,
+    pub(crate) case_resumptions: RefCell<CaseResumptionStore>,
+    pub(crate) last_known_good_time: RefCell<LastKnownGoodTime>,
+    pub(crate) subscriptions: RefCell<Subscriptions>,
+    pub(crate) subscriptions_notification: Notification,
+    pub(crate) events: RefCell<Events>,
+    pub(crate) nv_attributes: RefCell<NvAttributes>,
+    dataver_bumps: RefCell<[u32; DATAVER_BUMPS]>,
     persist_notification: Notification,
     pub(crate) send_notification: Notification,
+    pub(crate) construction_notification: Notification,
+    pub(crate) netif_notification: Notification,
+    pub(crate) shutdown_notification: Notification,
+    pub(crate) shutdown_complete_notification: Notification,
+    pub(crate) shutting_down: Cell<bool>,
+    pub(crate) idle_mode_duration: Cell<Option<u32>>,
+    pub(crate) packet_observer: Cell<Option<&'static dyn PacketObserver>>,
+    pub(crate) eviction_policy: Cell<Option<&'static dyn EvictionPolicy>>,
+    pub(crate) report_handler: Cell<Option<&'static dyn ReportHandler>>,
+    pub(crate) test_event_triggers: TestEventTriggers,
+    pub(crate) report_tag_compression: Cell<bool>,
+    pub(crate) stats: Cell<TransportStats>,
     pub(crate) mdns: MdnsImpl<'a>,
     pub(crate) tx_buf: BufferAccessImpl<MAX_RX_BUF_SIZE>,
     pub(crate) rx_buf: BufferAccessImpl<MAX_TX_BUF_SIZE>,
     pub(crate) epoch: Epoch,
     pub(crate) rand: Rand,
     dev_det: &'a BasicInfoConfig<'a>,
-    dev_att: &'a dyn DevAttDataFetcher,
+    dev_att: &'a dyn DacProvider,
+    op_keystore: Cell<Option<&'static dyn OpKeyStore>>,
     pub(crate) port: u16,
-    pub(crate) exchanges: RefCell<heapless::Vec<ExchangeCtx, MAX_EXCHANGES>>,
     pub(crate) ephemeral: RefCell<Option<ExchangeCtx>>,
     pub(crate) ephemeral_mutex: Mutex<NoopRawMutex, ()>,
-    pub session_mgr: RefCell<SessionMgr>, // Public for tests
+    storage: S,
 }
 
-impl<'a> Matter<'a> {
+impl<'a> Matter<'a, DefaultMatterStorage> {
     #[cfg(feature = "std")]
     #[inline(always)]
     pub const fn new_default(
         dev_det: &'a BasicInfoConfig<'a>,
-        dev_att: &'a dyn DevAttDataFetcher,
+        dev_att: &'a dyn DacProvider,
         mdns: MdnsService<'a>,
         port: u16,
     ) -> Self {
@@ -86,28 +230,71 @@ impl<'a> Matter<'a> {
         Self::new(dev_det, dev_att, mdns, sys_epoch, sys_rand, port)
     }
 
-    /// Creates a new Matter object
+    /// Creates a new Matter object, with the default capacities (see [`DefaultMatterStorage`])
     ///
     /// # Parameters
-    /// * dev_att: An object that implements the trait [DevAttDataFetcher]. Any Matter device
-    /// requires a set of device attestation certificates and keys. It is the responsibility of
-    /// this object to return the device attestation details when queried upon.
+    /// * dev_att: An object that implements the trait [DacProvider] (as does any
+    ///   [DevAttDataFetcher](crate::data_model::sdm::dev_att::DevAttDataFetcher)). Any Matter
+    ///   device requires a set of device attestation certificates and keys. It is the
+    ///   responsibility of this object to return the device attestation details, and to sign
+    ///   with the attestation key when queried upon.
     #[inline(always)]
     pub const fn new(
         dev_det: &'a BasicInfoConfig<'a>,
-        dev_att: &'a dyn DevAttDataFetcher,
+        dev_att: &'a dyn DacProvider,
+        mdns: MdnsService<'a>,
+        epoch: Epoch,
+        rand: Rand,
+        port: u16,
+    ) -> Self {
+        Self::new_with_capacity(dev_det, dev_att, mdns, epoch, rand, port)
+    }
+}
+
+impl<'a, const S: usize, const E: usize, const F: usize> Matter<'a, MatterStorage<S, E, F>> {
+    /// Creates a new Matter object with room for `S` sessions, `E` exchanges and `F` fabrics,
+    /// e.g. `Matter::<MatterStorage<4, 2, 1>>::new_with_capacity(..)` for a small device.
+    ///
+    /// See [`Matter::new`] for the parameters.
+    #[inline(always)]
+    pub const fn new_with_capacity(
+        dev_det: &'a BasicInfoConfig<'a>,
+        dev_att: &'a dyn DacProvider,
         mdns: MdnsService<'a>,
         epoch: Epoch,
         rand: Rand,
         port: u16,
     ) -> Self {
         Self {
-            fabric_mgr: RefCell::new(FabricMgr::new()),
             acl_mgr: RefCell::new(AclMgr::new()),
             pase_mgr: RefCell::new(PaseMgr::new(epoch, rand)),
-            failsafe: RefCell::new(FailSafe::new()),
+            failsafe: RefCell::new(FailSafe::new(epoch)),
+            paired_nodes: RefCell::new(PairedNodeMgr::new()),
+            icd_clients: RefCell::new(IcdClientMgr::new()),
+            bindings: RefCell::new(BindingMgr::new()),
+            group_key_mgr: RefCell::new(GroupKeyMgr::new()),
+            msg_ctrs: RefCell::new(MsgCounterMgr::new()),
+            case_resumptions: RefCell::new(CaseResumptionStore::new()),
+            last_known_good_time: RefCell::new(LastKnownGoodTime::new()),
+            subscriptions: RefCell::new(Subscriptions::new()),
+            subscriptions_notification: Notification::new(),
+            events: RefCell::new(Events::new()),
+            nv_attributes: RefCell::new(NvAttributes::new()),
+            dataver_bumps: RefCell::new([0; DATAVER_BUMPS]),
             persist_notification: Notification::new(),
             send_notification: Notification::new(),
+            construction_notification: Notification::new(),
+            netif_notification: Notification::new(),
+            shutdown_notification: Notification::new(),
+            shutdown_complete_notification: Notification::new(),
+            shutting_down: Cell::new(false),
+            idle_mode_duration: Cell::new(None),
+            packet_observer: Cell::new(None),
+            eviction_policy: Cell::new(None),
+            report_handler: Cell::new(None),
+            test_event_triggers: TestEventTriggers::new(),
+            report_tag_compression: Cell::new(false),
+            stats: Cell::new(TransportStats::new()),
             mdns: mdns.new_impl(dev_det, port),
             rx_buf: BufferAccessImpl::new(),
             tx_buf: BufferAccessImpl::new(),
@@ -115,44 +302,404 @@ impl<'a> Matter<'a> {
             rand,
             dev_det,
             dev_att,
+            op_keystore: Cell::new(None),
             port,
-            exchanges: RefCell::new(heapless::Vec::new()),
             ephemeral: RefCell::new(None),
             ephemeral_mutex: Mutex::new(()),
-            session_mgr: RefCell::new(SessionMgr::new(epoch, rand)),
+            storage: MatterStorage::new(epoch, rand),
         }
     }
+}
+
+impl<'a, const S: usize, const E: usize, const F: usize> Deref
+    for Matter<'a, MatterStorage<S, E, F>>
+{
+    type Target = Matter<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+impl<'a> Matter<'a> {
+    /// The exchanges - as tracked by the transport - of the object
+    pub(crate) fn exchanges(&self) -> &RefCell<[Option<ExchangeCtx>]> {
+        self.storage.exchanges()
+    }
+
+    pub fn session_mgr(&self) -> &RefCell<SessionMgr> {
+        self.storage.session_mgr()
+    }
+
+    pub(crate) fn fabric_mgr(&self) -> &RefCell<FabricMgr> {
+        self.storage.fabric_mgr()
+    }
 
     pub fn dev_det(&self) -> &BasicInfoConfig<'_> {
         self.dev_det
     }
 
-    pub fn dev_att(&self) -> &dyn DevAttDataFetcher {
+    pub fn dev_att(&self) -> &dyn DacProvider {
         self.dev_att
     }
 
+    /// Sets the keystore in which the operational keys of the fabrics added from now on
+    /// are generated and kept
+    pub fn set_op_keystore(&self, keystore: &'static dyn OpKeyStore) {
+        self.op_keystore.set(Some(keystore));
+    }
+
+    pub fn op_keystore(&self) -> Option<&'static dyn OpKeyStore> {
+        self.op_keystore.get()
+    }
+
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// Registers the idle mode duration (in seconds) of the node, if it is an Intermittently
+    /// Connected Device, so that the max interval of the subscriptions is negotiated to one
+    /// the node can keep while sleeping. `None` (the default) for an always-on node.
+    pub fn set_idle_mode_duration(&self, secs: Option<u32>) {
+        self.idle_mode_duration.set(secs);
+    }
+
+    pub fn idle_mode_duration(&self) -> Option<u32> {
+        self.idle_mode_duration.get()
+    }
+
     pub fn load_fabrics(&self, data: &[u8]) -> Result<(), Error> {
-        self.fabric_mgr.borrow_mut().load(data, &self.mdns)
+        self.fabric_mgr().borrow_mut().load(data, &self.mdns)
     }
 
     pub fn load_acls(&self, data: &[u8]) -> Result<(), Error> {
         self.acl_mgr.borrow_mut().load(data)
     }
 
+    pub fn load_paired_nodes(&self, data: &[u8]) -> Result<(), Error> {
+        self.paired_nodes.borrow_mut().load(data)
+    }
+
+    pub fn load_icd_clients(&self, data: &[u8]) -> Result<(), Error> {
+        self.icd_clients.borrow_mut().load(data)
+    }
+
+    pub fn load_bindings(&self, data: &[u8]) -> Result<(), Error> {
+        self.bindings.borrow_mut().load(data)
+    }
+
+    pub fn load_msg_counters(&self, data: &[u8]) -> Result<(), Error> {
+        self.msg_ctrs.borrow_mut().load(data)
+    }
+
+    pub fn load_last_known_good_time(&self, data: &[u8]) -> Result<(), Error> {
+        self.last_known_good_time.borrow_mut().load(data)
+    }
+
+    pub fn load_group_keys(&self, data: &[u8]) -> Result<(), Error> {
+        self.group_key_mgr.borrow_mut().load(data)
+    }
+
+    pub fn load_events(&self, data: &[u8]) -> Result<(), Error> {
+        self.events.borrow_mut().load(data)
+    }
+
+    /// Loads the values of the non-volatile attributes, which then need to be restored with
+    /// [`Matter::restore_nv_attributes`]
+    pub fn load_nv_attributes(&self, data: &[u8]) -> Result<(), Error> {
+        self.nv_attributes.borrow_mut().load(data)
+    }
+
     pub fn store_fabrics<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
-        self.fabric_mgr.borrow_mut().store(buf)
+        self.fabric_mgr().borrow_mut().store(buf)
     }
 
     pub fn store_acls<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
         self.acl_mgr.borrow_mut().store(buf)
     }
 
+    pub fn store_paired_nodes<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.paired_nodes.borrow_mut().store(buf)
+    }
+
+    /// Stores the message counters of the group messages, which have to survive a reboot
+    /// (see [`crate::transport::msg_ctr`])
+    pub fn store_icd_clients<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.icd_clients.borrow_mut().store(buf)
+    }
+
+    /// Stores the binding table of the endpoints (see [`crate::binding`])
+    pub fn store_bindings<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.bindings.borrow_mut().store(buf)
+    }
+
+    pub fn store_msg_counters<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.msg_ctrs.borrow_mut().store(buf)
+    }
+
+    /// Stores the Last Known Good UTC Time, against which the validity of the certificates
+    /// is checked (see [`crate::last_known_good_time`])
+    pub fn store_last_known_good_time<'b>(
+        &self,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Error> {
+        self.last_known_good_time.borrow_mut().store(buf)
+    }
+
+    /// Stores the group key sets and the Group Key Map of all fabrics
+    /// (see [`crate::group_keys`])
+    pub fn store_group_keys<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.group_key_mgr.borrow_mut().store(buf)
+    }
+
+    /// Stores the upper bound of the event numbers, which must never go back
+    /// (see [`crate::interaction_model::events`])
+    pub fn store_events<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.events.borrow_mut().store(buf)
+    }
+
+    /// Stores the values of the non-volatile attributes (see [`crate::nv_attributes`])
+    pub fn store_nv_attributes<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.nv_attributes.borrow_mut().store(buf)
+    }
+
+    /// Records the value of a non-volatile attribute which changed other than by a write
+    /// over the Interaction Model - e.g. because of a command, or of the device itself - so
+    /// that it is persisted. Writes over the Interaction Model are recorded automatically.
+    pub fn set_nv_attribute(
+        &self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attr_id: AttrId,
+        value: &dyn ToTLV,
+    ) -> Result<(), Error> {
+        self.nv_attributes
+            .borrow_mut()
+            .set(endpoint_id, cluster_id, attr_id, value)?;
+
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    /// Restores the loaded values of the non-volatile attributes (see
+    /// [`Matter::load_nv_attributes`]) by writing them to the handlers of their clusters.
+    /// To be called at startup, before the node starts responding.
+    ///
+    /// The values of the attributes which are no longer on the node, or which their handlers
+    /// reject, are skipped.
+    pub async fn restore_nv_attributes<T>(&self, handler: &T) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        let metadata = AsyncMetadata::lock(handler).await;
+        let node = metadata.node();
+
+        for index in 0.. {
+            // Not borrowed across the writes, which may record attributes as well
+            let Some(attribute) = self.nv_attributes.borrow().iter().nth(index).cloned() else {
+                break;
+            };
+
+            let attr = AttrDetails {
+                node: &node,
+                endpoint_id: attribute.endpoint_id,
+                cluster_id: attribute.cluster_id,
+                attr_id: attribute.attr_id,
+                list_index: None,
+                fab_idx: 0,
+                fab_filter: false,
+                dataver: None,
+                wildcard: false,
+            };
+
+            if attr.attribute().is_none() {
+                warn!(
+                    "Attribute {}/{:#x}/{:#x} is not on the node, not restored",
+                    attr.endpoint_id, attr.cluster_id, attr.attr_id
+                );
+                continue;
+            }
+
+            let value = attribute.value()?;
+
+            if let Err(e) = handler.write(&attr, AttrData::new(None, &value)).await {
+                warn!(
+                    "Attribute {}/{:#x}/{:#x} not restored: {}",
+                    attr.endpoint_id, attr.cluster_id, attr.attr_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reports the timestamps of the events emitted from now on as System time - i.e. the time
+    /// since boot - rather than as Epoch time. For nodes whose Epoch is not the UTC time, e.g.
+    /// because they have no real time clock.
+    pub fn set_event_system_time(&self, system_time: bool) {
+        self.events.borrow_mut().set_system_time(system_time);
+    }
+
+    /// Moves the Last Known Good UTC Time forward to `utc` (the time since the UNIX epoch),
+    /// as obtained from a trusted time source, e.g. Time Synchronization.
+    ///
+    /// Devices are also expected to call this on boot, with the time their firmware was built.
+    pub fn update_last_known_good_time(&self, utc: Duration) {
+        let secs = utc.as_secs().saturating_sub(MATTER_EPOCH_SECS);
+
+        if self
+            .last_known_good_time
+            .borrow_mut()
+            .update(secs.min(u32::MAX as u64) as u32)
+        {
+            self.notify_changed();
+        }
+    }
+
+    /// Adds the epoch key of a group the node is a member of, so that messages sent to the group
+    /// are accepted, and so that messages can be sent to the group with [`Matter::send_group`]
+    pub fn add_group_key(&self, fab_idx: u8, group_id: u16, epoch_key: &[u8]) -> Result<(), Error> {
+        let fabric_mgr = self.fabric_mgr().borrow();
+        let fabric = fabric_mgr
+            .get_fabric(fab_idx as _)?
+            .ok_or(ErrorCode::NotFound)?;
+
+        let key_set = KeySet::new(epoch_key, &fabric.compressed_id()?)?;
+
+        self.group_key_mgr
+            .borrow_mut()
+            .add(GroupKey::new(fab_idx, group_id, key_set.op_key())?)
+    }
+
+    pub fn remove_group_key(&self, fab_idx: u8, group_id: u16) -> Result<(), Error> {
+        self.group_key_mgr.borrow_mut().remove(fab_idx, group_id)
+    }
+
+    /// Makes an endpoint a member of a group, as done with the AddGroup command of the Groups
+    /// cluster, so that the group-addressed writes and invokes apply to it
+    pub fn add_group_endpoint(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        endpoint_id: u16,
+    ) -> Result<(), Error> {
+        self.group_key_mgr
+            .borrow_mut()
+            .add_membership(GroupMembership {
+                fab_idx,
+                group_id,
+                endpoint_id,
+            })?;
+
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    pub fn remove_group_endpoint(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        endpoint_id: u16,
+    ) -> Result<(), Error> {
+        self.group_key_mgr
+            .borrow_mut()
+            .remove_membership(&GroupMembership {
+                fab_idx,
+                group_id,
+                endpoint_id,
+            })?;
+
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    /// The IPv6 multicast address of a group. For receiving the messages sent to the group,
+    /// the UDP socket of the transport should join it on the operational network interface
+    /// Registers an ICD client - or updates its registration - as done with the RegisterClient
+    /// command of the ICD Management cluster; see [`IcdClientMgr::register`].
+    ///
+    /// Returns the ICDCounter, i.e. the counter of the next Check-In message.
+    pub fn register_icd_client(
+        &self,
+        client: IcdClient,
+        verification_key: Option<&[u8]>,
+        is_admin: bool,
+    ) -> Result<u32, Error> {
+        self.icd_clients
+            .borrow_mut()
+            .register(client, verification_key, is_admin)?;
+
+        let counter = self.msg_ctrs.borrow_mut().check_in_ctr(self.rand);
+
+        self.notify_changed();
+
+        Ok(counter)
+    }
+
+    /// Removes the registration of an ICD client, as done with the UnregisterClient command
+    /// of the ICD Management cluster; see [`IcdClientMgr::unregister`]
+    pub fn unregister_icd_client(
+        &self,
+        fab_idx: u8,
+        check_in_node_id: u64,
+        verification_key: Option<&[u8]>,
+        is_admin: bool,
+    ) -> Result<(), Error> {
+        self.icd_clients.borrow_mut().unregister(
+            fab_idx,
+            check_in_node_id,
+            verification_key,
+            is_admin,
+        )?;
+
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    /// The bindings of the local endpoint `endpoint` which apply to its client cluster `cluster`,
+    /// on all fabrics. The bound nodes can be interacted with over an exchange opened with
+    /// [`Matter::initiate_binding`].
+    pub fn resolve_bindings(
+        &self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+    ) -> heapless::Vec<Binding, MAX_BINDINGS> {
+        self.bindings.borrow().resolve(endpoint, cluster).collect()
+    }
+
+    /// The compressed fabric ID of the fabric at `fab_idx`
+    pub fn compressed_fabric_id(&self, fab_idx: u8) -> Result<u64, Error> {
+        self.fabric_mgr()
+            .borrow()
+            .get_fabric(fab_idx as _)?
+            .ok_or(ErrorCode::NotFound)?
+            .compressed_fabric_id()
+    }
+
+    pub fn group_multicast_addr(&self, fab_idx: u8, group_id: u16) -> Result<Ipv6Addr, Error> {
+        let fabric_mgr = self.fabric_mgr().borrow();
+        let fabric = fabric_mgr
+            .get_fabric(fab_idx as _)?
+            .ok_or(ErrorCode::NotFound)?;
+
+        Ok(group_multicast_addr(fabric.get_fabric_id(), group_id))
+    }
+
     pub fn is_changed(&self) -> bool {
-        self.acl_mgr.borrow().is_changed() || self.fabric_mgr.borrow().is_changed()
+        self.acl_mgr.borrow().is_changed()
+            || self.fabric_mgr().borrow().is_changed()
+            || self.paired_nodes.borrow().is_changed()
+            || self.icd_clients.borrow().is_changed()
+            || self.bindings.borrow().is_changed()
+            || self.msg_ctrs.borrow().is_changed()
+            || self.last_known_good_time.borrow().is_changed()
+            || self.group_key_mgr.borrow().is_changed()
+            || self.events.borrow().is_changed()
+            || self.nv_attributes.borrow().is_changed()
     }
 
     pub fn start_comissioning(
@@ -160,7 +707,13 @@ impl<'a> Matter<'a> {
         dev_comm: CommissioningData,
         buf: &mut [u8],
     ) -> Result<bool, Error> {
-        if !self.pase_mgr.borrow().is_pase_session_enabled() && self.fabric_mgr.borrow().is_empty()
+        // Administrators might open Basic commissioning windows later on
+        self.pase_mgr
+            .borrow_mut()
+            .set_basic_comm_data(dev_comm.verifier.clone(), dev_comm.discriminator);
+
+        if !self.pase_mgr.borrow().is_pase_session_enabled()
+            && self.fabric_mgr().borrow().is_empty()
         {
             print_pairing_code_and_qr(
                 self.dev_det,
@@ -181,6 +734,72 @@ impl<'a> Matter<'a> {
         }
     }
 
+    /// Commits the configuration changes made while the fail-safe was armed, once it is
+    /// disarmed with CommissioningComplete
+    pub(crate) fn commit_failsafe(&self, changes: PendingChanges) {
+        // The operational key of the fabric before UpdateNOC is no longer needed
+        if let Some((_, prev)) = changes.updated_fabric {
+            self.remove_op_key(prev.op_key().external_id());
+        }
+    }
+
+    /// Rolls back the configuration changes made while the fail-safe was armed, if it expired:
+    /// - a fabric added with AddNOC is removed, along with its ACLs, sessions and keys;
+    /// - a fabric updated with UpdateNOC is restored as it was before the update;
+    /// - the operational keys and trusted roots of the pending NOC requests are dropped.
+    ///
+    /// Returns `true` if the fail-safe expired.
+    pub fn expire_failsafe(&self) -> Result<bool, Error> {
+        let Some(changes) = self.failsafe.borrow_mut().expire() else {
+            return Ok(false);
+        };
+
+        if let Some(fab_idx) = changes.added_fabric {
+            info!("Fail-Safe expired, removing fabric {}", fab_idx);
+
+            let op_key_id = self
+                .fabric_mgr()
+                .borrow()
+                .get_fabric(fab_idx as _)?
+                .and_then(|fabric| fabric.op_key().external_id());
+
+            self.fabric_mgr().borrow_mut().remove(fab_idx, &self.mdns)?;
+            let _ = self.acl_mgr.borrow_mut().delete_for_fabric(fab_idx);
+            self.remove_fabric(fab_idx, None);
+            self.remove_op_key(op_key_id);
+        }
+
+        if let Some((fab_idx, prev)) = changes.updated_fabric {
+            info!("Fail-Safe expired, restoring fabric {}", fab_idx);
+
+            let updated = self
+                .fabric_mgr()
+                .borrow_mut()
+                .update(fab_idx, prev, &self.mdns)?;
+            self.remove_op_key(updated.op_key().external_id());
+        }
+
+        for noc_data in self.session_mgr().borrow_mut().take_noc_data() {
+            self.remove_op_key(noc_data.op_key.external_id());
+        }
+
+        if changes.network_changed {
+            warn!("Fail-Safe expired, the network configuration has to be reverted");
+        }
+
+        self.notify_changed();
+
+        Ok(true)
+    }
+
+    fn remove_op_key(&self, id: Option<OpKeyId>) {
+        if let Some((keystore, id)) = self.op_keystore().zip(id) {
+            if let Err(e) = keystore.remove(id) {
+                warn!("Failed to remove operational key {}: {}", id, e);
+            }
+        }
+    }
+
     pub fn notify_changed(&self) {
         if self.is_changed() {
             self.persist_notification.signal(());
@@ -190,57 +809,269 @@ impl<'a> Matter<'a> {
     pub async fn wait_changed(&self) {
         self.persist_notification.wait().await
     }
+
+    /// Notifies the subscribers that attribute values changed - e.g. because of a change of
+    /// the device state which did not come over the Interaction Model - so that their
+    /// subscribed paths are reported once the min interval of each subscription elapses.
+    pub fn notify_attributes_changed(&self) {
+        if self.subscriptions.borrow_mut().notify_changed() {
+            self.subscriptions_notification.signal(());
+        }
+    }
+
+    /// Notifies the subscribers of the attributes on the - possibly wildcard - `path` that
+    /// their values changed, as [`Matter::notify_attributes_changed`] does for all attributes.
+    ///
+    /// The changes are coalesced up to the next report of each subscription, and the reporting
+    /// engine is only woken up when a report becomes due earlier, so this can be called on every
+    /// change of a fast-changing value. As the reports leave out the clusters whose data version
+    /// did not change, the data version of the cluster of the attributes needs to be bumped too.
+    pub fn notify_attribute_changed(&self, path: &GenericPath) {
+        if self
+            .subscriptions
+            .borrow_mut()
+            .notify_attribute_changed(path)
+        {
+            self.subscriptions_notification.signal(());
+        }
+    }
+
+    /// Notifies the data model that the attribute `attr` of the cluster `cluster` on the endpoint
+    /// `endpoint` changed outside of an interaction - e.g. the light was toggled with its button.
+    ///
+    /// The data version of the cluster is bumped - on top of the one its handler reports, so
+    /// that this works for the handlers which do not track their changes with their own data
+    /// version too - and the subscriptions on the attribute are reported once their min
+    /// interval elapses. The reads and reports filtered with the previous data version of the
+    /// cluster therefore see the change.
+    pub fn attribute_changed(&self, endpoint: EndptId, cluster: ClusterId, attr: AttrId) {
+        let index = Self::dataver_bump_index(endpoint, cluster);
+        let mut bumps = self.dataver_bumps.borrow_mut();
+        bumps[index] = bumps[index].wrapping_add(1);
+
+        self.notify_attribute_changed(&GenericPath::new(
+            Some(endpoint),
+            Some(cluster),
+            Some(attr as _),
+        ));
+    }
+
+    /// The amount by which the data version of the cluster `cluster` on the endpoint `endpoint`
+    /// was bumped with [`Matter::attribute_changed`], to be added to the data version reported
+    /// by the handler of the cluster.
+    ///
+    /// The bumps are tracked in a few slots shared by all clusters, so a bump of another cluster
+    /// in the same slot only costs a spurious report of this one.
+    pub(crate) fn dataver_bump(&self, endpoint: EndptId, cluster: ClusterId) -> u32 {
+        self.dataver_bumps.borrow()[Self::dataver_bump_index(endpoint, cluster)]
+    }
+
+    fn dataver_bump_index(endpoint: EndptId, cluster: ClusterId) -> usize {
+        (endpoint as usize)
+            .wrapping_mul(31)
+            .wrapping_add(cluster as usize)
+            % DATAVER_BUMPS
+    }
+
+    /// Notifies the subscribers that the endpoint `endpoint_id` was added or removed at runtime
+    /// (see [`crate::data_model::objects::SharedNode`]), so that the attributes of the endpoint
+    /// and the PartsList of the Descriptor clusters are reported. The Descriptor clusters bump
+    /// their data version themselves, once they see the changed endpoints.
+    pub fn notify_endpoint_changed(&self, endpoint_id: EndptId) {
+        self.notify_attribute_changed(&GenericPath::new(Some(endpoint_id), None, None));
+        self.notify_attribute_changed(&GenericPath::new(
+            None,
+            Some(descriptor::ID),
+            Some(descriptor::Attributes::PartsList as _),
+        ));
+    }
+
+    /// Sets the handler of the reports of the subscriptions this node establishes as a client,
+    /// e.g. a [`crate::interaction_model::client::SubscribeClient`]. Without one, the reports
+    /// are rejected, which makes the publishers drop the subscriptions.
+    pub fn set_report_handler(&self, handler: Option<&'static dyn ReportHandler>) {
+        self.report_handler.set(handler);
+    }
+
+    /// Sets the handler of the TestEventTrigger command of the General Diagnostics cluster, which
+    /// the certification tests use to inject simulated conditions. Without one - the default -
+    /// the test event triggers are disabled.
+    pub fn set_test_event_trigger_handler(
+        &self,
+        handler: Option<&'static dyn TestEventTriggerHandler>,
+    ) {
+        self.test_event_triggers.set(handler);
+    }
+
+    /// Enables the tag compression of the attribute paths in the reports of the reads and the
+    /// subscriptions: consecutive attribute data of the same cluster then omit its endpoint and
+    /// cluster, which shrinks the reports of wildcard reads considerably.
+    ///
+    /// Disabled by default, as not every controller expands compressed paths.
+    pub fn set_report_tag_compression(&self, enabled: bool) {
+        self.report_tag_compression.set(enabled);
+    }
+
+    /// Emits an event of the given cluster, with `payload` as its data (typically the fields
+    /// of the event, as a struct), to be reported to the readers and the subscribers of the
+    /// event. Returns the number of the event.
+    pub fn emit_event(
+        &self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        event_id: EventId,
+        priority: EventPriority,
+        payload: &dyn ToTLV,
+    ) -> Result<EventNumber, Error> {
+        let number = {
+            let mut events = self.events.borrow_mut();
+
+            let number = events.emit(
+                endpoint,
+                cluster,
+                event_id,
+                priority,
+                (self.epoch)(),
+                payload,
+            )?;
+
+            let event = events.get(number).ok_or(ErrorCode::NotFound)?;
+
+            if self.subscriptions.borrow_mut().notify_event(event) {
+                self.subscriptions_notification.signal(());
+            }
+
+            number
+        };
+
+        // The upper bound of the event numbers might have moved
+        self.notify_changed();
+
+        Ok(number)
+    }
 }
 
-impl<'a> Borrow<RefCell<FabricMgr>> for Matter<'a> {
+impl<'a, S> Borrow<RefCell<FabricMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &RefCell<FabricMgr> {
-        &self.fabric_mgr
+        self.storage.fabric_mgr()
     }
 }
 
-impl<'a> Borrow<RefCell<AclMgr>> for Matter<'a> {
+impl<'a, S> Borrow<RefCell<AclMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &RefCell<AclMgr> {
         &self.acl_mgr
     }
 }
 
-impl<'a> Borrow<RefCell<PaseMgr>> for Matter<'a> {
+impl<'a, S> Borrow<RefCell<GroupKeyMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &RefCell<GroupKeyMgr> {
+        &self.group_key_mgr
+    }
+}
+
+impl<'a, S> Borrow<RefCell<PaseMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &RefCell<PaseMgr> {
         &self.pase_mgr
     }
 }
 
-impl<'a> Borrow<RefCell<FailSafe>> for Matter<'a> {
+impl<'a, S> Borrow<RefCell<FailSafe>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &RefCell<FailSafe> {
         &self.failsafe
     }
 }
 
-impl<'a> Borrow<BasicInfoConfig<'a>> for Matter<'a> {
+impl<'a, S> Borrow<RefCell<PairedNodeMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &RefCell<PairedNodeMgr> {
+        &self.paired_nodes
+    }
+}
+
+impl<'a, S> Borrow<RefCell<IcdClientMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &RefCell<IcdClientMgr> {
+        &self.icd_clients
+    }
+}
+
+impl<'a, S> Borrow<RefCell<BindingMgr>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &RefCell<BindingMgr> {
+        &self.bindings
+    }
+}
+
+impl<'a, S> Borrow<BasicInfoConfig<'a>> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &BasicInfoConfig<'a> {
         self.dev_det
     }
 }
 
-impl<'a> Borrow<dyn DevAttDataFetcher + 'a> for Matter<'a> {
-    fn borrow(&self) -> &(dyn DevAttDataFetcher + 'a) {
+impl<'a, S> Borrow<dyn DacProvider + 'a> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &(dyn DacProvider + 'a) {
         self.dev_att
     }
 }
 
-impl<'a> Borrow<dyn Mdns + 'a> for Matter<'a> {
+impl<'a, S> Borrow<dyn Mdns + 'a> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &(dyn Mdns + 'a) {
         &self.mdns
     }
 }
 
-impl<'a> Borrow<Epoch> for Matter<'a> {
+impl<'a, S> Borrow<TestEventTriggers> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
+    fn borrow(&self) -> &TestEventTriggers {
+        &self.test_event_triggers
+    }
+}
+
+impl<'a, S> Borrow<Epoch> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &Epoch {
         &self.epoch
     }
 }
 
-impl<'a> Borrow<Rand> for Matter<'a> {
+impl<'a, S> Borrow<Rand> for Matter<'a, S>
+where
+    S: ?Sized + Storage,
+{
     fn borrow(&self) -> &Rand {
         &self.rand
     }
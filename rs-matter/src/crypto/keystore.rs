@@ -0,0 +1,208 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Operational keys which might be kept outside of the RAM of the node.
+
+use crate::{
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
+    utils::rand::Rand,
+};
+
+use super::KeyPair;
+
+/// The ID of a key in an [`OpKeyStore`]
+pub type OpKeyId = u32;
+
+/// A keystore which generates and keeps the operational keys of the node - e.g. a secure element,
+/// a TPM or an OS keychain - so that their private keys never end up in the RAM of the node.
+///
+/// Without a keystore (see [`crate::Matter::set_op_keystore`]), the operational keys are
+/// generated by the crate itself, and persisted together with their fabrics.
+pub trait OpKeyStore {
+    /// Generates a new EC P-256 keypair and writes its PKCS #10 CSR (DER) into `csr`.
+    ///
+    /// Returns the ID of the new key and the length of the CSR.
+    fn generate(&self, csr: &mut [u8]) -> Result<(OpKeyId, usize), Error>;
+
+    /// Signs a message with the key (ECDSA with SHA-256, as the raw `r || s` values).
+    ///
+    /// Returns the length of the signature.
+    fn sign(&self, key: OpKeyId, msg: &[u8], signature: &mut [u8]) -> Result<usize, Error>;
+
+    /// Deletes the key, e.g. once its fabric is removed
+    fn remove(&self, key: OpKeyId) -> Result<(), Error>;
+}
+
+impl<T> OpKeyStore for &T
+where
+    T: OpKeyStore,
+{
+    fn generate(&self, csr: &mut [u8]) -> Result<(OpKeyId, usize), Error> {
+        (**self).generate(csr)
+    }
+
+    fn sign(&self, key: OpKeyId, msg: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
+        (**self).sign(key, msg, signature)
+    }
+
+    fn remove(&self, key: OpKeyId) -> Result<(), Error> {
+        (**self).remove(key)
+    }
+}
+
+/// The operational key of a fabric
+#[derive(Debug)]
+pub enum OpKey {
+    /// Generated by the crate itself and persisted with the fabric
+    Local(KeyPair),
+    /// Kept in the [`OpKeyStore`]; only its ID is persisted with the fabric
+    External(OpKeyId),
+}
+
+impl OpKey {
+    /// Generates a new operational key - in the keystore, if there is one - and writes its
+    /// CSR into `csr`
+    pub fn generate<'a>(
+        keystore: Option<&dyn OpKeyStore>,
+        rand: Rand,
+        csr: &'a mut [u8],
+    ) -> Result<(Self, &'a [u8]), Error> {
+        if let Some(keystore) = keystore {
+            let (id, len) = keystore.generate(csr)?;
+
+            Ok((Self::External(id), &csr[..len]))
+        } else {
+            let key_pair = KeyPair::new(rand)?;
+            let len = key_pair.get_csr(csr)?.len();
+
+            Ok((Self::Local(key_pair), &csr[..len]))
+        }
+    }
+
+    pub fn sign_msg(
+        &self,
+        keystore: Option<&dyn OpKeyStore>,
+        msg: &[u8],
+        signature: &mut [u8],
+    ) -> Result<usize, Error> {
+        match self {
+            Self::Local(key_pair) => key_pair.sign_msg(msg, signature),
+            Self::External(id) => keystore
+                .ok_or(ErrorCode::InvalidState)?
+                .sign(*id, msg, signature),
+        }
+    }
+
+    /// The ID of the key in the keystore, if it is kept there
+    pub fn external_id(&self) -> Option<OpKeyId> {
+        match self {
+            Self::Local(_) => None,
+            Self::External(id) => Some(*id),
+        }
+    }
+}
+
+impl<'a> FromTLV<'a> for OpKey {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        // Local keys are stored just like before keystores were supported
+        if t.confirm_array().is_ok() {
+            Ok(Self::Local(KeyPair::from_tlv(t)?))
+        } else {
+            Ok(Self::External(t.u32()?))
+        }
+    }
+}
+
+impl ToTLV for OpKey {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        match self {
+            Self::Local(key_pair) => key_pair.to_tlv(tw, tag),
+            Self::External(id) => tw.u32(tag, *id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::{
+        error::Error,
+        tlv::{get_root_node, FromTLV, TLVWriter, TagType, ToTLV},
+        utils::{rand::dummy_rand, writebuf::WriteBuf},
+    };
+
+    use super::{OpKey, OpKeyId, OpKeyStore};
+
+    struct TestKeyStore {
+        next_id: Cell<OpKeyId>,
+    }
+
+    impl OpKeyStore for TestKeyStore {
+        fn generate(&self, csr: &mut [u8]) -> Result<(OpKeyId, usize), Error> {
+            let id = self.next_id.get();
+            self.next_id.set(id + 1);
+
+            csr[..3].copy_from_slice(&[1, 2, 3]);
+
+            Ok((id, 3))
+        }
+
+        fn sign(&self, key: OpKeyId, _msg: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
+            signature[0] = key as u8;
+
+            Ok(1)
+        }
+
+        fn remove(&self, _key: OpKeyId) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_external_key() {
+        let keystore = TestKeyStore {
+            next_id: Cell::new(7),
+        };
+
+        let mut csr = [0; 16];
+        let (key, csr) = OpKey::generate(Some(&keystore), dummy_rand, &mut csr).unwrap();
+        assert_eq!(csr, &[1, 2, 3]);
+        assert_eq!(key.external_id(), Some(7));
+
+        let mut signature = [0; 64];
+        assert_eq!(
+            key.sign_msg(Some(&keystore), &[], &mut signature).unwrap(),
+            1
+        );
+        assert_eq!(signature[0], 7);
+
+        // No signing without the keystore
+        assert!(key.sign_msg(None, &[], &mut signature).is_err());
+
+        // Only the ID is persisted
+        let mut buf = [0; 16];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        key.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let len = tw.get_tail();
+
+        let loaded = OpKey::from_tlv(&get_root_node(&buf[..len]).unwrap()).unwrap();
+        assert_eq!(loaded.external_id(), Some(7));
+    }
+}
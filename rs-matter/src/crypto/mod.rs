@@ -37,6 +37,8 @@ pub const ECDH_SHARED_SECRET_LEN_BYTES: usize = 32;
 
 pub const EC_SIGNATURE_LEN_BYTES: usize = 64;
 
+pub mod keystore;
+
 #[cfg(all(feature = "mbedtls", target_os = "espidf"))]
 mod crypto_esp_mbedtls;
 #[cfg(all(feature = "mbedtls", target_os = "espidf"))]
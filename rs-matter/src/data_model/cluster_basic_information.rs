@@ -21,6 +21,7 @@ use super::objects::*;
 use crate::{
     attribute_enum,
     error::{Error, ErrorCode},
+    transport::session::DATA_MODEL_REVISION,
     utils::rand::Rand,
 };
 use heapless::String;
@@ -81,6 +82,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::DMRevision as u16,
             Access::RV,
@@ -110,7 +113,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             AttributesDiscriminants::NodeLabel as u16,
             Access::RWVM,
             Quality::N,
-        ),
+        )
+        .with_constraint(Constraint::Length(0, 32)),
         Attribute::new(
             AttributesDiscriminants::HwVer as u16,
             Access::RV,
@@ -133,6 +137,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[],
+    generated_commands: &[],
+    events: &[],
 };
 
 pub struct BasicInfoCluster<'a> {
@@ -157,7 +163,7 @@ impl<'a> BasicInfoCluster<'a> {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::DMRevision(codec) => codec.encode(writer, 1),
+                    Attributes::DMRevision(codec) => codec.encode(writer, DATA_MODEL_REVISION as _),
                     Attributes::VendorName(codec) => codec.encode(writer, self.cfg.vendor_name),
                     Attributes::VendorId(codec) => codec.encode(writer, self.cfg.vid),
                     Attributes::ProductName(codec) => codec.encode(writer, self.cfg.product_name),
@@ -185,7 +191,7 @@ impl<'a> BasicInfoCluster<'a> {
                     .decode(data)
                     .map_err(|_| Error::new(ErrorCode::InvalidAction))?
                     .try_into()
-                    .unwrap();
+                    .map_err(|_| Error::new(ErrorCode::ConstraintError))?;
             }
             _ => return Err(Error::new(ErrorCode::InvalidAction)),
         }
@@ -0,0 +1,178 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Binding cluster, through which the administrators manage the bindings of the endpoint
+//! it is on, i.e. the nodes and groups the client clusters of the endpoint act upon.
+//!
+//! The bindings of all endpoints are kept - and persisted - in the binding table of the node
+//! (see [`crate::binding`]).
+
+use core::cell::RefCell;
+
+use log::info;
+use strum::{EnumDiscriminants, FromRepr};
+
+use crate::{
+    attribute_enum,
+    binding::{BindingMgr, Target},
+    data_model::objects::*,
+    error::{Error, ErrorCode},
+    interaction_model::messages::ib::{attr_list_write, ListOperation},
+    tlv::{FromTLV, TLVElement, TagType, ToTLV},
+    utils::rand::Rand,
+};
+
+pub const ID: u32 = 0x001E;
+
+#[derive(FromRepr, EnumDiscriminants)]
+#[repr(u16)]
+pub enum Attributes {
+    Binding(()) = 0,
+}
+
+attribute_enum!(Attributes);
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(
+            AttributesDiscriminants::Binding as u16,
+            Access::RWFVM,
+            Quality::N,
+        ),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[],
+};
+
+pub struct BindingCluster<'a> {
+    data_ver: Dataver,
+    binding_mgr: &'a RefCell<BindingMgr>,
+}
+
+impl<'a> BindingCluster<'a> {
+    pub fn new(binding_mgr: &'a RefCell<BindingMgr>, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            binding_mgr,
+        }
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::Binding(_) => {
+                        writer.start_array(AttrDataWriter::TAG)?;
+
+                        for target in self.binding_mgr.borrow().targets(attr.endpoint_id) {
+                            if attr.is_fabric_visible(target.fab_idx.unwrap_or(0)) {
+                                target.to_tlv(&mut writer, TagType::Anonymous)?;
+                            }
+                        }
+
+                        writer.end_container()?;
+
+                        writer.complete()
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        match attr.attr_id.try_into()? {
+            Attributes::Binding(_) => {
+                attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
+                    self.write_binding_attr(&op, data, attr.endpoint_id, attr.fab_idx)
+                })?;
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// Write the Binding Attribute
+    ///
+    /// Only the bindings of the accessing fabric are added, edited or deleted
+    fn write_binding_attr(
+        &self,
+        op: &ListOperation,
+        data: &TLVElement,
+        endpoint_id: EndptId,
+        fab_idx: u8,
+    ) -> Result<(), Error> {
+        info!("Performing Binding operation {:?}", op);
+        match op {
+            ListOperation::AddItem | ListOperation::EditItem(_) => {
+                let mut target = Target::from_tlv(data).map_err(|_| ErrorCode::InvalidAction)?;
+                // Overwrite the fabric index with our accessing fabric index
+                target.fab_idx = Some(fab_idx);
+
+                let mut binding_mgr = self.binding_mgr.borrow_mut();
+
+                if let ListOperation::EditItem(index) = op {
+                    binding_mgr.edit(endpoint_id, fab_idx, *index as _, target)
+                } else {
+                    binding_mgr.add(endpoint_id, target)
+                }
+            }
+            ListOperation::DeleteItem(index) => {
+                self.binding_mgr
+                    .borrow_mut()
+                    .delete(endpoint_id, fab_idx, *index as _)
+            }
+            ListOperation::DeleteList => {
+                self.binding_mgr
+                    .borrow_mut()
+                    .delete_all(endpoint_id, fab_idx);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> Handler for BindingCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        BindingCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        BindingCluster::write(self, attr, data)
+    }
+}
+
+impl<'a> NonBlockingHandler for BindingCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for BindingCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
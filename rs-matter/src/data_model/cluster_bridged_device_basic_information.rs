@@ -0,0 +1,425 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Bridged Device Basic Information cluster, which describes each of the devices - e.g.
+//! the Zigbee or Z-Wave ones - a bridge exposes on endpoints of its own, as parts of its
+//! Aggregator endpoint, and tells whether they are reachable.
+//!
+//! A single [`BridgedDevicesCluster`] serves the cluster on the endpoints of all the bridged
+//! devices, which are added and removed at runtime along with their endpoints (see
+//! [`BridgedDevicesCluster::add_device`]). It is chained in front of the other handlers with
+//! [`ChainedHandler::chain_cluster`].
+
+use core::cell::RefCell;
+
+use heapless::{String, Vec};
+use log::warn;
+use strum::FromRepr;
+
+use super::objects::*;
+use crate::{
+    attribute_enum,
+    error::{Error, ErrorCode},
+    interaction_model::{
+        events::{EventId, EventPriority},
+        messages::GenericPath,
+    },
+    tlv::ToTLV,
+    utils::rand::Rand,
+    Matter,
+};
+
+pub const ID: u32 = 0x0039;
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    VendorName(AttrUtfType) = 1,
+    VendorId(AttrType<u16>) = 2,
+    ProductName(AttrUtfType) = 3,
+    NodeLabel(AttrUtfType) = 5,
+    HwVer(AttrType<u16>) = 7,
+    SwVer(AttrType<u32>) = 9,
+    SwVerString(AttrUtfType) = 0xa,
+    SerialNo(AttrUtfType) = 0x0f,
+    Reachable(AttrType<bool>) = 0x11,
+    UniqueId(AttrUtfType) = 0x12,
+}
+
+attribute_enum!(Attributes);
+
+pub enum AttributesDiscriminants {
+    VendorName = 1,
+    VendorId = 2,
+    ProductName = 3,
+    NodeLabel = 5,
+    HwVer = 7,
+    SwVer = 9,
+    SwVerString = 0xa,
+    SerialNo = 0x0f,
+    Reachable = 0x11,
+    UniqueId = 0x12,
+}
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    StartUp = 0,
+    ShutDown = 1,
+    Leave = 2,
+    ReachableChanged = 3,
+}
+
+#[derive(ToTLV)]
+struct ReachableChangedEvent {
+    reachable_new_value: bool,
+}
+
+#[derive(ToTLV)]
+struct LeaveEvent {}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(
+            AttributesDiscriminants::VendorName as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::VendorId as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ProductName as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::NodeLabel as u16,
+            Access::RWVM,
+            Quality::N,
+        )
+        .with_constraint(Constraint::Length(0, 32)),
+        Attribute::new(
+            AttributesDiscriminants::HwVer as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SwVer as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SwVerString as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::SerialNo as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::Reachable as u16,
+            Access::RV,
+            Quality::NONE,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::UniqueId as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[
+        Events::Leave as EventId,
+        Events::ReachableChanged as EventId,
+    ],
+};
+
+/// The description of a bridged device, as known to the bridge
+#[derive(Default)]
+pub struct BridgedDeviceInfo<'a> {
+    pub vid: u16,
+    pub hw_ver: u16,
+    pub sw_ver: u32,
+    pub sw_ver_str: &'a str,
+    pub serial_no: &'a str,
+    pub vendor_name: &'a str,
+    pub product_name: &'a str,
+    /// An ID of the device which persists across the restarts of the bridge, so that the
+    /// controllers recognize the device once its endpoint changes
+    pub unique_id: &'a str,
+}
+
+struct BridgedDevice<'a> {
+    endpoint_id: EndptId,
+    info: &'a BridgedDeviceInfo<'a>,
+    node_label: String<32>, // Max node-label as per the spec
+    reachable: bool,
+}
+
+/// The handler of the Bridged Device Basic Information cluster of up to `N` bridged devices
+pub struct BridgedDevicesCluster<'a, const N: usize> {
+    data_ver: Dataver,
+    devices: RefCell<Vec<BridgedDevice<'a>, N>>,
+}
+
+impl<'a, const N: usize> BridgedDevicesCluster<'a, N> {
+    pub fn new(rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            devices: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Adds a bridged device: its endpoint - with the Bridged Device Basic Information cluster,
+    /// next to the clusters of the device itself - is added to the node as a part of the
+    /// Aggregator endpoint `aggregator_id`.
+    ///
+    /// Fails with `Invalid` if there is an endpoint with the same ID already, and with
+    /// `NoSpace` once there are `N` bridged devices, or the node is full.
+    pub async fn add_device<const M: usize>(
+        &self,
+        matter: &Matter<'_>,
+        node: &SharedNode<'a, M>,
+        aggregator_id: EndptId,
+        endpoint: Endpoint<'a>,
+        info: &'a BridgedDeviceInfo<'a>,
+        reachable: bool,
+    ) -> Result<(), Error> {
+        let endpoint_id = endpoint.id;
+
+        if node
+            .lock()
+            .await
+            .node()
+            .endpoints
+            .iter()
+            .any(|endpoint| endpoint.id == endpoint_id)
+        {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        self.devices
+            .borrow_mut()
+            .push(BridgedDevice {
+                endpoint_id,
+                info,
+                node_label: String::new(),
+                reachable,
+            })
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        let endpoint = Endpoint {
+            parent: Some(aggregator_id),
+            ..endpoint
+        };
+
+        if node.add(matter, endpoint).await.is_err() {
+            self.remove(endpoint_id);
+
+            Err(ErrorCode::NoSpace)?;
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// Removes a bridged device - e.g. once it left the bridged network for good - along with
+    /// its endpoint and the persisted values of its attributes. The Leave event of the device
+    /// is emitted beforehand.
+    pub async fn remove_device<const M: usize>(
+        &self,
+        matter: &Matter<'_>,
+        node: &SharedNode<'a, M>,
+        endpoint_id: EndptId,
+    ) -> Result<(), Error> {
+        if self.is_reachable(endpoint_id).is_none() {
+            Err(ErrorCode::EndpointNotFound)?;
+        }
+
+        if let Err(e) = matter.emit_event(
+            endpoint_id,
+            ID,
+            Events::Leave as _,
+            EventPriority::Info,
+            &LeaveEvent {},
+        ) {
+            warn!(
+                "Failed to emit the Leave event of endpoint {}: {}",
+                endpoint_id, e
+            );
+        }
+
+        self.remove(endpoint_id);
+        node.remove(matter, endpoint_id).await;
+
+        matter
+            .nv_attributes
+            .borrow_mut()
+            .remove_endpoint(endpoint_id);
+        matter.notify_changed();
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    /// Whether the device of endpoint `endpoint_id` is reachable, if there is one
+    pub fn is_reachable(&self, endpoint_id: EndptId) -> Option<bool> {
+        self.devices
+            .borrow()
+            .iter()
+            .find(|device| device.endpoint_id == endpoint_id)
+            .map(|device| device.reachable)
+    }
+
+    /// Records whether the device of endpoint `endpoint_id` is reachable - e.g. once the bridge
+    /// stops hearing from it - and emits the ReachableChanged event of the device if that
+    /// changed
+    pub fn set_reachable(
+        &self,
+        matter: &Matter<'_>,
+        endpoint_id: EndptId,
+        reachable: bool,
+    ) -> Result<(), Error> {
+        {
+            let mut devices = self.devices.borrow_mut();
+            let device = devices
+                .iter_mut()
+                .find(|device| device.endpoint_id == endpoint_id)
+                .ok_or(ErrorCode::EndpointNotFound)?;
+
+            if device.reachable == reachable {
+                return Ok(());
+            }
+
+            device.reachable = reachable;
+        }
+
+        self.data_ver.changed();
+        matter.notify_attribute_changed(&GenericPath::new(
+            Some(endpoint_id),
+            Some(ID),
+            Some(AttributesDiscriminants::Reachable as _),
+        ));
+
+        matter.emit_event(
+            endpoint_id,
+            ID,
+            Events::ReachableChanged as _,
+            EventPriority::Info,
+            &ReachableChangedEvent {
+                reachable_new_value: reachable,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                let devices = self.devices.borrow();
+                let device = devices
+                    .iter()
+                    .find(|device| device.endpoint_id == attr.endpoint_id)
+                    .ok_or(ErrorCode::EndpointNotFound)?;
+                let info = device.info;
+
+                match attr.attr_id.try_into()? {
+                    Attributes::VendorName(codec) => codec.encode(writer, info.vendor_name),
+                    Attributes::VendorId(codec) => codec.encode(writer, info.vid),
+                    Attributes::ProductName(codec) => codec.encode(writer, info.product_name),
+                    Attributes::NodeLabel(codec) => {
+                        codec.encode(writer, device.node_label.as_str())
+                    }
+                    Attributes::HwVer(codec) => codec.encode(writer, info.hw_ver),
+                    Attributes::SwVer(codec) => codec.encode(writer, info.sw_ver),
+                    Attributes::SwVerString(codec) => codec.encode(writer, info.sw_ver_str),
+                    Attributes::SerialNo(codec) => codec.encode(writer, info.serial_no),
+                    Attributes::Reachable(codec) => codec.encode(writer, device.reachable),
+                    Attributes::UniqueId(codec) => codec.encode(writer, info.unique_id),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::NodeLabel(codec) => {
+                let mut devices = self.devices.borrow_mut();
+                let device = devices
+                    .iter_mut()
+                    .find(|device| device.endpoint_id == attr.endpoint_id)
+                    .ok_or(ErrorCode::EndpointNotFound)?;
+
+                device.node_label = codec
+                    .decode(data)
+                    .map_err(|_| Error::new(ErrorCode::InvalidAction))?
+                    .try_into()
+                    .map_err(|_| Error::new(ErrorCode::ConstraintError))?;
+            }
+            _ => return Err(Error::new(ErrorCode::InvalidAction)),
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+
+    fn remove(&self, endpoint_id: EndptId) {
+        self.devices
+            .borrow_mut()
+            .retain(|device| device.endpoint_id != endpoint_id);
+    }
+}
+
+impl<'a, const N: usize> Handler for BridgedDevicesCluster<'a, N> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        BridgedDevicesCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        BridgedDevicesCluster::write(self, attr, data)
+    }
+}
+
+impl<'a, const N: usize> NonBlockingHandler for BridgedDevicesCluster<'a, N> {}
+
+impl<'a, const N: usize> ChangeNotifier<()> for BridgedDevicesCluster<'a, N> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
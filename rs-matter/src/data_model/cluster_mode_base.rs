@@ -0,0 +1,336 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! A generic implementation of the clusters derived from the Mode Base cluster (Matter 1.2):
+//! the Dishwasher Mode, Laundry Washer Mode, RVC Run Mode, ... clusters only differ in their ID,
+//! the modes the device supports and the tags of these, so the device only supplies its table of
+//! modes (see [`ModeOption`]) and a [`ModeDelegate`] which carries out the mode changes.
+//!
+//! ```ignore
+//! const MODES: &[ModeOption] = &[
+//!     ModeOption::new("Normal", 0, &[ModeTag::new(TAG_AUTO)]),
+//!     ModeOption::new("Eco", 1, &[ModeTag::new(TAG_LOW_ENERGY)]),
+//! ];
+//!
+//! let handler = root_endpoint::handler(0, &matter)
+//!     .chain(1, DISHWASHER_MODE_ID, ModeCluster::new(MODES, 0, &dishwasher, *matter.borrow()));
+//! ```
+//!
+//! The OnOff feature - and with it the StartUpMode and OnMode attributes - is not supported.
+
+use core::cell::Cell;
+
+use log::info;
+use strum::FromRepr;
+
+use super::objects::*;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, TLVElement, ToTLV, UtfStr},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+};
+
+pub const OVEN_MODE_ID: u32 = 0x0049;
+pub const LAUNDRY_WASHER_MODE_ID: u32 = 0x0051;
+pub const REFRIGERATOR_MODE_ID: u32 = 0x0052;
+pub const RVC_RUN_MODE_ID: u32 = 0x0054;
+pub const RVC_CLEAN_MODE_ID: u32 = 0x0055;
+pub const DISHWASHER_MODE_ID: u32 = 0x0059;
+pub const MICROWAVE_OVEN_MODE_ID: u32 = 0x005E;
+
+/// The mode tags common to all clusters derived from Mode Base. The derived clusters define
+/// their own tags from 0x4000 on.
+pub const TAG_AUTO: u16 = 0x0000;
+pub const TAG_QUICK: u16 = 0x0001;
+pub const TAG_QUIET: u16 = 0x0002;
+pub const TAG_LOW_NOISE: u16 = 0x0003;
+pub const TAG_LOW_ENERGY: u16 = 0x0004;
+pub const TAG_VACATION: u16 = 0x0005;
+pub const TAG_MIN: u16 = 0x0006;
+pub const TAG_MAX: u16 = 0x0007;
+pub const TAG_NIGHT: u16 = 0x0008;
+pub const TAG_DAY: u16 = 0x0009;
+
+/// The statuses of a ChangeToMode command common to all clusters derived from Mode Base. The
+/// derived clusters define their own statuses from 0x40 to 0x7F.
+pub const STATUS_SUCCESS: u8 = 0x00;
+pub const STATUS_UNSUPPORTED_MODE: u8 = 0x01;
+pub const STATUS_GENERIC_FAILURE: u8 = 0x02;
+pub const STATUS_INVALID_IN_MODE: u8 = 0x03;
+
+#[derive(FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    SupportedModes = 0,
+    CurrentMode = 1,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(FromRepr)]
+#[repr(u32)]
+pub enum Commands {
+    ChangeToMode = 0x00,
+}
+
+command_enum!(Commands);
+
+#[repr(u16)]
+pub enum RespCommands {
+    ChangeToModeResponse = 0x01,
+}
+
+const ATTRIBUTES: &[Attribute] = &[
+    FEATURE_MAP,
+    ATTRIBUTE_LIST,
+    ACCEPTED_COMMAND_LIST,
+    GENERATED_COMMAND_LIST,
+    Attribute::new(
+        Attributes::SupportedModes as u16,
+        Access::RV,
+        Quality::FIXED,
+    ),
+    Attribute::new(Attributes::CurrentMode as u16, Access::RV, Quality::N),
+];
+
+const COMMANDS: &[Command] = &[Command::new(Commands::ChangeToMode as _, Access::WO)];
+
+/// The metadata of the Mode Base derived cluster with the ID `id`
+pub const fn cluster(id: ClusterId) -> Cluster<'static> {
+    Cluster {
+        id,
+        feature_map: 0,
+        attributes: ATTRIBUTES,
+        commands: COMMANDS,
+        generated_commands: &[RespCommands::ChangeToModeResponse as _],
+        events: &[],
+    }
+}
+
+pub const OVEN_MODE_CLUSTER: Cluster<'static> = cluster(OVEN_MODE_ID);
+pub const LAUNDRY_WASHER_MODE_CLUSTER: Cluster<'static> = cluster(LAUNDRY_WASHER_MODE_ID);
+pub const REFRIGERATOR_MODE_CLUSTER: Cluster<'static> = cluster(REFRIGERATOR_MODE_ID);
+pub const RVC_RUN_MODE_CLUSTER: Cluster<'static> = cluster(RVC_RUN_MODE_ID);
+pub const RVC_CLEAN_MODE_CLUSTER: Cluster<'static> = cluster(RVC_CLEAN_MODE_ID);
+pub const DISHWASHER_MODE_CLUSTER: Cluster<'static> = cluster(DISHWASHER_MODE_ID);
+pub const MICROWAVE_OVEN_MODE_CLUSTER: Cluster<'static> = cluster(MICROWAVE_OVEN_MODE_ID);
+
+/// A tag of a mode, i.e. one of the common [`TAG_AUTO`], [`TAG_QUICK`], ... tags, one of the
+/// tags of the derived cluster, or a manufacturer-specific one
+#[derive(Debug, ToTLV, Copy, Clone)]
+pub struct ModeTag {
+    pub mfg_code: Option<u16>,
+    pub value: u16,
+}
+
+impl ModeTag {
+    pub const fn new(value: u16) -> Self {
+        Self {
+            mfg_code: None,
+            value,
+        }
+    }
+
+    pub const fn new_mfg(mfg_code: u16, value: u16) -> Self {
+        Self {
+            mfg_code: Some(mfg_code),
+            value,
+        }
+    }
+}
+
+/// A mode the device supports, as listed in the SupportedModes attribute
+#[derive(Debug, ToTLV, Copy, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct ModeOption<'a> {
+    pub label: UtfStr<'a>,
+    pub mode: u8,
+    pub mode_tags: &'a [ModeTag],
+}
+
+impl<'a> ModeOption<'a> {
+    pub const fn new(label: &'a str, mode: u8, mode_tags: &'a [ModeTag]) -> Self {
+        Self {
+            label: UtfStr::new(label.as_bytes()),
+            mode,
+            mode_tags,
+        }
+    }
+}
+
+/// The application side of a Mode Base derived cluster, which carries out the mode changes
+pub trait ModeDelegate {
+    /// Changes the device to the supported mode `new_mode`, which differs from the current one.
+    /// A device which cannot change to the mode returns the status to respond with instead,
+    /// e.g. [`STATUS_INVALID_IN_MODE`] or a status of the derived cluster.
+    fn change_to_mode(&self, new_mode: u8) -> Result<(), u8>;
+}
+
+impl<T> ModeDelegate for &T
+where
+    T: ModeDelegate,
+{
+    fn change_to_mode(&self, new_mode: u8) -> Result<(), u8> {
+        (**self).change_to_mode(new_mode)
+    }
+}
+
+#[derive(FromTLV)]
+struct ChangeToModeReq {
+    new_mode: u8,
+}
+
+#[derive(ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct ChangeToModeResp<'a> {
+    status: u8,
+    status_text: Option<UtfStr<'a>>,
+}
+
+/// The handler of a Mode Base derived cluster on one endpoint
+pub struct ModeCluster<'a> {
+    data_ver: Dataver,
+    modes: &'a [ModeOption<'a>],
+    current_mode: Cell<u8>,
+    delegate: &'a dyn ModeDelegate,
+}
+
+impl<'a> ModeCluster<'a> {
+    pub fn new(
+        modes: &'a [ModeOption<'a>],
+        current_mode: u8,
+        delegate: &'a dyn ModeDelegate,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            modes,
+            current_mode: Cell::new(current_mode),
+            delegate,
+        }
+    }
+
+    pub fn current_mode(&self) -> u8 {
+        self.current_mode.get()
+    }
+
+    /// Sets the current mode, once the device changed its mode by itself, e.g. from its panel.
+    /// The subscribers are to be notified (see [`crate::Matter::attribute_changed`]).
+    pub fn set_current_mode(&self, mode: u8) -> Result<(), Error> {
+        if !self.is_supported(mode) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if self.current_mode.get() != mode {
+            self.current_mode.set(mode);
+            self.data_ver.changed();
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                attr.cluster()?.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::SupportedModes => writer.set_list(self.modes),
+                    Attributes::CurrentMode => writer.set(self.current_mode.get()),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        match cmd.cmd_id.try_into()? {
+            Commands::ChangeToMode => {
+                cmd_enter!("ChangeToMode");
+
+                let req = ChangeToModeReq::from_tlv(data)?;
+                let status = self.change_to_mode(req.new_mode);
+
+                encoder
+                    .with_command(RespCommands::ChangeToModeResponse as _)?
+                    .set(ChangeToModeResp {
+                        status,
+                        status_text: None,
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn change_to_mode(&self, new_mode: u8) -> u8 {
+        if !self.is_supported(new_mode) {
+            STATUS_UNSUPPORTED_MODE
+        } else if new_mode == self.current_mode.get() {
+            STATUS_SUCCESS
+        } else {
+            match self.delegate.change_to_mode(new_mode) {
+                Ok(()) => {
+                    info!("Mode changed to {}", new_mode);
+
+                    self.current_mode.set(new_mode);
+                    self.data_ver.changed();
+
+                    STATUS_SUCCESS
+                }
+                Err(status) => status,
+            }
+        }
+    }
+
+    fn is_supported(&self, mode: u8) -> bool {
+        self.modes.iter().any(|option| option.mode == mode)
+    }
+}
+
+impl<'a> Handler for ModeCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        ModeCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        ModeCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+}
+
+impl<'a> NonBlockingHandler for ModeCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for ModeCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -18,11 +18,15 @@
 use core::cell::Cell;
 
 use super::objects::*;
+use super::scenes::{ExtensionFieldSet, SceneCluster};
 use crate::{
-    attribute_enum, cmd_enter, command_enum, error::Error, tlv::TLVElement,
-    transport::exchange::Exchange, utils::rand::Rand,
+    attribute_enum, cmd_enter, command_enum,
+    error::{Error, ErrorCode},
+    tlv::{FromTLV, Nullable, TLVElement},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
 };
-use log::info;
+use log::{info, warn};
 use rs_matter_macros::idl_import;
 use strum::{EnumDiscriminants, FromRepr};
 
@@ -32,11 +36,17 @@ pub use on_off::ID;
 
 pub use on_off::Commands;
 pub use on_off::CommandsDiscriminants;
+pub use on_off::Feature;
+pub use on_off::StartUpOnOffEnum;
 
 #[derive(FromRepr, EnumDiscriminants)]
 #[repr(u16)]
 pub enum Attributes {
     OnOff(AttrType<bool>) = 0x0,
+    GlobalSceneControl(AttrType<bool>) = 0x4000,
+    OnTime(AttrType<u16>) = 0x4001,
+    OffWaitTime(AttrType<u16>) = 0x4002,
+    StartUpOnOff(AttrType<Nullable<u8>>) = 0x4003,
 }
 
 attribute_enum!(Attributes);
@@ -48,22 +58,91 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::OnOff as u16,
             Access::RV,
             Quality::SN,
         ),
+        Attribute::new(
+            AttributesDiscriminants::GlobalSceneControl as u16,
+            Access::RV,
+            Quality::NONE,
+        )
+        .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
+        Attribute::new(
+            AttributesDiscriminants::OnTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        )
+        .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
+        Attribute::new(
+            AttributesDiscriminants::OffWaitTime as u16,
+            Access::RWVO,
+            Quality::NONE,
+        )
+        .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
+        Attribute::new(
+            AttributesDiscriminants::StartUpOnOff as u16,
+            Access::RWVM,
+            Quality::NX,
+        )
+        .with_constraint(Constraint::Range(0, 2))
+        .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
     ],
     commands: &[
-        CommandsDiscriminants::Off as _,
-        CommandsDiscriminants::On as _,
-        CommandsDiscriminants::Toggle as _,
+        Command::new(CommandsDiscriminants::Off as _, Access::WO),
+        Command::new(CommandsDiscriminants::On as _, Access::WO)
+            .with_conformance(Conformance::NoneOf(Feature::OFF_ONLY.bits())),
+        Command::new(CommandsDiscriminants::Toggle as _, Access::WO)
+            .with_conformance(Conformance::NoneOf(Feature::OFF_ONLY.bits())),
+        Command::new(CommandsDiscriminants::OffWithEffect as _, Access::WO)
+            .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
+        Command::new(
+            CommandsDiscriminants::OnWithRecallGlobalScene as _,
+            Access::WO,
+        )
+        .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
+        Command::new(CommandsDiscriminants::OnWithTimedOff as _, Access::WO)
+            .with_conformance(Conformance::AnyOf(Feature::LIGHTING.bits())),
     ],
+    generated_commands: &[],
+    events: &[],
 };
 
+/// The metadata of the OnOff cluster of the lights, i.e. with the Lighting feature
+pub const LIGHTING_CLUSTER: Cluster<'static> = CLUSTER.with_features(Feature::LIGHTING.bits());
+
+/// The upper bound of the OnTime and OffWaitTime attributes, in 1/10ths of a second
+const MAX_TIME: u16 = 0xFFFE;
+
+#[derive(FromTLV)]
+struct OffWithEffectReq {
+    effect_identifier: u8,
+    effect_variant: u8,
+}
+
+#[derive(FromTLV)]
+struct OnWithTimedOffReq {
+    on_off_control: u8,
+    on_time: u16,
+    off_wait_time: u16,
+}
+
+/// The OnOffControl bit accepting the OnWithTimedOff command only while the device is on
+const ACCEPT_ONLY_WHEN_ON: u8 = 0x01;
+
 pub struct OnOffCluster {
     data_ver: Dataver,
     on: Cell<bool>,
+    global_scene_control: Cell<bool>,
+    /// The OnOff value captured by the OffWithEffect command, and recalled by the
+    /// OnWithRecallGlobalScene command
+    global_scene: Cell<bool>,
+    on_time: Cell<u16>,
+    off_wait_time: Cell<u16>,
+    start_up_on_off: Cell<Option<StartUpOnOffEnum>>,
 }
 
 impl OnOffCluster {
@@ -71,9 +150,71 @@ impl OnOffCluster {
         Self {
             data_ver: Dataver::new(rand),
             on: Cell::new(false),
+            global_scene_control: Cell::new(true),
+            global_scene: Cell::new(false),
+            on_time: Cell::new(0),
+            off_wait_time: Cell::new(0),
+            start_up_on_off: Cell::new(None),
         }
     }
 
+    pub fn get(&self) -> bool {
+        self.on.get()
+    }
+
+    pub fn on_time(&self) -> u16 {
+        self.on_time.get()
+    }
+
+    pub fn off_wait_time(&self) -> u16 {
+        self.off_wait_time.get()
+    }
+
+    pub fn start_up_on_off(&self) -> Option<StartUpOnOffEnum> {
+        self.start_up_on_off.get()
+    }
+
+    /// Applies the StartUpOnOff attribute to the OnOff attribute, as the device does when
+    /// it powers up. To be called at startup, once the persisted values of both were restored
+    /// (see [`crate::Matter::restore_nv_attributes`]); a null StartUpOnOff keeps the
+    /// OnOff value the device had before.
+    pub fn start_up(&self) {
+        match self.start_up_on_off.get() {
+            Some(StartUpOnOffEnum::Off) => self.set(false),
+            Some(StartUpOnOffEnum::On) => self.set(true),
+            Some(StartUpOnOffEnum::Toggle) => self.set(!self.on.get()),
+            None => (),
+        }
+    }
+
+    /// Counts the OnTime attribute down while the device is on - turning it off once it
+    /// expires - and the OffWaitTime attribute while it is off. To be called every 1/10th of
+    /// a second by the devices with the Lighting feature.
+    ///
+    /// Returns `true` if the attributes changed, so that the application can notify the
+    /// subscribers (see [`crate::Matter::attribute_changed`]).
+    pub fn tick(&self) -> bool {
+        let on_time = self.on_time.get();
+        let off_wait_time = self.off_wait_time.get();
+
+        if self.on.get() && on_time > 0 {
+            self.on_time.set(on_time - 1);
+
+            if on_time == 1 {
+                self.off_wait_time.set(0);
+                self.on.set(false);
+            }
+        } else if !self.on.get() && off_wait_time > 0 {
+            self.off_wait_time.set(off_wait_time - 1);
+        } else {
+            return false;
+        }
+
+        self.data_ver.changed();
+
+        true
+    }
+
     pub fn set(&self, on: bool) {
         if self.on.get() != on {
             self.on.set(on);
@@ -84,10 +225,21 @@ impl OnOffCluster {
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
-                CLUSTER.read(attr.attr_id, writer)
+                attr.cluster()?.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::OnOff(codec) => codec.encode(writer, self.on.get()),
+                    Attributes::GlobalSceneControl(codec) => {
+                        codec.encode(writer, self.global_scene_control.get())
+                    }
+                    Attributes::OnTime(codec) => codec.encode(writer, self.on_time.get()),
+                    Attributes::OffWaitTime(codec) => {
+                        codec.encode(writer, self.off_wait_time.get())
+                    }
+                    Attributes::StartUpOnOff(codec) => codec.encode(
+                        writer,
+                        Nullable::new(self.start_up_on_off.get().map(|value| value as _)),
+                    ),
                 }
             }
         } else {
@@ -96,10 +248,21 @@ impl OnOffCluster {
     }
 
     pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        let data = data.with_dataver(self.data_ver.get())?;
-
         match attr.attr_id.try_into()? {
-            Attributes::OnOff(codec) => self.set(codec.decode(data)?),
+            Attributes::OnOff(_) => self.set(data.get(self.data_ver.get())?),
+            Attributes::OnTime(_) => self.on_time.set(data.get(self.data_ver.get())?),
+            Attributes::OffWaitTime(_) => self.off_wait_time.set(data.get(self.data_ver.get())?),
+            Attributes::StartUpOnOff(_) => {
+                let start_up_on_off = match data.get(self.data_ver.get())? {
+                    Nullable::Null => None,
+                    Nullable::NotNull(value) => {
+                        Some(Self::start_up_on_off_from(value).ok_or(ErrorCode::ConstraintError)?)
+                    }
+                };
+
+                self.start_up_on_off.set(start_up_on_off);
+            }
+            _ => Err(ErrorCode::InvalidAction)?,
         }
 
         self.data_ver.changed();
@@ -109,33 +272,125 @@ impl OnOffCluster {
 
     pub fn invoke(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         cmd: &CmdDetails,
-        _data: &TLVElement,
+        data: &TLVElement,
         _encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
+        let was_on = self.on.get();
+
         match cmd.cmd_id.try_into()? {
             Commands::Off => {
                 cmd_enter!("Off");
-                self.set(false);
+                self.turn_off();
             }
             Commands::On => {
                 cmd_enter!("On");
-                self.set(true);
+                self.turn_on();
             }
             Commands::Toggle => {
                 cmd_enter!("Toggle");
-                self.set(!self.on.get());
+                if self.on.get() {
+                    self.turn_off();
+                } else {
+                    self.turn_on();
+                }
+            }
+            Commands::OffWithEffect => {
+                cmd_enter!("OffWithEffect");
+                let req = OffWithEffectReq::from_tlv(data)?;
+
+                // DelayedAllOff or DyingLight; rendering the effect is up to the device
+                if req.effect_identifier > 1 {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                info!(
+                    "Effect {}, variant {}",
+                    req.effect_identifier, req.effect_variant
+                );
+
+                if self.global_scene_control.get() {
+                    self.global_scene.set(self.on.get());
+                    self.global_scene_control.set(false);
+                }
+
+                self.turn_off();
+            }
+            Commands::OnWithRecallGlobalScene => {
+                cmd_enter!("OnWithRecallGlobalScene");
+
+                // Only recalled once after an OffWithEffect command
+                if !self.global_scene_control.get() {
+                    self.set(self.global_scene.get());
+                    self.global_scene_control.set(true);
+
+                    if self.on_time.get() == 0 {
+                        self.off_wait_time.set(0);
+                    }
+                }
+            }
+            Commands::OnWithTimedOff => {
+                cmd_enter!("OnWithTimedOff");
+                let req = OnWithTimedOffReq::from_tlv(data)?;
+
+                if req.on_time > MAX_TIME || req.off_wait_time > MAX_TIME {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                if req.on_off_control & ACCEPT_ONLY_WHEN_ON != 0 && !self.on.get() {
+                    // Discarded
+                } else if self.off_wait_time.get() > 0 && !self.on.get() {
+                    // Guarded against being turned back on
+                    self.off_wait_time
+                        .set(self.off_wait_time.get().min(req.off_wait_time));
+                } else {
+                    self.on_time.set(self.on_time.get().max(req.on_time));
+                    self.off_wait_time.set(req.off_wait_time);
+                    self.set(true);
+                }
             }
-            Commands::OffWithEffect
-            | Commands::OnWithRecallGlobalScene
-            | Commands::OnWithTimedOff => todo!(),
         }
 
         self.data_ver.changed();
 
+        if self.on.get() != was_on {
+            // Persisted, for a null StartUpOnOff to restore it at startup
+            if let Err(e) = exchange.matter.set_nv_attribute(
+                cmd.endpoint_id,
+                ID as _,
+                AttributesDiscriminants::OnOff as _,
+                &self.on.get(),
+            ) {
+                warn!("Failed to persist the OnOff attribute: {}", e);
+            }
+        }
+
         Ok(())
     }
+
+    fn turn_on(&self) {
+        self.set(true);
+        self.global_scene_control.set(true);
+
+        if self.on_time.get() == 0 {
+            self.off_wait_time.set(0);
+        }
+    }
+
+    fn turn_off(&self) {
+        self.set(false);
+        self.on_time.set(0);
+    }
+
+    fn start_up_on_off_from(value: u8) -> Option<StartUpOnOffEnum> {
+        match value {
+            0 => Some(StartUpOnOffEnum::Off),
+            1 => Some(StartUpOnOffEnum::On),
+            2 => Some(StartUpOnOffEnum::Toggle),
+            _ => None,
+        }
+    }
 }
 
 impl Handler for OnOffCluster {
@@ -158,6 +413,31 @@ impl Handler for OnOffCluster {
     }
 }
 
+impl SceneCluster for OnOffCluster {
+    fn cluster_id(&self) -> ClusterId {
+        ID as _
+    }
+
+    fn capture(&self) -> Result<ExtensionFieldSet, Error> {
+        let mut values = ExtensionFieldSet::new(ID as _);
+        values.set(AttributesDiscriminants::OnOff as _, self.on.get() as _)?;
+
+        Ok(values)
+    }
+
+    fn restore(&self, values: &ExtensionFieldSet, _transition_time_ms: u32) -> Result<(), Error> {
+        if values.cluster_id != ID as ClusterId {
+            Err(ErrorCode::InvalidArgument)?;
+        }
+
+        if let Some(on) = values.get(AttributesDiscriminants::OnOff as _) {
+            self.set(on != 0);
+        }
+
+        Ok(())
+    }
+}
+
 // TODO: Might be removed once the `on` member is externalized
 impl NonBlockingHandler for OnOffCluster {}
 
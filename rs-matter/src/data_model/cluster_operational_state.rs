@@ -0,0 +1,548 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! A generic implementation of the Operational State cluster (Matter 1.2), which also serves the
+//! clusters derived from it, e.g. the RVC Operational State and the Oven Cavity Operational State
+//! ones: these only differ in their ID, the commands they accept (see [`cluster`]) and their own
+//! operational states and errors, from 0x40 on.
+//!
+//! The device supplies its operational states and phases, and an [`OperationalStateDelegate`]
+//! which carries out the commands. The changes the device makes by itself - e.g. an operation
+//! which completes or fails - are told to the [`OperationalStateCluster`], which notifies the
+//! subscribers and emits the events of the cluster.
+
+use core::cell::Cell;
+
+use log::info;
+use strum::FromRepr;
+
+use super::objects::*;
+use crate::{
+    attribute_enum, cmd_enter, command_enum,
+    error::Error,
+    interaction_model::events::{EventId, EventPriority},
+    tlv::{Nullable, TLVElement, TLVWriter, TagType, ToTLV, UtfStr},
+    transport::exchange::Exchange,
+    utils::rand::Rand,
+    Matter,
+};
+
+pub const ID: u32 = 0x0060;
+pub const RVC_OPERATIONAL_STATE_ID: u32 = 0x0061;
+pub const OVEN_CAVITY_OPERATIONAL_STATE_ID: u32 = 0x0048;
+
+/// The operational states common to the Operational State cluster and the clusters derived from
+/// it. The derived clusters define their own states from 0x40 on, and the manufacturers from
+/// 0x80 on.
+pub const STATE_STOPPED: u8 = 0x00;
+pub const STATE_RUNNING: u8 = 0x01;
+pub const STATE_PAUSED: u8 = 0x02;
+pub const STATE_ERROR: u8 = 0x03;
+
+/// The operational errors common to the Operational State cluster and the clusters derived from
+/// it. The derived clusters define their own errors from 0x40 on, and the manufacturers from
+/// 0x80 on.
+pub const ERROR_NO_ERROR: u8 = 0x00;
+pub const ERROR_UNABLE_TO_START_OR_RESUME: u8 = 0x01;
+pub const ERROR_UNABLE_TO_COMPLETE_OPERATION: u8 = 0x02;
+pub const ERROR_COMMAND_INVALID_IN_STATE: u8 = 0x03;
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    PhaseList = 0x0000,
+    CurrentPhase = 0x0001,
+    OperationalStateList = 0x0003,
+    OperationalState = 0x0004,
+    OperationalError = 0x0005,
+}
+
+attribute_enum!(Attributes);
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u32)]
+pub enum Commands {
+    Pause = 0x00,
+    Stop = 0x01,
+    Start = 0x02,
+    Resume = 0x03,
+}
+
+command_enum!(Commands);
+
+#[repr(u16)]
+pub enum RespCommands {
+    OperationalCommandResponse = 0x04,
+}
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u32)]
+pub enum Events {
+    OperationalError = 0x00,
+    OperationCompletion = 0x01,
+}
+
+const ATTRIBUTES: &[Attribute] = &[
+    FEATURE_MAP,
+    ATTRIBUTE_LIST,
+    ACCEPTED_COMMAND_LIST,
+    GENERATED_COMMAND_LIST,
+    Attribute::new(Attributes::PhaseList as u16, Access::RV, Quality::X),
+    Attribute::new(Attributes::CurrentPhase as u16, Access::RV, Quality::X),
+    Attribute::new(
+        Attributes::OperationalStateList as u16,
+        Access::RV,
+        Quality::NONE,
+    ),
+    Attribute::new(
+        Attributes::OperationalState as u16,
+        Access::RV,
+        Quality::NONE,
+    ),
+    Attribute::new(
+        Attributes::OperationalError as u16,
+        Access::RV,
+        Quality::NONE,
+    ),
+];
+
+/// The commands of the Operational State cluster
+pub const COMMANDS: &[Command] = &[
+    Command::new(Commands::Pause as _, Access::WO),
+    Command::new(Commands::Stop as _, Access::WO),
+    Command::new(Commands::Start as _, Access::WO),
+    Command::new(Commands::Resume as _, Access::WO),
+];
+
+/// The commands of the RVC Operational State cluster, as a robotic vacuum cleaner is started and
+/// stopped with its run mode
+pub const RVC_COMMANDS: &[Command] = &[
+    Command::new(Commands::Pause as _, Access::WO),
+    Command::new(Commands::Resume as _, Access::WO),
+];
+
+/// The commands of the Oven Cavity Operational State cluster
+pub const OVEN_CAVITY_COMMANDS: &[Command] = &[
+    Command::new(Commands::Stop as _, Access::WO),
+    Command::new(Commands::Start as _, Access::WO),
+];
+
+/// The metadata of the Operational State cluster - or of a cluster derived from it - with the ID
+/// `id`, accepting the commands `commands`
+pub const fn cluster(id: ClusterId, commands: &'static [Command]) -> Cluster<'static> {
+    Cluster {
+        id,
+        feature_map: 0,
+        attributes: ATTRIBUTES,
+        commands,
+        generated_commands: &[RespCommands::OperationalCommandResponse as _],
+        events: &[
+            Events::OperationalError as EventId,
+            Events::OperationCompletion as EventId,
+        ],
+    }
+}
+
+pub const CLUSTER: Cluster<'static> = cluster(ID, COMMANDS);
+pub const RVC_OPERATIONAL_STATE_CLUSTER: Cluster<'static> =
+    cluster(RVC_OPERATIONAL_STATE_ID, RVC_COMMANDS);
+pub const OVEN_CAVITY_OPERATIONAL_STATE_CLUSTER: Cluster<'static> =
+    cluster(OVEN_CAVITY_OPERATIONAL_STATE_ID, OVEN_CAVITY_COMMANDS);
+
+/// An operational state the device supports, as listed in the OperationalStateList attribute.
+/// The manufacturer-specific states need a label.
+#[derive(Debug, ToTLV, Copy, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct OperationalStateOption<'a> {
+    pub id: u8,
+    pub label: Option<UtfStr<'a>>,
+}
+
+impl<'a> OperationalStateOption<'a> {
+    pub const fn new(id: u8) -> Self {
+        Self { id, label: None }
+    }
+
+    pub const fn with_label(self, label: &'a str) -> Self {
+        Self {
+            label: Some(UtfStr::new(label.as_bytes())),
+            ..self
+        }
+    }
+}
+
+/// The operational states of the Operational State cluster itself
+pub const STATES: &[OperationalStateOption<'static>] = &[
+    OperationalStateOption::new(STATE_STOPPED),
+    OperationalStateOption::new(STATE_RUNNING),
+    OperationalStateOption::new(STATE_PAUSED),
+    OperationalStateOption::new(STATE_ERROR),
+];
+
+/// An operational error, as reported by the OperationalError attribute and event, and by the
+/// responses to the commands. The manufacturer-specific errors need a label.
+#[derive(Debug, ToTLV, Copy, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct ErrorState<'a> {
+    pub id: u8,
+    pub label: Option<UtfStr<'a>>,
+    pub details: Option<UtfStr<'a>>,
+}
+
+impl<'a> ErrorState<'a> {
+    pub const fn new(id: u8) -> Self {
+        Self {
+            id,
+            label: None,
+            details: None,
+        }
+    }
+
+    pub const fn with_label(self, label: &'a str) -> Self {
+        Self {
+            label: Some(UtfStr::new(label.as_bytes())),
+            ..self
+        }
+    }
+
+    pub const fn with_details(self, details: &'a str) -> Self {
+        Self {
+            details: Some(UtfStr::new(details.as_bytes())),
+            ..self
+        }
+    }
+}
+
+/// The application side of the Operational State cluster - or of a cluster derived from it -
+/// which carries out the commands.
+///
+/// Each command returns the operational error to respond with if the device cannot carry it out,
+/// and defaults to [`ERROR_COMMAND_INVALID_IN_STATE`], so that a derived cluster only implements
+/// the commands it accepts. The commands which would not change the operational state - e.g.
+/// Pause while paused - do not reach the delegate.
+pub trait OperationalStateDelegate {
+    /// Pauses the operation, from the Running state or from a state of the derived cluster
+    fn pause(&self) -> Result<(), u8> {
+        Err(ERROR_COMMAND_INVALID_IN_STATE)
+    }
+
+    /// Resumes the operation, from the Paused state or from a state of the derived cluster
+    fn resume(&self) -> Result<(), u8> {
+        Err(ERROR_COMMAND_INVALID_IN_STATE)
+    }
+
+    /// Starts the operation
+    fn start(&self) -> Result<(), u8> {
+        Err(ERROR_COMMAND_INVALID_IN_STATE)
+    }
+
+    /// Stops the operation
+    fn stop(&self) -> Result<(), u8> {
+        Err(ERROR_COMMAND_INVALID_IN_STATE)
+    }
+}
+
+impl<T> OperationalStateDelegate for &T
+where
+    T: OperationalStateDelegate,
+{
+    fn pause(&self) -> Result<(), u8> {
+        (**self).pause()
+    }
+
+    fn resume(&self) -> Result<(), u8> {
+        (**self).resume()
+    }
+
+    fn start(&self) -> Result<(), u8> {
+        (**self).start()
+    }
+
+    fn stop(&self) -> Result<(), u8> {
+        (**self).stop()
+    }
+}
+
+#[derive(ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct OperationalCommandResp<'a> {
+    command_response_state: ErrorState<'a>,
+}
+
+#[derive(ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct OperationalErrorEvent<'a> {
+    error_state: ErrorState<'a>,
+}
+
+#[derive(ToTLV)]
+struct OperationCompletionEvent {
+    completion_error_code: u8,
+    total_operational_time: Option<Nullable<u32>>,
+    paused_time: Option<Nullable<u32>>,
+}
+
+/// The handler of the Operational State cluster - or of a cluster derived from it - on one
+/// endpoint
+pub struct OperationalStateCluster<'a> {
+    data_ver: Dataver,
+    endpoint_id: EndptId,
+    cluster_id: ClusterId,
+    states: &'a [OperationalStateOption<'a>],
+    phases: Option<&'a [&'a str]>,
+    state: Cell<u8>,
+    phase: Cell<Option<u8>>,
+    error: Cell<ErrorState<'a>>,
+    delegate: &'a dyn OperationalStateDelegate,
+}
+
+impl<'a> OperationalStateCluster<'a> {
+    /// Creates the handler of the cluster `cluster_id` on the endpoint `endpoint_id`, with the
+    /// operational states `states` (e.g. [`STATES`]) and - if the operation of the device has
+    /// phases - the labels of its phases. The device is initially stopped.
+    pub fn new(
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        states: &'a [OperationalStateOption<'a>],
+        phases: Option<&'a [&'a str]>,
+        delegate: &'a dyn OperationalStateDelegate,
+        rand: Rand,
+    ) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            endpoint_id,
+            cluster_id,
+            states,
+            phases,
+            state: Cell::new(STATE_STOPPED),
+            phase: Cell::new(None),
+            error: Cell::new(ErrorState::new(ERROR_NO_ERROR)),
+            delegate,
+        }
+    }
+
+    pub fn state(&self) -> u8 {
+        self.state.get()
+    }
+
+    pub fn phase(&self) -> Option<u8> {
+        self.phase.get()
+    }
+
+    /// Sets the operational state, once the device changed it by itself. Use
+    /// [`OperationalStateCluster::set_error`] to enter the Error state instead.
+    pub fn set_state(&self, matter: &Matter<'_>, state: u8) {
+        if self.state.get() != state {
+            self.state.set(state);
+            self.error.set(ErrorState::new(ERROR_NO_ERROR));
+            self.changed(matter, Attributes::OperationalState);
+            self.changed(matter, Attributes::OperationalError);
+        }
+    }
+
+    /// Sets the index of the current phase in the list of the phases, or `None` if the device
+    /// is not operating
+    pub fn set_phase(&self, matter: &Matter<'_>, phase: Option<u8>) {
+        if self.phase.get() != phase {
+            self.phase.set(phase);
+            self.changed(matter, Attributes::CurrentPhase);
+        }
+    }
+
+    /// Enters the Error state because of `error`, and emits the OperationalError event
+    pub fn set_error(&self, matter: &Matter<'_>, error: ErrorState<'a>) -> Result<(), Error> {
+        self.state.set(STATE_ERROR);
+        self.error.set(error);
+        self.changed(matter, Attributes::OperationalState);
+        self.changed(matter, Attributes::OperationalError);
+
+        matter.emit_event(
+            self.endpoint_id,
+            self.cluster_id,
+            Events::OperationalError as _,
+            EventPriority::Critical,
+            &OperationalErrorEvent { error_state: error },
+        )?;
+
+        Ok(())
+    }
+
+    /// Stops the device once its operation completed - successfully if `completion_error` is
+    /// [`ERROR_NO_ERROR`] - and emits the OperationCompletion event, with the total and the
+    /// paused times of the operation in seconds, if known
+    pub fn complete(
+        &self,
+        matter: &Matter<'_>,
+        completion_error: u8,
+        total_time: Option<u32>,
+        paused_time: Option<u32>,
+    ) -> Result<(), Error> {
+        self.set_state(matter, STATE_STOPPED);
+        self.set_phase(matter, None);
+
+        matter.emit_event(
+            self.endpoint_id,
+            self.cluster_id,
+            Events::OperationCompletion as _,
+            EventPriority::Info,
+            &OperationCompletionEvent {
+                completion_error_code: completion_error,
+                total_operational_time: total_time.map(Nullable::NotNull),
+                paused_time: paused_time.map(Nullable::NotNull),
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                attr.cluster()?.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::PhaseList => {
+                        self.encode_phase_list(AttrDataWriter::TAG, &mut writer)?;
+                        writer.complete()
+                    }
+                    Attributes::CurrentPhase => writer.set(match self.phase.get() {
+                        Some(phase) => Nullable::NotNull(phase),
+                        None => Nullable::Null,
+                    }),
+                    Attributes::OperationalStateList => writer.set_list(self.states),
+                    Attributes::OperationalState => writer.set(self.state.get()),
+                    Attributes::OperationalError => writer.set(self.error.get()),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn invoke(
+        &self,
+        _exchange: &Exchange,
+        cmd: &CmdDetails,
+        _data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        let command = cmd.cmd_id.try_into()?;
+
+        let error = match command {
+            Commands::Pause => {
+                cmd_enter!("Pause");
+                self.transition(command, STATE_PAUSED)
+            }
+            Commands::Stop => {
+                cmd_enter!("Stop");
+                self.transition(command, STATE_STOPPED)
+            }
+            Commands::Start => {
+                cmd_enter!("Start");
+                self.transition(command, STATE_RUNNING)
+            }
+            Commands::Resume => {
+                cmd_enter!("Resume");
+                self.transition(command, STATE_RUNNING)
+            }
+        };
+
+        encoder
+            .with_command(RespCommands::OperationalCommandResponse as _)?
+            .set(OperationalCommandResp {
+                command_response_state: ErrorState::new(error),
+            })?;
+
+        Ok(())
+    }
+
+    /// Carries out the command `command`, which transitions the device to the state `to`, and
+    /// returns the operational error to respond with
+    fn transition(&self, command: Commands, to: u8) -> u8 {
+        let from = self.state.get();
+
+        if from == to {
+            return ERROR_NO_ERROR;
+        }
+
+        let result = match command {
+            // Neither a stopped nor a failed operation can be paused or resumed, while the
+            // states of the derived clusters are up to the delegate
+            Commands::Pause | Commands::Resume if from == STATE_STOPPED || from == STATE_ERROR => {
+                Err(ERROR_COMMAND_INVALID_IN_STATE)
+            }
+            Commands::Pause => self.delegate.pause(),
+            Commands::Resume => self.delegate.resume(),
+            Commands::Start => self.delegate.start(),
+            Commands::Stop => self.delegate.stop(),
+        };
+
+        match result {
+            Ok(()) => {
+                info!("Operational state changed from {} to {}", from, to);
+
+                self.state.set(to);
+                self.error.set(ErrorState::new(ERROR_NO_ERROR));
+                self.data_ver.changed();
+
+                ERROR_NO_ERROR
+            }
+            Err(error) => error,
+        }
+    }
+
+    fn encode_phase_list(&self, tag: TagType, tw: &mut TLVWriter) -> Result<(), Error> {
+        if let Some(phases) = self.phases {
+            tw.start_array(tag)?;
+            for phase in phases {
+                tw.utf8(TagType::Anonymous, phase.as_bytes())?;
+            }
+            tw.end_container()
+        } else {
+            tw.null(tag)
+        }
+    }
+
+    fn changed(&self, matter: &Matter<'_>, attr: Attributes) {
+        self.data_ver.changed();
+        matter.attribute_changed(self.endpoint_id, self.cluster_id, attr as _);
+    }
+}
+
+impl<'a> Handler for OperationalStateCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        OperationalStateCluster::read(self, attr, encoder)
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        OperationalStateCluster::invoke(self, exchange, cmd, data, encoder)
+    }
+}
+
+impl<'a> NonBlockingHandler for OperationalStateCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for OperationalStateCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -22,8 +22,8 @@ use crate::{
 };
 
 use super::objects::{
-    AttrDataEncoder, AttrDetails, ChangeNotifier, Dataver, NonBlockingHandler, ATTRIBUTE_LIST,
-    FEATURE_MAP,
+    AttrDataEncoder, AttrDetails, ChangeNotifier, Dataver, NonBlockingHandler,
+    ACCEPTED_COMMAND_LIST, ATTRIBUTE_LIST, FEATURE_MAP, GENERATED_COMMAND_LIST,
 };
 
 const CLUSTER_NETWORK_COMMISSIONING_ID: u32 = 0x0031;
@@ -31,8 +31,15 @@ const CLUSTER_NETWORK_COMMISSIONING_ID: u32 = 0x0031;
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: CLUSTER_NETWORK_COMMISSIONING_ID as _,
     feature_map: 0,
-    attributes: &[FEATURE_MAP, ATTRIBUTE_LIST],
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+    ],
     commands: &[],
+    generated_commands: &[],
+    events: &[],
 };
 
 pub struct TemplateCluster {
@@ -49,7 +56,7 @@ impl TemplateCluster {
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
-                CLUSTER.read(attr.attr_id, writer)
+                attr.cluster()?.read(attr.attr_id, writer)
             } else {
                 Err(ErrorCode::AttributeNotFound.into())
             }
@@ -15,19 +15,28 @@
  *    limitations under the License.
  */
 
-use portable_atomic::{AtomicU32, Ordering};
+use core::cell::Cell;
+
+use embassy_futures::select::select;
+use embassy_time::{Duration, Timer};
+use log::{info, warn};
 
 use super::objects::*;
 use crate::{
     alloc,
     error::*,
-    interaction_model::core::Interaction,
+    interaction_model::{
+        client,
+        core::{IMStatusCode, Interaction, OpCode, ReportChunks, ReportDriver},
+        events,
+        messages::{msg::SubscribeReq, GenericPath},
+        subscriptions::MAX_SUBSCRIPTION_DATAVERS,
+    },
+    tlv::{get_root_node_struct, FromTLV},
     transport::{exchange::Exchange, packet::Packet},
+    Matter,
 };
 
-// TODO: For now...
-static SUBS_ID: AtomicU32 = AtomicU32::new(1);
-
 /// The Maximum number of expanded writer request per transaction
 ///
 /// The write requests are first wildcard-expanded, and these many number of
@@ -51,15 +60,48 @@ impl<T> DataModel<T> {
     where
         T: DataModelHandler,
     {
-        let timeout = Interaction::timeout(exchange, rx, tx).await?;
+        // The reports of the subscriptions this node established as a client
+        if rx.get_proto_opcode::<OpCode>()? == OpCode::ReportData {
+            return client::handle_report(exchange, rx, tx).await;
+        }
+
+        if !Interaction::timed(exchange, rx, tx).await? {
+            return Ok(());
+        }
+
+        let (mut rx, mut rx_status) = (rx, rx_status);
+
+        // Each chunk of a chunked write is received in the status packet, while the previous
+        // chunk is still in the request one; hence they swap roles with every chunk
+        while self.handle_interaction(exchange, rx, tx, rx_status).await? {
+            core::mem::swap(&mut rx, &mut rx_status);
+        }
+
+        Ok(())
+    }
+
+    /// Handles the interaction of the request in `rx`. Returns `true` if it is a write followed
+    /// by more chunks, the next one of which is then received in `rx_status`.
+    async fn handle_interaction<'p>(
+        &self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'p>,
+        tx: &mut Packet<'p>,
+        rx_status: &mut Packet<'p>,
+    ) -> Result<bool, Error>
+    where
+        T: DataModelHandler,
+    {
+        let matter = exchange.matter;
+
+        let subscription_id = || matter.subscriptions.borrow_mut().next_id();
 
         let mut interaction = alloc!(Interaction::new(
             exchange,
             rx,
             tx,
             rx_status,
-            || SUBS_ID.fetch_add(1, Ordering::SeqCst),
-            timeout,
+            subscription_id
         )?);
 
         #[cfg(feature = "alloc")]
@@ -70,6 +112,8 @@ impl<T> DataModel<T> {
 
         let metadata = self.0.lock().await;
 
+        let mut more_chunks = false;
+
         if interaction.start().await? {
             match interaction {
                 Interaction::Read {
@@ -77,13 +121,60 @@ impl<T> DataModel<T> {
                     ref mut driver,
                 } => {
                     let accessor = driver.accessor()?;
+                    let compression = PathCompression::new(matter.report_tag_compression.get());
 
-                    'outer: for item in metadata.node().read(req, None, &accessor) {
-                        while !AttrDataEncoder::handle_read(&item, &self.0, &mut driver.writer()?)
+                    'report: {
+                        for item in metadata.node().read(req, None, &accessor) {
+                            while !AttrDataEncoder::handle_read(
+                                &item,
+                                &self.0,
+                                &mut driver.writer()?,
+                                &compression,
+                                matter,
+                            )
                             .await?
-                        {
-                            if !driver.send_chunk(req).await? {
-                                break 'outer;
+                            {
+                                if !driver.send_chunk(req).await? {
+                                    break 'report;
+                                }
+                            }
+                        }
+
+                        if let Some(paths) = &req.event_requests {
+                            if !driver.start_events(req).await? {
+                                break 'report;
+                            }
+
+                            let node = metadata.node();
+
+                            for status in node.event_statuses(paths) {
+                                while !events::encode_status(&status, &mut driver.writer()?)? {
+                                    if !driver.send_chunk(req).await? {
+                                        break 'report;
+                                    }
+                                }
+                            }
+
+                            let mut from = req.event_min();
+
+                            loop {
+                                let next = matter.events.borrow().encode_next(
+                                    from,
+                                    paths,
+                                    &node,
+                                    &accessor,
+                                    &mut driver.writer()?,
+                                )?;
+
+                                match next {
+                                    Some((number, true)) => from = number + 1,
+                                    Some((_, false)) => {
+                                        if !driver.send_chunk(req).await? {
+                                            break 'report;
+                                        }
+                                    }
+                                    None => break,
+                                }
                             }
                         }
                     }
@@ -106,51 +197,444 @@ impl<T> DataModel<T> {
                     // Thus we support the Case1 by doing this. It does come at the cost of maintaining an
                     // additional list of expanded write requests as we start processing those.
                     let node = metadata.node();
-                    let write_attrs: heapless::Vec<_, MAX_WRITE_ATTRS_IN_ONE_TRANS> =
-                        node.write(req, &accessor).collect();
+                    let group = driver.group()?;
+                    let write_attrs: heapless::Vec<_, MAX_WRITE_ATTRS_IN_ONE_TRANS> = node
+                        .write(req, &accessor)
+                        .filter(|item| {
+                            Self::is_group_target(
+                                matter,
+                                group,
+                                item.as_ref().map(|(attr, _)| attr.endpoint_id),
+                            )
+                        })
+                        .collect();
 
-                    for item in write_attrs {
-                        AttrDataEncoder::handle_write(&item, &self.0, &mut driver.writer()?)
+                    for item in &write_attrs {
+                        AttrDataEncoder::handle_write(item, &self.0, &mut driver.writer()?, matter)
                             .await?;
                     }
 
-                    driver.complete(req).await?;
+                    for (attr, _) in write_attrs.iter().flatten() {
+                        matter.notify_attribute_changed(&GenericPath::new(
+                            Some(attr.endpoint_id),
+                            Some(attr.cluster_id),
+                            Some(attr.attr_id as _),
+                        ));
+                    }
+
+                    more_chunks = driver.complete(req).await?;
                 }
                 Interaction::Invoke {
                     req,
                     ref mut driver,
                 } => {
                     let accessor = driver.accessor()?;
+                    let group = driver.group()?;
 
-                    for item in metadata.node().invoke(req, &accessor) {
+                    let node = metadata.node();
+                    let items = node.invoke(req, &accessor).filter(|item| {
+                        Self::is_group_target(
+                            matter,
+                            group,
+                            item.as_ref().map(|(cmd, _)| cmd.endpoint_id),
+                        )
+                    });
+
+                    for item in items {
                         let (mut tw, exchange) = driver.writer_exchange()?;
 
                         CmdDataEncoder::handle(&item, &self.0, &mut tw, exchange).await?;
                     }
 
+                    // Commands usually change the state of the device
+                    matter.notify_attributes_changed();
+
                     driver.complete(req).await?;
                 }
                 Interaction::Subscribe {
                     req,
                     ref mut driver,
                 } => {
-                    let accessor = driver.accessor()?;
+                    let Some((fab_idx, peer_node_id)) = driver.subscriber()? else {
+                        warn!("Rejecting a subscription over a non-CASE session");
+                        driver.reject(IMStatusCode::InvalidAction).await?;
 
-                    'outer: for item in metadata.node().subscribing_read(req, None, &accessor) {
-                        while !AttrDataEncoder::handle_read(&item, &self.0, &mut driver.writer()?)
-                            .await?
-                        {
-                            if !driver.send_chunk(req).await? {
-                                break 'outer;
-                            }
+                        return Ok(false);
+                    };
+
+                    if req.min_int_floor > req.max_int_ceil {
+                        driver.reject(IMStatusCode::InvalidAction).await?;
+
+                        return Ok(false);
+                    }
+
+                    let id = driver.subscription_id();
+
+                    let added = {
+                        let mut subscriptions = matter.subscriptions.borrow_mut();
+
+                        if !req.keep_subs {
+                            subscriptions.remove_subscriber(fab_idx, peer_node_id);
                         }
+
+                        subscriptions.add(
+                            id,
+                            fab_idx,
+                            peer_node_id,
+                            req.min_int_floor,
+                            driver.max_int(req),
+                            rx.as_slice(),
+                        )
+                    };
+
+                    if added.is_err() {
+                        warn!("Rejecting subscription {}: no space left", id);
+                        driver.reject(IMStatusCode::ResourceExhausted).await?;
+
+                        return Ok(false);
                     }
 
-                    driver.complete(req).await?;
+                    let primed = async {
+                        self.report_chunks(matter, &metadata.node(), req, driver, true, true)
+                            .await?;
+
+                        driver.complete(req).await
+                    }
+                    .await;
+
+                    if matches!(primed, Ok(true)) {
+                        matter
+                            .subscriptions
+                            .borrow_mut()
+                            .activate(id, (matter.epoch)());
+                        matter.subscriptions_notification.signal(());
+                    } else {
+                        matter.subscriptions.borrow_mut().remove(id);
+                    }
+
+                    primed?;
                 }
             }
         }
 
-        Ok(())
+        Ok(more_chunks)
+    }
+
+    /// Whether the action on an expanded path of a request applies: for group-addressed
+    /// requests - with `group` being the fabric index and the ID of the group - only the
+    /// endpoints which are members of the group are acted upon, and as nothing is responded
+    /// to such requests, the paths which failed to expand are skipped
+    fn is_group_target<E>(
+        matter: &Matter<'_>,
+        group: Option<(u8, u16)>,
+        endpoint_id: Result<EndptId, E>,
+    ) -> bool {
+        match group {
+            Some((fab_idx, group_id)) => endpoint_id.is_ok_and(|endpoint_id| {
+                matter
+                    .group_key_mgr
+                    .borrow()
+                    .is_member(fab_idx, group_id, endpoint_id)
+            }),
+            None => true,
+        }
+    }
+
+    /// Runs the reporting engine of the subscriptions (see
+    /// [`crate::interaction_model::subscriptions`]): waits until the report of a subscription is
+    /// due, and sends it to the subscriber, over a new exchange.
+    ///
+    /// The buffers are the same as the ones of an exchange handler, see
+    /// [`crate::Matter::reporter`].
+    pub async fn report(
+        &self,
+        matter: &Matter<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        sx_buf: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        loop {
+            let now = (matter.epoch)();
+
+            let due = matter.subscriptions.borrow().due(now);
+
+            if let Some(id) = due {
+                let result = self
+                    .report_subscription(matter, id, tx_buf, rx_buf, sx_buf)
+                    .await;
+
+                let terminated = match result {
+                    Ok(true) => false,
+                    Ok(false) => {
+                        info!("Subscription {}: rejected by the subscriber", id);
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Subscription {}: report failed: {:?}", id, e);
+                        true
+                    }
+                };
+
+                if terminated {
+                    matter.subscriptions.borrow_mut().remove(id);
+                } else {
+                    matter
+                        .subscriptions
+                        .borrow_mut()
+                        .reported(id, (matter.epoch)());
+                }
+            } else {
+                let deadline = matter.subscriptions.borrow().deadline();
+
+                if let Some(deadline) = deadline {
+                    let timeout = deadline.saturating_sub(now);
+
+                    select(
+                        matter.subscriptions_notification.wait(),
+                        Timer::after(Duration::from_micros(timeout.as_micros() as _)),
+                    )
+                    .await;
+                } else {
+                    matter.subscriptions_notification.wait().await;
+                }
+            }
+        }
+    }
+
+    /// Sends a report of the subscription with ID `id`: of its subscribed attribute paths on the
+    /// clusters whose data version changed since the last report, and of the events emitted on
+    /// its event paths since then; or an empty one if there is nothing to report.
+    /// Returns `false` if the subscriber rejected the report.
+    async fn report_subscription(
+        &self,
+        matter: &Matter<'_>,
+        id: u32,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        sx_buf: &mut [u8],
+    ) -> Result<bool, Error>
+    where
+        T: DataModelHandler,
+    {
+        // The request is copied, so that the subscriptions are not borrowed while reporting
+        let (fab_idx, peer_node_id, len, changed, events_from) = {
+            let mut subscriptions = matter.subscriptions.borrow_mut();
+
+            let subscription = subscriptions.get(id).ok_or(ErrorCode::NotFound)?;
+            let (fab_idx, peer_node_id) = (subscription.fab_idx, subscription.peer_node_id);
+            let events_from = subscription.events_from();
+
+            let req = subscription.req();
+            rx_buf
+                .get_mut(..req.len())
+                .ok_or(ErrorCode::NoSpace)?
+                .copy_from_slice(req);
+            let len = req.len();
+
+            (
+                fab_idx,
+                peer_node_id,
+                len,
+                subscriptions.take_changed(id),
+                events_from,
+            )
+        };
+
+        let session_id = matter
+            .session_mgr()
+            .borrow()
+            .case_session(fab_idx, peer_node_id)
+            .ok_or(ErrorCode::NoSession)?;
+
+        let mut exchange = matter.initiate(session_id)?;
+
+        // Unless the session supports Large Payloads, the reports need to fit in the MTU
+        let max_tx_size = exchange.with_session(|sess| Ok(sess.max_tx_size()))?;
+        let tx_len = core::cmp::min(tx_buf.len(), max_tx_size);
+
+        let mut tx = Packet::new_tx(&mut tx_buf[..tx_len]);
+        let mut rx = Packet::new_rx(sx_buf);
+
+        let req = SubscribeReq::from_tlv(&get_root_node_struct(&rx_buf[..len])?)?;
+
+        // Events the subscriber already had when it subscribed are not reported either
+        let events_from = events_from.max(req.event_min());
+
+        let events_pending = req
+            .event_requests
+            .as_ref()
+            .map(|paths| {
+                matter.events.borrow().iter().any(|event| {
+                    event.number >= events_from && paths.iter().any(|path| event.matches(&path))
+                })
+            })
+            .unwrap_or(false);
+
+        let mut driver = ReportDriver::new(&mut exchange, id, &mut tx, &mut rx);
+
+        if !changed && !events_pending {
+            driver.keep_alive().await?;
+
+            return Ok(true);
+        }
+
+        driver.start(&req)?;
+
+        let metadata = self.0.lock().await;
+
+        if !self
+            .report_chunks(matter, &metadata.node(), &req, &mut driver, changed, false)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        driver.complete(&req).await
+    }
+
+    /// Encodes the ReportData chunks of a subscription with `driver`, sending all of them but
+    /// the last one, which is left to the caller to complete.
+    ///
+    /// Used for the priming report of the subscription as well as for its subsequent reports:
+    /// the data of its attribute paths is reported if `attrs` - leaving out the clusters whose
+    /// data version did not change since they were last reported - followed by its events not
+    /// reported yet, and by the statuses of its event paths if `priming`.
+    ///
+    /// Returns `false` if the subscriber did not accept a chunk.
+    async fn report_chunks<'a, 'p, D>(
+        &self,
+        matter: &Matter<'_>,
+        node: &Node<'_>,
+        req: &SubscribeReq<'_>,
+        driver: &mut D,
+        attrs: bool,
+        priming: bool,
+    ) -> Result<bool, Error>
+    where
+        T: DataModelHandler,
+        D: ReportChunks<'a, 'p>,
+    {
+        let id = driver.subscription_id();
+
+        // The data versions last reported are copied, as they are updated by the report itself
+        let (datavers, events_from) = {
+            let subscriptions = matter.subscriptions.borrow();
+
+            let subscription = subscriptions.get(id).ok_or(ErrorCode::NotFound)?;
+            let datavers: heapless::Vec<_, MAX_SUBSCRIPTION_DATAVERS> =
+                heapless::Vec::from_slice(subscription.datavers()).unwrap();
+
+            // Events the subscriber already had when it subscribed are not reported either
+            (datavers, subscription.events_from().max(req.event_min()))
+        };
+
+        let accessor = driver.accessor()?;
+
+        if attrs {
+            // The cluster of the last attribute its handler was too busy to read
+            let mut busy_cluster = None;
+            let compression = PathCompression::new(matter.report_tag_compression.get());
+
+            for item in node.subscribing_read(req, None, &accessor) {
+                // The clusters which did not change since they were last reported are left out
+                let item = item.map(|mut attr| {
+                    let reported = datavers.iter().find_map(|(ep, cl, dataver)| {
+                        (*ep == attr.endpoint_id && *cl == attr.cluster_id).then_some(*dataver)
+                    });
+
+                    attr.dataver = reported.or(attr.dataver);
+                    attr
+                });
+
+                let dataver = Cell::new(None);
+                let busy = Cell::new(false);
+
+                while !AttrDataEncoder::handle_read_dataver(
+                    &item,
+                    &self.0,
+                    &mut driver.writer()?,
+                    &compression,
+                    matter,
+                    &dataver,
+                    &busy,
+                )
+                .await?
+                {
+                    if !driver.send_chunk(req).await? {
+                        return Ok(false);
+                    }
+                }
+
+                let Ok(attr) = &item else {
+                    continue;
+                };
+
+                let cluster = (attr.endpoint_id, attr.cluster_id);
+
+                if busy.get() {
+                    // The cluster is reported again - in full - on the next report
+                    matter
+                        .subscriptions
+                        .borrow_mut()
+                        .busy(id, attr.endpoint_id, attr.cluster_id);
+                    busy_cluster = Some(cluster);
+                } else if let Some(dataver) = dataver.get() {
+                    if busy_cluster != Some(cluster) {
+                        matter.subscriptions.borrow_mut().dataver_reported(
+                            id,
+                            attr.endpoint_id,
+                            attr.cluster_id,
+                            dataver,
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(paths) = &req.event_requests {
+            if !driver.start_events(req).await? {
+                return Ok(false);
+            }
+
+            if priming {
+                for status in node.event_statuses(paths) {
+                    while !events::encode_status(&status, &mut driver.writer()?)? {
+                        if !driver.send_chunk(req).await? {
+                            return Ok(false);
+                        }
+                    }
+                }
+            }
+
+            let mut from = events_from;
+
+            loop {
+                let next = matter.events.borrow().encode_next(
+                    from,
+                    paths,
+                    node,
+                    &accessor,
+                    &mut driver.writer()?,
+                )?;
+
+                match next {
+                    Some((number, true)) => {
+                        matter.subscriptions.borrow_mut().event_reported(id, number);
+                        from = number + 1;
+                    }
+                    Some((_, false)) => {
+                        if !driver.send_chunk(req).await? {
+                            return Ok(false);
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        Ok(true)
     }
 }
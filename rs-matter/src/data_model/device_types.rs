@@ -15,19 +15,355 @@
  *    limitations under the License.
  */
 
-use super::objects::DeviceType;
+//! The standard device types, along with the server clusters their endpoints need to have.
+//!
+//! The clusters of an endpoint can be checked against its device type at compile time - e.g.
+//! with `const _: () = check_endpoint(&ENDPOINT);` - so that a node does not declare a device
+//! type it does not implement.
+
+use super::objects::{Cluster, ClusterId, DeviceType, Endpoint, Node};
+use super::{
+    cluster_basic_information, cluster_bridged_device_basic_information, cluster_on_off,
+    sdm::{
+        admin_commissioning, general_commissioning, general_diagnostics, group_key_management, noc,
+    },
+    system_model::{access_control, descriptor},
+};
+
+const IDENTIFY: ClusterId = 0x0003;
+const GROUPS: ClusterId = 0x0004;
+const SCENES: ClusterId = 0x0005;
+const LEVEL_CONTROL: ClusterId = 0x0008;
+const BOOLEAN_STATE: ClusterId = 0x0045;
+const DOOR_LOCK: ClusterId = 0x0101;
+const WINDOW_COVERING: ClusterId = 0x0102;
+const THERMOSTAT: ClusterId = 0x0201;
+const FAN_CONTROL: ClusterId = 0x0202;
+const COLOR_CONTROL: ClusterId = 0x0300;
+const ILLUMINANCE_MEASUREMENT: ClusterId = 0x0400;
+const TEMPERATURE_MEASUREMENT: ClusterId = 0x0402;
+const RELATIVE_HUMIDITY_MEASUREMENT: ClusterId = 0x0405;
+const OCCUPANCY_SENSING: ClusterId = 0x0406;
 
 pub const DEV_TYPE_ROOT_NODE: DeviceType = DeviceType {
     dtype: 0x0016,
     drev: 1,
 };
 
+pub const DEV_TYPE_AGGREGATOR: DeviceType = DeviceType {
+    dtype: 0x000E,
+    drev: 1,
+};
+
+pub const DEV_TYPE_BRIDGED_NODE: DeviceType = DeviceType {
+    dtype: 0x0013,
+    drev: 1,
+};
+
 pub const DEV_TYPE_ON_OFF_LIGHT: DeviceType = DeviceType {
     dtype: 0x0100,
     drev: 2,
 };
 
+pub const DEV_TYPE_DIMMABLE_LIGHT: DeviceType = DeviceType {
+    dtype: 0x0101,
+    drev: 2,
+};
+
+pub const DEV_TYPE_COLOR_TEMPERATURE_LIGHT: DeviceType = DeviceType {
+    dtype: 0x010C,
+    drev: 2,
+};
+
+pub const DEV_TYPE_EXTENDED_COLOR_LIGHT: DeviceType = DeviceType {
+    dtype: 0x010D,
+    drev: 2,
+};
+
+pub const DEV_TYPE_ON_OFF_PLUG_IN_UNIT: DeviceType = DeviceType {
+    dtype: 0x010A,
+    drev: 2,
+};
+
+pub const DEV_TYPE_DIMMABLE_PLUG_IN_UNIT: DeviceType = DeviceType {
+    dtype: 0x010B,
+    drev: 2,
+};
+
+pub const DEV_TYPE_ON_OFF_LIGHT_SWITCH: DeviceType = DeviceType {
+    dtype: 0x0103,
+    drev: 2,
+};
+
+pub const DEV_TYPE_CONTACT_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0015,
+    drev: 1,
+};
+
+pub const DEV_TYPE_LIGHT_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0106,
+    drev: 2,
+};
+
+pub const DEV_TYPE_OCCUPANCY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0107,
+    drev: 2,
+};
+
+pub const DEV_TYPE_TEMPERATURE_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0302,
+    drev: 2,
+};
+
+pub const DEV_TYPE_HUMIDITY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0307,
+    drev: 2,
+};
+
+pub const DEV_TYPE_THERMOSTAT: DeviceType = DeviceType {
+    dtype: 0x0301,
+    drev: 2,
+};
+
+pub const DEV_TYPE_DOOR_LOCK: DeviceType = DeviceType {
+    dtype: 0x000A,
+    drev: 2,
+};
+
+pub const DEV_TYPE_WINDOW_COVERING: DeviceType = DeviceType {
+    dtype: 0x0202,
+    drev: 2,
+};
+
+pub const DEV_TYPE_FAN: DeviceType = DeviceType {
+    dtype: 0x002B,
+    drev: 1,
+};
+
 pub const DEV_TYPE_ON_SMART_SPEAKER: DeviceType = DeviceType {
     dtype: 0x0022,
     drev: 2,
 };
+
+/// A standard device type, along with the server clusters its endpoints need to have - other
+/// than the Descriptor cluster, which all endpoints need to have
+#[derive(Debug, Clone)]
+pub struct DeviceTypeDefinition {
+    pub device_type: DeviceType,
+    pub required_clusters: &'static [ClusterId],
+}
+
+impl DeviceTypeDefinition {
+    const fn new(device_type: DeviceType, required_clusters: &'static [ClusterId]) -> Self {
+        Self {
+            device_type,
+            required_clusters,
+        }
+    }
+}
+
+pub const DEVICE_TYPES: &[DeviceTypeDefinition] = &[
+    DeviceTypeDefinition::new(
+        DEV_TYPE_ROOT_NODE,
+        &[
+            cluster_basic_information::ID,
+            access_control::ID,
+            group_key_management::ID,
+            general_commissioning::ID,
+            admin_commissioning::ID,
+            noc::ID,
+            general_diagnostics::ID,
+        ],
+    ),
+    DeviceTypeDefinition::new(DEV_TYPE_AGGREGATOR, &[]),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_BRIDGED_NODE,
+        &[cluster_bridged_device_basic_information::ID],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_ON_OFF_LIGHT,
+        &[IDENTIFY, GROUPS, SCENES, cluster_on_off::ID],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_DIMMABLE_LIGHT,
+        &[IDENTIFY, GROUPS, SCENES, cluster_on_off::ID, LEVEL_CONTROL],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_COLOR_TEMPERATURE_LIGHT,
+        &[
+            IDENTIFY,
+            GROUPS,
+            SCENES,
+            cluster_on_off::ID,
+            LEVEL_CONTROL,
+            COLOR_CONTROL,
+        ],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_EXTENDED_COLOR_LIGHT,
+        &[
+            IDENTIFY,
+            GROUPS,
+            SCENES,
+            cluster_on_off::ID,
+            LEVEL_CONTROL,
+            COLOR_CONTROL,
+        ],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_ON_OFF_PLUG_IN_UNIT,
+        &[IDENTIFY, GROUPS, SCENES, cluster_on_off::ID],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_DIMMABLE_PLUG_IN_UNIT,
+        &[IDENTIFY, GROUPS, SCENES, cluster_on_off::ID, LEVEL_CONTROL],
+    ),
+    DeviceTypeDefinition::new(DEV_TYPE_ON_OFF_LIGHT_SWITCH, &[IDENTIFY]),
+    DeviceTypeDefinition::new(DEV_TYPE_CONTACT_SENSOR, &[IDENTIFY, BOOLEAN_STATE]),
+    DeviceTypeDefinition::new(DEV_TYPE_LIGHT_SENSOR, &[IDENTIFY, ILLUMINANCE_MEASUREMENT]),
+    DeviceTypeDefinition::new(DEV_TYPE_OCCUPANCY_SENSOR, &[IDENTIFY, OCCUPANCY_SENSING]),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_TEMPERATURE_SENSOR,
+        &[IDENTIFY, TEMPERATURE_MEASUREMENT],
+    ),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_HUMIDITY_SENSOR,
+        &[IDENTIFY, RELATIVE_HUMIDITY_MEASUREMENT],
+    ),
+    DeviceTypeDefinition::new(DEV_TYPE_THERMOSTAT, &[IDENTIFY, THERMOSTAT]),
+    DeviceTypeDefinition::new(DEV_TYPE_DOOR_LOCK, &[IDENTIFY, DOOR_LOCK]),
+    DeviceTypeDefinition::new(DEV_TYPE_WINDOW_COVERING, &[IDENTIFY, WINDOW_COVERING]),
+    DeviceTypeDefinition::new(DEV_TYPE_FAN, &[IDENTIFY, GROUPS, FAN_CONTROL]),
+    DeviceTypeDefinition::new(
+        DEV_TYPE_ON_SMART_SPEAKER,
+        &[cluster_on_off::ID, LEVEL_CONTROL],
+    ),
+];
+
+/// The definition of the standard device type `device_type`, of any revision
+pub const fn definition(device_type: DeviceType) -> Option<&'static DeviceTypeDefinition> {
+    let mut index = 0;
+
+    while index < DEVICE_TYPES.len() {
+        if DEVICE_TYPES[index].device_type.dtype == device_type.dtype {
+            return Some(&DEVICE_TYPES[index]);
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+/// The first server cluster the device type of `endpoint` requires and the endpoint does not
+/// have, if any. The endpoints of the device types which are not standard only need to have
+/// the Descriptor cluster.
+pub const fn missing_cluster(endpoint: &Endpoint) -> Option<ClusterId> {
+    if !has_cluster(endpoint.clusters, descriptor::ID) {
+        return Some(descriptor::ID);
+    }
+
+    if let Some(definition) = definition(endpoint.device_type) {
+        let mut index = 0;
+
+        while index < definition.required_clusters.len() {
+            let cluster = definition.required_clusters[index];
+
+            if !has_cluster(endpoint.clusters, cluster) {
+                return Some(cluster);
+            }
+
+            index += 1;
+        }
+    }
+
+    None
+}
+
+/// Fails - at compile time, when evaluated in a const context - if the endpoint misses a
+/// server cluster its device type requires (see [`missing_cluster`])
+pub const fn check_endpoint(endpoint: &Endpoint) {
+    if missing_cluster(endpoint).is_some() {
+        panic!("The endpoint misses a cluster its device type requires");
+    }
+}
+
+/// Checks all the endpoints of the node, as [`check_endpoint`] does
+pub const fn check_node(node: &Node) {
+    let mut index = 0;
+
+    while index < node.endpoints.len() {
+        check_endpoint(&node.endpoints[index]);
+
+        index += 1;
+    }
+}
+
+const fn has_cluster(clusters: &[Cluster], id: ClusterId) -> bool {
+    let mut index = 0;
+
+    while index < clusters.len() {
+        if clusters[index].id == id {
+            return true;
+        }
+
+        index += 1;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::cluster_on_off;
+    use crate::data_model::objects::{DeviceType, Endpoint};
+    use crate::data_model::root_endpoint;
+    use crate::data_model::system_model::descriptor;
+
+    use super::{
+        check_endpoint, definition, missing_cluster, DEV_TYPE_AGGREGATOR, DEV_TYPE_ON_OFF_LIGHT,
+        DEV_TYPE_ROOT_NODE, IDENTIFY,
+    };
+
+    const AGGREGATOR: Endpoint<'static> = Endpoint {
+        id: 1,
+        device_type: DEV_TYPE_AGGREGATOR,
+        parent: None,
+        clusters: &[descriptor::CLUSTER],
+        client_clusters: &[],
+        tags: &[],
+    };
+
+    // Checked at compile time
+    const _: () = check_endpoint(&AGGREGATOR);
+
+    #[test]
+    fn test_missing_cluster() {
+        assert_eq!(missing_cluster(&root_endpoint::endpoint(0)), None);
+        assert_eq!(missing_cluster(&AGGREGATOR), None);
+
+        let light = Endpoint {
+            id: 2,
+            device_type: DEV_TYPE_ON_OFF_LIGHT,
+            parent: None,
+            clusters: &[cluster_on_off::CLUSTER],
+            client_clusters: &[],
+            tags: &[],
+        };
+        assert_eq!(missing_cluster(&light), Some(descriptor::ID));
+
+        let light = Endpoint {
+            clusters: &[descriptor::CLUSTER, cluster_on_off::CLUSTER],
+            ..light
+        };
+        assert_eq!(missing_cluster(&light), Some(IDENTIFY));
+
+        let root = definition(DEV_TYPE_ROOT_NODE).unwrap();
+        assert_eq!(root.device_type.dtype, DEV_TYPE_ROOT_NODE.dtype);
+        let vendor_specific = DeviceType {
+            dtype: 0xFFF1,
+            drev: 1,
+        };
+        assert!(definition(vendor_specific).is_none());
+    }
+}
@@ -18,10 +18,16 @@
 pub mod core;
 pub mod device_types;
 pub mod objects;
+pub mod scenes;
+pub mod semantic_tags;
 
 pub mod cluster_basic_information;
+pub mod cluster_binding;
+pub mod cluster_bridged_device_basic_information;
 // TODO pub mod cluster_media_playback;
+pub mod cluster_mode_base;
 pub mod cluster_on_off;
+pub mod cluster_operational_state;
 pub mod cluster_template;
 pub mod root_endpoint;
 pub mod sdm;
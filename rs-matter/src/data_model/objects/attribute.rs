@@ -17,6 +17,8 @@
 #![allow(clippy::bad_bit_mask)]
 
 use crate::data_model::objects::GlobalElements;
+use crate::error::{Error, ErrorCode};
+use crate::tlv::TLVElement;
 
 use super::{AttrId, Privilege};
 use bitflags::bitflags;
@@ -45,8 +47,13 @@ bitflags! {
         const RA = Self::READ.bits() | Self::NEED_ADMIN.bits();
         const RWVA = Self::READ.bits() | Self::WRITE.bits() | Self::NEED_VIEW.bits() | Self::NEED_ADMIN.bits();
         const RWFA = Self::READ.bits() | Self::WRITE.bits() | Self::FAB_SCOPED.bits() | Self::NEED_ADMIN.bits();
+        const RWVO = Self::READ.bits() | Self::WRITE.bits() | Self::NEED_VIEW.bits() | Self::NEED_OPERATE.bits();
         const RWVM = Self::READ.bits() | Self::WRITE.bits() | Self::NEED_VIEW.bits() | Self::NEED_MANAGE.bits();
         const RWFVM = Self::READ.bits() | Self::WRITE.bits() | Self::FAB_SCOPED.bits() |Self::NEED_VIEW.bits() | Self::NEED_MANAGE.bits();
+        // Commands are invoked with the WRITE operation
+        const WO = Self::WRITE.bits() | Self::NEED_OPERATE.bits();
+        const WM = Self::WRITE.bits() | Self::NEED_MANAGE.bits();
+        const WA = Self::WRITE.bits() | Self::NEED_ADMIN.bits();
     }
 }
 
@@ -84,6 +91,7 @@ bitflags! {
         const NULLABLE = 0x08;   // Short: X
 
         const SN = Self::SCENE.bits() | Self::PERSISTENT.bits();
+        const NX = Self::PERSISTENT.bits() | Self::NULLABLE.bits();
         const S = Self::SCENE.bits();
         const N = Self::PERSISTENT.bits();
         const F = Self::FIXED.bits();
@@ -91,11 +99,83 @@ bitflags! {
     }
 }
 
+/// The constraint on the values of an attribute. The written values are checked against it
+/// before they are passed on to the handler of the cluster (see [`Attribute::check_value`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    None,
+    /// An integer within the range, inclusive
+    Range(i64, i64),
+    /// A string - of characters or octets - or a list, whose length in bytes - or in entries -
+    /// is within the range, inclusive
+    Length(u16, u16),
+}
+
+impl Constraint {
+    /// Checks `data` against the constraint: a value outside of it is a `ConstraintError`,
+    /// and a value of a type the constraint does not apply to an `InvalidDataType`
+    pub fn check(&self, data: &TLVElement) -> Result<(), Error> {
+        let ok = match *self {
+            Self::None => true,
+            Self::Range(min, max) => {
+                let value = data
+                    .u64()
+                    .map(i128::from)
+                    .or_else(|_| data.i64().map(i128::from))
+                    .map_err(|_| ErrorCode::InvalidDataType)?;
+
+                (min as i128..=max as i128).contains(&value)
+            }
+            Self::Length(min, max) => {
+                let len = if let Ok(slice) = data.slice() {
+                    slice.len()
+                } else if data.confirm_array().is_ok() || data.confirm_list().is_ok() {
+                    data.enter().map(|entries| entries.count()).unwrap_or(0)
+                } else {
+                    Err(ErrorCode::InvalidDataType)?
+                };
+
+                (min as usize..=max as usize).contains(&len)
+            }
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(ErrorCode::ConstraintError.into())
+        }
+    }
+}
+
+/// Which features of its cluster an attribute or a command depends on. The elements whose
+/// conformance the feature map of their cluster does not satisfy are hidden: they are not
+/// reported in the attribute and command lists and cannot be accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conformance {
+    Mandatory,
+    /// Supported when any of the features - the bits of the feature map - is
+    AnyOf(u32),
+    /// Supported unless any of the features is
+    NoneOf(u32),
+}
+
+impl Conformance {
+    pub const fn is_supported(&self, feature_map: u32) -> bool {
+        match *self {
+            Self::Mandatory => true,
+            Self::AnyOf(features) => feature_map & features != 0,
+            Self::NoneOf(features) => feature_map & features == 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Attribute {
     pub id: AttrId,
     pub quality: Quality,
     pub access: Access,
+    pub constraint: Constraint,
+    pub conformance: Conformance,
 }
 
 impl Attribute {
@@ -104,15 +184,56 @@ impl Attribute {
             id,
             access,
             quality,
+            constraint: Constraint::None,
+            conformance: Conformance::Mandatory,
+        }
+    }
+
+    /// Constrains the values written to the attribute
+    pub const fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Makes the attribute depend on features of its cluster, e.g.
+    /// `Conformance::AnyOf(Feature::LIGHTING.bits())`
+    pub const fn with_conformance(mut self, conformance: Conformance) -> Self {
+        self.conformance = conformance;
+        self
+    }
+
+    /// Whether the attribute may be null, i.e. is of type [`crate::tlv::Nullable`]
+    pub fn is_nullable(&self) -> bool {
+        self.quality.contains(Quality::NULLABLE)
+    }
+
+    /// Checks a value written to the attribute against its constraint. Null always passes
+    /// for a nullable attribute and is a `ConstraintError` for any other.
+    pub fn check_value(&self, data: &TLVElement) -> Result<(), Error> {
+        if data.null().is_ok() {
+            if self.is_nullable() {
+                Ok(())
+            } else {
+                Err(ErrorCode::ConstraintError.into())
+            }
+        } else {
+            self.constraint.check(data)
         }
     }
 
+    /// Whether the values written to the attribute are persisted (see
+    /// [`crate::nv_attributes`]). The values of the fabric-scoped attributes are not, as
+    /// they are persisted by their clusters, along with the fabrics.
+    pub fn is_persistent(&self) -> bool {
+        self.quality.contains(Quality::PERSISTENT) && !self.access.contains(Access::FAB_SCOPED)
+    }
+
     pub fn is_system(&self) -> bool {
         Self::is_system_attr(self.id)
     }
 
     pub fn is_system_attr(attr_id: AttrId) -> bool {
-        attr_id >= (GlobalElements::ServerGenCmd as AttrId)
+        attr_id >= (GlobalElements::GeneratedCommandList as AttrId)
     }
 }
 
@@ -125,8 +246,12 @@ impl core::fmt::Display for Attribute {
 #[cfg(test)]
 #[allow(clippy::bool_assert_comparison)]
 mod tests {
-    use super::Access;
+    use super::{Access, Attribute, Conformance, Constraint, Quality};
+    use crate::data_model::cluster_on_off::{self, CommandsDiscriminants, Feature};
     use crate::data_model::objects::Privilege;
+    use crate::error::ErrorCode;
+    use crate::tlv::{TLVList, TLVWriter, TagType};
+    use crate::utils::writebuf::WriteBuf;
 
     #[test]
     fn test_read() {
@@ -192,4 +317,104 @@ mod tests {
         assert_eq!(c.is_ok(Access::WRITE, Privilege::MANAGE), true);
         assert_eq!(c.is_ok(Access::WRITE, Privilege::ADMIN), true);
     }
+
+    fn check(attr: &Attribute, encode: impl FnOnce(&mut TLVWriter)) -> Result<(), ErrorCode> {
+        let mut buf = [0; 32];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        encode(&mut tw);
+
+        let len = tw.get_tail();
+        let data = TLVList::new(&buf[..len]).iter().next().unwrap();
+
+        attr.check_value(&data).map_err(|e| e.code())
+    }
+
+    #[test]
+    fn test_constraints() {
+        let range = Attribute::new(0, Access::RWVM, Quality::NONE)
+            .with_constraint(Constraint::Range(-1, 254));
+
+        assert_eq!(
+            check(&range, |tw| tw.u8(TagType::Anonymous, 254).unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            check(&range, |tw| tw.i8(TagType::Anonymous, -1).unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            check(&range, |tw| tw.u16(TagType::Anonymous, 255).unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            check(&range, |tw| tw.i8(TagType::Anonymous, -2).unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            check(&range, |tw| tw.u64(TagType::Anonymous, u64::MAX).unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            check(&range, |tw| tw.bool(TagType::Anonymous, true).unwrap()),
+            Err(ErrorCode::InvalidDataType)
+        );
+        // Null is only allowed for nullable attributes
+        assert_eq!(
+            check(&range, |tw| tw.null(TagType::Anonymous).unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+
+        let nullable =
+            Attribute::new(0, Access::RWVM, Quality::X).with_constraint(Constraint::Range(0, 1));
+        assert_eq!(
+            check(&nullable, |tw| tw.null(TagType::Anonymous).unwrap()),
+            Ok(())
+        );
+
+        let length = Attribute::new(0, Access::RWVM, Quality::NONE)
+            .with_constraint(Constraint::Length(1, 4));
+
+        assert_eq!(
+            check(&length, |tw| tw.utf8(TagType::Anonymous, b"abcd").unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            check(&length, |tw| tw.utf8(TagType::Anonymous, b"abcde").unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            check(&length, |tw| tw.str8(TagType::Anonymous, b"").unwrap()),
+            Err(ErrorCode::ConstraintError)
+        );
+        assert_eq!(
+            check(&length, |tw| {
+                tw.start_array(TagType::Anonymous).unwrap();
+                tw.u8(TagType::Anonymous, 1).unwrap();
+                tw.u8(TagType::Anonymous, 2).unwrap();
+                tw.end_container().unwrap();
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_conformance() {
+        let lighting = Conformance::AnyOf(Feature::LIGHTING.bits());
+        let not_off_only = Conformance::NoneOf(Feature::OFF_ONLY.bits());
+
+        assert!(Conformance::Mandatory.is_supported(0));
+        assert!(!lighting.is_supported(0));
+        assert!(lighting.is_supported((Feature::LIGHTING | Feature::OFF_ONLY).bits()));
+        assert!(not_off_only.is_supported(Feature::LIGHTING.bits()));
+        assert!(!not_off_only.is_supported(Feature::OFF_ONLY.bits()));
+
+        // The On and Toggle commands of an OnOff cluster are hidden with the OffOnly feature
+        let cluster = cluster_on_off::CLUSTER.with_features(Feature::OFF_ONLY.bits());
+        let mut commands = cluster.supported_commands().map(|command| command.id);
+        assert_eq!(commands.next(), Some(CommandsDiscriminants::Off as u32));
+        assert_eq!(commands.next(), None);
+        assert_eq!(cluster_on_off::CLUSTER.supported_commands().count(), 3);
+    }
 }
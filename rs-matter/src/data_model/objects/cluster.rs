@@ -25,6 +25,7 @@ use crate::{
     error::{Error, ErrorCode},
     interaction_model::{
         core::IMStatusCode,
+        events::EventId,
         messages::{
             ib::{AttrPath, AttrStatus, CmdPath, CmdStatus},
             GenericPath,
@@ -41,9 +42,9 @@ pub enum GlobalElements {
     _ClusterRevision = 0xFFFD,
     FeatureMap = 0xFFFC,
     AttributeList = 0xFFFB,
-    _EventList = 0xFFFA,
-    _ClientGenCmd = 0xFFF9,
-    ServerGenCmd = 0xFFF8,
+    EventList = 0xFFFA,
+    AcceptedCommandList = 0xFFF9,
+    GeneratedCommandList = 0xFFF8,
     FabricIndex = 0xFE,
 }
 
@@ -58,6 +59,21 @@ pub const ATTRIBUTE_LIST: Attribute = Attribute::new(
     Quality::NONE,
 );
 
+pub const EVENT_LIST: Attribute =
+    Attribute::new(GlobalElements::EventList as _, Access::RV, Quality::NONE);
+
+pub const ACCEPTED_COMMAND_LIST: Attribute = Attribute::new(
+    GlobalElements::AcceptedCommandList as _,
+    Access::RV,
+    Quality::NONE,
+);
+
+pub const GENERATED_COMMAND_LIST: Attribute = Attribute::new(
+    GlobalElements::GeneratedCommandList as _,
+    Access::RV,
+    Quality::NONE,
+);
+
 // TODO: What if we instead of creating this, we just pass the AttrData/AttrPath to the read/write
 // methods?
 /// The Attribute Details structure records the details about the attribute under consideration.
@@ -85,6 +101,41 @@ impl<'a> AttrDetails<'a> {
         Attribute::is_system_attr(self.attr_id)
     }
 
+    /// The metadata of the cluster of the attribute, as declared by its endpoint - i.e. with
+    /// the features the endpoint supports
+    pub fn cluster(&self) -> Result<&'a Cluster<'a>, Error> {
+        self.node
+            .endpoints
+            .iter()
+            .find(|endpoint| endpoint.id == self.endpoint_id)
+            .ok_or(ErrorCode::EndpointNotFound)?
+            .clusters
+            .iter()
+            .find(|cluster| cluster.id == self.cluster_id)
+            .ok_or(ErrorCode::ClusterNotFound.into())
+    }
+
+    /// The metadata of the attribute, if it is on the node
+    pub fn attribute(&self) -> Option<&'a Attribute> {
+        self.cluster()
+            .ok()?
+            .supported_attributes()
+            .find(|attribute| attribute.id == self.attr_id)
+    }
+
+    /// Whether the entry of a fabric-scoped list on fabric `fab_idx` is reported: with fabric
+    /// filtering, only the entries of the accessing fabric are
+    pub fn is_fabric_visible(&self, fab_idx: u8) -> bool {
+        !self.fab_filter || self.fab_idx == fab_idx
+    }
+
+    /// Whether the fabric-sensitive fields of the entry of a fabric-scoped list on fabric
+    /// `fab_idx` are reported: only to the accessing fabric. The entries of the other fabrics
+    /// only carry their fabric index (see [`encode_redacted`])
+    pub fn is_fabric_sensitive_visible(&self, fab_idx: u8) -> bool {
+        self.fab_idx == fab_idx
+    }
+
     pub fn path(&self) -> AttrPath {
         AttrPath {
             endpoint: Some(self.endpoint_id),
@@ -96,6 +147,25 @@ impl<'a> AttrDetails<'a> {
     }
 
     pub fn status(&self, status: IMStatusCode) -> Result<Option<AttrStatus>, Error> {
+        self.cluster_status(status, 0)
+    }
+
+    /// The status of a failed read or write of the attribute, with the cluster-specific
+    /// status of the error, if any
+    pub fn error_status(&self, error: &Error) -> Result<Option<AttrStatus>, Error> {
+        match error.code() {
+            ErrorCode::ClusterStatus(status) => {
+                self.cluster_status(IMStatusCode::Failure, status as _)
+            }
+            code => self.status(code.into()),
+        }
+    }
+
+    fn cluster_status(
+        &self,
+        status: IMStatusCode,
+        cluster_status: u16,
+    ) -> Result<Option<AttrStatus>, Error> {
         if self.should_report(status) {
             Ok(Some(AttrStatus::new(
                 &GenericPath {
@@ -104,7 +174,7 @@ impl<'a> AttrDetails<'a> {
                     leaf: Some(self.attr_id as _),
                 },
                 status,
-                0,
+                cluster_status,
             )))
         } else {
             Ok(None)
@@ -133,6 +203,8 @@ pub struct CmdDetails<'a> {
     pub endpoint_id: EndptId,
     pub cluster_id: ClusterId,
     pub cmd_id: CmdId,
+    /// The reference of the command in a batched InvokeRequest, echoed in its response
+    pub command_ref: Option<u16>,
     pub wildcard: bool,
 }
 
@@ -154,16 +226,34 @@ impl<'a> CmdDetails<'a> {
     }
 
     pub fn status(&self, status: IMStatusCode) -> Option<CmdStatus> {
+        self.cluster_status(status, 0)
+    }
+
+    /// The status of a failed invocation of the command, with the cluster-specific status
+    /// of the error, if any
+    pub fn error_status(&self, error: &Error) -> Option<CmdStatus> {
+        match error.code() {
+            ErrorCode::ClusterStatus(status) => {
+                self.cluster_status(IMStatusCode::Failure, status as _)
+            }
+            code => self.status(code.into()),
+        }
+    }
+
+    fn cluster_status(&self, status: IMStatusCode, cluster_status: u16) -> Option<CmdStatus> {
         if self.should_report(status) {
-            Some(CmdStatus::new(
-                CmdPath::new(
-                    Some(self.endpoint_id),
-                    Some(self.cluster_id),
-                    Some(self.cmd_id),
-                ),
-                status,
-                0,
-            ))
+            Some(
+                CmdStatus::new(
+                    CmdPath::new(
+                        Some(self.endpoint_id),
+                        Some(self.cluster_id),
+                        Some(self.cmd_id),
+                    ),
+                    status,
+                    cluster_status,
+                )
+                .set_command_ref(self.command_ref),
+            )
         } else {
             None
         }
@@ -189,7 +279,12 @@ pub struct Cluster<'a> {
     pub id: ClusterId,
     pub feature_map: u32,
     pub attributes: &'a [Attribute],
-    pub commands: &'a [CmdId],
+    /// The commands accepted by the cluster
+    pub commands: &'a [Command],
+    /// The commands the cluster generates in response to the accepted ones
+    pub generated_commands: &'a [CmdId],
+    /// The events the cluster emits
+    pub events: &'a [EventId],
 }
 
 impl<'a> Cluster<'a> {
@@ -197,30 +292,54 @@ impl<'a> Cluster<'a> {
         id: ClusterId,
         feature_map: u32,
         attributes: &'a [Attribute],
-        commands: &'a [CmdId],
+        commands: &'a [Command],
+        generated_commands: &'a [CmdId],
+        events: &'a [EventId],
     ) -> Self {
         Self {
             id,
             feature_map,
             attributes,
             commands,
+            generated_commands,
+            events,
         }
     }
 
+    /// The metadata of the cluster with the features `feature_map`, e.g.
+    /// `on_off::CLUSTER.with_features(Feature::LIGHTING.bits())`, so that a cluster
+    /// declares all its optional attributes and commands once, and each endpoint exposes
+    /// those of the features it supports
+    pub const fn with_features(mut self, feature_map: u32) -> Self {
+        self.feature_map = feature_map;
+        self
+    }
+
+    /// The attributes of the cluster, without those its features hide
+    pub fn supported_attributes(&self) -> impl Iterator<Item = &'_ Attribute> + '_ {
+        self.attributes
+            .iter()
+            .filter(|attribute| attribute.conformance.is_supported(self.feature_map))
+    }
+
+    /// The commands of the cluster, without those its features hide
+    pub fn supported_commands(&self) -> impl Iterator<Item = &'_ Command> + '_ {
+        self.commands
+            .iter()
+            .filter(|command| command.conformance.is_supported(self.feature_map))
+    }
+
     pub fn match_attributes(
         &self,
         attr: Option<AttrId>,
     ) -> impl Iterator<Item = &'_ Attribute> + '_ {
-        self.attributes
-            .iter()
+        self.supported_attributes()
             .filter(move |attribute| attr.map(|attr| attr == attribute.id).unwrap_or(true))
     }
 
-    pub fn match_commands(&self, cmd: Option<CmdId>) -> impl Iterator<Item = CmdId> + '_ {
-        self.commands
-            .iter()
-            .filter(move |id| cmd.map(|cmd| **id == cmd).unwrap_or(true))
-            .copied()
+    pub fn match_commands(&self, cmd: Option<CmdId>) -> impl Iterator<Item = &'_ Command> + '_ {
+        self.supported_commands()
+            .filter(move |command| cmd.map(|cmd| cmd == command.id).unwrap_or(true))
     }
 
     pub fn check_attribute(
@@ -231,8 +350,7 @@ impl<'a> Cluster<'a> {
         write: bool,
     ) -> Result<(), IMStatusCode> {
         let attribute = self
-            .attributes
-            .iter()
+            .supported_attributes()
             .find(|attribute| attribute.id == attr)
             .ok_or(IMStatusCode::UnsupportedAttribute)?;
 
@@ -249,15 +367,18 @@ impl<'a> Cluster<'a> {
         accessor: &Accessor,
         ep: EndptId,
         cmd: CmdId,
+        timed: bool,
     ) -> Result<(), IMStatusCode> {
-        self.commands
-            .iter()
-            .find(|id| **id == cmd)
+        let command = self
+            .supported_commands()
+            .find(|command| command.id == cmd)
             .ok_or(IMStatusCode::UnsupportedCommand)?;
 
         Self::check_cmd_access(
             accessor,
             GenericPath::new(Some(ep), Some(self.id), Some(cmd)),
+            command,
+            timed,
         )
     }
 
@@ -289,31 +410,51 @@ impl<'a> Cluster<'a> {
         }
     }
 
+    /// Checks that `command` can be invoked by `accessor`, in a timed interaction if `timed`
     pub(crate) fn check_cmd_access(
         accessor: &Accessor,
         path: GenericPath,
+        command: &Command,
+        timed: bool,
     ) -> Result<(), IMStatusCode> {
         let mut access_req = AccessReq::new(accessor, path, Access::WRITE);
 
-        access_req.set_target_perms(
-            Access::WRITE
-                .union(Access::NEED_OPERATE)
-                .union(Access::NEED_MANAGE)
-                .union(Access::NEED_ADMIN),
-        ); // TODO
-        if access_req.allow() {
-            Ok(())
-        } else {
+        access_req.set_target_perms(command.access);
+        if !access_req.allow() {
             Err(IMStatusCode::UnsupportedAccess)
+        } else if command.timed && !timed {
+            Err(IMStatusCode::NeedsTimedInteraction)
+        } else {
+            Ok(())
         }
     }
 
+    /// Reads a global attribute of the cluster, as generated from the metadata of the cluster,
+    /// so that the handlers of the clusters only need to deal with their own attributes
     pub fn read(&self, attr: AttrId, mut writer: AttrDataWriter) -> Result<(), Error> {
         match attr.try_into()? {
             GlobalElements::AttributeList => {
                 self.encode_attribute_ids(AttrDataWriter::TAG, &mut writer)?;
                 writer.complete()
             }
+            GlobalElements::AcceptedCommandList => {
+                let ids = self.supported_commands().map(|cmd| cmd.id);
+                Self::encode_ids(ids, AttrDataWriter::TAG, &mut writer)?;
+                writer.complete()
+            }
+            GlobalElements::GeneratedCommandList => {
+                let ids = self.generated_commands.iter().copied();
+                Self::encode_ids(ids, AttrDataWriter::TAG, &mut writer)?;
+                writer.complete()
+            }
+            GlobalElements::EventList => {
+                Self::encode_ids(
+                    self.events.iter().copied(),
+                    AttrDataWriter::TAG,
+                    &mut writer,
+                )?;
+                writer.complete()
+            }
             GlobalElements::FeatureMap => writer.set(self.feature_map),
             other => {
                 error!("This attribute is not yet handled {:?}", other);
@@ -324,12 +465,24 @@ impl<'a> Cluster<'a> {
 
     fn encode_attribute_ids(&self, tag: TagType, tw: &mut TLVWriter) -> Result<(), Error> {
         tw.start_array(tag)?;
-        for a in self.attributes {
+        for a in self.supported_attributes() {
             tw.u16(TagType::Anonymous, a.id)?;
         }
 
         tw.end_container()
     }
+
+    fn encode_ids<I>(ids: I, tag: TagType, tw: &mut TLVWriter) -> Result<(), Error>
+    where
+        I: Iterator<Item = u32>,
+    {
+        tw.start_array(tag)?;
+        for id in ids {
+            tw.u32(TagType::Anonymous, id)?;
+        }
+
+        tw.end_container()
+    }
 }
 
 impl<'a> core::fmt::Display for Cluster<'a> {
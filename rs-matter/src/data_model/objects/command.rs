@@ -0,0 +1,66 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::fmt;
+
+use super::{Access, CmdId, Conformance};
+
+/// A command accepted by a cluster
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub id: CmdId,
+    /// The privilege needed to invoke the command (see [`Access::WO`], [`Access::WM`]
+    /// and [`Access::WA`])
+    pub access: Access,
+    /// Whether the command can only be invoked in a timed interaction
+    pub timed: bool,
+    pub conformance: Conformance,
+}
+
+impl Command {
+    pub const fn new(id: CmdId, access: Access) -> Self {
+        Self {
+            id,
+            access,
+            timed: false,
+            conformance: Conformance::Mandatory,
+        }
+    }
+
+    /// A command which can only be invoked in a timed interaction, and is rejected with
+    /// `NeedsTimedInteraction` otherwise
+    pub const fn new_timed(id: CmdId, access: Access) -> Self {
+        Self {
+            id,
+            access,
+            timed: true,
+            conformance: Conformance::Mandatory,
+        }
+    }
+
+    /// Makes the command depend on features of its cluster
+    pub const fn with_conformance(mut self, conformance: Conformance) -> Self {
+        self.conformance = conformance;
+        self
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
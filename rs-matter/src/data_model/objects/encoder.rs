@@ -15,6 +15,7 @@
  *    limitations under the License.
  */
 
+use core::cell::Cell;
 use core::fmt::{Debug, Formatter};
 use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
@@ -25,20 +26,32 @@ use crate::interaction_model::messages::ib::{
 };
 use crate::tlv::UtfStr;
 use crate::transport::exchange::Exchange;
+use crate::Matter;
 use crate::{
     error::{Error, ErrorCode},
     interaction_model::messages::ib::{AttrDataTag, AttrRespTag},
     tlv::{FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
 };
-use log::error;
+use log::{error, warn};
 
-use super::{AttrDetails, CmdDetails, DataModelHandler};
+use super::{AttrDetails, Attribute, ClusterId, CmdDetails, DataModelHandler, EndptId};
 
 // TODO: Should this return an IMStatusCode Error? But if yes, the higher layer
 // may have already started encoding the 'success' headers, we might not want to manage
 // the tw.rewind() in that case, if we add this support
 pub type EncodeValueGen<'a> = &'a dyn Fn(TagType, &mut TLVWriter);
 
+/// The tag of the FabricIndex field of the fabric-scoped structs
+pub const FABRIC_INDEX_TAG: u8 = 0xFE;
+
+/// Encodes the entry of a fabric-scoped list on another fabric than the accessing one, with all
+/// its fabric-sensitive fields omitted: only its fabric index is reported
+pub fn encode_redacted(tw: &mut TLVWriter, tag: TagType, fab_idx: u8) -> Result<(), Error> {
+    tw.start_struct(tag)?;
+    tw.u8(TagType::Context(FABRIC_INDEX_TAG), fab_idx)?;
+    tw.end_container()
+}
+
 #[derive(Clone)]
 /// A structure for encoding various types of values
 pub enum EncodeValue<'a> {
@@ -117,35 +130,142 @@ impl<'a> FromTLV<'a> for EncodeValue<'a> {
     }
 }
 
+/// The tag compression of the attribute paths of a report message: with it enabled, the
+/// attribute data following one of the same cluster omits the endpoint and the cluster of its
+/// path, which the receiver takes from the preceding path (see [`AttrPath::expand`]).
+///
+/// Needs to be reset whenever a new message of the report is started.
+pub struct PathCompression {
+    enabled: bool,
+    last: Cell<Option<(EndptId, ClusterId)>>,
+}
+
+impl PathCompression {
+    pub const fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            last: Cell::new(None),
+        }
+    }
+
+    /// Forgets the path encoded last, so that the next one is encoded in full
+    pub fn reset(&self) {
+        self.last.set(None);
+    }
+
+    /// Returns the path to encode in place of `path`, which is encoded next
+    fn compress(&self, path: &AttrPath) -> AttrPath {
+        let prefix = path.endpoint.zip(path.cluster);
+
+        let mut path = path.clone();
+
+        if self.enabled && prefix.is_some() && self.last.get() == prefix {
+            path.tag_compression = Some(true);
+            path.endpoint = None;
+            path.cluster = None;
+        }
+
+        self.last.set(prefix);
+
+        path
+    }
+}
+
 pub struct AttrDataEncoder<'a, 'b, 'c> {
     dataver_filter: Option<u32>,
+    dataver: Option<&'a Cell<Option<u32>>>,
+    dataver_bump: u32,
+    compression: Option<&'a PathCompression>,
     path: AttrPath,
     tw: &'a mut TLVWriter<'b, 'c>,
 }
 
 impl<'a, 'b, 'c> AttrDataEncoder<'a, 'b, 'c> {
+    /// Reads the attribute of `item` - or reports its failure status - into the report being
+    /// encoded by `tw`, with the tag `compression` of the report message. Returns `false`
+    /// if there is no space left in the message.
     pub async fn handle_read<T: DataModelHandler>(
         item: &Result<AttrDetails<'_>, AttrStatus>,
         handler: &T,
         tw: &mut TLVWriter<'_, '_>,
+        compression: &PathCompression,
+        matter: &Matter<'_>,
+    ) -> Result<bool, Error> {
+        Self::read(item, handler, tw, compression, matter, None, None).await
+    }
+
+    /// As [`Self::handle_read`], but also records in `dataver` the data version of the cluster
+    /// of the attribute - as reported by its handler - even if the data itself is filtered out,
+    /// and in `busy` whether the handler was temporarily unable to read the attribute.
+    ///
+    /// The data version is only recorded if the attribute was read successfully.
+    pub async fn handle_read_dataver<T: DataModelHandler>(
+        item: &Result<AttrDetails<'_>, AttrStatus>,
+        handler: &T,
+        tw: &mut TLVWriter<'_, '_>,
+        compression: &PathCompression,
+        matter: &Matter<'_>,
+        dataver: &Cell<Option<u32>>,
+        busy: &Cell<bool>,
+    ) -> Result<bool, Error> {
+        Self::read(
+            item,
+            handler,
+            tw,
+            compression,
+            matter,
+            Some(dataver),
+            Some(busy),
+        )
+        .await
+    }
+
+    async fn read<T: DataModelHandler>(
+        item: &Result<AttrDetails<'_>, AttrStatus>,
+        handler: &T,
+        tw: &mut TLVWriter<'_, '_>,
+        compression: &PathCompression,
+        matter: &Matter<'_>,
+        dataver: Option<&Cell<Option<u32>>>,
+        busy: Option<&Cell<bool>>,
     ) -> Result<bool, Error> {
         let status = match item {
             Ok(attr) => {
-                let encoder = AttrDataEncoder::new(attr, tw);
+                let mut encoder = AttrDataEncoder::new(attr, tw);
+                encoder.dataver = dataver;
+                encoder.dataver_bump = matter.dataver_bump(attr.endpoint_id, attr.cluster_id);
+                encoder.compression = Some(compression);
 
                 let result = handler.read(attr, encoder).await;
                 match result {
                     Ok(()) => None,
                     Err(e) => {
+                        // Whatever the handler encoded is discarded, and the status - if any -
+                        // has its path in full
+                        compression.reset();
+
                         if e.code() == ErrorCode::NoSpace {
                             return Ok(false);
-                        } else {
-                            attr.status(e.into())?
                         }
+
+                        // Only this path fails - with a status - and the rest of the
+                        // interaction goes on
+                        if let Some(dataver) = dataver {
+                            dataver.set(None);
+                        }
+
+                        if let Some(busy) = busy {
+                            busy.set(e.code() == ErrorCode::Busy);
+                        }
+
+                        attr.error_status(&e)?
                     }
                 }
             }
-            Err(status) => Some(status.clone()),
+            Err(status) => {
+                compression.reset();
+                Some(status.clone())
+            }
         };
 
         if let Some(status) = status {
@@ -159,13 +279,49 @@ impl<'a, 'b, 'c> AttrDataEncoder<'a, 'b, 'c> {
         item: &Result<(AttrDetails<'_>, TLVElement<'_>), AttrStatus>,
         handler: &T,
         tw: &mut TLVWriter<'_, '_>,
+        matter: &Matter<'_>,
     ) -> Result<(), Error> {
         let status = match item {
             Ok((attr, data)) => {
-                let result = handler.write(attr, AttrData::new(attr.dataver, data)).await;
+                // Whole values are checked against the constraint of the attribute upfront,
+                // so that the handlers get only the values within it
+                let attribute = attr.attribute().filter(|_| attr.list_index.is_none());
+                let checked = match attribute {
+                    Some(attribute) => attribute.check_value(data),
+                    None => Ok(()),
+                };
+
+                // The handler knows nothing of the bumps of its data version, see `with_dataver`
+                let dataver_bump = matter.dataver_bump(attr.endpoint_id, attr.cluster_id);
+                let dataver = attr
+                    .dataver
+                    .map(|dataver| dataver.wrapping_sub(dataver_bump));
+
+                let result = match checked {
+                    Ok(()) => handler.write(attr, AttrData::new(dataver, data)).await,
+                    Err(error) => Err(error),
+                };
                 match result {
-                    Ok(()) => attr.status(IMStatusCode::Success)?,
-                    Err(error) => attr.status(error.into())?,
+                    Ok(()) => {
+                        if attribute.map(Attribute::is_persistent).unwrap_or(false) {
+                            // The write did happen, so a value which cannot be persisted is
+                            // only lost on reboot
+                            if let Err(e) = matter.nv_attributes.borrow_mut().set(
+                                attr.endpoint_id,
+                                attr.cluster_id,
+                                attr.attr_id,
+                                data,
+                            ) {
+                                warn!(
+                                    "Failed to persist attribute {}/{:#x}/{:#x}: {}",
+                                    attr.endpoint_id, attr.cluster_id, attr.attr_id, e
+                                );
+                            }
+                        }
+
+                        attr.status(IMStatusCode::Success)?
+                    }
+                    Err(error) => attr.error_status(&error)?,
                 }
             }
             Err(status) => Some(status.clone()),
@@ -181,12 +337,23 @@ impl<'a, 'b, 'c> AttrDataEncoder<'a, 'b, 'c> {
     pub fn new(attr: &AttrDetails, tw: &'a mut TLVWriter<'b, 'c>) -> Self {
         Self {
             dataver_filter: attr.dataver,
+            dataver: None,
+            dataver_bump: 0,
+            compression: None,
             path: attr.path(),
             tw,
         }
     }
 
     pub fn with_dataver(self, dataver: u32) -> Result<Option<AttrDataWriter<'a, 'b, 'c>>, Error> {
+        // The data version of the cluster as seen by the clients: the one of its handler, bumped
+        // with every change made outside of an interaction (see `Matter::attribute_changed`)
+        let dataver = dataver.wrapping_add(self.dataver_bump);
+
+        if let Some(reported) = self.dataver {
+            reported.set(Some(dataver));
+        }
+
         if self
             .dataver_filter
             .map(|dataver_filter| dataver_filter != dataver)
@@ -197,8 +364,15 @@ impl<'a, 'b, 'c> AttrDataEncoder<'a, 'b, 'c> {
             writer.start_struct(TagType::Anonymous)?;
             writer.start_struct(TagType::Context(AttrRespTag::Data as _))?;
             writer.u32(TagType::Context(AttrDataTag::DataVer as _), dataver)?;
-            self.path
-                .to_tlv(&mut writer, TagType::Context(AttrDataTag::Path as _))?;
+
+            if let Some(compression) = self.compression {
+                compression
+                    .compress(&self.path)
+                    .to_tlv(&mut writer, TagType::Context(AttrDataTag::Path as _))?;
+            } else {
+                self.path
+                    .to_tlv(&mut writer, TagType::Context(AttrDataTag::Path as _))?;
+            }
 
             Ok(Some(writer))
         } else {
@@ -231,6 +405,21 @@ impl<'a, 'b, 'c> AttrDataWriter<'a, 'b, 'c> {
         self.complete()
     }
 
+    /// Encodes the items as the list value of the attribute and completes the writer
+    pub fn set_list<I>(self, items: I) -> Result<(), Error>
+    where
+        I: IntoIterator,
+        I::Item: ToTLV,
+    {
+        self.tw.start_array(Self::TAG)?;
+        for item in items {
+            item.to_tlv(self.tw, TagType::Anonymous)?;
+        }
+        self.tw.end_container()?;
+
+        self.complete()
+    }
+
     pub fn complete(mut self) -> Result<(), Error> {
         self.tw.end_container()?;
         self.tw.end_container()?;
@@ -286,6 +475,16 @@ impl<'a> AttrData<'a> {
 
         Ok(self.data)
     }
+
+    /// Checks the data version of the write and decodes its value
+    ///
+    /// A value which does not decode as `T` is reported as `InvalidDataType`
+    pub fn get<T>(self, dataver: u32) -> Result<T, Error>
+    where
+        T: FromTLV<'a>,
+    {
+        T::from_tlv(self.with_dataver(dataver)?).map_err(|_| ErrorCode::InvalidDataType.into())
+    }
 }
 
 #[derive(Default)]
@@ -310,6 +509,7 @@ impl CmdDataTracker {
 pub struct CmdDataEncoder<'a, 'b, 'c> {
     tracker: &'a mut CmdDataTracker,
     path: CmdPath,
+    command_ref: Option<u16>,
     tw: &'a mut TLVWriter<'b, 'c>,
 }
 
@@ -330,7 +530,7 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
                     Ok(()) => cmd.success(&tracker),
                     Err(error) => {
                         error!("Error invoking command: {}", error);
-                        cmd.status(error.into())
+                        cmd.error_status(&error)
                     }
                 }
             }
@@ -355,12 +555,13 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
         Self {
             tracker,
             path: cmd.path(),
+            command_ref: cmd.command_ref,
             tw,
         }
     }
 
     pub fn with_command(mut self, cmd: u16) -> Result<CmdDataWriter<'a, 'b, 'c>, Error> {
-        let mut writer = CmdDataWriter::new(self.tracker, self.tw);
+        let mut writer = CmdDataWriter::new(self.tracker, self.command_ref, self.tw);
 
         writer.start_struct(TagType::Anonymous)?;
         writer.start_struct(TagType::Context(InvRespTag::Cmd as _))?;
@@ -375,6 +576,7 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
 
 pub struct CmdDataWriter<'a, 'b, 'c> {
     tracker: &'a mut CmdDataTracker,
+    command_ref: Option<u16>,
     tw: &'a mut TLVWriter<'b, 'c>,
     anchor: usize,
     completed: bool,
@@ -383,11 +585,16 @@ pub struct CmdDataWriter<'a, 'b, 'c> {
 impl<'a, 'b, 'c> CmdDataWriter<'a, 'b, 'c> {
     pub const TAG: TagType = TagType::Context(CmdDataTag::Data as _);
 
-    fn new(tracker: &'a mut CmdDataTracker, tw: &'a mut TLVWriter<'b, 'c>) -> Self {
+    fn new(
+        tracker: &'a mut CmdDataTracker,
+        command_ref: Option<u16>,
+        tw: &'a mut TLVWriter<'b, 'c>,
+    ) -> Self {
         let anchor = tw.get_tail();
 
         Self {
             tracker,
+            command_ref,
             tw,
             anchor,
             completed: false,
@@ -400,6 +607,11 @@ impl<'a, 'b, 'c> CmdDataWriter<'a, 'b, 'c> {
     }
 
     pub fn complete(mut self) -> Result<(), Error> {
+        if let Some(command_ref) = self.command_ref {
+            self.tw
+                .u16(TagType::Context(CmdDataTag::CommandRef as _), command_ref)?;
+        }
+
         self.tw.end_container()?;
         self.tw.end_container()?;
 
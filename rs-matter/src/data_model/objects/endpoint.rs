@@ -19,16 +19,37 @@ use crate::{acl::Accessor, interaction_model::core::IMStatusCode};
 
 use core::fmt;
 
-use super::{AttrId, Attribute, Cluster, ClusterId, CmdId, DeviceType, EndptId};
+use super::{
+    AttrId, Attribute, Cluster, ClusterId, CmdId, Command, DeviceType, EndptId, SemanticTag,
+};
 
 #[derive(Debug, Clone)]
 pub struct Endpoint<'a> {
     pub id: EndptId,
     pub device_type: DeviceType,
+    /// The endpoint this one is a part of - e.g. the aggregator of a bridged device, or the
+    /// endpoint of a composed device - or `None` for the endpoints directly under the root one
+    pub parent: Option<EndptId>,
     pub clusters: &'a [Cluster<'a>],
+    /// The IDs of the clusters the endpoint is a client of, e.g. the OnOff cluster for
+    /// a light switch
+    pub client_clusters: &'a [ClusterId],
+    /// The semantic tags of the endpoint, reported in the TagList attribute of its Descriptor
+    /// cluster - whose metadata then needs to be `descriptor::TAG_LIST_CLUSTER`
+    pub tags: &'a [SemanticTag<'a>],
 }
 
 impl<'a> Endpoint<'a> {
+    /// The ID of the endpoint this one is a part of: the root endpoint, unless the endpoint
+    /// has another parent, or none for the root endpoint itself
+    pub fn parent_id(&self) -> Option<EndptId> {
+        if self.id == 0 {
+            None
+        } else {
+            Some(self.parent.unwrap_or(0))
+        }
+    }
+
     pub fn match_attributes(
         &self,
         cl: Option<ClusterId>,
@@ -45,7 +66,7 @@ impl<'a> Endpoint<'a> {
         &self,
         cl: Option<ClusterId>,
         cmd: Option<CmdId>,
-    ) -> impl Iterator<Item = (&'_ Cluster, CmdId)> + '_ {
+    ) -> impl Iterator<Item = (&'_ Cluster, &'_ Command)> + '_ {
         self.match_clusters(cl)
             .flat_map(move |cluster| cluster.match_commands(cmd).map(move |cmd| (cluster, cmd)))
     }
@@ -66,9 +87,10 @@ impl<'a> Endpoint<'a> {
         accessor: &Accessor,
         cl: ClusterId,
         cmd: CmdId,
+        timed: bool,
     ) -> Result<(), IMStatusCode> {
         self.check_cluster(cl)
-            .and_then(|cluster| cluster.check_command(accessor, self.id, cmd))
+            .and_then(|cluster| cluster.check_command(accessor, self.id, cmd, timed))
     }
 
     pub fn match_clusters(&self, cl: Option<ClusterId>) -> impl Iterator<Item = &'_ Cluster> + '_ {
@@ -180,6 +180,20 @@ impl<H, T> ChainedHandler<H, T> {
             next: self,
         }
     }
+
+    /// Chains a handler of the cluster `handler_cluster` on all endpoints (see
+    /// [`ChainedClusterHandler`])
+    pub const fn chain_cluster<H2>(
+        self,
+        handler_cluster: u32,
+        handler: H2,
+    ) -> ChainedClusterHandler<H2, Self> {
+        ChainedClusterHandler {
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
 }
 
 impl<H, T> Handler for ChainedHandler<H, T>
@@ -239,10 +253,126 @@ where
     }
 }
 
+/// A handler of a cluster on all the endpoints which have it - e.g. of the clusters of the
+/// endpoints of bridged devices, which are added and removed at runtime - chained in front of
+/// `next`. The handlers chained after it for a specific endpoint still take precedence.
+pub struct ChainedClusterHandler<H, T> {
+    pub handler_cluster: u32,
+    pub handler: H,
+    pub next: T,
+}
+
+impl<H, T> ChainedClusterHandler<H, T> {
+    pub const fn chain<H2>(
+        self,
+        handler_endpoint: u16,
+        handler_cluster: u32,
+        handler: H2,
+    ) -> ChainedHandler<H2, Self> {
+        ChainedHandler {
+            handler_endpoint,
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
+
+    pub const fn chain_cluster<H2>(
+        self,
+        handler_cluster: u32,
+        handler: H2,
+    ) -> ChainedClusterHandler<H2, Self> {
+        ChainedClusterHandler {
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
+}
+
+impl<H, T> Handler for ChainedClusterHandler<H, T>
+where
+    H: Handler,
+    T: Handler,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if self.handler_cluster == attr.cluster_id {
+            self.handler.read(attr, encoder)
+        } else {
+            self.next.read(attr, encoder)
+        }
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        if self.handler_cluster == attr.cluster_id {
+            self.handler.write(attr, data)
+        } else {
+            self.next.write(attr, data)
+        }
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        if self.handler_cluster == cmd.cluster_id {
+            self.handler.invoke(exchange, cmd, data, encoder)
+        } else {
+            self.next.invoke(exchange, cmd, data, encoder)
+        }
+    }
+}
+
+impl<H, T> NonBlockingHandler for ChainedClusterHandler<H, T>
+where
+    H: NonBlockingHandler,
+    T: NonBlockingHandler,
+{
+}
+
 /// Wrap your `NonBlockingHandler` or `AsyncHandler` implementation in this struct
 /// to get your code compilable with and without the `nightly` feature
+///
+/// This is also the adapter of a `NonBlockingHandler` - or of a whole chain of them - to an
+/// `AsyncHandler`, so that synchronous and asynchronous cluster handlers can be chained together
+/// (see [`HandlerCompat::chain`]).
 pub struct HandlerCompat<T>(pub T);
 
+impl<T> HandlerCompat<T> {
+    /// Chains a handler - typically an `AsyncHandler` - in front of the adapted
+    /// `NonBlockingHandler`, which handles all other endpoints and clusters
+    pub const fn chain<H>(
+        self,
+        handler_endpoint: u16,
+        handler_cluster: u32,
+        handler: H,
+    ) -> ChainedHandler<H, Self> {
+        ChainedHandler {
+            handler_endpoint,
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
+
+    /// Chains a handler of the cluster `handler_cluster` on all endpoints (see
+    /// [`ChainedClusterHandler`]) in front of the adapted `NonBlockingHandler`
+    pub const fn chain_cluster<H>(
+        self,
+        handler_cluster: u32,
+        handler: H,
+    ) -> ChainedClusterHandler<H, Self> {
+        ChainedClusterHandler {
+            handler_cluster,
+            handler,
+            next: self,
+        }
+    }
+}
+
 impl<T> Handler for HandlerCompat<T>
 where
     T: Handler,
@@ -286,6 +416,59 @@ macro_rules! handler_chain_type {
     };
 }
 
+/// Assembles the metadata of a node - a `Node<'static>` constant - together with the chain of
+/// the handlers of its clusters, from a single list of endpoints, so that the two cannot get
+/// out of sync. Evaluates to a `(Node, ChainedHandler)` tuple, which is a handler of the data
+/// model as is.
+///
+/// The root endpoint comes with the handler of all its clusters, and every other endpoint
+/// with its device type, and each of its clusters with its handler. The endpoints, device types
+/// and clusters need to be constants.
+///
+/// ```ignore
+/// let handler = node!(
+///     id: 0,
+///     root: root_endpoint::endpoint(0) => root_endpoint::handler(0, &matter),
+///     endpoints: [
+///         1, DEV_TYPE_ON_OFF_LIGHT => [
+///             descriptor::CLUSTER => DescriptorCluster::new(*matter.borrow()),
+///             cluster_on_off::CLUSTER => OnOffCluster::new(*matter.borrow()),
+///         ],
+///     ],
+/// );
+/// ```
+#[macro_export]
+macro_rules! node {
+    (
+        id: $id:expr,
+        root: $root:expr => $root_handler:expr,
+        endpoints: [
+            $($endpoint:expr, $device_type:expr => [
+                $($cluster:expr => $handler:expr),* $(,)?
+            ]),* $(,)?
+        ] $(,)?
+    ) => {{
+        use $crate::data_model::objects::{Endpoint, Node};
+
+        const NODE: Node<'static> = Node {
+            id: $id,
+            endpoints: &[
+                $root,
+                $(Endpoint {
+                    id: $endpoint,
+                    device_type: $device_type,
+                    parent: None,
+                    clusters: &[$($cluster),*],
+                    client_clusters: &[],
+                    tags: &[],
+                }),*
+            ],
+        };
+
+        (NODE, $root_handler$($(.chain($endpoint, $cluster.id, $handler))*)*)
+    }};
+}
+
 mod asynch {
     use crate::{
         data_model::objects::{AttrData, AttrDataEncoder, AttrDetails, CmdDataEncoder, CmdDetails},
@@ -294,8 +477,24 @@ mod asynch {
         transport::exchange::Exchange,
     };
 
-    use super::{ChainedHandler, EmptyHandler, Handler, HandlerCompat, NonBlockingHandler};
+    use super::{
+        ChainedClusterHandler, ChainedHandler, EmptyHandler, Handler, HandlerCompat,
+        NonBlockingHandler,
+    };
 
+    /// The asynchronous variant of [`Handler`], for the cluster handlers which need to
+    /// await - e.g. for I/O on an async bus - while reading or writing an attribute or
+    /// invoking a command.
+    ///
+    /// The data model is driven by an `AsyncHandler`. A [`NonBlockingHandler`] is adapted to one
+    /// with [`HandlerCompat`], and the two kinds can be mixed in one chain, by wrapping either
+    /// each synchronous handler, or the whole chain of them:
+    ///
+    /// ```ignore
+    /// let handler = HandlerCompat(root_endpoint::handler(0, &matter))
+    ///     .chain(1, descriptor::ID, HandlerCompat(DescriptorCluster::new(*matter.borrow())))
+    ///     .chain(1, cluster_on_off::ID, RelayOnOff::new(&relay_bus));
+    /// ```
     pub trait AsyncHandler {
         async fn read<'a>(
             &'a self,
@@ -501,4 +700,93 @@ mod asynch {
             }
         }
     }
+
+    impl<H, T> AsyncHandler for ChainedClusterHandler<H, T>
+    where
+        H: AsyncHandler,
+        T: AsyncHandler,
+    {
+        async fn read<'a>(
+            &'a self,
+            attr: &'a AttrDetails<'_>,
+            encoder: AttrDataEncoder<'a, '_, '_>,
+        ) -> Result<(), Error> {
+            if self.handler_cluster == attr.cluster_id {
+                self.handler.read(attr, encoder).await
+            } else {
+                self.next.read(attr, encoder).await
+            }
+        }
+
+        async fn write<'a>(
+            &'a self,
+            attr: &'a AttrDetails<'_>,
+            data: AttrData<'a>,
+        ) -> Result<(), Error> {
+            if self.handler_cluster == attr.cluster_id {
+                self.handler.write(attr, data).await
+            } else {
+                self.next.write(attr, data).await
+            }
+        }
+
+        async fn invoke<'a>(
+            &'a self,
+            exchange: &'a Exchange<'_>,
+            cmd: &'a CmdDetails<'_>,
+            data: &'a TLVElement<'_>,
+            encoder: CmdDataEncoder<'a, '_, '_>,
+        ) -> Result<(), Error> {
+            if self.handler_cluster == cmd.cluster_id {
+                self.handler.invoke(exchange, cmd, data, encoder).await
+            } else {
+                self.next.invoke(exchange, cmd, data, encoder).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::device_types::{DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_ROOT_NODE};
+    use crate::data_model::objects::EmptyHandler;
+    use crate::data_model::system_model::descriptor::{self, DescriptorCluster};
+    use crate::data_model::{cluster_on_off, cluster_on_off::OnOffCluster};
+    use crate::utils::rand::dummy_rand;
+
+    #[test]
+    fn test_node() {
+        let (node, handler) = node!(
+            id: 0,
+            root: Endpoint {
+                id: 0,
+                device_type: DEV_TYPE_ROOT_NODE,
+                parent: None,
+                clusters: &[],
+                client_clusters: &[],
+                tags: &[],
+            } => EmptyHandler,
+            endpoints: [
+                1, DEV_TYPE_ON_OFF_LIGHT => [
+                    descriptor::CLUSTER => DescriptorCluster::new(dummy_rand),
+                    cluster_on_off::CLUSTER => OnOffCluster::new(dummy_rand),
+                ],
+            ],
+        );
+
+        assert_eq!(node.endpoints.len(), 2);
+        assert_eq!(node.endpoints[1].id, 1);
+        assert_eq!(node.endpoints[1].clusters[0].id, descriptor::ID);
+        assert_eq!(node.endpoints[1].clusters[1].id, cluster_on_off::ID);
+
+        // The handlers are chained in reverse
+        assert_eq!(
+            (handler.handler_endpoint, handler.handler_cluster),
+            (1, cluster_on_off::ID)
+        );
+        assert_eq!(
+            (handler.next.handler_endpoint, handler.next.handler_cluster),
+            (1, descriptor::ID)
+        );
+    }
 }
@@ -15,7 +15,9 @@
  *    limitations under the License.
  */
 
-use crate::data_model::objects::Node;
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::MutexGuard};
+
+use crate::data_model::objects::{DynamicNode, Node};
 
 pub use asynch::*;
 
@@ -82,6 +84,18 @@ impl<'a> MetadataGuard for Node<'a> {
     }
 }
 
+impl<'a, const N: usize> MetadataGuard for DynamicNode<'a, N> {
+    fn node(&self) -> Node<'_> {
+        DynamicNode::node(self)
+    }
+}
+
+impl<'a, const N: usize> MetadataGuard for MutexGuard<'_, NoopRawMutex, DynamicNode<'a, N>> {
+    fn node(&self) -> Node<'_> {
+        DynamicNode::node(self)
+    }
+}
+
 impl<'a> Metadata for Node<'a> {
     type MetadataGuard<'g> = Node<'g> where Self: 'g;
 
@@ -120,7 +134,9 @@ where
 }
 
 pub mod asynch {
-    use crate::data_model::objects::{HandlerCompat, Node};
+    use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::MutexGuard};
+
+    use crate::data_model::objects::{DynamicNode, HandlerCompat, Node, SharedNode};
 
     use super::{Metadata, MetadataGuard};
 
@@ -165,6 +181,14 @@ pub mod asynch {
         }
     }
 
+    impl<'a, const N: usize> AsyncMetadata for SharedNode<'a, N> {
+        type MetadataGuard<'g> = MutexGuard<'g, NoopRawMutex, DynamicNode<'a, N>> where Self: 'g;
+
+        async fn lock(&self) -> Self::MetadataGuard<'_> {
+            SharedNode::lock(self).await
+        }
+    }
+
     impl<M, H> AsyncMetadata for (M, H)
     where
         M: AsyncMetadata,
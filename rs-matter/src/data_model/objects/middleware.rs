@@ -0,0 +1,217 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use crate::{error::Error, tlv::TLVElement, transport::exchange::Exchange};
+
+use super::{
+    AsyncHandler, AttrData, AttrDataEncoder, AttrDetails, CmdDataEncoder, CmdDetails, Handler,
+    NonBlockingHandler,
+};
+
+/// An operation on the data model, as seen by a [`Middleware`]
+#[derive(Clone, Copy)]
+pub enum Operation<'a> {
+    Read(&'a AttrDetails<'a>),
+    Write(&'a AttrDetails<'a>),
+    Invoke(&'a Exchange<'a>, &'a CmdDetails<'a>),
+}
+
+/// A layer around a handler - typically the whole chain of the handlers of a node - which
+/// observes the operations on the data model before and after they reach the handler, and which
+/// can veto them, e.g. for logging, metrics, rate limiting or a custom authorization.
+///
+/// Middlewares compose as tuples, with the first one of the tuple being the outermost layer:
+///
+/// ```ignore
+/// let handler = MiddlewareHandler::new((Logger, RateLimiter::new()), handler);
+/// ```
+pub trait Middleware {
+    /// Called before the operation reaches the handler. An error vetoes the operation, and is
+    /// reported as its status, as if the handler itself had failed.
+    fn before(&self, _op: Operation) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once the handler completed the operation - or once a middleware vetoed it - with
+    /// its result
+    fn after(&self, _op: Operation, _result: &Result<(), Error>) {}
+}
+
+impl<T> Middleware for &T
+where
+    T: Middleware,
+{
+    fn before(&self, op: Operation) -> Result<(), Error> {
+        (**self).before(op)
+    }
+
+    fn after(&self, op: Operation, result: &Result<(), Error>) {
+        (**self).after(op, result)
+    }
+}
+
+impl Middleware for () {}
+
+impl<M1, M2> Middleware for (M1, M2)
+where
+    M1: Middleware,
+    M2: Middleware,
+{
+    fn before(&self, op: Operation) -> Result<(), Error> {
+        self.0.before(op)?;
+
+        let result = self.1.before(op);
+        if result.is_err() {
+            // The outer layer saw the operation, so it sees its completion too
+            self.0.after(op, &result);
+        }
+
+        result
+    }
+
+    fn after(&self, op: Operation, result: &Result<(), Error>) {
+        self.1.after(op, result);
+        self.0.after(op, result);
+    }
+}
+
+/// A handler - synchronous or asynchronous - with a [`Middleware`] around it
+pub struct MiddlewareHandler<M, H> {
+    middleware: M,
+    handler: H,
+}
+
+impl<M, H> MiddlewareHandler<M, H> {
+    pub const fn new(middleware: M, handler: H) -> Self {
+        Self {
+            middleware,
+            handler,
+        }
+    }
+
+    pub fn middleware(&self) -> &M {
+        &self.middleware
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+}
+
+impl<M, H> MiddlewareHandler<M, H>
+where
+    M: Middleware,
+{
+    fn around<F>(&self, op: Operation, f: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<(), Error>,
+    {
+        // A vetoing layer already notified the layers it is wrapped in
+        self.middleware.before(op)?;
+
+        let result = f();
+        self.middleware.after(op, &result);
+
+        result
+    }
+}
+
+impl<M, H> Handler for MiddlewareHandler<M, H>
+where
+    M: Middleware,
+    H: Handler,
+{
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        self.around(Operation::Read(attr), || self.handler.read(attr, encoder))
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        self.around(Operation::Write(attr), || self.handler.write(attr, data))
+    }
+
+    fn invoke(
+        &self,
+        exchange: &Exchange,
+        cmd: &CmdDetails,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        self.around(Operation::Invoke(exchange, cmd), || {
+            self.handler.invoke(exchange, cmd, data, encoder)
+        })
+    }
+}
+
+impl<M, H> NonBlockingHandler for MiddlewareHandler<M, H>
+where
+    M: Middleware,
+    H: NonBlockingHandler,
+{
+}
+
+impl<M, H> AsyncHandler for MiddlewareHandler<M, H>
+where
+    M: Middleware,
+    H: AsyncHandler,
+{
+    async fn read<'a>(
+        &'a self,
+        attr: &'a AttrDetails<'_>,
+        encoder: AttrDataEncoder<'a, '_, '_>,
+    ) -> Result<(), Error> {
+        let op = Operation::Read(attr);
+
+        self.middleware.before(op)?;
+
+        let result = self.handler.read(attr, encoder).await;
+        self.middleware.after(op, &result);
+
+        result
+    }
+
+    async fn write<'a>(
+        &'a self,
+        attr: &'a AttrDetails<'_>,
+        data: AttrData<'a>,
+    ) -> Result<(), Error> {
+        let op = Operation::Write(attr);
+
+        self.middleware.before(op)?;
+
+        let result = self.handler.write(attr, data).await;
+        self.middleware.after(op, &result);
+
+        result
+    }
+
+    async fn invoke<'a>(
+        &'a self,
+        exchange: &'a Exchange<'_>,
+        cmd: &'a CmdDetails<'_>,
+        data: &'a TLVElement<'_>,
+        encoder: CmdDataEncoder<'a, '_, '_>,
+    ) -> Result<(), Error> {
+        let op = Operation::Invoke(exchange, cmd);
+
+        self.middleware.before(op)?;
+
+        let result = self.handler.invoke(exchange, cmd, data, encoder).await;
+        self.middleware.after(op, &result);
+
+        result
+    }
+}
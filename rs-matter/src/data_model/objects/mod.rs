@@ -14,7 +14,7 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
-use crate::tlv::ToTLV;
+use crate::tlv::{Nullable, ToTLV, UtfStr};
 
 mod attribute;
 pub use attribute::*;
@@ -22,6 +22,9 @@ pub use attribute::*;
 mod cluster;
 pub use cluster::*;
 
+mod command;
+pub use command::*;
+
 mod endpoint;
 pub use endpoint::*;
 
@@ -37,6 +40,9 @@ pub use encoder::*;
 mod handler;
 pub use handler::*;
 
+mod middleware;
+pub use middleware::*;
+
 mod dataver;
 pub use dataver::*;
 
@@ -53,3 +59,35 @@ pub struct DeviceType {
     pub dtype: u16,
     pub drev: u16,
 }
+
+/// A semantic tag labelling an endpoint, e.g. the `Left` and `Right` tags of the Common
+/// Position namespace for the two endpoints of a two-gang switch
+/// (see [`crate::data_model::semantic_tags`])
+#[derive(Debug, ToTLV, Copy, Clone)]
+#[tlvargs(lifetime = "'a")]
+pub struct SemanticTag<'a> {
+    /// The manufacturer code of the namespace, or null for the standard namespaces
+    pub mfg_code: Nullable<u16>,
+    pub namespace_id: u8,
+    pub tag: u8,
+    pub label: Option<Nullable<UtfStr<'a>>>,
+}
+
+impl<'a> SemanticTag<'a> {
+    /// A tag of a standard namespace, without a label
+    pub const fn new(namespace_id: u8, tag: u8) -> Self {
+        Self {
+            mfg_code: Nullable::Null,
+            namespace_id,
+            tag,
+            label: None,
+        }
+    }
+
+    pub const fn with_label(self, label: &'a str) -> Self {
+        Self {
+            label: Some(Nullable::NotNull(UtfStr::new(label.as_bytes()))),
+            ..self
+        }
+    }
+}
@@ -22,7 +22,7 @@ use crate::{
     interaction_model::{
         core::IMStatusCode,
         messages::{
-            ib::{AttrPath, AttrStatus, CmdStatus, DataVersionFilter},
+            ib::{AttrPath, AttrStatus, CmdStatus, DataVersionFilter, EventPath, EventStatus},
             msg::{InvReq, ReadReq, SubscribeReq, WriteReq},
             GenericPath,
         },
@@ -35,7 +35,16 @@ use core::{
     iter::{once, Once},
 };
 
-use super::{AttrDetails, AttrId, Attribute, Cluster, ClusterId, CmdDetails, CmdId, EndptId};
+use embassy_sync::{
+    blocking_mutex::raw::NoopRawMutex,
+    mutex::{Mutex, MutexGuard},
+};
+
+use crate::Matter;
+
+use super::{
+    AttrDetails, AttrId, Attribute, Cluster, ClusterId, CmdDetails, CmdId, Command, EndptId,
+};
 
 pub enum WildcardIter<T, E> {
     None,
@@ -280,6 +289,9 @@ impl<'a> Node<'a> {
         req: &'m InvReq,
         accessor: &'m Accessor<'m>,
     ) -> impl Iterator<Item = Result<(CmdDetails, TLVElement<'m>), CmdStatus>> + 'm {
+        // A timed request only gets here if it is part of a timed interaction
+        let timed = req.timed_request.unwrap_or(false);
+
         alloc!(req
             .inv_requests
             .iter()
@@ -295,7 +307,9 @@ impl<'a> Node<'a> {
                         .filter(move |(ep, cl, cmd)| {
                             Cluster::check_cmd_access(
                                 accessor,
-                                GenericPath::new(Some(ep.id), Some(cl.id), Some(*cmd)),
+                                GenericPath::new(Some(ep.id), Some(cl.id), Some(cmd.id)),
+                                cmd,
+                                timed,
                             )
                             .is_ok()
                         })
@@ -305,7 +319,8 @@ impl<'a> Node<'a> {
                                     node: self,
                                     endpoint_id: ep.id,
                                     cluster_id: cl.id,
-                                    cmd_id: cmd,
+                                    cmd_id: cmd.id,
+                                    command_ref: cmd_data.command_ref,
                                     wildcard: true,
                                 },
                                 cmd_data.data.clone().unwrap_tlv().unwrap(),
@@ -318,18 +333,20 @@ impl<'a> Node<'a> {
                     let cl = cmd_data.path.path.cluster.unwrap();
                     let cmd = cmd_data.path.path.leaf.unwrap();
 
-                    let result = match self.check_command(accessor, ep, cl, cmd) {
+                    let result = match self.check_command(accessor, ep, cl, cmd, timed) {
                         Ok(()) => Ok((
                             CmdDetails {
                                 node: self,
                                 endpoint_id: cmd_data.path.path.endpoint.unwrap(),
                                 cluster_id: cmd_data.path.path.cluster.unwrap(),
                                 cmd_id: cmd_data.path.path.leaf.unwrap(),
+                                command_ref: cmd_data.command_ref,
                                 wildcard: false,
                             },
                             cmd_data.data.unwrap_tlv().unwrap(),
                         )),
-                        Err(err) => Err(CmdStatus::new(cmd_data.path, err, 0)),
+                        Err(err) => Err(CmdStatus::new(cmd_data.path, err, 0)
+                            .set_command_ref(cmd_data.command_ref)),
                     };
 
                     WildcardIter::Single(once(result))
@@ -337,6 +354,24 @@ impl<'a> Node<'a> {
             }))
     }
 
+    /// The statuses of the concrete event paths on endpoints or clusters the node does not have.
+    /// As with attribute paths, the wildcard event paths are silently limited to the endpoints
+    /// and clusters of the node.
+    pub fn event_statuses<'m>(
+        &'m self,
+        paths: &'m TLVArray<'m, EventPath>,
+    ) -> impl Iterator<Item = EventStatus> + 'm {
+        paths.iter().filter_map(move |path| {
+            let (Some(ep), Some(cl), Some(_)) = (path.endpoint, path.cluster, path.event) else {
+                return None;
+            };
+
+            self.check_cluster(ep, cl)
+                .err()
+                .map(|status| EventStatus::new(path, status, 0))
+        })
+    }
+
     fn matches(path: Option<&GenericPath>, ep: EndptId, cl: ClusterId, leaf: u32) -> bool {
         if let Some(path) = path {
             path.endpoint.map(|id| id == ep).unwrap_or(true)
@@ -365,7 +400,7 @@ impl<'a> Node<'a> {
         ep: Option<EndptId>,
         cl: Option<ClusterId>,
         cmd: Option<CmdId>,
-    ) -> impl Iterator<Item = (&'_ Endpoint, &'_ Cluster, CmdId)> + '_ {
+    ) -> impl Iterator<Item = (&'_ Endpoint, &'_ Cluster, &'_ Command)> + '_ {
         self.match_endpoints(ep).flat_map(move |endpoint| {
             endpoint
                 .match_commands(cl, cmd)
@@ -391,9 +426,15 @@ impl<'a> Node<'a> {
         ep: EndptId,
         cl: ClusterId,
         cmd: CmdId,
+        timed: bool,
     ) -> Result<(), IMStatusCode> {
         self.check_endpoint(ep)
-            .and_then(|endpoint| endpoint.check_command(accessor, cl, cmd))
+            .and_then(|endpoint| endpoint.check_command(accessor, cl, cmd, timed))
+    }
+
+    pub fn check_cluster(&self, ep: EndptId, cl: ClusterId) -> Result<&Cluster<'_>, IMStatusCode> {
+        self.check_endpoint(ep)
+            .and_then(|endpoint| endpoint.check_cluster(cl))
     }
 
     pub fn match_endpoints(&self, ep: Option<EndptId>) -> impl Iterator<Item = &'_ Endpoint> + '_ {
@@ -469,3 +510,45 @@ impl<'a, const N: usize> core::fmt::Display for DynamicNode<'a, N> {
         self.node().fmt(f)
     }
 }
+
+/// A [`DynamicNode`] whose endpoints are added and removed at runtime - e.g. by a bridge, as it
+/// discovers the devices it bridges - while the data model uses it as its metadata.
+///
+/// The endpoints are only changed in-between the interactions, as the data model keeps the
+/// node locked while processing one.
+pub struct SharedNode<'a, const N: usize>(Mutex<NoopRawMutex, DynamicNode<'a, N>>);
+
+impl<'a, const N: usize> SharedNode<'a, N> {
+    pub const fn new(node: DynamicNode<'a, N>) -> Self {
+        Self(Mutex::new(node))
+    }
+
+    /// Adds an endpoint - with its clusters - and has the change reported to the subscribers.
+    /// The endpoint is returned back if there is one with the same ID already, or if there is
+    /// no space left for it.
+    pub async fn add(
+        &self,
+        matter: &Matter<'_>,
+        endpoint: Endpoint<'a>,
+    ) -> Result<(), Endpoint<'a>> {
+        let endpoint_id = endpoint.id;
+
+        self.0.lock().await.add(endpoint)?;
+        matter.notify_endpoint_changed(endpoint_id);
+
+        Ok(())
+    }
+
+    /// Removes the endpoint with ID `endpoint_id`, if there is one, and has the change reported
+    /// to the subscribers
+    pub async fn remove(&self, matter: &Matter<'_>, endpoint_id: EndptId) -> Option<Endpoint<'a>> {
+        let endpoint = self.0.lock().await.remove(endpoint_id)?;
+        matter.notify_endpoint_changed(endpoint_id);
+
+        Some(endpoint)
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, NoopRawMutex, DynamicNode<'a, N>> {
+        self.0.lock().await
+    }
+}
@@ -3,6 +3,7 @@ use core::{borrow::Borrow, cell::RefCell};
 use crate::{
     acl::AclMgr,
     fabric::FabricMgr,
+    group_keys::GroupKeyMgr,
     handler_chain_type,
     mdns::Mdns,
     secure_channel::pake::PaseMgr,
@@ -14,11 +15,11 @@ use super::{
     objects::{Cluster, EmptyHandler, Endpoint, EndptId},
     sdm::{
         admin_commissioning::{self, AdminCommCluster},
-        dev_att::DevAttDataFetcher,
+        dev_att::DacProvider,
         ethernet_nw_diagnostics::{self, EthNwDiagCluster},
         failsafe::FailSafe,
         general_commissioning::{self, GenCommCluster},
-        general_diagnostics::{self, GenDiagCluster},
+        general_diagnostics::{self, GenDiagCluster, TestEventTriggers},
         group_key_management,
         group_key_management::GrpKeyMgmtCluster,
         noc::{self, NocCluster},
@@ -38,9 +39,9 @@ pub type RootEndpointHandler<'a> = handler_chain_type!(
     AdminCommCluster<'a>,
     NocCluster<'a>,
     AccessControlCluster<'a>,
-    GenDiagCluster,
+    GenDiagCluster<'a>,
     EthNwDiagCluster,
-    GrpKeyMgmtCluster
+    GrpKeyMgmtCluster<'a>
 );
 
 pub const CLUSTERS: [Cluster<'static>; 10] = [
@@ -60,21 +61,30 @@ pub const fn endpoint(id: EndptId) -> Endpoint<'static> {
     Endpoint {
         id,
         device_type: super::device_types::DEV_TYPE_ROOT_NODE,
+        parent: None,
         clusters: &CLUSTERS,
+        client_clusters: &[],
+        tags: &[],
     }
 }
 
+// The root endpoint has all the clusters the Root Node device type requires
+const _: () = super::device_types::check_endpoint(&endpoint(0));
+
 pub fn handler<'a, T>(endpoint_id: u16, matter: &'a T) -> RootEndpointHandler<'a>
 where
     T: Borrow<BasicInfoConfig<'a>>
-        + Borrow<dyn DevAttDataFetcher + 'a>
+        + Borrow<dyn DacProvider + 'a>
         + Borrow<RefCell<PaseMgr>>
         + Borrow<RefCell<FabricMgr>>
         + Borrow<RefCell<AclMgr>>
         + Borrow<RefCell<FailSafe>>
+        + Borrow<RefCell<GroupKeyMgr>>
         + Borrow<dyn Mdns + 'a>
+        + Borrow<TestEventTriggers>
         + Borrow<Epoch>
         + Borrow<Rand>
+        + ?Sized
         + 'a,
 {
     wrap(
@@ -86,6 +96,8 @@ where
         matter.borrow(),
         matter.borrow(),
         matter.borrow(),
+        matter.borrow(),
+        matter.borrow(),
         *matter.borrow(),
         *matter.borrow(),
     )
@@ -95,12 +107,14 @@ where
 pub fn wrap<'a>(
     endpoint_id: u16,
     basic_info: &'a BasicInfoConfig<'a>,
-    dev_att: &'a dyn DevAttDataFetcher,
+    dev_att: &'a dyn DacProvider,
     pase: &'a RefCell<PaseMgr>,
     fabric: &'a RefCell<FabricMgr>,
     acl: &'a RefCell<AclMgr>,
     failsafe: &'a RefCell<FailSafe>,
+    group_keys: &'a RefCell<GroupKeyMgr>,
     mdns: &'a dyn Mdns,
+    test_event_triggers: &'a TestEventTriggers,
     epoch: Epoch,
     rand: Rand,
 ) -> RootEndpointHandler<'a> {
@@ -108,7 +122,7 @@ pub fn wrap<'a>(
         .chain(
             endpoint_id,
             group_key_management::ID,
-            GrpKeyMgmtCluster::new(rand),
+            GrpKeyMgmtCluster::new(fabric, group_keys, rand),
         )
         .chain(
             endpoint_id,
@@ -118,7 +132,7 @@ pub fn wrap<'a>(
         .chain(
             endpoint_id,
             general_diagnostics::ID,
-            GenDiagCluster::new(rand),
+            GenDiagCluster::new(test_event_triggers, rand),
         )
         .chain(
             endpoint_id,
@@ -0,0 +1,158 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The scene support of the clusters: the clusters with scene-relevant attributes (OnOff,
+//! LevelControl, ColorControl, ...) implement [`SceneCluster`], so that the Scenes Management
+//! cluster can capture those attributes into a scene and restore them once the scene is recalled.
+
+use heapless::Vec;
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, ToTLV};
+
+use super::objects::{AttrId, ClusterId};
+
+/// The maximum number of scene-relevant attributes of a single cluster
+pub const MAX_SCENE_ATTRIBUTES: usize = 8;
+
+/// The value of a scene-relevant attribute, as stored in a scene. Booleans, enums and the
+/// unsigned and signed integers are all stored as their (sign-extended) bits.
+#[derive(Debug, Clone, PartialEq, Eq, FromTLV, ToTLV)]
+pub struct AttributeValuePair {
+    pub attribute_id: AttrId,
+    pub value: u32,
+}
+
+impl AttributeValuePair {
+    pub const fn new(attribute_id: AttrId, value: u32) -> Self {
+        Self {
+            attribute_id,
+            value,
+        }
+    }
+}
+
+/// The scene-relevant attribute values of a single cluster, as stored in a scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionFieldSet {
+    pub cluster_id: ClusterId,
+    pub attribute_values: Vec<AttributeValuePair, MAX_SCENE_ATTRIBUTES>,
+}
+
+impl ExtensionFieldSet {
+    pub const fn new(cluster_id: ClusterId) -> Self {
+        Self {
+            cluster_id,
+            attribute_values: Vec::new(),
+        }
+    }
+
+    /// Adds the value of an attribute, replacing its previous value if any
+    pub fn set(&mut self, attribute_id: AttrId, value: u32) -> Result<(), Error> {
+        if let Some(pair) = self
+            .attribute_values
+            .iter_mut()
+            .find(|pair| pair.attribute_id == attribute_id)
+        {
+            pair.value = value;
+        } else {
+            self.attribute_values
+                .push(AttributeValuePair::new(attribute_id, value))
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of an attribute, if stored
+    pub fn get(&self, attribute_id: AttrId) -> Option<u32> {
+        self.attribute_values
+            .iter()
+            .find(|pair| pair.attribute_id == attribute_id)
+            .map(|pair| pair.value)
+    }
+}
+
+/// A cluster with scene-relevant attributes
+pub trait SceneCluster {
+    /// The ID of the cluster
+    fn cluster_id(&self) -> ClusterId;
+
+    /// Captures the current values of the scene-relevant attributes of the cluster
+    fn capture(&self) -> Result<ExtensionFieldSet, Error>;
+
+    /// Restores the scene-relevant attributes of the cluster from `values`, transitioning to
+    /// them over `transition_time_ms` where the cluster supports transitions. The attributes
+    /// which are not in `values`, or which the cluster does not support, are left as they are.
+    ///
+    /// As the values are restored outside of an interaction on the cluster, the subscribers of
+    /// its attributes are to be notified (see [`crate::Matter::attribute_changed`]).
+    fn restore(&self, values: &ExtensionFieldSet, transition_time_ms: u32) -> Result<(), Error>;
+}
+
+impl<T> SceneCluster for &T
+where
+    T: SceneCluster,
+{
+    fn cluster_id(&self) -> ClusterId {
+        (**self).cluster_id()
+    }
+
+    fn capture(&self) -> Result<ExtensionFieldSet, Error> {
+        (**self).capture()
+    }
+
+    fn restore(&self, values: &ExtensionFieldSet, transition_time_ms: u32) -> Result<(), Error> {
+        (**self).restore(values, transition_time_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::cluster_on_off::{self, OnOffCluster};
+    use crate::utils::rand::dummy_rand;
+
+    use super::{ExtensionFieldSet, SceneCluster};
+
+    #[test]
+    fn test_on_off_scene() {
+        let on_off = OnOffCluster::new(dummy_rand);
+        on_off.set(true);
+
+        let scene = on_off.capture().unwrap();
+        assert_eq!(scene.cluster_id, cluster_on_off::ID);
+        assert_eq!(
+            scene.get(cluster_on_off::AttributesDiscriminants::OnOff as _),
+            Some(1)
+        );
+
+        on_off.set(false);
+        on_off.restore(&scene, 0).unwrap();
+        assert!(on_off.get());
+
+        // The attributes missing from the scene are left as they are
+        on_off
+            .restore(&ExtensionFieldSet::new(cluster_on_off::ID), 0)
+            .unwrap();
+        assert!(on_off.get());
+
+        // The scenes of other clusters are not restored
+        let mut other = ExtensionFieldSet::new(0x0008);
+        other.set(0, 0).unwrap();
+        assert!(on_off.restore(&other, 0).is_err());
+    }
+}
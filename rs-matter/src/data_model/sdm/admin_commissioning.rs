@@ -15,23 +15,38 @@
  *    limitations under the License.
  */
 
-use core::cell::RefCell;
+use core::{cell::RefCell, time::Duration};
 
 use crate::data_model::objects::*;
 use crate::mdns::Mdns;
-use crate::secure_channel::pake::PaseMgr;
-use crate::secure_channel::spake2p::VerifierData;
+use crate::secure_channel::pake::{CommWindowAdmin, CommWindowKind, PaseMgr};
+use crate::secure_channel::spake2p::{
+    VerifierData, MAX_SALT_SIZE_BYTES, MIN_SALT_SIZE_BYTES, VERIFIER_SIZE_BYTES,
+};
 use crate::tlv::{FromTLV, Nullable, OctetStr, TLVElement};
 use crate::transport::exchange::Exchange;
 use crate::utils::rand::Rand;
 use crate::{attribute_enum, cmd_enter};
 use crate::{command_enum, error::*};
-use log::info;
+use log::{error, info};
 use num_derive::FromPrimitive;
 use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x003C;
 
+/// The Basic Commissioning Method is supported, in addition to the Enhanced one
+const FEATURE_BASIC: u32 = 0x01;
+
+const MIN_COMM_TIMEOUT_SECS: u16 = 180;
+const MAX_COMM_TIMEOUT_SECS: u16 = 900;
+
+const MIN_PBKDF_ITERATIONS: u32 = 1000;
+const MAX_PBKDF_ITERATIONS: u32 = 100000;
+
+/// The cluster-specific status of a RevokeCommissioning command invoked while no commissioning
+/// window is open
+pub const STATUS_WINDOW_NOT_OPEN: u8 = 0x04;
+
 #[derive(FromPrimitive, Debug, Copy, Clone, PartialEq)]
 pub enum WindowStatus {
     WindowNotOpen = 0,
@@ -44,7 +59,7 @@ pub enum WindowStatus {
 pub enum Attributes {
     WindowStatus(AttrType<u8>) = 0,
     AdminFabricIndex(AttrType<Nullable<u8>>) = 1,
-    AdminVendorId(AttrType<Nullable<u8>>) = 2,
+    AdminVendorId(AttrType<Nullable<u16>>) = 2,
 }
 
 attribute_enum!(Attributes);
@@ -61,10 +76,12 @@ command_enum!(Commands);
 
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
-    feature_map: 0,
+    feature_map: FEATURE_BASIC,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::WindowStatus as u16,
             Access::RV,
@@ -82,22 +99,29 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[
-        Commands::OpenCommWindow as _,
-        // Commands::OpenBasicCommWindow as _,
-        Commands::RevokeComm as _,
+        Command::new_timed(Commands::OpenCommWindow as _, Access::WA),
+        Command::new_timed(Commands::OpenBasicCommWindow as _, Access::WA),
+        Command::new_timed(Commands::RevokeComm as _, Access::WA),
     ],
+    generated_commands: &[],
+    events: &[],
 };
 
 #[derive(FromTLV)]
 #[tlvargs(lifetime = "'a")]
 pub struct OpenCommWindowReq<'a> {
-    _timeout: u16,
+    timeout: u16,
     verifier: OctetStr<'a>,
     discriminator: u16,
     iterations: u32,
     salt: OctetStr<'a>,
 }
 
+#[derive(FromTLV)]
+pub struct OpenBasicCommWindowReq {
+    timeout: u16,
+}
+
 pub struct AdminCommCluster<'a> {
     data_ver: Dataver,
     pase_mgr: &'a RefCell<PaseMgr>,
@@ -118,11 +142,24 @@ impl<'a> AdminCommCluster<'a> {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
+                let pase_mgr = self.pase_mgr.borrow();
+                let admin = pase_mgr.comm_window_admin();
+
                 match attr.attr_id.try_into()? {
-                    Attributes::WindowStatus(codec) => codec.encode(writer, 1),
-                    Attributes::AdminVendorId(codec) => codec.encode(writer, Nullable::NotNull(1)),
+                    Attributes::WindowStatus(codec) => {
+                        let status = match pase_mgr.comm_window_kind() {
+                            None => WindowStatus::WindowNotOpen,
+                            Some(CommWindowKind::Basic) => WindowStatus::BasicWindowOpen,
+                            Some(CommWindowKind::Enhanced) => WindowStatus::EnhancedWindowOpen,
+                        };
+
+                        codec.encode(writer, status as u8)
+                    }
+                    Attributes::AdminVendorId(codec) => {
+                        codec.encode(writer, Nullable::new(admin.map(|admin| admin.vendor_id)))
+                    }
                     Attributes::AdminFabricIndex(codec) => {
-                        codec.encode(writer, Nullable::NotNull(1))
+                        codec.encode(writer, Nullable::new(admin.map(|admin| admin.fab_idx)))
                     }
                 }
             }
@@ -133,14 +170,17 @@ impl<'a> AdminCommCluster<'a> {
 
     pub fn invoke(
         &self,
+        exchange: &Exchange,
         cmd: &CmdDetails,
         data: &TLVElement,
         _encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
-            Commands::OpenCommWindow => self.handle_command_opencomm_win(data)?,
+            Commands::OpenCommWindow => self.handle_command_opencomm_win(exchange, data)?,
+            Commands::OpenBasicCommWindow => {
+                self.handle_command_openbasiccomm_win(exchange, data)?
+            }
             Commands::RevokeComm => self.handle_command_revokecomm_win(data)?,
-            _ => Err(ErrorCode::CommandNotFound)?,
         }
 
         self.data_ver.changed();
@@ -148,25 +188,88 @@ impl<'a> AdminCommCluster<'a> {
         Ok(())
     }
 
-    fn handle_command_opencomm_win(&self, data: &TLVElement) -> Result<(), Error> {
+    fn handle_command_opencomm_win(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+    ) -> Result<(), Error> {
         cmd_enter!("Open Commissioning Window");
         let req = OpenCommWindowReq::from_tlv(data)?;
+        let timeout = Self::comm_timeout(req.timeout)?;
+
+        if req.verifier.0.len() != VERIFIER_SIZE_BYTES
+            || !(MIN_SALT_SIZE_BYTES..=MAX_SALT_SIZE_BYTES).contains(&req.salt.0.len())
+            || !(MIN_PBKDF_ITERATIONS..=MAX_PBKDF_ITERATIONS).contains(&req.iterations)
+        {
+            error!("Invalid PAKE parameters");
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
         let verifier = VerifierData::new(req.verifier.0, req.iterations, req.salt.0);
-        self.pase_mgr
-            .borrow_mut()
-            .enable_pase_session(verifier, req.discriminator, self.mdns)?;
+        self.pase_mgr.borrow_mut().open_comm_window(
+            verifier,
+            req.discriminator,
+            CommWindowKind::Enhanced,
+            Some(timeout),
+            Self::admin(exchange)?,
+            self.mdns,
+        )?;
+
+        Ok(())
+    }
+
+    fn handle_command_openbasiccomm_win(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+    ) -> Result<(), Error> {
+        cmd_enter!("Open Basic Commissioning Window");
+        let req = OpenBasicCommWindowReq::from_tlv(data)?;
+        let timeout = Self::comm_timeout(req.timeout)?;
+
+        self.pase_mgr.borrow_mut().open_basic_comm_window(
+            timeout,
+            Self::admin(exchange)?,
+            self.mdns,
+        )?;
 
         Ok(())
     }
 
     fn handle_command_revokecomm_win(&self, _data: &TLVElement) -> Result<(), Error> {
         cmd_enter!("Revoke Commissioning Window");
-        self.pase_mgr.borrow_mut().disable_pase_session(self.mdns)?;
-
-        // TODO: Send status code if no commissioning window is open
+        if !self.pase_mgr.borrow_mut().disable_pase_session(self.mdns)? {
+            error!("No commissioning window open");
+            Err(ErrorCode::ClusterStatus(STATUS_WINDOW_NOT_OPEN))?;
+        }
 
         Ok(())
     }
+
+    fn comm_timeout(timeout_secs: u16) -> Result<Duration, Error> {
+        if !(MIN_COMM_TIMEOUT_SECS..=MAX_COMM_TIMEOUT_SECS).contains(&timeout_secs) {
+            error!("Invalid commissioning timeout: {}s", timeout_secs);
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        Ok(Duration::from_secs(timeout_secs as _))
+    }
+
+    /// The administrator opening a commissioning window, i.e. the fabric of the invoking session
+    fn admin(exchange: &Exchange) -> Result<Option<CommWindowAdmin>, Error> {
+        let Some(fab_idx) = exchange.with_session(|sess| Ok(sess.get_local_fabric_idx()))? else {
+            return Ok(None);
+        };
+
+        let fabric_mgr = exchange.matter.fabric_mgr().borrow();
+
+        Ok(fabric_mgr
+            .get_fabric(fab_idx as _)?
+            .map(|fabric| CommWindowAdmin {
+                fab_idx,
+                vendor_id: fabric.get_vendor_id(),
+            }))
+    }
 }
 
 impl<'a> Handler for AdminCommCluster<'a> {
@@ -176,12 +279,12 @@ impl<'a> Handler for AdminCommCluster<'a> {
 
     fn invoke(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         cmd: &CmdDetails,
         data: &TLVElement,
         encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
-        AdminCommCluster::invoke(self, cmd, data, encoder)
+        AdminCommCluster::invoke(self, exchange, cmd, data, encoder)
     }
 }
 
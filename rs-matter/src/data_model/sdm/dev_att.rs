@@ -15,7 +15,10 @@
  *    limitations under the License.
  */
 
-use crate::error::Error;
+use crate::{
+    crypto::{self, KeyPair},
+    error::Error,
+};
 
 /// Device Attestation Data Type
 pub enum DataType {
@@ -43,3 +46,52 @@ pub trait DevAttDataFetcher {
     /// The type of data that can be queried is defined in the [DataType] enum.
     fn get_devatt_data(&self, data_type: DataType, data: &mut [u8]) -> Result<usize, Error>;
 }
+
+/// The Device Attestation Provider Trait
+///
+/// Provides the Device Attestation data of the Matter device, and signs with its attestation key.
+/// Unlike with [DevAttDataFetcher], the private key of the DAC never has to leave the provider, so
+/// it might be kept e.g. in a secure element or in a separate keystore.
+///
+/// Any [DevAttDataFetcher] is a provider too.
+pub trait DacProvider {
+    /// Get the Certificate Declaration
+    fn get_cert_declaration(&self, data: &mut [u8]) -> Result<usize, Error>;
+
+    /// Get the Product Attestation Intermediary Certificate
+    fn get_pai(&self, data: &mut [u8]) -> Result<usize, Error>;
+
+    /// Get the Device Attestation Certificate
+    fn get_dac(&self, data: &mut [u8]) -> Result<usize, Error>;
+
+    /// Sign a message with the private key of the Device Attestation Certificate
+    ///
+    /// Returns the length of the signature.
+    fn sign_with_dac(&self, msg: &[u8], signature: &mut [u8]) -> Result<usize, Error>;
+}
+
+impl<T> DacProvider for T
+where
+    T: DevAttDataFetcher,
+{
+    fn get_cert_declaration(&self, data: &mut [u8]) -> Result<usize, Error> {
+        self.get_devatt_data(DataType::CertDeclaration, data)
+    }
+
+    fn get_pai(&self, data: &mut [u8]) -> Result<usize, Error> {
+        self.get_devatt_data(DataType::PAI, data)
+    }
+
+    fn get_dac(&self, data: &mut [u8]) -> Result<usize, Error> {
+        self.get_devatt_data(DataType::DAC, data)
+    }
+
+    fn sign_with_dac(&self, msg: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
+        let mut pubkey = [0_u8; crypto::EC_POINT_LEN_BYTES];
+        let mut privkey = [0_u8; crypto::BIGNUM_LEN_BYTES];
+        self.get_devatt_data(DataType::DACPubKey, &mut pubkey)?;
+        self.get_devatt_data(DataType::DACPrivKey, &mut privkey)?;
+
+        KeyPair::new_from_components(&pubkey, &privkey)?.sign_msg(msg, signature)
+    }
+}
@@ -46,6 +46,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::PacketRxCount as u16,
             Access::RV,
@@ -57,7 +59,12 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Quality::FIXED,
         ),
     ],
-    commands: &[CommandsDiscriminants::ResetCounts as _],
+    commands: &[Command::new(
+        CommandsDiscriminants::ResetCounts as _,
+        Access::WM,
+    )],
+    generated_commands: &[],
+    events: &[],
 };
 
 pub struct EthNwDiagCluster {
@@ -15,53 +15,127 @@
  *    limitations under the License.
  */
 
+//! The Fail-Safe context, which a commissioner arms with the ArmFailSafe command of the
+//! General Commissioning cluster before it starts changing the configuration of the node.
+//!
+//! While armed, the fail-safe keeps track of the changes which are not committed yet: the fabric
+//! added with AddNOC, the state of the fabric updated with UpdateNOC before the update, and
+//! whether the network configuration changed. These are committed with CommissioningComplete,
+//! or rolled back if the fail-safe expires first - see [`crate::Matter::expire_failsafe`].
+
+use core::time::Duration;
+
 use crate::{
+    alloc,
     error::{Error, ErrorCode},
+    fabric::Fabric,
     transport::session::SessionMode,
+    utils::epoch::Epoch,
 };
-use log::error;
+use log::{error, info};
+
+/// For how long the fail-safe can be kept armed by re-arming it, since it was first armed
+pub const MAX_CUMULATIVE_FAILSAFE_SECS: u16 = 120;
+
+/// The fabric as it was before an UpdateNOC. Boxed when possible, as it is much larger
+/// than the rest of the fail-safe state.
+#[cfg(feature = "alloc")]
+type PrevFabric = alloc::boxed::Box<Fabric>;
+#[cfg(not(feature = "alloc"))]
+type PrevFabric = Fabric;
 
-#[derive(PartialEq)]
-#[allow(dead_code)]
 #[allow(clippy::enum_variant_names)]
+#[cfg_attr(not(feature = "alloc"), allow(clippy::large_enum_variant))]
 enum NocState {
     NocNotRecvd,
     // This is the local fabric index
     AddNocRecvd(u8),
-    UpdateNocRecvd(u8),
+    // The local fabric index, and the fabric as it was before the update
+    UpdateNocRecvd(u8, PrevFabric),
 }
 
-#[derive(PartialEq)]
 pub struct ArmedCtx {
     session_mode: SessionMode,
-    timeout: u16,
+    expires_at: Duration,
+    max_expires_at: Duration,
     noc_state: NocState,
+    network_changed: bool,
 }
 
-#[derive(PartialEq)]
+#[cfg_attr(not(feature = "alloc"), allow(clippy::large_enum_variant))]
 pub enum State {
     Idle,
     Armed(ArmedCtx),
 }
 
+/// The configuration changes made while the fail-safe was armed
+#[derive(Default)]
+pub struct PendingChanges {
+    /// The index of the fabric added with AddNOC
+    pub added_fabric: Option<u8>,
+    /// The index of the fabric updated with UpdateNOC, and the fabric as it was before
+    pub updated_fabric: Option<(u8, Fabric)>,
+    /// Whether the network configuration changed
+    pub network_changed: bool,
+}
+
+impl PendingChanges {
+    fn from_ctx(ctx: ArmedCtx) -> Self {
+        let mut changes = Self {
+            network_changed: ctx.network_changed,
+            ..Default::default()
+        };
+
+        match ctx.noc_state {
+            NocState::NocNotRecvd => (),
+            NocState::AddNocRecvd(idx) => changes.added_fabric = Some(idx),
+            NocState::UpdateNocRecvd(idx, fabric) => {
+                #[cfg(feature = "alloc")]
+                let fabric = *fabric;
+
+                changes.updated_fabric = Some((idx, fabric));
+            }
+        }
+
+        changes
+    }
+}
+
 pub struct FailSafe {
     state: State,
+    epoch: Epoch,
 }
 
 impl FailSafe {
     #[inline(always)]
-    pub const fn new() -> Self {
-        Self { state: State::Idle }
+    pub const fn new(epoch: Epoch) -> Self {
+        Self {
+            state: State::Idle,
+            epoch,
+        }
     }
 
+    /// Arms the fail-safe for `timeout` seconds, or re-arms it if it is already armed from
+    /// the same kind of session. Re-arming with a `timeout` of 0 expires it right away.
     pub fn arm(&mut self, timeout: u16, session_mode: SessionMode) -> Result<(), Error> {
+        let now = (self.epoch)();
+        let timeout = Duration::from_secs(timeout as _);
+
         match &mut self.state {
             State::Idle => {
-                self.state = State::Armed(ArmedCtx {
-                    session_mode,
-                    timeout,
-                    noc_state: NocState::NocNotRecvd,
-                })
+                // Arming with a timeout of 0 is a no-op
+                if !timeout.is_zero() {
+                    let max_expires_at =
+                        now + Duration::from_secs(MAX_CUMULATIVE_FAILSAFE_SECS as _);
+
+                    self.state = State::Armed(ArmedCtx {
+                        session_mode,
+                        expires_at: (now + timeout).min(max_expires_at),
+                        max_expires_at,
+                        noc_state: NocState::NocNotRecvd,
+                        network_changed: false,
+                    })
+                }
             }
             State::Armed(c) => {
                 if c.session_mode != session_mode {
@@ -69,50 +143,84 @@ impl FailSafe {
                     Err(ErrorCode::Invalid)?;
                 }
                 // re-arm
-                c.timeout = timeout;
+                c.expires_at = (now + timeout).min(c.max_expires_at);
             }
         }
         Ok(())
     }
 
-    pub fn disarm(&mut self, session_mode: SessionMode) -> Result<(), Error> {
-        match &mut self.state {
+    /// Disarms the fail-safe on CommissioningComplete, returning the changes to be committed
+    pub fn disarm(&mut self, session_mode: SessionMode) -> Result<PendingChanges, Error> {
+        match &self.state {
             State::Idle => {
                 error!("Received Fail-Safe Disarm without it being armed");
                 Err(ErrorCode::Invalid)?;
             }
-            State::Armed(c) => {
-                match c.noc_state {
-                    NocState::NocNotRecvd => Err(ErrorCode::Invalid)?,
-                    NocState::AddNocRecvd(idx) | NocState::UpdateNocRecvd(idx) => {
-                        if let SessionMode::Case(c) = session_mode {
-                            if c.fab_idx != idx {
-                                error!(
-                                    "Received disarm in separate session from previous Add/Update NOC"
-                                );
-                                Err(ErrorCode::Invalid)?;
-                            }
-                        } else {
-                            error!("Received disarm in a non-CASE session");
+            State::Armed(c) => match c.noc_state {
+                NocState::NocNotRecvd => Err(ErrorCode::Invalid)?,
+                NocState::AddNocRecvd(idx) | NocState::UpdateNocRecvd(idx, _) => {
+                    if let SessionMode::Case(c) = session_mode {
+                        if c.fab_idx != idx {
+                            error!(
+                                "Received disarm in separate session from previous Add/Update NOC"
+                            );
                             Err(ErrorCode::Invalid)?;
                         }
+                    } else {
+                        error!("Received disarm in a non-CASE session");
+                        Err(ErrorCode::Invalid)?;
                     }
                 }
-                self.state = State::Idle;
-            }
+            },
+        }
+
+        Ok(self.take_changes())
+    }
+
+    /// When the fail-safe expires, if it is armed
+    pub fn deadline(&self) -> Option<Duration> {
+        match &self.state {
+            State::Idle => None,
+            State::Armed(c) => Some(c.expires_at),
+        }
+    }
+
+    /// Disarms the fail-safe if it expired, returning the changes to be rolled back
+    pub fn expire(&mut self) -> Option<PendingChanges> {
+        if self
+            .deadline()
+            .map(|deadline| deadline <= (self.epoch)())
+            .unwrap_or(false)
+        {
+            info!("Fail-Safe expired");
+
+            Some(self.take_changes())
+        } else {
+            None
         }
-        Ok(())
     }
 
     pub fn is_armed(&self) -> bool {
-        self.state != State::Idle
+        matches!(self.state, State::Armed(_))
+    }
+
+    /// The fabric of the session which armed the fail-safe; `None` if it is not armed,
+    /// or if it was armed over PASE
+    pub fn fab_idx(&self) -> Option<u8> {
+        match &self.state {
+            State::Armed(ArmedCtx {
+                session_mode: SessionMode::Case(c),
+                ..
+            }) => Some(c.fab_idx),
+            _ => None,
+        }
     }
 
     pub fn record_add_noc(&mut self, fabric_index: u8) -> Result<(), Error> {
         match &mut self.state {
             State::Idle => Err(ErrorCode::Invalid.into()),
             State::Armed(c) => {
-                if c.noc_state == NocState::NocNotRecvd {
+                if matches!(c.noc_state, NocState::NocNotRecvd) {
                     c.noc_state = NocState::AddNocRecvd(fabric_index);
                     Ok(())
                 } else {
@@ -122,17 +230,139 @@ impl FailSafe {
         }
     }
 
+    /// Records that the fabric at `fabric_index` was updated; `prev` is the fabric
+    /// as it was before the update, which is restored if the fail-safe expires
+    pub fn record_update_noc(&mut self, fabric_index: u8, prev: Fabric) -> Result<(), Error> {
+        match &mut self.state {
+            State::Idle => Err(ErrorCode::Invalid.into()),
+            State::Armed(c) => {
+                if matches!(c.noc_state, NocState::NocNotRecvd) {
+                    c.noc_state = NocState::UpdateNocRecvd(fabric_index, alloc!(prev));
+                    Ok(())
+                } else {
+                    Err(ErrorCode::Invalid.into())
+                }
+            }
+        }
+    }
+
+    /// Records that the network configuration changed, e.g. with the AddOrUpdate*Network or
+    /// ConnectNetwork commands of the Network Commissioning cluster
+    pub fn record_network_change(&mut self) -> Result<(), Error> {
+        match &mut self.state {
+            State::Idle => Err(ErrorCode::Invalid.into()),
+            State::Armed(c) => {
+                c.network_changed = true;
+                Ok(())
+            }
+        }
+    }
+
     pub fn allow_noc_change(&self) -> Result<bool, Error> {
         let allow = match &self.state {
             State::Idle => false,
-            State::Armed(c) => c.noc_state == NocState::NocNotRecvd,
+            State::Armed(c) => matches!(c.noc_state, NocState::NocNotRecvd),
         };
         Ok(allow)
     }
+
+    fn take_changes(&mut self) -> PendingChanges {
+        match core::mem::replace(&mut self.state, State::Idle) {
+            State::Idle => Default::default(),
+            State::Armed(ctx) => PendingChanges::from_ctx(ctx),
+        }
+    }
 }
 
-impl Default for FailSafe {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, time::Duration};
+
+    use crate::transport::session::{CaseDetails, SessionMode};
+
+    use super::{FailSafe, MAX_CUMULATIVE_FAILSAFE_SECS};
+
+    std::thread_local! {
+        static NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+    }
+
+    fn test_epoch() -> Duration {
+        NOW.with(Cell::get)
+    }
+
+    fn set_now(secs: u64) {
+        NOW.with(|now| now.set(Duration::from_secs(secs)));
+    }
+
+    #[test]
+    fn test_failsafe_expiry() {
+        set_now(0);
+
+        let mut failsafe = FailSafe::new(test_epoch);
+        assert!(failsafe.expire().is_none());
+
+        // Arming with 0 is a no-op
+        failsafe.arm(0, SessionMode::Pase).unwrap();
+        assert!(!failsafe.is_armed());
+
+        failsafe.arm(60, SessionMode::Pase).unwrap();
+        assert_eq!(failsafe.deadline(), Some(Duration::from_secs(60)));
+        assert_eq!(failsafe.fab_idx(), None);
+
+        // Re-arming from another kind of session is not allowed
+        let case = SessionMode::Case(CaseDetails::new(1, &Default::default()));
+        assert!(failsafe.arm(60, case).is_err());
+
+        failsafe.record_add_noc(1).unwrap();
+        assert!(!failsafe.allow_noc_change().unwrap());
+
+        // Re-arming cannot extend the fail-safe beyond the maximum cumulative time
+        set_now(50);
+        failsafe.arm(300, SessionMode::Pase).unwrap();
+        assert_eq!(
+            failsafe.deadline(),
+            Some(Duration::from_secs(MAX_CUMULATIVE_FAILSAFE_SECS as _))
+        );
+
+        set_now(MAX_CUMULATIVE_FAILSAFE_SECS as u64 - 1);
+        assert!(failsafe.expire().is_none());
+
+        set_now(MAX_CUMULATIVE_FAILSAFE_SECS as u64);
+        let changes = failsafe.expire().unwrap();
+        assert_eq!(changes.added_fabric, Some(1));
+        assert!(changes.updated_fabric.is_none());
+        assert!(!failsafe.is_armed());
+        assert!(failsafe.expire().is_none());
+    }
+
+    #[test]
+    fn test_failsafe_rearm_with_zero_expires() {
+        set_now(10);
+
+        let mut failsafe = FailSafe::new(test_epoch);
+        failsafe.arm(60, SessionMode::Pase).unwrap();
+        failsafe.record_network_change().unwrap();
+
+        failsafe.arm(0, SessionMode::Pase).unwrap();
+        let changes = failsafe.expire().unwrap();
+        assert!(changes.network_changed);
+        assert!(changes.added_fabric.is_none());
+    }
+
+    #[test]
+    fn test_failsafe_disarm_requires_noc() {
+        set_now(0);
+
+        let mut failsafe = FailSafe::new(test_epoch);
+        let case = SessionMode::Case(CaseDetails::new(2, &Default::default()));
+
+        failsafe.arm(60, case.clone()).unwrap();
+        assert_eq!(failsafe.fab_idx(), Some(2));
+        assert!(failsafe.disarm(case.clone()).is_err());
+
+        failsafe.record_add_noc(2).unwrap();
+        let changes = failsafe.disarm(case).unwrap();
+        assert_eq!(changes.added_fabric, Some(2));
+        assert!(!failsafe.is_armed());
     }
 }
@@ -18,7 +18,7 @@
 use core::cell::RefCell;
 
 use crate::data_model::objects::*;
-use crate::data_model::sdm::failsafe::FailSafe;
+use crate::data_model::sdm::failsafe::{FailSafe, MAX_CUMULATIVE_FAILSAFE_SECS};
 use crate::tlv::{FromTLV, TLVElement, ToTLV, UtfStr};
 use crate::transport::exchange::Exchange;
 use crate::utils::rand::Rand;
@@ -86,6 +86,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::BreadCrumb as u16,
             Access::READ.union(Access::WRITE).union(Access::NEED_ADMIN),
@@ -108,10 +110,16 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[
-        Commands::ArmFailsafe as _,
-        Commands::SetRegulatoryConfig as _,
-        Commands::CommissioningComplete as _,
+        Command::new(Commands::ArmFailsafe as _, Access::WA),
+        Command::new(Commands::SetRegulatoryConfig as _, Access::WA),
+        Command::new(Commands::CommissioningComplete as _, Access::WA),
     ],
+    generated_commands: &[
+        RespCommands::ArmFailsafeResp as _,
+        RespCommands::SetRegulatoryConfigResp as _,
+        RespCommands::CommissioningCompleteResp as _,
+    ],
+    events: &[],
 };
 
 #[derive(FromTLV, ToTLV)]
@@ -140,7 +148,7 @@ impl<'a> GenCommCluster<'a> {
             // TODO: Arch-Specific
             basic_comm_info: BasicCommissioningInfo {
                 expiry_len: 120,
-                max_cmltv_failsafe_secs: 120,
+                max_cmltv_failsafe_secs: MAX_CUMULATIVE_FAILSAFE_SECS,
             },
         }
     }
@@ -278,13 +286,16 @@ impl<'a> GenCommCluster<'a> {
 
         // AddNOC or UpdateNOC must have happened, and that too for the same fabric
         // scope that is for this session
-        if self
-            .failsafe
-            .borrow_mut()
-            .disarm(exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?)
-            .is_err()
-        {
-            status = CommissioningError::ErrInvalidAuth as u8;
+        if status == CommissioningError::Ok as u8 {
+            let changes = self
+                .failsafe
+                .borrow_mut()
+                .disarm(exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?);
+
+            match changes {
+                Ok(changes) => exchange.matter.commit_failsafe(changes),
+                Err(_) => status = CommissioningError::ErrInvalidAuth as u8,
+            }
         }
 
         let cmd_data = CommonResponse {
@@ -14,11 +14,13 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
+use core::cell::Cell;
+
 use crate::{
     attribute_enum, cmd_enter, command_enum,
     data_model::objects::*,
     error::{Error, ErrorCode},
-    tlv::TLVElement,
+    tlv::{FromTLV, OctetStr, TLVElement},
     transport::exchange::Exchange,
     utils::rand::Rand,
 };
@@ -51,6 +53,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::NetworkInterfaces as u16,
             Access::RV,
@@ -67,17 +71,98 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Quality::NONE,
         ),
     ],
-    commands: &[CommandsDiscriminants::TestEventTrigger as _],
+    commands: &[Command::new(
+        CommandsDiscriminants::TestEventTrigger as _,
+        Access::WM,
+    )],
+    generated_commands: &[],
+    events: &[],
 };
 
-pub struct GenDiagCluster {
+/// The handler of the TestEventTrigger command, through which the certification tests inject
+/// simulated conditions - e.g. a smoke alarm, or the expiry of an ICD check-in - into the device
+/// (see [`crate::Matter::set_test_event_trigger_handler`])
+pub trait TestEventTriggerHandler {
+    /// The key the TestEventTrigger commands need to carry. A key of zeros disables the triggers.
+    fn enable_key(&self) -> &[u8; 16];
+
+    /// Triggers the test event `trigger`, whose meaning is defined by the test plans. Fails
+    /// with `ErrorCode::InvalidCommand` if the device does not support the trigger.
+    fn trigger(&self, trigger: u64) -> Result<(), Error>;
+}
+
+impl<T> TestEventTriggerHandler for &T
+where
+    T: TestEventTriggerHandler,
+{
+    fn enable_key(&self) -> &[u8; 16] {
+        (**self).enable_key()
+    }
+
+    fn trigger(&self, trigger: u64) -> Result<(), Error> {
+        (**self).trigger(trigger)
+    }
+}
+
+/// The - optional - handler of the test event triggers of a node
+pub struct TestEventTriggers(Cell<Option<&'static dyn TestEventTriggerHandler>>);
+
+impl TestEventTriggers {
+    pub const fn new() -> Self {
+        Self(Cell::new(None))
+    }
+
+    pub fn set(&self, handler: Option<&'static dyn TestEventTriggerHandler>) {
+        self.0.set(handler);
+    }
+
+    /// Whether the node has a handler of the test event triggers, with a non-zero enable key
+    pub fn enabled(&self) -> bool {
+        self.0
+            .get()
+            .map(|handler| handler.enable_key().iter().any(|b| *b != 0))
+            .unwrap_or(false)
+    }
+
+    /// Triggers the test event `trigger` if `enable_key` is the enable key of the handler
+    pub fn trigger(&self, enable_key: &[u8], trigger: u64) -> Result<(), Error> {
+        let handler = self
+            .0
+            .get()
+            .filter(|_| self.enabled())
+            .ok_or(ErrorCode::ConstraintError)?;
+
+        if handler.enable_key() != enable_key {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        handler.trigger(trigger)
+    }
+}
+
+impl Default for TestEventTriggers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct TestEventTriggerReq<'a> {
+    enable_key: OctetStr<'a>,
+    event_trigger: u64,
+}
+
+pub struct GenDiagCluster<'a> {
     data_ver: Dataver,
+    test_event_triggers: &'a TestEventTriggers,
 }
 
-impl GenDiagCluster {
-    pub fn new(rand: Rand) -> Self {
+impl<'a> GenDiagCluster<'a> {
+    pub fn new(test_event_triggers: &'a TestEventTriggers, rand: Rand) -> Self {
         Self {
             data_ver: Dataver::new(rand),
+            test_event_triggers,
         }
     }
 
@@ -88,6 +173,9 @@ impl GenDiagCluster {
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::RebootCount(codec) => codec.encode(writer, 1),
+                    Attributes::TestEventTriggersEnabled(codec) => {
+                        codec.encode(writer, self.test_event_triggers.enabled())
+                    }
                     _ => Err(ErrorCode::AttributeNotFound.into()),
                 }
             }
@@ -108,12 +196,18 @@ impl GenDiagCluster {
         &self,
         _exchange: &Exchange,
         cmd: &CmdDetails,
-        _data: &TLVElement,
+        data: &TLVElement,
         _encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
             Commands::TestEventTrigger => {
-                cmd_enter!("TestEventTrigger: Not yet supported");
+                cmd_enter!("TestEventTrigger");
+
+                let req = TestEventTriggerReq::from_tlv(data)?;
+                info!("Test event trigger: {:#x}", req.event_trigger);
+
+                self.test_event_triggers
+                    .trigger(req.enable_key.0, req.event_trigger)?;
             }
         }
 
@@ -123,7 +217,7 @@ impl GenDiagCluster {
     }
 }
 
-impl Handler for GenDiagCluster {
+impl<'a> Handler for GenDiagCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         GenDiagCluster::read(self, attr, encoder)
     }
@@ -144,9 +238,9 @@ impl Handler for GenDiagCluster {
 }
 
 // TODO: Might be removed once the `on` member is externalized
-impl NonBlockingHandler for GenDiagCluster {}
+impl<'a> NonBlockingHandler for GenDiagCluster<'a> {}
 
-impl ChangeNotifier<()> for GenDiagCluster {
+impl<'a> ChangeNotifier<()> for GenDiagCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
     }
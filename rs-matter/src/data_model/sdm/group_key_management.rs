@@ -14,15 +14,24 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
+use core::cell::RefCell;
+
 use crate::{
     attribute_enum, cmd_enter, command_enum,
+    crypto::SYMM_KEY_LEN_BYTES,
     data_model::objects::*,
     error::{Error, ErrorCode},
-    tlv::TLVElement,
+    fabric::FabricMgr,
+    group_keys::{
+        GroupKeyMapEntry, GroupKeyMgr, GroupKeySecurityPolicy, GroupKeySet, IPK_KEY_SET_ID,
+        MAX_EPOCH_KEYS, MAX_GROUPS_PER_FABRIC, MAX_GROUP_KEY_SETS_PER_FABRIC,
+    },
+    interaction_model::messages::ib::{attr_list_write, ListOperation},
+    tlv::{FromTLV, Nullable, OctetStr, TLVElement, TagType, ToTLV},
     transport::exchange::Exchange,
     utils::rand::Rand,
 };
-use log::info;
+use log::{error, info};
 use strum::{EnumDiscriminants, FromRepr};
 
 pub const ID: u32 = 0x003F;
@@ -42,16 +51,27 @@ attribute_enum!(Attributes);
 #[repr(u32)]
 pub enum Commands {
     KeySetWrite = 0x0,
+    KeySetRead = 0x1,
+    KeySetRemove = 0x3,
+    KeySetReadAllIndices = 0x4,
 }
 
 command_enum!(Commands);
 
+#[repr(u16)]
+pub enum RespCommands {
+    KeySetReadResp = 0x2,
+    KeySetReadAllIndicesResp = 0x5,
+}
+
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
     feature_map: 0,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::GroupKeyMap as u16,
             Access::RWFVM,
@@ -73,17 +93,131 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Quality::FIXED,
         ),
     ],
-    commands: &[CommandsDiscriminants::KeySetWrite as _],
+    commands: &[
+        Command::new(CommandsDiscriminants::KeySetWrite as _, Access::WA),
+        Command::new(CommandsDiscriminants::KeySetRead as _, Access::WA),
+        Command::new(CommandsDiscriminants::KeySetRemove as _, Access::WA),
+        Command::new(CommandsDiscriminants::KeySetReadAllIndices as _, Access::WA),
+    ],
+    generated_commands: &[
+        RespCommands::KeySetReadResp as _,
+        RespCommands::KeySetReadAllIndicesResp as _,
+    ],
+    events: &[],
 };
 
-pub struct GrpKeyMgmtCluster {
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(lifetime = "'a")]
+struct GroupKeySetStruct<'a> {
+    key_set_id: u16,
+    policy: u8,
+    epoch_key0: Nullable<OctetStr<'a>>,
+    epoch_start_time0: Nullable<u64>,
+    epoch_key1: Nullable<OctetStr<'a>>,
+    epoch_start_time1: Nullable<u64>,
+    epoch_key2: Nullable<OctetStr<'a>>,
+    epoch_start_time2: Nullable<u64>,
+}
+
+impl<'a> GroupKeySetStruct<'a> {
+    /// The key set as read back: the epoch keys themselves are never disclosed
+    fn new(key_set: &GroupKeySet) -> Self {
+        let mut start_times = [Nullable::Null, Nullable::Null, Nullable::Null];
+        for (start_time, key) in start_times.iter_mut().zip(key_set.epoch_keys()) {
+            *start_time = Nullable::NotNull(key.start_time);
+        }
+
+        let [epoch_start_time0, epoch_start_time1, epoch_start_time2] = start_times;
+
+        Self {
+            key_set_id: key_set.key_set_id,
+            policy: key_set.policy,
+            epoch_key0: Nullable::Null,
+            epoch_start_time0,
+            epoch_key1: Nullable::Null,
+            epoch_start_time1,
+            epoch_key2: Nullable::Null,
+            epoch_start_time2,
+        }
+    }
+
+    /// The `(epoch key, start time)` pairs of a written key set. The first one is mandatory,
+    /// and the following ones - if any - have to start later than the previous one
+    fn epoch_keys(&self) -> Result<heapless::Vec<(&'a [u8], u64), MAX_EPOCH_KEYS>, Error> {
+        let mut epoch_keys = heapless::Vec::<_, MAX_EPOCH_KEYS>::new();
+        let mut ended = false;
+
+        for (key, start_time) in [
+            (&self.epoch_key0, &self.epoch_start_time0),
+            (&self.epoch_key1, &self.epoch_start_time1),
+            (&self.epoch_key2, &self.epoch_start_time2),
+        ] {
+            match (key, start_time) {
+                (Nullable::NotNull(key), Nullable::NotNull(start_time)) if !ended => {
+                    if key.0.len() != SYMM_KEY_LEN_BYTES {
+                        Err(ErrorCode::ConstraintError)?;
+                    }
+
+                    if matches!(epoch_keys.last(), Some((_, last)) if start_time <= last) {
+                        Err(ErrorCode::InvalidCommand)?;
+                    }
+
+                    epoch_keys.push((key.0, *start_time)).unwrap();
+                }
+                (Nullable::Null, Nullable::Null) => ended = true,
+                _ => Err(ErrorCode::InvalidCommand)?,
+            }
+        }
+
+        if epoch_keys.is_empty() {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        Ok(epoch_keys)
+    }
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct KeySetWriteReq<'a> {
+    group_key_set: GroupKeySetStruct<'a>,
+}
+
+#[derive(FromTLV)]
+struct KeySetReq {
+    key_set_id: u16,
+}
+
+#[derive(ToTLV)]
+struct KeySetReadResp<'a> {
+    group_key_set: GroupKeySetStruct<'a>,
+}
+
+#[derive(FromTLV, ToTLV)]
+#[tlvargs(start = 1)]
+struct GroupKeyMapStruct {
+    group_id: u16,
+    key_set_id: u16,
+    #[tagval(0xFE)]
+    fab_idx: Option<u8>,
+}
+
+pub struct GrpKeyMgmtCluster<'a> {
     data_ver: Dataver,
+    fabric_mgr: &'a RefCell<FabricMgr>,
+    group_key_mgr: &'a RefCell<GroupKeyMgr>,
 }
 
-impl GrpKeyMgmtCluster {
-    pub fn new(rand: Rand) -> Self {
+impl<'a> GrpKeyMgmtCluster<'a> {
+    pub fn new(
+        fabric_mgr: &'a RefCell<FabricMgr>,
+        group_key_mgr: &'a RefCell<GroupKeyMgr>,
+        rand: Rand,
+    ) -> Self {
         Self {
             data_ver: Dataver::new(rand),
+            fabric_mgr,
+            group_key_mgr,
         }
     }
 
@@ -93,9 +227,25 @@ impl GrpKeyMgmtCluster {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
-                    Attributes::MaxGroupsPerFabric(codec) => codec.encode(writer, 1),
-                    Attributes::MaxGroupKeysPerFabric(codec) => codec.encode(writer, 1),
-                    _ => Err(ErrorCode::AttributeNotFound.into()),
+                    Attributes::GroupKeyMap(_) => writer.set_list(
+                        self.group_key_mgr
+                            .borrow()
+                            .key_map()
+                            .filter(|entry| attr.is_fabric_visible(entry.fab_idx))
+                            .map(|entry| GroupKeyMapStruct {
+                                group_id: entry.group_id,
+                                key_set_id: entry.key_set_id,
+                                fab_idx: Some(entry.fab_idx),
+                            }),
+                    ),
+                    // Empty until the Groups cluster is supported
+                    Attributes::GroupTable(_) => writer.set_list(core::iter::empty::<u16>()),
+                    Attributes::MaxGroupsPerFabric(codec) => {
+                        codec.encode(writer, MAX_GROUPS_PER_FABRIC as _)
+                    }
+                    Attributes::MaxGroupKeysPerFabric(codec) => {
+                        codec.encode(writer, MAX_GROUP_KEY_SETS_PER_FABRIC as _)
+                    }
                 }
             }
         } else {
@@ -103,34 +253,191 @@ impl GrpKeyMgmtCluster {
         }
     }
 
-    pub fn write(&self, _attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        let _data = data.with_dataver(self.data_ver.get())?;
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        match attr.attr_id.try_into()? {
+            Attributes::GroupKeyMap(_) => {
+                attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
+                    self.write_key_map_attr(&op, data, attr.fab_idx)
+                })?;
 
-        self.data_ver.changed();
+                self.data_ver.changed();
 
-        Ok(())
+                Ok(())
+            }
+            _ => {
+                error!("Attribute not yet supported: this shouldn't happen");
+                Err(ErrorCode::AttributeNotFound.into())
+            }
+        }
     }
 
     pub fn invoke(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         cmd: &CmdDetails,
-        _data: &TLVElement,
-        _encoder: CmdDataEncoder,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
+        // All commands of the cluster are fabric-scoped
+        let fab_idx = exchange
+            .with_session(|sess| Ok(sess.get_local_fabric_idx()))?
+            .ok_or(ErrorCode::UnsupportedAccess)?;
+
         match cmd.cmd_id.try_into()? {
-            Commands::KeySetWrite => {
-                cmd_enter!("KeySetWrite: Not yet supported");
+            Commands::KeySetWrite => self.handle_command_keyset_write(fab_idx, data),
+            Commands::KeySetRead => self.handle_command_keyset_read(fab_idx, data, encoder),
+            Commands::KeySetRemove => self.handle_command_keyset_remove(fab_idx, data),
+            Commands::KeySetReadAllIndices => {
+                self.handle_command_keyset_read_all_indices(fab_idx, encoder)
+            }
+        }
+    }
+
+    fn write_key_map_attr(
+        &self,
+        op: &ListOperation,
+        data: &TLVElement,
+        fab_idx: u8,
+    ) -> Result<(), Error> {
+        info!("Performing Group Key Map operation {:?}", op);
+        match op {
+            ListOperation::AddItem | ListOperation::EditItem(_) => {
+                let entry = GroupKeyMapStruct::from_tlv(data)?;
+
+                // The entry always belongs to the accessing fabric
+                let entry = GroupKeyMapEntry {
+                    fab_idx,
+                    group_id: entry.group_id,
+                    key_set_id: entry.key_set_id,
+                };
+
+                if let ListOperation::EditItem(index) = op {
+                    self.group_key_mgr
+                        .borrow_mut()
+                        .edit_key_map_entry(*index, entry)
+                } else {
+                    self.group_key_mgr.borrow_mut().add_key_map_entry(entry)
+                }
+            }
+            ListOperation::DeleteItem(index) => self
+                .group_key_mgr
+                .borrow_mut()
+                .delete_key_map_entry(*index, fab_idx),
+            ListOperation::DeleteList => {
+                self.group_key_mgr.borrow_mut().delete_key_map(fab_idx);
+                Ok(())
             }
         }
+    }
+
+    fn handle_command_keyset_write(&self, fab_idx: u8, data: &TLVElement) -> Result<(), Error> {
+        cmd_enter!("KeySetWrite");
+
+        let req = KeySetWriteReq::from_tlv(data).map_err(Error::map_invalid_command)?;
+        let key_set = &req.group_key_set;
+
+        // Only the TrustFirst policy is supported, as the CacheAndSync feature is not
+        let policy = GroupKeySecurityPolicy::try_from(key_set.policy)?;
+        if policy != GroupKeySecurityPolicy::TrustFirst {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let epoch_keys = key_set.epoch_keys()?;
+
+        let compressed_id = self
+            .fabric_mgr
+            .borrow()
+            .get_fabric(fab_idx as _)?
+            .ok_or(ErrorCode::NotFound)?
+            .compressed_id()?;
+
+        self.group_key_mgr
+            .borrow_mut()
+            .set_key_set(GroupKeySet::new(
+                fab_idx,
+                key_set.key_set_id,
+                policy,
+                &epoch_keys,
+                &compressed_id,
+            )?)?;
+
+        if key_set.key_set_id == IPK_KEY_SET_ID {
+            // The IPK of the fabric is the epoch key of its key set 0 currently in effect
+            let (ipk, _) = epoch_keys
+                .iter()
+                .max_by_key(|(_, start_time)| *start_time)
+                .unwrap();
+
+            self.fabric_mgr.borrow_mut().set_ipk(fab_idx, ipk)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_command_keyset_read(
+        &self,
+        fab_idx: u8,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        cmd_enter!("KeySetRead");
+
+        let req = KeySetReq::from_tlv(data).map_err(Error::map_invalid_command)?;
+
+        let group_key_mgr = self.group_key_mgr.borrow();
+        let key_set = group_key_mgr
+            .key_set(fab_idx, req.key_set_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        encoder
+            .with_command(RespCommands::KeySetReadResp as _)?
+            .set(KeySetReadResp {
+                group_key_set: GroupKeySetStruct::new(key_set),
+            })
+    }
+
+    fn handle_command_keyset_remove(&self, fab_idx: u8, data: &TLVElement) -> Result<(), Error> {
+        cmd_enter!("KeySetRemove");
+
+        let req = KeySetReq::from_tlv(data).map_err(Error::map_invalid_command)?;
+
+        // The IPK cannot be removed
+        if req.key_set_id == IPK_KEY_SET_ID {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        self.group_key_mgr
+            .borrow_mut()
+            .remove_key_set(fab_idx, req.key_set_id)?;
 
+        // The Group Key Map entries of the key set are gone as well
         self.data_ver.changed();
 
         Ok(())
     }
+
+    fn handle_command_keyset_read_all_indices(
+        &self,
+        fab_idx: u8,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        cmd_enter!("KeySetReadAllIndices");
+
+        let mut writer = encoder.with_command(RespCommands::KeySetReadAllIndicesResp as _)?;
+
+        writer.start_struct(CmdDataWriter::TAG)?;
+        writer.start_array(TagType::Context(0))?;
+        for key_set in self.group_key_mgr.borrow().key_sets(fab_idx) {
+            writer.u16(TagType::Anonymous, key_set.key_set_id)?;
+        }
+        writer.end_container()?;
+        writer.end_container()?;
+
+        writer.complete()
+    }
 }
 
-impl Handler for GrpKeyMgmtCluster {
+impl<'a> Handler for GrpKeyMgmtCluster<'a> {
     fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
         GrpKeyMgmtCluster::read(self, attr, encoder)
     }
@@ -150,10 +457,9 @@ impl Handler for GrpKeyMgmtCluster {
     }
 }
 
-// TODO: Might be removed once the `on` member is externalized
-impl NonBlockingHandler for GrpKeyMgmtCluster {}
+impl<'a> NonBlockingHandler for GrpKeyMgmtCluster<'a> {}
 
-impl ChangeNotifier<()> for GrpKeyMgmtCluster {
+impl<'a> ChangeNotifier<()> for GrpKeyMgmtCluster<'a> {
     fn consume_change(&mut self) -> Option<()> {
         self.data_ver.consume_change(())
     }
@@ -0,0 +1,160 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Localization Configuration cluster, through which the controllers set the locale - out of
+//! the locales the device supports - its user interface uses
+
+use core::cell::Cell;
+
+use strum::FromRepr;
+
+use crate::{
+    attribute_enum,
+    data_model::objects::*,
+    error::{Error, ErrorCode},
+    tlv::UtfStr,
+    utils::rand::Rand,
+};
+
+pub const ID: u32 = 0x002B;
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    ActiveLocale(AttrUtfType) = 0,
+    SupportedLocales(()) = 1,
+}
+
+attribute_enum!(Attributes);
+
+pub enum AttributesDiscriminants {
+    ActiveLocale = 0,
+    SupportedLocales = 1,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(
+            AttributesDiscriminants::ActiveLocale as u16,
+            Access::RWVM,
+            Quality::N,
+        )
+        .with_constraint(Constraint::Length(0, 35)),
+        Attribute::new(
+            AttributesDiscriminants::SupportedLocales as u16,
+            Access::RV,
+            Quality::FIXED,
+        ),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[],
+};
+
+pub struct LocalizationConfigurationCluster<'a> {
+    data_ver: Dataver,
+    /// The locales the device supports - as IETF BCP 47 language tags, e.g. `en-US` - the
+    /// first of which is active until a controller sets another one
+    supported_locales: &'a [&'a str],
+    active_locale: Cell<usize>,
+}
+
+impl<'a> LocalizationConfigurationCluster<'a> {
+    pub fn new(supported_locales: &'a [&'a str], rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            supported_locales,
+            active_locale: Cell::new(0),
+        }
+    }
+
+    pub fn active_locale(&self) -> &'a str {
+        self.supported_locales
+            .get(self.active_locale.get())
+            .copied()
+            .unwrap_or("")
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                CLUSTER.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::ActiveLocale(codec) => codec.encode(writer, self.active_locale()),
+                    Attributes::SupportedLocales(_) => writer.set_list(
+                        self.supported_locales
+                            .iter()
+                            .map(|locale| UtfStr::new(locale.as_bytes())),
+                    ),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::ActiveLocale(codec) => {
+                let locale = codec
+                    .decode(data)
+                    .map_err(|_| Error::new(ErrorCode::InvalidAction))?;
+
+                // Only the supported locales can be activated
+                let index = self
+                    .supported_locales
+                    .iter()
+                    .position(|supported| *supported == locale)
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                self.active_locale.set(index);
+            }
+            _ => return Err(Error::new(ErrorCode::InvalidAction)),
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for LocalizationConfigurationCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        LocalizationConfigurationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        LocalizationConfigurationCluster::write(self, attr, data)
+    }
+}
+
+impl<'a> NonBlockingHandler for LocalizationConfigurationCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for LocalizationConfigurationCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
@@ -22,5 +22,8 @@ pub mod failsafe;
 pub mod general_commissioning;
 pub mod general_diagnostics;
 pub mod group_key_management;
+pub mod localization_configuration;
 pub mod noc;
 pub mod nw_commissioning;
+pub mod time_format_localization;
+pub mod unit_localization;
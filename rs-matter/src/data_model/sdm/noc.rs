@@ -19,22 +19,23 @@ use core::cell::RefCell;
 
 use crate::acl::{AclEntry, AclMgr, AuthMode};
 use crate::cert::{Cert, MAX_CERT_TLV_LEN};
-use crate::crypto::{self, KeyPair};
+use crate::crypto::{self, keystore::OpKey};
 use crate::data_model::objects::*;
-use crate::data_model::sdm::dev_att;
-use crate::fabric::{Fabric, FabricMgr, MAX_SUPPORTED_FABRICS};
+use crate::fabric::{Fabric, FabricMgr};
+use crate::group_keys::{GroupKeySecurityPolicy, GroupKeySet, IPK_KEY_SET_ID};
 use crate::mdns::Mdns;
+use crate::secure_channel::case::Case;
 use crate::tlv::{FromTLV, OctetStr, TLVElement, TLVWriter, TagType, ToTLV, UtfStr};
 use crate::transport::exchange::Exchange;
-use crate::transport::session::SessionMode;
+use crate::transport::session::{NocCatIds, SessionMode};
 use crate::utils::epoch::Epoch;
 use crate::utils::rand::Rand;
 use crate::utils::writebuf::WriteBuf;
 use crate::{attribute_enum, cmd_enter, command_enum, error::*};
-use log::{error, info};
+use log::{error, info, warn};
 use strum::{EnumDiscriminants, FromRepr};
 
-use super::dev_att::{DataType, DevAttDataFetcher};
+use super::dev_att::DacProvider;
 use super::failsafe::FailSafe;
 
 // Node Operational Credentials Cluster
@@ -80,6 +81,8 @@ const MAX_CSR_LEN: usize = 300;
 // As defined in the Matter Spec
 const RESP_MAX: usize = 900;
 
+const ATTESTATION_NONCE_LEN: usize = 32;
+
 pub const ID: u32 = 0x003E;
 
 #[derive(FromRepr)]
@@ -89,6 +92,7 @@ pub enum Commands {
     CertChainReq = 0x02,
     CSRReq = 0x04,
     AddNOC = 0x06,
+    UpdateNOC = 0x07,
     UpdateFabricLabel = 0x09,
     RemoveFabric = 0x0a,
     AddTrustedRootCert = 0x0b,
@@ -123,6 +127,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::CurrentFabricIndex as u16,
             Access::RV,
@@ -145,25 +151,33 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[
-        Commands::AttReq as _,
-        Commands::CertChainReq as _,
-        Commands::CSRReq as _,
-        Commands::AddNOC as _,
-        Commands::UpdateFabricLabel as _,
-        Commands::RemoveFabric as _,
-        Commands::AddTrustedRootCert as _,
+        Command::new(Commands::AttReq as _, Access::WA),
+        Command::new(Commands::CertChainReq as _, Access::WA),
+        Command::new(Commands::CSRReq as _, Access::WA),
+        Command::new(Commands::AddNOC as _, Access::WA),
+        Command::new(Commands::UpdateNOC as _, Access::WA),
+        Command::new(Commands::UpdateFabricLabel as _, Access::WA),
+        Command::new(Commands::RemoveFabric as _, Access::WA),
+        Command::new(Commands::AddTrustedRootCert as _, Access::WA),
+    ],
+    generated_commands: &[
+        RespCommands::AttReqResp as _,
+        RespCommands::CertChainResp as _,
+        RespCommands::CSRResp as _,
+        RespCommands::NOCResp as _,
     ],
+    events: &[],
 };
 
 pub struct NocData {
-    pub key_pair: KeyPair,
+    pub op_key: OpKey,
     pub root_ca: heapless::Vec<u8, { MAX_CERT_TLV_LEN }>,
 }
 
 impl NocData {
-    pub fn new(key_pair: KeyPair) -> Self {
+    pub fn new(op_key: OpKey) -> Self {
         Self {
-            key_pair,
+            op_key,
             root_ca: heapless::Vec::new(),
         }
     }
@@ -191,6 +205,13 @@ struct AddNocReq<'a> {
     vendor_id: u16,
 }
 
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a")]
+struct UpdateNocReq<'a> {
+    noc_value: OctetStr<'a>,
+    icac_value: Option<OctetStr<'a>>,
+}
+
 #[derive(FromTLV)]
 #[tlvargs(lifetime = "'a")]
 struct CommonReq<'a> {
@@ -217,7 +238,7 @@ pub struct NocCluster<'a> {
     data_ver: Dataver,
     epoch: Epoch,
     rand: Rand,
-    dev_att: &'a dyn DevAttDataFetcher,
+    dev_att: &'a dyn DacProvider,
     fabric_mgr: &'a RefCell<FabricMgr>,
     acl_mgr: &'a RefCell<AclMgr>,
     failsafe: &'a RefCell<FailSafe>,
@@ -226,7 +247,7 @@ pub struct NocCluster<'a> {
 
 impl<'a> NocCluster<'a> {
     pub fn new(
-        dev_att: &'a dyn DevAttDataFetcher,
+        dev_att: &'a dyn DacProvider,
         fabric_mgr: &'a RefCell<FabricMgr>,
         acl_mgr: &'a RefCell<AclMgr>,
         failsafe: &'a RefCell<FailSafe>,
@@ -253,13 +274,14 @@ impl<'a> NocCluster<'a> {
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::SupportedFabrics(codec) => {
-                        codec.encode(writer, MAX_SUPPORTED_FABRICS as _)
+                        codec.encode(writer, self.fabric_mgr.borrow().capacity() as _)
                     }
                     Attributes::CurrentFabricIndex(codec) => codec.encode(writer, attr.fab_idx),
                     Attributes::Fabrics(_) => {
                         writer.start_array(AttrDataWriter::TAG)?;
                         self.fabric_mgr.borrow().for_each(|entry, fab_idx| {
-                            if !attr.fab_filter || attr.fab_idx == fab_idx {
+                            // No field of a fabric descriptor is fabric-sensitive
+                            if attr.is_fabric_visible(fab_idx) {
                                 let root_ca_cert = entry.get_root_ca()?;
 
                                 entry
@@ -296,6 +318,7 @@ impl<'a> NocCluster<'a> {
     ) -> Result<(), Error> {
         match cmd.cmd_id.try_into()? {
             Commands::AddNOC => self.handle_command_addnoc(exchange, data, encoder)?,
+            Commands::UpdateNOC => self.handle_command_updatenoc(exchange, data, encoder)?,
             Commands::CSRReq => self.handle_command_csrrequest(exchange, data, encoder)?,
             Commands::AddTrustedRootCert => {
                 self.handle_command_addtrustedrootcert(exchange, data)?
@@ -355,25 +378,35 @@ impl<'a> NocCluster<'a> {
         let noc_cert = Cert::new(r.noc_value.0).map_err(|_| NocStatus::InvalidNOC)?;
         info!("Received NOC as: {}", noc_cert);
 
+        let mut cat_ids: NocCatIds = Default::default();
+        noc_cert
+            .get_cat_ids(&mut cat_ids)
+            .map_err(|_| NocStatus::InvalidNOC)?;
+
         let noc = heapless::Vec::from_slice(r.noc_value.0).map_err(|_| NocStatus::InvalidNOC)?;
 
-        let icac = if let Some(icac_value) = r.icac_value {
-            if !icac_value.0.is_empty() {
-                let icac_cert = Cert::new(icac_value.0).map_err(|_| NocStatus::InvalidNOC)?;
-                info!("Received ICAC as: {}", icac_cert);
+        let icac_value = r.icac_value.filter(|icac_value| !icac_value.0.is_empty());
+        let icac_cert = icac_value
+            .as_ref()
+            .map(|icac_value| Cert::new(icac_value.0))
+            .transpose()
+            .map_err(|_| NocStatus::InvalidNOC)?;
+        if let Some(icac_cert) = &icac_cert {
+            info!("Received ICAC as: {}", icac_cert);
+        }
 
-                let icac =
-                    heapless::Vec::from_slice(icac_value.0).map_err(|_| NocStatus::InvalidNOC)?;
-                Some(icac)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let icac = icac_value
+            .map(|icac_value| heapless::Vec::from_slice(icac_value.0))
+            .transpose()
+            .map_err(|_| NocStatus::InvalidNOC)?;
+
+        {
+            let root_cert = Cert::new(&noc_data.root_ca).map_err(|_| NocStatus::InvalidNOC)?;
+            Self::update_last_known_good_time(exchange, &noc_cert, icac_cert.as_ref(), &root_cert)?;
+        }
 
         let fabric = Fabric::new(
-            noc_data.key_pair,
+            noc_data.op_key,
             noc_data.root_ca,
             icac,
             noc,
@@ -390,11 +423,159 @@ impl<'a> NocCluster<'a> {
 
         self.add_acl(fab_idx, r.case_admin_subject)?;
 
+        // The IPK is also the group key set 0 of the fabric
+        let ipk = GroupKeySet::new(
+            fab_idx,
+            IPK_KEY_SET_ID,
+            GroupKeySecurityPolicy::TrustFirst,
+            &[(r.ipk_value.0, 0)],
+            &self
+                .fabric_mgr
+                .borrow()
+                .get_fabric(fab_idx as _)?
+                .ok_or(NocStatus::InvalidFabricIndex)?
+                .compressed_id()?,
+        )?;
+        exchange
+            .matter
+            .group_key_mgr
+            .borrow_mut()
+            .set_key_set(ipk)?;
+
         self.failsafe.borrow_mut().record_add_noc(fab_idx)?;
 
         Ok(fab_idx)
     }
 
+    fn _handle_command_updatenoc(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+    ) -> Result<u8, NocError> {
+        // The NOC being updated is the one of the fabric of the accessing CASE session
+        let SessionMode::Case(c) =
+            exchange.with_session(|sess| Ok(sess.get_session_mode().clone()))?
+        else {
+            error!("UpdateNOC received in a non-CASE session");
+            Err(NocStatus::InvalidFabricIndex)?
+        };
+        let fab_idx = c.fab_idx;
+
+        let noc_data = exchange
+            .with_session_mut(|sess| Ok(sess.take_noc_data()))?
+            .ok_or(NocStatus::MissingCsr)?;
+
+        if !self
+            .failsafe
+            .borrow_mut()
+            .allow_noc_change()
+            .map_err(|_| NocStatus::InsufficientPrivlege)?
+        {
+            error!("UpdateNOC not allowed by Fail Safe");
+            Err(NocStatus::InsufficientPrivlege)?;
+        }
+
+        let r = UpdateNocReq::from_tlv(data).map_err(|_| NocStatus::InvalidNOC)?;
+
+        let noc_cert = Cert::new(r.noc_value.0).map_err(|_| NocStatus::InvalidNOC)?;
+        info!("Received NOC as: {}", noc_cert);
+
+        let mut cat_ids: NocCatIds = Default::default();
+        noc_cert
+            .get_cat_ids(&mut cat_ids)
+            .map_err(|_| NocStatus::InvalidNOC)?;
+
+        let icac_value = r.icac_value.filter(|icac_value| !icac_value.0.is_empty());
+        let icac_cert = icac_value
+            .as_ref()
+            .map(|icac_value| Cert::new(icac_value.0))
+            .transpose()
+            .map_err(|_| NocStatus::InvalidNOC)?;
+        if let Some(icac_cert) = &icac_cert {
+            info!("Received ICAC as: {}", icac_cert);
+        }
+
+        let noc = heapless::Vec::from_slice(r.noc_value.0).map_err(|_| NocStatus::InvalidNOC)?;
+        let icac = icac_value
+            .map(|icac_value| heapless::Vec::from_slice(icac_value.0))
+            .transpose()
+            .map_err(|_| NocStatus::InvalidNOC)?;
+
+        let fabric = {
+            let fabric_mgr = self.fabric_mgr.borrow();
+            let fabric = fabric_mgr
+                .get_fabric(fab_idx as _)?
+                .ok_or(NocStatus::InvalidFabricIndex)?;
+
+            // The new chain has to be rooted in the trusted root of the fabric, and
+            // has to stay on the same fabric
+            let chain = Case::validate_certs(
+                fabric,
+                &noc_cert,
+                icac_cert.as_ref(),
+                &exchange.matter.last_known_good_time.borrow(),
+            );
+            if let Err(e) = chain {
+                error!("UpdateNOC certificate chain is invalid: {}", e);
+                Err(NocStatus::InvalidNOC)?;
+            }
+
+            let root_cert = Cert::new(&fabric.root_ca)?;
+            Self::update_last_known_good_time(exchange, &noc_cert, icac_cert.as_ref(), &root_cert)?;
+
+            Fabric::new(
+                noc_data.op_key,
+                fabric.root_ca.clone(),
+                icac,
+                noc,
+                &fabric.ipk.epoch_key,
+                fabric.get_vendor_id(),
+                fabric.label(),
+            )
+            .map_err(|_| NocStatus::InvalidNOC)?
+        };
+
+        let old = self
+            .fabric_mgr
+            .borrow_mut()
+            .update(fab_idx, fabric, self.mdns)
+            .map_err(|_| NocStatus::InvalidFabricIndex)?;
+
+        // The previous fabric - and its operational key - is kept until CommissioningComplete,
+        // so that it can be restored if the Fail-Safe expires
+        self.failsafe.borrow_mut().record_update_noc(fab_idx, old)?;
+
+        Ok(fab_idx)
+    }
+
+    /// The current time cannot be earlier than the NotBefore of any certificate of the
+    /// operational chain of the node, so the Last Known Good UTC Time is moved forward to
+    /// the latest of them. Fails if any of them expired before that time.
+    fn update_last_known_good_time(
+        exchange: &Exchange,
+        noc: &Cert,
+        icac: Option<&Cert>,
+        root: &Cert,
+    ) -> Result<(), NocError> {
+        let mut lkgt = exchange.matter.last_known_good_time.borrow_mut();
+
+        let mut certs = heapless::Vec::<&Cert, 3>::new();
+        certs.push(noc).unwrap();
+        certs.push(root).unwrap();
+        if let Some(icac) = icac {
+            certs.push(icac).unwrap();
+        }
+
+        lkgt.update_from_certs(&certs);
+
+        if let Err(e) = lkgt.validate(&certs) {
+            error!("The operational certificate chain expired: {}", e);
+            Err(NocStatus::InvalidNOC)?;
+        }
+
+        Ok(())
+    }
+
     fn create_nocresponse(
         encoder: CmdDataEncoder,
         status_code: NocStatus,
@@ -448,12 +629,20 @@ impl<'a> NocCluster<'a> {
 
     fn handle_command_rmfabric(
         &self,
-        _exchange: &Exchange,
+        exchange: &Exchange,
         data: &TLVElement,
         encoder: CmdDataEncoder,
     ) -> Result<(), Error> {
         cmd_enter!("Remove Fabric");
         let req = RemoveFabricReq::from_tlv(data).map_err(Error::map_invalid_data_type)?;
+        let op_key_id = self
+            .fabric_mgr
+            .borrow()
+            .get_fabric(req.fab_idx as _)
+            .ok()
+            .flatten()
+            .and_then(|fabric| fabric.op_key().external_id());
+
         if self
             .fabric_mgr
             .borrow_mut()
@@ -461,7 +650,17 @@ impl<'a> NocCluster<'a> {
             .is_ok()
         {
             let _ = self.acl_mgr.borrow_mut().delete_for_fabric(req.fab_idx);
-            // TODO: transaction.terminate();
+
+            exchange
+                .matter
+                .remove_fabric(req.fab_idx, Some(exchange.id()));
+
+            if let Some((keystore, id)) = exchange.matter.op_keystore().zip(op_key_id) {
+                if let Err(e) = keystore.remove(id) {
+                    warn!("Failed to remove the operational key of the fabric: {}", e);
+                }
+            }
+
             Ok(())
         } else {
             Self::create_nocresponse(encoder, NocStatus::InvalidFabricIndex, req.fab_idx, "")
@@ -487,6 +686,25 @@ impl<'a> NocCluster<'a> {
         Ok(())
     }
 
+    fn handle_command_updatenoc(
+        &self,
+        exchange: &Exchange,
+        data: &TLVElement,
+        encoder: CmdDataEncoder,
+    ) -> Result<(), Error> {
+        cmd_enter!("UpdateNOC");
+
+        let (status, fab_idx) = match self._handle_command_updatenoc(exchange, data) {
+            Ok(fab_idx) => (NocStatus::Ok, fab_idx),
+            Err(NocError::Status(status)) => (status, 0),
+            Err(NocError::Error(error)) => Err(error)?,
+        };
+
+        Self::create_nocresponse(encoder, status, fab_idx, "")?;
+
+        Ok(())
+    }
+
     fn handle_command_attrequest(
         &self,
         exchange: &Exchange,
@@ -498,6 +716,11 @@ impl<'a> NocCluster<'a> {
         let req = CommonReq::from_tlv(data).map_err(Error::map_invalid_command)?;
         info!("Received Attestation Nonce:{:?}", req.str);
 
+        if req.str.0.len() != ATTESTATION_NONCE_LEN {
+            error!("Invalid attestation nonce length");
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
         let mut attest_challenge = [0u8; crypto::SYMM_KEY_LEN_BYTES];
         exchange.with_session(|sess| {
             attest_challenge.copy_from_slice(sess.get_att_challenge());
@@ -541,7 +764,10 @@ impl<'a> NocCluster<'a> {
         let cert_type = get_certchainrequest_params(data).map_err(Error::map_invalid_command)?;
 
         let mut buf: [u8; RESP_MAX] = [0; RESP_MAX];
-        let len = self.dev_att.get_devatt_data(cert_type, &mut buf)?;
+        let len = match cert_type {
+            CertChainType::Dac => self.dev_att.get_dac(&mut buf)?,
+            CertChainType::Pai => self.dev_att.get_pai(&mut buf)?,
+        };
         let buf = &buf[0..len];
 
         let cmd_data = CertChainResp {
@@ -570,7 +796,8 @@ impl<'a> NocCluster<'a> {
             Err(ErrorCode::UnsupportedAccess)?;
         }
 
-        let noc_keypair = KeyPair::new(self.rand)?;
+        let mut csr: [u8; MAX_CSR_LEN] = [0; MAX_CSR_LEN];
+        let (op_key, csr) = OpKey::generate(exchange.matter.op_keystore(), self.rand, &mut csr)?;
         let mut attest_challenge = [0u8; crypto::SYMM_KEY_LEN_BYTES];
         exchange.with_session(|sess| {
             attest_challenge.copy_from_slice(sess.get_att_challenge());
@@ -582,7 +809,7 @@ impl<'a> NocCluster<'a> {
         let mut buf: [u8; RESP_MAX] = [0; RESP_MAX];
         let mut nocsr_element = WriteBuf::new(&mut buf);
         writer.start_struct(CmdDataWriter::TAG)?;
-        add_nocsrelement(&noc_keypair, req.str.0, &mut nocsr_element, &mut writer)?;
+        add_nocsrelement(csr, req.str.0, &mut nocsr_element, &mut writer)?;
         add_attestation_signature(
             self.dev_att,
             &mut nocsr_element,
@@ -593,7 +820,7 @@ impl<'a> NocCluster<'a> {
 
         writer.complete()?;
 
-        let noc_data = NocData::new(noc_keypair);
+        let noc_data = NocData::new(op_key);
         // Store this in the session data instead of cluster data, so it gets cleared
         // if the session goes away for some reason
         exchange.with_session_mut(|sess| {
@@ -670,13 +897,13 @@ impl<'a> ChangeNotifier<()> for NocCluster<'a> {
 
 fn add_attestation_element(
     epoch: Epoch,
-    dev_att: &dyn DevAttDataFetcher,
+    dev_att: &dyn DacProvider,
     att_nonce: &[u8],
     write_buf: &mut WriteBuf,
     t: &mut TLVWriter,
 ) -> Result<(), Error> {
     let mut cert_dec: [u8; MAX_CERT_DECLARATION_LEN] = [0; MAX_CERT_DECLARATION_LEN];
-    let len = dev_att.get_devatt_data(dev_att::DataType::CertDeclaration, &mut cert_dec)?;
+    let len = dev_att.get_cert_declaration(&mut cert_dec)?;
     let cert_dec = &cert_dec[0..len];
 
     let epoch = epoch().as_secs() as u32;
@@ -691,32 +918,23 @@ fn add_attestation_element(
 }
 
 fn add_attestation_signature(
-    dev_att: &dyn DevAttDataFetcher,
+    dev_att: &dyn DacProvider,
     attest_element: &mut WriteBuf,
     attest_challenge: &[u8],
     resp: &mut TLVWriter,
 ) -> Result<(), Error> {
-    let dac_key = {
-        let mut pubkey = [0_u8; crypto::EC_POINT_LEN_BYTES];
-        let mut privkey = [0_u8; crypto::BIGNUM_LEN_BYTES];
-        dev_att.get_devatt_data(dev_att::DataType::DACPubKey, &mut pubkey)?;
-        dev_att.get_devatt_data(dev_att::DataType::DACPrivKey, &mut privkey)?;
-        KeyPair::new_from_components(&pubkey, &privkey)
-    }?;
     attest_element.copy_from_slice(attest_challenge)?;
     let mut signature = [0u8; crypto::EC_SIGNATURE_LEN_BYTES];
-    dac_key.sign_msg(attest_element.as_slice(), &mut signature)?;
-    resp.str8(TagType::Context(1), &signature)
+    let len = dev_att.sign_with_dac(attest_element.as_slice(), &mut signature)?;
+    resp.str8(TagType::Context(1), &signature[..len])
 }
 
 fn add_nocsrelement(
-    noc_keypair: &KeyPair,
+    csr: &[u8],
     csr_nonce: &[u8],
     write_buf: &mut WriteBuf,
     resp: &mut TLVWriter,
 ) -> Result<(), Error> {
-    let mut csr: [u8; MAX_CSR_LEN] = [0; MAX_CSR_LEN];
-    let csr = noc_keypair.get_csr(&mut csr)?;
     let mut writer = TLVWriter::new(write_buf);
     writer.start_struct(TagType::Anonymous)?;
     writer.str8(TagType::Context(1), csr)?;
@@ -726,15 +944,22 @@ fn add_nocsrelement(
     resp.str8(TagType::Context(0), write_buf.as_slice())
 }
 
-fn get_certchainrequest_params(data: &TLVElement) -> Result<DataType, Error> {
+/// The certificate requested with a CertificateChainRequest
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CertChainType {
+    Dac,
+    Pai,
+}
+
+fn get_certchainrequest_params(data: &TLVElement) -> Result<CertChainType, Error> {
     let cert_type = CertChainReq::from_tlv(data)?.cert_type;
 
     const CERT_TYPE_DAC: u8 = 1;
     const CERT_TYPE_PAI: u8 = 2;
     info!("Received Cert Type:{:?}", cert_type);
     match cert_type {
-        CERT_TYPE_DAC => Ok(dev_att::DataType::DAC),
-        CERT_TYPE_PAI => Ok(dev_att::DataType::PAI),
+        CERT_TYPE_DAC => Ok(CertChainType::Dac),
+        CERT_TYPE_PAI => Ok(CertChainType::Pai),
         _ => Err(ErrorCode::Invalid.into()),
     }
 }
@@ -21,10 +21,11 @@ use crate::{
     attribute_enum,
     data_model::objects::{
         Access, AttrDataEncoder, AttrDataWriter, AttrDetails, AttrType, Attribute, ChangeNotifier,
-        Cluster, Dataver, Handler, NonBlockingHandler, Quality, ATTRIBUTE_LIST, FEATURE_MAP,
+        Cluster, Dataver, Handler, NonBlockingHandler, Quality, ACCEPTED_COMMAND_LIST,
+        ATTRIBUTE_LIST, FEATURE_MAP, GENERATED_COMMAND_LIST,
     },
     error::Error,
-    tlv::{OctetStr, TagType, ToTLV},
+    tlv::{OctetStr, ToTLV},
     utils::rand::Rand,
 };
 
@@ -56,6 +57,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(Attributes::MaxNetworks as u16, Access::RA, Quality::F),
         Attribute::new(Attributes::Networks as u16, Access::RA, Quality::NONE),
         Attribute::new(
@@ -81,6 +84,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[],
+    generated_commands: &[],
+    events: &[],
 };
 
 pub struct NwCommCluster {
@@ -148,12 +153,7 @@ impl Handler for NwCommCluster {
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::MaxNetworks => AttrType::<u8>::new().encode(writer, 1),
-                    Attributes::Networks => {
-                        writer.start_array(AttrDataWriter::TAG)?;
-                        info.nw_info.to_tlv(&mut writer, TagType::Anonymous)?;
-                        writer.end_container()?;
-                        writer.complete()
-                    }
+                    Attributes::Networks => writer.set_list([&info.nw_info]),
                     Attributes::ConnectMaxTimeSecs => {
                         AttrType::<u8>::new().encode(writer, info.connect_max_time_secs)
                     }
@@ -0,0 +1,216 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Time Format Localization cluster, through which the controllers set how the user interface
+//! of the device conveys the time of day and - with the CalendarFormat feature - the dates
+
+use core::cell::Cell;
+
+use strum::FromRepr;
+
+use crate::{
+    attribute_enum,
+    data_model::objects::*,
+    error::{Error, ErrorCode},
+    utils::rand::Rand,
+};
+
+pub const ID: u32 = 0x002C;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum HourFormat {
+    Hour12 = 0,
+    Hour24 = 1,
+    UseActiveLocale = 0xFF,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum CalendarType {
+    Buddhist = 0,
+    Chinese = 1,
+    Coptic = 2,
+    Ethiopian = 3,
+    Gregorian = 4,
+    Hebrew = 5,
+    Indian = 6,
+    Islamic = 7,
+    Japanese = 8,
+    Korean = 9,
+    Persian = 10,
+    Taiwanese = 11,
+    UseActiveLocale = 0xFF,
+}
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    HourFormat(AttrType<u8>) = 0,
+    ActiveCalendarType(AttrType<u8>) = 1,
+    SupportedCalendarTypes(()) = 2,
+}
+
+attribute_enum!(Attributes);
+
+pub enum AttributesDiscriminants {
+    HourFormat = 0,
+    ActiveCalendarType = 1,
+    SupportedCalendarTypes = 2,
+}
+
+enum FeatureMap {
+    CalendarFormat = 0x01,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(
+            AttributesDiscriminants::HourFormat as u16,
+            Access::RWVM,
+            Quality::N,
+        ),
+        Attribute::new(
+            AttributesDiscriminants::ActiveCalendarType as u16,
+            Access::RWVM,
+            Quality::N,
+        )
+        .with_conformance(Conformance::AnyOf(FeatureMap::CalendarFormat as _)),
+        Attribute::new(
+            AttributesDiscriminants::SupportedCalendarTypes as u16,
+            Access::RV,
+            Quality::FIXED,
+        )
+        .with_conformance(Conformance::AnyOf(FeatureMap::CalendarFormat as _)),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[],
+};
+
+/// The metadata of the Time Format Localization cluster of the devices which also convey dates
+pub const CALENDAR_FORMAT_CLUSTER: Cluster<'static> =
+    CLUSTER.with_features(FeatureMap::CalendarFormat as _);
+
+pub struct TimeFormatLocalizationCluster<'a> {
+    data_ver: Dataver,
+    hour_format: Cell<HourFormat>,
+    /// The calendar types the device supports, the first of which is active until a controller
+    /// sets another one. Empty without the CalendarFormat feature.
+    calendar_types: &'a [CalendarType],
+    active_calendar_type: Cell<CalendarType>,
+}
+
+impl<'a> TimeFormatLocalizationCluster<'a> {
+    pub fn new(hour_format: HourFormat, calendar_types: &'a [CalendarType], rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            hour_format: Cell::new(hour_format),
+            calendar_types,
+            active_calendar_type: Cell::new(
+                calendar_types
+                    .first()
+                    .copied()
+                    .unwrap_or(CalendarType::Gregorian),
+            ),
+        }
+    }
+
+    pub fn hour_format(&self) -> HourFormat {
+        self.hour_format.get()
+    }
+
+    pub fn active_calendar_type(&self) -> CalendarType {
+        self.active_calendar_type.get()
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                // The feature map and the attribute list depend on the metadata the endpoint
+                // uses, i.e. on whether it has the CalendarFormat feature
+                attr.cluster()?.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::HourFormat(codec) => {
+                        codec.encode(writer, self.hour_format.get() as _)
+                    }
+                    Attributes::ActiveCalendarType(codec) => {
+                        codec.encode(writer, self.active_calendar_type.get() as _)
+                    }
+                    Attributes::SupportedCalendarTypes(_) => writer.set_list(
+                        self.calendar_types
+                            .iter()
+                            .map(|calendar_type| *calendar_type as u8),
+                    ),
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::HourFormat(codec) => {
+                let hour_format =
+                    HourFormat::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?;
+
+                self.hour_format.set(hour_format);
+            }
+            Attributes::ActiveCalendarType(codec) => {
+                // Only the supported calendar types can be activated
+                let calendar_type = CalendarType::from_repr(codec.decode(data)?)
+                    .filter(|calendar_type| self.calendar_types.contains(calendar_type))
+                    .ok_or(ErrorCode::ConstraintError)?;
+
+                self.active_calendar_type.set(calendar_type);
+            }
+            _ => return Err(Error::new(ErrorCode::InvalidAction)),
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl<'a> Handler for TimeFormatLocalizationCluster<'a> {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        TimeFormatLocalizationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        TimeFormatLocalizationCluster::write(self, attr, data)
+    }
+}
+
+impl<'a> NonBlockingHandler for TimeFormatLocalizationCluster<'a> {}
+
+impl<'a> ChangeNotifier<()> for TimeFormatLocalizationCluster<'a> {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
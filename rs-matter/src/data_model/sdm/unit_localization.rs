@@ -0,0 +1,150 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Unit Localization cluster, through which the controllers set the units the user interface
+//! of the device conveys its measurements in - with the TemperatureUnit feature, the unit of the
+//! temperatures
+
+use core::cell::Cell;
+
+use strum::FromRepr;
+
+use crate::{
+    attribute_enum,
+    data_model::objects::*,
+    error::{Error, ErrorCode},
+    utils::rand::Rand,
+};
+
+pub const ID: u32 = 0x002D;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum TempUnit {
+    Fahrenheit = 0,
+    Celsius = 1,
+    Kelvin = 2,
+}
+
+#[derive(Clone, Copy, Debug, FromRepr)]
+#[repr(u16)]
+pub enum Attributes {
+    TemperatureUnit(AttrType<u8>) = 0,
+}
+
+attribute_enum!(Attributes);
+
+pub enum AttributesDiscriminants {
+    TemperatureUnit = 0,
+}
+
+enum FeatureMap {
+    TemperatureUnit = 0x01,
+}
+
+pub const CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: 0,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(
+            AttributesDiscriminants::TemperatureUnit as u16,
+            Access::RWVM,
+            Quality::N,
+        )
+        .with_conformance(Conformance::AnyOf(FeatureMap::TemperatureUnit as _)),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[],
+};
+
+/// The metadata of the Unit Localization cluster of the devices which convey temperatures
+pub const TEMPERATURE_UNIT_CLUSTER: Cluster<'static> =
+    CLUSTER.with_features(FeatureMap::TemperatureUnit as _);
+
+pub struct UnitLocalizationCluster {
+    data_ver: Dataver,
+    temperature_unit: Cell<TempUnit>,
+}
+
+impl UnitLocalizationCluster {
+    pub fn new(temperature_unit: TempUnit, rand: Rand) -> Self {
+        Self {
+            data_ver: Dataver::new(rand),
+            temperature_unit: Cell::new(temperature_unit),
+        }
+    }
+
+    pub fn temperature_unit(&self) -> TempUnit {
+        self.temperature_unit.get()
+    }
+
+    pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
+            if attr.is_system() {
+                attr.cluster()?.read(attr.attr_id, writer)
+            } else {
+                match attr.attr_id.try_into()? {
+                    Attributes::TemperatureUnit(codec) => {
+                        codec.encode(writer, self.temperature_unit.get() as _)
+                    }
+                }
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        let data = data.with_dataver(self.data_ver.get())?;
+
+        match attr.attr_id.try_into()? {
+            Attributes::TemperatureUnit(codec) => {
+                let unit =
+                    TempUnit::from_repr(codec.decode(data)?).ok_or(ErrorCode::ConstraintError)?;
+
+                self.temperature_unit.set(unit);
+            }
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
+    }
+}
+
+impl Handler for UnitLocalizationCluster {
+    fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        UnitLocalizationCluster::read(self, attr, encoder)
+    }
+
+    fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
+        UnitLocalizationCluster::write(self, attr, data)
+    }
+}
+
+impl NonBlockingHandler for UnitLocalizationCluster {}
+
+impl ChangeNotifier<()> for UnitLocalizationCluster {
+    fn consume_change(&mut self) -> Option<()> {
+        self.data_ver.consume_change(())
+    }
+}
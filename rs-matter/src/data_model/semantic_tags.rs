@@ -0,0 +1,45 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The standard namespaces of semantic tags (Matter 1.2) and their most common tags
+
+use super::objects::SemanticTag;
+
+pub const NAMESPACE_COMMON_DIRECTION: u8 = 0x04;
+pub const NAMESPACE_COMMON_LEVEL: u8 = 0x05;
+pub const NAMESPACE_COMMON_LOCATION: u8 = 0x06;
+pub const NAMESPACE_COMMON_NUMBER: u8 = 0x07;
+pub const NAMESPACE_COMMON_POSITION: u8 = 0x08;
+
+pub const TAG_POSITION_LEFT: SemanticTag<'static> = SemanticTag::new(NAMESPACE_COMMON_POSITION, 0);
+pub const TAG_POSITION_RIGHT: SemanticTag<'static> = SemanticTag::new(NAMESPACE_COMMON_POSITION, 1);
+pub const TAG_POSITION_TOP: SemanticTag<'static> = SemanticTag::new(NAMESPACE_COMMON_POSITION, 2);
+pub const TAG_POSITION_BOTTOM: SemanticTag<'static> =
+    SemanticTag::new(NAMESPACE_COMMON_POSITION, 3);
+pub const TAG_POSITION_MIDDLE: SemanticTag<'static> =
+    SemanticTag::new(NAMESPACE_COMMON_POSITION, 4);
+
+pub const TAG_LOCATION_INDOOR: SemanticTag<'static> =
+    SemanticTag::new(NAMESPACE_COMMON_LOCATION, 0);
+pub const TAG_LOCATION_OUTDOOR: SemanticTag<'static> =
+    SemanticTag::new(NAMESPACE_COMMON_LOCATION, 1);
+
+/// The tag of the Common Number namespace for `number`, e.g. for the N-th outlet of a
+/// power strip
+pub const fn number(number: u8) -> SemanticTag<'static> {
+    SemanticTag::new(NAMESPACE_COMMON_NUMBER, number)
+}
@@ -47,6 +47,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::Acl as u16,
             Access::RWFA,
@@ -74,6 +76,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
         ),
     ],
     commands: &[],
+    generated_commands: &[],
+    events: &[],
 };
 
 pub struct AccessControlCluster<'a> {
@@ -98,23 +102,25 @@ impl<'a> AccessControlCluster<'a> {
                     Attributes::Acl(_) => {
                         writer.start_array(AttrDataWriter::TAG)?;
                         self.acl_mgr.borrow().for_each_acl(|entry| {
-                            if !attr.fab_filter || Some(attr.fab_idx) == entry.fab_idx {
-                                entry.to_tlv(&mut writer, TagType::Anonymous)?;
+                            let fab_idx = entry.fab_idx.unwrap_or(0);
+
+                            if !attr.is_fabric_visible(fab_idx) {
+                                return Ok(());
                             }
 
-                            Ok(())
+                            // All fields of an ACL entry but its fabric index are fabric-sensitive
+                            if attr.is_fabric_sensitive_visible(fab_idx) {
+                                entry.to_tlv(&mut writer, TagType::Anonymous)
+                            } else {
+                                encode_redacted(&mut writer, TagType::Anonymous, fab_idx)
+                            }
                         })?;
                         writer.end_container()?;
 
                         writer.complete()
                     }
-                    Attributes::Extension(_) => {
-                        // Empty for now
-                        writer.start_array(AttrDataWriter::TAG)?;
-                        writer.end_container()?;
-
-                        writer.complete()
-                    }
+                    // Empty for now
+                    Attributes::Extension(_) => writer.set_list(core::iter::empty::<u16>()),
                     Attributes::SubjectsPerEntry(codec) => {
                         codec.encode(writer, acl::SUBJECTS_PER_ENTRY as u16)
                     }
@@ -136,7 +142,11 @@ impl<'a> AccessControlCluster<'a> {
             Attributes::Acl(_) => {
                 attr_list_write(attr, data.with_dataver(self.data_ver.get())?, |op, data| {
                     self.write_acl_attr(&op, data, attr.fab_idx)
-                })
+                })?;
+
+                self.data_ver.changed();
+
+                Ok(())
             }
             _ => {
                 error!("Attribute not yet supported: this shouldn't happen");
@@ -203,7 +213,8 @@ mod tests {
 
     use crate::{
         acl::{AclEntry, AclMgr, AuthMode},
-        data_model::objects::{AttrDataEncoder, AttrDetails, Node, Privilege},
+        data_model::objects::{AttrData, AttrDataEncoder, AttrDetails, Node, Privilege},
+        error::ErrorCode,
         interaction_model::messages::ib::ListOperation,
         tlv::{get_root_node_struct, ElementType, TLVElement, TLVWriter, TagType, ToTLV},
         utils::{rand::dummy_rand, writebuf::WriteBuf},
@@ -211,6 +222,49 @@ mod tests {
 
     use super::AccessControlCluster;
 
+    #[test]
+    /// Writes succeed only at the current data version of the cluster, and move it
+    fn acl_cluster_write_dataver() {
+        let mut buf: [u8; 100] = [0; 100];
+        let mut writebuf = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        let acl_mgr = RefCell::new(AclMgr::new());
+        let acl = AccessControlCluster::new(&acl_mgr, dummy_rand);
+
+        let new = AclEntry::new(1, Privilege::VIEW, AuthMode::Case);
+        new.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let data = get_root_node_struct(writebuf.as_slice()).unwrap();
+
+        let attr = AttrDetails {
+            node: &Node {
+                id: 0,
+                endpoints: &[],
+            },
+            endpoint_id: 0,
+            cluster_id: 0,
+            attr_id: 0,
+            list_index: None,
+            fab_idx: 1,
+            fab_filter: false,
+            dataver: None,
+            wildcard: false,
+        };
+
+        let dataver = acl.data_ver.get();
+
+        let result = acl.write(&attr, AttrData::new(Some(dataver.wrapping_add(1)), &data));
+        assert_eq!(result.unwrap_err().code(), ErrorCode::DataVersionMismatch);
+
+        acl.write(&attr, AttrData::new(Some(dataver), &data))
+            .unwrap();
+        assert_eq!(acl.data_ver.get(), dataver.wrapping_add(1));
+
+        // The version the entry was added at is stale now
+        let result = acl.write(&attr, AttrData::new(Some(dataver), &data));
+        assert_eq!(result.unwrap_err().code(), ErrorCode::DataVersionMismatch);
+    }
+
     #[test]
     /// Add an ACL entry
     fn acl_cluster_add() {
@@ -332,7 +386,8 @@ mod tests {
             acl_mgr.borrow_mut().add(i).unwrap();
         }
         let acl = AccessControlCluster::new(&acl_mgr, dummy_rand);
-        // Test 1, all 3 entries are read in the response without fabric filtering
+        // Test 1, all 3 entries are read in the response without fabric filtering, but only the
+        // one of the accessing fabric with its fabric-sensitive fields
         {
             let attr = AttrDetails {
                 node: &Node {
@@ -360,10 +415,10 @@ mod tests {
                 //     1, 24, 21, 36, 1, 5, 36, 2, 2, 54, 3, 24, 54, 4, 24, 36, 254, 2, 24, 24, 24,
                 //     24
                 // ],
+                // The entries of fabric 2 only carry their fabric index
                 &[
-                    21, 53, 1, 36, 0, 0, 55, 1, 36, 2, 0, 36, 3, 0, 36, 4, 0, 24, 54, 2, 21, 36, 1,
-                    1, 36, 2, 2, 54, 3, 24, 54, 4, 24, 36, 254, 2, 24, 21, 36, 1, 1, 36, 2, 2, 54,
-                    3, 24, 54, 4, 24, 36, 254, 1, 24, 21, 36, 1, 5, 36, 2, 2, 54, 3, 24, 54, 4, 24,
+                    21, 53, 1, 36, 0, 0, 55, 1, 36, 2, 0, 36, 3, 0, 36, 4, 0, 24, 54, 2, 21, 36,
+                    254, 2, 24, 21, 36, 1, 1, 36, 2, 2, 54, 3, 24, 54, 4, 24, 36, 254, 1, 24, 21,
                     36, 254, 2, 24, 24, 24, 24
                 ],
                 writebuf.as_slice()
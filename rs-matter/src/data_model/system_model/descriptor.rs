@@ -15,9 +15,12 @@
  *    limitations under the License.
  */
 
+use core::cell::Cell;
+
 use strum::FromRepr;
 
 use crate::attribute_enum;
+use crate::data_model::device_types::DEV_TYPE_AGGREGATOR;
 use crate::data_model::objects::*;
 use crate::error::Error;
 use crate::tlv::{TLVWriter, TagType, ToTLV};
@@ -33,31 +36,53 @@ pub enum Attributes {
     ServerList = 1,
     ClientList = 2,
     PartsList = 3,
+    TagList = 4,
 }
 
 attribute_enum!(Attributes);
 
+enum FeatureMap {
+    TagList = 0x01,
+}
+
 pub const CLUSTER: Cluster<'static> = Cluster {
     id: ID as _,
     feature_map: 0,
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(Attributes::DeviceTypeList as u16, Access::RV, Quality::NONE),
         Attribute::new(Attributes::ServerList as u16, Access::RV, Quality::NONE),
         Attribute::new(Attributes::PartsList as u16, Access::RV, Quality::NONE),
         Attribute::new(Attributes::ClientList as u16, Access::RV, Quality::NONE),
     ],
     commands: &[],
+    generated_commands: &[],
+    events: &[],
 };
 
-struct StandardPartsMatcher;
-
-impl PartsMatcher for StandardPartsMatcher {
-    fn describe(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
-        our_endpoint == 0 && endpoint != our_endpoint
-    }
-}
+/// The metadata of the Descriptor cluster of the endpoints with semantic tags
+/// (see [`Endpoint::tags`])
+pub const TAG_LIST_CLUSTER: Cluster<'static> = Cluster {
+    id: ID as _,
+    feature_map: FeatureMap::TagList as _,
+    attributes: &[
+        FEATURE_MAP,
+        ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
+        Attribute::new(Attributes::DeviceTypeList as u16, Access::RV, Quality::NONE),
+        Attribute::new(Attributes::ServerList as u16, Access::RV, Quality::NONE),
+        Attribute::new(Attributes::PartsList as u16, Access::RV, Quality::NONE),
+        Attribute::new(Attributes::ClientList as u16, Access::RV, Quality::NONE),
+        Attribute::new(Attributes::TagList as u16, Access::RV, Quality::NONE),
+    ],
+    commands: &[],
+    generated_commands: &[],
+    events: &[],
+};
 
 struct AggregatorPartsMatcher;
 
@@ -67,6 +92,8 @@ impl PartsMatcher for AggregatorPartsMatcher {
     }
 }
 
+/// Overrides the PartsList of the Descriptor cluster, which is otherwise computed from the
+/// parents of the endpoints of the node (see [`Endpoint::parent`])
 pub trait PartsMatcher {
     fn describe(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool;
 }
@@ -90,15 +117,28 @@ where
 }
 
 pub struct DescriptorCluster<'a> {
-    matcher: &'a dyn PartsMatcher,
+    matcher: Option<&'a dyn PartsMatcher>,
     data_ver: Dataver,
+    /// The digest of the endpoints of the node - as last read - so that the data version is
+    /// bumped once endpoints are added or removed at runtime
+    digest: Cell<Option<u32>>,
 }
 
 impl DescriptorCluster<'static> {
+    /// A Descriptor cluster whose PartsList is computed from the parents of the endpoints:
+    /// - the root endpoint and the Aggregator endpoints list all their descendants (the
+    ///   "full-family" pattern)
+    /// - any other endpoint lists its direct children (the "tree" pattern)
     pub fn new(rand: Rand) -> Self {
-        Self::new_matching(&StandardPartsMatcher, rand)
+        Self {
+            matcher: None,
+            data_ver: Dataver::new(rand),
+            digest: Cell::new(None),
+        }
     }
 
+    /// A Descriptor cluster for an Aggregator endpoint of a node whose bridged endpoints do
+    /// not declare it as their parent: all endpoints other than the root one are its parts
     pub fn new_aggregator(rand: Rand) -> Self {
         Self::new_matching(&AggregatorPartsMatcher, rand)
     }
@@ -107,15 +147,20 @@ impl DescriptorCluster<'static> {
 impl<'a> DescriptorCluster<'a> {
     pub fn new_matching(matcher: &'a dyn PartsMatcher, rand: Rand) -> DescriptorCluster<'a> {
         Self {
-            matcher,
+            matcher: Some(matcher),
             data_ver: Dataver::new(rand),
+            digest: Cell::new(None),
         }
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
+        self.refresh(attr.node);
+
         if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
-                CLUSTER.read(attr.attr_id, writer)
+                // The feature map and the attribute list depend on the metadata the endpoint
+                // uses, i.e. on whether it has a TagList
+                attr.cluster()?.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
                     Attributes::DeviceTypeList => {
@@ -154,6 +199,15 @@ impl<'a> DescriptorCluster<'a> {
                         )?;
                         writer.complete()
                     }
+                    Attributes::TagList => {
+                        self.encode_tag_list(
+                            attr.node,
+                            attr.endpoint_id,
+                            AttrDataWriter::TAG,
+                            &mut writer,
+                        )?;
+                        writer.complete()
+                    }
                 }
             }
         } else {
@@ -161,6 +215,43 @@ impl<'a> DescriptorCluster<'a> {
         }
     }
 
+    /// Bumps the data version if the endpoints of the node - their IDs, device types, parents,
+    /// clusters or tags - changed since the last read
+    fn refresh(&self, node: &Node) {
+        // FNV-1a
+        let digest = node
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| {
+                [
+                    endpoint.id as u32,
+                    endpoint.device_type.dtype as u32,
+                    endpoint.device_type.drev as u32,
+                    endpoint.parent_id().map(|id| id as u32 + 1).unwrap_or(0),
+                ]
+                .into_iter()
+                .chain(endpoint.clusters.iter().map(|cluster| cluster.id))
+                .chain(endpoint.client_clusters.iter().copied())
+                .chain(endpoint.tags.iter().map(|tag| {
+                    (tag.mfg_code.map(|code| code as u32 + 1).unwrap_or(0) << 16)
+                        | (tag.namespace_id as u32) << 8
+                        | tag.tag as u32
+                }))
+            })
+            .flat_map(u32::to_le_bytes)
+            .fold(0x811c9dc5_u32, |digest, byte| {
+                (digest ^ byte as u32).wrapping_mul(0x01000193)
+            });
+
+        if self
+            .digest
+            .replace(Some(digest))
+            .is_some_and(|prev| prev != digest)
+        {
+            self.data_ver.changed();
+        }
+    }
+
     fn encode_devtype_list(
         &self,
         node: &Node,
@@ -207,24 +298,92 @@ impl<'a> DescriptorCluster<'a> {
     ) -> Result<(), Error> {
         tw.start_array(tag)?;
 
+        for part in self.parts(node, endpoint_id) {
+            tw.u16(TagType::Anonymous, part)?;
+        }
+
+        tw.end_container()
+    }
+
+    /// The IDs of the endpoints which are parts of the endpoint `endpoint_id`
+    fn parts<'n>(
+        &'n self,
+        node: &'n Node,
+        endpoint_id: EndptId,
+    ) -> impl Iterator<Item = EndptId> + 'n {
+        let full_family = endpoint_id == 0
+            || node.endpoints.iter().any(|endpoint| {
+                endpoint.id == endpoint_id
+                    && endpoint.device_type.dtype == DEV_TYPE_AGGREGATOR.dtype
+            });
+
+        node.endpoints
+            .iter()
+            .filter(move |endpoint| match self.matcher {
+                Some(matcher) => matcher.describe(endpoint_id, endpoint.id),
+                None if full_family => Self::is_descendant(node, endpoint, endpoint_id),
+                None => endpoint.id != endpoint_id && endpoint.parent_id() == Some(endpoint_id),
+            })
+            .map(|endpoint| endpoint.id)
+    }
+
+    /// Whether `endpoint` is - directly or not - a part of the endpoint `ancestor_id`
+    fn is_descendant(node: &Node, endpoint: &Endpoint, ancestor_id: EndptId) -> bool {
+        let mut parent_id = endpoint.parent_id();
+
+        // Bounded, so that a cycle in the parents does not loop forever
+        for _ in 0..node.endpoints.len() {
+            match parent_id {
+                Some(id) if id == ancestor_id => return endpoint.id != ancestor_id,
+                Some(id) => {
+                    parent_id = node
+                        .endpoints
+                        .iter()
+                        .find(|endpoint| endpoint.id == id)
+                        .and_then(Endpoint::parent_id)
+                }
+                None => break,
+            }
+        }
+
+        false
+    }
+
+    fn encode_client_list(
+        &self,
+        node: &Node,
+        endpoint_id: u16,
+        tag: TagType,
+        tw: &mut TLVWriter,
+    ) -> Result<(), Error> {
+        tw.start_array(tag)?;
         for endpoint in node.endpoints {
-            if self.matcher.describe(endpoint_id, endpoint.id) {
-                tw.u16(TagType::Anonymous, endpoint.id)?;
+            if endpoint.id == endpoint_id {
+                for cluster in endpoint.client_clusters {
+                    tw.u32(TagType::Anonymous, *cluster)?;
+                }
             }
         }
 
         tw.end_container()
     }
 
-    fn encode_client_list(
+    fn encode_tag_list(
         &self,
-        _node: &Node,
-        _endpoint_id: u16,
+        node: &Node,
+        endpoint_id: u16,
         tag: TagType,
         tw: &mut TLVWriter,
     ) -> Result<(), Error> {
-        // No Clients supported
         tw.start_array(tag)?;
+        for endpoint in node.endpoints {
+            if endpoint.id == endpoint_id {
+                for semtag in endpoint.tags {
+                    semtag.to_tlv(tw, TagType::Anonymous)?;
+                }
+            }
+        }
+
         tw.end_container()
     }
 }
@@ -242,3 +401,157 @@ impl<'a> ChangeNotifier<()> for DescriptorCluster<'a> {
         self.data_ver.consume_change(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::device_types::{DEV_TYPE_AGGREGATOR, DEV_TYPE_ROOT_NODE};
+    use crate::data_model::objects::{DeviceType, DynamicNode, Endpoint, EndptId, Node};
+    use crate::data_model::semantic_tags::{
+        NAMESPACE_COMMON_POSITION, TAG_POSITION_LEFT, TAG_POSITION_RIGHT,
+    };
+    use crate::tlv::{TLVList, TLVWriter, TagType};
+    use crate::utils::rand::dummy_rand;
+    use crate::utils::writebuf::WriteBuf;
+
+    use super::{DescriptorCluster, CLUSTER, TAG_LIST_CLUSTER};
+
+    const DEV_TYPE: DeviceType = DeviceType {
+        dtype: 0x0100,
+        drev: 2,
+    };
+
+    const fn endpoint(
+        id: EndptId,
+        device_type: DeviceType,
+        parent: Option<EndptId>,
+    ) -> Endpoint<'static> {
+        Endpoint {
+            id,
+            device_type,
+            parent,
+            clusters: &[CLUSTER],
+            client_clusters: &[],
+            tags: &[],
+        }
+    }
+
+    #[test]
+    fn test_parts() {
+        // 0: root
+        // - 1: aggregator
+        //   - 2, 3: bridged devices, 3 being composed of 4
+        // - 5: composed device
+        //   - 6: composed of 7
+        const NODE: Node<'static> = Node {
+            id: 0,
+            endpoints: &[
+                endpoint(0, DEV_TYPE_ROOT_NODE, None),
+                endpoint(1, DEV_TYPE_AGGREGATOR, None),
+                endpoint(2, DEV_TYPE, Some(1)),
+                endpoint(3, DEV_TYPE, Some(1)),
+                endpoint(4, DEV_TYPE, Some(3)),
+                endpoint(5, DEV_TYPE, None),
+                endpoint(6, DEV_TYPE, Some(5)),
+                endpoint(7, DEV_TYPE, Some(6)),
+            ],
+        };
+
+        let descriptor = DescriptorCluster::new(dummy_rand);
+        let parts = |endpoint_id| {
+            descriptor
+                .parts(&NODE, endpoint_id)
+                .collect::<heapless::Vec<_, 8>>()
+        };
+
+        // Full-family
+        assert_eq!(parts(0), [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(parts(1), [2, 3, 4]);
+
+        // Tree
+        assert_eq!(parts(3), [4]);
+        assert_eq!(parts(5), [6]);
+        assert_eq!(parts(6), [7]);
+        assert!(parts(2).is_empty());
+
+        // Explicit
+        let aggregator = DescriptorCluster::new_aggregator(dummy_rand);
+        assert_eq!(
+            aggregator.parts(&NODE, 1).collect::<heapless::Vec<_, 8>>(),
+            [2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_tag_list() {
+        // A two-gang switch
+        const NODE: Node<'static> = Node {
+            id: 0,
+            endpoints: &[
+                Endpoint {
+                    id: 1,
+                    device_type: DEV_TYPE,
+                    parent: None,
+                    clusters: &[TAG_LIST_CLUSTER],
+                    client_clusters: &[],
+                    tags: &[TAG_POSITION_LEFT.with_label("Ceiling")],
+                },
+                Endpoint {
+                    id: 2,
+                    device_type: DEV_TYPE,
+                    parent: None,
+                    clusters: &[TAG_LIST_CLUSTER],
+                    client_clusters: &[],
+                    tags: &[TAG_POSITION_RIGHT],
+                },
+            ],
+        };
+
+        let descriptor = DescriptorCluster::new(dummy_rand);
+        let mut buf = [0; 64];
+
+        for (endpoint_id, tag, label) in [(1, 0, Some("Ceiling")), (2, 1, None)] {
+            let mut wb = WriteBuf::new(&mut buf);
+            let mut tw = TLVWriter::new(&mut wb);
+            descriptor
+                .encode_tag_list(&NODE, endpoint_id, TagType::Anonymous, &mut tw)
+                .unwrap();
+            let len = tw.get_tail();
+
+            let list = TLVList::new(&buf[..len]).iter().next().unwrap();
+            let mut tags = list.enter().unwrap();
+            let semtag = tags.next().unwrap();
+            assert!(tags.next().is_none());
+
+            assert!(semtag.find_tag(0).unwrap().null().is_ok());
+            let namespace_id = semtag.find_tag(1).unwrap().u8().unwrap();
+            assert_eq!(namespace_id, NAMESPACE_COMMON_POSITION);
+            assert_eq!(semtag.find_tag(2).unwrap().u8().unwrap(), tag);
+            assert_eq!(
+                semtag.find_tag(3).ok().map(|label| label.str().unwrap()),
+                label
+            );
+        }
+    }
+
+    #[test]
+    fn test_dataver_bumped_on_endpoint_changes() {
+        let descriptor = DescriptorCluster::new(dummy_rand);
+        let mut node = DynamicNode::<4>::new(0);
+
+        node.add(endpoint(0, DEV_TYPE, None)).unwrap();
+        descriptor.refresh(&node.node());
+        let dataver = descriptor.data_ver.get();
+
+        descriptor.refresh(&node.node());
+        assert_eq!(descriptor.data_ver.get(), dataver);
+
+        node.add(endpoint(1, DEV_TYPE, None)).unwrap();
+        descriptor.refresh(&node.node());
+        assert_eq!(descriptor.data_ver.get(), dataver.wrapping_add(1));
+
+        assert!(node.add(endpoint(1, DEV_TYPE, None)).is_err());
+        assert!(node.remove(1).is_some());
+        descriptor.refresh(&node.node());
+        assert_eq!(descriptor.data_ver.get(), dataver.wrapping_add(2));
+    }
+}
@@ -31,7 +31,12 @@ pub enum ErrorCode {
     InvalidDataType,
     UnsupportedAccess,
     ResourceExhausted,
+    ConstraintError,
     Busy,
+    /// A cluster-specific status, reported as a `Failure` carrying the status in
+    /// the ClusterStatus field of the StatusIB
+    ClusterStatus(u8),
+    ConnectionClosed,
     DataVersionMismatch,
     Crypto,
     TLSStack,
@@ -66,11 +71,14 @@ pub enum ErrorCode {
     InvalidSignature,
     InvalidState,
     InvalidTime,
+    MsgCtrExhausted,
     InvalidArgument,
     RwLock,
     TLVNotFound,
     TLVTypeMismatch,
     TruncatedPacket,
+    TxTimeout,
+    RxTimeout,
     Utf8Fail,
 }
 
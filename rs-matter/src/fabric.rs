@@ -16,6 +16,7 @@
  */
 
 use core::fmt::Write;
+use core::ops::{Deref, DerefMut};
 
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use heapless::{String, Vec};
@@ -23,15 +24,21 @@ use log::info;
 
 use crate::{
     cert::{Cert, MAX_CERT_TLV_LEN},
-    crypto::{self, hkdf_sha256, HmacSha256, KeyPair},
+    crypto::{
+        self, hkdf_sha256,
+        keystore::{OpKey, OpKeyStore},
+        HmacSha256,
+    },
     error::{Error, ErrorCode},
     group_keys::KeySet,
     mdns::{Mdns, ServiceMode},
-    tlv::{self, FromTLV, OctetStr, TLVList, TLVWriter, TagType, ToTLV, UtfStr},
+    tlv::{FromTLV, OctetStr, TLVList, TLVWriter, TagType, ToTLV, UtfStr},
     utils::writebuf::WriteBuf,
 };
 
-const COMPRESSED_FABRIC_ID_LEN: usize = 8;
+pub const COMPRESSED_FABRIC_ID_LEN: usize = 8;
+
+const DEST_ID_SUFFIX_LEN: usize = crypto::EC_POINT_LEN_BYTES + 8 + 8;
 
 #[derive(Debug, ToTLV)]
 #[tlvargs(lifetime = "'a", start = 1)]
@@ -51,7 +58,7 @@ pub struct Fabric {
     node_id: u64,
     fabric_id: u64,
     vendor_id: u16,
-    key_pair: KeyPair,
+    op_key: OpKey,
     pub root_ca: Vec<u8, { MAX_CERT_TLV_LEN }>,
     pub icac: Option<Vec<u8, { MAX_CERT_TLV_LEN }>>,
     pub noc: Vec<u8, { MAX_CERT_TLV_LEN }>,
@@ -62,7 +69,7 @@ pub struct Fabric {
 
 impl Fabric {
     pub fn new(
-        key_pair: KeyPair,
+        op_key: OpKey,
         root_ca: heapless::Vec<u8, { MAX_CERT_TLV_LEN }>,
         icac: Option<heapless::Vec<u8, { MAX_CERT_TLV_LEN }>>,
         noc: heapless::Vec<u8, { MAX_CERT_TLV_LEN }>,
@@ -83,27 +90,15 @@ impl Fabric {
             KeySet::new(ipk, &compressed_id)?
         };
 
-        let mut mdns_service_name = heapless::String::<33>::new();
-        for c in compressed_id {
-            let mut hex = heapless::String::<4>::new();
-            write!(&mut hex, "{:02X}", c).unwrap();
-            mdns_service_name.push_str(&hex).unwrap();
-        }
-        mdns_service_name.push('-').unwrap();
-        let mut node_id_be: [u8; 8] = [0; 8];
-        BigEndian::write_u64(&mut node_id_be, node_id);
-        for c in node_id_be {
-            let mut hex = heapless::String::<4>::new();
-            write!(&mut hex, "{:02X}", c).unwrap();
-            mdns_service_name.push_str(&hex).unwrap();
-        }
+        let mdns_service_name =
+            Self::operational_instance_name(BigEndian::read_u64(&compressed_id), node_id);
         info!("MDNS Service Name: {}", mdns_service_name);
 
         Ok(Self {
             node_id,
             fabric_id,
             vendor_id,
-            key_pair,
+            op_key,
             root_ca,
             icac,
             noc,
@@ -113,6 +108,38 @@ impl Fabric {
         })
     }
 
+    /// The compressed fabric ID - derived from the root public key and the fabric ID - as
+    /// used for the operational instance names and for deriving the operational group keys
+    pub fn compressed_fabric_id(&self) -> Result<u64, Error> {
+        Ok(BigEndian::read_u64(&self.compressed_id()?))
+    }
+
+    /// The operational instance name of the node on this fabric, as advertised over mDNS
+    pub fn mdns_service_name(&self) -> &str {
+        &self.mdns_service_name
+    }
+
+    /// The operational instance name of a node: `<compressed fabric ID>-<node ID>`, both as
+    /// 16 upper-case hex digits
+    pub fn operational_instance_name(compressed_fabric_id: u64, node_id: u64) -> String<33> {
+        let mut name = String::new();
+        write!(&mut name, "{:016X}-{:016X}", compressed_fabric_id, node_id).unwrap();
+
+        name
+    }
+
+    /// The compressed fabric ID, as big-endian bytes
+    pub fn compressed_id(&self) -> Result<[u8; COMPRESSED_FABRIC_ID_LEN], Error> {
+        let mut compressed_id = [0_u8; COMPRESSED_FABRIC_ID_LEN];
+        Fabric::get_compressed_id(
+            self.get_root_ca()?.get_pubkey(),
+            self.fabric_id,
+            &mut compressed_id,
+        )?;
+
+        Ok(compressed_id)
+    }
+
     fn get_compressed_id(root_pubkey: &[u8], fabric_id: u64, out: &mut [u8]) -> Result<(), Error> {
         let root_pubkey = &root_pubkey[1..];
         let mut fabric_id_be: [u8; 8] = [0; 8];
@@ -126,35 +153,39 @@ impl Fabric {
     }
 
     pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<(), Error> {
-        let mut mac = HmacSha256::new(self.ipk.op_key())?;
-
-        mac.update(random)?;
-        mac.update(self.get_root_ca()?.get_pubkey())?;
-
-        let mut buf: [u8; 8] = [0; 8];
-        LittleEndian::write_u64(&mut buf, self.fabric_id);
-        mac.update(&buf)?;
-
-        LittleEndian::write_u64(&mut buf, self.node_id);
-        mac.update(&buf)?;
-
-        let mut id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
-        mac.finish(&mut id)?;
-        if id.as_slice() == target {
+        if DestIdCandidate::new(self)?.matches(random, target)? {
             Ok(())
         } else {
             Err(ErrorCode::NotFound.into())
         }
     }
 
-    pub fn sign_msg(&self, msg: &[u8], signature: &mut [u8]) -> Result<usize, Error> {
-        self.key_pair.sign_msg(msg, signature)
+    /// Signs with the operational key of the fabric, which might be kept in the keystore
+    pub fn sign_msg(
+        &self,
+        keystore: Option<&dyn OpKeyStore>,
+        msg: &[u8],
+        signature: &mut [u8],
+    ) -> Result<usize, Error> {
+        self.op_key.sign_msg(keystore, msg, signature)
+    }
+
+    pub fn op_key(&self) -> &OpKey {
+        &self.op_key
     }
 
     pub fn get_node_id(&self) -> u64 {
         self.node_id
     }
 
+    pub fn get_vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
     pub fn get_fabric_id(&self) -> u64 {
         self.fabric_id
     }
@@ -181,34 +212,123 @@ impl Fabric {
     }
 }
 
-pub const MAX_SUPPORTED_FABRICS: usize = 3;
+/// What the Sigma1 destination identifiers of a fabric are computed from, except for the
+/// random of the initiator: the IPK, and `RootPublicKey || FabricID || NodeID`.
+///
+/// Deriving these means parsing the root certificate of the fabric, so they are kept per fabric
+/// instead, and only recomputed when the fabric - and thus its IPK or its root - changes.
+struct DestIdCandidate {
+    ipk: [u8; crypto::SYMM_KEY_LEN_BYTES],
+    suffix: [u8; DEST_ID_SUFFIX_LEN],
+}
+
+impl DestIdCandidate {
+    fn new(fabric: &Fabric) -> Result<Self, Error> {
+        let mut ipk = [0; crypto::SYMM_KEY_LEN_BYTES];
+        ipk.copy_from_slice(fabric.ipk.op_key());
+
+        let root_ca = fabric.get_root_ca()?;
+        let root_pubkey = root_ca.get_pubkey();
+        if root_pubkey.len() != crypto::EC_POINT_LEN_BYTES {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let mut suffix = [0; DEST_ID_SUFFIX_LEN];
+        let (pubkey, ids) = suffix.split_at_mut(crypto::EC_POINT_LEN_BYTES);
+        pubkey.copy_from_slice(root_pubkey);
+        LittleEndian::write_u64(&mut ids[..8], fabric.fabric_id);
+        LittleEndian::write_u64(&mut ids[8..], fabric.node_id);
+
+        Ok(Self { ipk, suffix })
+    }
+
+    fn matches(&self, random: &[u8], target: &[u8]) -> Result<bool, Error> {
+        let mut mac = HmacSha256::new(&self.ipk)?;
+
+        mac.update(random)?;
+        mac.update(&self.suffix)?;
+
+        let mut id = [0_u8; crypto::SHA256_HASH_LEN_BYTES];
+        mac.finish(&mut id)?;
+
+        Ok(id.as_slice() == target)
+    }
+}
+
+pub const MAX_SUPPORTED_FABRICS: usize = crate::config::MAX_FABRICS;
+
+/// A slot of the [`FabricMgr`], taken by a fabric or not
+pub struct FabricSlot {
+    fabric: Option<Fabric>,
+    dest_id: Option<DestIdCandidate>,
+}
 
-type FabricEntries = Vec<Option<Fabric>, MAX_SUPPORTED_FABRICS>;
+impl FabricSlot {
+    const EMPTY: Self = Self {
+        fabric: None,
+        dest_id: None,
+    };
+}
 
-pub struct FabricMgr {
-    fabrics: FabricEntries,
+/// The fabric manager, with the fabric slots `S` - `[FabricSlot; N]` for a manager with room
+/// for `N` fabrics, which dereferences to `FabricMgr<[FabricSlot]>`, i.e. the manager of any
+/// capacity all methods are implemented on.
+pub struct FabricMgr<S: ?Sized = [FabricSlot]> {
     changed: bool,
+    slots: S,
 }
 
-impl FabricMgr {
+impl<const N: usize> FabricMgr<[FabricSlot; N]> {
     #[inline(always)]
     pub const fn new() -> Self {
         Self {
-            fabrics: FabricEntries::new(),
             changed: false,
+            slots: [FabricSlot::EMPTY; N],
         }
     }
+}
+
+impl<const N: usize> Deref for FabricMgr<[FabricSlot; N]> {
+    type Target = FabricMgr;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+impl<const N: usize> DerefMut for FabricMgr<[FabricSlot; N]> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self
+    }
+}
+
+impl FabricMgr {
+    /// The maximum number of fabrics
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
 
     pub fn load(&mut self, data: &[u8], mdns: &dyn Mdns) -> Result<(), Error> {
-        for fabric in self.fabrics.iter().flatten() {
+        for fabric in self.fabrics() {
             mdns.remove(&fabric.mdns_service_name)?;
         }
 
         let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
 
-        tlv::from_tlv(&mut self.fabrics, &root)?;
+        root.confirm_array()?;
+
+        self.slots.fill_with(|| FabricSlot::EMPTY);
+
+        for (index, element) in root.enter().into_iter().flatten().enumerate() {
+            let slot = self.slots.get_mut(index).ok_or(ErrorCode::NoSpace)?;
+            slot.fabric = Some(Fabric::from_tlv(&element)?);
+        }
 
-        for fabric in self.fabrics.iter().flatten() {
+        for index in 0..self.slots.len() {
+            self.refresh_dest_id(index)?;
+        }
+
+        for fabric in self.fabrics() {
             mdns.add(&fabric.mdns_service_name, ServiceMode::Commissioned)?;
         }
 
@@ -222,9 +342,11 @@ impl FabricMgr {
             let mut wb = WriteBuf::new(buf);
             let mut tw = TLVWriter::new(&mut wb);
 
-            self.fabrics
-                .as_slice()
-                .to_tlv(&mut tw, TagType::Anonymous)?;
+            tw.start_array(TagType::Anonymous)?;
+            for fabric in self.fabrics() {
+                fabric.to_tlv(&mut tw, TagType::Anonymous)?;
+            }
+            tw.end_container()?;
 
             self.changed = false;
 
@@ -241,32 +363,30 @@ impl FabricMgr {
     }
 
     pub fn add(&mut self, f: Fabric, mdns: &dyn Mdns) -> Result<u8, Error> {
-        let slot = self.fabrics.iter().position(|x| x.is_none());
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| slot.fabric.is_none())
+            .ok_or(ErrorCode::NoSpace)?;
 
-        if slot.is_some() || self.fabrics.len() < MAX_SUPPORTED_FABRICS {
-            mdns.add(&f.mdns_service_name, ServiceMode::Commissioned)?;
-            self.changed = true;
+        let dest_id = DestIdCandidate::new(&f)?;
 
-            if let Some(index) = slot {
-                self.fabrics[index] = Some(f);
+        mdns.add(&f.mdns_service_name, ServiceMode::Commissioned)?;
+        self.changed = true;
 
-                Ok((index + 1) as u8)
-            } else {
-                self.fabrics
-                    .push(Some(f))
-                    .map_err(|_| ErrorCode::NoSpace)
-                    .unwrap();
+        self.slots[index] = FabricSlot {
+            fabric: Some(f),
+            dest_id: Some(dest_id),
+        };
 
-                Ok(self.fabrics.len() as u8)
-            }
-        } else {
-            Err(ErrorCode::NoSpace.into())
-        }
+        Ok((index + 1) as u8)
     }
 
     pub fn remove(&mut self, fab_idx: u8, mdns: &dyn Mdns) -> Result<(), Error> {
-        if fab_idx > 0 && fab_idx as usize <= self.fabrics.len() {
-            if let Some(f) = self.fabrics[(fab_idx - 1) as usize].take() {
+        if fab_idx > 0 && fab_idx as usize <= self.slots.len() {
+            let slot = &mut self.slots[(fab_idx - 1) as usize];
+            if let Some(f) = slot.fabric.take() {
+                slot.dest_id = None;
                 mdns.remove(&f.mdns_service_name)?;
                 self.changed = true;
                 Ok(())
@@ -278,10 +398,53 @@ impl FabricMgr {
         }
     }
 
+    /// Replaces the fabric at the given index - e.g. with one carrying an updated NOC -
+    /// re-advertising it with its new operational instance name. Returns the replaced fabric.
+    pub fn update(&mut self, fab_idx: u8, f: Fabric, mdns: &dyn Mdns) -> Result<Fabric, Error> {
+        if fab_idx > 0 && fab_idx as usize <= self.slots.len() {
+            let slot = &mut self.slots[(fab_idx - 1) as usize];
+            if let Some(old) = slot.fabric.as_ref() {
+                let dest_id = DestIdCandidate::new(&f)?;
+
+                mdns.remove(&old.mdns_service_name)?;
+                mdns.add(&f.mdns_service_name, ServiceMode::Commissioned)?;
+                self.changed = true;
+
+                let old = slot.fabric.replace(f).ok_or(ErrorCode::NotFound)?;
+                slot.dest_id = Some(dest_id);
+
+                Ok(old)
+            } else {
+                Err(ErrorCode::NotFound.into())
+            }
+        } else {
+            Err(ErrorCode::NotFound.into())
+        }
+    }
+
+    /// Replaces the Identity Protection Key of the fabric at the given index, e.g. when
+    /// the IPK key set is written with the Group Key Management cluster
+    pub fn set_ipk(&mut self, fab_idx: u8, ipk: &[u8]) -> Result<(), Error> {
+        let index = (fab_idx as usize)
+            .checked_sub(1)
+            .ok_or(ErrorCode::NotFound)?;
+        let fabric = self
+            .slots
+            .get_mut(index)
+            .and_then(|slot| slot.fabric.as_mut())
+            .ok_or(ErrorCode::NotFound)?;
+
+        fabric.ipk = KeySet::new(ipk, &fabric.compressed_id()?)?;
+        self.changed = true;
+
+        // The destination identifiers are derived from the IPK
+        self.refresh_dest_id(index)
+    }
+
     pub fn match_dest_id(&self, random: &[u8], target: &[u8]) -> Result<usize, Error> {
-        for (index, fabric) in self.fabrics.iter().enumerate() {
-            if let Some(fabric) = fabric {
-                if fabric.match_dest_id(random, target).is_ok() {
+        for (index, slot) in self.slots.iter().enumerate() {
+            if let Some(dest_id) = &slot.dest_id {
+                if dest_id.matches(random, target)? {
                     return Ok(index + 1);
                 }
             }
@@ -289,20 +452,36 @@ impl FabricMgr {
         Err(ErrorCode::NotFound.into())
     }
 
+    /// Recomputes the destination identifier candidate of the fabric at `index`
+    fn refresh_dest_id(&mut self, index: usize) -> Result<(), Error> {
+        let slot = &mut self.slots[index];
+
+        slot.dest_id = slot.fabric.as_ref().map(DestIdCandidate::new).transpose()?;
+
+        Ok(())
+    }
+
     pub fn get_fabric(&self, idx: usize) -> Result<Option<&Fabric>, Error> {
         if idx == 0 {
             Ok(None)
         } else {
-            Ok(self.fabrics[idx - 1].as_ref())
+            Ok(self
+                .slots
+                .get(idx - 1)
+                .and_then(|slot| slot.fabric.as_ref()))
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        !self.fabrics.iter().any(Option::is_some)
+        self.fabrics().next().is_none()
     }
 
     pub fn used_count(&self) -> usize {
-        self.fabrics.iter().filter(|f| f.is_some()).count()
+        self.fabrics().count()
+    }
+
+    fn fabrics(&self) -> impl Iterator<Item = &Fabric> {
+        self.slots.iter().filter_map(|slot| slot.fabric.as_ref())
     }
 
     // Parameters to T are the Fabric and its Fabric Index
@@ -310,8 +489,8 @@ impl FabricMgr {
     where
         T: FnMut(&Fabric, u8) -> Result<(), Error>,
     {
-        for (index, fabric) in self.fabrics.iter().enumerate() {
-            if let Some(fabric) = fabric {
+        for (index, slot) in self.slots.iter().enumerate() {
+            if let Some(fabric) = &slot.fabric {
                 f(fabric, (index + 1) as u8)?;
             }
         }
@@ -319,21 +498,44 @@ impl FabricMgr {
     }
 
     pub fn set_label(&mut self, index: u8, label: &str) -> Result<(), Error> {
-        if !label.is_empty()
-            && self
-                .fabrics
-                .iter()
-                .filter_map(|f| f.as_ref())
-                .any(|f| f.label == label)
-        {
+        if !label.is_empty() && self.fabrics().any(|f| f.label == label) {
             return Err(ErrorCode::Invalid.into());
         }
 
         let index = (index - 1) as usize;
-        if let Some(fabric) = &mut self.fabrics[index] {
+        if let Some(fabric) = &mut self.slots[index].fabric {
             fabric.label = label.try_into().unwrap();
             self.changed = true;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, ByteOrder};
+
+    use super::{Fabric, COMPRESSED_FABRIC_ID_LEN};
+
+    #[test]
+    fn test_compressed_fabric_id() {
+        // The test vector of the spec
+        const ROOT_PUBKEY: [u8; 65] = [
+            0x04, 0x4a, 0x9f, 0x42, 0xb1, 0xca, 0x48, 0x40, 0xd3, 0x72, 0x92, 0xbb, 0xc7, 0xf6,
+            0xa7, 0xe1, 0x1e, 0x22, 0x20, 0x0c, 0x97, 0x6f, 0xc9, 0x00, 0xdb, 0xc9, 0x8a, 0x7a,
+            0x38, 0x3a, 0x64, 0x1c, 0xb8, 0x25, 0x4a, 0x2e, 0x56, 0xd4, 0xe2, 0x95, 0xa8, 0x47,
+            0x94, 0x3b, 0x4e, 0x38, 0x97, 0xc4, 0xa7, 0x73, 0xe9, 0x30, 0x27, 0x7b, 0x4d, 0x9f,
+            0xbe, 0xde, 0x8a, 0x05, 0x26, 0x86, 0xbf, 0xac, 0xfa,
+        ];
+
+        let mut compressed_id = [0; COMPRESSED_FABRIC_ID_LEN];
+        Fabric::get_compressed_id(&ROOT_PUBKEY, 0x2906_C908_D115_D362, &mut compressed_id).unwrap();
+        let compressed_fabric_id = BigEndian::read_u64(&compressed_id);
+        assert_eq!(compressed_fabric_id, 0x87E1_B004_E235_A130);
+
+        assert_eq!(
+            Fabric::operational_instance_name(compressed_fabric_id, 0x1234),
+            "87E1B004E235A130-0000000000001234"
+        );
+    }
+}
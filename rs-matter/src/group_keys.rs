@@ -16,13 +16,38 @@
  */
 
 use crate::{
+    config,
     crypto::{self, SYMM_KEY_LEN_BYTES},
     error::{Error, ErrorCode},
-    tlv::{FromTLV, ToTLV},
+    tlv::{self, FromTLV, TLVList, TLVWriter, TagType, ToTLV},
+    transport::network::Ipv6Addr,
+    utils::writebuf::WriteBuf,
 };
 
 type KeySetKey = [u8; SYMM_KEY_LEN_BYTES];
 
+pub const MAX_GROUP_KEYS: usize = config::MAX_GROUP_KEYS;
+
+/// The maximum number of group key sets per fabric, including the IPK key set
+pub const MAX_GROUP_KEY_SETS_PER_FABRIC: usize = config::MAX_GROUP_KEY_SETS_PER_FABRIC;
+
+/// The maximum number of groups - i.e. of Group Key Map entries - per fabric
+pub const MAX_GROUPS_PER_FABRIC: usize = config::MAX_GROUPS_PER_FABRIC;
+
+/// The maximum number of group memberships of the endpoints - i.e. of (group, endpoint)
+/// pairs - per fabric
+pub const MAX_GROUP_MEMBERSHIPS_PER_FABRIC: usize = config::MAX_GROUP_MEMBERSHIPS_PER_FABRIC;
+
+/// The number of epoch keys of a group key set
+pub const MAX_EPOCH_KEYS: usize = 3;
+
+/// The ID of the group key set holding the Identity Protection Key (IPK) of a fabric
+pub const IPK_KEY_SET_ID: u16 = 0;
+
+const MAX_KEY_SETS: usize = MAX_GROUP_KEY_SETS_PER_FABRIC * config::MAX_FABRICS;
+const MAX_KEY_MAP_ENTRIES: usize = MAX_GROUPS_PER_FABRIC * config::MAX_FABRICS;
+const MAX_GROUP_MEMBERSHIPS: usize = MAX_GROUP_MEMBERSHIPS_PER_FABRIC * config::MAX_FABRICS;
+
 #[derive(Debug, Default, FromTLV, ToTLV)]
 pub struct KeySet {
     pub epoch_key: KeySetKey,
@@ -54,3 +79,741 @@ impl KeySet {
         &self.epoch_key
     }
 }
+
+/// The security policy of a group key set
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GroupKeySecurityPolicy {
+    TrustFirst = 0,
+    CacheAndSync = 1,
+}
+
+impl TryFrom<u8> for GroupKeySecurityPolicy {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::TrustFirst),
+            1 => Ok(Self::CacheAndSync),
+            _ => Err(ErrorCode::ConstraintError.into()),
+        }
+    }
+}
+
+/// An epoch key of a group key set. Only the operational key derived from it is kept,
+/// as the epoch key itself is never read back
+#[derive(Debug, Clone, Default, FromTLV, ToTLV)]
+pub struct EpochKey {
+    /// The start time of the key, in microseconds since the Matter epoch
+    pub start_time: u64,
+    op_key: KeySetKey,
+    session_id: u16,
+}
+
+impl EpochKey {
+    pub fn new(epoch_key: &[u8], start_time: u64, compressed_id: &[u8]) -> Result<Self, Error> {
+        if epoch_key.len() != SYMM_KEY_LEN_BYTES {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let key_set = KeySet::new(epoch_key, compressed_id)?;
+
+        Ok(Self {
+            start_time,
+            op_key: key_set.op_key,
+            session_id: GroupKey::session_id_from_op_key(&key_set.op_key)?,
+        })
+    }
+
+    /// The ID of the group session of all messages encrypted with this key
+    pub fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    pub fn op_key(&self) -> &[u8] {
+        &self.op_key
+    }
+}
+
+type EpochKeys = [Option<EpochKey>; MAX_EPOCH_KEYS];
+
+/// A group key set of a fabric: up to three epoch keys, of which the one with the latest
+/// start time is used for sending, while all of them are accepted when receiving
+#[derive(Debug, Clone, FromTLV, ToTLV)]
+pub struct GroupKeySet {
+    pub fab_idx: u8,
+    pub key_set_id: u16,
+    pub policy: u8,
+    epoch_keys: EpochKeys,
+}
+
+impl GroupKeySet {
+    /// Derives the operational keys of the `(epoch key, start time)` pairs of the key set
+    pub fn new(
+        fab_idx: u8,
+        key_set_id: u16,
+        policy: GroupKeySecurityPolicy,
+        epoch_keys: &[(&[u8], u64)],
+        compressed_id: &[u8],
+    ) -> Result<Self, Error> {
+        // The first epoch key is mandatory
+        if epoch_keys.is_empty() || epoch_keys.len() > MAX_EPOCH_KEYS {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        let mut key_set = Self {
+            fab_idx,
+            key_set_id,
+            policy: policy as u8,
+            epoch_keys: Default::default(),
+        };
+
+        for (slot, (epoch_key, start_time)) in key_set.epoch_keys.iter_mut().zip(epoch_keys) {
+            *slot = Some(EpochKey::new(epoch_key, *start_time, compressed_id)?);
+        }
+
+        Ok(key_set)
+    }
+
+    pub fn epoch_keys(&self) -> impl Iterator<Item = &EpochKey> {
+        self.epoch_keys.iter().flatten()
+    }
+
+    /// The epoch key used for sending: the one with the latest start time
+    pub fn current(&self) -> Option<&EpochKey> {
+        self.epoch_keys().max_by_key(|key| key.start_time)
+    }
+}
+
+/// An entry of the Group Key Map of a fabric, mapping a group to the key set used by it
+#[derive(Debug, Clone, PartialEq, Eq, FromTLV, ToTLV)]
+pub struct GroupKeyMapEntry {
+    pub fab_idx: u8,
+    pub group_id: u16,
+    pub key_set_id: u16,
+}
+
+/// The membership of an endpoint in a group, as managed by the Groups cluster. The actions of
+/// the group-addressed requests apply to the endpoints which are members of the group only
+#[derive(Debug, Clone, PartialEq, Eq, FromTLV, ToTLV)]
+pub struct GroupMembership {
+    pub fab_idx: u8,
+    pub group_id: u16,
+    pub endpoint_id: u16,
+}
+
+/// The IPv6 multicast address the messages of a group are sent to:
+/// `FF35:0040:FD<Fabric ID>00:<Group ID>`
+///
+/// Note that - unlike the operational instance names and the group keys - the address is
+/// built with the Fabric ID itself, and not with the compressed fabric ID.
+pub fn group_multicast_addr(fabric_id: u64, group_id: u16) -> Ipv6Addr {
+    let fabric_id = fabric_id.to_be_bytes();
+    let group_id = group_id.to_be_bytes();
+
+    let mut addr = [0; 16];
+    addr[..5].copy_from_slice(&[0xff, 0x35, 0x00, 0x40, 0xfd]);
+    addr[5..13].copy_from_slice(&fabric_id);
+    addr[14..].copy_from_slice(&group_id);
+
+    Ipv6Addr::from(addr)
+}
+
+/// The operational key of a group the node is a member of, together with
+/// the group session ID derived from it
+#[derive(Debug, Clone)]
+pub struct GroupKey {
+    pub fab_idx: u8,
+    pub group_id: u16,
+    session_id: u16,
+    op_key: KeySetKey,
+}
+
+impl GroupKey {
+    pub fn new(fab_idx: u8, group_id: u16, op_key: &[u8]) -> Result<Self, Error> {
+        let mut key = Self {
+            fab_idx,
+            group_id,
+            session_id: 0,
+            op_key: Default::default(),
+        };
+
+        if op_key.len() != key.op_key.len() {
+            Err(ErrorCode::InvalidKeyLength)?;
+        }
+
+        key.op_key.copy_from_slice(op_key);
+        key.session_id = Self::session_id_from_op_key(op_key)?;
+
+        Ok(key)
+    }
+
+    fn from_epoch_key(fab_idx: u8, group_id: u16, key: &EpochKey) -> Self {
+        Self {
+            fab_idx,
+            group_id,
+            session_id: key.session_id,
+            op_key: key.op_key,
+        }
+    }
+
+    fn session_id_from_op_key(op_key: &[u8]) -> Result<u16, Error> {
+        const GRP_KEY_HASH_INFO: [u8; 12] = [
+            0x47, 0x72, 0x6f, 0x75, 0x70, 0x4b, 0x65, 0x79, 0x48, 0x61, 0x73, 0x68,
+        ];
+
+        let mut hash = [0; 2];
+        crypto::hkdf_sha256(&[], op_key, &GRP_KEY_HASH_INFO, &mut hash)
+            .map_err(|_| Error::from(ErrorCode::NoSpace))?;
+
+        Ok(u16::from_be_bytes(hash))
+    }
+
+    /// The ID of the group session of all messages encrypted with this key
+    pub fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    pub fn op_key(&self) -> &[u8] {
+        &self.op_key
+    }
+}
+
+/// The operational group keys of all groups the node is a member of, across all fabrics.
+///
+/// The keys of a group either come from the group key set the Group Key Map of its fabric
+/// maps the group to - as configured with the Group Key Management cluster - or have been
+/// added directly with [`GroupKeyMgr::add`].
+///
+/// Also keeps the group memberships of the endpoints of the node.
+pub struct GroupKeyMgr {
+    keys: heapless::Vec<GroupKey, MAX_GROUP_KEYS>,
+    key_sets: heapless::Vec<GroupKeySet, MAX_KEY_SETS>,
+    key_map: heapless::Vec<GroupKeyMapEntry, MAX_KEY_MAP_ENTRIES>,
+    memberships: heapless::Vec<GroupMembership, MAX_GROUP_MEMBERSHIPS>,
+    changed: bool,
+}
+
+impl GroupKeyMgr {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            keys: heapless::Vec::new(),
+            key_sets: heapless::Vec::new(),
+            key_map: heapless::Vec::new(),
+            memberships: heapless::Vec::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut self.key_sets, &root.find_tag(0)?)?;
+        tlv::from_tlv(&mut self.key_map, &root.find_tag(1)?)?;
+
+        // Not there in the data stored before the group memberships were persisted
+        self.memberships.clear();
+        if let Ok(memberships) = root.find_tag(2) {
+            tlv::from_tlv(&mut self.memberships, &memberships)?;
+        }
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    /// Stores the group key sets, the Group Key Map and the group memberships of all fabrics.
+    /// The keys added directly with [`GroupKeyMgr::add`] are not persisted
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.start_struct(TagType::Anonymous)?;
+            self.key_sets
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(0))?;
+            self.key_map
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(1))?;
+            self.memberships
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(2))?;
+            tw.end_container()?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Adds a group key set, replacing the existing one with the same ID - if any
+    pub fn set_key_set(&mut self, key_set: GroupKeySet) -> Result<(), Error> {
+        if let Some(existing) = self
+            .key_sets
+            .iter_mut()
+            .find(|ks| ks.fab_idx == key_set.fab_idx && ks.key_set_id == key_set.key_set_id)
+        {
+            *existing = key_set;
+        } else {
+            if self.key_sets(key_set.fab_idx).count() >= MAX_GROUP_KEY_SETS_PER_FABRIC {
+                Err(ErrorCode::ResourceExhausted)?;
+            }
+
+            self.key_sets
+                .push(key_set)
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes a group key set, together with the Group Key Map entries referring to it
+    pub fn remove_key_set(&mut self, fab_idx: u8, key_set_id: u16) -> Result<(), Error> {
+        let index = self
+            .key_sets
+            .iter()
+            .position(|ks| ks.fab_idx == fab_idx && ks.key_set_id == key_set_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        self.key_sets.swap_remove(index);
+        self.key_map
+            .retain(|e| e.fab_idx != fab_idx || e.key_set_id != key_set_id);
+        self.changed = true;
+
+        Ok(())
+    }
+
+    pub fn key_set(&self, fab_idx: u8, key_set_id: u16) -> Option<&GroupKeySet> {
+        self.key_sets
+            .iter()
+            .find(|ks| ks.fab_idx == fab_idx && ks.key_set_id == key_set_id)
+    }
+
+    /// The group key sets of a fabric
+    pub fn key_sets(&self, fab_idx: u8) -> impl Iterator<Item = &GroupKeySet> {
+        self.key_sets.iter().filter(move |ks| ks.fab_idx == fab_idx)
+    }
+
+    /// The Group Key Map entries of all fabrics
+    pub fn key_map(&self) -> impl Iterator<Item = &GroupKeyMapEntry> {
+        self.key_map.iter()
+    }
+
+    /// Adds an entry to the Group Key Map of its fabric. A group can only be mapped to a
+    /// single key set, and never to the IPK key set
+    pub fn add_key_map_entry(&mut self, entry: GroupKeyMapEntry) -> Result<(), Error> {
+        self.check_key_map_entry(&entry, None)?;
+
+        if self.key_map_entries(entry.fab_idx).count() >= MAX_GROUPS_PER_FABRIC {
+            Err(ErrorCode::ResourceExhausted)?;
+        }
+
+        self.key_map
+            .push(entry)
+            .map_err(|_| ErrorCode::ResourceExhausted)?;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Replaces the entry of the Group Key Map of a fabric at `index`, which is relative
+    /// to the entries of that fabric
+    pub fn edit_key_map_entry(&mut self, index: u16, entry: GroupKeyMapEntry) -> Result<(), Error> {
+        let index = self.key_map_index(index, entry.fab_idx)?;
+        self.check_key_map_entry(&entry, Some(index))?;
+
+        self.key_map[index] = entry;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes the entry of the Group Key Map of a fabric at `index`, which is relative
+    /// to the entries of that fabric
+    pub fn delete_key_map_entry(&mut self, index: u16, fab_idx: u8) -> Result<(), Error> {
+        let index = self.key_map_index(index, fab_idx)?;
+
+        // Keeps the order of the entries of the fabric, as list indices refer to it
+        self.key_map.remove(index);
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes all entries of the Group Key Map of a fabric
+    pub fn delete_key_map(&mut self, fab_idx: u8) {
+        let len = self.key_map.len();
+
+        self.key_map.retain(|e| e.fab_idx != fab_idx);
+
+        if self.key_map.len() != len {
+            self.changed = true;
+        }
+    }
+
+    fn key_map_entries(&self, fab_idx: u8) -> impl Iterator<Item = &GroupKeyMapEntry> {
+        self.key_map.iter().filter(move |e| e.fab_idx == fab_idx)
+    }
+
+    fn key_map_index(&self, index: u16, fab_idx: u8) -> Result<usize, Error> {
+        self.key_map
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.fab_idx == fab_idx)
+            .nth(index as usize)
+            .map(|(index, _)| index)
+            .ok_or(ErrorCode::NotFound.into())
+    }
+
+    fn check_key_map_entry(
+        &self,
+        entry: &GroupKeyMapEntry,
+        replaced: Option<usize>,
+    ) -> Result<(), Error> {
+        if entry.group_id == 0 || entry.key_set_id == IPK_KEY_SET_ID {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let duplicate = self.key_map.iter().enumerate().any(|(index, e)| {
+            Some(index) != replaced && e.fab_idx == entry.fab_idx && e.group_id == entry.group_id
+        });
+
+        if duplicate {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        Ok(())
+    }
+
+    /// The key set the Group Key Map of the fabric maps the group to - if any
+    fn mapped_key_set(&self, fab_idx: u8, group_id: u16) -> Option<&GroupKeySet> {
+        self.key_map_entries(fab_idx)
+            .find(|e| e.group_id == group_id)
+            .and_then(|e| self.key_set(fab_idx, e.key_set_id))
+    }
+
+    /// Makes an endpoint a member of a group of a fabric
+    pub fn add_membership(&mut self, membership: GroupMembership) -> Result<(), Error> {
+        if membership.group_id == 0 {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if self.memberships.contains(&membership) {
+            return Ok(());
+        }
+
+        let fabric_memberships = self
+            .memberships
+            .iter()
+            .filter(|m| m.fab_idx == membership.fab_idx)
+            .count();
+
+        if fabric_memberships >= MAX_GROUP_MEMBERSHIPS_PER_FABRIC {
+            Err(ErrorCode::ResourceExhausted)?;
+        }
+
+        self.memberships
+            .push(membership)
+            .map_err(|_| ErrorCode::ResourceExhausted)?;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes the membership of an endpoint in a group of a fabric
+    pub fn remove_membership(&mut self, membership: &GroupMembership) -> Result<(), Error> {
+        let index = self
+            .memberships
+            .iter()
+            .position(|m| m == membership)
+            .ok_or(ErrorCode::NotFound)?;
+
+        self.memberships.swap_remove(index);
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// The endpoints which are members of a group of a fabric
+    pub fn group_endpoints(&self, fab_idx: u8, group_id: u16) -> impl Iterator<Item = u16> + '_ {
+        self.memberships
+            .iter()
+            .filter(move |m| m.fab_idx == fab_idx && m.group_id == group_id)
+            .map(|m| m.endpoint_id)
+    }
+
+    /// Whether an endpoint is a member of a group of a fabric
+    pub fn is_member(&self, fab_idx: u8, group_id: u16, endpoint_id: u16) -> bool {
+        self.group_endpoints(fab_idx, group_id)
+            .any(|ep| ep == endpoint_id)
+    }
+
+    /// Adds the key of a group, replacing the existing one - if any
+    pub fn add(&mut self, key: GroupKey) -> Result<(), Error> {
+        if let Some(existing) = self
+            .keys
+            .iter_mut()
+            .find(|k| k.fab_idx == key.fab_idx && k.group_id == key.group_id)
+        {
+            *existing = key;
+        } else {
+            self.keys.push(key).map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, fab_idx: u8, group_id: u16) -> Result<(), Error> {
+        let index = self
+            .keys
+            .iter()
+            .position(|k| k.fab_idx == fab_idx && k.group_id == group_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        self.keys.swap_remove(index);
+
+        Ok(())
+    }
+
+    /// Removes the keys, the key sets, the Group Key Map and the group memberships of the given
+    /// fabric, e.g. when the fabric is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        self.keys.retain(|k| k.fab_idx != fab_idx);
+
+        let len = self.key_sets.len() + self.key_map.len() + self.memberships.len();
+
+        self.key_sets.retain(|ks| ks.fab_idx != fab_idx);
+        self.key_map.retain(|e| e.fab_idx != fab_idx);
+        self.memberships.retain(|m| m.fab_idx != fab_idx);
+
+        if self.key_sets.len() + self.key_map.len() + self.memberships.len() != len {
+            self.changed = true;
+        }
+    }
+
+    /// Returns the key used for sending messages to a group
+    pub fn get(&self, fab_idx: u8, group_id: u16) -> Option<GroupKey> {
+        self.keys
+            .iter()
+            .find(|k| k.fab_idx == fab_idx && k.group_id == group_id)
+            .cloned()
+            .or_else(|| {
+                self.mapped_key_set(fab_idx, group_id)
+                    .and_then(GroupKeySet::current)
+                    .map(|key| GroupKey::from_epoch_key(fab_idx, group_id, key))
+            })
+    }
+
+    /// Returns the keys which might have been used to encrypt a message
+    /// with the provided group session ID and destination group
+    pub fn candidates(
+        &self,
+        session_id: u16,
+        group_id: u16,
+    ) -> impl Iterator<Item = GroupKey> + '_ {
+        let direct = self
+            .keys
+            .iter()
+            .filter(move |k| k.session_id == session_id && k.group_id == group_id)
+            .cloned();
+
+        let mapped = self
+            .key_map
+            .iter()
+            .filter(move |e| e.group_id == group_id)
+            .filter_map(move |e| self.key_set(e.fab_idx, e.key_set_id))
+            .flat_map(move |ks| {
+                ks.epoch_keys()
+                    .filter(move |key| key.session_id == session_id)
+                    .map(move |key| GroupKey::from_epoch_key(ks.fab_idx, group_id, key))
+            });
+
+        direct.chain(mapped)
+    }
+}
+
+impl Default for GroupKeyMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::network::Ipv6Addr;
+
+    use super::{
+        group_multicast_addr, GroupKey, GroupKeyMapEntry, GroupKeyMgr, GroupKeySecurityPolicy,
+        GroupKeySet, GroupMembership, IPK_KEY_SET_ID, MAX_GROUP_MEMBERSHIPS_PER_FABRIC,
+    };
+
+    const COMPRESSED_ID: [u8; 8] = [0x87, 0xe1, 0xb0, 0x04, 0xe2, 0x35, 0xa1, 0x30];
+
+    #[test]
+    fn test_group_multicast_addr() {
+        assert_eq!(
+            group_multicast_addr(0x1122334455667788, 0x0101),
+            Ipv6Addr::new(0xff35, 0x0040, 0xfd11, 0x2233, 0x4455, 0x6677, 0x8800, 0x0101)
+        );
+    }
+
+    #[test]
+    fn test_group_key_mgr() {
+        let mut mgr = GroupKeyMgr::new();
+
+        let key1 = GroupKey::new(1, 0x0101, &[1; 16]).unwrap();
+        let key2 = GroupKey::new(2, 0x0101, &[2; 16]).unwrap();
+
+        mgr.add(key1.clone()).unwrap();
+        mgr.add(key2.clone()).unwrap();
+
+        assert_eq!(mgr.get(1, 0x0101).unwrap().op_key(), &[1; 16]);
+        assert_eq!(mgr.get(2, 0x0101).unwrap().op_key(), &[2; 16]);
+        assert!(mgr.get(1, 0x0102).is_none());
+
+        assert!(mgr
+            .candidates(key1.session_id(), 0x0101)
+            .any(|k| k.fab_idx == 1));
+        assert_eq!(mgr.candidates(key1.session_id(), 0x0102).count(), 0);
+
+        // Replaces the existing key of the group
+        mgr.add(GroupKey::new(1, 0x0101, &[3; 16]).unwrap())
+            .unwrap();
+        assert_eq!(mgr.get(1, 0x0101).unwrap().op_key(), &[3; 16]);
+
+        mgr.remove(1, 0x0101).unwrap();
+        assert!(mgr.get(1, 0x0101).is_none());
+        assert!(mgr.remove(1, 0x0101).is_err());
+        assert!(mgr.get(2, 0x0101).is_some());
+
+        assert!(GroupKey::new(1, 0x0101, &[1; 8]).is_err());
+    }
+
+    #[test]
+    fn test_group_key_sets() {
+        let mut mgr = GroupKeyMgr::new();
+
+        let key_set = GroupKeySet::new(
+            1,
+            0x01a1,
+            GroupKeySecurityPolicy::TrustFirst,
+            &[(&[1; 16], 100), (&[2; 16], 200)],
+            &COMPRESSED_ID,
+        )
+        .unwrap();
+        let old_key = key_set.epoch_keys().next().unwrap().clone();
+        let new_key = key_set.current().unwrap().clone();
+        assert_eq!(new_key.start_time, 200);
+
+        mgr.set_key_set(key_set).unwrap();
+        assert!(mgr.is_changed());
+
+        // Not mapped yet
+        assert!(mgr.get(1, 0x0101).is_none());
+
+        // Neither the IPK key set nor group 0 can be mapped
+        let entry = |group_id, key_set_id| GroupKeyMapEntry {
+            fab_idx: 1,
+            group_id,
+            key_set_id,
+        };
+        assert!(mgr
+            .add_key_map_entry(entry(0x0101, IPK_KEY_SET_ID))
+            .is_err());
+        assert!(mgr.add_key_map_entry(entry(0, 0x01a1)).is_err());
+
+        mgr.add_key_map_entry(entry(0x0101, 0x01a1)).unwrap();
+        // A group is mapped to a single key set
+        assert!(mgr.add_key_map_entry(entry(0x0101, 0x01a2)).is_err());
+
+        // Sending uses the epoch key with the latest start time
+        assert_eq!(mgr.get(1, 0x0101).unwrap().op_key(), new_key.op_key());
+
+        // Receiving accepts all epoch keys
+        assert!(mgr
+            .candidates(old_key.session_id(), 0x0101)
+            .any(|k| k.op_key() == old_key.op_key() && k.fab_idx == 1));
+        assert!(mgr
+            .candidates(new_key.session_id(), 0x0101)
+            .any(|k| k.op_key() == new_key.op_key()));
+        assert_eq!(mgr.candidates(new_key.session_id(), 0x0102).count(), 0);
+
+        let mut buf = [0; 1024];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+        assert!(!mgr.is_changed());
+
+        let mut loaded = GroupKeyMgr::new();
+        loaded.load(data).unwrap();
+        assert_eq!(loaded.get(1, 0x0101).unwrap().op_key(), new_key.op_key());
+        assert_eq!(loaded.key_map().count(), 1);
+
+        // Removing the key set removes its Group Key Map entries
+        loaded.remove_key_set(1, 0x01a1).unwrap();
+        assert!(loaded.get(1, 0x0101).is_none());
+        assert_eq!(loaded.key_map().count(), 0);
+
+        mgr.remove_fabric(1);
+        assert!(mgr.is_changed());
+        assert!(mgr.key_set(1, 0x01a1).is_none());
+        assert!(mgr.get(1, 0x0101).is_none());
+    }
+
+    #[test]
+    fn test_group_memberships() {
+        let mut mgr = GroupKeyMgr::new();
+
+        let membership = |fab_idx, group_id, endpoint_id| GroupMembership {
+            fab_idx,
+            group_id,
+            endpoint_id,
+        };
+
+        assert!(mgr.add_membership(membership(1, 0, 1)).is_err());
+
+        mgr.add_membership(membership(1, 0x0101, 1)).unwrap();
+        mgr.add_membership(membership(1, 0x0101, 2)).unwrap();
+        mgr.add_membership(membership(2, 0x0101, 3)).unwrap();
+        // Adding an existing membership again is a no-op
+        mgr.add_membership(membership(1, 0x0101, 1)).unwrap();
+        assert!(mgr.is_changed());
+
+        assert_eq!(mgr.group_endpoints(1, 0x0101).count(), 2);
+        assert!(mgr.is_member(1, 0x0101, 2));
+        assert!(!mgr.is_member(1, 0x0101, 3));
+        assert!(mgr.is_member(2, 0x0101, 3));
+        assert!(!mgr.is_member(1, 0x0102, 1));
+
+        let mut buf = [0; 1024];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+
+        let mut loaded = GroupKeyMgr::new();
+        loaded.load(data).unwrap();
+        assert!(loaded.is_member(1, 0x0101, 1));
+        assert!(loaded.is_member(2, 0x0101, 3));
+
+        loaded.remove_membership(&membership(1, 0x0101, 1)).unwrap();
+        assert!(!loaded.is_member(1, 0x0101, 1));
+        assert!(loaded.remove_membership(&membership(1, 0x0101, 1)).is_err());
+
+        for endpoint_id in 0..MAX_GROUP_MEMBERSHIPS_PER_FABRIC as u16 {
+            let _ = mgr.add_membership(membership(1, 0x0102, endpoint_id));
+        }
+        assert!(mgr.add_membership(membership(1, 0x0103, 1)).is_err());
+
+        mgr.remove_fabric(1);
+        assert_eq!(mgr.group_endpoints(1, 0x0101).count(), 0);
+        assert!(mgr.is_member(2, 0x0101, 3));
+    }
+}
@@ -0,0 +1,318 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The clients registered with this node, when it operates as an Intermittently Connected
+//! Device (ICD).
+//!
+//! Clients register - with the RegisterClient command of the ICD Management cluster - the
+//! symmetric key the ICD encrypts its Check-In messages with (see
+//! [`crate::secure_channel::check_in`]), along with the subject the ICD checks in on behalf of.
+//!
+//! The registrations are persisted (see [`crate::Matter::store_icd_clients`]), as the clients
+//! expect the ICD to keep checking in with them after a reboot. The keys are never reported
+//! back to the clients.
+
+use heapless::Vec;
+
+use crate::{
+    error::{Error, ErrorCode},
+    secure_channel::check_in::CHECK_IN_KEY_LEN,
+    tlv::{self, FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+pub const MAX_ICD_CLIENTS_PER_FABRIC: usize = crate::config::MAX_ICD_CLIENTS_PER_FABRIC;
+
+const MAX_ICD_CLIENTS: usize = MAX_ICD_CLIENTS_PER_FABRIC * crate::config::MAX_FABRICS;
+
+/// Whether the client keeps its registration for good, or only while it is around
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IcdClientType {
+    Permanent = 0,
+    Ephemeral = 1,
+}
+
+impl ToTLV for IcdClientType {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.u8(tag, *self as u8)
+    }
+}
+
+impl<'a> FromTLV<'a> for IcdClientType {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        match t.u8()? {
+            0 => Ok(Self::Permanent),
+            1 => Ok(Self::Ephemeral),
+            _ => Err(ErrorCode::InvalidData.into()),
+        }
+    }
+}
+
+/// The registration of an ICD client
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct IcdClient {
+    pub fab_idx: u8,
+    /// The node ID the Check-In messages are sent to
+    pub check_in_node_id: u64,
+    /// The subject - a node ID or a CAT - the client monitors the ICD as
+    pub monitored_subject: u64,
+    pub client_type: IcdClientType,
+    key: Vec<u8, CHECK_IN_KEY_LEN>,
+}
+
+impl IcdClient {
+    pub fn new(
+        fab_idx: u8,
+        check_in_node_id: u64,
+        monitored_subject: u64,
+        key: &[u8],
+        client_type: IcdClientType,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            fab_idx,
+            check_in_node_id,
+            monitored_subject,
+            client_type,
+            key: Vec::from_slice(key).map_err(|_| ErrorCode::ConstraintError)?,
+        })
+    }
+
+    /// The symmetric key the Check-In messages to the client are encrypted with
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+type IcdClientEntries = Vec<Option<IcdClient>, MAX_ICD_CLIENTS>;
+
+pub struct IcdClientMgr {
+    clients: IcdClientEntries,
+    changed: bool,
+}
+
+impl IcdClientMgr {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            clients: IcdClientEntries::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut self.clients, &root)?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.clients
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Registers a client, or updates its registration if it is already registered on the
+    /// fabric. Unless the update comes from an administrator, `verification_key` has to match
+    /// the key the client registered with.
+    pub fn register(
+        &mut self,
+        client: IcdClient,
+        verification_key: Option<&[u8]>,
+        is_admin: bool,
+    ) -> Result<(), Error> {
+        if client.key.len() != CHECK_IN_KEY_LEN {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        if let Some(existing) = self.find_mut(client.fab_idx, client.check_in_node_id) {
+            if !is_admin && verification_key != Some(existing.key()) {
+                Err(ErrorCode::UnsupportedAccess)?;
+            }
+
+            *existing = client;
+        } else if self.clients_on_fabric(client.fab_idx) >= MAX_ICD_CLIENTS_PER_FABRIC {
+            Err(ErrorCode::ResourceExhausted)?;
+        } else if let Some(slot) = self.clients.iter_mut().find(|c| c.is_none()) {
+            *slot = Some(client);
+        } else {
+            self.clients
+                .push(Some(client))
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes the registration of a client. Unless the removal comes from an administrator,
+    /// `verification_key` has to match the key the client registered with.
+    pub fn unregister(
+        &mut self,
+        fab_idx: u8,
+        check_in_node_id: u64,
+        verification_key: Option<&[u8]>,
+        is_admin: bool,
+    ) -> Result<(), Error> {
+        let slot = self
+            .clients
+            .iter_mut()
+            .find(|c| {
+                c.as_ref()
+                    .map(|c| c.fab_idx == fab_idx && c.check_in_node_id == check_in_node_id)
+                    .unwrap_or(false)
+            })
+            .ok_or(ErrorCode::NotFound)?;
+
+        if !is_admin && verification_key != slot.as_ref().map(IcdClient::key) {
+            Err(ErrorCode::UnsupportedAccess)?;
+        }
+
+        *slot = None;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    pub fn get(&self, fab_idx: u8, check_in_node_id: u64) -> Option<&IcdClient> {
+        self.iter()
+            .find(|c| c.fab_idx == fab_idx && c.check_in_node_id == check_in_node_id)
+    }
+
+    /// All registered clients, on all fabrics
+    pub fn iter(&self) -> impl Iterator<Item = &IcdClient> {
+        self.clients.iter().flatten()
+    }
+
+    /// Removes all clients registered on the given fabric, e.g. when the fabric itself is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        for slot in self.clients.iter_mut() {
+            if slot.as_ref().map(|c| c.fab_idx == fab_idx).unwrap_or(false) {
+                *slot = None;
+                self.changed = true;
+            }
+        }
+    }
+
+    fn find_mut(&mut self, fab_idx: u8, check_in_node_id: u64) -> Option<&mut IcdClient> {
+        self.clients
+            .iter_mut()
+            .flatten()
+            .find(|c| c.fab_idx == fab_idx && c.check_in_node_id == check_in_node_id)
+    }
+
+    fn clients_on_fabric(&self, fab_idx: u8) -> usize {
+        self.iter().filter(|c| c.fab_idx == fab_idx).count()
+    }
+}
+
+impl Default for IcdClientMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IcdClient, IcdClientMgr, IcdClientType, MAX_ICD_CLIENTS_PER_FABRIC};
+
+    const KEY: [u8; 16] = [0x11; 16];
+    const OTHER_KEY: [u8; 16] = [0x22; 16];
+
+    fn client(fab_idx: u8, node_id: u64, subject: u64, key: &[u8]) -> IcdClient {
+        IcdClient::new(fab_idx, node_id, subject, key, IcdClientType::Permanent).unwrap()
+    }
+
+    #[test]
+    fn test_register() {
+        let mut mgr = IcdClientMgr::new();
+
+        mgr.register(client(1, 100, 100, &KEY), None, false)
+            .unwrap();
+        assert_eq!(mgr.get(1, 100).unwrap().key(), &KEY);
+        assert!(mgr.get(2, 100).is_none());
+
+        // Keys have a fixed length
+        assert!(mgr
+            .register(client(1, 101, 101, &KEY[..8]), None, false)
+            .is_err());
+
+        // Re-registering needs the current key, unless done by an administrator
+        assert!(mgr
+            .register(client(1, 100, 100, &OTHER_KEY), None, false)
+            .is_err());
+        mgr.register(client(1, 100, 100, &OTHER_KEY), Some(&KEY), false)
+            .unwrap();
+        mgr.register(client(1, 100, 200, &KEY), None, true).unwrap();
+        assert_eq!(mgr.get(1, 100).unwrap().monitored_subject, 200);
+        assert_eq!(mgr.iter().count(), 1);
+
+        assert!(mgr.unregister(1, 100, Some(&OTHER_KEY), false).is_err());
+        mgr.unregister(1, 100, Some(&KEY), false).unwrap();
+        assert!(mgr.get(1, 100).is_none());
+        assert!(mgr.unregister(1, 100, None, true).is_err());
+    }
+
+    #[test]
+    fn test_limits_and_persistence() {
+        let mut mgr = IcdClientMgr::new();
+
+        for node_id in 0..MAX_ICD_CLIENTS_PER_FABRIC as u64 {
+            mgr.register(client(1, node_id, node_id, &KEY), None, false)
+                .unwrap();
+        }
+        assert!(mgr
+            .register(client(1, 1000, 1000, &KEY), None, false)
+            .is_err());
+        mgr.register(client(2, 1000, 1000, &KEY), None, false)
+            .unwrap();
+
+        let mut buf = [0; 1024];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+        assert!(!mgr.is_changed());
+
+        let mut loaded = IcdClientMgr::new();
+        loaded.load(data).unwrap();
+        assert_eq!(loaded.iter().count(), MAX_ICD_CLIENTS_PER_FABRIC + 1);
+        assert_eq!(loaded.get(2, 1000), mgr.get(2, 1000));
+
+        loaded.remove_fabric(1);
+        assert_eq!(loaded.iter().count(), 1);
+        assert!(loaded.is_changed());
+    }
+}
@@ -0,0 +1,678 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The client side of the Interaction Model: the Read, Write and Invoke interactions this node
+//! initiates with a peer - e.g. to control the devices it is bound to, or as a controller - over
+//! an exchange opened with [`crate::Matter::initiate`].
+//!
+//! The responses are decoded in place, from the RX packet of the client, so they can only be
+//! inspected until the next interaction. [`ReadClient::read_attr`],
+//! [`WriteClient::write_attr`] and [`InvokeClient::invoke_cmd`] decode single values into
+//! owned types instead.
+//!
+//! A StatusResponse of the peer, rejecting a request as a whole, is returned as the error its
+//! status stands for (see [`IMStatusCode::to_result`]).
+//!
+//! A [`SubscribeClient`] keeps a subscription to a peer established. The reports of the
+//! subscription arrive on exchanges initiated by the peer, so they are handed over by the
+//! exchange handlers to the [`ReportHandler`] set with [`crate::Matter::set_report_handler`].
+
+use core::cell::Cell;
+
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+use log::{error, info, warn};
+
+use crate::{
+    data_model::objects::{AttrId, ClusterId, CmdId, EncodeValue, EndptId},
+    error::{Error, ErrorCode},
+    tlv::{get_root_node_struct, FromTLV, TLVArray, TLVWriter, TagType, ToTLV},
+    transport::{exchange::Exchange, packet::Packet},
+    utils::select::Notification,
+    Matter,
+};
+
+use super::{
+    core::{IMStatusCode, OpCode, PROTO_ID_INTERACTION_MODEL},
+    messages::{
+        ib::{self, AttrData, AttrPath, AttrResp, CmdData, CmdPath},
+        msg::{
+            InvReq, InvResp, ReadReq, ReportDataMsg, StatusResp, SubscribeReq, SubscribeResp,
+            TimedReq, WriteReq, WriteResp,
+        },
+        GenericPath,
+    },
+};
+
+/// Reads attributes and events of a peer
+pub struct ReadClient<'a, 'r, 'p> {
+    exchange: &'r mut Exchange<'a>,
+    tx: &'r mut Packet<'p>,
+    rx: &'r mut Packet<'p>,
+}
+
+impl<'a, 'r, 'p> ReadClient<'a, 'r, 'p> {
+    pub fn new(
+        exchange: &'r mut Exchange<'a>,
+        tx: &'r mut Packet<'p>,
+        rx: &'r mut Packet<'p>,
+    ) -> Self {
+        Self { exchange, tx, rx }
+    }
+
+    /// Sends `req`, and calls `f` with each chunk of the report the peer responds with, in order.
+    /// The paths the peer could not read are reported in the chunks with their status.
+    pub async fn read<F>(&mut self, req: &ReadReq<'_>, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&ReportDataMsg<'_>) -> Result<(), Error>,
+    {
+        tx_request(self.tx, OpCode::ReadRequest, req)?;
+        self.exchange.exchange(self.tx, self.rx).await?;
+
+        loop {
+            expect(self.exchange, self.rx, OpCode::ReportData).await?;
+
+            let (more_chunks, suppress_response) = {
+                let report = ReportDataMsg::from_tlv(&get_root_node_struct(self.rx.as_slice())?)?;
+
+                f(&report)?;
+
+                (
+                    report.more_chunks.unwrap_or(false),
+                    report.suppress_response.unwrap_or(false),
+                )
+            };
+
+            if more_chunks {
+                tx_status(self.tx, IMStatusCode::Success)?;
+                self.exchange.exchange(self.tx, self.rx).await?;
+            } else if suppress_response {
+                // Nothing else is sent on the exchange, so the report needs a standalone ack
+                self.exchange.acknowledge().await?;
+
+                break Ok(());
+            } else {
+                tx_status(self.tx, IMStatusCode::Success)?;
+                self.exchange.send_complete(self.tx).await?;
+
+                break Ok(());
+            }
+        }
+    }
+
+    /// Reads a single attribute, and decodes its value as a `T`. The value needs to fit
+    /// in a single AttributeDataIB, i.e. it cannot be a list long enough to be chunked.
+    pub async fn read_attr<T>(
+        &mut self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        attr: AttrId,
+    ) -> Result<T, Error>
+    where
+        T: for<'t> FromTLV<'t>,
+    {
+        let path = [attr_path(endpoint, cluster, attr)];
+        let req = ReadReq::new(true).set_attr_requests(&path);
+
+        let mut value = None;
+
+        self.read(&req, |report| {
+            for resp in report.attr_resps() {
+                match resp {
+                    AttrResp::Data(data) => {
+                        let data = data.data.unwrap_tlv().ok_or(ErrorCode::Invalid)?;
+                        value = Some(T::from_tlv(&data)?);
+                    }
+                    AttrResp::Status(status) => status.status().to_result()?,
+                }
+            }
+
+            Ok(())
+        })
+        .await?;
+
+        value.ok_or_else(|| ErrorCode::AttributeNotFound.into())
+    }
+}
+
+/// Writes attributes of a peer. Chunked writes are not supported, so all data of a request
+/// needs to fit in a single message.
+pub struct WriteClient<'a, 'r, 'p> {
+    exchange: &'r mut Exchange<'a>,
+    tx: &'r mut Packet<'p>,
+    rx: &'r mut Packet<'p>,
+}
+
+impl<'a, 'r, 'p> WriteClient<'a, 'r, 'p> {
+    pub fn new(
+        exchange: &'r mut Exchange<'a>,
+        tx: &'r mut Packet<'p>,
+        rx: &'r mut Packet<'p>,
+    ) -> Self {
+        Self { exchange, tx, rx }
+    }
+
+    /// Sends `req`, and returns the WriteResponse of the peer, with the status of each written
+    /// path - or no statuses at all, if `req` suppresses the response.
+    ///
+    /// With a `timeout` (in milliseconds), `req` follows a TimedRequest, and needs to be marked
+    /// as timed (see [`WriteReq::set_timed_request`]).
+    pub async fn write(
+        &mut self,
+        req: &WriteReq<'_>,
+        timeout: Option<u16>,
+    ) -> Result<WriteResp<'_>, Error> {
+        if let Some(timeout) = timeout {
+            timed(self.exchange, self.tx, self.rx, timeout).await?;
+        }
+
+        tx_request(self.tx, OpCode::WriteRequest, req)?;
+
+        if req.supress_response.unwrap_or(false) {
+            self.exchange.send_complete(self.tx).await?;
+
+            return Ok(WriteResp {
+                write_responses: TLVArray::new(&[]),
+            });
+        }
+
+        self.exchange.exchange(self.tx, self.rx).await?;
+        expect(self.exchange, self.rx, OpCode::WriteResponse).await?;
+
+        // Nothing else is sent on the exchange, so the response needs a standalone ack
+        self.exchange.acknowledge().await?;
+
+        WriteResp::from_tlv(&get_root_node_struct(self.rx.as_slice())?)
+    }
+
+    /// Writes `value` to a single attribute, timed if there is a `timeout` (in milliseconds)
+    pub async fn write_attr<T: ToTLV>(
+        &mut self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        attr: AttrId,
+        value: &T,
+        timeout: Option<u16>,
+    ) -> Result<(), Error> {
+        let data = [AttrData::new(
+            None,
+            attr_path(endpoint, cluster, attr),
+            EncodeValue::Value(value),
+        )];
+
+        let mut req = WriteReq::new(false, &data);
+        if timeout.is_some() {
+            req = req.set_timed_request(true);
+        }
+
+        let resp = self.write(&req, timeout).await?;
+
+        for status in resp.write_responses.iter() {
+            status.status().to_result()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Invokes commands of a peer
+pub struct InvokeClient<'a, 'r, 'p> {
+    exchange: &'r mut Exchange<'a>,
+    tx: &'r mut Packet<'p>,
+    rx: &'r mut Packet<'p>,
+}
+
+impl<'a, 'r, 'p> InvokeClient<'a, 'r, 'p> {
+    pub fn new(
+        exchange: &'r mut Exchange<'a>,
+        tx: &'r mut Packet<'p>,
+        rx: &'r mut Packet<'p>,
+    ) -> Self {
+        Self { exchange, tx, rx }
+    }
+
+    /// Sends `req`, and returns the InvokeResponse of the peer, with the response data - or the
+    /// status - of each invoked command. Without any, if `req` suppresses the response.
+    ///
+    /// With a `timeout` (in milliseconds), `req` follows a TimedRequest, and needs to be marked
+    /// as timed.
+    pub async fn invoke(
+        &mut self,
+        req: &InvReq<'_>,
+        timeout: Option<u16>,
+    ) -> Result<InvResp<'_>, Error> {
+        if let Some(timeout) = timeout {
+            timed(self.exchange, self.tx, self.rx, timeout).await?;
+        }
+
+        tx_request(self.tx, OpCode::InvokeRequest, req)?;
+
+        if req.suppress_response.unwrap_or(false) {
+            self.exchange.send_complete(self.tx).await?;
+
+            return Ok(InvResp {
+                suppress_response: Some(true),
+                inv_responses: None,
+            });
+        }
+
+        self.exchange.exchange(self.tx, self.rx).await?;
+        expect(self.exchange, self.rx, OpCode::InvokeResponse).await?;
+
+        // Nothing else is sent on the exchange, so the response needs a standalone ack
+        self.exchange.acknowledge().await?;
+
+        InvResp::from_tlv(&get_root_node_struct(self.rx.as_slice())?)
+    }
+
+    /// Invokes a single command, with `data` as its fields, timed if there is a `timeout`
+    /// (in milliseconds). Returns the data of the response of the command, decoded as an `R`,
+    /// or `None` if the peer only responded with a success status.
+    pub async fn invoke_cmd<R>(
+        &mut self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        cmd: CmdId,
+        data: &dyn ToTLV,
+        timeout: Option<u16>,
+    ) -> Result<Option<R>, Error>
+    where
+        R: for<'t> FromTLV<'t>,
+    {
+        let cmds = [CmdData::new(
+            CmdPath::new(Some(endpoint), Some(cluster), Some(cmd)),
+            EncodeValue::Value(data),
+        )];
+
+        let req = InvReq {
+            suppress_response: None,
+            timed_request: timeout.map(|_| true),
+            inv_requests: Some(TLVArray::new(&cmds)),
+        };
+
+        let resp = self.invoke(&req, timeout).await?;
+
+        let resp = resp
+            .inv_responses
+            .iter()
+            .flat_map(|resps| resps.iter())
+            .next()
+            .ok_or(ErrorCode::Invalid)?;
+
+        match resp {
+            ib::InvResp::Cmd(data) => {
+                let data = data.data.unwrap_tlv().ok_or(ErrorCode::Invalid)?;
+
+                Ok(Some(R::from_tlv(&data)?))
+            }
+            ib::InvResp::Status(status) => {
+                status.status().to_result()?;
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// How much longer than the max interval of a subscription its client waits for a report,
+/// before it considers the subscription lost, to allow for the retransmissions of the report
+const LIVENESS_MARGIN_SECS: u64 = 10;
+
+/// How long a [`SubscribeClient`] waits before trying to subscribe again, after it failed to
+const RESUBSCRIBE_DELAY_SECS: u64 = 5;
+
+/// Handles the reports of the subscriptions this node established as a client
+/// (see [`crate::Matter::set_report_handler`])
+pub trait ReportHandler {
+    /// Handles a chunk of a report of the subscription with ID `subscription_id`, including
+    /// the empty reports which only keep the subscription alive. Returns `false` if the
+    /// subscription is not known, in which case the peer is told to drop it.
+    fn handle(&self, subscription_id: u32, report: &ReportDataMsg<'_>) -> bool;
+}
+
+impl<T> ReportHandler for &T
+where
+    T: ReportHandler,
+{
+    fn handle(&self, subscription_id: u32, report: &ReportDataMsg<'_>) -> bool {
+        (*self).handle(subscription_id, report)
+    }
+}
+
+/// Keeps a subscription to a peer established (see [`SubscribeClient::run`]), passing all of
+/// its reports - the priming one included - on to a [`ReportHandler`].
+///
+/// The client needs to be set as the report handler of the Matter instance - so it needs to be
+/// `'static`, e.g. leaked - so that it gets the reports of the subscription and notices when
+/// they stop coming.
+pub struct SubscribeClient<H> {
+    handler: H,
+    subscription_id: Cell<Option<u32>>,
+    notification: Notification,
+}
+
+impl<H> SubscribeClient<H>
+where
+    H: ReportHandler,
+{
+    pub const fn new(handler: H) -> Self {
+        Self {
+            handler,
+            subscription_id: Cell::new(None),
+            notification: Notification::new(),
+        }
+    }
+
+    /// The ID of the subscription, while it is established
+    pub fn subscription_id(&self) -> Option<u32> {
+        self.subscription_id.get()
+    }
+
+    /// Subscribes with `req` to the peer `peer_node_id` on fabric `fab_idx`, over the most
+    /// recently used CASE session with it. Once no report arrives within the max interval of
+    /// the subscription - as negotiated with the peer - the subscription is considered lost,
+    /// and established again. So is it if subscribing fails, after a delay.
+    ///
+    /// Runs until it is dropped. `tx_buf` and `rx_buf` are the packet buffers of the exchanges
+    /// establishing the subscription.
+    pub async fn run(
+        &self,
+        matter: &Matter<'_>,
+        fab_idx: u8,
+        peer_node_id: u64,
+        req: &SubscribeReq<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+    ) -> Result<(), Error> {
+        loop {
+            match self
+                .subscribe(matter, fab_idx, peer_node_id, req, tx_buf, rx_buf)
+                .await
+            {
+                Ok(max_int) => {
+                    self.wait_lost(max_int).await;
+
+                    warn!(
+                        "Subscription {:?}: no report within the max interval, subscribing again",
+                        self.subscription_id.get()
+                    );
+
+                    self.subscription_id.set(None);
+                }
+                Err(e) => {
+                    warn!("Subscribing failed: {:?}", e);
+
+                    Timer::after(Duration::from_secs(RESUBSCRIBE_DELAY_SECS)).await;
+                }
+            }
+        }
+    }
+
+    /// Establishes the subscription, passing its priming report on to the handler. Returns
+    /// the max interval of the subscription.
+    async fn subscribe(
+        &self,
+        matter: &Matter<'_>,
+        fab_idx: u8,
+        peer_node_id: u64,
+        req: &SubscribeReq<'_>,
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+    ) -> Result<u16, Error> {
+        let session_id = matter
+            .session_mgr()
+            .borrow()
+            .case_session(fab_idx, peer_node_id)
+            .ok_or(ErrorCode::NoSession)?;
+
+        let mut exchange = matter.initiate(session_id)?;
+
+        // Unless the session supports Large Payloads, the request needs to fit in the MTU
+        let max_tx_size = exchange.with_session(|sess| Ok(sess.max_tx_size()))?;
+        let tx_len = core::cmp::min(tx_buf.len(), max_tx_size);
+
+        let mut tx = Packet::new_tx(&mut tx_buf[..tx_len]);
+        let mut rx = Packet::new_rx(rx_buf);
+
+        tx_request(&mut tx, OpCode::SubscribeRequest, req)?;
+        exchange.exchange(&mut tx, &mut rx).await?;
+
+        // The chunks of the priming report, each of which is confirmed, and the SubscribeResponse
+        while rx.get_proto_opcode::<OpCode>()? != OpCode::SubscribeResponse {
+            expect(&mut exchange, &rx, OpCode::ReportData).await?;
+
+            {
+                let report = ReportDataMsg::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
+                let subscription_id = report.subscription_id.ok_or(ErrorCode::Invalid)?;
+
+                self.handler.handle(subscription_id, &report);
+            }
+
+            tx_status(&mut tx, IMStatusCode::Success)?;
+            exchange.exchange(&mut tx, &mut rx).await?;
+        }
+
+        let resp = SubscribeResp::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
+
+        // Nothing else is sent on the exchange, so the response needs a standalone ack
+        exchange.acknowledge().await?;
+
+        info!(
+            "Subscription {}: established, max interval {}s",
+            resp.subs_id, resp.max_int
+        );
+
+        self.notification.reset();
+        self.subscription_id.set(Some(resp.subs_id));
+
+        Ok(resp.max_int)
+    }
+
+    /// Waits until no report arrives within the max interval of the subscription
+    async fn wait_lost(&self, max_int: u16) {
+        let timeout = Duration::from_secs(max_int as u64 + LIVENESS_MARGIN_SECS);
+
+        while let Either::First(_) = select(self.notification.wait(), Timer::after(timeout)).await {
+        }
+    }
+}
+
+impl<H> ReportHandler for SubscribeClient<H>
+where
+    H: ReportHandler,
+{
+    fn handle(&self, subscription_id: u32, report: &ReportDataMsg<'_>) -> bool {
+        if self.subscription_id.get() != Some(subscription_id) {
+            return false;
+        }
+
+        self.notification.signal(());
+
+        self.handler.handle(subscription_id, report)
+    }
+}
+
+/// Handles a report - which might span several chunks - of a subscription this node
+/// established as a client, received on an exchange initiated by the publisher
+pub(crate) async fn handle_report(
+    exchange: &mut Exchange<'_>,
+    rx: &mut Packet<'_>,
+    tx: &mut Packet<'_>,
+) -> Result<(), Error> {
+    let handler = exchange.matter.report_handler.get();
+
+    loop {
+        let (accepted, more_chunks, suppress_response) = {
+            let report = ReportDataMsg::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
+
+            let accepted = report
+                .subscription_id
+                .zip(handler)
+                .map(|(id, handler)| handler.handle(id, &report))
+                .unwrap_or(false);
+
+            (
+                accepted,
+                report.more_chunks.unwrap_or(false),
+                report.suppress_response.unwrap_or(false),
+            )
+        };
+
+        if !accepted {
+            tx_status(tx, IMStatusCode::InvalidSubscription)?;
+            exchange.send_complete(tx).await?;
+
+            break Ok(());
+        } else if more_chunks {
+            tx_status(tx, IMStatusCode::Success)?;
+            exchange.exchange(tx, rx).await?;
+
+            expect(exchange, rx, OpCode::ReportData).await?;
+        } else if suppress_response {
+            // Nothing else is sent on the exchange, so the report needs a standalone ack
+            exchange.acknowledge().await?;
+
+            break Ok(());
+        } else {
+            tx_status(tx, IMStatusCode::Success)?;
+            exchange.send_complete(tx).await?;
+
+            break Ok(());
+        }
+    }
+}
+
+fn attr_path(endpoint: EndptId, cluster: ClusterId, attr: AttrId) -> AttrPath {
+    AttrPath::new(&GenericPath::new(
+        Some(endpoint),
+        Some(cluster),
+        Some(attr as _),
+    ))
+}
+
+/// Sends a TimedRequest, opening a timed window of `timeout` milliseconds on the exchange
+/// for the Write or Invoke request following it
+async fn timed(
+    exchange: &mut Exchange<'_>,
+    tx: &mut Packet<'_>,
+    rx: &mut Packet<'_>,
+    timeout: u16,
+) -> Result<(), Error> {
+    tx_request(tx, OpCode::TimedRequest, &TimedReq { timeout })?;
+    exchange.exchange(tx, rx).await?;
+
+    expect(exchange, rx, OpCode::StatusResponse).await?;
+
+    let status = StatusResp::from_tlv(&get_root_node_struct(rx.as_slice())?)?.status;
+
+    if status != IMStatusCode::Success {
+        // The interaction is over, so the status response needs a standalone ack
+        exchange.acknowledge().await?;
+    }
+
+    status.to_result()
+}
+
+/// Checks that the peer responded with `opcode`. A StatusResponse - ending the interaction -
+/// is acknowledged and returned as the error its status stands for instead.
+async fn expect(exchange: &mut Exchange<'_>, rx: &Packet<'_>, opcode: OpCode) -> Result<(), Error> {
+    let rx_opcode: OpCode = rx.get_proto_opcode()?;
+
+    if rx_opcode == opcode {
+        return Ok(());
+    }
+
+    if rx_opcode == OpCode::StatusResponse {
+        let status = StatusResp::from_tlv(&get_root_node_struct(rx.as_slice())?)?.status;
+
+        exchange.acknowledge().await?;
+
+        status.to_result()?;
+    }
+
+    error!("Unexpected opcode in a response: {:?}", rx_opcode);
+
+    Err(ErrorCode::InvalidOpcode.into())
+}
+
+fn tx_request(tx: &mut Packet, opcode: OpCode, req: &dyn ToTLV) -> Result<(), Error> {
+    tx.reset();
+    tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+    tx.set_proto_opcode(opcode as u8);
+
+    let mut tw = TLVWriter::new(tx.get_writebuf()?);
+
+    req.to_tlv(&mut tw, TagType::Anonymous)
+}
+
+fn tx_status(tx: &mut Packet, status: IMStatusCode) -> Result<(), Error> {
+    tx_request(tx, OpCode::StatusResponse, &StatusResp { status })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorCode;
+    use crate::interaction_model::core::{IMStatusCode, OpCode};
+    use crate::interaction_model::messages::{ib::Status, msg::ReadReq};
+    use crate::tlv::{get_root_node_struct, FromTLV};
+    use crate::transport::packet::Packet;
+
+    use super::{attr_path, tx_request};
+
+    #[test]
+    fn test_status_to_result() {
+        assert!(Status::new(IMStatusCode::Success, 0).to_result().is_ok());
+        assert_eq!(
+            Status::new(IMStatusCode::UnsupportedAttribute, 0)
+                .to_result()
+                .unwrap_err()
+                .code(),
+            ErrorCode::AttributeNotFound
+        );
+        assert_eq!(
+            Status::new(IMStatusCode::Failure, 2)
+                .to_result()
+                .unwrap_err()
+                .code(),
+            ErrorCode::ClusterStatus(2)
+        );
+    }
+
+    #[test]
+    fn test_tx_request() {
+        let mut buf = [0; 256];
+        let mut tx = Packet::new_tx(&mut buf);
+
+        let paths = [attr_path(1, 6, 0)];
+        let req = ReadReq::new(true).set_attr_requests(&paths);
+
+        tx_request(&mut tx, OpCode::ReadRequest, &req).unwrap();
+
+        assert_eq!(
+            tx.get_proto_opcode::<OpCode>().unwrap(),
+            OpCode::ReadRequest
+        );
+
+        let sent = ReadReq::from_tlv(&get_root_node_struct(tx.as_slice()).unwrap()).unwrap();
+        assert!(sent.fabric_filtered);
+
+        let sent_paths = sent.attr_requests.unwrap();
+        let mut sent_paths = sent_paths.iter();
+        assert_eq!(sent_paths.next(), Some(paths[0].clone()));
+        assert_eq!(sent_paths.next(), None);
+    }
+}
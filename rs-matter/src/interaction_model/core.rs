@@ -21,7 +21,11 @@ use crate::{
     acl::Accessor,
     error::*,
     tlv::{get_root_node_struct, FromTLV, TLVElement, TLVWriter, TagType, ToTLV},
-    transport::{exchange::Exchange, packet::Packet},
+    transport::{
+        exchange::Exchange,
+        packet::Packet,
+        session::{SessionMode, MAX_PATHS_PER_INVOKE},
+    },
     utils::epoch::Epoch,
 };
 use log::error;
@@ -83,11 +87,43 @@ impl From<ErrorCode> for IMStatusCode {
             ErrorCode::Busy => IMStatusCode::Busy,
             ErrorCode::DataVersionMismatch => IMStatusCode::DataVersionMismatch,
             ErrorCode::ResourceExhausted => IMStatusCode::ResourceExhausted,
+            ErrorCode::ConstraintError => IMStatusCode::ConstraintError,
+            ErrorCode::InvalidDataType => IMStatusCode::InvalidDataType,
+            ErrorCode::NotFound => IMStatusCode::NotFound,
             _ => IMStatusCode::Failure,
         }
     }
 }
 
+impl IMStatusCode {
+    /// `Ok` for a success status, or else the error a status reported by a peer stands for
+    pub fn to_result(self) -> Result<(), Error> {
+        let code = match self {
+            Self::Success => return Ok(()),
+            Self::UnsupportedEndpoint => ErrorCode::EndpointNotFound,
+            Self::UnsupportedCluster => ErrorCode::ClusterNotFound,
+            Self::UnsupportedAttribute => ErrorCode::AttributeNotFound,
+            Self::UnsupportedCommand => ErrorCode::CommandNotFound,
+            Self::InvalidAction => ErrorCode::InvalidAction,
+            Self::InvalidCommand => ErrorCode::InvalidCommand,
+            Self::UnsupportedAccess => ErrorCode::UnsupportedAccess,
+            Self::Busy => ErrorCode::Busy,
+            Self::DataVersionMismatch => ErrorCode::DataVersionMismatch,
+            Self::ResourceExhausted => ErrorCode::ResourceExhausted,
+            Self::ConstraintError => ErrorCode::ConstraintError,
+            Self::InvalidDataType => ErrorCode::InvalidDataType,
+            Self::NotFound => ErrorCode::NotFound,
+            Self::Timeout => ErrorCode::RxTimeout,
+            status => {
+                error!("Peer responded with status {:?}", status);
+                ErrorCode::Invalid
+            }
+        };
+
+        Err(code.into())
+    }
+}
+
 impl From<Error> for IMStatusCode {
     fn from(value: Error) -> Self {
         Self::from(value.code())
@@ -129,7 +165,13 @@ pub const PROTO_ID_INTERACTION_MODEL: u16 = 0x01;
 const LONG_READS_TLV_RESERVE_SIZE: usize = 24;
 
 impl<'a> ReadReq<'a> {
-    pub fn tx_start<'r, 'p>(&self, tx: &'r mut Packet<'p>) -> Result<TLVWriter<'r, 'p>, Error> {
+    /// Starts a chunk of the report, with the AttributeReports open - or the EventReports, once
+    /// the attributes are reported (`events`)
+    pub fn tx_start<'r, 'p>(
+        &self,
+        tx: &'r mut Packet<'p>,
+        events: bool,
+    ) -> Result<TLVWriter<'r, 'p>, Error> {
         tx.reset();
         tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
         tx.set_proto_opcode(OpCode::ReportData as u8);
@@ -138,25 +180,29 @@ impl<'a> ReadReq<'a> {
 
         tw.start_struct(TagType::Anonymous)?;
 
-        if self.attr_requests.is_some() {
-            tw.start_array(TagType::Context(msg::ReportDataTag::AttributeReports as u8))?;
-        }
+        Self::start_reports(&mut tw, self.attr_requests.is_some(), events)?;
 
         Ok(tw)
     }
 
-    pub fn tx_finish_chunk(&self, tx: &mut Packet) -> Result<(), Error> {
-        self.complete(tx, true)
+    /// Closes the AttributeReports of the current chunk, and opens its EventReports.
+    /// Returns `false` - with nothing written - if there is no space left in the chunk.
+    pub fn tx_start_events(&self, tx: &mut Packet) -> Result<bool, Error> {
+        Self::switch_to_events(tx, self.attr_requests.is_some())
+    }
+
+    pub fn tx_finish_chunk(&self, tx: &mut Packet, events: bool) -> Result<(), Error> {
+        self.complete(tx, events, true)
     }
 
-    pub fn tx_finish(&self, tx: &mut Packet) -> Result<(), Error> {
-        self.complete(tx, false)
+    pub fn tx_finish(&self, tx: &mut Packet, events: bool) -> Result<(), Error> {
+        self.complete(tx, events, false)
     }
 
-    fn complete(&self, tx: &mut Packet<'_>, more_chunks: bool) -> Result<(), Error> {
+    fn complete(&self, tx: &mut Packet<'_>, events: bool, more_chunks: bool) -> Result<(), Error> {
         let mut tw = Self::restore_long_read_space(tx)?;
 
-        if self.attr_requests.is_some() {
+        if self.attr_requests.is_some() || events {
             tw.end_container()?;
         }
 
@@ -175,6 +221,33 @@ impl<'a> ReadReq<'a> {
         tw.end_container()
     }
 
+    fn start_reports(tw: &mut TLVWriter, attrs: bool, events: bool) -> Result<(), Error> {
+        if events {
+            tw.start_array(TagType::Context(msg::ReportDataTag::EventReports as u8))
+        } else if attrs {
+            tw.start_array(TagType::Context(msg::ReportDataTag::AttributeReports as u8))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn switch_to_events(tx: &mut Packet, attrs: bool) -> Result<bool, Error> {
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        let anchor = tw.get_tail();
+
+        let result = if attrs { tw.end_container() } else { Ok(()) }
+            .and_then(|_| Self::start_reports(&mut tw, attrs, true));
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) if e.code() == ErrorCode::NoSpace => {
+                tw.rewind_to(anchor);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     fn reserve_long_read_space<'p, 'b>(tx: &'p mut Packet<'b>) -> Result<TLVWriter<'p, 'b>, Error> {
         let wb = tx.get_writebuf()?;
         wb.shrink(LONG_READS_TLV_RESERVE_SIZE)?;
@@ -200,6 +273,10 @@ impl<'a> WriteReq<'a> {
         if has_timed_out(epoch, timeout) {
             Interaction::status_response(tx, IMStatusCode::Timeout)?;
 
+            Ok(None)
+        } else if timed_mismatch(self.timed_request, timeout) {
+            Interaction::status_response(tx, IMStatusCode::TimedRequestMisMatch)?;
+
             Ok(None)
         } else {
             tx.reset();
@@ -233,38 +310,62 @@ impl<'a> InvReq<'a> {
         if has_timed_out(epoch, timeout) {
             Interaction::status_response(tx, IMStatusCode::Timeout)?;
 
+            Ok(None)
+        } else if timed_mismatch(self.timed_request, timeout) {
+            Interaction::status_response(tx, IMStatusCode::TimedRequestMisMatch)?;
+
+            Ok(None)
+        } else if !self.is_valid_batch() {
+            Interaction::status_response(tx, IMStatusCode::InvalidAction)?;
+
             Ok(None)
         } else {
-            let timed_tx = timeout.map(|_| true);
-            let timed_request = self.timed_request.filter(|a| *a);
+            tx.reset();
+            tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+            tx.set_proto_opcode(OpCode::InvokeResponse as u8);
 
-            // Either both should be None, or both should be Some(true)
-            if timed_tx != timed_request {
-                Interaction::status_response(tx, IMStatusCode::TimedRequestMisMatch)?;
+            let mut tw = TLVWriter::new(tx.get_writebuf()?);
 
-                Ok(None)
-            } else {
-                tx.reset();
-                tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
-                tx.set_proto_opcode(OpCode::InvokeResponse as u8);
+            tw.start_struct(TagType::Anonymous)?;
 
-                let mut tw = TLVWriter::new(tx.get_writebuf()?);
+            // Suppress Response -> TODO: Need to revisit this for cases where we send a command back
+            tw.bool(
+                TagType::Context(msg::InvRespTag::SupressResponse as u8),
+                false,
+            )?;
 
-                tw.start_struct(TagType::Anonymous)?;
+            if self.inv_requests.is_some() {
+                tw.start_array(TagType::Context(msg::InvRespTag::InvokeResponses as u8))?;
+            }
 
-                // Suppress Response -> TODO: Need to revisit this for cases where we send a command back
-                tw.bool(
-                    TagType::Context(msg::InvRespTag::SupressResponse as u8),
-                    false,
-                )?;
+            Ok(Some(tw))
+        }
+    }
+
+    /// Whether the request has no more commands than this node accepts in a single request
+    /// (see [`MAX_PATHS_PER_INVOKE`]), and - if it has more than one - whether they are on
+    /// distinct concrete paths, each with its own command reference, so that their responses
+    /// can be told apart
+    fn is_valid_batch(&self) -> bool {
+        let Some(requests) = &self.inv_requests else {
+            return true;
+        };
 
-                if self.inv_requests.is_some() {
-                    tw.start_array(TagType::Context(msg::InvRespTag::InvokeResponses as u8))?;
-                }
+        let count = requests.iter().count();
 
-                Ok(Some(tw))
-            }
+        if count > MAX_PATHS_PER_INVOKE as usize {
+            return false;
         }
+
+        count == 1
+            || requests.iter().enumerate().all(|(index, cmd)| {
+                cmd.command_ref.is_some()
+                    && !cmd.path.path.is_wildcard()
+                    && requests
+                        .iter()
+                        .skip(index + 1)
+                        .all(|other| other.command_ref != cmd.command_ref && other.path != cmd.path)
+            })
     }
 
     pub fn tx_finish(&self, tx: &mut Packet<'_>) -> Result<(), Error> {
@@ -294,11 +395,36 @@ impl TimedReq {
     }
 }
 
+/// The max interval (in seconds) a publisher may choose for a subscription, unless the
+/// subscriber requested an even longer one
+pub const SUBSCRIPTION_MAX_INTERVAL_PUBLISHER_LIMIT: u16 = 60 * 60;
+
 impl<'a> SubscribeReq<'a> {
+    /// The max interval of the subscription, as negotiated with the subscriber: the ceiling it
+    /// requested, but at least 1 second.
+    ///
+    /// An Intermittently Connected Device (`idle_mode_duration` set) cannot promise a report
+    /// more often than it wakes up, so the interval is extended to its idle mode duration,
+    /// up to [`SUBSCRIPTION_MAX_INTERVAL_PUBLISHER_LIMIT`] (or the requested ceiling, if longer).
+    pub fn max_int(&self, idle_mode_duration: Option<u32>) -> u16 {
+        let ceil = self.max_int_ceil.max(1);
+
+        if let Some(idle_mode_duration) = idle_mode_duration {
+            let limit = ceil.max(SUBSCRIPTION_MAX_INTERVAL_PUBLISHER_LIMIT);
+
+            ceil.max(idle_mode_duration.min(limit as u32) as u16)
+        } else {
+            ceil
+        }
+    }
+
+    /// Starts a chunk of a report, with the AttributeReports open - or the EventReports, once
+    /// the attributes are reported (`events`)
     pub fn tx_start<'r, 'p>(
         &self,
         tx: &'r mut Packet<'p>,
         subscription_id: u32,
+        events: bool,
     ) -> Result<TLVWriter<'r, 'p>, Error> {
         tx.reset();
         tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
@@ -313,17 +439,26 @@ impl<'a> SubscribeReq<'a> {
             subscription_id,
         )?;
 
-        if self.attr_requests.is_some() {
-            tw.start_array(TagType::Context(msg::ReportDataTag::AttributeReports as u8))?;
-        }
+        ReadReq::start_reports(&mut tw, self.attr_requests.is_some(), events)?;
 
         Ok(tw)
     }
 
-    pub fn tx_finish_chunk(&self, tx: &mut Packet<'_>, more_chunks: bool) -> Result<(), Error> {
+    /// Closes the AttributeReports of the current chunk, and opens its EventReports.
+    /// Returns `false` - with nothing written - if there is no space left in the chunk.
+    pub fn tx_start_events(&self, tx: &mut Packet) -> Result<bool, Error> {
+        ReadReq::switch_to_events(tx, self.attr_requests.is_some())
+    }
+
+    pub fn tx_finish_chunk(
+        &self,
+        tx: &mut Packet<'_>,
+        events: bool,
+        more_chunks: bool,
+    ) -> Result<(), Error> {
         let mut tw = ReadReq::restore_long_read_space(tx)?;
 
-        if self.attr_requests.is_some() {
+        if self.attr_requests.is_some() || events {
             tw.end_container()?;
         }
 
@@ -342,16 +477,42 @@ impl<'a> SubscribeReq<'a> {
         tw.end_container()
     }
 
-    pub fn tx_process_final(&self, tx: &mut Packet, subscription_id: u32) -> Result<(), Error> {
+    pub fn tx_process_final(
+        &self,
+        tx: &mut Packet,
+        subscription_id: u32,
+        max_int: u16,
+    ) -> Result<(), Error> {
         tx.reset();
         tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
         tx.set_proto_opcode(OpCode::SubscribeResponse as u8);
 
         let mut tw = TLVWriter::new(tx.get_writebuf()?);
 
-        let resp = SubscribeResp::new(subscription_id, 40);
+        let resp = SubscribeResp::new(subscription_id, max_int);
         resp.to_tlv(&mut tw, TagType::Anonymous)
     }
+
+    /// Prepares an empty report, which only keeps the subscription alive.
+    /// The subscriber does not respond to it.
+    pub fn tx_keep_alive(tx: &mut Packet, subscription_id: u32) -> Result<(), Error> {
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+        tx.set_proto_opcode(OpCode::ReportData as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+
+        tw.start_struct(TagType::Anonymous)?;
+        tw.u32(
+            TagType::Context(msg::ReportDataTag::SubscriptionId as u8),
+            subscription_id,
+        )?;
+        tw.bool(
+            TagType::Context(msg::ReportDataTag::SupressResponse as u8),
+            true,
+        )?;
+        tw.end_container()
+    }
 }
 
 pub struct ReadDriver<'a, 'r, 'p> {
@@ -359,6 +520,7 @@ pub struct ReadDriver<'a, 'r, 'p> {
     tx: &'r mut Packet<'p>,
     rx: &'r mut Packet<'p>,
     completed: bool,
+    events: bool,
 }
 
 impl<'a, 'r, 'p> ReadDriver<'a, 'r, 'p> {
@@ -368,15 +530,30 @@ impl<'a, 'r, 'p> ReadDriver<'a, 'r, 'p> {
             tx,
             rx,
             completed: false,
+            events: false,
         }
     }
 
     fn start(&mut self, req: &ReadReq) -> Result<(), Error> {
-        req.tx_start(self.tx)?;
+        req.tx_start(self.tx, false)?;
 
         Ok(())
     }
 
+    /// Moves on to reporting the events, once the attributes are reported.
+    /// Returns `false` if the reader did not accept a chunk of the report.
+    pub async fn start_events(&mut self, req: &ReadReq<'_>) -> Result<bool, Error> {
+        while !req.tx_start_events(self.tx)? {
+            if !self.send_chunk(req).await? {
+                return Ok(false);
+            }
+        }
+
+        self.events = true;
+
+        Ok(true)
+    }
+
     pub fn accessor(&self) -> Result<Accessor<'a>, Error> {
         self.exchange.accessor()
     }
@@ -390,20 +567,20 @@ impl<'a, 'r, 'p> ReadDriver<'a, 'r, 'p> {
     }
 
     pub async fn send_chunk(&mut self, req: &ReadReq<'_>) -> Result<bool, Error> {
-        req.tx_finish_chunk(self.tx)?;
+        req.tx_finish_chunk(self.tx, self.events)?;
 
         if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
             self.completed = true;
             Ok(false)
         } else {
-            req.tx_start(self.tx)?;
+            req.tx_start(self.tx, self.events)?;
 
             Ok(true)
         }
     }
 
     pub async fn complete(&mut self, req: &ReadReq<'_>) -> Result<(), Error> {
-        req.tx_finish(self.tx)?;
+        req.tx_finish(self.tx, self.events)?;
 
         self.exchange.send_complete(self.tx).await
     }
@@ -412,6 +589,7 @@ impl<'a, 'r, 'p> ReadDriver<'a, 'r, 'p> {
 pub struct WriteDriver<'a, 'r, 'p> {
     exchange: &'r mut Exchange<'a>,
     tx: &'r mut Packet<'p>,
+    rx: &'r mut Packet<'p>,
     epoch: Epoch,
     timeout: Option<Duration>,
 }
@@ -422,10 +600,12 @@ impl<'a, 'r, 'p> WriteDriver<'a, 'r, 'p> {
         epoch: Epoch,
         timeout: Option<Duration>,
         tx: &'r mut Packet<'p>,
+        rx: &'r mut Packet<'p>,
     ) -> Self {
         Self {
             exchange,
             tx,
+            rx,
             epoch,
             timeout,
         }
@@ -441,6 +621,12 @@ impl<'a, 'r, 'p> WriteDriver<'a, 'r, 'p> {
         }
     }
 
+    /// The fabric index and the ID of the destination group, if the request is group-addressed
+    pub fn group(&self) -> Result<Option<(u8, u16)>, Error> {
+        self.exchange
+            .with_session(|sess| Ok(sess.get_local_fabric_idx().zip(sess.get_group_id())))
+    }
+
     pub fn accessor(&self) -> Result<Accessor<'a>, Error> {
         self.exchange.accessor()
     }
@@ -449,13 +635,36 @@ impl<'a, 'r, 'p> WriteDriver<'a, 'r, 'p> {
         Ok(TLVWriter::new(self.tx.get_writebuf()?))
     }
 
-    pub async fn complete(&mut self, req: &WriteReq<'_>) -> Result<(), Error> {
-        if !req.supress_response.unwrap_or_default() {
+    /// Sends the WriteResponse to the request, unless the requester suppressed it, in which case
+    /// the request is only acknowledged. If the request is a chunk of a chunked write but not
+    /// its last one, also receives the next chunk - a WriteRequest on the same exchange - and
+    /// returns `true`.
+    ///
+    /// Group-addressed writes cannot be chunked, and are never responded to.
+    pub async fn complete(&mut self, req: &WriteReq<'_>) -> Result<bool, Error> {
+        if req.more_chunked() && self.group()?.is_none() {
             req.tx_finish(self.tx)?;
-            self.exchange.send_complete(self.tx).await?;
-        }
+            self.exchange.exchange(self.tx, self.rx).await?;
 
-        Ok(())
+            let opcode: OpCode = self.rx.get_proto_opcode()?;
+
+            if opcode != OpCode::WriteRequest {
+                error!("Opcode not allowed in a chunked write: {:?}", opcode);
+                Err(ErrorCode::InvalidOpcode)?;
+            }
+
+            Ok(true)
+        } else {
+            if req.supress_response.unwrap_or_default() {
+                // Still acknowledge the request, or else the requester keeps re-sending it
+                self.exchange.acknowledge().await?;
+            } else {
+                req.tx_finish(self.tx)?;
+                self.exchange.send_complete(self.tx).await?;
+            }
+
+            Ok(false)
+        }
     }
 }
 
@@ -491,6 +700,12 @@ impl<'a, 'r, 'p> InvokeDriver<'a, 'r, 'p> {
         }
     }
 
+    /// The fabric index and the ID of the destination group, if the request is group-addressed
+    pub fn group(&self) -> Result<Option<(u8, u16)>, Error> {
+        self.exchange
+            .with_session(|sess| Ok(sess.get_local_fabric_idx().zip(sess.get_group_id())))
+    }
+
     pub fn accessor(&self) -> Result<Accessor<'a>, Error> {
         self.exchange.accessor()
     }
@@ -503,22 +718,46 @@ impl<'a, 'r, 'p> InvokeDriver<'a, 'r, 'p> {
         Ok((TLVWriter::new(self.tx.get_writebuf()?), (self.exchange)))
     }
 
+    /// Sends the InvokeResponse to the request, unless the requester suppressed it, in which
+    /// case the request is only acknowledged
     pub async fn complete(&mut self, req: &InvReq<'_>) -> Result<(), Error> {
-        if !req.suppress_response.unwrap_or_default() {
+        if req.suppress_response.unwrap_or_default() {
+            self.exchange.acknowledge().await
+        } else {
             req.tx_finish(self.tx)?;
-            self.exchange.send_complete(self.tx).await?;
+            self.exchange.send_complete(self.tx).await
         }
-
-        Ok(())
     }
 }
 
+/// The chunked ReportData messages of a subscription, shared by its priming report (see
+/// [`SubscribeDriver`]) and its subsequent reports (see [`ReportDriver`]), so that either
+/// can span as many chunks as needed
+pub trait ReportChunks<'a, 'p> {
+    /// The ID of the reported subscription
+    fn subscription_id(&self) -> u32;
+
+    fn accessor(&self) -> Result<Accessor<'a>, Error>;
+
+    /// A writer of the current chunk
+    fn writer(&mut self) -> Result<TLVWriter<'_, 'p>, Error>;
+
+    /// Moves on to reporting the events, once the attributes are reported.
+    /// Returns `false` if the subscriber did not accept a chunk of the report.
+    async fn start_events(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error>;
+
+    /// Sends the current chunk of the report, with more chunks to follow. Returns `false` if
+    /// the subscriber did not accept it.
+    async fn send_chunk(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error>;
+}
+
 pub struct SubscribeDriver<'a, 'r, 'p> {
     exchange: &'r mut Exchange<'a>,
     tx: &'r mut Packet<'p>,
     rx: &'r mut Packet<'p>,
     subscription_id: u32,
     completed: bool,
+    events: bool,
 }
 
 impl<'a, 'r, 'p> SubscribeDriver<'a, 'r, 'p> {
@@ -534,20 +773,73 @@ impl<'a, 'r, 'p> SubscribeDriver<'a, 'r, 'p> {
             rx,
             subscription_id,
             completed: false,
+            events: false,
         }
     }
 
     fn start(&mut self, req: &SubscribeReq) -> Result<(), Error> {
-        req.tx_start(self.tx, self.subscription_id)?;
+        req.tx_start(self.tx, self.subscription_id, false)?;
 
         Ok(())
     }
 
-    pub fn accessor(&self) -> Result<Accessor<'a>, Error> {
+    /// The max interval of the subscription, negotiated with the idle mode duration
+    /// of this node, if it is an Intermittently Connected Device
+    pub fn max_int(&self, req: &SubscribeReq<'_>) -> u16 {
+        req.max_int(self.exchange.matter.idle_mode_duration())
+    }
+
+    /// The fabric index and the node ID of the subscriber, if the subscription request came
+    /// over a CASE session. Subscriptions over other sessions cannot be reported.
+    pub fn subscriber(&self) -> Result<Option<(u8, u64)>, Error> {
+        self.exchange.with_session(|sess| {
+            Ok(match sess.get_session_mode() {
+                SessionMode::Case(_) => sess.get_local_fabric_idx().zip(sess.get_peer_node_id()),
+                _ => None,
+            })
+        })
+    }
+
+    /// Rejects the subscription with `status`, instead of priming it
+    pub async fn reject(&mut self, status: IMStatusCode) -> Result<(), Error> {
+        self.completed = true;
+
+        Interaction::status_response(self.tx, status)?;
+        self.exchange.send_complete(self.tx).await
+    }
+
+    /// Sends the last chunk of the priming report and - once the subscriber accepted the
+    /// report - the SubscribeResponse. Returns whether the subscription was established.
+    pub async fn complete(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        if !self.completed {
+            req.tx_finish_chunk(self.tx, self.events, false)?;
+
+            if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
+                self.completed = true;
+            } else {
+                let max_int = self.max_int(req);
+
+                req.tx_process_final(self.tx, self.subscription_id, max_int)?;
+                self.exchange.send_complete(self.tx).await?;
+
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl<'a, 'r, 'p> ReportChunks<'a, 'p> for SubscribeDriver<'a, 'r, 'p> {
+    fn subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+
+    fn accessor(&self) -> Result<Accessor<'a>, Error> {
         self.exchange.accessor()
     }
 
-    pub fn writer(&mut self) -> Result<TLVWriter<'_, 'p>, Error> {
+    fn writer(&mut self) -> Result<TLVWriter<'_, 'p>, Error> {
         if self.completed {
             Err(ErrorCode::Invalid.into()) // TODO
         } else {
@@ -555,33 +847,124 @@ impl<'a, 'r, 'p> SubscribeDriver<'a, 'r, 'p> {
         }
     }
 
-    pub async fn send_chunk(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
-        req.tx_finish_chunk(self.tx, true)?;
+    async fn start_events(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        while !req.tx_start_events(self.tx)? {
+            if !self.send_chunk(req).await? {
+                return Ok(false);
+            }
+        }
+
+        self.events = true;
+
+        Ok(true)
+    }
+
+    async fn send_chunk(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        req.tx_finish_chunk(self.tx, self.events, true)?;
 
         if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
             self.completed = true;
             Ok(false)
         } else {
-            req.tx_start(self.tx, self.subscription_id)?;
+            req.tx_start(self.tx, self.subscription_id, self.events)?;
 
             Ok(true)
         }
     }
+}
 
-    pub async fn complete(&mut self, req: &SubscribeReq<'_>) -> Result<(), Error> {
-        if !self.completed {
-            req.tx_finish_chunk(self.tx, false)?;
+/// Drives a report of an established subscription, over an exchange initiated by this node
+pub struct ReportDriver<'a, 'r, 'p> {
+    exchange: &'r mut Exchange<'a>,
+    tx: &'r mut Packet<'p>,
+    rx: &'r mut Packet<'p>,
+    subscription_id: u32,
+    events: bool,
+}
 
-            if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
-                self.completed = true;
-            } else {
-                req.tx_process_final(self.tx, self.subscription_id)?;
-                self.exchange.send_complete(self.tx).await?;
-            }
+impl<'a, 'r, 'p> ReportDriver<'a, 'r, 'p> {
+    pub fn new(
+        exchange: &'r mut Exchange<'a>,
+        subscription_id: u32,
+        tx: &'r mut Packet<'p>,
+        rx: &'r mut Packet<'p>,
+    ) -> Self {
+        Self {
+            exchange,
+            tx,
+            rx,
+            subscription_id,
+            events: false,
         }
+    }
+
+    pub fn start(&mut self, req: &SubscribeReq) -> Result<(), Error> {
+        req.tx_start(self.tx, self.subscription_id, false)?;
 
         Ok(())
     }
+
+    /// Sends the last chunk of the report. Returns `false` if the subscriber did not accept
+    /// it, in which case the subscription should be terminated.
+    pub async fn complete(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        req.tx_finish_chunk(self.tx, self.events, false)?;
+
+        let accepted =
+            exchange_confirm(self.exchange, self.tx, self.rx).await? == IMStatusCode::Success;
+
+        // Nothing else is sent on the exchange, so the status response needs a standalone ack
+        self.exchange.acknowledge().await?;
+
+        Ok(accepted)
+    }
+
+    /// Sends an empty report, which only keeps the subscription alive
+    pub async fn keep_alive(&mut self) -> Result<(), Error> {
+        SubscribeReq::tx_keep_alive(self.tx, self.subscription_id)?;
+
+        self.exchange.send_complete(self.tx).await
+    }
+}
+
+impl<'a, 'r, 'p> ReportChunks<'a, 'p> for ReportDriver<'a, 'r, 'p> {
+    fn subscription_id(&self) -> u32 {
+        self.subscription_id
+    }
+
+    fn accessor(&self) -> Result<Accessor<'a>, Error> {
+        self.exchange.accessor()
+    }
+
+    fn writer(&mut self) -> Result<TLVWriter<'_, 'p>, Error> {
+        Ok(TLVWriter::new(self.tx.get_writebuf()?))
+    }
+
+    async fn start_events(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        while !req.tx_start_events(self.tx)? {
+            if !self.send_chunk(req).await? {
+                return Ok(false);
+            }
+        }
+
+        self.events = true;
+
+        Ok(true)
+    }
+
+    /// A chunk which is not accepted terminates the subscription
+    async fn send_chunk(&mut self, req: &SubscribeReq<'_>) -> Result<bool, Error> {
+        req.tx_finish_chunk(self.tx, self.events, true)?;
+
+        if exchange_confirm(self.exchange, self.tx, self.rx).await? != IMStatusCode::Success {
+            self.exchange.acknowledge().await?;
+
+            Ok(false)
+        } else {
+            req.tx_start(self.tx, self.subscription_id, self.events)?;
+
+            Ok(true)
+        }
+    }
 }
 
 pub enum Interaction<'a, 'r, 'p> {
@@ -604,29 +987,43 @@ pub enum Interaction<'a, 'r, 'p> {
 }
 
 impl<'a, 'r, 'p> Interaction<'a, 'r, 'p> {
-    pub async fn timeout(
+    /// Processes the TimedRequest the interaction might start with, which opens the timed window
+    /// of the exchange (see [`Exchange::timed_deadline`]), and receives the request following it.
+    ///
+    /// Returns `false` if the interaction is over, because the TimedRequest is not followed by
+    /// a Write or an Invoke request.
+    pub async fn timed(
         exchange: &mut Exchange<'_>,
         rx: &mut Packet<'_>,
         tx: &mut Packet<'_>,
-    ) -> Result<Option<Duration>, Error> {
+    ) -> Result<bool, Error> {
         let epoch = exchange.matter.epoch;
 
-        let mut opcode: OpCode = rx.get_proto_opcode()?;
+        let opcode: OpCode = rx.get_proto_opcode()?;
 
-        let mut timeout = None;
+        if opcode != OpCode::TimedRequest {
+            return Ok(true);
+        }
 
-        while opcode == OpCode::TimedRequest {
-            let rx_data = rx.as_slice();
-            let req = TimedReq::from_tlv(&get_root_node_struct(rx_data)?)?;
+        let req = TimedReq::from_tlv(&get_root_node_struct(rx.as_slice())?)?;
 
-            timeout = Some(req.tx_process(tx, epoch)?);
+        let deadline = req.tx_process(tx, epoch)?;
+        exchange.set_timed_deadline(deadline)?;
 
-            exchange.exchange(tx, rx).await?;
+        exchange.exchange(tx, rx).await?;
 
-            opcode = rx.get_proto_opcode()?;
-        }
+        let opcode: OpCode = rx.get_proto_opcode()?;
+
+        if matches!(opcode, OpCode::WriteRequest | OpCode::InvokeRequest) {
+            Ok(true)
+        } else {
+            error!("Opcode not allowed after a TimedRequest: {:?}", opcode);
 
-        Ok(timeout)
+            Self::status_response(tx, IMStatusCode::InvalidAction)?;
+            exchange.send_complete(tx).await?;
+
+            Ok(false)
+        }
     }
 
     #[inline(always)]
@@ -636,12 +1033,12 @@ impl<'a, 'r, 'p> Interaction<'a, 'r, 'p> {
         tx: &'r mut Packet<'p>,
         rx_status: &'r mut Packet<'p>,
         subscription_id: S,
-        timeout: Option<Duration>,
     ) -> Result<Interaction<'a, 'r, 'p>, Error>
     where
         S: FnOnce() -> u32,
     {
         let epoch = exchange.matter.epoch;
+        let timeout = exchange.timed_deadline()?;
 
         let opcode = rx.get_proto_opcode()?;
         let rx_data = rx.as_slice();
@@ -655,7 +1052,7 @@ impl<'a, 'r, 'p> Interaction<'a, 'r, 'p> {
             }
             OpCode::WriteRequest => {
                 let req = WriteReq::from_tlv(&get_root_node_struct(rx_data)?)?;
-                let driver = WriteDriver::new(exchange, epoch, timeout, tx);
+                let driver = WriteDriver::new(exchange, epoch, timeout, tx, rx_status);
 
                 Ok(Self::Write { req, driver })
             }
@@ -731,3 +1128,9 @@ async fn exchange_confirm(
 fn has_timed_out(epoch: Epoch, timeout: Option<Duration>) -> bool {
     timeout.map(|timeout| epoch() > timeout).unwrap_or(false)
 }
+
+/// Whether the TimedRequest flag of a Write or Invoke request does not match whether it
+/// followed a TimedRequest
+fn timed_mismatch(timed_request: Option<bool>, timeout: Option<Duration>) -> bool {
+    timed_request.unwrap_or(false) != timeout.is_some()
+}
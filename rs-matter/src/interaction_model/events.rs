@@ -0,0 +1,563 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The events emitted by this node (see [`crate::Matter::emit_event`]), kept until they are
+//! reported in the EventReports of reads and subscriptions.
+//!
+//! Each event gets a number, which increases monotonically across all priorities. The events
+//! of each priority are kept in a circular buffer of their own, so that a burst of Debug events
+//! does not evict the Critical ones: once the buffer of a priority is full, its oldest event is
+//! dropped.
+//!
+//! The subscriptions report the events numbered after the last one they reported. Events
+//! emitted on an urgent event path of a subscription are reported once its min interval
+//! elapses, the other ones with its next report.
+//!
+//! The event numbers must never go back, not even after a reboot, or the controllers would
+//! take the new events for ones they already got. Instead of storing the number of every event,
+//! an upper bound of it is stored, moved forward by [`EVENT_NUMBER_WINDOW`] well before the
+//! numbers reach it. After a reboot, numbering resumes from that bound
+//! (see [`crate::Matter::store_events`]).
+//!
+//! The timestamps of the events are the Epoch time - i.e. the UTC time - by default. Nodes
+//! which do not know the UTC time report the System time - i.e. the time since boot - instead
+//! (see [`crate::Matter::set_event_system_time`]).
+
+use core::time::Duration;
+
+use heapless::{Deque, Vec};
+
+use crate::{
+    acl::{AccessReq, Accessor},
+    data_model::objects::{Access, ClusterId, EncodeValue, EndptId, Node},
+    error::{Error, ErrorCode},
+    interaction_model::messages::{
+        ib::{EventData, EventPath, EventResp, EventStatus},
+        GenericPath,
+    },
+    tlv::{FromTLV, TLVArray, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+pub use crate::config::EVENT_NUMBER_WINDOW;
+
+pub const MAX_EVENTS_DEBUG: usize = crate::config::MAX_EVENTS_DEBUG;
+pub const MAX_EVENTS_INFO: usize = crate::config::MAX_EVENTS_INFO;
+pub const MAX_EVENTS_CRITICAL: usize = crate::config::MAX_EVENTS_CRITICAL;
+pub const MAX_EVENT_DATA_SIZE: usize = crate::config::MAX_EVENT_DATA_SIZE;
+
+pub type EventId = u32;
+pub type EventNumber = u64;
+
+const TAG_EVENT_NUMBER_LIMIT: u8 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventPriority {
+    Debug = 0,
+    Info = 1,
+    Critical = 2,
+}
+
+impl ToTLV for EventPriority {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        tw.u8(tag, *self as u8)
+    }
+}
+
+impl<'a> FromTLV<'a> for EventPriority {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        match t.u8()? {
+            0 => Ok(Self::Debug),
+            1 => Ok(Self::Info),
+            2 => Ok(Self::Critical),
+            _ => Err(ErrorCode::InvalidData.into()),
+        }
+    }
+}
+
+/// When an event was emitted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventTimestamp {
+    /// The time since the UNIX epoch, for nodes which know the UTC time
+    Epoch(Duration),
+    /// The time since boot, for nodes which do not
+    System(Duration),
+}
+
+impl EventTimestamp {
+    pub fn as_duration(&self) -> Duration {
+        match self {
+            Self::Epoch(timestamp) | Self::System(timestamp) => *timestamp,
+        }
+    }
+}
+
+pub struct Event {
+    pub number: EventNumber,
+    pub endpoint: EndptId,
+    pub cluster: ClusterId,
+    pub event_id: EventId,
+    pub priority: EventPriority,
+    /// When the event was emitted, as per the Epoch of the Matter instance
+    pub timestamp: EventTimestamp,
+    data: Vec<u8, MAX_EVENT_DATA_SIZE>,
+}
+
+impl Event {
+    /// The TLV-encoded payload of the event
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Whether the event is on the - possibly wildcard - event path
+    pub fn matches(&self, path: &EventPath) -> bool {
+        path.endpoint.map(|e| e == self.endpoint).unwrap_or(true)
+            && path.cluster.map(|c| c == self.cluster).unwrap_or(true)
+            && path.event.map(|e| e == self.event_id).unwrap_or(true)
+    }
+
+    /// Whether `accessor` may read the event, i.e. has the View privilege on its cluster
+    pub fn allows(&self, accessor: &Accessor) -> bool {
+        let path = GenericPath::new(Some(self.endpoint), Some(self.cluster), Some(self.event_id));
+
+        let mut access_req = AccessReq::new(accessor, path, Access::READ);
+        access_req.set_target_perms(Access::RV);
+
+        access_req.allow()
+    }
+
+    /// Encodes the event as an EventReportIB. Returns `false` - with nothing encoded - if the
+    /// event does not fit.
+    pub fn encode(&self, tw: &mut TLVWriter) -> Result<bool, Error> {
+        let data = TLVList::new(&self.data)
+            .iter()
+            .next()
+            .ok_or(ErrorCode::Invalid)?;
+
+        let (epoch_ts, system_ts) = match self.timestamp {
+            EventTimestamp::Epoch(timestamp) => (Some(timestamp.as_millis() as u64), None),
+            EventTimestamp::System(timestamp) => (None, Some(timestamp.as_millis() as u64)),
+        };
+
+        let resp = EventResp::Data(EventData {
+            path: EventPath {
+                endpoint: Some(self.endpoint),
+                cluster: Some(self.cluster),
+                event: Some(self.event_id),
+                ..Default::default()
+            },
+            event_number: self.number,
+            priority: self.priority,
+            epoch_ts,
+            system_ts,
+            delta_epoch_ts: None,
+            delta_system_ts: None,
+            data: EncodeValue::Value(&data),
+        });
+
+        encode_resp(&resp, tw)
+    }
+}
+
+/// Encodes the status of an event path as an EventReportIB. Returns `false` - with nothing
+/// encoded - if the status does not fit.
+pub fn encode_status(status: &EventStatus, tw: &mut TLVWriter) -> Result<bool, Error> {
+    encode_resp(&EventResp::Status(status.clone()), tw)
+}
+
+fn encode_resp(resp: &EventResp, tw: &mut TLVWriter) -> Result<bool, Error> {
+    let anchor = tw.get_tail();
+
+    match resp.to_tlv(tw, TagType::Anonymous) {
+        Ok(()) => Ok(true),
+        Err(e) if e.code() == ErrorCode::NoSpace => {
+            tw.rewind_to(anchor);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub struct Events {
+    debug: Deque<Event, MAX_EVENTS_DEBUG>,
+    info: Deque<Event, MAX_EVENTS_INFO>,
+    critical: Deque<Event, MAX_EVENTS_CRITICAL>,
+    next_number: EventNumber,
+    /// The persisted upper bound of the event numbers
+    limit: EventNumber,
+    system_time: bool,
+    changed: bool,
+}
+
+impl Events {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            debug: Deque::new(),
+            info: Deque::new(),
+            critical: Deque::new(),
+            next_number: 0,
+            limit: 0,
+            system_time: false,
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        let limit = root.find_tag(TAG_EVENT_NUMBER_LIMIT as _)?.u64()?;
+
+        // The events emitted before the data was loaded - if any - are renumbered, so that
+        // the numbers keep increasing
+        for event in self
+            .debug
+            .iter_mut()
+            .chain(self.info.iter_mut())
+            .chain(self.critical.iter_mut())
+        {
+            event.number += limit;
+        }
+
+        self.next_number += limit;
+        self.limit = limit;
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.start_struct(TagType::Anonymous)?;
+            tw.u64(TagType::Context(TAG_EVENT_NUMBER_LIMIT), self.limit)?;
+            tw.end_container()?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Whether the timestamps of the events are the System time - i.e. the time since boot -
+    /// rather than the Epoch time
+    pub fn set_system_time(&mut self, system_time: bool) {
+        self.system_time = system_time;
+    }
+
+    /// The number the next emitted event will get
+    pub fn next_number(&self) -> EventNumber {
+        self.next_number
+    }
+
+    /// Records an event, whose payload is `payload` TLV-encoded, dropping the oldest event of
+    /// the same priority if its buffer is full. Returns the number of the event.
+    ///
+    /// `timestamp` is the Epoch or the System time, depending on [`Events::set_system_time`].
+    pub fn emit(
+        &mut self,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        event_id: EventId,
+        priority: EventPriority,
+        timestamp: Duration,
+        payload: &dyn ToTLV,
+    ) -> Result<EventNumber, Error> {
+        let mut buf = [0; MAX_EVENT_DATA_SIZE];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        payload.to_tlv(&mut tw, TagType::Anonymous)?;
+
+        let len = tw.get_tail();
+
+        let event = Event {
+            number: self.next_number,
+            endpoint,
+            cluster,
+            event_id,
+            priority,
+            timestamp: if self.system_time {
+                EventTimestamp::System(timestamp)
+            } else {
+                EventTimestamp::Epoch(timestamp)
+            },
+            data: Vec::from_slice(&buf[..len]).map_err(|_| ErrorCode::NoSpace)?,
+        };
+
+        match priority {
+            EventPriority::Debug => Self::push(&mut self.debug, event),
+            EventPriority::Info => Self::push(&mut self.info, event),
+            EventPriority::Critical => Self::push(&mut self.critical, event),
+        }
+
+        let number = self.next_number;
+        self.next_number += 1;
+
+        if number >= self.limit.saturating_sub(EVENT_NUMBER_WINDOW / 2) {
+            // Move the bound forward, while there is still room for half a window of events
+            // until it is persisted
+            self.limit = number.saturating_add(EVENT_NUMBER_WINDOW);
+            self.changed = true;
+        }
+
+        Ok(number)
+    }
+
+    /// All kept events, of all priorities, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.debug
+            .iter()
+            .chain(self.info.iter())
+            .chain(self.critical.iter())
+    }
+
+    pub fn get(&self, number: EventNumber) -> Option<&Event> {
+        self.iter().find(|e| e.number == number)
+    }
+
+    /// The event with the lowest number - but at least `from` - which is on any of `paths`,
+    /// of a cluster `node` has, and `accessor` may read
+    pub fn next(
+        &self,
+        from: EventNumber,
+        paths: &TLVArray<EventPath>,
+        node: &Node,
+        accessor: &Accessor,
+    ) -> Option<&Event> {
+        self.iter()
+            .filter(|e| e.number >= from)
+            .filter(|e| paths.iter().any(|path| e.matches(&path)))
+            .filter(|e| node.check_cluster(e.endpoint, e.cluster).is_ok())
+            .filter(|e| e.allows(accessor))
+            .min_by_key(|e| e.number)
+    }
+
+    /// Encodes - as an EventReportIB - the next event to report (see [`Events::next`]).
+    /// Returns the number of the event and whether it was encoded, as it might not fit;
+    /// or `None` if there are no more events to report.
+    pub fn encode_next(
+        &self,
+        from: EventNumber,
+        paths: &TLVArray<EventPath>,
+        node: &Node,
+        accessor: &Accessor,
+        tw: &mut TLVWriter,
+    ) -> Result<Option<(EventNumber, bool)>, Error> {
+        if let Some(event) = self.next(from, paths, node, accessor) {
+            Ok(Some((event.number, event.encode(tw)?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn push<const N: usize>(events: &mut Deque<Event, N>, event: Event) {
+        if events.is_full() {
+            events.pop_front();
+        }
+
+        let _ = events.push_back(event);
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::tlv::{TLVList, TLVWriter, TagType};
+    use crate::utils::writebuf::WriteBuf;
+
+    use super::{
+        EventPriority, EventTimestamp, Events, EVENT_NUMBER_WINDOW, MAX_EVENTS_CRITICAL,
+        MAX_EVENTS_DEBUG,
+    };
+
+    #[test]
+    fn test_event_numbers_and_eviction() {
+        let mut events = Events::new();
+
+        for i in 0..MAX_EVENTS_DEBUG + 2 {
+            let number = events
+                .emit(1, 6, 0, EventPriority::Debug, Duration::ZERO, &(i as u8))
+                .unwrap();
+            assert_eq!(number, i as u64);
+        }
+
+        let critical = events
+            .emit(1, 6, 1, EventPriority::Critical, Duration::ZERO, &true)
+            .unwrap();
+        assert_eq!(critical, (MAX_EVENTS_DEBUG + 2) as u64);
+        assert_eq!(events.next_number(), critical + 1);
+
+        // The oldest Debug events are gone, the Critical one is kept
+        assert!(events.get(0).is_none());
+        assert!(events.get(1).is_none());
+        assert!(events.get(2).is_some());
+        assert_eq!(
+            events.get(critical).unwrap().priority,
+            EventPriority::Critical
+        );
+        assert_eq!(events.iter().count(), MAX_EVENTS_DEBUG + 1);
+
+        for _ in 0..MAX_EVENTS_CRITICAL {
+            events
+                .emit(1, 6, 1, EventPriority::Critical, Duration::ZERO, &false)
+                .unwrap();
+        }
+        assert!(events.get(critical).is_none());
+        assert_eq!(
+            events.iter().count(),
+            MAX_EVENTS_DEBUG + MAX_EVENTS_CRITICAL
+        );
+    }
+
+    #[test]
+    fn test_event_data() {
+        let mut events = Events::new();
+
+        let number = events
+            .emit(
+                2,
+                0x28,
+                0,
+                EventPriority::Info,
+                Duration::from_millis(1500),
+                &0x1234_u16,
+            )
+            .unwrap();
+
+        let event = events.get(number).unwrap();
+        assert_eq!(
+            event.timestamp,
+            EventTimestamp::Epoch(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            TLVList::new(event.data())
+                .iter()
+                .next()
+                .unwrap()
+                .u16()
+                .unwrap(),
+            0x1234
+        );
+
+        // An event does not fit in a buffer that is too small, and nothing is written then
+        let mut buf = [0; 8];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        tw.start_struct(TagType::Anonymous).unwrap();
+        assert!(!event.encode(&mut tw).unwrap());
+        assert_eq!(tw.get_tail(), 1);
+
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        assert!(event.encode(&mut tw).unwrap());
+
+        // Payloads larger than the maximum size are rejected
+        let large = [0_u8; super::MAX_EVENT_DATA_SIZE];
+        assert!(events
+            .emit(2, 0x28, 0, EventPriority::Info, Duration::ZERO, &large)
+            .is_err());
+        assert_eq!(events.next_number(), number + 1);
+    }
+
+    #[test]
+    fn test_event_numbers_persisted() {
+        let mut events = Events::new();
+        let mut buf = [0; 32];
+
+        // The bound is stored on the first event, and then only every half a window
+        events
+            .emit(1, 6, 0, EventPriority::Info, Duration::ZERO, &true)
+            .unwrap();
+        assert!(events.is_changed());
+        let data = events.store(&mut buf).unwrap().unwrap().to_vec();
+        assert!(!events.is_changed());
+        assert!(events.store(&mut buf).unwrap().is_none());
+
+        for _ in 1..EVENT_NUMBER_WINDOW / 2 {
+            events
+                .emit(1, 6, 0, EventPriority::Info, Duration::ZERO, &true)
+                .unwrap();
+            assert!(!events.is_changed());
+        }
+
+        // After a reboot, the numbers resume from the stored bound, so that they never go back
+        let mut rebooted = Events::new();
+        rebooted.load(&data).unwrap();
+        assert!(rebooted.next_number() >= events.next_number());
+
+        let number = rebooted
+            .emit(1, 6, 0, EventPriority::Info, Duration::ZERO, &true)
+            .unwrap();
+        assert_eq!(number, EVENT_NUMBER_WINDOW);
+        assert!(rebooted.is_changed());
+    }
+
+    #[test]
+    fn test_event_timestamps() {
+        let mut events = Events::new();
+
+        let epoch = events
+            .emit(1, 6, 0, EventPriority::Info, Duration::from_secs(2), &true)
+            .unwrap();
+
+        events.set_system_time(true);
+
+        let system = events
+            .emit(1, 6, 0, EventPriority::Info, Duration::from_secs(3), &true)
+            .unwrap();
+
+        assert_eq!(
+            events.get(epoch).unwrap().timestamp,
+            EventTimestamp::Epoch(Duration::from_secs(2))
+        );
+        assert_eq!(
+            events.get(system).unwrap().timestamp,
+            EventTimestamp::System(Duration::from_secs(3))
+        );
+
+        // The System time is encoded as such
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        assert!(events.get(system).unwrap().encode(&mut tw).unwrap());
+        let len = tw.get_tail();
+
+        let data = TLVList::new(&buf[..len]).iter().next().unwrap();
+        let data = data.find_tag(1).unwrap();
+        assert!(data.find_tag(3).is_err());
+        assert_eq!(data.find_tag(4).unwrap().u64().unwrap(), 3000);
+    }
+}
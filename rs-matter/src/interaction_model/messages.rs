@@ -51,6 +51,17 @@ impl GenericPath {
             _ => Err(ErrorCode::Invalid.into()),
         }
     }
+    /// Returns true, if the - possibly wildcard - paths have a concrete path in common
+    pub fn overlaps(&self, other: &GenericPath) -> bool {
+        fn overlap<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+            a.zip(b).map(|(a, b)| a == b).unwrap_or(true)
+        }
+
+        overlap(self.endpoint, other.endpoint)
+            && overlap(self.cluster, other.cluster)
+            && overlap(self.leaf, other.leaf)
+    }
+
     /// Returns true, if the path is wildcard
     pub fn is_wildcard(&self) -> bool {
         !matches!(
@@ -67,13 +78,13 @@ impl GenericPath {
 pub mod msg {
 
     use crate::{
-        interaction_model::core::IMStatusCode,
+        interaction_model::{core::IMStatusCode, events::EventNumber},
         tlv::{FromTLV, TLVArray, ToTLV},
     };
 
     use super::ib::{
         self, AttrData, AttrPath, AttrResp, AttrStatus, CmdData, DataVersionFilter, EventFilter,
-        EventPath,
+        EventPath, EventResp,
     };
 
     #[derive(Debug, Default, FromTLV, ToTLV)]
@@ -83,8 +94,8 @@ pub mod msg {
         pub min_int_floor: u16,
         pub max_int_ceil: u16,
         pub attr_requests: Option<TLVArray<'a, AttrPath>>,
-        event_requests: Option<TLVArray<'a, EventPath>>,
-        event_filters: Option<TLVArray<'a, EventFilter>>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
         // The Context Tags are discontiguous for some reason
         _dummy: Option<bool>,
         pub fabric_filtered: bool,
@@ -105,6 +116,22 @@ pub mod msg {
             self.attr_requests = Some(TLVArray::new(requests));
             self
         }
+
+        /// The number of the first event to report, as per the event filters of the request
+        pub fn event_min(&self) -> EventNumber {
+            event_min(self.event_filters.as_ref())
+        }
+    }
+
+    /// The highest minimum event number of the event filters, i.e. the number of the event
+    /// following the last one the requester already has; 0 without filters
+    fn event_min(filters: Option<&TLVArray<EventFilter>>) -> EventNumber {
+        filters
+            .iter()
+            .flat_map(|filters| filters.iter())
+            .filter_map(|filter| filter.event_min)
+            .max()
+            .unwrap_or(0)
     }
 
     #[derive(Debug, FromTLV, ToTLV)]
@@ -167,8 +194,8 @@ pub mod msg {
     #[tlvargs(lifetime = "'a")]
     pub struct ReadReq<'a> {
         pub attr_requests: Option<TLVArray<'a, AttrPath>>,
-        event_requests: Option<TLVArray<'a, EventPath>>,
-        event_filters: Option<TLVArray<'a, EventFilter>>,
+        pub event_requests: Option<TLVArray<'a, EventPath>>,
+        pub event_filters: Option<TLVArray<'a, EventFilter>>,
         pub fabric_filtered: bool,
         pub dataver_filters: Option<TLVArray<'a, DataVersionFilter>>,
     }
@@ -185,13 +212,18 @@ pub mod msg {
             self.attr_requests = Some(TLVArray::new(requests));
             self
         }
+
+        /// The number of the first event to report, as per the event filters of the request
+        pub fn event_min(&self) -> EventNumber {
+            event_min(self.event_filters.as_ref())
+        }
     }
 
     #[derive(FromTLV, ToTLV, Debug)]
     #[tlvargs(lifetime = "'a")]
     pub struct WriteReq<'a> {
         pub supress_response: Option<bool>,
-        timed_request: Option<bool>,
+        pub timed_request: Option<bool>,
         pub write_requests: TLVArray<'a, AttrData<'a>>,
         more_chunked: Option<bool>,
     }
@@ -209,6 +241,23 @@ pub mod msg {
             }
             w
         }
+
+        /// Marks the request as following a TimedRequest
+        pub fn set_timed_request(mut self, timed_request: bool) -> Self {
+            self.timed_request = Some(timed_request);
+            self
+        }
+
+        /// Marks the request as a chunk of a chunked write, which is followed by more chunks
+        pub fn set_more_chunked(mut self, more_chunked: bool) -> Self {
+            self.more_chunked = Some(more_chunked);
+            self
+        }
+
+        /// Whether the request is followed by more chunks of the same write
+        pub fn more_chunked(&self) -> bool {
+            self.more_chunked.unwrap_or(false)
+        }
     }
 
     // Report Data
@@ -217,16 +266,32 @@ pub mod msg {
     pub struct ReportDataMsg<'a> {
         pub subscription_id: Option<u32>,
         pub attr_reports: Option<TLVArray<'a, AttrResp<'a>>>,
-        // TODO
-        pub event_reports: Option<bool>,
+        pub event_reports: Option<TLVArray<'a, EventResp<'a>>>,
         pub more_chunks: Option<bool>,
         pub suppress_response: Option<bool>,
     }
 
+    impl<'a> ReportDataMsg<'a> {
+        /// The attribute reports, with the paths encoded with tag compression expanded
+        pub fn attr_resps(&self) -> impl Iterator<Item = AttrResp<'a>> + '_ {
+            let mut prev = AttrPath::default();
+
+            self.attr_reports
+                .iter()
+                .flat_map(|reports| reports.iter())
+                .map(move |mut resp| {
+                    resp.expand_path(&prev);
+                    prev = resp.path().clone();
+
+                    resp
+                })
+        }
+    }
+
     pub enum ReportDataTag {
         SubscriptionId = 0,
         AttributeReports = 1,
-        _EventReport = 2,
+        EventReports = 2,
         MoreChunkedMsgs = 3,
         SupressResponse = 4,
     }
@@ -249,7 +314,10 @@ pub mod ib {
     use crate::{
         data_model::objects::{AttrDetails, AttrId, ClusterId, CmdId, EncodeValue, EndptId},
         error::{Error, ErrorCode},
-        interaction_model::core::IMStatusCode,
+        interaction_model::{
+            core::IMStatusCode,
+            events::{EventId, EventNumber, EventPriority},
+        },
         tlv::{FromTLV, Nullable, TLVElement, TLVWriter, TagType, ToTLV},
     };
     use log::error;
@@ -266,10 +334,7 @@ pub mod ib {
 
     impl<'a> InvResp<'a> {
         pub fn status_new(cmd_path: CmdPath, status: IMStatusCode, cluster_status: u16) -> Self {
-            Self::Status(CmdStatus {
-                path: cmd_path,
-                status: Status::new(status, cluster_status),
-            })
+            Self::Status(CmdStatus::new(cmd_path, status, cluster_status))
         }
     }
 
@@ -294,6 +359,7 @@ pub mod ib {
     pub struct CmdStatus {
         path: CmdPath,
         status: Status,
+        command_ref: Option<u16>,
     }
 
     impl CmdStatus {
@@ -304,8 +370,29 @@ pub mod ib {
                     status,
                     cluster_status,
                 },
+                command_ref: None,
             }
         }
+
+        /// Sets the reference of the command the status is for, as given in a batched
+        /// InvokeRequest
+        pub fn set_command_ref(mut self, command_ref: Option<u16>) -> Self {
+            self.command_ref = command_ref;
+            self
+        }
+
+        pub fn path(&self) -> &CmdPath {
+            &self.path
+        }
+
+        pub fn status(&self) -> &Status {
+            &self.status
+        }
+
+        /// The reference of the command the status is for, in a batched InvokeRequest
+        pub fn command_ref(&self) -> Option<u16> {
+            self.command_ref
+        }
     }
 
     #[derive(Debug, Clone, FromTLV, ToTLV)]
@@ -313,17 +400,30 @@ pub mod ib {
     pub struct CmdData<'a> {
         pub path: CmdPath,
         pub data: EncodeValue<'a>,
+        pub command_ref: Option<u16>,
     }
 
     impl<'a> CmdData<'a> {
         pub fn new(path: CmdPath, data: EncodeValue<'a>) -> Self {
-            Self { path, data }
+            Self {
+                path,
+                data,
+                command_ref: None,
+            }
+        }
+
+        /// Sets the reference of the command, which tells apart the responses to the commands
+        /// of a batched InvokeRequest
+        pub fn set_command_ref(mut self, command_ref: u16) -> Self {
+            self.command_ref = Some(command_ref);
+            self
         }
     }
 
     pub enum CmdDataTag {
         Path = 0,
         Data = 1,
+        CommandRef = 2,
     }
 
     // Status
@@ -340,6 +440,16 @@ pub mod ib {
                 cluster_status,
             }
         }
+
+        /// `Ok` for a success status, or else the error the status stands for - including
+        /// a cluster-specific status, if any
+        pub fn to_result(&self) -> Result<(), Error> {
+            if self.status == IMStatusCode::Failure && self.cluster_status != 0 {
+                Err(ErrorCode::ClusterStatus(self.cluster_status as _).into())
+            } else {
+                self.status.to_result()
+            }
+        }
     }
 
     // Attribute Response
@@ -351,6 +461,22 @@ pub mod ib {
     }
 
     impl<'a> AttrResp<'a> {
+        pub fn path(&self) -> &AttrPath {
+            match self {
+                AttrResp::Status(status) => &status.path,
+                AttrResp::Data(data) => &data.path,
+            }
+        }
+
+        /// Expands the path of the response, if encoded with tag compression (see
+        /// [`AttrPath::expand`])
+        pub fn expand_path(&mut self, prev: &AttrPath) {
+            match self {
+                AttrResp::Status(status) => status.path.expand(prev),
+                AttrResp::Data(data) => data.path.expand(prev),
+            }
+        }
+
         pub fn unwrap_data(self) -> AttrData<'a> {
             match self {
                 AttrResp::Data(d) => d,
@@ -430,6 +556,11 @@ pub mod ib {
             } else {
                 f(ListOperation::EditItem(index), data)
             }
+        } else if let Some(Nullable::Null) = attr.list_index {
+            // A NULL list index appends the data as an item - even if it is a list itself.
+            // This is how a chunked write of a long list carries the items not fitting in its
+            // first chunk, which replaces the whole list
+            f(ListOperation::AddItem, data)
         } else if data.confirm_array().is_ok() {
             // If data is list, this is either Delete List or OverWrite List operation
             // in either case, we have to first delete the whole list
@@ -460,6 +591,14 @@ pub mod ib {
                 status: super::ib::Status::new(status, cluster_status),
             }
         }
+
+        pub fn path(&self) -> &AttrPath {
+            &self.path
+        }
+
+        pub fn status(&self) -> &Status {
+            &self.status
+        }
     }
 
     // Attribute Path
@@ -487,6 +626,17 @@ pub mod ib {
         pub fn to_gp(&self) -> GenericPath {
             GenericPath::new(self.endpoint, self.cluster, self.attr.map(|x| x as u32))
         }
+
+        /// Expands a path encoded with tag compression: its omitted node, endpoint and cluster
+        /// are those of `prev`, the path which preceded it in the same report message
+        pub fn expand(&mut self, prev: &AttrPath) {
+            if self.tag_compression == Some(true) {
+                self.node = self.node.or(prev.node);
+                self.endpoint = self.endpoint.or(prev.endpoint);
+                self.cluster = self.cluster.or(prev.cluster);
+                self.tag_compression = None;
+            }
+        }
     }
 
     // Command Path
@@ -559,16 +709,69 @@ pub mod ib {
         pub data_ver: u32,
     }
 
-    #[derive(FromTLV, ToTLV, Clone, Debug)]
+    #[derive(Default, FromTLV, ToTLV, Clone, PartialEq, Debug)]
     #[tlvargs(datatype = "list")]
     pub struct EventPath {
         pub node: Option<u64>,
         pub endpoint: Option<EndptId>,
         pub cluster: Option<ClusterId>,
-        pub event: Option<u32>,
+        pub event: Option<EventId>,
         pub is_urgent: Option<bool>,
     }
 
+    // Event Response
+    #[derive(Clone, FromTLV, ToTLV, PartialEq, Debug)]
+    #[tlvargs(lifetime = "'a")]
+    pub enum EventResp<'a> {
+        Status(EventStatus),
+        Data(EventData<'a>),
+    }
+
+    pub enum EventRespTag {
+        Status = 0,
+        Data = 1,
+    }
+
+    // Event Data
+    #[derive(Clone, PartialEq, FromTLV, ToTLV, Debug)]
+    #[tlvargs(lifetime = "'a")]
+    pub struct EventData<'a> {
+        pub path: EventPath,
+        pub event_number: EventNumber,
+        pub priority: EventPriority,
+        pub epoch_ts: Option<u64>,
+        pub system_ts: Option<u64>,
+        pub delta_epoch_ts: Option<u64>,
+        pub delta_system_ts: Option<u64>,
+        pub data: EncodeValue<'a>,
+    }
+
+    pub enum EventDataTag {
+        Path = 0,
+        EventNumber = 1,
+        Priority = 2,
+        EpochTimestamp = 3,
+        SystemTimestamp = 4,
+        DeltaEpochTimestamp = 5,
+        DeltaSystemTimestamp = 6,
+        Data = 7,
+    }
+
+    #[derive(Debug, Clone, PartialEq, FromTLV, ToTLV)]
+    pub struct EventStatus {
+        path: EventPath,
+        status: Status,
+    }
+
+    impl EventStatus {
+        pub fn new(path: EventPath, status: IMStatusCode, cluster_status: u16) -> Self {
+            Self {
+                path,
+                status: Status::new(status, cluster_status),
+            }
+        }
+    }
+
     #[derive(FromTLV, ToTLV, Clone, Debug)]
     pub struct EventFilter {
         pub node: Option<u64>,
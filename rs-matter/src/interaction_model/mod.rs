@@ -15,5 +15,8 @@
  *    limitations under the License.
  */
 
+pub mod client;
 pub mod core;
+pub mod events;
 pub mod messages;
+pub mod subscriptions;
@@ -0,0 +1,593 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The subscriptions this node reports to, as a publisher.
+//!
+//! A subscription is established by a SubscribeRequest over a CASE session, and is primed
+//! with a report of all subscribed paths right away. Afterwards, the reporting engine
+//! (see [`crate::data_model::core::DataModel::report`]) sends to the subscriber:
+//! - a report of all subscribed paths, once attributes on them changed (see
+//!   [`crate::Matter::notify_attribute_changed`] and
+//!   [`crate::Matter::notify_attributes_changed`]) and the min interval of the subscription
+//!   elapsed since its last report; the changes within the min interval are coalesced into
+//!   that single report;
+//! - a report of the events emitted since its last report, once an event was emitted on one of
+//!   its urgent event paths (see [`crate::interaction_model::events`]) and the min interval
+//!   elapsed; the events on its other event paths are reported along with its next report;
+//! - an empty report otherwise, once the max interval elapses, which keeps the subscription -
+//!   and the CASE session it runs over - alive.
+//!
+//! The subscriptions are not bound to a session, but to the fabric and the node ID of the
+//! subscriber: the reports are sent over any CASE session with the subscriber. A subscription
+//! is terminated once a report cannot be delivered.
+//!
+//! The SubscribeRequest of each subscription is kept, as the reports are generated from it.
+//! So are the data versions of the clusters last reported to the subscriber: they act as
+//! DataVersionFilters in the following reports, which thus leave out the clusters that did not
+//! change since.
+
+use core::time::Duration;
+
+use heapless::Vec;
+use log::info;
+
+use crate::{
+    data_model::objects::{ClusterId, EndptId},
+    error::{Error, ErrorCode},
+    interaction_model::{
+        events::{Event, EventNumber},
+        messages::{msg::SubscribeReq, GenericPath},
+    },
+    tlv::{get_root_node_struct, FromTLV},
+};
+
+pub const MAX_SUBSCRIPTIONS: usize = crate::config::MAX_SUBSCRIPTIONS;
+pub const MAX_SUBSCRIPTION_REQ_SIZE: usize = crate::config::MAX_SUBSCRIPTION_REQ_SIZE;
+pub const MAX_SUBSCRIPTION_DATAVERS: usize = crate::config::MAX_SUBSCRIPTION_DATAVERS;
+
+pub struct Subscription {
+    pub id: u32,
+    pub fab_idx: u8,
+    pub peer_node_id: u64,
+    pub min_int_secs: u16,
+    pub max_int_secs: u16,
+    reported_at: Duration,
+    changed: bool,
+    urgent_events: bool,
+    events_from: EventNumber,
+    // Not reported until primed
+    active: bool,
+    req: Vec<u8, MAX_SUBSCRIPTION_REQ_SIZE>,
+    datavers: Vec<(EndptId, ClusterId, u32), MAX_SUBSCRIPTION_DATAVERS>,
+}
+
+impl Subscription {
+    /// The encoded SubscribeRequest the subscription was established with
+    pub fn req(&self) -> &[u8] {
+        &self.req
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The number of the first event the subscription did not report yet
+    pub fn events_from(&self) -> EventNumber {
+        self.events_from
+    }
+
+    /// The data versions of the clusters as last reported to the subscriber
+    pub fn datavers(&self) -> &[(EndptId, ClusterId, u32)] {
+        &self.datavers
+    }
+
+    /// The data version of a cluster as last reported to the subscriber, if tracked
+    pub fn dataver(&self, endpoint: EndptId, cluster: ClusterId) -> Option<u32> {
+        self.datavers()
+            .iter()
+            .find(|(ep, cl, _)| *ep == endpoint && *cl == cluster)
+            .map(|(_, _, dataver)| *dataver)
+    }
+
+    /// Marks the subscription as changed. Returns `true` if its report became due earlier.
+    fn mark_changed(&mut self) -> bool {
+        let deadline = self.report_deadline();
+
+        self.changed = true;
+
+        self.report_deadline() != deadline
+    }
+
+    /// When the next report of the subscription is due, if it is active
+    pub fn report_deadline(&self) -> Option<Duration> {
+        if !self.active {
+            return None;
+        }
+
+        let interval = if self.changed || self.urgent_events {
+            self.min_int_secs.min(self.max_int_secs)
+        } else {
+            self.max_int_secs
+        };
+
+        Some(self.reported_at + Duration::from_secs(interval as _))
+    }
+}
+
+pub struct Subscriptions {
+    subscriptions: Vec<Subscription, MAX_SUBSCRIPTIONS>,
+    next_id: u32,
+}
+
+impl Subscriptions {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            subscriptions: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// A new subscription ID
+    pub fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+
+        id
+    }
+
+    /// Adds a subscription, which is only reported once it is primed (see
+    /// [`Subscriptions::activate`]). Fails with [`ErrorCode::ResourceExhausted`] when all
+    /// subscription slots are taken, or when `req` - the encoded SubscribeRequest - is too large.
+    pub fn add(
+        &mut self,
+        id: u32,
+        fab_idx: u8,
+        peer_node_id: u64,
+        min_int_secs: u16,
+        max_int_secs: u16,
+        req: &[u8],
+    ) -> Result<(), Error> {
+        let req = Vec::from_slice(req).map_err(|_| ErrorCode::ResourceExhausted)?;
+
+        self.subscriptions
+            .push(Subscription {
+                id,
+                fab_idx,
+                peer_node_id,
+                min_int_secs,
+                max_int_secs,
+                reported_at: Duration::ZERO,
+                changed: false,
+                urgent_events: false,
+                events_from: 0,
+                active: false,
+                req,
+                datavers: Vec::new(),
+            })
+            .map_err(|_| ErrorCode::ResourceExhausted)?;
+
+        Ok(())
+    }
+
+    /// Activates a primed subscription, whose reports are due from now on
+    pub fn activate(&mut self, id: u32, now: Duration) -> bool {
+        if let Some(subscription) = self.get_mut(id) {
+            subscription.active = true;
+            subscription.reported_at = now;
+
+            info!("Subscription {} established", id);
+
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+
+        len != self.subscriptions.len()
+    }
+
+    /// Removes all subscriptions of a subscriber, e.g. when it subscribes again without
+    /// keeping its existing subscriptions. Returns how many were removed.
+    pub fn remove_subscriber(&mut self, fab_idx: u8, peer_node_id: u64) -> usize {
+        let len = self.subscriptions.len();
+        self.subscriptions
+            .retain(|s| s.fab_idx != fab_idx || s.peer_node_id != peer_node_id);
+
+        len - self.subscriptions.len()
+    }
+
+    /// Removes all subscriptions on the given fabric, e.g. when the fabric itself is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        self.subscriptions.retain(|s| s.fab_idx != fab_idx);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Subscription> {
+        self.iter().find(|s| s.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Subscription> {
+        self.subscriptions.iter()
+    }
+
+    /// Marks all subscriptions as changed, so that their subscribed paths are reported
+    /// once their min interval elapses. Returns `true` if the report of any subscription
+    /// became due earlier, i.e. if the reporting engine needs to be woken up.
+    pub fn notify_changed(&mut self) -> bool {
+        let mut earlier = false;
+
+        for subscription in self.subscriptions.iter_mut() {
+            earlier |= subscription.mark_changed();
+        }
+
+        earlier
+    }
+
+    /// Marks the subscriptions with an attribute path overlapping with the - possibly wildcard -
+    /// `path` as changed, so that their subscribed paths are reported once their min interval
+    /// elapses. Subscriptions already marked are left as they are, coalescing the changes up to
+    /// their next report.
+    ///
+    /// Returns `true` if the report of any subscription became due earlier, i.e. if the
+    /// reporting engine needs to be woken up.
+    pub fn notify_attribute_changed(&mut self, path: &GenericPath) -> bool {
+        let mut earlier = false;
+
+        for subscription in self.subscriptions.iter_mut() {
+            if subscription.changed {
+                continue;
+            }
+
+            let subscribed = get_root_node_struct(&subscription.req)
+                .and_then(|root| SubscribeReq::from_tlv(&root))
+                .map(|req| {
+                    req.attr_requests
+                        .iter()
+                        .flat_map(|paths| paths.iter())
+                        .any(|attr_path| attr_path.to_gp().overlaps(path))
+                })
+                .unwrap_or(false);
+
+            if subscribed {
+                earlier |= subscription.mark_changed();
+            }
+        }
+
+        earlier
+    }
+
+    /// Marks the subscriptions with an urgent event path `event` is on, so that the event is
+    /// reported once their min interval elapses. Returns `true` if any subscription was marked.
+    pub fn notify_event(&mut self, event: &Event) -> bool {
+        let mut notified = false;
+
+        for subscription in self.subscriptions.iter_mut() {
+            let urgent = get_root_node_struct(&subscription.req)
+                .and_then(|root| SubscribeReq::from_tlv(&root))
+                .map(|req| {
+                    req.event_requests
+                        .iter()
+                        .flat_map(|paths| paths.iter())
+                        .any(|path| path.is_urgent == Some(true) && event.matches(&path))
+                })
+                .unwrap_or(false);
+
+            if urgent {
+                subscription.urgent_events = true;
+                notified = true;
+            }
+        }
+
+        notified
+    }
+
+    /// Records that a subscription reported the event numbered `number`
+    pub fn event_reported(&mut self, id: u32, number: EventNumber) {
+        if let Some(subscription) = self.get_mut(id) {
+            subscription.events_from = subscription.events_from.max(number + 1);
+        }
+    }
+
+    /// Records that a subscription reported a cluster with data version `dataver`. Once more
+    /// clusters are reported than can be tracked, the version tracked first is dropped, and
+    /// that cluster is thus reported in full again.
+    pub fn dataver_reported(
+        &mut self,
+        id: u32,
+        endpoint: EndptId,
+        cluster: ClusterId,
+        dataver: u32,
+    ) {
+        if let Some(subscription) = self.get_mut(id) {
+            let datavers = &mut subscription.datavers;
+
+            if let Some(entry) = datavers
+                .iter_mut()
+                .find(|(ep, cl, _)| *ep == endpoint && *cl == cluster)
+            {
+                entry.2 = dataver;
+            } else {
+                if datavers.is_full() {
+                    datavers.remove(0);
+                }
+
+                datavers.push((endpoint, cluster, dataver)).unwrap();
+            }
+        }
+    }
+
+    /// Records that the handler of a cluster reported by a subscription was temporarily unable
+    /// to serve one of its attributes: the data version of the cluster is forgotten and the
+    /// subscription is marked as changed, so that the cluster is reported again, in full, once
+    /// the min interval of the subscription elapses.
+    pub fn busy(&mut self, id: u32, endpoint: EndptId, cluster: ClusterId) {
+        if let Some(subscription) = self.get_mut(id) {
+            subscription
+                .datavers
+                .retain(|(ep, cl, _)| *ep != endpoint || *cl != cluster);
+            subscription.changed = true;
+        }
+    }
+
+    /// Returns - and clears - whether the subscribed paths of a subscription changed since
+    /// its last report
+    pub fn take_changed(&mut self, id: u32) -> bool {
+        self.get_mut(id)
+            .map(|s| core::mem::replace(&mut s.changed, false))
+            .unwrap_or(false)
+    }
+
+    /// Records that a subscription was reported at `now`
+    pub fn reported(&mut self, id: u32, now: Duration) {
+        if let Some(subscription) = self.get_mut(id) {
+            subscription.reported_at = now;
+            subscription.urgent_events = false;
+        }
+    }
+
+    /// The ID of a subscription whose report is due at `now`, if any
+    pub fn due(&self, now: Duration) -> Option<u32> {
+        self.iter()
+            .find(|s| s.report_deadline().map(|d| d <= now).unwrap_or(false))
+            .map(|s| s.id)
+    }
+
+    /// When the next report of any subscription is due
+    pub fn deadline(&self) -> Option<Duration> {
+        self.iter().filter_map(Subscription::report_deadline).min()
+    }
+
+    fn get_mut(&mut self, id: u32) -> Option<&mut Subscription> {
+        self.subscriptions.iter_mut().find(|s| s.id == id)
+    }
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        interaction_model::{
+            events::{EventPriority, Events},
+            messages::{
+                ib::{AttrPath, EventPath},
+                msg::SubscribeReq,
+                GenericPath,
+            },
+        },
+        tlv::{TLVArray, TLVWriter, TagType, ToTLV},
+        utils::writebuf::WriteBuf,
+    };
+
+    use super::Subscriptions;
+
+    #[test]
+    fn test_report_deadlines() {
+        let mut subs = Subscriptions::new();
+
+        let id = subs.next_id();
+        subs.add(id, 1, 100, 2, 60, &[0x15, 0x18]).unwrap();
+
+        // Not reported until primed
+        subs.notify_changed();
+        assert_eq!(subs.deadline(), None);
+
+        let now = Duration::from_secs(10);
+        assert!(subs.activate(id, now));
+        assert!(subs.take_changed(id));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(70)));
+        assert_eq!(subs.due(Duration::from_secs(69)), None);
+        assert_eq!(subs.due(Duration::from_secs(70)), Some(id));
+
+        // Changes are reported after the min interval
+        subs.notify_changed();
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(12)));
+
+        assert!(subs.take_changed(id));
+        subs.reported(id, Duration::from_secs(12));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(72)));
+    }
+
+    #[test]
+    fn test_attribute_changes() {
+        let paths = [AttrPath::new(&GenericPath::new(Some(1), Some(6), None))];
+
+        let mut req = SubscribeReq::new(false, 2, 60);
+        req.attr_requests = Some(TLVArray::new(&paths));
+
+        let mut buf = [0; 128];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let len = tw.get_tail();
+
+        let mut subs = Subscriptions::new();
+        let id = subs.next_id();
+        subs.add(id, 1, 100, 2, 60, &buf[..len]).unwrap();
+
+        // Not woken up until primed
+        assert!(!subs.notify_attribute_changed(&GenericPath::new(Some(1), Some(6), Some(0))));
+        assert!(subs.take_changed(id));
+
+        subs.activate(id, Duration::ZERO);
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(60)));
+
+        // Changes off the subscribed paths are not reported
+        assert!(!subs.notify_attribute_changed(&GenericPath::new(Some(0), Some(0x28), Some(5))));
+        assert!(!subs.notify_attribute_changed(&GenericPath::new(Some(1), Some(8), None)));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(60)));
+
+        assert!(subs.notify_attribute_changed(&GenericPath::new(Some(1), Some(6), Some(0))));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(2)));
+
+        // Further changes are coalesced into the same report
+        assert!(!subs.notify_attribute_changed(&GenericPath::new(None, Some(6), None)));
+        assert!(!subs.notify_changed());
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(2)));
+
+        assert!(subs.take_changed(id));
+        subs.reported(id, Duration::from_secs(2));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(62)));
+
+        assert!(subs.notify_changed());
+    }
+
+    #[test]
+    fn test_urgent_events() {
+        let paths = [
+            EventPath {
+                endpoint: Some(1),
+                cluster: Some(6),
+                is_urgent: Some(true),
+                ..Default::default()
+            },
+            EventPath {
+                cluster: Some(0x28),
+                ..Default::default()
+            },
+        ];
+
+        let mut req = SubscribeReq::new(false, 1, 60);
+        req.event_requests = Some(TLVArray::new(&paths));
+
+        let mut buf = [0; 128];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        req.to_tlv(&mut tw, TagType::Anonymous).unwrap();
+        let len = tw.get_tail();
+
+        let mut subs = Subscriptions::new();
+        let id = subs.next_id();
+        subs.add(id, 1, 100, 1, 60, &buf[..len]).unwrap();
+        subs.activate(id, Duration::ZERO);
+
+        let mut events = Events::new();
+        let on_off = events
+            .emit(1, 6, 0, EventPriority::Info, Duration::ZERO, &true)
+            .unwrap();
+        let basic = events
+            .emit(0, 0x28, 0, EventPriority::Info, Duration::ZERO, &true)
+            .unwrap();
+
+        // Non-urgent events wait for the next report
+        assert!(!subs.notify_event(events.get(basic).unwrap()));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(60)));
+
+        assert!(subs.notify_event(events.get(on_off).unwrap()));
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(1)));
+        assert!(!subs.take_changed(id));
+
+        subs.event_reported(id, basic);
+        subs.reported(id, Duration::from_secs(1));
+        assert_eq!(subs.get(id).unwrap().events_from(), basic + 1);
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_datavers() {
+        let mut subs = Subscriptions::new();
+
+        let id = subs.next_id();
+        subs.add(id, 1, 100, 0, 10, &[]).unwrap();
+
+        subs.dataver_reported(id, 0, 0x28, 5);
+        subs.dataver_reported(id, 0, 0x28, 6);
+        assert_eq!(subs.get(id).unwrap().dataver(0, 0x28), Some(6));
+        assert_eq!(subs.get(id).unwrap().dataver(1, 0x28), None);
+
+        // The version tracked first is dropped once more are reported
+        for cluster in 0..super::MAX_SUBSCRIPTION_DATAVERS as u32 {
+            subs.dataver_reported(id, 1, cluster, cluster);
+        }
+
+        assert_eq!(subs.get(id).unwrap().dataver(0, 0x28), None);
+        assert_eq!(subs.get(id).unwrap().dataver(1, 0), Some(0));
+    }
+
+    #[test]
+    fn test_busy() {
+        let mut subs = Subscriptions::new();
+
+        let id = subs.next_id();
+        subs.add(id, 1, 100, 2, 60, &[]).unwrap();
+        subs.activate(id, Duration::from_secs(10));
+
+        subs.dataver_reported(id, 0, 0x28, 5);
+        subs.dataver_reported(id, 1, 6, 7);
+
+        // The busy cluster is reported again, in full, after the min interval
+        subs.busy(id, 1, 6);
+        assert_eq!(subs.get(id).unwrap().dataver(0, 0x28), Some(5));
+        assert_eq!(subs.get(id).unwrap().dataver(1, 6), None);
+        assert_eq!(subs.deadline(), Some(Duration::from_secs(12)));
+        assert!(subs.take_changed(id));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut subs = Subscriptions::new();
+
+        for (fab_idx, node_id) in [(1, 100), (1, 100), (1, 101), (2, 100)] {
+            let id = subs.next_id();
+            subs.add(id, fab_idx, node_id, 0, 10, &[]).unwrap();
+        }
+
+        assert_eq!(subs.remove_subscriber(1, 100), 2);
+        assert!(subs.get(1).is_none());
+        assert!(subs.get(3).is_some());
+
+        subs.remove_fabric(1);
+        assert_eq!(subs.iter().count(), 1);
+        assert!(subs.remove(4));
+        assert!(!subs.remove(4));
+
+        // Too large requests are rejected
+        assert!(subs
+            .add(5, 1, 100, 0, 10, &[0; super::MAX_SUBSCRIPTION_REQ_SIZE + 1])
+            .is_err());
+    }
+}
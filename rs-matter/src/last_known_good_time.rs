@@ -0,0 +1,165 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Last Known Good UTC Time: a lower bound of the current UTC time, which the node keeps -
+//! and persists - so that it can reject expired certificates even without a trusted time source.
+//!
+//! It only ever moves forward, whenever the node learns of a later time:
+//! - from a trusted time source (see [`crate::Matter::update_last_known_good_time`]);
+//! - from the NotBefore of the certificates of its own NOC chain, when it is added or updated;
+//! - from the NotBefore of the certificates of its CASE peers, once they are validated.
+//!
+//! Without a trusted time source, a certificate is considered valid as long as it did not
+//! expire before the Last Known Good UTC Time; its NotBefore cannot be checked.
+
+use log::{info, warn};
+
+use crate::{
+    cert::Cert,
+    error::{Error, ErrorCode},
+    tlv::{TLVList, TLVWriter, TagType},
+    utils::writebuf::WriteBuf,
+};
+
+pub struct LastKnownGoodTime {
+    /// Seconds since the Matter epoch (2000-01-01 00:00:00 UTC)
+    time: u32,
+    changed: bool,
+}
+
+impl LastKnownGoodTime {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            time: 0,
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        self.time = root.u32()?;
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.u32(TagType::Anonymous, self.time)?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// The Last Known Good UTC Time, in seconds since the Matter epoch
+    pub fn get(&self) -> u32 {
+        self.time
+    }
+
+    /// Moves the Last Known Good UTC Time forward to `time` (in seconds since the Matter epoch),
+    /// unless it is already later. Returns whether it moved.
+    pub fn update(&mut self, time: u32) -> bool {
+        if time > self.time {
+            info!("Last Known Good UTC Time: {} -> {}", self.time, time);
+
+            self.time = time;
+            self.changed = true;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves the Last Known Good UTC Time forward to the latest NotBefore of `certs`,
+    /// as none of them could have been issued before the current time
+    pub fn update_from_certs(&mut self, certs: &[&Cert]) -> bool {
+        let not_before = certs
+            .iter()
+            .map(|cert| cert.get_not_before())
+            .max()
+            .unwrap_or(0);
+
+        self.update(not_before)
+    }
+
+    /// Fails with [`ErrorCode::InvalidTime`] if any of `certs` expired before the
+    /// Last Known Good UTC Time
+    pub fn validate(&self, certs: &[&Cert]) -> Result<(), Error> {
+        for cert in certs {
+            // A NotAfter of 0 means that the certificate never expires
+            let not_after = cert.get_not_after();
+
+            if not_after != 0 && not_after < self.time {
+                warn!(
+                    "Certificate expired at {}, before the Last Known Good UTC Time {}",
+                    not_after, self.time
+                );
+                Err(ErrorCode::InvalidTime)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for LastKnownGoodTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LastKnownGoodTime;
+
+    #[test]
+    fn test_lkgt_only_moves_forward() {
+        let mut lkgt = LastKnownGoodTime::new();
+        assert!(!lkgt.is_changed());
+
+        assert!(lkgt.update(1000));
+        assert!(lkgt.is_changed());
+        assert!(!lkgt.update(999));
+        assert!(!lkgt.update(1000));
+        assert_eq!(lkgt.get(), 1000);
+
+        let mut buf = [0; 16];
+        let data = lkgt.store(&mut buf).unwrap().unwrap();
+        assert!(!lkgt.is_changed());
+
+        let mut loaded = LastKnownGoodTime::new();
+        loaded.load(data).unwrap();
+        assert_eq!(loaded.get(), 1000);
+        assert!(!loaded.is_changed());
+    }
+}
@@ -73,16 +73,22 @@
 #![allow(async_fn_in_trait)]
 
 pub mod acl;
+pub mod binding;
 pub mod cert;
 pub mod codec;
+pub mod config;
 pub mod core;
 pub mod crypto;
 pub mod data_model;
 pub mod error;
 pub mod fabric;
 pub mod group_keys;
+pub mod icd;
 pub mod interaction_model;
+pub mod last_known_good_time;
 pub mod mdns;
+pub mod nv_attributes;
+pub mod paired_nodes;
 pub mod pairing;
 pub mod persist;
 pub mod secure_channel;
@@ -37,6 +37,7 @@ mod builtin;
 )))]
 pub use builtin::{
     Host, MDNS_IPV4_BROADCAST_ADDR, MDNS_IPV6_BROADCAST_ADDR, MDNS_PORT, MDNS_SOCKET_BIND_ADDR,
+    MDNS_SOCKET_BIND_ADDR_IPV4,
 };
 
 /// A trait representing an mDNS implementation capable of registering and de-registering Matter-specific services
@@ -49,6 +50,10 @@ pub trait Mdns {
 
     /// Remove a service; if service with that name is not registered, it will be ignored
     fn remove(&self, service: &str) -> Result<(), Error>;
+
+    /// Re-advertise all registered services, e.g. after the network interfaces or their
+    /// addresses changed. Responders which track the network interfaces themselves can ignore it
+    fn refresh(&self) {}
 }
 
 impl<T> Mdns for &mut T
@@ -66,6 +71,10 @@ where
     fn remove(&self, service: &str) -> Result<(), Error> {
         (**self).remove(service)
     }
+
+    fn refresh(&self) {
+        (**self).refresh();
+    }
 }
 
 impl<T> Mdns for &T
@@ -83,6 +92,10 @@ where
     fn remove(&self, service: &str) -> Result<(), Error> {
         (**self).remove(service)
     }
+
+    fn refresh(&self) {
+        (**self).refresh();
+    }
 }
 
 /// Models the mDNS implementation to be used by the Matter stack
@@ -142,6 +155,14 @@ impl<'a> Mdns for MdnsImpl<'a> {
             Self::Provided(mdns) => mdns.remove(service),
         }
     }
+
+    fn refresh(&self) {
+        match self {
+            Self::Disabled => {}
+            Self::Builtin(mdns) => mdns.refresh(),
+            Self::Provided(mdns) => mdns.refresh(),
+        }
+    }
 }
 
 pub struct Service<'a> {
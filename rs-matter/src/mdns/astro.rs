@@ -32,6 +32,10 @@ impl<'a> MdnsImpl<'a> {
         self.services.borrow_mut().clear();
     }
 
+    pub fn refresh(&self) {
+        // Nothing to do: Bonjour tracks the network interfaces itself
+    }
+
     pub fn add(&self, name: &str, mode: ServiceMode) -> Result<(), Error> {
         let _ = self.remove(name);
 
@@ -6,6 +6,7 @@ use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Timer};
 use log::{info, warn};
 
+use crate::config::MAX_MDNS_SERVICES;
 use crate::data_model::cluster_basic_information::BasicInfoConfig;
 use crate::error::{Error, ErrorCode};
 use crate::transport::network::{
@@ -29,6 +30,10 @@ mod proto;
 pub const MDNS_SOCKET_BIND_ADDR: SocketAddr =
     SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, MDNS_PORT, 0, 0));
 
+/// The address to bind the mDNS socket to, on networks or platforms without IPv6
+pub const MDNS_SOCKET_BIND_ADDR_IPV4: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT));
+
 pub const MDNS_IPV6_BROADCAST_ADDR: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x00fb);
 pub const MDNS_IPV4_BROADCAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
 
@@ -37,7 +42,7 @@ pub const MDNS_PORT: u16 = 5353;
 pub struct MdnsImpl<'a> {
     dev_det: &'a BasicInfoConfig<'a>,
     matter_port: u16,
-    services: RefCell<heapless::Vec<(heapless::String<40>, ServiceMode), 4>>,
+    services: RefCell<heapless::Vec<(heapless::String<40>, ServiceMode), MAX_MDNS_SERVICES>>,
     notification: Notification,
 }
 
@@ -79,6 +84,11 @@ impl<'a> MdnsImpl<'a> {
         Ok(())
     }
 
+    /// Re-broadcasts all services right away, rather than at the next periodic broadcast
+    pub fn refresh(&self) {
+        self.notification.signal(());
+    }
+
     pub fn for_each<F>(&self, mut callback: F) -> Result<(), Error>
     where
         F: FnMut(&Service) -> Result<(), Error>,
@@ -94,6 +104,12 @@ impl<'a> MdnsImpl<'a> {
         Ok(())
     }
 
+    /// Runs the responder over the provided mDNS socket halves.
+    ///
+    /// The services are always advertised over IPv4 ([`MDNS_IPV4_BROADCAST_ADDR`]) with an
+    /// A record. They are also advertised over IPv6 ([`MDNS_IPV6_BROADCAST_ADDR`], on `interface`)
+    /// with an AAAA record, unless `interface` is `None` or `host` has no IPv6 address - i.e. when
+    /// running IPv4-only.
     pub async fn run<S, R, SB, RB>(
         &self,
         send: S,
@@ -141,6 +157,7 @@ impl<'a> MdnsImpl<'a> {
             )))
             .chain(
                 interface
+                    .filter(|_| host.ipv6.is_some())
                     .map(|interface| {
                         SocketAddr::V6(SocketAddrV6::new(
                             MDNS_IPV6_BROADCAST_ADDR,
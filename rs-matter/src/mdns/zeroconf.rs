@@ -43,6 +43,10 @@ impl<'a> MdnsImpl<'a> {
         self.services.borrow_mut().clear();
     }
 
+    pub fn refresh(&self) {
+        // Nothing to do: Avahi tracks the network interfaces itself
+    }
+
     pub fn add(&self, name: &str, mode: ServiceMode) -> Result<(), Error> {
         let _ = self.remove(name);
 
@@ -0,0 +1,265 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The values of the non-volatile attributes, i.e. of the attributes with the
+//! [`Quality::PERSISTENT`](crate::data_model::objects::Quality::PERSISTENT) quality, which
+//! need to survive a reboot of the node.
+//!
+//! The values written to those attributes over the Interaction Model are recorded here, keyed
+//! by endpoint, cluster and attribute, and persisted (see [`crate::Matter::store_nv_attributes`]).
+//! Once loaded back at startup, they are written to the handlers of their clusters
+//! (see [`crate::Matter::restore_nv_attributes`]).
+
+use heapless::Vec;
+
+use crate::{
+    data_model::objects::{AttrId, ClusterId, EndptId},
+    error::{Error, ErrorCode},
+    tlv::{self, FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    utils::writebuf::WriteBuf,
+};
+
+pub const MAX_NV_ATTRIBUTES: usize = crate::config::MAX_NV_ATTRIBUTES;
+
+pub const MAX_NV_ATTRIBUTE_SIZE: usize = crate::config::MAX_NV_ATTRIBUTE_SIZE;
+
+/// The value of a non-volatile attribute
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct NvAttribute {
+    pub endpoint_id: EndptId,
+    pub cluster_id: ClusterId,
+    pub attr_id: AttrId,
+    value: Vec<u8, MAX_NV_ATTRIBUTE_SIZE>,
+}
+
+impl NvAttribute {
+    /// The value of the attribute, as it was written
+    pub fn value(&self) -> Result<TLVElement<'_>, Error> {
+        TLVList::new(&self.value)
+            .iter()
+            .next()
+            .ok_or(ErrorCode::Invalid.into())
+    }
+}
+
+type NvAttributeEntries = Vec<NvAttribute, MAX_NV_ATTRIBUTES>;
+
+pub struct NvAttributes {
+    attributes: NvAttributeEntries,
+    changed: bool,
+}
+
+impl NvAttributes {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            attributes: NvAttributeEntries::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut self.attributes, &root)?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.attributes
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Records the value of an attribute, replacing its previous value if any. Values which do
+    /// not fit in [`MAX_NV_ATTRIBUTE_SIZE`] bytes are rejected with `NoSpace`, as are the new
+    /// attributes once [`MAX_NV_ATTRIBUTES`] of them are recorded.
+    pub fn set(
+        &mut self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attr_id: AttrId,
+        value: &dyn ToTLV,
+    ) -> Result<(), Error> {
+        let mut buf = [0; MAX_NV_ATTRIBUTE_SIZE];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        value
+            .to_tlv(&mut tw, TagType::Anonymous)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        let len = tw.get_tail();
+        let value = &buf[..len];
+
+        if let Some(attribute) = self.attributes.iter_mut().find(|attribute| {
+            attribute.endpoint_id == endpoint_id
+                && attribute.cluster_id == cluster_id
+                && attribute.attr_id == attr_id
+        }) {
+            if attribute.value.as_slice() != value {
+                attribute.value = Vec::from_slice(value).unwrap();
+                self.changed = true;
+            }
+        } else {
+            self.attributes
+                .push(NvAttribute {
+                    endpoint_id,
+                    cluster_id,
+                    attr_id,
+                    value: Vec::from_slice(value).unwrap(),
+                })
+                .map_err(|_| ErrorCode::NoSpace)?;
+
+            self.changed = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(
+        &self,
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attr_id: AttrId,
+    ) -> Option<&NvAttribute> {
+        self.attributes.iter().find(|attribute| {
+            attribute.endpoint_id == endpoint_id
+                && attribute.cluster_id == cluster_id
+                && attribute.attr_id == attr_id
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NvAttribute> {
+        self.attributes.iter()
+    }
+
+    /// Forgets the values of the attributes of the endpoint, e.g. once it is removed for good
+    pub fn remove_endpoint(&mut self, endpoint_id: EndptId) {
+        let len = self.attributes.len();
+
+        self.attributes
+            .retain(|attribute| attribute.endpoint_id != endpoint_id);
+
+        if self.attributes.len() != len {
+            self.changed = true;
+        }
+    }
+}
+
+impl Default for NvAttributes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tlv::{TLVList, UtfStr};
+
+    use super::{NvAttributes, MAX_NV_ATTRIBUTES, MAX_NV_ATTRIBUTE_SIZE};
+
+    #[test]
+    fn test_set_and_get() {
+        let mut attributes = NvAttributes::new();
+
+        attributes.set(1, 6, 0x4003, &2u8).unwrap();
+        attributes.set(0, 0x28, 5, &UtfStr::new(b"label")).unwrap();
+        assert!(attributes.is_changed());
+
+        let value = attributes.get(1, 6, 0x4003).unwrap().value().unwrap();
+        assert_eq!(value.u8().unwrap(), 2);
+        let value = attributes.get(0, 0x28, 5).unwrap().value().unwrap();
+        assert_eq!(value.str().unwrap(), "label");
+        assert!(attributes.get(2, 6, 0x4003).is_none());
+
+        // Overwriting with the same value is not a change
+        let mut buf = [0; 256];
+        attributes.store(&mut buf).unwrap();
+        attributes.set(1, 6, 0x4003, &2u8).unwrap();
+        assert!(!attributes.is_changed());
+
+        attributes.set(1, 6, 0x4003, &1u8).unwrap();
+        assert!(attributes.is_changed());
+        let value = attributes.get(1, 6, 0x4003).unwrap().value().unwrap();
+        assert_eq!(value.u8().unwrap(), 1);
+        assert_eq!(attributes.iter().count(), 2);
+
+        attributes.remove_endpoint(1);
+        assert!(attributes.get(1, 6, 0x4003).is_none());
+        assert_eq!(attributes.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut attributes = NvAttributes::new();
+
+        let too_long = [b'a'; MAX_NV_ATTRIBUTE_SIZE];
+        assert!(attributes.set(0, 0x28, 5, &UtfStr::new(&too_long)).is_err());
+
+        for attr_id in 0..MAX_NV_ATTRIBUTES as u16 {
+            attributes.set(1, 6, attr_id, &true).unwrap();
+        }
+        assert!(attributes
+            .set(1, 6, MAX_NV_ATTRIBUTES as u16, &true)
+            .is_err());
+
+        // Existing attributes can still be updated
+        attributes.set(1, 6, 0, &false).unwrap();
+    }
+
+    #[test]
+    fn test_load_store() {
+        let mut attributes = NvAttributes::new();
+
+        attributes.set(1, 6, 0x4003, &2u8).unwrap();
+        attributes.set(0, 0x28, 5, &UtfStr::new(b"label")).unwrap();
+
+        let mut buf = [0; 256];
+        let data = attributes.store(&mut buf).unwrap().unwrap();
+        assert!(TLVList::new(data).iter().next().is_some());
+
+        let mut loaded = NvAttributes::new();
+        loaded.load(data).unwrap();
+        assert!(!loaded.is_changed());
+
+        let value = loaded.get(1, 6, 0x4003).unwrap().value().unwrap();
+        assert_eq!(value.u8().unwrap(), 2);
+        let value = loaded.get(0, 0x28, 5).unwrap().value().unwrap();
+        assert_eq!(value.str().unwrap(), "label");
+    }
+}
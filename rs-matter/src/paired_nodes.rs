@@ -0,0 +1,373 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The table of nodes commissioned by this node, when it operates in the controller role.
+//!
+//! The table is persisted together with the fabrics and the ACLs (see [`crate::Matter::store_paired_nodes`]),
+//! so that a controller can reconnect to its nodes after a restart without re-commissioning them.
+
+use heapless::Vec;
+
+use crate::{
+    crypto,
+    error::{Error, ErrorCode},
+    tlv::{self, FromTLV, TLVElement, TLVList, TLVWriter, TagType, ToTLV},
+    transport::network::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    utils::writebuf::WriteBuf,
+};
+
+pub const MAX_PAIRED_NODES: usize = crate::config::MAX_PAIRED_NODES;
+pub const MAX_NODE_ADDRESSES: usize = 2;
+
+/// A resolved operational address of a paired node
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct NodeAddress {
+    ip: Vec<u8, 16>,
+    port: u16,
+}
+
+impl NodeAddress {
+    pub fn new(addr: SocketAddr) -> Self {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => Vec::from_slice(&ip.octets()),
+            IpAddr::V6(ip) => Vec::from_slice(&ip.octets()),
+        };
+
+        Self {
+            ip: ip.unwrap(),
+            port: addr.port(),
+        }
+    }
+
+    pub fn addr(&self) -> Result<SocketAddr, Error> {
+        let ip = if let Ok(octets) = <[u8; 4]>::try_from(self.ip.as_slice()) {
+            IpAddr::V4(Ipv4Addr::from(octets))
+        } else if let Ok(octets) = <[u8; 16]>::try_from(self.ip.as_slice()) {
+            IpAddr::V6(Ipv6Addr::from(octets))
+        } else {
+            Err(ErrorCode::Invalid)?
+        };
+
+        Ok(SocketAddr::new(ip, self.port))
+    }
+}
+
+/// The resolved addresses of a paired node, the most recently resolved one first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct NodeAddresses([Option<NodeAddress>; MAX_NODE_ADDRESSES]);
+
+impl ToTLV for NodeAddresses {
+    fn to_tlv(&self, tw: &mut TLVWriter, tag: TagType) -> Result<(), Error> {
+        self.0.to_tlv(tw, tag)
+    }
+}
+
+impl<'a> FromTLV<'a> for NodeAddresses {
+    fn from_tlv(t: &TLVElement<'a>) -> Result<Self, Error> {
+        Ok(Self(FromTLV::from_tlv(t)?))
+    }
+}
+
+/// The Check-In registration of a paired Intermittently Connected Device
+#[derive(Debug, Clone, Default, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct IcdInfo {
+    pub check_in_key: Vec<u8, { crypto::SYMM_KEY_LEN_BYTES }>,
+    pub check_in_counter: u32,
+    pub monitored_subject: u64,
+}
+
+/// A summary of the device attestation performed during commissioning
+#[derive(Debug, Clone, Default, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct AttestationInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub verified: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+pub struct PairedNode {
+    pub node_id: u64,
+    pub fab_idx: u8,
+    addresses: NodeAddresses,
+    pub icd: Option<IcdInfo>,
+    pub attestation: AttestationInfo,
+}
+
+impl PairedNode {
+    pub fn new(fab_idx: u8, node_id: u64, attestation: AttestationInfo) -> Self {
+        Self {
+            node_id,
+            fab_idx,
+            addresses: Default::default(),
+            icd: None,
+            attestation,
+        }
+    }
+
+    /// Returns the resolved addresses of the node, the most recently resolved one first
+    pub fn addresses(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.addresses
+            .0
+            .iter()
+            .flatten()
+            .filter_map(|addr| addr.addr().ok())
+    }
+
+    /// Records a newly resolved address of the node, evicting the oldest one if necessary
+    pub fn add_address(&mut self, addr: SocketAddr) {
+        let addr = NodeAddress::new(addr);
+
+        let addresses = &mut self.addresses.0;
+
+        let position = addresses
+            .iter()
+            .position(|a| a.as_ref() == Some(&addr))
+            .unwrap_or(MAX_NODE_ADDRESSES - 1);
+
+        addresses[..=position].rotate_right(1);
+        addresses[0] = Some(addr);
+    }
+
+    pub fn clear_addresses(&mut self) {
+        self.addresses = Default::default();
+    }
+}
+
+type PairedNodeEntries = Vec<Option<PairedNode>, MAX_PAIRED_NODES>;
+
+pub struct PairedNodeMgr {
+    nodes: PairedNodeEntries,
+    changed: bool,
+}
+
+impl PairedNodeMgr {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            nodes: PairedNodeEntries::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        tlv::from_tlv(&mut self.nodes, &root)?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            self.nodes.as_slice().to_tlv(&mut tw, TagType::Anonymous)?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Adds a newly commissioned node to the table
+    pub fn add(&mut self, node: PairedNode) -> Result<(), Error> {
+        if self.get(node.fab_idx, node.node_id).is_some() {
+            Err(ErrorCode::Duplicate)?;
+        }
+
+        if let Some(slot) = self.nodes.iter_mut().find(|n| n.is_none()) {
+            *slot = Some(node);
+        } else {
+            self.nodes
+                .push(Some(node))
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        self.changed = true;
+
+        Ok(())
+    }
+
+    pub fn get(&self, fab_idx: u8, node_id: u64) -> Option<&PairedNode> {
+        self.nodes
+            .iter()
+            .flatten()
+            .find(|n| n.fab_idx == fab_idx && n.node_id == node_id)
+    }
+
+    /// Updates the entry of a paired node, e.g. with a newly resolved address or a new ICD registration
+    pub fn update<F, T>(&mut self, fab_idx: u8, node_id: u64, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut PairedNode) -> Result<T, Error>,
+    {
+        let node = self
+            .nodes
+            .iter_mut()
+            .flatten()
+            .find(|n| n.fab_idx == fab_idx && n.node_id == node_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        let result = f(node)?;
+
+        self.changed = true;
+
+        Ok(result)
+    }
+
+    pub fn remove(&mut self, fab_idx: u8, node_id: u64) -> Result<(), Error> {
+        let slot = self
+            .nodes
+            .iter_mut()
+            .find(|n| {
+                n.as_ref()
+                    .map(|n| n.fab_idx == fab_idx && n.node_id == node_id)
+                    .unwrap_or(false)
+            })
+            .ok_or(ErrorCode::NotFound)?;
+
+        *slot = None;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Removes all nodes paired on the given fabric, e.g. when the fabric itself is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        for slot in self.nodes.iter_mut() {
+            if slot.as_ref().map(|n| n.fab_idx == fab_idx).unwrap_or(false) {
+                *slot = None;
+                self.changed = true;
+            }
+        }
+    }
+
+    pub fn for_each<T>(&self, mut f: T) -> Result<(), Error>
+    where
+        T: FnMut(&PairedNode) -> Result<(), Error>,
+    {
+        for node in self.nodes.iter().flatten() {
+            f(node)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PairedNodeMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::network::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use super::{AttestationInfo, PairedNode, PairedNodeMgr};
+
+    fn node(fab_idx: u8, node_id: u64) -> PairedNode {
+        PairedNode::new(
+            fab_idx,
+            node_id,
+            AttestationInfo {
+                vendor_id: 0xFFF1,
+                product_id: 0x8000,
+                verified: true,
+            },
+        )
+    }
+
+    #[test]
+    fn test_crud() {
+        let mut mgr = PairedNodeMgr::new();
+
+        mgr.add(node(1, 100)).unwrap();
+        mgr.add(node(2, 100)).unwrap();
+        assert!(mgr.add(node(1, 100)).is_err());
+
+        assert!(mgr.get(1, 100).is_some());
+        assert!(mgr.get(1, 101).is_none());
+
+        let addr = SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540);
+        mgr.update(1, 100, |n| {
+            n.add_address(addr);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(mgr.get(1, 100).unwrap().addresses().next(), Some(addr));
+
+        mgr.remove(1, 100).unwrap();
+        assert!(mgr.get(1, 100).is_none());
+        assert!(mgr.remove(1, 100).is_err());
+
+        mgr.remove_fabric(2);
+        assert!(mgr.get(2, 100).is_none());
+    }
+
+    #[test]
+    fn test_addresses() {
+        let mut node = node(1, 100);
+
+        let addr1 = SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540);
+        let addr2 = SocketAddr::new(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into(), 5540);
+        let addr3 = SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 5540);
+
+        node.add_address(addr1);
+        node.add_address(addr2);
+        assert!(node.addresses().eq([addr2, addr1]));
+
+        node.add_address(addr1);
+        assert!(node.addresses().eq([addr1, addr2]));
+
+        node.add_address(addr3);
+        assert!(node.addresses().eq([addr3, addr1]));
+    }
+
+    #[test]
+    fn test_store_load() {
+        let mut mgr = PairedNodeMgr::new();
+        assert!(!mgr.is_changed());
+
+        let mut n = node(1, 100);
+        n.add_address(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540));
+        mgr.add(n.clone()).unwrap();
+        mgr.add(node(1, 101)).unwrap();
+        mgr.remove(1, 101).unwrap();
+        assert!(mgr.is_changed());
+
+        let mut buf = [0; 512];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+        assert!(!mgr.is_changed());
+
+        let mut loaded = PairedNodeMgr::new();
+        loaded.load(data).unwrap();
+
+        assert_eq!(loaded.get(1, 100), Some(&n));
+        assert!(loaded.get(1, 101).is_none());
+    }
+}
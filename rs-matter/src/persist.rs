@@ -51,6 +51,38 @@ pub mod fileio {
                 matter.load_fabrics(data)?;
             }
 
+            if let Some(data) = Self::load(&dir, "paired_nodes", &mut buf)? {
+                matter.load_paired_nodes(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "icd_clients", &mut buf)? {
+                matter.load_icd_clients(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "bindings", &mut buf)? {
+                matter.load_bindings(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "msg_counters", &mut buf)? {
+                matter.load_msg_counters(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "last_known_good_time", &mut buf)? {
+                matter.load_last_known_good_time(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "group_keys", &mut buf)? {
+                matter.load_group_keys(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "events", &mut buf)? {
+                matter.load_events(data)?;
+            }
+
+            if let Some(data) = Self::load(&dir, "nv_attributes", &mut buf)? {
+                matter.load_nv_attributes(data)?;
+            }
+
             Ok(Self { matter, dir, buf })
         }
 
@@ -66,6 +98,38 @@ pub mod fileio {
                     if let Some(data) = self.matter.store_fabrics(&mut self.buf)? {
                         Self::store(&self.dir, "fabrics", data)?;
                     }
+
+                    if let Some(data) = self.matter.store_paired_nodes(&mut self.buf)? {
+                        Self::store(&self.dir, "paired_nodes", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_icd_clients(&mut self.buf)? {
+                        Self::store(&self.dir, "icd_clients", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_bindings(&mut self.buf)? {
+                        Self::store(&self.dir, "bindings", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_msg_counters(&mut self.buf)? {
+                        Self::store(&self.dir, "msg_counters", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_last_known_good_time(&mut self.buf)? {
+                        Self::store(&self.dir, "last_known_good_time", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_group_keys(&mut self.buf)? {
+                        Self::store(&self.dir, "group_keys", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_events(&mut self.buf)? {
+                        Self::store(&self.dir, "events", data)?;
+                    }
+
+                    if let Some(data) = self.matter.store_nv_attributes(&mut self.buf)? {
+                        Self::store(&self.dir, "nv_attributes", data)?;
+                    }
                 }
             }
         }
@@ -15,26 +15,96 @@
  *    limitations under the License.
  */
 
-use log::{error, trace};
+use log::{error, info, trace, warn};
 
 use crate::{
     alloc,
     cert::Cert,
-    crypto::{self, KeyPair, Sha256},
+    config::MAX_CASE_RESUMPTIONS,
+    crypto::{self, keystore::OpKeyStore, KeyPair, Sha256},
     error::{Error, ErrorCode},
     fabric::Fabric,
+    last_known_good_time::LastKnownGoodTime,
     secure_channel::common::{self, OpCode, PROTO_ID_SECURE_CHANNEL},
     secure_channel::common::{complete_with_status, SCStatusCodes},
-    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType},
+    secure_channel::status_report::StatusReport,
+    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType, ToTLV},
     transport::{
         exchange::Exchange,
         network::Address,
         packet::Packet,
-        session::{CaseDetails, CloneData, NocCatIds, SessionMode},
+        session::{CaseDetails, CloneData, NocCatIds, SessionMode, SessionParams},
     },
-    utils::{rand::Rand, writebuf::WriteBuf},
+    utils::writebuf::WriteBuf,
 };
 
+const RESUMPTION_ID_LEN: usize = 16;
+const SIGMA_RANDOM_LEN: usize = 32;
+
+const S1RK_INFO: &[u8] = b"Sigma1_Resume";
+const S2RK_INFO: &[u8] = b"Sigma2_Resume";
+const SEKEYS_RESUMPTION_INFO: &[u8] = b"SessionResumptionKeys";
+
+const RESUME1_MIC_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_SigmaS1";
+const RESUME2_MIC_NONCE: [u8; crypto::AEAD_NONCE_LEN_BYTES] = *b"NCASE_SigmaS2";
+
+/// What is kept from an established CASE session so that the peer can resume it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResumptionRecord {
+    resumption_id: [u8; RESUMPTION_ID_LEN],
+    shared_secret: [u8; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
+    fab_idx: u8,
+    peer_nodeid: u64,
+    peer_catids: NocCatIds,
+}
+
+/// The resumption records of the most recently established CASE sessions, at most one per peer.
+///
+/// The records are only kept in memory, so a reboot results in full CASE handshakes.
+pub struct CaseResumptionStore {
+    records: heapless::Vec<ResumptionRecord, MAX_CASE_RESUMPTIONS>,
+}
+
+impl CaseResumptionStore {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            records: heapless::Vec::new(),
+        }
+    }
+
+    fn get(&self, resumption_id: &[u8]) -> Option<&ResumptionRecord> {
+        self.records
+            .iter()
+            .find(|record| record.resumption_id == resumption_id)
+    }
+
+    /// Adds a record, replacing the one of the same peer, if any.
+    /// The oldest record is forgotten, if there is no room for a new one.
+    fn add(&mut self, record: ResumptionRecord) {
+        self.records.retain(|existing| {
+            existing.fab_idx != record.fab_idx || existing.peer_nodeid != record.peer_nodeid
+        });
+
+        if self.records.is_full() {
+            self.records.remove(0);
+        }
+
+        let _ = self.records.push(record);
+    }
+
+    /// Removes the records of all peers on the given fabric, e.g. when the fabric is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        self.records.retain(|record| record.fab_idx != fab_idx);
+    }
+}
+
+impl Default for CaseResumptionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 struct CaseSession {
     peer_sessid: u16,
@@ -43,7 +113,9 @@ struct CaseSession {
     shared_secret: [u8; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
     our_pub_key: [u8; crypto::EC_POINT_LEN_BYTES],
     peer_pub_key: [u8; crypto::EC_POINT_LEN_BYTES],
+    peer_session_params: SessionParams,
     local_fabric_idx: usize,
+    resumption_id: [u8; RESUMPTION_ID_LEN],
 }
 
 impl CaseSession {
@@ -56,7 +128,9 @@ impl CaseSession {
             shared_secret: [0; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
             our_pub_key: [0; crypto::EC_POINT_LEN_BYTES],
             peer_pub_key: [0; crypto::EC_POINT_LEN_BYTES],
+            peer_session_params: SessionParams::new(),
             local_fabric_idx: 0,
+            resumption_id: [0; RESUMPTION_ID_LEN],
         })
     }
 }
@@ -75,6 +149,20 @@ impl Case {
         rx: &mut Packet<'_>,
         tx: &mut Packet<'_>,
     ) -> Result<(), Error> {
+        if let Some(wait) = exchange.matter.session_busy_wait_time(rx) {
+            warn!("No room for a new session, sending Busy");
+
+            // The minimum wait time, in ms
+            let wait = (wait.as_millis() as u16).to_le_bytes();
+            complete_with_status(exchange, tx, SCStatusCodes::Busy, Some(&wait)).await?;
+
+            return Ok(());
+        }
+
+        if self.handle_casesigma1_resume(exchange, rx, tx).await? {
+            return Ok(());
+        }
+
         let mut session = alloc!(CaseSession::new()?);
 
         self.handle_casesigma1(exchange, rx, tx, &mut session)
@@ -92,7 +180,7 @@ impl Case {
         rx.check_proto_opcode(OpCode::CASESigma3 as _)?;
 
         let result = {
-            let fabric_mgr = exchange.matter.fabric_mgr.borrow();
+            let fabric_mgr = exchange.matter.fabric_mgr().borrow();
 
             let fabric = fabric_mgr.get_fabric(case_session.local_fabric_idx)?;
             if let Some(fabric) = fabric {
@@ -126,7 +214,16 @@ impl Case {
                 #[cfg(not(feature = "alloc"))]
                 let initiator_icac_mut = initiator_icac.as_ref();
 
-                if let Err(e) = Case::validate_certs(fabric, &initiator_noc, initiator_icac_mut) {
+                let mut peer_catids: NocCatIds = Default::default();
+
+                let chain = Case::validate_certs(
+                    fabric,
+                    &initiator_noc,
+                    initiator_icac_mut,
+                    &exchange.matter.last_known_good_time.borrow(),
+                );
+
+                if let Err(e) = chain {
                     error!("Certificate Chain doesn't match: {}", e);
                     Err(SCStatusCodes::InvalidParameter)
                 } else if let Err(e) = Case::validate_sigma3_sign(
@@ -138,20 +235,46 @@ impl Case {
                 ) {
                     error!("Sigma3 Signature doesn't match: {}", e);
                     Err(SCStatusCodes::InvalidParameter)
+                } else if let Err(e) = initiator_noc.get_cat_ids(&mut peer_catids) {
+                    error!("Invalid CASE Authenticated Tags in the NOC: {}", e);
+                    Err(SCStatusCodes::InvalidParameter)
                 } else {
                     // Only now do we add this message to the TT Hash
-                    let mut peer_catids: NocCatIds = Default::default();
-                    initiator_noc.get_cat_ids(&mut peer_catids);
                     case_session.tt_hash.update(rx.as_slice())?;
 
-                    Ok(Case::get_session_clone_data(
+                    // The current time cannot be earlier than the NotBefore of the validated chain
+                    let mut certs = heapless::Vec::<&Cert, 2>::new();
+                    certs.push(&initiator_noc).map_err(|_| ErrorCode::NoSpace)?;
+                    if let Some(icac) = initiator_icac_mut {
+                        certs.push(icac).map_err(|_| ErrorCode::NoSpace)?;
+                    }
+                    exchange
+                        .matter
+                        .last_known_good_time
+                        .borrow_mut()
+                        .update_from_certs(&certs);
+
+                    let peer_nodeid = initiator_noc.get_node_id()?;
+
+                    let clone_data = Case::get_session_clone_data(
                         fabric.ipk.op_key(),
                         fabric.get_node_id(),
-                        initiator_noc.get_node_id()?,
+                        peer_nodeid,
                         exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
                         case_session,
                         &peer_catids,
-                    )?)
+                    )?;
+
+                    Ok((
+                        clone_data,
+                        ResumptionRecord {
+                            resumption_id: case_session.resumption_id,
+                            shared_secret: case_session.shared_secret,
+                            fab_idx: case_session.local_fabric_idx as u8,
+                            peer_nodeid,
+                            peer_catids,
+                        },
+                    ))
                 }
             } else {
                 Err(SCStatusCodes::NoSharedTrustRoots)
@@ -159,8 +282,9 @@ impl Case {
         };
 
         let status = match result {
-            Ok(clone_data) => {
+            Ok((clone_data, record)) => {
                 exchange.clone_session(tx, &clone_data).await?;
+                exchange.matter.case_resumptions.borrow_mut().add(record);
                 SCStatusCodes::SessionEstablishmentSuccess
             }
             Err(status) => status,
@@ -169,6 +293,147 @@ impl Case {
         complete_with_status(exchange, tx, status, None).await
     }
 
+    /// Resumes a previous CASE session, if the Sigma1 of the peer asks for it and the
+    /// resumption record of that session is still known.
+    ///
+    /// Returns `false` if the full CASE handshake should be done instead.
+    async fn handle_casesigma1_resume(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+    ) -> Result<bool, Error> {
+        rx.check_proto_opcode(OpCode::CASESigma1 as _)?;
+
+        let (mut record, mut clone_data, local_sessid, initiator_random) = {
+            let root = get_root_node_struct(rx.as_slice())?;
+            let r = Sigma1Req::from_tlv(&root)?;
+
+            let (Some(resumption_id), Some(resume_mic)) = (r.resumption_id, r.initiator_resume_mic)
+            else {
+                return Ok(false);
+            };
+
+            let record = exchange
+                .matter
+                .case_resumptions
+                .borrow()
+                .get(resumption_id.0)
+                .cloned();
+            let Some(record) = record else {
+                info!("Unknown resumption ID, doing a full CASE handshake");
+                return Ok(false);
+            };
+
+            if r.initiator_random.0.len() != SIGMA_RANDOM_LEN {
+                error!("Invalid initiator random length");
+                Err(ErrorCode::Invalid)?;
+            }
+
+            let mut initiator_random = [0; SIGMA_RANDOM_LEN];
+            initiator_random.copy_from_slice(r.initiator_random.0);
+
+            let mut mic = [0; crypto::AEAD_MIC_LEN_BYTES];
+            Case::get_resume_mic(
+                &record.shared_secret,
+                &initiator_random,
+                &record.resumption_id,
+                S1RK_INFO,
+                &RESUME1_MIC_NONCE,
+                &mut mic,
+            )?;
+
+            if mic != resume_mic.0 {
+                warn!("Sigma1 resume MIC doesn't match, doing a full CASE handshake");
+                return Ok(false);
+            }
+
+            let local_nodeid = {
+                let fabric_mgr = exchange.matter.fabric_mgr().borrow();
+
+                let Some(fabric) = fabric_mgr.get_fabric(record.fab_idx as _)? else {
+                    info!("Fabric of the resumption record is gone, doing a full CASE handshake");
+                    return Ok(false);
+                };
+
+                fabric.get_node_id()
+            };
+
+            let mut peer_session_params = SessionParams::new();
+            if let Some(session_params) = r.initiator_session_params {
+                peer_session_params = session_params;
+
+                exchange.with_session_mut(|sess| {
+                    sess.set_session_params(session_params);
+                    Ok(())
+                })?;
+            }
+
+            let local_sessid = exchange.get_next_sess_id();
+
+            let mut clone_data = CloneData::new(
+                local_nodeid,
+                record.peer_nodeid,
+                r.initiator_sessid,
+                local_sessid,
+                exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
+                SessionMode::Case(CaseDetails::new(record.fab_idx, &record.peer_catids)),
+            );
+            clone_data.session_params = peer_session_params;
+
+            (record, clone_data, local_sessid, initiator_random)
+        };
+
+        // A new resumption ID for the next time, as the current one is now used up
+        (exchange.matter.rand)(&mut record.resumption_id);
+
+        let mut mic = [0; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &record.shared_secret,
+            &initiator_random,
+            &record.resumption_id,
+            S2RK_INFO,
+            &RESUME2_MIC_NONCE,
+            &mut mic,
+        )?;
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::CASESigma2Resume as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), &record.resumption_id)?;
+        tw.str8(TagType::Context(2), &mic)?;
+        tw.u16(TagType::Context(3), local_sessid)?;
+        SessionParams::local().to_tlv(&mut tw, TagType::Context(4))?;
+        tw.end_container()?;
+
+        exchange.exchange(tx, rx).await?;
+
+        let status = StatusReport::from_packet(rx)?;
+        if !status.is_session_establishment_success() {
+            error!("CASE session resumption rejected by the peer: {:?}", status);
+            exchange.acknowledge().await?;
+
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Case::get_resumption_session_keys(
+            &record.shared_secret,
+            &initiator_random,
+            &record.resumption_id,
+            &mut clone_data,
+        )?;
+
+        exchange.clone_session(tx, &clone_data).await?;
+        exchange.matter.case_resumptions.borrow_mut().add(record);
+
+        exchange.acknowledge().await?;
+
+        Ok(true)
+    }
+
     async fn handle_casesigma1(
         &mut self,
         exchange: &mut Exchange<'_>,
@@ -184,7 +449,7 @@ impl Case {
 
         let local_fabric_idx = exchange
             .matter
-            .fabric_mgr
+            .fabric_mgr()
             .borrow_mut()
             .match_dest_id(r.initiator_random.0, r.dest_id.0);
         if local_fabric_idx.is_err() {
@@ -210,6 +475,17 @@ impl Case {
             Err(ErrorCode::Invalid)?;
         }
         case_session.peer_pub_key.copy_from_slice(r.peer_pub_key.0);
+
+        if let Some(session_params) = r.initiator_session_params {
+            case_session.peer_session_params = session_params;
+
+            // Retransmit to the peer as per its parameters right away, also during the handshake
+            exchange.with_session_mut(|sess| {
+                sess.set_session_params(session_params);
+                Ok(())
+            })?;
+        }
+
         trace!(
             "Destination ID matched to fabric index {}",
             case_session.local_fabric_idx
@@ -227,9 +503,12 @@ impl Case {
         }
         //        println!("Derived secret: {:x?} len: {}", secret, len);
 
-        let mut our_random: [u8; 32] = [0; 32];
+        let mut our_random: [u8; SIGMA_RANDOM_LEN] = [0; SIGMA_RANDOM_LEN];
         (exchange.matter.rand)(&mut our_random);
 
+        // Allows the peer to resume the session later on, if it gets established
+        (exchange.matter.rand)(&mut case_session.resumption_id);
+
         // Derive the Encrypted Part
         const MAX_ENCRYPTED_SIZE: usize = 800;
 
@@ -237,7 +516,7 @@ impl Case {
         let mut signature = alloc!([0u8; crypto::EC_SIGNATURE_LEN_BYTES]);
 
         let fabric_found = {
-            let fabric_mgr = exchange.matter.fabric_mgr.borrow();
+            let fabric_mgr = exchange.matter.fabric_mgr().borrow();
 
             let fabric = fabric_mgr.get_fabric(case_session.local_fabric_idx)?;
             if let Some(fabric) = fabric {
@@ -249,6 +528,7 @@ impl Case {
 
                 let sign_len = Case::get_sigma2_sign(
                     fabric,
+                    exchange.matter.op_keystore(),
                     &case_session.our_pub_key,
                     &case_session.peer_pub_key,
                     signature_mut,
@@ -263,7 +543,6 @@ impl Case {
 
                 let encrypted_len = Case::get_sigma2_encryption(
                     fabric,
-                    exchange.matter.rand,
                     &our_random,
                     case_session,
                     signature,
@@ -283,6 +562,7 @@ impl Case {
                 tw.u16(TagType::Context(2), local_sessid)?;
                 tw.str8(TagType::Context(3), &case_session.our_pub_key)?;
                 tw.str16(TagType::Context(4), encrypted)?;
+                SessionParams::local().to_tlv(&mut tw, TagType::Context(5))?;
                 tw.end_container()?;
 
                 case_session.tt_hash.update(tx.as_mut_slice())?;
@@ -334,6 +614,7 @@ impl Case {
             )),
         );
 
+        clone_data.session_params = case_session.peer_session_params;
         clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
         clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
         clone_data
@@ -367,7 +648,14 @@ impl Case {
         Ok(())
     }
 
-    fn validate_certs(fabric: &Fabric, noc: &Cert, icac: Option<&Cert>) -> Result<(), Error> {
+    /// Validates the NOC chain of a peer against the root of `fabric`.
+    /// None of the certificates may have expired before the Last Known Good UTC Time.
+    pub(crate) fn validate_certs(
+        fabric: &Fabric,
+        noc: &Cert,
+        icac: Option<&Cert>,
+        lkgt: &LastKnownGoodTime,
+    ) -> Result<(), Error> {
         let mut verifier = noc.verify_chain_start();
 
         if fabric.get_fabric_id() != noc.get_fabric_id()? {
@@ -384,9 +672,14 @@ impl Case {
             verifier = verifier.add_cert(icac)?;
         }
 
-        verifier
-            .add_cert(&Cert::new(&fabric.root_ca)?)?
-            .finalise()?;
+        let root = Cert::new(&fabric.root_ca)?;
+
+        lkgt.validate(&[noc, &root])?;
+        if let Some(icac) = icac {
+            lkgt.validate(&[icac])?;
+        }
+
+        verifier.add_cert(&root)?.finalise()?;
         Ok(())
     }
 
@@ -403,11 +696,13 @@ impl Case {
             Err(ErrorCode::NoSpace)?;
         }
         let mut salt = heapless::Vec::<u8, 256>::new();
-        salt.extend_from_slice(ipk).unwrap();
+        salt.extend_from_slice(ipk)
+            .map_err(|_| ErrorCode::NoSpace)?;
         let tt = tt.clone();
         let mut tt_hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
         tt.finish(&mut tt_hash)?;
-        salt.extend_from_slice(&tt_hash).unwrap();
+        salt.extend_from_slice(&tt_hash)
+            .map_err(|_| ErrorCode::NoSpace)?;
         //        println!("Session Key: salt: {:x?}, len: {}", salt, salt.len());
 
         crypto::hkdf_sha256(salt.as_slice(), shared_secret, &SEKEYS_INFO, key)
@@ -417,6 +712,75 @@ impl Case {
         Ok(())
     }
 
+    fn get_resume_key(
+        shared_secret: &[u8],
+        initiator_random: &[u8],
+        resumption_id: &[u8],
+        info: &[u8],
+        key: &mut [u8],
+    ) -> Result<(), Error> {
+        let mut salt = heapless::Vec::<u8, { SIGMA_RANDOM_LEN + RESUMPTION_ID_LEN }>::new();
+        salt.extend_from_slice(initiator_random)
+            .map_err(|_| ErrorCode::NoSpace)?;
+        salt.extend_from_slice(resumption_id)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        crypto::hkdf_sha256(salt.as_slice(), shared_secret, info, key)
+            .map_err(|_x| ErrorCode::NoSpace)?;
+
+        Ok(())
+    }
+
+    /// The MIC proving the knowledge of the shared secret of the resumed session,
+    /// i.e. the tag of an empty message encrypted with the resumption key
+    fn get_resume_mic(
+        shared_secret: &[u8],
+        initiator_random: &[u8],
+        resumption_id: &[u8],
+        info: &[u8],
+        nonce: &[u8; crypto::AEAD_NONCE_LEN_BYTES],
+        mic: &mut [u8; crypto::AEAD_MIC_LEN_BYTES],
+    ) -> Result<(), Error> {
+        let mut key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_resume_key(
+            shared_secret,
+            initiator_random,
+            resumption_id,
+            info,
+            &mut key,
+        )?;
+
+        let mut tag = [0_u8; crypto::AEAD_MIC_LEN_BYTES];
+        crypto::encrypt_in_place(&key, nonce, &[], &mut tag, 0)?;
+        mic.copy_from_slice(&tag);
+
+        Ok(())
+    }
+
+    fn get_resumption_session_keys(
+        shared_secret: &[u8],
+        initiator_random: &[u8],
+        resumption_id: &[u8],
+        clone_data: &mut CloneData,
+    ) -> Result<(), Error> {
+        let mut session_keys = [0_u8; 3 * crypto::SYMM_KEY_LEN_BYTES];
+        Case::get_resume_key(
+            shared_secret,
+            initiator_random,
+            resumption_id,
+            SEKEYS_RESUMPTION_INFO,
+            &mut session_keys,
+        )?;
+
+        clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
+        clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
+        clone_data
+            .att_challenge
+            .copy_from_slice(&session_keys[32..48]);
+
+        Ok(())
+    }
+
     fn get_sigma3_decryption(
         ipk: &[u8],
         case_session: &CaseSession,
@@ -451,13 +815,15 @@ impl Case {
             Err(ErrorCode::NoSpace)?;
         }
         let mut salt = heapless::Vec::<u8, 256>::new();
-        salt.extend_from_slice(ipk).unwrap();
+        salt.extend_from_slice(ipk)
+            .map_err(|_| ErrorCode::NoSpace)?;
 
         let tt = tt.clone();
 
         let mut tt_hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
         tt.finish(&mut tt_hash)?;
-        salt.extend_from_slice(&tt_hash).unwrap();
+        salt.extend_from_slice(&tt_hash)
+            .map_err(|_| ErrorCode::NoSpace)?;
         //        println!("Sigma3Key: salt: {:x?}, len: {}", salt, salt.len());
 
         crypto::hkdf_sha256(salt.as_slice(), shared_secret, &S3K_INFO, key)
@@ -478,15 +844,19 @@ impl Case {
             Err(ErrorCode::NoSpace)?;
         }
         let mut salt = heapless::Vec::<u8, 256>::new();
-        salt.extend_from_slice(ipk).unwrap();
-        salt.extend_from_slice(our_random).unwrap();
-        salt.extend_from_slice(&case_session.our_pub_key).unwrap();
+        salt.extend_from_slice(ipk)
+            .map_err(|_| ErrorCode::NoSpace)?;
+        salt.extend_from_slice(our_random)
+            .map_err(|_| ErrorCode::NoSpace)?;
+        salt.extend_from_slice(&case_session.our_pub_key)
+            .map_err(|_| ErrorCode::NoSpace)?;
 
         let tt = case_session.tt_hash.clone();
 
         let mut tt_hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
         tt.finish(&mut tt_hash)?;
-        salt.extend_from_slice(&tt_hash).unwrap();
+        salt.extend_from_slice(&tt_hash)
+            .map_err(|_| ErrorCode::NoSpace)?;
         //        println!("Sigma2Key: salt: {:x?}, len: {}", salt, salt.len());
 
         crypto::hkdf_sha256(salt.as_slice(), &case_session.shared_secret, &S2K_INFO, key)
@@ -498,15 +868,11 @@ impl Case {
 
     fn get_sigma2_encryption(
         fabric: &Fabric,
-        rand: Rand,
         our_random: &[u8],
         case_session: &CaseSession,
         signature: &[u8],
         out: &mut [u8],
     ) -> Result<usize, Error> {
-        let mut resumption_id: [u8; 16] = [0; 16];
-        rand(&mut resumption_id);
-
         let mut sigma2_key = [0_u8; crypto::SYMM_KEY_LEN_BYTES];
         Case::get_sigma2_key(
             fabric.ipk.op_key(),
@@ -524,7 +890,7 @@ impl Case {
         };
 
         tw.str8(TagType::Context(3), signature)?;
-        tw.str8(TagType::Context(4), &resumption_id)?;
+        tw.str8(TagType::Context(4), &case_session.resumption_id)?;
         tw.end_container()?;
         //println!("TBE is {:x?}", write_buf.as_borrow_slice());
         let nonce: [u8; crypto::AEAD_NONCE_LEN_BYTES] = [
@@ -550,6 +916,7 @@ impl Case {
 
     fn get_sigma2_sign(
         fabric: &Fabric,
+        keystore: Option<&dyn OpKeyStore>,
         our_pub_key: &[u8],
         peer_pub_key: &[u8],
         signature: &mut [u8],
@@ -568,7 +935,7 @@ impl Case {
         tw.str8(TagType::Context(4), peer_pub_key)?;
         tw.end_container()?;
         //println!("TBS is {:x?}", write_buf.as_borrow_slice());
-        fabric.sign_msg(write_buf.as_slice(), signature)
+        fabric.sign_msg(keystore, write_buf.as_slice(), signature)
     }
 }
 
@@ -579,6 +946,9 @@ struct Sigma1Req<'a> {
     initiator_sessid: u16,
     dest_id: OctetStr<'a>,
     peer_pub_key: OctetStr<'a>,
+    initiator_session_params: Option<SessionParams>,
+    resumption_id: Option<OctetStr<'a>>,
+    initiator_resume_mic: Option<OctetStr<'a>>,
 }
 
 #[derive(FromTLV)]
@@ -588,3 +958,98 @@ struct Sigma3Decrypt<'a> {
     initiator_icac: Option<OctetStr<'a>>,
     signature: OctetStr<'a>,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto;
+
+    use super::{
+        Case, CaseResumptionStore, ResumptionRecord, MAX_CASE_RESUMPTIONS, RESUME1_MIC_NONCE,
+        S1RK_INFO, S2RK_INFO,
+    };
+
+    fn record(id: u8, fab_idx: u8, peer_nodeid: u64) -> ResumptionRecord {
+        ResumptionRecord {
+            resumption_id: [id; 16],
+            shared_secret: [id; crypto::ECDH_SHARED_SECRET_LEN_BYTES],
+            fab_idx,
+            peer_nodeid,
+            peer_catids: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_resumption_store() {
+        let mut store = CaseResumptionStore::new();
+
+        store.add(record(1, 1, 100));
+        assert_eq!(store.get(&[1; 16]), Some(&record(1, 1, 100)));
+
+        // A new session with the same peer replaces its previous record
+        store.add(record(2, 1, 100));
+        assert!(store.get(&[1; 16]).is_none());
+        assert!(store.get(&[2; 16]).is_some());
+
+        // The oldest record is forgotten when the store is full
+        for i in 0..MAX_CASE_RESUMPTIONS as u8 {
+            store.add(record(10 + i, 2, i as _));
+        }
+        assert!(store.get(&[2; 16]).is_none());
+        assert!(store.get(&[10; 16]).is_some());
+
+        store.remove_fabric(2);
+        assert!(store.get(&[10; 16]).is_none());
+    }
+
+    #[test]
+    fn test_resume_mic() {
+        let secret = [7; crypto::ECDH_SHARED_SECRET_LEN_BYTES];
+        let random = [3; 32];
+
+        let mut mic = [0; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &secret,
+            &random,
+            &[1; 16],
+            S1RK_INFO,
+            &RESUME1_MIC_NONCE,
+            &mut mic,
+        )
+        .unwrap();
+
+        let mut other = [0; crypto::AEAD_MIC_LEN_BYTES];
+        Case::get_resume_mic(
+            &secret,
+            &random,
+            &[1; 16],
+            S1RK_INFO,
+            &RESUME1_MIC_NONCE,
+            &mut other,
+        )
+        .unwrap();
+        assert_eq!(mic, other);
+
+        // Bound to the resumption ID, and to the direction
+        Case::get_resume_mic(
+            &secret,
+            &random,
+            &[2; 16],
+            S1RK_INFO,
+            &RESUME1_MIC_NONCE,
+            &mut other,
+        )
+        .unwrap();
+        assert_ne!(mic, other);
+
+        Case::get_resume_mic(
+            &secret,
+            &random,
+            &[1; 16],
+            S2RK_INFO,
+            &RESUME1_MIC_NONCE,
+            &mut other,
+        )
+        .unwrap();
+        assert_ne!(mic, other);
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The ICD Check-In protocol.
+//!
+//! An Intermittently Connected Device (ICD) notifies its registered clients that it is
+//! available, by sending each of them an unsecured Check-In message, encrypted with the
+//! symmetric key the client registered with the ICD Management cluster.
+//!
+//! The payload of the message is `Nonce || Ciphertext || MIC`, where:
+//! - `Nonce` is the first 13 bytes of `HMAC-SHA256(key, CheckInCounter)`;
+//! - `Ciphertext` is the AES-CCM encryption of `CheckInCounter || ActiveModeThreshold`,
+//!   both little-endian.
+
+use core::time::Duration;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    crypto::{self, HmacSha256},
+    error::{Error, ErrorCode},
+    transport::packet::Packet,
+};
+
+use super::common::{OpCode, PROTO_ID_SECURE_CHANNEL};
+
+/// The length of the symmetric keys registered by the ICD clients
+pub const CHECK_IN_KEY_LEN: usize = crypto::SYMM_KEY_LEN_BYTES;
+
+const COUNTER_LEN: usize = 4;
+const ACTIVE_MODE_THRESHOLD_LEN: usize = 2;
+const PLAINTEXT_LEN: usize = COUNTER_LEN + ACTIVE_MODE_THRESHOLD_LEN;
+
+/// The length of the payload of the Check-In messages sent by this node
+pub const CHECK_IN_PAYLOAD_LEN: usize =
+    crypto::AEAD_NONCE_LEN_BYTES + PLAINTEXT_LEN + crypto::AEAD_MIC_LEN_BYTES;
+
+/// The content of a Check-In message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CheckIn {
+    /// The Check-In counter of the ICD
+    pub counter: u32,
+    /// How long the ICD stays in active mode after its last network activity, in milliseconds
+    pub active_mode_threshold: u16,
+}
+
+impl CheckIn {
+    /// Encrypts the Check-In message with `key`, and returns the length of the payload
+    /// written to `buf`
+    pub fn encode(&self, key: &[u8], buf: &mut [u8]) -> Result<usize, Error> {
+        if key.len() != CHECK_IN_KEY_LEN {
+            Err(ErrorCode::InvalidKeyLength)?;
+        }
+
+        let buf = buf
+            .get_mut(..CHECK_IN_PAYLOAD_LEN)
+            .ok_or(ErrorCode::NoSpace)?;
+
+        let (nonce, data) = buf.split_at_mut(crypto::AEAD_NONCE_LEN_BYTES);
+        Self::nonce(key, self.counter, nonce)?;
+
+        LittleEndian::write_u32(&mut data[..COUNTER_LEN], self.counter);
+        LittleEndian::write_u16(
+            &mut data[COUNTER_LEN..PLAINTEXT_LEN],
+            self.active_mode_threshold,
+        );
+
+        crypto::encrypt_in_place(key, nonce, &[], data, PLAINTEXT_LEN)?;
+
+        Ok(CHECK_IN_PAYLOAD_LEN)
+    }
+
+    /// Decrypts a Check-In message payload with `key`, as done by the ICD clients
+    pub fn decode(key: &[u8], payload: &[u8]) -> Result<Self, Error> {
+        if key.len() != CHECK_IN_KEY_LEN {
+            Err(ErrorCode::InvalidKeyLength)?;
+        }
+
+        // Any application data beyond the ActiveModeThreshold is ignored
+        if payload.len() < CHECK_IN_PAYLOAD_LEN {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let mut buf = [0; CHECK_IN_PAYLOAD_LEN];
+        buf.copy_from_slice(&payload[..CHECK_IN_PAYLOAD_LEN]);
+
+        let (nonce, data) = buf.split_at_mut(crypto::AEAD_NONCE_LEN_BYTES);
+        crypto::decrypt_in_place(key, nonce, &[], data)?;
+
+        let check_in = Self {
+            counter: LittleEndian::read_u32(&data[..COUNTER_LEN]),
+            active_mode_threshold: LittleEndian::read_u16(&data[COUNTER_LEN..PLAINTEXT_LEN]),
+        };
+
+        // The nonce is bound to the counter
+        let mut expected = [0; crypto::AEAD_NONCE_LEN_BYTES];
+        Self::nonce(key, check_in.counter, &mut expected)?;
+        if nonce[..] != expected[..] {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(check_in)
+    }
+
+    /// Prepares `tx` as a Check-In message, encrypted with `key`
+    pub fn prep_tx(&self, key: &[u8], tx: &mut Packet) -> Result<(), Error> {
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::ICDCheckIn as u8);
+
+        let mut payload = [0; CHECK_IN_PAYLOAD_LEN];
+        let len = self.encode(key, &mut payload)?;
+
+        tx.get_writebuf()?.append(&payload[..len])
+    }
+
+    fn nonce(key: &[u8], counter: u32, nonce: &mut [u8]) -> Result<(), Error> {
+        let mut counter_le = [0; COUNTER_LEN];
+        LittleEndian::write_u32(&mut counter_le, counter);
+
+        let mut mac = HmacSha256::new(key)?;
+        mac.update(&counter_le)?;
+
+        let mut hash = [0; crypto::SHA256_HASH_LEN_BYTES];
+        mac.finish(&mut hash)?;
+
+        nonce.copy_from_slice(&hash[..crypto::AEAD_NONCE_LEN_BYTES]);
+
+        Ok(())
+    }
+}
+
+/// Tracks when the next round of Check-In messages is due.
+///
+/// An ICD checks in with its clients when it boots, and then whenever it has been idle for
+/// `interval` - typically its idle mode duration - without any of its clients reaching out.
+#[derive(Debug, Clone)]
+pub struct CheckInSchedule {
+    interval: Duration,
+    last: Option<Duration>,
+}
+
+impl CheckInSchedule {
+    pub const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// When the next Check-In messages are due; `None` means right away
+    pub fn deadline(&self) -> Option<Duration> {
+        self.last.map(|last| last + self.interval)
+    }
+
+    pub fn is_due(&self, now: Duration) -> bool {
+        self.deadline()
+            .map(|deadline| now >= deadline)
+            .unwrap_or(true)
+    }
+
+    /// Records that the Check-In messages were sent, or that the clients were otherwise
+    /// in touch with the ICD (e.g. over a subscription), which postpones the next round
+    pub fn checked_in(&mut self, now: Duration) {
+        self.last = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{CheckIn, CheckInSchedule, CHECK_IN_PAYLOAD_LEN};
+
+    const KEY: [u8; 16] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+        0x10,
+    ];
+
+    #[test]
+    fn test_check_in_roundtrip() {
+        let check_in = CheckIn {
+            counter: 0x12345678,
+            active_mode_threshold: 300,
+        };
+
+        let mut buf = [0; 64];
+        let len = check_in.encode(&KEY, &mut buf).unwrap();
+        assert_eq!(len, CHECK_IN_PAYLOAD_LEN);
+
+        assert_eq!(CheckIn::decode(&KEY, &buf[..len]).unwrap(), check_in);
+
+        // A different counter yields a different nonce
+        let mut other = [0; 64];
+        CheckIn {
+            counter: 0x12345679,
+            ..check_in
+        }
+        .encode(&KEY, &mut other)
+        .unwrap();
+        assert_ne!(buf[..13], other[..13]);
+
+        // Wrong key
+        let mut key = KEY;
+        key[0] ^= 0xff;
+        assert!(CheckIn::decode(&key, &buf[..len]).is_err());
+
+        // Tampered payload
+        buf[20] ^= 0xff;
+        assert!(CheckIn::decode(&KEY, &buf[..len]).is_err());
+
+        // Too short
+        assert!(CheckIn::decode(&KEY, &buf[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn test_check_in_schedule() {
+        let mut schedule = CheckInSchedule::new(Duration::from_secs(60));
+
+        // Due right away on boot
+        assert!(schedule.is_due(Duration::ZERO));
+
+        schedule.checked_in(Duration::from_secs(10));
+        assert_eq!(schedule.deadline(), Some(Duration::from_secs(70)));
+        assert!(!schedule.is_due(Duration::from_secs(69)));
+        assert!(schedule.is_due(Duration::from_secs(70)));
+    }
+}
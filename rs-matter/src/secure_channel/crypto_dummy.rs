@@ -58,11 +58,25 @@ impl CryptoSpake2 {
         Err(ErrorCode::Invalid.into())
     }
 
+    pub fn get_w0(&mut self, _w0: &mut [u8]) -> Result<(), Error> {
+        Err(ErrorCode::Invalid.into())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_L(&mut self, _l: &mut [u8]) -> Result<(), Error> {
+        Err(ErrorCode::Invalid.into())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_pB(&mut self, _pB: &mut [u8], _rand: Rand) -> Result<(), Error> {
         Err(ErrorCode::Invalid.into())
     }
 
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, _pA: &mut [u8], _rand: Rand) -> Result<(), Error> {
+        Err(ErrorCode::Invalid.into())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_TT_as_verifier(
         &mut self,
@@ -73,4 +87,15 @@ impl CryptoSpake2 {
     ) -> Result<(), Error> {
         Err(ErrorCode::Invalid.into())
     }
+
+    #[allow(non_snake_case)]
+    pub fn get_TT_as_prover(
+        &mut self,
+        _context: &[u8],
+        _pA: &[u8],
+        _pB: &[u8],
+        _out: &mut [u8],
+    ) -> Result<(), Error> {
+        Err(ErrorCode::Invalid.into())
+    }
 }
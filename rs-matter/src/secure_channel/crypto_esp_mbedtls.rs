@@ -86,6 +86,15 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    pub fn get_w0(&mut self, w0: &mut [u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_L(&mut self, l: &mut [u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_pB(&mut self, pB: &mut [u8], _rand: Rand) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
@@ -97,6 +106,17 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, pA: &mut [u8], _rand: Rand) -> Result<(), Error> {
+        // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
+        //   for x
+        //   - select random x between 0 to p
+        //   - X = x*P + w0*M
+        //   - pA = X
+
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_TT_as_verifier(
         &mut self,
@@ -107,6 +127,17 @@ impl CryptoSpake2 {
     ) -> Result<(), Error> {
         Ok(())
     }
+
+    #[allow(non_snake_case)]
+    pub fn get_TT_as_prover(
+        &mut self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
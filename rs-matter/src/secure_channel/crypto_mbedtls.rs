@@ -138,6 +138,27 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    pub fn get_w0(&mut self, w0: &mut [u8]) -> Result<(), Error> {
+        let w0_internal = self.w0.to_binary_padded(w0.len())?;
+        if w0_internal.len() != w0.len() {
+            error!("w0 length mismatch");
+            Err(ErrorCode::Invalid)?;
+        }
+        w0.copy_from_slice(&w0_internal);
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_L(&mut self, l: &mut [u8]) -> Result<(), Error> {
+        let l_internal = self.L.to_binary(&self.group, false)?;
+        if l_internal.len() != l.len() {
+            error!("L length mismatch");
+            Err(ErrorCode::Invalid)?;
+        }
+        l.copy_from_slice(&l_internal);
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_pB(&mut self, pB: &mut [u8], _rand: Rand) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
@@ -163,6 +184,31 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, pA: &mut [u8], _rand: Rand) -> Result<(), Error> {
+        // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
+        //   for x
+        //   - select random x between 0 to p
+        //   - X = x*P + w0*M
+        //   - pA = X
+
+        // A private key on this curve is a random number between 0 to p
+        let mut ctr_drbg: CtrDrbg = CtrDrbg::new(Arc::new(OsEntropy::new()), None)?;
+        self.xy = Pk::generate_ec(&mut ctr_drbg, EcGroupId::SecP256R1)?.ec_private()?;
+
+        let P = self.group.generator()?;
+        let X = EcPoint::muladd(&mut self.group, &P, &self.xy, &self.M, &self.w0)?;
+
+        let pA_internal = X.to_binary(&self.group, false)?;
+        let pA_internal = pA_internal.as_slice();
+        if pA_internal.len() != pA.len() {
+            error!("pA length mismatch");
+            Err(ErrorCode::Invalid)?;
+        }
+        pA.copy_from_slice(pA_internal);
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_TT_as_verifier(
         &mut self,
@@ -170,6 +216,52 @@ impl CryptoSpake2 {
         pA: &[u8],
         pB: &[u8],
         out: &mut [u8],
+    ) -> Result<(), Error> {
+        let X = EcPoint::from_binary(&self.group, pA)?;
+        let (Z, V) = Self::get_ZV_as_verifier(
+            &self.w0,
+            &self.L,
+            &self.M,
+            &X,
+            &self.xy,
+            &self.order,
+            &mut self.group,
+        )?;
+
+        self.get_TT(context, pA, pB, &Z, &V, out)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_TT_as_prover(
+        &mut self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        let Y = EcPoint::from_binary(&self.group, pB)?;
+        let (Z, V) = Self::get_ZV_as_prover(
+            &self.w0,
+            &self.w1,
+            &self.N,
+            &Y,
+            &self.xy,
+            &self.order,
+            &mut self.group,
+        )?;
+
+        self.get_TT(context, pA, pB, &Z, &V, out)
+    }
+
+    #[allow(non_snake_case)]
+    fn get_TT(
+        &self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        Z: &EcPoint,
+        V: &EcPoint,
+        out: &mut [u8],
     ) -> Result<(), Error> {
         let mut TT = Md::new(mbedtls::hash::Type::Sha256)?;
         // context
@@ -186,17 +278,6 @@ impl CryptoSpake2 {
         // Y = pB
         Self::add_to_tt(&mut TT, pB)?;
 
-        let X = EcPoint::from_binary(&self.group, pA)?;
-        let (Z, V) = Self::get_ZV_as_verifier(
-            &self.w0,
-            &self.L,
-            &self.M,
-            &X,
-            &self.xy,
-            &self.order,
-            &mut self.group,
-        )?;
-
         // Z
         let tmp = Z.to_binary(&self.group, false)?;
         let tmp = tmp.as_slice();
@@ -228,7 +309,6 @@ impl CryptoSpake2 {
 
     #[inline(always)]
     #[allow(non_snake_case)]
-    #[allow(dead_code)]
     fn get_ZV_as_prover(
         w0: &Mpi,
         w1: &Mpi,
@@ -22,11 +22,12 @@ use elliptic_curve::ops::*;
 use elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
 use elliptic_curve::Field;
 use elliptic_curve::PrimeField;
+use log::error;
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 use sha2::Digest;
 
-use crate::error::Error;
+use crate::error::{Error, ErrorCode};
 use crate::utils::rand::Rand;
 
 const MATTER_M_BIN: [u8; 65] = [
@@ -59,8 +60,8 @@ pub struct CryptoSpake2 {
 impl CryptoSpake2 {
     #[allow(non_snake_case)]
     pub fn new() -> Result<Self, Error> {
-        let M = p256::EncodedPoint::from_bytes(MATTER_M_BIN).unwrap();
-        let N = p256::EncodedPoint::from_bytes(MATTER_N_BIN).unwrap();
+        let M = p256::EncodedPoint::from_bytes(MATTER_M_BIN).map_err(|_| ErrorCode::Invalid)?;
+        let N = p256::EncodedPoint::from_bytes(MATTER_N_BIN).map_err(|_| ErrorCode::Invalid)?;
         let L = p256::EncodedPoint::default();
         let pB = p256::EncodedPoint::default();
 
@@ -151,7 +152,7 @@ impl CryptoSpake2 {
     #[allow(non_snake_case)]
     #[allow(dead_code)]
     pub fn set_L(&mut self, l: &[u8]) -> Result<(), Error> {
-        self.L = p256::EncodedPoint::from_bytes(l).unwrap();
+        self.L = p256::EncodedPoint::from_bytes(l).map_err(|_| ErrorCode::Invalid)?;
         Ok(())
     }
 
@@ -165,6 +166,27 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    pub fn get_w0(&self, w0: &mut [u8]) -> Result<(), Error> {
+        let w0_internal = self.w0.to_bytes();
+        if w0_internal.len() != w0.len() {
+            error!("w0 length mismatch");
+            Err(ErrorCode::Invalid)?;
+        }
+        w0.copy_from_slice(&w0_internal);
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_L(&self, l: &mut [u8]) -> Result<(), Error> {
+        let l_internal = self.L.as_bytes();
+        if l_internal.len() != l.len() {
+            error!("L length mismatch");
+            Err(ErrorCode::Invalid)?;
+        }
+        l.copy_from_slice(l_internal);
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_pB(&mut self, pB: &mut [u8], rand: Rand) -> Result<(), Error> {
         // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
@@ -184,6 +206,24 @@ impl CryptoSpake2 {
         Ok(())
     }
 
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, pA: &mut [u8], rand: Rand) -> Result<(), Error> {
+        // From the SPAKE2+ spec (https://datatracker.ietf.org/doc/draft-bar-cfrg-spake2plus/)
+        //   for x
+        //   - select random x between 0 to p
+        //   - X = x*P + w0*M
+        //   - pA = X
+        let mut rand = RandRngCore(rand);
+        self.xy = p256::Scalar::random(&mut rand);
+
+        let P = p256::AffinePoint::GENERATOR;
+        let M = p256::AffinePoint::from_encoded_point(&self.M).unwrap();
+        let X = Self::do_add_mul(P, self.xy, M, self.w0)?;
+        pA.copy_from_slice(X.as_bytes());
+
+        Ok(())
+    }
+
     #[allow(non_snake_case)]
     pub fn get_TT_as_verifier(
         &mut self,
@@ -191,6 +231,46 @@ impl CryptoSpake2 {
         pA: &[u8],
         pB: &[u8],
         out: &mut [u8],
+    ) -> Result<(), Error> {
+        // pA comes from the wire, so it might not be a valid point
+        let X = p256::EncodedPoint::from_bytes(pA).map_err(|_| ErrorCode::Invalid)?;
+        let X: Option<p256::AffinePoint> = p256::AffinePoint::from_encoded_point(&X).into();
+        let X = X.ok_or(ErrorCode::Invalid)?;
+        let L: Option<p256::AffinePoint> = p256::AffinePoint::from_encoded_point(&self.L).into();
+        let L = L.ok_or(ErrorCode::Invalid)?;
+        let M = p256::AffinePoint::from_encoded_point(&self.M).unwrap();
+        let (Z, V) = Self::get_ZV_as_verifier(self.w0, L, M, X, self.xy)?;
+
+        self.get_TT(context, pA, pB, &Z, &V, out)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_TT_as_prover(
+        &mut self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        // pB comes from the wire, so it might not be a valid point
+        let Y = p256::EncodedPoint::from_bytes(pB).map_err(|_| ErrorCode::Invalid)?;
+        let Y: Option<p256::AffinePoint> = p256::AffinePoint::from_encoded_point(&Y).into();
+        let Y = Y.ok_or(ErrorCode::Invalid)?;
+        let N = p256::AffinePoint::from_encoded_point(&self.N).unwrap();
+        let (Z, V) = Self::get_ZV_as_prover(self.w0, self.w1, N, Y, self.xy)?;
+
+        self.get_TT(context, pA, pB, &Z, &V, out)
+    }
+
+    #[allow(non_snake_case)]
+    fn get_TT(
+        &self,
+        context: &[u8],
+        pA: &[u8],
+        pB: &[u8],
+        Z: &p256::EncodedPoint,
+        V: &p256::EncodedPoint,
+        out: &mut [u8],
     ) -> Result<(), Error> {
         let mut TT = sha2::Sha256::new();
         // Context
@@ -206,13 +286,6 @@ impl CryptoSpake2 {
         Self::add_to_tt(&mut TT, pA)?;
         // Y = pB
         Self::add_to_tt(&mut TT, pB)?;
-
-        let X = p256::EncodedPoint::from_bytes(pA).unwrap();
-        let X = p256::AffinePoint::from_encoded_point(&X).unwrap();
-        let L = p256::AffinePoint::from_encoded_point(&self.L).unwrap();
-        let M = p256::AffinePoint::from_encoded_point(&self.M).unwrap();
-        let (Z, V) = Self::get_ZV_as_verifier(self.w0, L, M, X, self.xy)?;
-
         // Z
         Self::add_to_tt(&mut TT, Z.as_bytes())?;
         // V
@@ -246,7 +319,6 @@ impl CryptoSpake2 {
 
     #[inline(always)]
     #[allow(non_snake_case)]
-    #[allow(dead_code)]
     fn get_ZV_as_prover(
         w0: p256::Scalar,
         w1: p256::Scalar,
@@ -15,7 +15,24 @@
  *    limitations under the License.
  */
 
+// The secure channel parses the session establishment messages of any peer, so
+// - just like the transport - it is not allowed to use any of the explicitly
+// panicking macros, nor to unwrap. The few unwraps which cannot fail are allowed
+// one by one, with the reason why
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::unreachable,
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used
+    )
+)]
+
 pub mod case;
+pub mod check_in;
 pub mod common;
 #[cfg(not(any(feature = "openssl", feature = "mbedtls", feature = "rustcrypto")))]
 mod crypto_dummy;
@@ -20,6 +20,7 @@ use core::{fmt::Write, time::Duration};
 use super::{
     common::{SCStatusCodes, PROTO_ID_SECURE_CHANNEL},
     spake2p::{Spake2P, VerifierData},
+    status_report::{GeneralCode, StatusReport},
 };
 use crate::{
     alloc, crypto,
@@ -28,22 +29,50 @@ use crate::{
     secure_channel::common::{complete_with_status, OpCode},
     tlv::{self, get_root_node_struct, FromTLV, OctetStr, TLVWriter, TagType, ToTLV},
     transport::{
-        exchange::{Exchange, ExchangeId},
+        exchange::{Exchange, ExchangeId, SessionId},
         packet::Packet,
-        session::{CloneData, SessionMode},
+        session::{CloneData, SessionMode, SessionParams},
     },
     utils::{epoch::Epoch, rand::Rand},
 };
-use log::{error, info};
+use log::{error, info, warn};
+
+/// The maximum number of failed PASE attempts, after which the commissioning window is closed
+pub const MAX_PASE_FAILED_ATTEMPTS: u8 = 20;
+
+/// The kind of an open commissioning window
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommWindowKind {
+    /// Opened with the passcode of the device (Basic Commissioning Method)
+    Basic,
+    /// Opened with a verifier provided by an administrator (Enhanced Commissioning Method)
+    Enhanced,
+}
+
+/// The administrator which opened a commissioning window
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CommWindowAdmin {
+    pub fab_idx: u8,
+    pub vendor_id: u16,
+}
 
 struct PaseSession {
     mdns_service_name: heapless::String<16>,
     verifier: VerifierData,
+    kind: CommWindowKind,
+    /// When the window closes on its own, if ever
+    expires_at: Option<Duration>,
+    admin: Option<CommWindowAdmin>,
 }
 
 pub struct PaseMgr {
     session: Option<PaseSession>,
+    /// The passcode of the device and its discriminator, used for Basic commissioning windows
+    basic_comm_data: Option<(VerifierData, u16)>,
     timeout: Option<Timeout>,
+    /// The failed attempts in the currently open commissioning window - successful ones in
+    /// between do not reset the count
+    failed_attempts: u8,
     epoch: Epoch,
     rand: Rand,
 }
@@ -53,7 +82,9 @@ impl PaseMgr {
     pub const fn new(epoch: Epoch, rand: Rand) -> Self {
         Self {
             session: None,
+            basic_comm_data: None,
             timeout: None,
+            failed_attempts: 0,
             epoch,
             rand,
         }
@@ -63,18 +94,76 @@ impl PaseMgr {
         self.session.is_some()
     }
 
+    /// Opens a Basic commissioning window which does not close on its own, e.g. for the initial
+    /// commissioning of the device
     pub fn enable_pase_session(
         &mut self,
         verifier: VerifierData,
         discriminator: u16,
         mdns: &dyn Mdns,
     ) -> Result<(), Error> {
+        self.open_comm_window(
+            verifier,
+            discriminator,
+            CommWindowKind::Basic,
+            None,
+            None,
+            mdns,
+        )
+    }
+
+    /// Sets the passcode and the discriminator of the device, used when an administrator
+    /// opens a Basic commissioning window
+    pub fn set_basic_comm_data(&mut self, verifier: VerifierData, discriminator: u16) {
+        self.basic_comm_data = Some((verifier, discriminator));
+    }
+
+    /// Opens a Basic commissioning window with the passcode of the device,
+    /// closing it after `timeout`
+    pub fn open_basic_comm_window(
+        &mut self,
+        timeout: Duration,
+        admin: Option<CommWindowAdmin>,
+        mdns: &dyn Mdns,
+    ) -> Result<(), Error> {
+        let (verifier, discriminator) = self
+            .basic_comm_data
+            .clone()
+            .ok_or(ErrorCode::InvalidState)?;
+
+        self.open_comm_window(
+            verifier,
+            discriminator,
+            CommWindowKind::Basic,
+            Some(timeout),
+            admin,
+            mdns,
+        )
+    }
+
+    /// Opens a commissioning window, advertising the device as commissionable
+    /// until the window is closed.
+    ///
+    /// Fails with `Busy` if a window is open already.
+    pub fn open_comm_window(
+        &mut self,
+        verifier: VerifierData,
+        discriminator: u16,
+        kind: CommWindowKind,
+        timeout: Option<Duration>,
+        admin: Option<CommWindowAdmin>,
+        mdns: &dyn Mdns,
+    ) -> Result<(), Error> {
+        if self.session.is_some() {
+            Err(ErrorCode::Busy)?;
+        }
+
         let mut buf = [0; 8];
         (self.rand)(&mut buf);
         let num = u64::from_be_bytes(buf);
 
         let mut mdns_service_name = heapless::String::<16>::new();
-        write!(&mut mdns_service_name, "{:016X}", num).unwrap();
+        write!(&mut mdns_service_name, "{:016X}", num).map_err(|_| ErrorCode::NoSpace)?;
 
         mdns.add(
             &mdns_service_name,
@@ -84,7 +173,11 @@ impl PaseMgr {
         self.session = Some(PaseSession {
             mdns_service_name,
             verifier,
+            kind,
+            expires_at: timeout.map(|timeout| (self.epoch)() + timeout),
+            admin,
         });
+        self.failed_attempts = 0;
 
         Ok(())
     }
@@ -102,6 +195,64 @@ impl PaseMgr {
 
         Ok(disabled)
     }
+
+    /// The kind of the open commissioning window, if any
+    pub fn comm_window_kind(&self) -> Option<CommWindowKind> {
+        self.session.as_ref().map(|session| session.kind)
+    }
+
+    /// The administrator which opened the commissioning window, if it was opened by one
+    pub fn comm_window_admin(&self) -> Option<CommWindowAdmin> {
+        self.session.as_ref().and_then(|session| session.admin)
+    }
+
+    /// When the open commissioning window closes on its own, if ever
+    pub fn comm_window_deadline(&self) -> Option<Duration> {
+        self.session.as_ref().and_then(|session| session.expires_at)
+    }
+
+    /// Closes the commissioning window if its timeout elapsed.
+    ///
+    /// Returns `true` if the window got closed.
+    pub fn expire_comm_window(&mut self, mdns: &dyn Mdns) -> Result<bool, Error> {
+        if self
+            .comm_window_deadline()
+            .map(|deadline| deadline <= (self.epoch)())
+            .unwrap_or(false)
+        {
+            info!("Commissioning window timed out, closing");
+
+            self.timeout = None;
+            self.disable_pase_session(mdns)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub fn failed_attempts(&self) -> u8 {
+        self.failed_attempts
+    }
+
+    /// Records a failed PASE attempt, closing the commissioning window once there were
+    /// [`MAX_PASE_FAILED_ATTEMPTS`] failed attempts in the currently open commissioning window,
+    /// so that the passcode cannot be brute-forced.
+    ///
+    /// Returns `true` if the commissioning window got closed.
+    pub fn record_failed_attempt(&mut self, mdns: &dyn Mdns) -> Result<bool, Error> {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+
+        if self.failed_attempts >= MAX_PASE_FAILED_ATTEMPTS {
+            warn!(
+                "{} failed PASE attempts, closing the commissioning window",
+                self.failed_attempts
+            );
+
+            self.timeout = None;
+            self.disable_pase_session(mdns)
+        } else {
+            Ok(false)
+        }
+    }
 }
 
 // This file basically deals with the handlers for the PASE secure channel protocol
@@ -113,6 +264,9 @@ const PASE_DISCARD_TIMEOUT_SECS: Duration = Duration::from_secs(60);
 
 const SPAKE2_SESSION_KEYS_INFO: [u8; 11] = *b"SessionKeys";
 
+/// The maximum length of the PBKDFParamRequest sent by the initiator
+const MAX_PBKDF_PARAM_REQ_LEN: usize = 64;
+
 struct Timeout {
     start_time: Duration,
     exch_id: ExchangeId,
@@ -154,6 +308,226 @@ impl Pake {
         self.handle_pasepake3(exchange, rx, tx, &mut spake2p).await
     }
 
+    /// Establishes a PASE session with the peer of `exchange` - an exchange initiated over the
+    /// unsecured session with the peer, see [`crate::Matter::initiate_unsecured`] - by proving
+    /// the knowledge of its `passcode`, as done when commissioning the peer.
+    ///
+    /// Returns the ID of the new session.
+    #[allow(non_snake_case)]
+    pub async fn initiate(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        passcode: u32,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+    ) -> Result<SessionId, Error> {
+        let mut spake2p = alloc!(Spake2P::new());
+
+        let local_sessid = self
+            .send_pbkdfparamrequest(exchange, passcode, rx, tx, &mut spake2p)
+            .await?;
+
+        let mut pA: [u8; 65] = [0; 65];
+        self.send_pasepake1(exchange, &mut pA, rx, tx, &mut spake2p)
+            .await?;
+        self.send_pasepake3(exchange, local_sessid, &pA, rx, tx, &mut spake2p)
+            .await
+    }
+
+    async fn send_pbkdfparamrequest(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        passcode: u32,
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        spake2p: &mut Spake2P,
+    ) -> Result<u16, Error> {
+        let mut our_random: [u8; 32] = [0; 32];
+        (exchange.matter.rand)(&mut our_random);
+
+        let local_sessid = exchange.get_next_sess_id();
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::PBKDFParamRequest as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        let req = PBKDFParamReqOut {
+            initiator_random: OctetStr(&our_random),
+            initiator_ssid: local_sessid,
+            passcode_id: 0,
+            has_params: false,
+            initiator_session_params: Some(SessionParams::local()),
+        };
+        req.to_tlv(&mut tw, TagType::Anonymous)?;
+
+        // The request is part of the context of the Spake2+ exchange, so keep it for later
+        let mut req = heapless::Vec::<u8, MAX_PBKDF_PARAM_REQ_LEN>::new();
+        req.extend_from_slice(tx.as_slice())
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        exchange.exchange(tx, rx).await?;
+
+        Self::check_status(rx, OpCode::PBKDFParamResponse)?;
+
+        let root = tlv::get_root_node(rx.as_slice())?;
+        let resp = PBKDFParamRespIn::from_tlv(&root)?;
+
+        if resp.init_random.0 != &our_random[..] {
+            error!("PBKDFParamResponse for another PBKDFParamRequest");
+            Err(ErrorCode::Invalid)?;
+        }
+
+        // We did not have the PBKDF parameters, so the peer must send them
+        let params = resp.params.ok_or(ErrorCode::Invalid)?;
+
+        if let Some(session_params) = resp.responder_session_params {
+            exchange.with_session_mut(|sess| {
+                sess.set_session_params(session_params);
+                Ok(())
+            })?;
+        }
+
+        spake2p.set_app_data(((local_sessid as u32) << 16) | resp.local_sessid as u32);
+        spake2p.start_prover(passcode, params.count, params.salt.0)?;
+        spake2p.set_context(&req, rx.as_slice())?;
+
+        Ok(local_sessid)
+    }
+
+    #[allow(non_snake_case)]
+    async fn send_pasepake1(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        pA: &mut [u8],
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        spake2p: &mut Spake2P,
+    ) -> Result<(), Error> {
+        spake2p.get_pA(pA, exchange.matter.rand)?;
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::PASEPake1 as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), pA)?;
+        tw.end_container()?;
+
+        exchange.exchange(tx, rx).await
+    }
+
+    #[allow(non_snake_case)]
+    async fn send_pasepake3(
+        &mut self,
+        exchange: &mut Exchange<'_>,
+        local_sessid: u16,
+        pA: &[u8],
+        rx: &mut Packet<'_>,
+        tx: &mut Packet<'_>,
+        spake2p: &mut Spake2P,
+    ) -> Result<SessionId, Error> {
+        Self::check_status(rx, OpCode::PASEPake2)?;
+
+        let mut cA: [u8; 32] = [0; 32];
+
+        let clone_data = {
+            let root = get_root_node_struct(rx.as_slice())?;
+            let pB = root.find_tag(1)?.slice()?;
+            let cB = root.find_tag(2)?.slice()?;
+
+            let ke = match spake2p.handle_pB(pA, pB, cB, &mut cA) {
+                Ok(ke) => ke,
+                Err(e) => {
+                    // The peer does not know the passcode, or we got it wrong
+                    complete_with_status(exchange, tx, SCStatusCodes::InvalidParameter, None)
+                        .await?;
+
+                    return Err(e);
+                }
+            };
+
+            let mut session_keys: [u8; 48] = [0; 48];
+            crypto::hkdf_sha256(&[], ke, &SPAKE2_SESSION_KEYS_INFO, &mut session_keys)
+                .map_err(|_x| ErrorCode::NoSpace)?;
+
+            let data = spake2p.get_app_data();
+            let peer_sessid: u16 = (data & 0xffff) as u16;
+            let mut clone_data = CloneData::new(
+                0,
+                0,
+                peer_sessid,
+                local_sessid,
+                exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
+                SessionMode::Pase,
+            );
+            clone_data.session_params = exchange.with_session(|sess| Ok(*sess.session_params()))?;
+            // The keys of the initiator are the other way round than those of the responder
+            clone_data.enc_key.copy_from_slice(&session_keys[0..16]);
+            clone_data.dec_key.copy_from_slice(&session_keys[16..32]);
+            clone_data
+                .att_challenge
+                .copy_from_slice(&session_keys[32..48]);
+
+            clone_data
+        };
+
+        tx.reset();
+        tx.set_proto_id(PROTO_ID_SECURE_CHANNEL);
+        tx.set_proto_opcode(OpCode::PASEPake3 as u8);
+
+        let mut tw = TLVWriter::new(tx.get_writebuf()?);
+        tw.start_struct(TagType::Anonymous)?;
+        tw.str8(TagType::Context(1), &cA)?;
+        tw.end_container()?;
+
+        exchange.exchange(tx, rx).await?;
+
+        let status = StatusReport::from_packet(rx)?;
+        if !status.is_session_establishment_success() {
+            error!("PASE session rejected by the peer: {:?}", status);
+            exchange.acknowledge().await?;
+
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let sess_index = exchange.clone_session(tx, &clone_data).await?;
+        let session_id = exchange
+            .matter
+            .session_mgr()
+            .borrow_mut()
+            .mut_by_index(sess_index)
+            .ok_or(ErrorCode::NoSession)?
+            .id();
+
+        exchange.acknowledge().await?;
+
+        info!("PASE session established: {:?}", session_id);
+
+        Ok(session_id)
+    }
+
+    /// Checks that `rx` is a message with `opcode`, rather than a status report
+    /// with which the peer aborted the session establishment
+    fn check_status(rx: &Packet<'_>, opcode: OpCode) -> Result<(), Error> {
+        if rx.get_proto_raw_opcode() == OpCode::StatusReport as u8 {
+            let status = StatusReport::from_packet(rx)?;
+            error!(
+                "PASE session establishment aborted by the peer: {:?}",
+                status
+            );
+
+            if status.general_code == GeneralCode::Busy as u16 {
+                Err(ErrorCode::Busy)?;
+            }
+
+            Err(ErrorCode::Invalid)?;
+        }
+
+        rx.check_proto_opcode(opcode as _)
+    }
+
     #[allow(non_snake_case)]
     async fn handle_pasepake3(
         &mut self,
@@ -187,6 +561,7 @@ impl Pake {
                 exchange.with_session(|sess| Ok(sess.get_peer_addr()))?,
                 SessionMode::Pase,
             );
+            clone_data.session_params = exchange.with_session(|sess| Ok(*sess.session_params()))?;
             clone_data.dec_key.copy_from_slice(&session_keys[0..16]);
             clone_data.enc_key.copy_from_slice(&session_keys[16..32]);
             clone_data
@@ -211,7 +586,20 @@ impl Pake {
 
                 SCStatusCodes::SessionEstablishmentSuccess
             }
-            Err(status) => status,
+            Err(status) => {
+                if status == SCStatusCodes::InvalidParameter {
+                    // The peer does not know the passcode
+                    let mdns = &exchange.matter.mdns;
+
+                    exchange
+                        .matter
+                        .pase_mgr
+                        .borrow_mut()
+                        .record_failed_attempt(mdns)?;
+                }
+
+                status
+            }
         };
 
         complete_with_status(exchange, tx, status, None).await
@@ -264,15 +652,31 @@ impl Pake {
         rx.check_proto_opcode(OpCode::PBKDFParamRequest as _)?;
         self.update_timeout(exchange, tx, true).await?;
 
+        let passcode_id = {
+            let root = tlv::get_root_node(rx.as_slice())?;
+            PBKDFParamReq::from_tlv(&root)?.passcode_id
+        };
+
+        if passcode_id != 0 {
+            error!("Can't yet handle passcode_id != 0");
+            complete_with_status(exchange, tx, SCStatusCodes::InvalidParameter, None).await?;
+
+            Err(ErrorCode::Invalid)?;
+        }
+
         {
             let pase = exchange.matter.pase_mgr.borrow();
             let session = pase.session.as_ref().ok_or(ErrorCode::NoSession)?;
 
             let root = tlv::get_root_node(rx.as_slice())?;
             let a = PBKDFParamReq::from_tlv(&root)?;
-            if a.passcode_id != 0 {
-                error!("Can't yet handle passcode_id != 0");
-                Err(ErrorCode::Invalid)?;
+
+            // Retransmit to the peer as per its parameters right away, also during the handshake
+            if let Some(session_params) = a.initiator_session_params {
+                exchange.with_session_mut(|sess| {
+                    sess.set_session_params(session_params);
+                    Ok(())
+                })?;
             }
 
             let mut our_random: [u8; 32] = [0; 32];
@@ -293,6 +697,7 @@ impl Pake {
                 our_random: OctetStr(&our_random),
                 local_sessid,
                 params: None,
+                responder_session_params: Some(SessionParams::local()),
             };
             if !a.has_params {
                 let params_resp = PBKDFParamRespParams {
@@ -396,6 +801,35 @@ struct PBKDFParamResp<'a> {
     our_random: OctetStr<'a>,
     local_sessid: u16,
     params: Option<PBKDFParamRespParams<'a>>,
+    responder_session_params: Option<SessionParams>,
+}
+
+#[derive(ToTLV)]
+#[tlvargs(start = 1)]
+struct PBKDFParamReqOut<'a> {
+    initiator_random: OctetStr<'a>,
+    initiator_ssid: u16,
+    passcode_id: u16,
+    has_params: bool,
+    initiator_session_params: Option<SessionParams>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a", start = 1)]
+struct PBKDFParamRespParamsIn<'a> {
+    count: u32,
+    salt: OctetStr<'a>,
+}
+
+#[derive(FromTLV)]
+#[tlvargs(lifetime = "'a", start = 1)]
+struct PBKDFParamRespIn<'a> {
+    init_random: OctetStr<'a>,
+    // Not needed, as the context of the Spake2+ exchange covers the whole response
+    _our_random: OctetStr<'a>,
+    local_sessid: u16,
+    params: Option<PBKDFParamRespParamsIn<'a>>,
+    responder_session_params: Option<SessionParams>,
 }
 
 #[allow(non_snake_case)]
@@ -412,4 +846,107 @@ struct PBKDFParamReq<'a> {
     initiator_ssid: u16,
     passcode_id: u16,
     has_params: bool,
+    initiator_session_params: Option<SessionParams>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        mdns::{Mdns, ServiceMode},
+        secure_channel::spake2p::VerifierData,
+        utils::{epoch::dummy_epoch, rand::dummy_rand},
+    };
+
+    use core::time::Duration;
+
+    use super::{CommWindowKind, PaseMgr, MAX_PASE_FAILED_ATTEMPTS};
+
+    struct NoopMdns;
+
+    impl Mdns for NoopMdns {
+        fn reset(&self) {}
+
+        fn add(&self, _service: &str, _mode: ServiceMode) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn remove(&self, _service: &str) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_attempts_close_window() {
+        let mut mgr = PaseMgr::new(dummy_epoch, dummy_rand);
+
+        mgr.enable_pase_session(
+            VerifierData::new_with_pw(123456, dummy_rand),
+            250,
+            &NoopMdns,
+        )
+        .unwrap();
+
+        for _ in 1..MAX_PASE_FAILED_ATTEMPTS {
+            assert!(!mgr.record_failed_attempt(&NoopMdns).unwrap());
+            assert!(mgr.is_pase_session_enabled());
+        }
+
+        assert!(mgr.record_failed_attempt(&NoopMdns).unwrap());
+        assert!(!mgr.is_pase_session_enabled());
+
+        // A new window starts counting afresh
+        mgr.enable_pase_session(
+            VerifierData::new_with_pw(123456, dummy_rand),
+            250,
+            &NoopMdns,
+        )
+        .unwrap();
+        assert_eq!(mgr.failed_attempts(), 0);
+    }
+
+    #[test]
+    fn test_comm_window() {
+        let mut mgr = PaseMgr::new(dummy_epoch, dummy_rand);
+
+        // No passcode to open a Basic window with
+        assert!(mgr
+            .open_basic_comm_window(Duration::from_secs(180), None, &NoopMdns)
+            .is_err());
+
+        mgr.set_basic_comm_data(VerifierData::new_with_pw(123456, dummy_rand), 250);
+        mgr.open_basic_comm_window(Duration::from_secs(180), None, &NoopMdns)
+            .unwrap();
+        assert_eq!(mgr.comm_window_kind(), Some(CommWindowKind::Basic));
+        assert!(!mgr.expire_comm_window(&NoopMdns).unwrap());
+
+        // Only one window at a time
+        assert!(mgr
+            .open_comm_window(
+                VerifierData::new_with_pw(654321, dummy_rand),
+                250,
+                CommWindowKind::Enhanced,
+                Some(Duration::from_secs(180)),
+                None,
+                &NoopMdns,
+            )
+            .is_err());
+
+        mgr.disable_pase_session(&NoopMdns).unwrap();
+        assert_eq!(mgr.comm_window_kind(), None);
+
+        mgr.open_comm_window(
+            VerifierData::new_with_pw(654321, dummy_rand),
+            250,
+            CommWindowKind::Enhanced,
+            Some(Duration::ZERO),
+            None,
+            &NoopMdns,
+        )
+        .unwrap();
+        assert_eq!(mgr.comm_window_kind(), Some(CommWindowKind::Enhanced));
+
+        assert!(mgr.expire_comm_window(&NoopMdns).unwrap());
+        assert_eq!(mgr.comm_window_kind(), None);
+    }
 }
@@ -73,8 +73,9 @@ const CRYPTO_GROUP_SIZE_BYTES: usize = 32;
 const CRYPTO_W_SIZE_BYTES: usize = CRYPTO_GROUP_SIZE_BYTES + 8;
 const CRYPTO_PUBLIC_KEY_SIZE_BYTES: usize = (2 * CRYPTO_GROUP_SIZE_BYTES) + 1;
 
-const MAX_SALT_SIZE_BYTES: usize = 32;
-const VERIFIER_SIZE_BYTES: usize = CRYPTO_GROUP_SIZE_BYTES + CRYPTO_PUBLIC_KEY_SIZE_BYTES;
+pub const MIN_SALT_SIZE_BYTES: usize = 16;
+pub const MAX_SALT_SIZE_BYTES: usize = 32;
+pub const VERIFIER_SIZE_BYTES: usize = CRYPTO_GROUP_SIZE_BYTES + CRYPTO_PUBLIC_KEY_SIZE_BYTES;
 
 fn crypto_spake2_new() -> Result<CryptoSpake2, Error> {
     CryptoSpake2::new()
@@ -86,6 +87,7 @@ impl Default for Spake2P {
     }
 }
 
+#[derive(Clone)]
 pub struct VerifierData {
     pub data: VerifierOption,
     // For the VerifierOption::Verifier, the following fields only serve
@@ -94,6 +96,7 @@ pub struct VerifierData {
     pub count: u32,
 }
 
+#[derive(Clone)]
 pub enum VerifierOption {
     /// With Password
     Password(u32),
@@ -112,6 +115,23 @@ impl VerifierData {
         s
     }
 
+    /// Computes the verifier of a passcode with a random salt and the default iteration count,
+    /// so that only the verifier - and not the passcode itself - needs to be stored on the device
+    pub fn new_verifier_with_pw(pw: u32, rand: Rand) -> Result<Self, Error> {
+        let mut salt = [0; MAX_SALT_SIZE_BYTES];
+        rand(&mut salt);
+
+        Self::new_verifier_from_pw(pw, SPAKE2_ITERATION_COUNT, &salt)
+    }
+
+    /// Computes the verifier of a passcode with the given salt and iteration count
+    pub fn new_verifier_from_pw(pw: u32, count: u32, salt: &[u8]) -> Result<Self, Error> {
+        let mut verifier = [0; VERIFIER_SIZE_BYTES];
+        Spake2P::compute_verifier(pw, count, salt, &mut verifier)?;
+
+        Ok(Self::new(&verifier, count, salt))
+    }
+
     pub fn new(verifier: &[u8], count: u32, salt: &[u8]) -> Self {
         let mut v = [0_u8; VERIFIER_SIZE_BYTES];
         let mut s = [0_u8; MAX_SALT_SIZE_BYTES];
@@ -166,6 +186,34 @@ impl Spake2P {
         let _ = pbkdf2_hmac(&pw_str, iter as usize, salt, w0w1s);
     }
 
+    /// Computes the PASE verifier of a passcode, i.e. `w0 || L`, as provisioned on devices
+    /// which do not store their passcode, or as sent in an OpenCommissioningWindow command
+    pub fn compute_verifier(
+        pw: u32,
+        count: u32,
+        salt: &[u8],
+        verifier: &mut [u8; VERIFIER_SIZE_BYTES],
+    ) -> Result<(), Error> {
+        if !(MIN_SALT_SIZE_BYTES..=MAX_SALT_SIZE_BYTES).contains(&salt.len()) {
+            error!("Salt of invalid length");
+            Err(ErrorCode::InvalidArgument)?;
+        }
+
+        let mut w0w1s: [u8; 2 * CRYPTO_W_SIZE_BYTES] = [0; (2 * CRYPTO_W_SIZE_BYTES)];
+        Spake2P::get_w0w1s(pw, count, salt, &mut w0w1s);
+
+        let w0s_len = w0w1s.len() / 2;
+        let mut crypto_spake2 = crypto_spake2_new()?;
+        crypto_spake2.set_w0_from_w0s(&w0w1s[0..w0s_len])?;
+        crypto_spake2.set_L_from_w1s(&w0w1s[w0s_len..])?;
+
+        let (w0, l) = verifier.split_at_mut(CRYPTO_GROUP_SIZE_BYTES);
+        crypto_spake2.get_w0(w0)?;
+        crypto_spake2.get_L(l)?;
+
+        Ok(())
+    }
+
     pub fn start_verifier(&mut self, verifier: &VerifierData) -> Result<(), Error> {
         self.crypto_spake2 = Some(crypto_spake2_new()?);
         match verifier.data {
@@ -195,6 +243,69 @@ impl Spake2P {
         Ok(())
     }
 
+    /// Derives w0 and w1 from the passcode, with the PBKDF parameters of the verifier,
+    /// so as to prove the knowledge of the passcode to the verifier
+    pub fn start_prover(&mut self, pw: u32, count: u32, salt: &[u8]) -> Result<(), Error> {
+        let mut w0w1s: [u8; 2 * CRYPTO_W_SIZE_BYTES] = [0; (2 * CRYPTO_W_SIZE_BYTES)];
+        Spake2P::get_w0w1s(pw, count, salt, &mut w0w1s);
+
+        let w0s_len = w0w1s.len() / 2;
+        let mut crypto_spake2 = crypto_spake2_new()?;
+        crypto_spake2.set_w0_from_w0s(&w0w1s[0..w0s_len])?;
+        crypto_spake2.set_w1_from_w1s(&w0w1s[w0s_len..])?;
+
+        self.crypto_spake2 = Some(crypto_spake2);
+        self.mode = Spake2Mode::Prover;
+        Ok(())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn get_pA(&mut self, pA: &mut [u8], rand: Rand) -> Result<(), Error> {
+        if self.mode != Spake2Mode::Prover {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        self.crypto_spake2
+            .as_mut()
+            .ok_or(ErrorCode::InvalidState)?
+            .get_pA(pA, rand)
+    }
+
+    /// Computes cA for the verifier, and confirms the cB of the verifier.
+    ///
+    /// Returns Ke only if cB is confirmed.
+    #[allow(non_snake_case)]
+    pub fn handle_pB(
+        &mut self,
+        pA: &[u8],
+        pB: &[u8],
+        cB: &[u8],
+        cA: &mut [u8],
+    ) -> Result<&[u8], Error> {
+        if self.mode != Spake2Mode::Prover {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        // We are finished with using the crypto_spake2 after this
+        let mut crypto_spake2 = self.crypto_spake2.take().ok_or(ErrorCode::InvalidState)?;
+        let context = self.context.take().ok_or(ErrorCode::InvalidState)?;
+
+        let mut hash = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        context.finish(&mut hash)?;
+        let mut TT = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        crypto_spake2.get_TT_as_prover(&hash, pA, pB, &mut TT)?;
+
+        let mut expected_cB = [0u8; crypto::SHA256_HASH_LEN_BYTES];
+        Spake2P::get_Ke_and_cAcB(&TT, pA, pB, &mut self.Ke, cA, &mut expected_cB)?;
+
+        if cB.ct_eq(&expected_cB).unwrap_u8() == 1 {
+            Ok(&self.Ke)
+        } else {
+            error!("cB mismatch, the verifier does not know the passcode");
+            Err(ErrorCode::Invalid.into())
+        }
+    }
+
     #[allow(non_snake_case)]
     pub fn handle_pA(
         &mut self,
@@ -240,7 +351,6 @@ impl Spake2P {
 
     #[inline(always)]
     #[allow(non_snake_case)]
-    #[allow(dead_code)]
     fn get_Ke_and_cAcB(
         TT: &[u8],
         pA: &[u8],
@@ -283,12 +393,40 @@ impl Spake2P {
 #[cfg(test)]
 mod tests {
 
-    use super::Spake2P;
+    use super::{Spake2P, VerifierData};
     use crate::{
         crypto,
-        secure_channel::{spake2p::CRYPTO_W_SIZE_BYTES, spake2p_test_vectors::test_vectors::*},
+        secure_channel::{
+            common::SCStatusCodes,
+            spake2p::{CRYPTO_GROUP_SIZE_BYTES, CRYPTO_W_SIZE_BYTES, VERIFIER_SIZE_BYTES},
+            spake2p_test_vectors::test_vectors::*,
+        },
+        utils::rand::sys_rand,
     };
 
+    #[test]
+    fn test_compute_verifier() {
+        let salt = [
+            0x4, 0xa1, 0xd2, 0xc6, 0x11, 0xf0, 0xbd, 0x36, 0x78, 0x67, 0x79, 0x7b, 0xfe, 0x82,
+            0x36, 0x0,
+        ];
+
+        let mut verifier = [0; VERIFIER_SIZE_BYTES];
+        Spake2P::compute_verifier(123456, 2000, &salt, &mut verifier).unwrap();
+        // L is an uncompressed point
+        assert_eq!(verifier[CRYPTO_GROUP_SIZE_BYTES], 0x04);
+
+        let mut other = [0; VERIFIER_SIZE_BYTES];
+        Spake2P::compute_verifier(123456, 2000, &salt, &mut other).unwrap();
+        assert_eq!(verifier, other);
+
+        Spake2P::compute_verifier(123457, 2000, &salt, &mut other).unwrap();
+        assert_ne!(verifier, other);
+
+        // Too short a salt
+        assert!(Spake2P::compute_verifier(123456, 2000, &salt[..8], &mut other).is_err());
+    }
+
     #[test]
     fn test_pbkdf2() {
         // These are the vectors from one sample run of chip-tool along with our PBKDFParamResponse
@@ -311,6 +449,42 @@ mod tests {
         )
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_prover_verifier() {
+        let salt = [0x5a; 16];
+
+        for (pw, confirmed) in [(123456, true), (123457, false)] {
+            let mut verifier = Spake2P::new();
+            verifier
+                .start_verifier(&VerifierData::new_verifier_from_pw(123456, 1000, &salt).unwrap())
+                .unwrap();
+            verifier.set_context(b"request", b"response").unwrap();
+
+            let mut prover = Spake2P::new();
+            prover.start_prover(pw, 1000, &salt).unwrap();
+            prover.set_context(b"request", b"response").unwrap();
+
+            let mut pA = [0; 65];
+            prover.get_pA(&mut pA, sys_rand).unwrap();
+
+            let mut pB = [0; 65];
+            let mut cB = [0; 32];
+            verifier.handle_pA(&pA, &mut pB, &mut cB, sys_rand).unwrap();
+
+            let mut cA = [0; 32];
+            let prover_ke = prover.handle_pB(&pA, &pB, &cB, &mut cA).ok();
+            assert_eq!(prover_ke.is_some(), confirmed);
+
+            let (status, verifier_ke) = verifier.handle_cA(&cA);
+            assert_eq!(
+                status == SCStatusCodes::SessionEstablishmentSuccess,
+                confirmed
+            );
+            assert_eq!(prover_ke, verifier_ke);
+        }
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_get_Ke_and_cAcB() {
@@ -16,7 +16,10 @@
  */
 
 use super::common::*;
-use crate::{error::Error, transport::packet::Packet};
+use crate::{
+    error::{Error, ErrorCode},
+    transport::packet::Packet,
+};
 
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
@@ -60,3 +63,35 @@ pub fn create_status_report(
 
     Ok(())
 }
+
+/// The header of a status report received from the peer; any protocol-specific data is ignored
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StatusReport {
+    pub general_code: u16,
+    pub proto_id: u32,
+    pub proto_code: u16,
+}
+
+impl StatusReport {
+    pub fn from_packet(rx: &Packet) -> Result<Self, Error> {
+        rx.check_proto_opcode(OpCode::StatusReport as _)?;
+
+        let data = rx.as_slice();
+        if data.len() < 8 {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        Ok(Self {
+            general_code: u16::from_le_bytes([data[0], data[1]]),
+            proto_id: u32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+            proto_code: u16::from_le_bytes([data[6], data[7]]),
+        })
+    }
+
+    /// Whether this is the report of a successfully established secure session
+    pub fn is_session_establishment_success(&self) -> bool {
+        self.general_code == GeneralCode::Success as u16
+            && self.proto_id == PROTO_ID_SECURE_CHANNEL as u32
+            && self.proto_code == SCStatusCodes::SessionEstablishmentSuccess as u16
+    }
+}
@@ -258,13 +258,21 @@ impl<T: ToTLV> ToTLV for Option<T> {
 /// The value may be null or a valid value
 /// Note: Null is different from Option. If the value is optional, include Option<> too. For
 /// example, Option<Nullable<T>>
-#[derive(Copy, Clone, PartialEq, Debug, Hash, Eq)]
+///
+/// The attributes of type `Nullable<T>` should have the `Quality::NULLABLE` quality, so that
+/// nulls written to them are accepted by the write path.
+#[derive(Copy, Clone, PartialEq, Debug, Hash, Eq, Default)]
 pub enum Nullable<T> {
+    #[default]
     Null,
     NotNull(T),
 }
 
 impl<T> Nullable<T> {
+    pub fn new(value: Option<T>) -> Self {
+        value.into()
+    }
+
     pub fn as_mut(&mut self) -> Nullable<&mut T> {
         match self {
             Nullable::Null => Nullable::Null,
@@ -292,6 +300,35 @@ impl<T> Nullable<T> {
             Nullable::NotNull(t) => Some(t),
         }
     }
+
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Nullable<U> {
+        match self {
+            Nullable::Null => Nullable::Null,
+            Nullable::NotNull(t) => Nullable::NotNull(f(t)),
+        }
+    }
+
+    pub fn unwrap_or(self, default: T) -> T {
+        match self {
+            Nullable::Null => default,
+            Nullable::NotNull(t) => t,
+        }
+    }
+}
+
+impl<T> From<Option<T>> for Nullable<T> {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(t) => Nullable::NotNull(t),
+            None => Nullable::Null,
+        }
+    }
+}
+
+impl<T> From<Nullable<T>> for Option<T> {
+    fn from(value: Nullable<T>) -> Self {
+        value.notnull()
+    }
 }
 
 impl<'a, T: FromTLV<'a>> FromTLV<'a> for Nullable<T> {
@@ -511,7 +548,7 @@ macro_rules! bitflags_tlv {
 
 #[cfg(test)]
 mod tests {
-    use super::{FromTLV, OctetStr, TLVWriter, TagType, ToTLV};
+    use super::{FromTLV, Nullable, OctetStr, TLVWriter, TagType, ToTLV};
     use crate::{tlv::TLVList, utils::writebuf::WriteBuf};
     use rs_matter_macros::{FromTLV, ToTLV};
 
@@ -645,4 +682,34 @@ mod tests {
             [21, 36, 1, 10, 24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
         );
     }
+
+    #[test]
+    fn test_nullable() {
+        let mut buf = [0; 20];
+        let mut writebuf = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut writebuf);
+
+        tw.start_struct(TagType::Anonymous).unwrap();
+        Nullable::<u8>::Null
+            .to_tlv(&mut tw, TagType::Context(0))
+            .unwrap();
+        Nullable::NotNull(5u8)
+            .to_tlv(&mut tw, TagType::Context(1))
+            .unwrap();
+        tw.end_container().unwrap();
+
+        assert_eq!(&buf[..8], &[21, 52, 0, 36, 1, 5, 24, 0]);
+
+        let root = TLVList::new(&buf).iter().next().unwrap();
+        let null = Nullable::<u8>::from_tlv(&root.find_tag(0).unwrap()).unwrap();
+        let value = Nullable::<u8>::from_tlv(&root.find_tag(1).unwrap()).unwrap();
+
+        assert_eq!(null, Nullable::Null);
+        assert_eq!(value, Nullable::NotNull(5));
+        assert_eq!(null.unwrap_or(7), 7);
+        assert_eq!(value.map(|v| v * 2), Nullable::NotNull(10));
+        assert_eq!(Option::from(value), Some(5));
+        assert_eq!(Nullable::from(None::<u8>), Nullable::Null);
+        assert_eq!(Nullable::<u8>::default(), Nullable::Null);
+    }
 }
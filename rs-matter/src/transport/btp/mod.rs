@@ -0,0 +1,425 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The Bluetooth Transport Protocol (BTP), used for commissioning over BLE.
+//!
+//! BTP runs on top of a GATT server exposing the Matter BLE service: the central writes
+//! to characteristic C1 and the peripheral (us) indicates on characteristic C2. The GATT server
+//! itself is provided by the platform via the [`GattPeripheral`] trait.
+//!
+//! [`Btp`] implements the handshake, the segmentation and reassembly of Matter messages
+//! and the window-based acknowledgements, and exposes the result as a regular
+//! [`NetworkSend`] / [`NetworkReceive`] pair (via `&Btp`) which can be passed to [`crate::Matter::run`].
+//! Only one BTP session (i.e. one central) is supported at a time.
+
+use core::cell::RefCell;
+use core::pin::pin;
+
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::mutex::Mutex as AsyncMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Instant, Timer};
+
+use log::{info, warn};
+
+use crate::data_model::cluster_basic_information::BasicInfoConfig;
+use crate::error::{Error, ErrorCode};
+use crate::utils::select::EitherUnwrap;
+
+use self::packet::HandshakeReq;
+use self::session::{Session, MAX_SEGMENT_SIZE};
+
+use super::network::{Address, NetworkReceive, NetworkSend};
+
+pub use super::network::BtAddr;
+
+pub mod packet;
+pub mod session;
+
+/// The 16-bit UUID of the Matter BLE service
+pub const MATTER_BLE_SERVICE_UUID16: u16 = 0xfff6;
+
+/// The UUID of characteristic C1, written by the central
+pub const C1_CHARACTERISTIC_UUID: u128 = 0x18ee2ef5_263d_4559_959f_4f9c429f9d11;
+
+/// The UUID of characteristic C2, indicated by the peripheral
+pub const C2_CHARACTERISTIC_UUID: u128 = 0x18ee2ef5_263d_4559_959f_4f9c429f9d12;
+
+/// The UUID of characteristic C3, carrying the additional commissioning data
+pub const C3_CHARACTERISTIC_UUID: u128 = 0x64630238_8772_45f2_b87d_748a83218f04;
+
+/// The service data advertised with the Matter BLE service UUID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvData([u8; 8]);
+
+impl AdvData {
+    pub fn new(dev_det: &BasicInfoConfig, discriminator: u16) -> Self {
+        let mut data = [0; 8];
+
+        // Opcode 0 (commissionable), followed by the 12-bit discriminator and advertisement version 0
+        data[1..3].copy_from_slice(&(discriminator & 0x0fff).to_le_bytes());
+        data[3..5].copy_from_slice(&dev_det.vid.to_le_bytes());
+        data[5..7].copy_from_slice(&dev_det.pid.to_le_bytes());
+        // No additional data in C3
+
+        Self(data)
+    }
+
+    pub fn service_data(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// An event reported by the GATT server of the platform
+#[derive(Debug)]
+pub enum GattPeripheralEvent<'a> {
+    /// The central subscribed for indications on C2
+    NotifySubscribed(BtAddr),
+    /// The central unsubscribed from C2, or disconnected
+    NotifyUnsubscribed(BtAddr),
+    /// The central wrote to C1
+    Write { address: BtAddr, data: &'a [u8] },
+}
+
+/// The GATT server of the platform, exposing the Matter BLE service
+pub trait GattPeripheral {
+    /// Advertises the Matter BLE service with the provided service data and serves the
+    /// C1, C2 and C3 characteristics, reporting all central activity to `callback`
+    async fn run<F>(&self, adv_data: &AdvData, callback: F) -> Result<(), Error>
+    where
+        F: Fn(GattPeripheralEvent);
+
+    /// Indicates `data` on C2 to the central with the provided address
+    async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error>;
+}
+
+impl<T> GattPeripheral for &T
+where
+    T: GattPeripheral,
+{
+    async fn run<F>(&self, adv_data: &AdvData, callback: F) -> Result<(), Error>
+    where
+        F: Fn(GattPeripheralEvent),
+    {
+        (*self).run(adv_data, callback).await
+    }
+
+    async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error> {
+        (*self).indicate(data, address).await
+    }
+}
+
+/// The BTP layer on top of a [`GattPeripheral`]
+pub struct Btp<M, T>
+where
+    M: RawMutex,
+{
+    gatt: T,
+    session: Mutex<M, RefCell<Option<Session>>>,
+    rx_notification: Signal<M, ()>,
+    tx_notification: Signal<M, ()>,
+    ctl_notification: Signal<M, ()>,
+    tx_lock: AsyncMutex<M, ()>,
+}
+
+impl<M, T> Btp<M, T>
+where
+    M: RawMutex,
+    T: GattPeripheral,
+{
+    pub const fn new(gatt: T) -> Self {
+        Self {
+            gatt,
+            session: Mutex::new(RefCell::new(None)),
+            rx_notification: Signal::new(),
+            tx_notification: Signal::new(),
+            ctl_notification: Signal::new(),
+            tx_lock: AsyncMutex::new(()),
+        }
+    }
+
+    /// Runs the GATT server, as well as the sending of the handshake responses and the standalone acknowledgements.
+    ///
+    /// Needs to run for as long as `Matter::run` runs with this BTP instance as its transport.
+    pub async fn run(&self, adv_data: &AdvData) -> Result<(), Error> {
+        let mut gatt = pin!(self.gatt.run(adv_data, |event| self.on_event(event)));
+        let mut ctl = pin!(self.run_ctl());
+
+        select(&mut gatt, &mut ctl).await.unwrap()
+    }
+
+    fn on_event(&self, event: GattPeripheralEvent) {
+        let result = self.session.lock(|session| {
+            let mut session = session.borrow_mut();
+
+            match event {
+                GattPeripheralEvent::Write { address, data }
+                    if HandshakeReq::is_handshake(data) =>
+                {
+                    if session.is_some() {
+                        warn!(
+                            "New BTP handshake from {}, dropping the active session",
+                            address
+                        );
+                    }
+
+                    *session = None;
+
+                    let req = HandshakeReq::decode(data)?;
+                    *session = Some(Session::new(address, &req)?);
+
+                    info!("BTP handshake request from {}", address);
+                }
+                GattPeripheralEvent::Write { address, data } => {
+                    let Some(current) = session.as_mut().filter(|s| s.address() == address) else {
+                        warn!("Dropping a BTP packet from {}: no session", address);
+                        return Ok(());
+                    };
+
+                    if let Err(e) = current.process_rx(Instant::now(), data) {
+                        *session = None;
+                        Err(e)?;
+                    }
+                }
+                GattPeripheralEvent::NotifySubscribed(address) => {
+                    if let Some(current) = session.as_mut().filter(|s| s.address() == address) {
+                        current.set_subscribed();
+                    }
+                }
+                GattPeripheralEvent::NotifyUnsubscribed(address) => {
+                    if session
+                        .as_ref()
+                        .filter(|s| s.address() == address)
+                        .is_some()
+                    {
+                        info!("BTP session with {} closed", address);
+                        *session = None;
+                    }
+                }
+            }
+
+            Ok::<_, Error>(())
+        });
+
+        if let Err(e) = result {
+            warn!("BTP session error: {:?}", e);
+        }
+
+        self.notify();
+    }
+
+    async fn run_ctl(&self) -> Result<(), Error> {
+        let mut buf = [0; MAX_SEGMENT_SIZE as usize];
+
+        loop {
+            let deadline = self
+                .session
+                .lock(|session| session.borrow().as_ref().and_then(|s| s.deadline()));
+
+            if let Some(deadline) = deadline {
+                select(self.ctl_notification.wait(), Timer::at(deadline)).await;
+            } else {
+                self.ctl_notification.wait().await;
+            }
+
+            let _guard = self.tx_lock.lock().await;
+
+            let now = Instant::now();
+
+            let prepared = self.session.lock(|session| {
+                let mut session = session.borrow_mut();
+
+                let Some(current) = session.as_mut() else {
+                    return Ok(None);
+                };
+
+                if current.is_peer_ack_timed_out(now) {
+                    warn!("BTP session with {} timed out", current.address());
+                    *session = None;
+                    return Ok(None);
+                }
+
+                let mut len = current.prep_handshake_resp(now, &mut buf)?;
+                if len == 0 && current.is_ack_due(now) {
+                    len = current.prep_ack(now, &mut buf)?;
+                }
+
+                Ok::<_, Error>((len > 0).then_some((len, current.address())))
+            });
+
+            match prepared {
+                Ok(Some((len, address))) => {
+                    if let Err(e) = self.gatt.indicate(&buf[..len], address).await {
+                        warn!("Indicating to {} failed: {:?}", address, e);
+                    }
+
+                    // There might be more to send, check again
+                    self.ctl_notification.signal(());
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    warn!("BTP session error: {:?}", e);
+                    self.close();
+                }
+            }
+
+            // The window of the peer might have changed
+            self.tx_notification.signal(());
+            self.rx_notification.signal(());
+        }
+    }
+
+    fn close(&self) {
+        self.session.lock(|session| *session.borrow_mut() = None);
+        self.notify();
+    }
+
+    fn notify(&self) {
+        self.rx_notification.signal(());
+        self.tx_notification.signal(());
+        self.ctl_notification.signal(());
+    }
+}
+
+impl<M, T> NetworkSend for &Btp<M, T>
+where
+    M: RawMutex,
+    T: GattPeripheral,
+{
+    async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
+        let address = addr.btp().ok_or(ErrorCode::InvalidPeerAddr)?;
+
+        let mut buf = [0; MAX_SEGMENT_SIZE as usize];
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let guard = self.tx_lock.lock().await;
+
+            let prepared = self.session.lock(|session| {
+                let mut session = session.borrow_mut();
+
+                let current = session
+                    .as_mut()
+                    .filter(|s| s.address() == address)
+                    .ok_or(ErrorCode::NoSession)?;
+
+                if current.can_send() {
+                    current
+                        .prep_tx(Instant::now(), data, offset, &mut buf)
+                        .map(Some)
+                } else {
+                    Ok(None)
+                }
+            })?;
+
+            if let Some((len, payload_len)) = prepared {
+                self.gatt.indicate(&buf[..len], address).await?;
+                offset += payload_len;
+
+                // Re-schedule the acknowledgement timers
+                self.ctl_notification.signal(());
+            } else {
+                drop(guard);
+                self.tx_notification.wait().await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<M, T> NetworkReceive for &Btp<M, T>
+where
+    M: RawMutex,
+    T: GattPeripheral,
+{
+    async fn wait_available(&mut self) -> Result<(), Error> {
+        loop {
+            let available = self.session.lock(|session| {
+                session
+                    .borrow()
+                    .as_ref()
+                    .map(|s| s.is_rx_available())
+                    .unwrap_or(false)
+            });
+
+            if available {
+                break Ok(());
+            }
+
+            self.rx_notification.wait().await;
+        }
+    }
+
+    async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+        loop {
+            self.wait_available().await?;
+
+            let received = self.session.lock(|session| {
+                let mut session = session.borrow_mut();
+
+                if let Some(current) = session.as_mut() {
+                    Ok::<_, Error>(
+                        current
+                            .take_rx(buffer)?
+                            .map(|len| (len, Address::Btp(current.address()))),
+                    )
+                } else {
+                    Ok(None)
+                }
+            })?;
+
+            if let Some(received) = received {
+                // The peer might be waiting for an acknowledgement
+                self.ctl_notification.signal(());
+
+                break Ok(received);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::cluster_basic_information::BasicInfoConfig;
+
+    use super::AdvData;
+
+    #[test]
+    fn test_adv_data() {
+        let dev_det = BasicInfoConfig {
+            vid: 0xfff1,
+            pid: 0x8000,
+            hw_ver: 1,
+            sw_ver: 1,
+            sw_ver_str: "1",
+            serial_no: "aabbccdd",
+            device_name: "OnOff Light",
+            product_name: "Light123",
+            vendor_name: "Vendor PQR",
+        };
+
+        let adv_data = AdvData::new(&dev_det, 3840);
+
+        assert_eq!(
+            adv_data.service_data(),
+            &[0x00, 0x00, 0x0f, 0xf1, 0xff, 0x00, 0x80, 0x00]
+        );
+    }
+}
@@ -0,0 +1,267 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use bitflags::bitflags;
+
+use crate::error::{Error, ErrorCode};
+use crate::utils::writebuf::WriteBuf;
+
+/// The only BTP version supported by Matter
+pub const BTP_VERSION: u8 = 4;
+
+/// The management opcode of the handshake request and response
+pub const HANDSHAKE_OPCODE: u8 = 0x6c;
+
+bitflags! {
+    #[repr(transparent)]
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct HdrFlags: u8 {
+        const HANDSHAKE = 0x40;
+        const MANAGEMENT = 0x20;
+        const ACK = 0x08;
+        const ENDING_SEGMENT = 0x04;
+        const CONTINUING_SEGMENT = 0x02;
+        const BEGINNING_SEGMENT = 0x01;
+    }
+}
+
+const HANDSHAKE_FLAGS: HdrFlags = HdrFlags::HANDSHAKE
+    .union(HdrFlags::MANAGEMENT)
+    .union(HdrFlags::ENDING_SEGMENT)
+    .union(HdrFlags::BEGINNING_SEGMENT);
+
+fn byte(data: &[u8], offset: usize) -> Result<u8, Error> {
+    data.get(offset)
+        .copied()
+        .ok_or_else(|| ErrorCode::TruncatedPacket.into())
+}
+
+fn le_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    Ok(u16::from_le_bytes([
+        byte(data, offset)?,
+        byte(data, offset + 1)?,
+    ]))
+}
+
+/// The handshake request, written by the central to characteristic C1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeReq {
+    /// Up to 8 supported versions, one per nibble
+    pub versions: [u8; 4],
+    /// The ATT_MTU of the connection, or 0 if unknown to the central
+    pub mtu: u16,
+    pub window_size: u8,
+}
+
+impl HandshakeReq {
+    pub fn is_handshake(data: &[u8]) -> bool {
+        data.first()
+            .map(|flags| HdrFlags::from_bits_truncate(*flags).contains(HdrFlags::HANDSHAKE))
+            .unwrap_or(false)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        if byte(data, 0)? != HANDSHAKE_FLAGS.bits() || byte(data, 1)? != HANDSHAKE_OPCODE {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        Ok(Self {
+            versions: [
+                byte(data, 2)?,
+                byte(data, 3)?,
+                byte(data, 4)?,
+                byte(data, 5)?,
+            ],
+            mtu: le_u16(data, 6)?,
+            window_size: byte(data, 8)?,
+        })
+    }
+
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u8(HANDSHAKE_FLAGS.bits())?;
+        wb.le_u8(HANDSHAKE_OPCODE)?;
+        wb.append(&self.versions)?;
+        wb.le_u16(self.mtu)?;
+        wb.le_u8(self.window_size)
+    }
+
+    pub fn supports(&self, version: u8) -> bool {
+        self.versions
+            .iter()
+            .any(|v| (v & 0x0f) == version || (v >> 4) == version)
+    }
+}
+
+/// The handshake response, indicated by the peripheral on characteristic C2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeResp {
+    pub version: u8,
+    pub segment_size: u16,
+    pub window_size: u8,
+}
+
+impl HandshakeResp {
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        if byte(data, 0)? != HANDSHAKE_FLAGS.bits() || byte(data, 1)? != HANDSHAKE_OPCODE {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        Ok(Self {
+            version: byte(data, 2)? & 0x0f,
+            segment_size: le_u16(data, 3)?,
+            window_size: byte(data, 5)?,
+        })
+    }
+
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u8(HANDSHAKE_FLAGS.bits())?;
+        wb.le_u8(HANDSHAKE_OPCODE)?;
+        wb.le_u8(self.version & 0x0f)?;
+        wb.le_u16(self.segment_size)?;
+        wb.le_u8(self.window_size)
+    }
+}
+
+/// The header of a BTP data or standalone ack packet
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BtpHdr {
+    pub flags: HdrFlags,
+    pub ack_num: Option<u8>,
+    pub seq_num: u8,
+    /// The length of the whole message; only present in its first segment
+    pub msg_len: Option<u16>,
+}
+
+impl BtpHdr {
+    /// Decodes the header, returning it together with the payload of the segment
+    pub fn decode(data: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let flags = HdrFlags::from_bits(byte(data, 0)?).ok_or(ErrorCode::InvalidData)?;
+        if flags.intersects(HdrFlags::HANDSHAKE | HdrFlags::MANAGEMENT) {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        let mut offset = 1;
+
+        let ack_num = if flags.contains(HdrFlags::ACK) {
+            offset += 1;
+            Some(byte(data, offset - 1)?)
+        } else {
+            None
+        };
+
+        let seq_num = byte(data, offset)?;
+        offset += 1;
+
+        let msg_len = if flags.contains(HdrFlags::BEGINNING_SEGMENT) {
+            offset += 2;
+            Some(le_u16(data, offset - 2)?)
+        } else {
+            None
+        };
+
+        let hdr = Self {
+            flags,
+            ack_num,
+            seq_num,
+            msg_len,
+        };
+
+        Ok((hdr, &data[offset..]))
+    }
+
+    pub fn encode(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u8(self.flags.bits())?;
+        if let Some(ack_num) = self.ack_num {
+            wb.le_u8(ack_num)?;
+        }
+        wb.le_u8(self.seq_num)?;
+        if let Some(msg_len) = self.msg_len {
+            wb.le_u16(msg_len)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        2 + if self.ack_num.is_some() { 1 } else { 0 } + if self.msg_len.is_some() { 2 } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::writebuf::WriteBuf;
+
+    use super::{BtpHdr, HandshakeReq, HandshakeResp, HdrFlags, BTP_VERSION};
+
+    #[test]
+    fn test_handshake() {
+        // A handshake request as sent by chip-tool
+        let data = [0x65, 0x6c, 0x04, 0x00, 0x00, 0x00, 0xf7, 0x00, 0x06];
+
+        assert!(HandshakeReq::is_handshake(&data));
+
+        let req = HandshakeReq::decode(&data).unwrap();
+        assert!(req.supports(BTP_VERSION));
+        assert!(!req.supports(3));
+        assert_eq!(req.mtu, 247);
+        assert_eq!(req.window_size, 6);
+
+        let mut buf = [0; 16];
+        let mut wb = WriteBuf::new(&mut buf);
+        req.encode(&mut wb).unwrap();
+        assert_eq!(wb.as_slice(), &data);
+
+        let resp = HandshakeResp {
+            version: BTP_VERSION,
+            segment_size: 244,
+            window_size: 6,
+        };
+
+        let mut buf = [0; 16];
+        let mut wb = WriteBuf::new(&mut buf);
+        resp.encode(&mut wb).unwrap();
+        assert_eq!(wb.as_slice(), &[0x65, 0x6c, 0x04, 0xf4, 0x00, 0x06]);
+        assert_eq!(HandshakeResp::decode(wb.as_slice()).unwrap(), resp);
+    }
+
+    #[test]
+    fn test_hdr() {
+        let hdr = BtpHdr {
+            flags: HdrFlags::ACK | HdrFlags::BEGINNING_SEGMENT,
+            ack_num: Some(3),
+            seq_num: 4,
+            msg_len: Some(300),
+        };
+
+        let mut buf = [0; 16];
+        let mut wb = WriteBuf::new(&mut buf);
+        hdr.encode(&mut wb).unwrap();
+        wb.append(&[0xaa, 0xbb]).unwrap();
+
+        assert_eq!(wb.as_slice(), &[0x09, 3, 4, 0x2c, 0x01, 0xaa, 0xbb]);
+        assert_eq!(hdr.encoded_len(), 5);
+
+        let (decoded, payload) = BtpHdr::decode(wb.as_slice()).unwrap();
+        assert_eq!(decoded, hdr);
+        assert_eq!(payload, &[0xaa, 0xbb]);
+
+        // Truncated
+        assert!(BtpHdr::decode(&[0x09, 3]).is_err());
+        // Handshake packets are not data packets
+        assert!(BtpHdr::decode(&[0x65, 0x6c, 0x04]).is_err());
+    }
+}
@@ -0,0 +1,601 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::cmp::{max, min};
+
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use log::{error, warn};
+
+use crate::error::{Error, ErrorCode};
+use crate::transport::packet::MAX_RX_MTU_SIZE;
+use crate::utils::writebuf::WriteBuf;
+
+use super::packet::{BtpHdr, HandshakeReq, HandshakeResp, HdrFlags, BTP_VERSION};
+use super::BtAddr;
+
+/// The size of the ATT header, which is not available for BTP segments
+const ATT_HDR_SIZE: u16 = 3;
+
+/// The minimum ATT_MTU of any BLE connection
+const MIN_MTU: u16 = 23;
+
+/// The largest segment size this implementation supports
+pub const MAX_SEGMENT_SIZE: u16 = 244;
+
+/// The largest receive window this implementation supports
+pub const MAX_WINDOW_SIZE: u8 = 6;
+
+/// A pending acknowledgement must be sent within this time, even if there is no data to piggyback it on
+pub const ACK_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// A sent packet must be acknowledged by the peer within this time, or else the session is closed
+pub const PEER_ACK_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The handshake request was received, but the peer is not subscribed to C2 yet
+    HandshakeReceived,
+    /// The peer is subscribed to C2 and the handshake response should be sent
+    Subscribed,
+    /// The handshake is complete
+    Connected,
+}
+
+/// The state of a BTP session with a single central.
+///
+/// The session does not do any I/O; it only processes the segments written by the peer
+/// and prepares the segments which should be indicated to it.
+#[derive(Debug)]
+pub struct Session {
+    address: BtAddr,
+    state: State,
+    segment_size: u16,
+    window_size: u8,
+    // The sequence number expected in the next packet from the peer
+    rx_next_seq: u8,
+    // The newest received sequence number which we did not acknowledge yet
+    rx_ack: Option<(u8, Instant)>,
+    rx_unacked: u8,
+    rx_msg_len: Option<usize>,
+    rx_complete: bool,
+    rx_buf: Vec<u8, MAX_RX_MTU_SIZE>,
+    // The sequence number of the next packet we send
+    tx_next_seq: u8,
+    // The newest sequence number acknowledged by the peer
+    tx_acked: u8,
+    // When did the peer last acknowledge our packets, if we are waiting for an acknowledgement
+    tx_unacked_since: Option<Instant>,
+}
+
+impl Session {
+    pub fn new(address: BtAddr, req: &HandshakeReq) -> Result<Self, Error> {
+        if !req.supports(BTP_VERSION) {
+            warn!(
+                "Peer {} does not support BTP version {}",
+                address, BTP_VERSION
+            );
+            Err(ErrorCode::Invalid)?;
+        }
+
+        if req.window_size == 0 {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        let mtu = if req.mtu == 0 { MIN_MTU } else { req.mtu };
+
+        Ok(Self {
+            address,
+            state: State::HandshakeReceived,
+            segment_size: min(max(mtu, MIN_MTU) - ATT_HDR_SIZE, MAX_SEGMENT_SIZE),
+            window_size: min(req.window_size, MAX_WINDOW_SIZE),
+            rx_next_seq: 0,
+            rx_ack: None,
+            rx_unacked: 0,
+            rx_msg_len: None,
+            rx_complete: false,
+            rx_buf: Vec::new(),
+            tx_next_seq: 0,
+            tx_acked: u8::MAX,
+            tx_unacked_since: None,
+        })
+    }
+
+    pub fn address(&self) -> BtAddr {
+        self.address
+    }
+
+    pub fn segment_size(&self) -> usize {
+        self.segment_size as _
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == State::Connected
+    }
+
+    pub fn set_subscribed(&mut self) {
+        if self.state == State::HandshakeReceived {
+            self.state = State::Subscribed;
+        }
+    }
+
+    /// Prepares the handshake response, if the peer has subscribed and the response is not sent yet
+    pub fn prep_handshake_resp(&mut self, now: Instant, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.state != State::Subscribed {
+            return Ok(0);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        HandshakeResp {
+            version: BTP_VERSION,
+            segment_size: self.segment_size,
+            window_size: self.window_size,
+        }
+        .encode(&mut wb)?;
+
+        // The handshake response implicitly carries sequence number 0, which the peer must acknowledge
+        self.consume_tx_seq(now);
+        self.state = State::Connected;
+
+        Ok(wb.get_tail())
+    }
+
+    /// Processes a packet written by the peer to C1
+    pub fn process_rx(&mut self, now: Instant, data: &[u8]) -> Result<(), Error> {
+        if !self.is_connected() {
+            error!(
+                "Packet received from {} before the handshake completed",
+                self.address
+            );
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        let (hdr, payload) = BtpHdr::decode(data)?;
+
+        if hdr.seq_num != self.rx_next_seq {
+            error!(
+                "Unexpected sequence number from {}: received {}, expected {}",
+                self.address, hdr.seq_num, self.rx_next_seq
+            );
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        if let Some(ack_num) = hdr.ack_num {
+            let acked = ack_num.wrapping_sub(self.tx_acked);
+            if acked == 0 || acked > self.tx_in_flight() {
+                error!("Invalid acknowledgement from {}: {}", self.address, ack_num);
+                Err(ErrorCode::InvalidData)?;
+            }
+
+            self.tx_acked = ack_num;
+            self.tx_unacked_since = (self.tx_in_flight() > 0).then_some(now);
+        }
+
+        self.rx_next_seq = self.rx_next_seq.wrapping_add(1);
+        self.rx_ack = Some((
+            hdr.seq_num,
+            self.rx_ack.map(|(_, since)| since).unwrap_or(now),
+        ));
+        self.rx_unacked = self.rx_unacked.saturating_add(1);
+
+        self.reassemble(&hdr, payload)
+    }
+
+    fn reassemble(&mut self, hdr: &BtpHdr, payload: &[u8]) -> Result<(), Error> {
+        if let Some(msg_len) = hdr.msg_len {
+            if self.rx_msg_len.is_some() || self.rx_complete {
+                error!(
+                    "New message from {} while the previous one is still pending",
+                    self.address
+                );
+                Err(ErrorCode::InvalidState)?;
+            }
+
+            if msg_len as usize > self.rx_buf.capacity() {
+                error!(
+                    "Message from {} is too large: {} bytes",
+                    self.address, msg_len
+                );
+                Err(ErrorCode::NoSpace)?;
+            }
+
+            self.rx_buf.clear();
+            self.rx_msg_len = Some(msg_len as _);
+        } else if hdr
+            .flags
+            .intersects(HdrFlags::CONTINUING_SEGMENT | HdrFlags::ENDING_SEGMENT)
+            && self.rx_msg_len.is_none()
+        {
+            error!(
+                "Continuing segment from {} without a beginning",
+                self.address
+            );
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        let Some(msg_len) = self.rx_msg_len else {
+            // A standalone acknowledgement
+            return Ok(());
+        };
+
+        if self.rx_buf.len() + payload.len() > msg_len {
+            error!("Segment from {} exceeds the message length", self.address);
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        self.rx_buf
+            .extend_from_slice(payload)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        if hdr.flags.contains(HdrFlags::ENDING_SEGMENT) {
+            if self.rx_buf.len() != msg_len {
+                error!("Message from {} is truncated", self.address);
+                Err(ErrorCode::TruncatedPacket)?;
+            }
+
+            self.rx_msg_len = None;
+            self.rx_complete = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_rx_available(&self) -> bool {
+        self.rx_complete
+    }
+
+    /// Copies the reassembled message into `buf`
+    pub fn take_rx(&mut self, buf: &mut [u8]) -> Result<Option<usize>, Error> {
+        if !self.rx_complete {
+            return Ok(None);
+        }
+
+        let len = self.rx_buf.len();
+        if len > buf.len() {
+            Err(ErrorCode::NoSpace)?;
+        }
+
+        buf[..len].copy_from_slice(&self.rx_buf);
+
+        self.rx_buf.clear();
+        self.rx_complete = false;
+
+        Ok(Some(len))
+    }
+
+    /// The number of sent packets which are not acknowledged by the peer yet
+    fn tx_in_flight(&self) -> u8 {
+        self.tx_next_seq.wrapping_sub(self.tx_acked).wrapping_sub(1)
+    }
+
+    fn consume_tx_seq(&mut self, now: Instant) -> u8 {
+        let seq_num = self.tx_next_seq;
+
+        self.tx_next_seq = self.tx_next_seq.wrapping_add(1);
+        if self.tx_unacked_since.is_none() {
+            self.tx_unacked_since = Some(now);
+        }
+
+        seq_num
+    }
+
+    fn take_ack(&mut self) -> Option<u8> {
+        self.rx_unacked = 0;
+        self.rx_ack.take().map(|(seq_num, _)| seq_num)
+    }
+
+    /// Whether the peer's receive window allows us to send a data segment.
+    /// The last slot of the window is reserved for packets which also acknowledge the peer.
+    pub fn can_send(&self) -> bool {
+        if !self.is_connected() {
+            return false;
+        }
+
+        let remaining = self.window_size.saturating_sub(self.tx_in_flight());
+
+        remaining > 1 || (remaining == 1 && self.rx_ack.is_some())
+    }
+
+    /// Whether a standalone acknowledgement should be sent now.
+    /// This is the case when our receive window is about to close, or when the pending acknowledgement
+    /// is getting too old.
+    pub fn is_ack_due(&self, now: Instant) -> bool {
+        match self.rx_ack {
+            Some((_, since)) => {
+                self.rx_unacked + 1 >= self.window_size
+                    || now.saturating_duration_since(since) >= ACK_TIMEOUT
+            }
+            None => false,
+        }
+    }
+
+    /// The next time the session needs attention if nothing is received in the meantime
+    pub fn deadline(&self) -> Option<Instant> {
+        let ack = self.rx_ack.map(|(_, since)| since + ACK_TIMEOUT);
+        let peer_ack = self.tx_unacked_since.map(|since| since + PEER_ACK_TIMEOUT);
+
+        match (ack, peer_ack) {
+            (Some(ack), Some(peer_ack)) => Some(min(ack, peer_ack)),
+            (ack, peer_ack) => ack.or(peer_ack),
+        }
+    }
+
+    /// Whether the peer failed to acknowledge our packets in time
+    pub fn is_peer_ack_timed_out(&self, now: Instant) -> bool {
+        self.tx_unacked_since
+            .map(|since| now.saturating_duration_since(since) >= PEER_ACK_TIMEOUT)
+            .unwrap_or(false)
+    }
+
+    /// Prepares a standalone acknowledgement
+    pub fn prep_ack(&mut self, now: Instant, buf: &mut [u8]) -> Result<usize, Error> {
+        let ack_num = self.take_ack().ok_or(ErrorCode::InvalidState)?;
+
+        let hdr = BtpHdr {
+            flags: HdrFlags::ACK,
+            ack_num: Some(ack_num),
+            seq_num: self.consume_tx_seq(now),
+            msg_len: None,
+        };
+
+        let mut wb = WriteBuf::new(buf);
+        hdr.encode(&mut wb)?;
+
+        Ok(wb.get_tail())
+    }
+
+    /// Prepares the next segment of `msg`, starting at `offset`.
+    ///
+    /// Returns the length of the segment and the number of message bytes it carries.
+    pub fn prep_tx(
+        &mut self,
+        now: Instant,
+        msg: &[u8],
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        if !self.can_send() {
+            Err(ErrorCode::Busy)?;
+        }
+
+        let mut flags = if offset == 0 {
+            HdrFlags::BEGINNING_SEGMENT
+        } else {
+            HdrFlags::CONTINUING_SEGMENT
+        };
+
+        let msg_len = if offset == 0 {
+            Some(u16::try_from(msg.len()).map_err(|_| ErrorCode::NoSpace)?)
+        } else {
+            None
+        };
+
+        let ack_num = self.take_ack();
+        if ack_num.is_some() {
+            flags |= HdrFlags::ACK;
+        }
+
+        let mut hdr = BtpHdr {
+            flags,
+            ack_num,
+            seq_num: 0,
+            msg_len,
+        };
+
+        let payload_len = min(
+            msg.len() - offset,
+            min(self.segment_size(), buf.len()) - hdr.encoded_len(),
+        );
+
+        if offset + payload_len == msg.len() {
+            hdr.flags.remove(HdrFlags::CONTINUING_SEGMENT);
+            hdr.flags |= HdrFlags::ENDING_SEGMENT;
+        }
+
+        hdr.seq_num = self.consume_tx_seq(now);
+
+        let mut wb = WriteBuf::new(buf);
+        hdr.encode(&mut wb)?;
+        wb.append(&msg[offset..offset + payload_len])?;
+
+        Ok((wb.get_tail(), payload_len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_time::{Duration, Instant};
+
+    use crate::transport::btp::packet::{BtpHdr, HandshakeReq, HandshakeResp, HdrFlags};
+    use crate::transport::btp::BtAddr;
+    use crate::utils::writebuf::WriteBuf;
+
+    use super::{Session, ACK_TIMEOUT, PEER_ACK_TIMEOUT};
+
+    const PEER: BtAddr = BtAddr([1, 2, 3, 4, 5, 6]);
+
+    fn connect(mtu: u16, window_size: u8) -> Session {
+        let req = HandshakeReq {
+            versions: [0x04, 0, 0, 0],
+            mtu,
+            window_size,
+        };
+
+        let mut session = Session::new(PEER, &req).unwrap();
+
+        let mut buf = [0; 16];
+        assert_eq!(
+            session
+                .prep_handshake_resp(Instant::from_millis(0), &mut buf)
+                .unwrap(),
+            0
+        );
+
+        session.set_subscribed();
+        let len = session
+            .prep_handshake_resp(Instant::from_millis(0), &mut buf)
+            .unwrap();
+
+        let resp = HandshakeResp::decode(&buf[..len]).unwrap();
+        assert_eq!(resp.segment_size, mtu - 3);
+        assert!(session.is_connected());
+
+        session
+    }
+
+    fn segment(hdr: BtpHdr, payload: &[u8]) -> ([u8; 64], usize) {
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        hdr.encode(&mut wb).unwrap();
+        wb.append(payload).unwrap();
+        let len = wb.get_tail();
+
+        (buf, len)
+    }
+
+    #[test]
+    fn test_reassembly() {
+        let mut session = connect(23, 4);
+        let now = Instant::from_millis(10);
+
+        let (buf, len) = segment(
+            BtpHdr {
+                flags: HdrFlags::BEGINNING_SEGMENT | HdrFlags::ACK,
+                ack_num: Some(0),
+                seq_num: 0,
+                msg_len: Some(6),
+            },
+            &[1, 2, 3, 4],
+        );
+        session.process_rx(now, &buf[..len]).unwrap();
+        assert!(!session.is_rx_available());
+
+        let (buf, len) = segment(
+            BtpHdr {
+                flags: HdrFlags::ENDING_SEGMENT,
+                ack_num: None,
+                seq_num: 1,
+                msg_len: None,
+            },
+            &[5, 6],
+        );
+        session.process_rx(now, &buf[..len]).unwrap();
+        assert!(session.is_rx_available());
+
+        let mut msg = [0; 16];
+        assert_eq!(session.take_rx(&mut msg).unwrap(), Some(6));
+        assert_eq!(&msg[..6], &[1, 2, 3, 4, 5, 6]);
+        assert!(!session.is_rx_available());
+
+        // Out of sequence
+        let (buf, len) = segment(
+            BtpHdr {
+                flags: HdrFlags::BEGINNING_SEGMENT | HdrFlags::ENDING_SEGMENT,
+                ack_num: None,
+                seq_num: 5,
+                msg_len: Some(1),
+            },
+            &[1],
+        );
+        assert!(session.process_rx(now, &buf[..len]).is_err());
+    }
+
+    #[test]
+    fn test_segmentation() {
+        let mut session = connect(23, 6);
+        let now = Instant::from_millis(10);
+
+        let msg = [0x5a; 40];
+        let mut offset = 0;
+        let mut segments = 0;
+
+        while offset < msg.len() {
+            let mut buf = [0; 64];
+            let (len, payload_len) = session.prep_tx(now, &msg, offset, &mut buf).unwrap();
+            assert!(len <= session.segment_size());
+
+            let (hdr, payload) = BtpHdr::decode(&buf[..len]).unwrap();
+            assert_eq!(hdr.flags.contains(HdrFlags::BEGINNING_SEGMENT), offset == 0);
+            assert_eq!(
+                hdr.flags.contains(HdrFlags::ENDING_SEGMENT),
+                offset + payload_len == msg.len()
+            );
+            assert_eq!(payload.len(), payload_len);
+
+            offset += payload_len;
+            segments += 1;
+        }
+
+        // 20-byte segments: 16 + 18 + 6 bytes of payload
+        assert_eq!(segments, 3);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut session = connect(23, 3);
+        let now = Instant::from_millis(10);
+
+        let mut buf = [0; 64];
+
+        // The handshake response is in flight, so only one more packet fits in the window
+        assert!(session.can_send());
+        session.prep_tx(now, &[1], 0, &mut buf).unwrap();
+        assert!(!session.can_send());
+        assert!(session.prep_tx(now, &[1], 0, &mut buf).is_err());
+
+        // The peer acknowledges both packets with a standalone ack
+        let (ack, len) = segment(
+            BtpHdr {
+                flags: HdrFlags::ACK,
+                ack_num: Some(1),
+                seq_num: 0,
+                msg_len: None,
+            },
+            &[],
+        );
+        session.process_rx(now, &ack[..len]).unwrap();
+        assert!(session.can_send());
+        assert!(!session.is_rx_available());
+
+        // The standalone ack of the peer needs to be acknowledged in time
+        assert!(!session.is_ack_due(now));
+        assert!(session.is_ack_due(now + ACK_TIMEOUT));
+
+        let len = session.prep_ack(now, &mut buf).unwrap();
+        let (hdr, _) = BtpHdr::decode(&buf[..len]).unwrap();
+        assert_eq!(hdr.ack_num, Some(0));
+        assert_eq!(hdr.seq_num, 2);
+        assert!(!session.is_ack_due(now + ACK_TIMEOUT));
+
+        // ... and our standalone ack needs to be acknowledged in turn
+        assert!(!session.is_peer_ack_timed_out(now + Duration::from_secs(1)));
+        assert!(session.is_peer_ack_timed_out(now + PEER_ACK_TIMEOUT));
+
+        // Acknowledging a packet which was never sent is an error
+        let (ack, len) = segment(
+            BtpHdr {
+                flags: HdrFlags::ACK,
+                ack_num: Some(5),
+                seq_num: 1,
+                msg_len: None,
+            },
+            &[],
+        );
+        assert!(session.process_rx(now, &ack[..len]).is_err());
+    }
+}
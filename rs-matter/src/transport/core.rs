@@ -15,11 +15,9 @@
  *    limitations under the License.
  */
 
-use core::borrow::Borrow;
-use core::mem::MaybeUninit;
 use core::pin::pin;
 
-use embassy_futures::select::{select, select_slice, Either};
+use embassy_futures::select::{select, select3, select_slice};
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, channel::Channel};
 use embassy_time::{Duration, Timer};
 
@@ -29,14 +27,18 @@ use crate::interaction_model::core::IMStatusCode;
 use crate::mdns::Mdns;
 use crate::secure_channel::common::SCStatusCodes;
 use crate::secure_channel::status_report::{create_status_report, GeneralCode};
-use crate::utils::buf::BufferAccess;
+use crate::utils::buf::{BufferAccess, BufferPool};
+use crate::utils::epoch::Epoch;
 use crate::utils::select::Notification;
 use crate::{
     alloc,
+    binding::Binding,
     data_model::{core::DataModel, objects::DataModelHandler},
     error::{Error, ErrorCode},
+    group_keys::group_multicast_addr,
     interaction_model::core::PROTO_ID_INTERACTION_MODEL,
     secure_channel::{
+        check_in::CheckIn,
         common::{OpCode, PROTO_ID_SECURE_CHANNEL},
         core::SecureChannel,
     },
@@ -50,39 +52,147 @@ use super::{
         Exchange, ExchangeCtr, ExchangeCtx, ExchangeId, ExchangeState, Role, SessionId,
         MAX_EXCHANGES,
     },
-    mrp::ReliableMessage,
-    network::{Ipv6Addr, NetworkReceive, NetworkSend, SocketAddr, SocketAddrV6},
+    mrp::{MrpParams, ReliableMessage},
+    network::{
+        Address, Ipv4Addr, Ipv6Addr, NetworkReceive, NetworkSend, SocketAddr, SocketAddrV4,
+        SocketAddrV6,
+    },
     packet::{MAX_RX_BUF_SIZE, MAX_RX_STATUS_BUF_SIZE, MAX_TX_BUF_SIZE},
+    session::{EvictionPolicy, LruEviction, Session, SessionInfo, SessionMgr},
 };
 
+/// The upper bound of the minimum wait time advertised in the Busy status reports
+const MAX_BUSY_WAIT_TIME: core::time::Duration = core::time::Duration::from_secs(5);
+
+/// How long a shutdown waits for the pending acks and retransmissions to go out
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The address to bind the Matter UDP socket to. On most platforms, the socket is dual-stack
+/// and receives both IPv6 and IPv4 packets.
 pub const MATTER_SOCKET_BIND_ADDR: SocketAddr =
     SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, MATTER_PORT, 0, 0));
 
-type TxBuf = MaybeUninit<[u8; MAX_TX_BUF_SIZE]>;
-type RxBuf = MaybeUninit<[u8; MAX_RX_BUF_SIZE]>;
-type SxBuf = MaybeUninit<[u8; MAX_RX_STATUS_BUF_SIZE]>;
+/// The address to bind the Matter UDP socket to, on networks or platforms without IPv6
+pub const MATTER_SOCKET_BIND_ADDR_IPV4: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MATTER_PORT));
+
+/// The direction of a packet reported to a [`PacketObserver`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PacketDirection {
+    Rx,
+    Tx,
+}
+
+/// An observer of all packets exchanged by the stack, e.g. for capturing them in a pcap file
+/// with `transport::pcap::PcapWriter` (`std` only). Set with [`Matter::set_packet_observer`].
+///
+/// Received packets are reported right after being decrypted, and packets to be sent right
+/// before being encrypted, so the observer gets the plain text of every message: its headers
+/// in [`Packet::plain`] and [`Packet::proto`], and its payload in [`Packet::as_slice`].
+/// Retransmissions are not reported again.
+pub trait PacketObserver {
+    fn observe(&self, direction: PacketDirection, packet: &Packet<'_>);
+}
+
+impl<T> PacketObserver for &T
+where
+    T: PacketObserver,
+{
+    fn observe(&self, direction: PacketDirection, packet: &Packet<'_>) {
+        (*self).observe(direction, packet)
+    }
+}
+
+/// Counters of the transport, accumulated since the creation of the [`Matter`] object
+/// or the last call to [`Matter::reset_transport_stats`]. Returned by
+/// [`Matter::transport_stats`], e.g. for feeding the diagnostics clusters or the telemetry
+/// of the application.
+///
+/// All counters wrap around on overflow.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TransportStats {
+    /// Packets received from the network, including the ones dropped afterwards
+    pub rx_packets: u32,
+    /// Packets sent to the network, including retransmissions and standalone acks
+    pub tx_packets: u32,
+    /// MRP retransmissions of unacknowledged messages
+    pub retransmissions: u32,
+    /// Received messages dropped as duplicates of already-received ones
+    pub duplicates_dropped: u32,
+    /// Sessions evicted to make room for new ones
+    pub sessions_evicted: u32,
+    /// Secure sessions closed because their message counter was close to exhaustion
+    pub sessions_exhausted: u32,
+    /// Busy status reports sent because all exchanges were occupied
+    pub busy_sent: u32,
+    /// Busy status reports sent in response to CASE session establishments, because all
+    /// session slots were taken and none could be evicted. If this grows, the session capacity
+    /// of the [`Matter`] object is likely too small for the number of peers of the node
+    pub case_busy_sent: u32,
+}
+
+impl TransportStats {
+    pub const fn new() -> Self {
+        Self {
+            rx_packets: 0,
+            tx_packets: 0,
+            retransmissions: 0,
+            duplicates_dropped: 0,
+            sessions_evicted: 0,
+            sessions_exhausted: 0,
+            busy_sent: 0,
+            case_busy_sent: 0,
+        }
+    }
+}
 
-pub struct PacketBuffers {
-    tx: [TxBuf; MAX_EXCHANGES],
-    rx: [RxBuf; MAX_EXCHANGES],
-    sx: [SxBuf; MAX_EXCHANGES + 1],
+/// The urgency of a packet pending in an exchange, from the least to the most urgent.
+///
+/// Acks and status reports are small and complete (or unblock) an exchange on the peer side,
+/// so they are sent before any data. Due retransmissions are favored over fresh data as well,
+/// as they are closer to failing the exchange with an MRP timeout.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum TxPriority {
+    Data,
+    Retransmission,
+    Status,
+    Ack,
 }
 
-impl PacketBuffers {
-    const TX_ELEM: TxBuf = MaybeUninit::uninit();
-    const RX_ELEM: RxBuf = MaybeUninit::uninit();
-    const SX_ELEM: SxBuf = MaybeUninit::uninit();
+/// The queue over which [`Matter::run_transport`] hands over newly-created exchanges
+/// to the exchange handlers run by [`Matter::run_handlers`].
+///
+/// The queue is guarded by a `NoopRawMutex` on purpose: the exchanges it carries borrow the
+/// `Matter` object, which keeps its state in `RefCell`s and is not `Sync`. The exchanges are
+/// thus not `Send`, and a `Sync` raw mutex - like `CriticalSectionRawMutex` - would not make
+/// the queue shareable between threads either.
+pub type ExchangeQueue<'a> = Channel<NoopRawMutex, ExchangeCtr<'a>, 1>;
+
+/// The packet buffers of `N` exchange handlers.
+///
+/// Each exchange handler processes one exchange at a time, so `N` is the number of exchanges
+/// which can be processed concurrently. Since each handler needs its own set of buffers
+/// (more than 2.5KB, or 128KB with the `large-payload` feature), memory-constrained devices
+/// might want to run with fewer handlers than the exchange capacity of the [`Matter`] object -
+/// the maximum number of exchanges the transport can track.
+/// Running with more handlers than that is of no use, as the extra ones would never get an
+/// exchange to process.
+pub struct ExchangeBuffers<const N: usize> {
+    tx: BufferPool<N, MAX_TX_BUF_SIZE>,
+    rx: BufferPool<N, MAX_RX_BUF_SIZE>,
+    sx: BufferPool<N, MAX_RX_STATUS_BUF_SIZE>,
+}
 
-    const TX_INIT: [TxBuf; MAX_EXCHANGES] = [Self::TX_ELEM; MAX_EXCHANGES];
-    const RX_INIT: [RxBuf; MAX_EXCHANGES] = [Self::RX_ELEM; MAX_EXCHANGES];
-    const SX_INIT: [SxBuf; MAX_EXCHANGES + 1] = [Self::SX_ELEM; MAX_EXCHANGES + 1];
+/// The packet buffers of [`MAX_EXCHANGES`] exchange handlers
+pub type PacketBuffers = ExchangeBuffers<MAX_EXCHANGES>;
 
+impl<const N: usize> ExchangeBuffers<N> {
     #[inline(always)]
     pub const fn new() -> Self {
         Self {
-            tx: Self::TX_INIT,
-            rx: Self::RX_INIT,
-            sx: Self::SX_INIT,
+            tx: BufferPool::new(),
+            rx: BufferPool::new(),
+            sx: BufferPool::new(),
         }
     }
 }
@@ -115,12 +225,23 @@ impl<'a> Matter<'a> {
         }
     }
 
+    /// Runs the Matter stack: the transport (see [`Matter::run_transport`]), the
+    /// exchange handlers (see [`Matter::run_handlers`]) and the reporting engine of the
+    /// subscriptions (see [`Matter::reporter`]) joined in a single future.
+    ///
+    /// Users who need to poll the transport and the exchange handlers from different
+    /// executors should instead call [`Matter::run_transport`], [`Matter::run_handlers`]
+    /// and [`Matter::reporter`] directly, with a shared [`ExchangeQueue`].
+    ///
+    /// When [`Matter::notify_netif_changed`] is called, the future completes with `Ok(())`
+    /// and the exchanges the handlers were responding to are aborted. The caller should then
+    /// re-bind its sockets and run the stack again.
     #[allow(clippy::too_many_arguments)]
-    pub async fn run<H, S, R>(
+    pub async fn run<H, S, R, const B: usize>(
         &self,
         send: S,
         recv: R,
-        buffers: &mut PacketBuffers,
+        buffers: &ExchangeBuffers<B>,
         dev_comm: CommissioningData,
         handler: &H,
     ) -> Result<(), Error>
@@ -129,82 +250,260 @@ impl<'a> Matter<'a> {
         S: NetworkSend,
         R: NetworkReceive,
     {
-        info!("Running Matter transport");
+        self.start_transport(dev_comm).await?;
 
-        {
-            let mut recv_buf = self.rx_buf.get().await;
+        let queue = ExchangeQueue::new();
+
+        let result = {
+            let mut transport = pin!(self.run_transport(send, recv, &queue));
+            let mut handlers = pin!(self.run_handlers(buffers, &queue, handler));
+            let mut reporter = pin!(self.reporter(handler));
+
+            select3(&mut transport, &mut handlers, &mut reporter)
+                .await
+                .unwrap()
+        };
 
-            if self.start_comissioning(dev_comm, &mut recv_buf)? {
-                info!("Comissioning started");
+        // The handlers are gone, so the exchanges they were responding to can never complete
+        for ctx in self.exchanges().borrow_mut().iter_mut() {
+            if matches!(ctx, Some(ctx) if ctx.role != Role::Initiator) {
+                *ctx = None;
             }
         }
 
-        let construction_notification = Notification::new();
+        result
+    }
+
+    /// Enables the commissioning window (if the device is not commissioned yet) and prints
+    /// the pairing code. Needs to be called once before [`Matter::run_transport`] when the
+    /// transport and the exchange handlers are run separately.
+    pub async fn start_transport(&self, dev_comm: CommissioningData) -> Result<(), Error> {
+        info!("Running Matter transport");
+
+        self.shutting_down.set(false);
+
+        let mut recv_buf = self.rx_buf.get().await;
+
+        if self.start_comissioning(dev_comm, &mut recv_buf)? {
+            info!("Comissioning started");
+        }
+
+        Ok(())
+    }
 
-        let mut rx = pin!(self.handle_rx(recv, buffers, &construction_notification, handler));
-        let mut tx = pin!(self.handle_tx(send));
+    /// Runs the packet pumps of the transport: the RX multiplexer, which decodes incoming
+    /// packets and dispatches new exchanges into `queue`, and the TX pump, which sends
+    /// all outgoing packets.
+    ///
+    /// The future does not need the packet buffers of the exchange handlers, so it can be
+    /// polled by a different executor than the one polling [`Matter::run_handlers`] - e.g. a
+    /// higher-priority one - as long as both executors run on the same thread. Executors on
+    /// other cores or in interrupt context require `Send` futures, which the futures borrowing
+    /// the `Matter` object cannot be until the object is `Sync` (see [`ExchangeQueue`]).
+    ///
+    /// The future completes with `Ok(())` when [`Matter::notify_netif_changed`] is called,
+    /// so that the caller can re-bind its sockets and run the transport again.
+    pub async fn run_transport<'t, 'e, S, R, const N: usize>(
+        &'t self,
+        send: S,
+        recv: R,
+        queue: &Channel<NoopRawMutex, ExchangeCtr<'e>, N>,
+    ) -> Result<(), Error>
+    where
+        S: NetworkSend,
+        R: NetworkReceive,
+        't: 'e,
+    {
+        let mut rx = pin!(self.transport_rx(recv, queue));
+        let mut tx = pin!(self.transport_tx(send));
 
         select(&mut rx, &mut tx).await.unwrap()
     }
 
-    #[inline(always)]
-    async fn handle_rx<H, R>(
-        &self,
+    /// The RX half of [`Matter::run_transport`]: receives the incoming packets, processes them
+    /// and dispatches the new exchanges into `queue`.
+    ///
+    /// Together with [`Matter::transport_tx`] and [`Matter::responder`], this allows running
+    /// each part of the stack in its own task, e.g. one spawned with
+    /// `tokio::task::spawn_local`. As the `Matter` object is not `Sync`, the futures are not
+    /// `Send` (see [`ExchangeQueue`]), so all tasks have to be run by executors on the same
+    /// thread - on a multi-threaded tokio runtime, by a `tokio::task::LocalSet`.
+    ///
+    /// The future completes with `Ok(())` when [`Matter::notify_netif_changed`] is called;
+    /// the caller should then drop the [`Matter::transport_tx`] future as well, re-bind its
+    /// sockets and run both again.
+    ///
+    /// It also completes with `Ok(())` once a shutdown initiated with [`Matter::shutdown`]
+    /// is over, right after [`Matter::transport_tx`] does.
+    pub async fn transport_rx<'t, 'e, R, const N: usize>(
+        &'t self,
         recv: R,
-        buffers: &mut PacketBuffers,
-        construction_notification: &Notification,
-        handler: &H,
+        queue: &Channel<NoopRawMutex, ExchangeCtr<'e>, N>,
     ) -> Result<(), Error>
     where
-        H: DataModelHandler,
         R: NetworkReceive,
+        't: 'e,
     {
-        info!("Creating queue for {} exchanges", 1);
+        let mut sts_buf = alloc!([0; MAX_RX_STATUS_BUF_SIZE]);
 
-        let channel = Channel::<NoopRawMutex, _, 1>::new();
+        let mut rx = pin!(self.handle_rx_multiplex(recv, &mut sts_buf, queue));
+        let mut netif = pin!(async {
+            self.netif_notification.wait().await;
+            info!("Network interfaces changed, exiting transport loop");
 
-        info!("Creating {} handlers", MAX_EXCHANGES);
-        let mut handlers = heapless::Vec::<_, MAX_EXCHANGES>::new();
+            Ok::<_, Error>(())
+        });
+        let mut shutdown = pin!(async {
+            self.shutdown_complete_notification.wait().await;
+            info!("Shutdown complete, exiting transport loop");
 
-        info!("Handlers size: {}", core::mem::size_of_val(&handlers));
+            Ok::<_, Error>(())
+        });
+
+        let result = select3(&mut rx, &mut netif, &mut shutdown).await.unwrap();
 
-        // Unsafely allow mutable aliasing in the packet pools by different indices
-        let pools: *mut PacketBuffers = buffers;
+        if let Err(e) = &result {
+            error!("Exitting transport RX loop due to an error: {:?}", e);
+        }
 
-        for index in 0..MAX_EXCHANGES {
-            let channel = &channel;
-            let handler_id = index;
+        result
+    }
 
-            let pools = unsafe { pools.as_mut() }.unwrap();
+    /// The TX half of [`Matter::run_transport`]: sends all outgoing packets, including
+    /// the retransmissions and the standalone acks.
+    ///
+    /// The future completes with `Ok(())` once a shutdown initiated with [`Matter::shutdown`]
+    /// is over, and otherwise only on error; see [`Matter::transport_rx`] for how it should
+    /// be run.
+    pub async fn transport_tx<S>(&self, mut send: S) -> Result<(), Error>
+    where
+        S: NetworkSend,
+    {
+        let result = {
+            let mut tx = pin!(self.handle_tx(&mut send));
+            let mut shutdown = pin!(async {
+                self.shutdown_notification.wait().await;
+                info!("Shutting down, flushing pending acks and retransmissions");
 
-            let tx_buf = unsafe { pools.tx[handler_id].assume_init_mut() };
-            let rx_buf = unsafe { pools.rx[handler_id].assume_init_mut() };
-            let sx_buf = unsafe { pools.sx[handler_id].assume_init_mut() };
+                // The TX pump keeps running meanwhile
+                select(self.wait_flushed(), Timer::after(SHUTDOWN_FLUSH_TIMEOUT)).await;
 
-            handlers
-                .push(self.exchange_handler(tx_buf, rx_buf, sx_buf, handler_id, channel, handler))
-                .map_err(|_| ())
-                .unwrap();
-        }
+                Ok::<_, Error>(())
+            });
 
-        let mut rx = pin!(self.handle_rx_multiplex(
-            recv,
-            unsafe { buffers.sx[MAX_EXCHANGES].assume_init_mut() },
-            construction_notification,
-            &channel,
-        ));
+            select(&mut tx, &mut shutdown).await.unwrap()
+        };
 
-        let result = select(&mut rx, select_slice(&mut handlers)).await;
+        let result = if result.is_ok() {
+            let result = self.close_sessions(&mut send).await;
+            self.shutdown_complete_notification.signal(());
 
-        if let Either::First(result) = result {
-            if let Err(e) = &result {
-                error!("Exitting RX loop due to an error: {:?}", e);
-            }
+            result
+        } else {
+            result
+        };
 
-            result?;
+        if let Err(e) = &result {
+            error!("Exitting transport TX loop due to an error: {:?}", e);
         }
 
-        Ok(())
+        result
+    }
+
+    /// Runs a single exchange handler, which processes the exchanges dispatched by
+    /// [`Matter::transport_rx`] into `queue` with the provided data model `handler`, one at
+    /// a time.
+    ///
+    /// Unlike [`Matter::run_handlers`], the responder allocates its own packet buffers, so
+    /// that several responders - each in its own task - can process exchanges concurrently.
+    /// `handler_id` only identifies the responder in the logs.
+    pub async fn responder<H, const N: usize>(
+        &self,
+        handler_id: impl core::fmt::Display,
+        queue: &Channel<NoopRawMutex, ExchangeCtr<'_>, N>,
+        handler: &H,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        let mut tx_buf = alloc!([0; MAX_TX_BUF_SIZE]);
+        let mut rx_buf = alloc!([0; MAX_RX_BUF_SIZE]);
+        let mut sx_buf = alloc!([0; MAX_RX_STATUS_BUF_SIZE]);
+
+        self.exchange_handler(
+            &mut tx_buf[..],
+            &mut rx_buf[..],
+            &mut sx_buf[..],
+            handler_id,
+            queue,
+            handler,
+        )
+        .await
+    }
+
+    /// Runs the reporting engine of the subscriptions, which sends the reports of the
+    /// subscriptions established with the provided data model `handler` - over exchanges
+    /// initiated by this node.
+    ///
+    /// Like [`Matter::responder`], the reporting engine allocates its own packet buffers.
+    pub async fn reporter<H>(&self, handler: &H) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        let mut tx_buf = alloc!([0; MAX_TX_BUF_SIZE]);
+        let mut rx_buf = alloc!([0; MAX_RX_BUF_SIZE]);
+        let mut sx_buf = alloc!([0; MAX_RX_STATUS_BUF_SIZE]);
+
+        DataModel::new(handler)
+            .report(self, &mut tx_buf[..], &mut rx_buf[..], &mut sx_buf[..])
+            .await
+    }
+
+    /// Runs the exchange handlers, which process the exchanges dispatched by
+    /// [`Matter::run_transport`] into `queue` with the provided data model `handler`.
+    ///
+    /// One exchange handler is run for each set of packet buffers in `buffers`.
+    pub async fn run_handlers<H, const B: usize, const N: usize>(
+        &self,
+        buffers: &ExchangeBuffers<B>,
+        queue: &Channel<NoopRawMutex, ExchangeCtr<'_>, N>,
+        handler: &H,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        info!("Creating {} handlers", B);
+        let handlers: [_; B] = core::array::from_fn(|handler_id| async move {
+            // Each handler owns the buffers with its own index in the pools - and the pools
+            // have exactly `B` buffers each
+            #[allow(clippy::unwrap_used)]
+            let (tx, rx, sx) = (
+                buffers.tx.buffer(handler_id).unwrap(),
+                buffers.rx.buffer(handler_id).unwrap(),
+                buffers.sx.buffer(handler_id).unwrap(),
+            );
+
+            let mut tx_buf = tx.get().await;
+            let mut rx_buf = rx.get().await;
+            let mut sx_buf = sx.get().await;
+
+            self.exchange_handler(
+                &mut tx_buf,
+                &mut rx_buf,
+                &mut sx_buf,
+                handler_id,
+                queue,
+                handler,
+            )
+            .await
+        });
+
+        info!("Handlers size: {}", core::mem::size_of_val(&handlers));
+
+        let handlers = pin!(handlers);
+        let handlers = unsafe { handlers.map_unchecked_mut(|handlers| handlers.as_mut_slice()) };
+
+        select_slice(handlers).await.0
     }
 
     #[inline(always)]
@@ -221,11 +520,16 @@ impl<'a> Matter<'a> {
 
                     if self.pull_tx(&mut tx)? {
                         let addr = tx.peer;
+                        let local = tx.local;
 
                         let start = tx.get_writebuf()?.get_start();
                         let end = tx.get_writebuf()?.get_tail();
 
-                        send.send_to(&send_buf[start..end], addr).await?;
+                        send.send_from(&send_buf[start..end], addr, local).await?;
+
+                        self.update_stats(|stats| {
+                            stats.tx_packets = stats.tx_packets.wrapping_add(1)
+                        });
                     } else {
                         break;
                     }
@@ -241,7 +545,6 @@ impl<'a> Matter<'a> {
         &'t self,
         mut receiver: R,
         sts_buf: &mut [u8; MAX_RX_STATUS_BUF_SIZE],
-        construction_notification: &'e Notification,
         channel: &Channel<NoopRawMutex, ExchangeCtr<'e>, N>,
     ) -> Result<(), Error>
     where
@@ -258,26 +561,33 @@ impl<'a> Matter<'a> {
             {
                 let mut recv_buf = self.rx_buf.get().await;
 
-                let (len, remote) = receiver.recv_from(&mut recv_buf).await?;
+                let (len, remote, local) = receiver.recv_from_local(&mut recv_buf).await?;
 
                 let mut rx = alloc!(Packet::new_rx(&mut recv_buf[..len]));
                 rx.peer = remote;
+                rx.local = local;
 
-                if let Some(exchange_ctr) = self
-                    .process_rx(construction_notification, &mut rx, &mut sts_tx)
-                    .await?
-                {
-                    let exchange_id = exchange_ctr.id().clone();
+                // Errors while processing a single packet are never fatal for the transport,
+                // as the packet might be malformed or even malicious
+                let result = self.process_rx(&mut rx, &mut sts_tx).await;
 
-                    info!("Transport: got new exchange: {:?}", exchange_id);
+                match result {
+                    Ok(Some(exchange_ctr)) => {
+                        let exchange_id = exchange_ctr.id().clone();
 
-                    channel.send(exchange_ctr).await;
-                    info!("Transport: exchange sent");
+                        info!("Transport: got new exchange: {:?}", exchange_id);
 
-                    self.wait_construction(construction_notification, &rx, &exchange_id)
-                        .await?;
+                        channel.send(exchange_ctr).await;
+                        info!("Transport: exchange sent");
 
-                    info!("Transport: exchange started");
+                        if let Err(e) = self.wait_construction(&rx, &exchange_id).await {
+                            warn!("Transport: exchange construction failed: {:?}", e);
+                        } else {
+                            info!("Transport: exchange started");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(e) => warn!("Transport: dropping packet because of error: {:?}", e),
                 }
             }
         }
@@ -289,9 +599,9 @@ impl<'a> Matter<'a> {
     #[inline(always)]
     pub async fn exchange_handler<const N: usize, H>(
         &self,
-        tx_buf: &mut [u8; MAX_TX_BUF_SIZE],
-        rx_buf: &mut [u8; MAX_RX_BUF_SIZE],
-        sx_buf: &mut [u8; MAX_RX_STATUS_BUF_SIZE],
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        sx_buf: &mut [u8],
         handler_id: impl core::fmt::Display,
         channel: &Channel<NoopRawMutex, ExchangeCtr<'_>, N>,
         handler: &H,
@@ -326,20 +636,25 @@ impl<'a> Matter<'a> {
     #[inline(always)]
     pub async fn handle_exchange<H>(
         &self,
-        tx_buf: &mut [u8; MAX_TX_BUF_SIZE],
-        rx_buf: &mut [u8; MAX_RX_BUF_SIZE],
-        sx_buf: &mut [u8; MAX_RX_STATUS_BUF_SIZE],
+        tx_buf: &mut [u8],
+        rx_buf: &mut [u8],
+        sx_buf: &mut [u8],
         exchange_ctr: ExchangeCtr<'_>,
         handler: &H,
     ) -> Result<(), Error>
     where
         H: DataModelHandler,
     {
-        let mut tx = alloc!(Packet::new_tx(tx_buf.as_mut()));
-        let mut rx = alloc!(Packet::new_rx(rx_buf.as_mut()));
+        let mut rx = alloc!(Packet::new_rx(rx_buf));
 
         let mut exchange = alloc!(exchange_ctr.get(&mut rx).await?);
 
+        // Unless the session supports Large Payloads, the responses need to fit in the MTU
+        let max_tx_size = exchange.with_session(|sess| Ok(sess.max_tx_size()))?;
+        let tx_len = core::cmp::min(tx_buf.len(), max_tx_size);
+
+        let mut tx = alloc!(Packet::new_tx(&mut tx_buf[..tx_len]));
+
         match rx.get_proto_id() {
             PROTO_ID_SECURE_CHANNEL => {
                 let sc = SecureChannel::new();
@@ -366,32 +681,237 @@ impl<'a> Matter<'a> {
         Ok(())
     }
 
+    /// Shuts the transport down gracefully, e.g. before rebooting for an OTA update or after
+    /// a factory reset:
+    /// - new exchanges are no longer accepted;
+    /// - the pending acks and retransmissions are sent, for up to 5 seconds;
+    /// - the peers of all secure unicast sessions are notified with a CloseSession status
+    ///   report, and the sessions are removed.
+    ///
+    /// [`Matter::run`] (or [`Matter::transport_rx`] and [`Matter::transport_tx`], when run
+    /// separately) then completes with `Ok(())`.
+    pub fn shutdown(&self) {
+        info!("Shutdown requested");
+
+        self.shutting_down.set(true);
+        self.shutdown_notification.signal(());
+    }
+
+    /// Whether a shutdown was initiated with [`Matter::shutdown`]
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.get()
+    }
+
+    /// Notifies the stack that the network interfaces or their addresses changed, e.g. because
+    /// the device roamed to another Wi-Fi access point or an interface bounced.
+    ///
+    /// The transport completes (see [`Matter::run_transport`]), so that the caller can re-bind
+    /// its sockets, and the mDNS services are re-advertised. Sessions are kept; those whose
+    /// peers are no longer reachable can be removed with [`Matter::remove_unreachable_sessions`].
+    pub fn notify_netif_changed(&self) {
+        info!("Network interfaces changed");
+
+        self.netif_notification.signal(());
+        self.mdns.refresh();
+    }
+
+    /// Removes all sessions whose peer address is no longer reachable according to `reachable`,
+    /// and fails the exchanges over them. Returns the number of removed sessions.
+    pub fn remove_unreachable_sessions<F>(&self, reachable: F) -> usize
+    where
+        F: Fn(&Address) -> bool,
+    {
+        let removed = self
+            .session_mgr()
+            .borrow_mut()
+            .remove_if(|sess| !reachable(&sess.get_peer_addr()));
+
+        for ctx in self.exchanges().borrow_mut().iter_mut().flatten() {
+            if !reachable(&ctx.id.session_id.peer_addr)
+                && !matches!(ctx.state, ExchangeState::Closed | ExchangeState::Failed(_))
+            {
+                warn!("Exchange {:?}: peer no longer reachable, closing", ctx.id);
+
+                if let ExchangeState::ExchangeRecv { notification, .. } = &ctx.state {
+                    unsafe { &**notification }.signal(());
+                }
+
+                ctx.state = ExchangeState::Failed(ErrorCode::NoSession);
+            }
+        }
+
+        if removed > 0 {
+            info!("Removed {} sessions with unreachable peers", removed);
+        }
+
+        removed
+    }
+
+    /// Tears down the transport state of a fabric which was removed:
+    /// - all its sessions are expired; they accept no new exchanges and are removed as soon as
+    ///   the exchanges over them are gone;
+    /// - all exchanges over these sessions are failed - which also ends the subscriptions
+    ///   running on them - except `keep`, i.e. the exchange over which the fabric was removed,
+    ///   so that the response can still be sent;
+    /// - the group keys, group message counters, CASE resumption records, paired nodes,
+    ///   ICD clients, bindings and subscriptions of the fabric are dropped.
+    pub(crate) fn remove_fabric(&self, fab_idx: u8, keep: Option<&ExchangeId>) {
+        let mut session_mgr = self.session_mgr().borrow_mut();
+
+        let expired = session_mgr.expire_fabric(fab_idx);
+
+        for ctx in self.exchanges().borrow_mut().iter_mut().flatten() {
+            if Some(&ctx.id) == keep
+                || matches!(ctx.state, ExchangeState::Closed | ExchangeState::Failed(_))
+            {
+                continue;
+            }
+
+            let session_expired = Self::ctx_session(&session_mgr, ctx)
+                .and_then(|index| session_mgr.mut_by_index(index))
+                .map(|session| session.is_expired())
+                .unwrap_or(false);
+
+            if session_expired {
+                warn!("Exchange {:?}: fabric removed, closing", ctx.id);
+
+                if let ExchangeState::ExchangeRecv { notification, .. } = &ctx.state {
+                    unsafe { &**notification }.signal(());
+                }
+
+                ctx.state = ExchangeState::Failed(ErrorCode::NoSession);
+            }
+        }
+
+        self.group_key_mgr.borrow_mut().remove_fabric(fab_idx);
+        self.msg_ctrs.borrow_mut().remove_fabric(fab_idx);
+        self.case_resumptions.borrow_mut().remove_fabric(fab_idx);
+        self.paired_nodes.borrow_mut().remove_fabric(fab_idx);
+        self.icd_clients.borrow_mut().remove_fabric(fab_idx);
+        self.bindings.borrow_mut().remove_fabric(fab_idx);
+        self.subscriptions.borrow_mut().remove_fabric(fab_idx);
+
+        info!("Fabric {} removed, expired {} sessions", fab_idx, expired);
+
+        self.notify_changed();
+    }
+
+    /// The number of exchanges - not closed yet - running over `session`
+    fn session_exchanges(&self, session: &Session) -> usize {
+        self.exchanges()
+            .borrow()
+            .iter()
+            .flatten()
+            .filter(|ctx| !matches!(ctx.state, ExchangeState::Closed))
+            .filter(|ctx| {
+                session.matches(
+                    ctx.id.session_id.id,
+                    ctx.id.session_id.peer_addr,
+                    ctx.id.session_id.peer_nodeid,
+                    ctx.id.session_id.is_encrypted,
+                )
+            })
+            .count()
+    }
+
+    /// The index of the session of an exchange, if the session is still around
+    fn ctx_session(session_mgr: &SessionMgr, ctx: &ExchangeCtx) -> Option<usize> {
+        session_mgr.get(
+            ctx.id.session_id.id,
+            ctx.id.session_id.peer_addr,
+            ctx.id.session_id.peer_nodeid,
+            ctx.id.session_id.is_encrypted,
+        )
+    }
+
+    /// Sets (or resets to the default [`LruEviction`], with `None`) the policy picking the
+    /// session to be evicted when all session slots are taken and a new session is needed
+    pub fn set_eviction_policy(&self, policy: Option<&'static dyn EvictionPolicy>) {
+        self.eviction_policy.set(policy);
+    }
+
+    /// Sets (or clears, with `None`) the observer of all packets exchanged by the stack
+    pub fn set_packet_observer(&self, observer: Option<&'static dyn PacketObserver>) {
+        self.packet_observer.set(observer);
+    }
+
+    /// Returns a snapshot of the transport counters
+    pub fn transport_stats(&self) -> TransportStats {
+        self.stats.get()
+    }
+
+    /// Calls `f` with a snapshot of each session - without its keys - e.g. for diagnostics
+    pub fn for_each_session<F>(&self, f: F)
+    where
+        F: FnMut(SessionInfo),
+    {
+        self.session_mgr()
+            .borrow()
+            .iter()
+            .map(Session::info)
+            .for_each(f)
+    }
+
+    /// Resets all transport counters to zero
+    pub fn reset_transport_stats(&self) {
+        self.stats.set(TransportStats::new());
+    }
+
+    fn update_stats<F>(&self, f: F)
+    where
+        F: FnOnce(&mut TransportStats),
+    {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
     pub fn reset_transport(&self) {
-        self.exchanges.borrow_mut().clear();
-        self.session_mgr.borrow_mut().reset();
+        self.exchanges().borrow_mut().fill_with(|| None);
+        self.session_mgr().borrow_mut().reset();
         self.mdns.reset();
     }
 
     pub async fn process_rx<'r>(
         &'r self,
-        construction_notification: &'r Notification,
         src_rx: &mut Packet<'_>,
         sts_tx: &mut Packet<'_>,
     ) -> Result<Option<ExchangeCtr<'r>>, Error> {
+        self.update_stats(|stats| stats.rx_packets = stats.rx_packets.wrapping_add(1));
+
+        // A privacy-protected header needs to be de-obfuscated before it can be decoded
+        self.session_mgr().borrow().privacy_decode(src_rx)?;
+
         src_rx.plain_hdr_decode()?;
 
         self.purge()?;
+        self.close_exhausted_sessions(sts_tx).await?;
 
         let (exchange_index, new) = loop {
-            let result = self.assign_exchange(&mut self.exchanges.borrow_mut(), src_rx);
+            let result = self.assign_exchange(&mut self.exchanges().borrow_mut(), src_rx);
 
             match result {
                 Err(e) => match e.code() {
                     ErrorCode::Duplicate => {
+                        self.update_stats(|stats| {
+                            stats.duplicates_dropped = stats.duplicates_dropped.wrapping_add(1)
+                        });
+
                         self.send_notification.signal(());
                         return Ok(None);
                     }
-                    // TODO: NoSession, NoExchange and others
+                    ErrorCode::NoExchange => {
+                        // A message for an exchange which is already gone (or was never there).
+                        // Acknowledge it anyway, so that the peer stops retransmitting it
+                        warn!("Dropping a message for an unknown exchange");
+
+                        if src_rx.proto.is_reliable() {
+                            self.send_standalone_ack(src_rx, sts_tx).await?;
+                        }
+
+                        return Ok(None);
+                    }
+                    // TODO: NoSession and others
                     ErrorCode::NoSpaceSessions => self.evict_session(sts_tx).await?,
                     ErrorCode::NoSpaceExchanges => {
                         self.send_busy(src_rx, sts_tx).await?;
@@ -403,35 +923,54 @@ impl<'a> Matter<'a> {
             }
         }?;
 
-        let mut exchanges = self.exchanges.borrow_mut();
-        let ctx = &mut exchanges[exchange_index];
+        let mut exchanges = self.exchanges().borrow_mut();
+        let ctx = exchanges[exchange_index]
+            .as_mut()
+            .ok_or(ErrorCode::NoExchange)?;
 
         src_rx.log("Got packet");
 
-        if src_rx.proto.is_ack() {
-            if new {
-                Err(ErrorCode::Invalid)?;
-            } else {
-                let state = &mut ctx.state;
+        let standalone_ack = src_rx.proto.proto_id == PROTO_ID_SECURE_CHANNEL
+            && src_rx.proto.proto_opcode == OpCode::MRPStandAloneAck as u8;
 
-                match state {
-                    ExchangeState::ExchangeRecv {
-                        tx_acknowledged, ..
-                    } => {
-                        *tx_acknowledged = true;
-                    }
-                    ExchangeState::CompleteAcknowledge { notification, .. } => {
-                        unsafe { notification.as_ref() }.unwrap().signal(());
-                        ctx.state = ExchangeState::Closed;
-                    }
-                    _ => {
-                        // TODO: Error handling
-                        todo!()
-                    }
-                }
+        if new && self.shutting_down.get() {
+            warn!("Shutting down, refusing a new exchange");
 
-                self.notify_changed();
+            ctx.state = ExchangeState::Closed;
+
+            return Ok(None);
+        }
+
+        if new && standalone_ack {
+            // Nothing to acknowledge on an exchange which did not exist, nor anything to process
+            warn!("Dropping a standalone ack opening a new exchange");
+
+            ctx.state = ExchangeState::Closed;
+
+            return Ok(None);
+        }
+
+        if src_rx.proto.is_ack() && !new {
+            let state = &mut ctx.state;
+
+            match state {
+                ExchangeState::ExchangeRecv {
+                    tx_acknowledged, ..
+                } => {
+                    *tx_acknowledged = true;
+                }
+                ExchangeState::CompleteAcknowledge { notification, .. } => {
+                    unsafe { &**notification }.signal(());
+                    ctx.state = ExchangeState::Closed;
+                }
+                _ => {
+                    // Most likely a late duplicate of an ack already processed;
+                    // the message itself might still be of interest though
+                    warn!("Ignoring an ack while in an unexpected exchange state");
+                }
             }
+
+            self.notify_changed();
         }
 
         if new {
@@ -441,15 +980,12 @@ impl<'a> Matter<'a> {
                     matter: self,
                     notification: Notification::new(),
                 },
-                construction_notification,
             };
 
             self.notify_changed();
 
             Ok(Some(constructor))
-        } else if src_rx.proto.proto_id == PROTO_ID_SECURE_CHANNEL
-            && src_rx.proto.proto_opcode == OpCode::MRPStandAloneAck as u8
-        {
+        } else if standalone_ack {
             // Standalone ack, do nothing
             Ok(None)
         } else {
@@ -461,15 +997,19 @@ impl<'a> Matter<'a> {
                 } => {
                     // TODO: Handle Busy status codes
 
-                    let rx = unsafe { rx.as_mut() }.unwrap();
+                    let rx = unsafe { &mut **rx };
                     rx.load(src_rx)?;
 
-                    unsafe { notification.as_ref() }.unwrap().signal(());
+                    unsafe { &**notification }.signal(());
                     *state = ExchangeState::Active;
                 }
                 _ => {
-                    // TODO: Error handling
-                    todo!()
+                    // The handler of the exchange is not waiting for a message, so there
+                    // is no one to process this one. Drop it without acknowledging it,
+                    // as it was not processed
+                    warn!("Dropping a message received while in an unexpected exchange state");
+
+                    ctx.mrp.cancel_ack(src_rx.plain.ctr);
                 }
             }
 
@@ -481,82 +1021,131 @@ impl<'a> Matter<'a> {
 
     pub async fn wait_construction(
         &self,
-        construction_notification: &Notification,
         src_rx: &Packet<'_>,
         exchange_id: &ExchangeId,
     ) -> Result<(), Error> {
-        construction_notification.wait().await;
+        self.construction_notification.wait().await;
 
-        let mut exchanges = self.exchanges.borrow_mut();
+        let mut exchanges = self.exchanges().borrow_mut();
 
-        let ctx = ExchangeCtx::get(&mut exchanges, exchange_id).unwrap();
+        let ctx = ExchangeCtx::get(&mut exchanges, exchange_id).ok_or(ErrorCode::NoExchange)?;
 
         let state = &mut ctx.state;
 
         match state {
             ExchangeState::Construction { rx, notification } => {
-                let rx = unsafe { rx.as_mut() }.unwrap();
+                let rx = unsafe { &mut **rx };
                 rx.load(src_rx)?;
 
-                unsafe { notification.as_ref() }.unwrap().signal(());
+                unsafe { &**notification }.signal(());
                 *state = ExchangeState::Active;
             }
-            _ => unreachable!(),
+            _ => Err(ErrorCode::InvalidState)?,
         }
 
         Ok(())
     }
 
+    /// Waits until there might be something to send: either until notified, or until the
+    /// earliest retransmission, acknowledgement, exchange expiry or commissioning window timeout
     pub async fn wait_tx(&self) -> Result<(), Error> {
-        select(
-            self.send_notification.wait(),
-            Timer::after(Duration::from_millis(100)),
-        )
-        .await;
+        if let Some(deadline) = self.tx_deadline() {
+            let timeout = deadline.saturating_sub((self.epoch)());
+
+            select(
+                self.send_notification.wait(),
+                Timer::after(Duration::from_micros(timeout.as_micros() as _)),
+            )
+            .await;
+        } else {
+            self.send_notification.wait().await;
+        }
 
         Ok(())
     }
 
+    fn tx_deadline(&self) -> Option<core::time::Duration> {
+        self.exchanges()
+            .borrow()
+            .iter()
+            .flatten()
+            .chain(self.ephemeral.borrow().iter())
+            .filter_map(ExchangeCtx::deadline)
+            .chain(self.pase_mgr.borrow().comm_window_deadline())
+            .chain(self.failsafe.borrow().deadline())
+            .min()
+    }
+
     pub fn pull_tx(&self, dest_tx: &mut Packet) -> Result<bool, Error> {
         self.purge()?;
+        self.expire();
+        self.pase_mgr.borrow_mut().expire_comm_window(&self.mdns)?;
+        self.expire_failsafe()?;
 
         let mut ephemeral = self.ephemeral.borrow_mut();
-        let mut exchanges = self.exchanges.borrow_mut();
+        let mut exchanges = self.exchanges().borrow_mut();
 
-        self.pull_tx_exchanges(ephemeral.iter_mut().chain(exchanges.iter_mut()), dest_tx)
+        self.pull_tx_exchanges(
+            ephemeral.iter_mut().chain(exchanges.iter_mut().flatten()),
+            dest_tx,
+        )
     }
 
-    fn pull_tx_exchanges<'i, I>(
-        &self,
-        mut exchanges: I,
-        dest_tx: &mut Packet,
-    ) -> Result<bool, Error>
+    /// Returns the priority of the packet the exchange has to send, if any
+    fn tx_priority(ctx: &ExchangeCtx, epoch: Epoch) -> Option<TxPriority> {
+        match &ctx.state {
+            ExchangeState::Acknowledge { .. } => Some(TxPriority::Ack),
+            ExchangeState::ExchangeSend { tx, .. } | ExchangeState::Complete { tx, .. } => {
+                if unsafe { &**tx }.is_status() {
+                    Some(TxPriority::Status)
+                } else {
+                    Some(TxPriority::Data)
+                }
+            }
+            ExchangeState::ExchangeRecv {
+                tx_acknowledged: false,
+                ..
+            }
+            | ExchangeState::CompleteAcknowledge { .. }
+                if ctx.mrp.is_retrans_due(epoch) =>
+            {
+                Some(TxPriority::Retransmission)
+            }
+            _ => ctx.mrp.is_ack_ready(epoch).then_some(TxPriority::Ack),
+        }
+    }
+
+    fn pull_tx_exchanges<'i, I>(&self, exchanges: I, dest_tx: &mut Packet) -> Result<bool, Error>
     where
         I: Iterator<Item = &'i mut ExchangeCtx>,
     {
-        let ctx = exchanges.find(|ctx| {
-            matches!(
-                &ctx.state,
-                ExchangeState::Acknowledge { .. }
-                    | ExchangeState::ExchangeSend { .. }
-                    // | ExchangeState::ExchangeRecv {
-                    //     tx_acknowledged: false,
-                    //     ..
-                    // }
-                    | ExchangeState::Complete { .. } // | ExchangeState::CompleteAcknowledge { .. }
-            ) || ctx.mrp.is_ack_ready(*self.borrow())
-        });
+        let epoch = self.epoch;
+
+        // Pick the exchange with the most urgent packet; among equally urgent ones, the first
+        let mut next: Option<(TxPriority, &mut ExchangeCtx)> = None;
+
+        for ctx in exchanges {
+            if let Some(priority) = Self::tx_priority(ctx, epoch) {
+                if next.as_ref().map(|(p, _)| priority > *p).unwrap_or(true) {
+                    next = Some((priority, ctx));
+                }
+            }
+        }
+
+        let ctx = next.map(|(_, ctx)| ctx);
 
         if let Some(ctx) = ctx {
             self.notify_changed();
 
+            let mut standalone_ack = false;
+
             let state = &mut ctx.state;
 
             let send = match state {
                 ExchangeState::Acknowledge { notification } => {
-                    ReliableMessage::prepare_ack(ctx.id.id, dest_tx);
+                    standalone_ack = true;
 
-                    unsafe { notification.as_ref() }.unwrap().signal(());
+                    unsafe { &**notification }.signal(());
                     *state = ExchangeState::Active;
 
                     true
@@ -566,11 +1155,11 @@ impl<'a> Matter<'a> {
                     rx,
                     notification,
                 } => {
-                    let tx = unsafe { tx.as_ref() }.unwrap();
+                    let tx = unsafe { &**tx };
                     dest_tx.load(tx)?;
 
                     *state = ExchangeState::ExchangeRecv {
-                        _tx: tx,
+                        tx,
                         tx_acknowledged: false,
                         rx: *rx,
                         notification: *notification,
@@ -578,37 +1167,98 @@ impl<'a> Matter<'a> {
 
                     true
                 }
-                // ExchangeState::ExchangeRecv { .. } => {
-                //     // TODO: Re-send the tx package if due
-                //     false
-                // }
+                ExchangeState::ExchangeRecv {
+                    tx,
+                    tx_acknowledged: false,
+                    notification,
+                    ..
+                } if ctx.mrp.is_retrans_due(epoch) => {
+                    if ctx.mrp.retransmit(epoch, self.rand) {
+                        let tx = unsafe { &**tx };
+                        dest_tx.load(tx)?;
+
+                        self.update_stats(|stats| {
+                            stats.retransmissions = stats.retransmissions.wrapping_add(1)
+                        });
+
+                        true
+                    } else {
+                        unsafe { &**notification }.signal(());
+                        *state = ExchangeState::Failed(ErrorCode::TxTimeout);
+
+                        // Other exchanges might have something to send
+                        self.send_notification.signal(());
+
+                        false
+                    }
+                }
                 ExchangeState::Complete { tx, notification } => {
-                    let tx = unsafe { tx.as_ref() }.unwrap();
+                    let tx = unsafe { &**tx };
                     dest_tx.load(tx)?;
 
                     if dest_tx.is_reliable() {
                         *state = ExchangeState::CompleteAcknowledge {
-                            _tx: tx as *const _,
+                            tx: tx as *const _,
                             notification: *notification,
                         };
                     } else {
-                        unsafe { notification.as_ref() }.unwrap().signal(());
+                        unsafe { &**notification }.signal(());
                         ctx.state = ExchangeState::Closed;
                     }
 
                     true
                 }
-                // ExchangeState::CompleteAcknowledge { .. } => {
-                //     // TODO: Re-send the tx package if due
-                //     false
-                // }
+                ExchangeState::CompleteAcknowledge { tx, notification }
+                    if ctx.mrp.is_retrans_due(epoch) =>
+                {
+                    if ctx.mrp.retransmit(epoch, self.rand) {
+                        let tx = unsafe { &**tx };
+                        dest_tx.load(tx)?;
+
+                        self.update_stats(|stats| {
+                            stats.retransmissions = stats.retransmissions.wrapping_add(1)
+                        });
+
+                        true
+                    } else {
+                        unsafe { &**notification }.signal(());
+                        *state = ExchangeState::Failed(ErrorCode::TxTimeout);
+
+                        // Other exchanges might have something to send
+                        self.send_notification.signal(());
+
+                        false
+                    }
+                }
                 _ => {
-                    ReliableMessage::prepare_ack(ctx.id.id, dest_tx);
+                    standalone_ack = true;
                     true
                 }
             };
 
+            if standalone_ack {
+                ReliableMessage::prepare_ack(ctx.id.id, dest_tx);
+
+                if let Err(e) = ctx.pre_send(
+                    &mut self.session_mgr().borrow_mut(),
+                    dest_tx,
+                    self.packet_observer.get(),
+                ) {
+                    // The session is most likely gone, so is the exchange
+                    warn!(
+                        "Cannot send a standalone ack, closing the exchange: {:?}",
+                        e
+                    );
+                    ctx.state = ExchangeState::Closed;
+                    self.send_notification.signal(());
+
+                    return Ok(false);
+                }
+            }
+
             if send {
+                ctx.last_activity = epoch();
+
                 dest_tx.log("Sending packet");
                 self.notify_changed();
 
@@ -619,44 +1269,253 @@ impl<'a> Matter<'a> {
         Ok(false)
     }
 
-    fn purge(&self) -> Result<(), Error> {
-        loop {
-            let mut exchanges = self.exchanges.borrow_mut();
+    /// Fails all exchanges which have been waiting for a message from the peer for longer
+    /// than their timeout, thus waking up and freeing the handlers processing them
+    fn expire(&self) {
+        let mut exchanges = self.exchanges().borrow_mut();
 
-            if let Some(index) = exchanges.iter_mut().enumerate().find_map(|(index, ctx)| {
-                matches!(ctx.state, ExchangeState::Closed).then_some(index)
-            }) {
-                exchanges.swap_remove(index);
-            } else {
-                break;
+        for ctx in exchanges.iter_mut().flatten() {
+            if ctx.is_expired(self.epoch) {
+                warn!(
+                    "Exchange {:?}: no message from the peer for {:?}, closing",
+                    ctx.id, ctx.timeout
+                );
+
+                if let ExchangeState::ExchangeRecv { notification, .. } = &ctx.state {
+                    unsafe { &**notification }.signal(());
+                }
+
+                ctx.state = ExchangeState::Failed(ErrorCode::RxTimeout);
+            }
+        }
+    }
+
+    fn purge(&self) -> Result<(), Error> {
+        for ctx in self.exchanges().borrow_mut().iter_mut() {
+            if matches!(ctx, Some(ctx) if matches!(ctx.state, ExchangeState::Closed)) {
+                *ctx = None;
             }
         }
 
+        // Expired sessions go away together with their last exchange
+        let removed = self
+            .session_mgr()
+            .borrow_mut()
+            .remove_expired(|session| self.session_exchanges(session) > 0);
+        if removed > 0 {
+            info!("Removed {} expired sessions", removed);
+        }
+
         Ok(())
     }
 
+    /// Sends `tx` - with its protocol ID, opcode and payload already set - as a group message
+    /// to all members of the group, over the multicast address of the group.
+    ///
+    /// The key of the group must have been added with [`Matter::add_group_key`] beforehand.
+    /// Group messages are never acknowledged, so delivery is not guaranteed.
+    pub async fn send_group(
+        &self,
+        fab_idx: u8,
+        group_id: u16,
+        tx: &mut Packet<'_>,
+    ) -> Result<(), Error> {
+        let ctx = {
+            let fabric_mgr = self.fabric_mgr().borrow();
+            let fabric = fabric_mgr
+                .get_fabric(fab_idx as _)?
+                .ok_or(ErrorCode::NotFound)?;
+
+            let group_key_mgr = self.group_key_mgr.borrow();
+            let key = group_key_mgr
+                .get(fab_idx, group_id)
+                .ok_or(ErrorCode::NotFound)?;
+
+            let addr = Address::Udp(SocketAddr::V6(SocketAddrV6::new(
+                group_multicast_addr(fabric.get_fabric_id(), group_id),
+                MATTER_PORT,
+                0,
+                0,
+            )));
+
+            let mut session_mgr = self.session_mgr().borrow_mut();
+
+            let sess_index = session_mgr.get_or_add_group_tx(addr, fabric.get_node_id(), &key)?;
+            let session = session_mgr
+                .mut_by_index(sess_index)
+                .ok_or(ErrorCode::NoSession)?;
+
+            // All group messages sent by this node share the same persisted counter
+            session.set_msg_ctr(self.msg_ctrs.borrow_mut().next_group_ctr(self.rand));
+            if self.msg_ctrs.borrow().is_changed() {
+                self.notify_changed();
+            }
+
+            let session_id = session.id();
+
+            ExchangeCtx::prep_ephemeral(
+                session_id,
+                &mut session_mgr,
+                None,
+                tx,
+                self.packet_observer.get(),
+            )?
+        };
+
+        self.send_ephemeral(ctx, tx).await
+    }
+
+    /// Sends a Check-In message to an ICD client at `peer`, encrypted with the symmetric `key`
+    /// the client registered, and advertising the active mode threshold of this node
+    /// (in milliseconds).
+    ///
+    /// Check-In messages are unsecured and never acknowledged, so delivery is not guaranteed.
+    pub async fn send_check_in(
+        &self,
+        peer: Address,
+        key: &[u8],
+        active_mode_threshold: u16,
+        tx: &mut Packet<'_>,
+    ) -> Result<(), Error> {
+        let counter = self.msg_ctrs.borrow_mut().next_check_in_ctr(self.rand);
+        if self.msg_ctrs.borrow().is_changed() {
+            self.notify_changed();
+        }
+
+        CheckIn {
+            counter,
+            active_mode_threshold,
+        }
+        .prep_tx(key, tx)?;
+
+        let session_id = SessionId {
+            id: 0,
+            peer_addr: peer,
+            peer_nodeid: None,
+            is_encrypted: false,
+        };
+
+        let ctx = ExchangeCtx::prep_ephemeral(
+            session_id,
+            &mut self.session_mgr().borrow_mut(),
+            None,
+            tx,
+            self.packet_observer.get(),
+        )?;
+
+        self.send_ephemeral(ctx, tx).await
+    }
+
+    /// Opens a new exchange with `peer`, as its initiator, over the unsecured session with
+    /// the peer - which is created if necessary. Used for establishing a secure session with
+    /// the peer, e.g. with [`crate::secure_channel::pake::Pake::initiate`].
+    pub fn initiate_unsecured(&self, peer: Address) -> Result<Exchange<'_>, Error> {
+        let session_id = SessionId {
+            id: 0,
+            peer_addr: peer,
+            peer_nodeid: None,
+            is_encrypted: false,
+        };
+
+        {
+            let mut session_mgr = self.session_mgr().borrow_mut();
+
+            if session_mgr.get(0, peer, None, false).is_none() {
+                session_mgr.add(peer, None)?;
+            }
+        }
+
+        self.initiate(session_id)
+    }
+
+    /// Opens a new exchange, as its initiator, over the session with ID `session_id`, e.g. to
+    /// act as a client of the peer (see [`crate::interaction_model::client`])
+    pub fn initiate(&self, session_id: SessionId) -> Result<Exchange<'_>, Error> {
+        if self.shutting_down.get() {
+            Err(ErrorCode::InvalidState)?;
+        }
+
+        let mut exchanges = self.exchanges().borrow_mut();
+
+        // A random exchange ID, not in use with the peer already
+        let id = loop {
+            let mut buf = [0; 2];
+            (self.rand)(&mut buf);
+
+            let id = ExchangeId {
+                id: u16::from_le_bytes(buf),
+                session_id: session_id.clone(),
+            };
+
+            if exchanges.iter().flatten().all(|ctx| ctx.id != id) {
+                break id;
+            }
+        };
+
+        info!("Initiating new exchange: {:?}", id);
+
+        Self::register(
+            &mut exchanges,
+            id.clone(),
+            Role::Initiator,
+            true,
+            self.epoch,
+        )?;
+
+        Ok(Exchange {
+            id,
+            matter: self,
+            notification: Notification::new(),
+        })
+    }
+
+    /// Opens a new exchange with a bound node (see [`Matter::resolve_bindings`]), over the
+    /// CASE session with the node, so as to act upon it as a client. Fails with
+    /// [`ErrorCode::NoSession`] if there is no such session - or if the binding is to a group.
+    pub fn initiate_binding(&self, binding: &Binding) -> Result<Exchange<'_>, Error> {
+        let Binding::Node { fab_idx, node_id, .. } = binding else {
+            return Err(ErrorCode::NoSession.into());
+        };
+
+        let session_id = self
+            .session_mgr()
+            .borrow()
+            .case_session(*fab_idx, *node_id)
+            .ok_or(ErrorCode::NoSession)?;
+
+        self.initiate(session_id)
+    }
+
     pub(crate) async fn evict_session(&self, tx: &mut Packet<'_>) -> Result<(), Error> {
-        let sess_index = self.session_mgr.borrow().get_session_for_eviction();
+        let policy = self.eviction_policy.get().unwrap_or(&LruEviction);
+
+        let sess_index = self
+            .session_mgr()
+            .borrow()
+            .get_session_for_eviction(policy, |session| self.session_exchanges(session));
         if let Some(sess_index) = sess_index {
-            let ctx = {
-                create_status_report(
-                    tx,
-                    GeneralCode::Success,
-                    PROTO_ID_SECURE_CHANNEL as _,
-                    SCStatusCodes::CloseSession as _,
-                    None,
-                )?;
+            self.update_stats(|stats| {
+                stats.sessions_evicted = stats.sessions_evicted.wrapping_add(1)
+            });
 
-                let mut session_mgr = self.session_mgr.borrow_mut();
-                let session_id = session_mgr.mut_by_index(sess_index).unwrap().id();
-                warn!("Evicting session: {:?}", session_id);
+            {
+                let mut session_mgr = self.session_mgr().borrow_mut();
+                let session = session_mgr
+                    .mut_by_index(sess_index)
+                    .ok_or(ErrorCode::NoSession)?;
 
-                let ctx = ExchangeCtx::prep_ephemeral(session_id, &mut session_mgr, None, tx)?;
+                if session.is_group() {
+                    // There is no peer to notify for a group session
+                    warn!("Evicting group session: {:?}", session.id());
+                    session_mgr.remove(sess_index);
 
-                session_mgr.remove(sess_index);
+                    return Ok(());
+                }
+            }
 
-                ctx
-            };
+            warn!("Evicting session");
+
+            let ctx = self.prep_close_session(sess_index, tx)?;
 
             self.send_ephemeral(ctx, tx).await
         } else {
@@ -664,9 +1523,132 @@ impl<'a> Matter<'a> {
         }
     }
 
+    /// Closes - notifying their peers with a CloseSession status report - the secure sessions
+    /// whose message counter is close to exhaustion, once no exchanges run over them anymore.
+    /// The peers are then expected to establish new sessions, with new keys and counters.
+    async fn close_exhausted_sessions(&self, tx: &mut Packet<'_>) -> Result<(), Error> {
+        loop {
+            // Not in the loop condition, so that the session manager is not borrowed in the body
+            let sess_index = self
+                .session_mgr()
+                .borrow()
+                .first_exhausted(|session| self.session_exchanges(session) > 0);
+
+            let Some(sess_index) = sess_index else {
+                break;
+            };
+
+            self.update_stats(|stats| {
+                stats.sessions_exhausted = stats.sessions_exhausted.wrapping_add(1)
+            });
+
+            warn!("Closing a session with an exhausted message counter");
+
+            let ctx = self.prep_close_session(sess_index, tx)?;
+
+            self.send_ephemeral(ctx, tx).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Prepares a CloseSession status report in `tx` for the peer of the session with index
+    /// `sess_index`, and removes the session
+    fn prep_close_session(
+        &self,
+        sess_index: usize,
+        tx: &mut Packet<'_>,
+    ) -> Result<ExchangeCtx, Error> {
+        create_status_report(
+            tx,
+            GeneralCode::Success,
+            PROTO_ID_SECURE_CHANNEL as _,
+            SCStatusCodes::CloseSession as _,
+            None,
+        )?;
+
+        let mut session_mgr = self.session_mgr().borrow_mut();
+        let session_id = session_mgr
+            .mut_by_index(sess_index)
+            .ok_or(ErrorCode::NoSession)?
+            .id();
+        info!("Closing session: {:?}", session_id);
+
+        let ctx = ExchangeCtx::prep_ephemeral(
+            session_id,
+            &mut session_mgr,
+            None,
+            tx,
+            self.packet_observer.get(),
+        )?;
+
+        session_mgr.remove(sess_index);
+
+        Ok(ctx)
+    }
+
+    /// Waits until no exchange has an ack or a retransmission pending
+    async fn wait_flushed(&self) {
+        while self.ephemeral.borrow().is_some()
+            || self
+                .exchanges()
+                .borrow()
+                .iter()
+                .flatten()
+                .any(|ctx| !ctx.mrp.is_empty())
+        {
+            Timer::after(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Notifies the peers of all secure unicast sessions that their sessions are closed, and
+    /// removes the sessions. The last step of a shutdown, once the TX pump is no longer running.
+    async fn close_sessions<S>(&self, send: &mut S) -> Result<(), Error>
+    where
+        S: NetworkSend,
+    {
+        loop {
+            // Not in the loop condition, so that the session manager is not borrowed in the body
+            let sess_index = self
+                .session_mgr()
+                .borrow()
+                .position(|session| session.is_encrypted() && !session.is_group());
+
+            let Some(sess_index) = sess_index else {
+                break;
+            };
+
+            let mut send_buf = self.tx_buf.get().await;
+            let mut tx = alloc!(Packet::new_tx(&mut send_buf));
+
+            if let Err(e) = self.prep_close_session(sess_index, &mut tx) {
+                warn!("Cannot close session, removing it anyway: {:?}", e);
+                self.session_mgr().borrow_mut().remove(sess_index);
+
+                continue;
+            }
+
+            let addr = tx.peer;
+            let local = tx.local;
+
+            let start = tx.get_writebuf()?.get_start();
+            let end = tx.get_writebuf()?.get_tail();
+
+            if let Err(e) = send.send_from(&send_buf[start..end], addr, local).await {
+                warn!("Cannot notify the peer of a closed session: {:?}", e);
+            } else {
+                self.update_stats(|stats| stats.tx_packets = stats.tx_packets.wrapping_add(1));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn send_busy(&self, rx: &Packet<'_>, tx: &mut Packet<'_>) -> Result<(), Error> {
         warn!("Sending Busy as all exchanges are occupied");
 
+        self.update_stats(|stats| stats.busy_sent = stats.busy_sent.wrapping_add(1));
+
         create_status_report(
             tx,
             GeneralCode::Busy,
@@ -676,14 +1658,115 @@ impl<'a> Matter<'a> {
             } else {
                 IMStatusCode::Busy as _
             },
-            None, // TODO: ms
+            // The minimum wait time, in ms
+            Some(&(self.busy_wait_time(rx).as_millis() as u32).to_le_bytes()),
         )?;
 
         let ctx = ExchangeCtx::prep_ephemeral(
             SessionId::load(rx),
-            &mut self.session_mgr.borrow_mut(),
+            &mut self.session_mgr().borrow_mut(),
             Some(rx),
             tx,
+            self.packet_observer.get(),
+        )?;
+
+        self.send_ephemeral(ctx, tx).await
+    }
+
+    /// The minimum time the peer should wait before retrying, when told that all exchanges
+    /// are occupied.
+    ///
+    /// Exchanges being processed locally free up quickly, but those waiting for a message
+    /// from their peer need at least a round trip each, so the time grows with their number,
+    /// starting from the retransmission interval of the session of the peer.
+    fn busy_wait_time(&self, rx: &Packet<'_>) -> core::time::Duration {
+        let interval = self.peer_retrans_interval(rx);
+
+        let waiting = self
+            .exchanges()
+            .borrow()
+            .iter()
+            .flatten()
+            .filter(|ctx| {
+                matches!(
+                    ctx.state,
+                    ExchangeState::ExchangeRecv { .. } | ExchangeState::CompleteAcknowledge { .. }
+                )
+            })
+            .count();
+
+        (interval * (1 + waiting as u32)).min(MAX_BUSY_WAIT_TIME)
+    }
+
+    /// If no new session can be established for the peer of `rx` - as all session slots are
+    /// taken, and the eviction policy does not pick any session to make room - the minimum
+    /// time the peer should wait before retrying, as advertised in the Busy status report
+    /// sent to it. Counted in [`TransportStats::case_busy_sent`].
+    ///
+    /// Sessions with exchanges in progress are only evictable once their exchanges are done,
+    /// so the time grows with the number of such exchanges, starting from the retransmission
+    /// interval of the session of the peer.
+    pub(crate) fn session_busy_wait_time(&self, rx: &Packet<'_>) -> Option<core::time::Duration> {
+        let policy = self.eviction_policy.get().unwrap_or(&LruEviction);
+
+        let (evictable, in_use) = {
+            let session_mgr = self.session_mgr().borrow();
+
+            let evictable = !session_mgr.is_full()
+                || session_mgr
+                    .get_session_for_eviction(policy, |session| self.session_exchanges(session))
+                    .is_some();
+
+            let in_use = self
+                .exchanges()
+                .borrow()
+                .iter()
+                .flatten()
+                .filter(|ctx| !matches!(ctx.state, ExchangeState::Closed))
+                .filter(|ctx| Self::ctx_session(&session_mgr, ctx).is_some())
+                .count();
+
+            (evictable, in_use)
+        };
+
+        if evictable {
+            return None;
+        }
+
+        self.update_stats(|stats| stats.case_busy_sent = stats.case_busy_sent.wrapping_add(1));
+
+        let interval = self.peer_retrans_interval(rx);
+
+        Some((interval * (1 + in_use as u32)).min(MAX_BUSY_WAIT_TIME))
+    }
+
+    /// The retransmission interval of the session of the peer of `rx`
+    fn peer_retrans_interval(&self, rx: &Packet<'_>) -> core::time::Duration {
+        let session_id = SessionId::load(rx);
+
+        let mut session_mgr = self.session_mgr().borrow_mut();
+
+        session_mgr
+            .get(
+                session_id.id,
+                session_id.peer_addr,
+                session_id.peer_nodeid,
+                session_id.is_encrypted,
+            )
+            .and_then(|sess_index| session_mgr.mut_by_index(sess_index))
+            .map(|session| session.retrans_interval(self.epoch))
+            .unwrap_or(MrpParams::new().active_interval())
+    }
+
+    async fn send_standalone_ack(&self, rx: &Packet<'_>, tx: &mut Packet<'_>) -> Result<(), Error> {
+        ReliableMessage::prepare_ack(rx.proto.exch_id, tx);
+
+        let ctx = ExchangeCtx::prep_ephemeral(
+            SessionId::load(rx),
+            &mut self.session_mgr().borrow_mut(),
+            Some(rx),
+            tx,
+            self.packet_observer.get(),
         )?;
 
         self.send_ephemeral(ctx, tx).await
@@ -714,47 +1797,85 @@ impl<'a> Matter<'a> {
 
     fn assign_exchange(
         &self,
-        exchanges: &mut heapless::Vec<ExchangeCtx, MAX_EXCHANGES>,
+        exchanges: &mut [Option<ExchangeCtx>],
         rx: &mut Packet<'_>,
     ) -> Result<(usize, bool), Error> {
         // Get the session
 
-        let mut session_mgr = self.session_mgr.borrow_mut();
+        let mut session_mgr = self.session_mgr().borrow_mut();
 
-        let sess_index = session_mgr.post_recv(rx)?;
-        let session = session_mgr.mut_by_index(sess_index).unwrap();
+        let sess_index = if rx.plain.is_group() {
+            session_mgr.post_recv_group(
+                rx,
+                &self.group_key_mgr.borrow(),
+                &self.msg_ctrs.borrow(),
+            )?
+        } else {
+            session_mgr.post_recv(rx)?
+        };
+        let session = session_mgr
+            .mut_by_index(sess_index)
+            .ok_or(ErrorCode::NoSession)?;
 
         // Decrypt the message
         session.recv(self.epoch, rx)?;
 
+        // No new exchanges over a session which is going away
+        let accept_new = !session.is_expired();
+
+        if session.is_group() {
+            if let (Some(fab_idx), Some(src_nodeid)) =
+                (session.get_local_fabric_idx(), rx.plain.get_src_u64())
+            {
+                self.msg_ctrs
+                    .borrow_mut()
+                    .update_peer(fab_idx, src_nodeid, rx.plain.ctr);
+
+                if self.msg_ctrs.borrow().is_changed() {
+                    self.notify_changed();
+                }
+            }
+        }
+
+        if let Some(observer) = self.packet_observer.get() {
+            observer.observe(PacketDirection::Rx, rx);
+        }
+
         // Get the exchange
         let (exchange_index, new) = Self::register(
             exchanges,
             ExchangeId::load(rx),
             Role::complementary(rx.proto.is_initiator()),
             // We create a new exchange, only if the peer is the initiator
-            rx.proto.is_initiator(),
+            rx.proto.is_initiator() && accept_new,
+            self.epoch,
         )?;
 
         // Message Reliability Protocol
-        exchanges[exchange_index].mrp.recv(rx, self.epoch)?;
+        let ctx = exchanges[exchange_index]
+            .as_mut()
+            .ok_or(ErrorCode::NoExchange)?;
+        ctx.mrp.recv(rx, self.epoch)?;
+        ctx.last_activity = (self.epoch)();
 
         Ok((exchange_index, new))
     }
 
     fn register(
-        exchanges: &mut heapless::Vec<ExchangeCtx, MAX_EXCHANGES>,
+        exchanges: &mut [Option<ExchangeCtx>],
         id: ExchangeId,
         role: Role,
         create_new: bool,
+        epoch: Epoch,
     ) -> Result<(usize, bool), Error> {
-        let exchange_index = exchanges
-            .iter_mut()
-            .enumerate()
-            .find_map(|(index, exchange)| (exchange.id == id).then_some(index));
+        let exchange = exchanges.iter().enumerate().find_map(|(index, exchange)| {
+            exchange
+                .as_ref()
+                .filter(|exchange| exchange.id == id)
+                .map(|exchange| (index, exchange))
+        });
 
-        if let Some(exchange_index) = exchange_index {
-            let exchange = &mut exchanges[exchange_index];
+        if let Some((exchange_index, exchange)) = exchange {
             if exchange.role == role {
                 Ok((exchange_index, false))
             } else {
@@ -763,20 +1884,620 @@ impl<'a> Matter<'a> {
         } else if create_new {
             info!("Creating new exchange: {:?}", id);
 
-            let exchange = ExchangeCtx {
-                id,
-                role,
-                mrp: ReliableMessage::new(),
-                state: ExchangeState::Active,
-            };
+            let exchange_index = exchanges
+                .iter()
+                .position(Option::is_none)
+                .ok_or(ErrorCode::NoSpaceExchanges)?;
 
-            exchanges
-                .push(exchange)
-                .map_err(|_| ErrorCode::NoSpaceExchanges)?;
+            exchanges[exchange_index] = Some(ExchangeCtx::new(id, role, epoch));
 
-            Ok((exchanges.len() - 1, true))
+            Ok((exchange_index, true))
         } else {
             Err(ErrorCode::NoExchange.into())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::cluster_basic_information::BasicInfoConfig;
+    use crate::data_model::sdm::dev_att::{DataType, DevAttDataFetcher};
+    use crate::error::Error;
+    use crate::mdns::MdnsService;
+    use core::ptr;
+    use core::time::Duration;
+
+    use embassy_futures::{block_on, poll_once};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use crate::error::ErrorCode;
+    use crate::interaction_model::core::{OpCode, PROTO_ID_INTERACTION_MODEL};
+    use crate::transport::exchange::{ExchangeCtx, ExchangeId, ExchangeState, Role, SessionId};
+    use crate::transport::network::{Address, Ipv4Addr, Loopback, NetworkReceive, SocketAddr};
+    use crate::transport::packet::{
+        Packet, MAX_RX_BUF_SIZE, MAX_RX_STATUS_BUF_SIZE, MAX_TX_BUF_SIZE,
+    };
+    use crate::transport::session::{
+        CaseDetails, CloneData, EvictionCandidate, EvictionPolicy, SessionMode, MAX_SESSIONS,
+    };
+    use crate::utils::select::Notification;
+    use crate::utils::{epoch::dummy_epoch, rand::dummy_rand};
+    use crate::{DefaultMatterStorage, Matter, MatterStorage, MATTER_PORT};
+
+    use super::TransportStats;
+
+    const BASIC_INFO: BasicInfoConfig<'static> = BasicInfoConfig {
+        vid: 10,
+        pid: 11,
+        hw_ver: 12,
+        sw_ver: 13,
+        sw_ver_str: "13",
+        serial_no: "aabbccdd",
+        device_name: "Test Device",
+        product_name: "TestProd",
+        vendor_name: "TestVendor",
+    };
+
+    struct DummyDevAtt;
+
+    impl DevAttDataFetcher for DummyDevAtt {
+        fn get_devatt_data(&self, _data_type: DataType, _data: &mut [u8]) -> Result<usize, Error> {
+            Ok(2)
+        }
+    }
+
+    fn matter() -> Matter<'static, DefaultMatterStorage> {
+        Matter::new(
+            &BASIC_INFO,
+            &DummyDevAtt,
+            MdnsService::Disabled,
+            dummy_epoch,
+            dummy_rand,
+            MATTER_PORT,
+        )
+    }
+
+    /// Adds `ctx` to the exchanges of `matter`, in the first free slot
+    fn add_exchange(matter: &Matter<'_>, ctx: ExchangeCtx) {
+        let mut exchanges = matter.exchanges().borrow_mut();
+        let slot = exchanges.iter_mut().find(|slot| slot.is_none()).unwrap();
+
+        *slot = Some(ctx);
+    }
+
+    #[test]
+    fn test_malformed_packets_are_rejected() {
+        let matter = matter();
+
+        let packets: &[&[u8]] = &[
+            // Empty
+            &[],
+            // Truncated plain header
+            &[0x00, 0x01, 0x00],
+            // Unknown message flags
+            &[0xff; 8],
+            // Source node ID flag set, but no source node ID
+            &[0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+            // Encrypted message on a non-existing session
+            &[0x00, 0x34, 0x12, 0x00, 0x01, 0x00, 0x00, 0x00, 0xa5, 0xa5],
+            // Unencrypted message with a truncated protocol header
+            &[0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x05],
+        ];
+
+        for packet in packets {
+            let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+            let rx_buf = &mut rx_buf[..packet.len()];
+            rx_buf.copy_from_slice(packet);
+
+            let mut sts_buf = [0; MAX_RX_STATUS_BUF_SIZE];
+
+            let mut rx = Packet::new_rx(rx_buf);
+            let mut sts_tx = Packet::new_tx(&mut sts_buf);
+
+            let result = embassy_futures::block_on(matter.process_rx(&mut rx, &mut sts_tx));
+
+            assert!(result.is_err(), "Packet {:x?} was not rejected", packet);
+        }
+    }
+
+    #[test]
+    fn test_unexpected_messages_are_dropped() {
+        let matter = matter();
+
+        let id = ExchangeId {
+            id: 1,
+            session_id: SessionId {
+                id: 0,
+                peer_addr: Address::default(),
+                peer_nodeid: None,
+                is_encrypted: false,
+            },
+        };
+
+        // The handler of the exchange is busy, i.e. not waiting for a message
+        let mut ctx = ExchangeCtx::new(id, Role::Responder, dummy_epoch);
+        ctx.state = ExchangeState::Active;
+
+        add_exchange(&matter, ctx);
+
+        let packets: &[&[u8]] = &[
+            // A reliable message on the busy exchange
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x02, 0x01, 0x00, 0x01, 0x00,
+                0x15, 0x18,
+            ],
+            // A message from the responder of an unknown exchange
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x05, 0x02, 0x00, 0x01, 0x00,
+                0x15, 0x18,
+            ],
+            // A standalone ack opening a new exchange
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x10, 0x03, 0x00, 0x00, 0x00,
+            ],
+        ];
+
+        for packet in packets {
+            let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+            let rx_buf = &mut rx_buf[..packet.len()];
+            rx_buf.copy_from_slice(packet);
+
+            let mut sts_buf = [0; MAX_RX_STATUS_BUF_SIZE];
+
+            let mut rx = Packet::new_rx(rx_buf);
+            let mut sts_tx = Packet::new_tx(&mut sts_buf);
+
+            let result = embassy_futures::block_on(matter.process_rx(&mut rx, &mut sts_tx));
+
+            assert!(
+                matches!(result, Ok(None)),
+                "Packet {:x?} was not dropped",
+                packet
+            );
+        }
+
+        let exchanges = matter.exchanges().borrow();
+        assert_eq!(exchanges.iter().flatten().count(), 2);
+        assert!(matches!(
+            exchanges[0].as_ref().unwrap().state,
+            ExchangeState::Active
+        ));
+        // The message dropped on the busy exchange is not acknowledged
+        assert!(exchanges[0].as_ref().unwrap().mrp.ack_deadline().is_none());
+        assert!(matches!(
+            exchanges[1].as_ref().unwrap().state,
+            ExchangeState::Closed
+        ));
+    }
+
+    #[test]
+    fn test_transport_stats() {
+        let matter = matter();
+
+        assert_eq!(matter.transport_stats(), TransportStats::new());
+
+        // Dropped packets are counted as received too
+        for _ in 0..2 {
+            let mut rx_buf = [0x00, 0x01, 0x00];
+            let mut sts_buf = [0; MAX_RX_STATUS_BUF_SIZE];
+
+            let mut rx = Packet::new_rx(&mut rx_buf);
+            let mut sts_tx = Packet::new_tx(&mut sts_buf);
+
+            let result = embassy_futures::block_on(matter.process_rx(&mut rx, &mut sts_tx));
+            assert!(result.is_err());
+        }
+
+        let stats = matter.transport_stats();
+        assert_eq!(stats.rx_packets, 2);
+        assert_eq!(stats.tx_packets, 0);
+        assert_eq!(stats.duplicates_dropped, 0);
+
+        matter.reset_transport_stats();
+        assert_eq!(matter.transport_stats(), TransportStats::default());
+    }
+
+    #[test]
+    fn test_stale_exchanges_expire() {
+        let matter = matter();
+
+        let mut tx_buf = [0; MAX_TX_BUF_SIZE];
+        let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+        let tx = Packet::new_tx(&mut tx_buf);
+        let mut rx = Packet::new_rx(&mut rx_buf);
+
+        let notifications = [Notification::new(), Notification::new()];
+
+        for (index, notification) in notifications.iter().enumerate() {
+            let id = ExchangeId {
+                id: index as _,
+                session_id: SessionId {
+                    id: 0,
+                    peer_addr: Address::default(),
+                    peer_nodeid: None,
+                    is_encrypted: false,
+                },
+            };
+
+            let mut ctx = ExchangeCtx::new(id, Role::Initiator, dummy_epoch);
+            if index == 0 {
+                ctx.timeout = Duration::ZERO;
+            }
+
+            ctx.state = ExchangeState::ExchangeRecv {
+                tx: ptr::from_ref(&tx).cast(),
+                tx_acknowledged: true,
+                rx: ptr::from_mut(&mut rx).cast(),
+                notification,
+            };
+
+            add_exchange(&matter, ctx);
+        }
+
+        let mut send_buf = [0; MAX_TX_BUF_SIZE];
+        let mut send_tx = Packet::new_tx(&mut send_buf);
+
+        assert!(!matter.pull_tx(&mut send_tx).unwrap());
+
+        // Only the exchange with the elapsed timeout is failed, and its handler is woken up
+        assert!(notifications[0].signaled());
+        assert!(!notifications[1].signaled());
+
+        let exchanges = matter.exchanges().borrow();
+        assert!(matches!(
+            exchanges[0].as_ref().unwrap().state,
+            ExchangeState::Failed(ErrorCode::RxTimeout)
+        ));
+        assert!(matches!(
+            exchanges[1].as_ref().unwrap().state,
+            ExchangeState::ExchangeRecv { .. }
+        ));
+    }
+
+    #[test]
+    fn test_shutdown_closes_sessions() {
+        let matter = matter();
+
+        let device = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 1).into(), 5540));
+        let peer = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540));
+
+        let link = Loopback::<NoopRawMutex, 4>::new(device, peer);
+        let (mut send, _) = link.a();
+        let (_, mut peer_recv) = link.b();
+
+        {
+            let mut session_mgr = matter.session_mgr().borrow_mut();
+            session_mgr.add(peer, None).unwrap();
+            session_mgr
+                .clone_session(&CloneData::new(1, 2, 3, 4, peer, SessionMode::Pase))
+                .unwrap();
+        }
+
+        matter.shutdown();
+        assert!(matter.is_shutting_down());
+
+        block_on(matter.close_sessions(&mut send)).unwrap();
+
+        // Only the peer of the secure session is notified
+        let mut buf = [0; MAX_RX_BUF_SIZE];
+        let (_, addr) = block_on(peer_recv.recv_from(&mut buf)).unwrap();
+        assert_eq!(addr, device);
+        assert!(poll_once(peer_recv.wait_available()).is_pending());
+
+        let session_mgr = matter.session_mgr().borrow();
+        assert!(session_mgr.position(|s| s.is_encrypted()).is_none());
+        assert!(session_mgr.position(|s| !s.is_encrypted()).is_some());
+    }
+
+    #[test]
+    fn test_busy_wait_time() {
+        let matter = matter();
+
+        let mut tx_buf = [0; MAX_TX_BUF_SIZE];
+        let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+        let tx = Packet::new_tx(&mut tx_buf);
+        let mut rx = Packet::new_rx(&mut rx_buf);
+
+        // No session with the peer yet, so the default active interval of MRP applies
+        assert_eq!(matter.busy_wait_time(&rx), Duration::from_millis(300));
+
+        let notification = Notification::new();
+
+        for index in 0..2 {
+            let id = ExchangeId {
+                id: index,
+                session_id: SessionId {
+                    id: 0,
+                    peer_addr: Address::default(),
+                    peer_nodeid: None,
+                    is_encrypted: false,
+                },
+            };
+
+            let mut ctx = ExchangeCtx::new(id, Role::Initiator, dummy_epoch);
+            ctx.state = ExchangeState::ExchangeRecv {
+                tx: ptr::from_ref(&tx).cast(),
+                tx_acknowledged: true,
+                rx: ptr::from_mut(&mut rx).cast(),
+                notification: &notification,
+            };
+
+            add_exchange(&matter, ctx);
+        }
+
+        // Each exchange waiting for its peer adds a round trip
+        assert_eq!(matter.busy_wait_time(&rx), Duration::from_millis(900));
+    }
+
+    #[test]
+    fn test_session_busy_wait_time() {
+        struct NoEviction;
+
+        impl EvictionPolicy for NoEviction {
+            fn select(
+                &self,
+                _candidates: &mut dyn Iterator<Item = EvictionCandidate<'_>>,
+            ) -> Option<usize> {
+                None
+            }
+        }
+
+        let matter = matter();
+
+        let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+        let rx = Packet::new_rx(&mut rx_buf);
+
+        for index in 0..MAX_SESSIONS {
+            let peer_addr = Address::Udp(SocketAddr::new(
+                Ipv4Addr::new(192, 168, 1, index as u8 + 1).into(),
+                5540,
+            ));
+
+            // Room for a new session until the last slot is taken
+            assert_eq!(matter.session_busy_wait_time(&rx), None);
+
+            matter
+                .session_mgr()
+                .borrow_mut()
+                .add(peer_addr, None)
+                .unwrap();
+        }
+
+        // The default policy always makes room
+        assert_eq!(matter.session_busy_wait_time(&rx), None);
+
+        matter.set_eviction_policy(Some(&NoEviction));
+        assert_eq!(
+            matter.session_busy_wait_time(&rx),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(matter.transport_stats().case_busy_sent, 1);
+    }
+
+    #[test]
+    fn test_status_reports_are_sent_first() {
+        let matter = matter();
+
+        let mut data_buf = [0; MAX_TX_BUF_SIZE];
+        let mut data_tx = Packet::new_tx(&mut data_buf);
+        data_tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+        data_tx.set_proto_opcode(OpCode::ReportData as _);
+
+        let mut status_buf = [0; MAX_TX_BUF_SIZE];
+        let mut status_tx = Packet::new_tx(&mut status_buf);
+        status_tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+        status_tx.set_proto_opcode(OpCode::StatusResponse as _);
+
+        let mut rx_buf = [0; MAX_RX_BUF_SIZE];
+        let mut rx = Packet::new_rx(&mut rx_buf);
+
+        let notifications = [Notification::new(), Notification::new()];
+
+        for (index, notification) in notifications.iter().enumerate() {
+            let id = ExchangeId {
+                id: index as _,
+                session_id: SessionId {
+                    id: 0,
+                    peer_addr: Address::default(),
+                    peer_nodeid: None,
+                    is_encrypted: false,
+                },
+            };
+
+            let mut ctx = ExchangeCtx::new(id, Role::Responder, dummy_epoch);
+
+            // The data message is queued first
+            let tx = if index == 0 { &data_tx } else { &status_tx };
+
+            ctx.state = ExchangeState::ExchangeSend {
+                tx: ptr::from_ref(tx).cast(),
+                rx: ptr::from_mut(&mut rx).cast(),
+                notification,
+            };
+
+            add_exchange(&matter, ctx);
+        }
+
+        let mut send_buf = [0; MAX_TX_BUF_SIZE];
+        let mut send_tx = Packet::new_tx(&mut send_buf);
+
+        assert!(matter.pull_tx(&mut send_tx).unwrap());
+        assert!(send_tx.is_status());
+
+        send_tx.reset();
+
+        assert!(matter.pull_tx(&mut send_tx).unwrap());
+        assert!(!send_tx.is_status());
+        assert_eq!(send_tx.get_proto_raw_opcode(), OpCode::ReportData as u8);
+    }
+
+    #[test]
+    fn test_unreachable_sessions_are_removed() {
+        let matter = matter();
+
+        let udp_peer = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540));
+        let tcp_peer = Address::Tcp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 3).into(), 5540));
+
+        for peer_addr in [udp_peer, tcp_peer] {
+            matter
+                .session_mgr()
+                .borrow_mut()
+                .add(peer_addr, None)
+                .unwrap();
+
+            let id = ExchangeId {
+                id: 1,
+                session_id: SessionId {
+                    id: 0,
+                    peer_addr,
+                    peer_nodeid: None,
+                    is_encrypted: false,
+                },
+            };
+
+            let mut ctx = ExchangeCtx::new(id, Role::Responder, dummy_epoch);
+            ctx.state = ExchangeState::Active;
+
+            add_exchange(&matter, ctx);
+        }
+
+        // E.g. the IP addresses of the device changed, but the TCP connections are still up
+        assert_eq!(matter.remove_unreachable_sessions(|addr| addr.is_tcp()), 1);
+
+        let session_mgr = matter.session_mgr().borrow();
+        assert!(session_mgr.get(0, udp_peer, None, false).is_none());
+        assert!(session_mgr.get(0, tcp_peer, None, false).is_some());
+
+        let exchanges = matter.exchanges().borrow();
+        assert!(matches!(
+            exchanges[0].as_ref().unwrap().state,
+            ExchangeState::Failed(ErrorCode::NoSession)
+        ));
+        assert!(matches!(
+            exchanges[1].as_ref().unwrap().state,
+            ExchangeState::Active
+        ));
+    }
+
+    #[test]
+    fn test_fabric_removal_expires_sessions() {
+        let matter = matter();
+
+        let peer_addr = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540));
+
+        // Two sessions on fabric 1, and one on fabric 2, each with an exchange
+        for (sess_id, fab_idx) in [(1, 1), (2, 1), (3, 2)] {
+            let mode = SessionMode::Case(CaseDetails::new(fab_idx, &Default::default()));
+            matter
+                .session_mgr()
+                .borrow_mut()
+                .clone_session(&CloneData::new(1, 2, sess_id, sess_id, peer_addr, mode))
+                .unwrap();
+
+            let id = ExchangeId {
+                id: sess_id,
+                session_id: SessionId {
+                    id: sess_id,
+                    peer_addr,
+                    peer_nodeid: None,
+                    is_encrypted: true,
+                },
+            };
+
+            let mut ctx = ExchangeCtx::new(id, Role::Responder, dummy_epoch);
+            ctx.state = ExchangeState::Active;
+
+            add_exchange(&matter, ctx);
+        }
+
+        // The fabric is removed over the exchange of the first session
+        let keep = matter.exchanges().borrow()[0].as_ref().unwrap().id.clone();
+        matter.remove_fabric(1, Some(&keep));
+
+        {
+            let exchanges = matter.exchanges().borrow();
+            assert!(matches!(
+                exchanges[0].as_ref().unwrap().state,
+                ExchangeState::Active
+            ));
+            assert!(matches!(
+                exchanges[1].as_ref().unwrap().state,
+                ExchangeState::Failed(ErrorCode::NoSession)
+            ));
+            assert!(matches!(
+                exchanges[2].as_ref().unwrap().state,
+                ExchangeState::Active
+            ));
+        }
+
+        // Expired sessions stay until their exchanges are gone
+        matter.exchanges().borrow_mut()[1].as_mut().unwrap().state = ExchangeState::Closed;
+        matter.purge().unwrap();
+
+        {
+            let session_mgr = matter.session_mgr().borrow();
+            assert!(session_mgr.get(1, peer_addr, None, true).is_some());
+            assert!(session_mgr.get(2, peer_addr, None, true).is_none());
+            assert!(session_mgr.get(3, peer_addr, None, true).is_some());
+        }
+
+        matter.exchanges().borrow_mut()[0].as_mut().unwrap().state = ExchangeState::Closed;
+        matter.purge().unwrap();
+
+        let session_mgr = matter.session_mgr().borrow();
+        assert!(session_mgr.get(1, peer_addr, None, true).is_none());
+        assert!(session_mgr.get(3, peer_addr, None, true).is_some());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let matter = Matter::<MatterStorage<2, 1, 1>>::new_with_capacity(
+            &BASIC_INFO,
+            &DummyDevAtt,
+            MdnsService::Disabled,
+            dummy_epoch,
+            dummy_rand,
+            MATTER_PORT,
+        );
+
+        assert_eq!(matter.fabric_mgr().borrow().capacity(), 1);
+
+        let peer_addr = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540));
+
+        {
+            let mut session_mgr = matter.session_mgr().borrow_mut();
+            assert_eq!(session_mgr.capacity(), 2);
+
+            session_mgr.add(peer_addr, None).unwrap();
+            session_mgr.add(peer_addr, None).unwrap();
+            assert!(session_mgr.is_full());
+            assert_eq!(
+                session_mgr.add(peer_addr, None).map_err(|e| e.code()),
+                Err(ErrorCode::NoSpaceSessions)
+            );
+        }
+
+        let mut exchanges = matter.exchanges().borrow_mut();
+        assert_eq!(exchanges.len(), 1);
+
+        let id = |id| ExchangeId {
+            id,
+            session_id: SessionId {
+                id: 0,
+                peer_addr,
+                peer_nodeid: None,
+                is_encrypted: false,
+            },
+        };
+
+        assert_eq!(
+            Matter::register(&mut exchanges, id(1), Role::Responder, true, dummy_epoch).unwrap(),
+            (0, true)
+        );
+        assert_eq!(
+            Matter::register(&mut exchanges, id(2), Role::Responder, true, dummy_epoch)
+                .map_err(|e| e.code()),
+            Err(ErrorCode::NoSpaceExchanges)
+        );
+    }
+}
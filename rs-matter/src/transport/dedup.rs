@@ -41,6 +41,11 @@ impl RxCtrState {
 
     /// Receive a message and update Rx State accordingly
     /// Returns a bool indicating whether the message is a duplicate
+    /// The largest message counter received so far
+    pub fn max_ctr(&self) -> u32 {
+        self.max_ctr
+    }
+
     pub fn recv(&mut self, msg_ctr: u32, is_encrypted: bool) -> bool {
         let idiff = (msg_ctr as i32) - (self.max_ctr as i32);
         let udiff = idiff.unsigned_abs();
@@ -1,18 +1,24 @@
+use core::time::Duration;
+
 use crate::{
     acl::Accessor,
     error::{Error, ErrorCode},
-    utils::{epoch::Epoch, select::Notification},
+    utils::{epoch::Epoch, rand::Rand, select::Notification},
     Matter,
 };
 
 use super::{
+    core::{PacketDirection, PacketObserver},
     mrp::ReliableMessage,
     network::Address,
     packet::Packet,
     session::{CloneData, Session, SessionMgr},
 };
 
-pub const MAX_EXCHANGES: usize = 8;
+pub const MAX_EXCHANGES: usize = crate::config::MAX_EXCHANGES;
+
+/// How long an exchange waits for a message from the peer, before it is closed
+pub const DEFAULT_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub(crate) enum Role {
@@ -37,14 +43,63 @@ pub(crate) struct ExchangeCtx {
     pub(crate) role: Role,
     pub(crate) mrp: ReliableMessage,
     pub(crate) state: ExchangeState,
+    /// When a message was last sent or received on the exchange
+    pub(crate) last_activity: Duration,
+    pub(crate) timeout: Duration,
+    /// When the timed window opened by a TimedRequest on the exchange closes, if any
+    pub(crate) timed_deadline: Option<Duration>,
 }
 
 impl ExchangeCtx {
     pub(crate) fn get<'r>(
-        exchanges: &'r mut heapless::Vec<ExchangeCtx, MAX_EXCHANGES>,
+        exchanges: &'r mut [Option<ExchangeCtx>],
         id: &ExchangeId,
     ) -> Option<&'r mut ExchangeCtx> {
-        exchanges.iter_mut().find(|exchange| exchange.id == *id)
+        exchanges
+            .iter_mut()
+            .flatten()
+            .find(|exchange| exchange.id == *id)
+    }
+
+    pub(crate) fn new(id: ExchangeId, role: Role, epoch: Epoch) -> Self {
+        Self {
+            id,
+            role,
+            mrp: ReliableMessage::new(),
+            state: ExchangeState::Active,
+            last_activity: epoch(),
+            timeout: DEFAULT_EXCHANGE_TIMEOUT,
+            timed_deadline: None,
+        }
+    }
+
+    /// The earliest time at which the exchange needs attention from the transport - i.e. for
+    /// (re)sending a message or an acknowledgement, or for expiring - if any
+    pub(crate) fn deadline(&self) -> Option<Duration> {
+        // Only these states retransmit, see `Matter::pull_tx`
+        let retrans = matches!(
+            self.state,
+            ExchangeState::ExchangeRecv {
+                tx_acknowledged: false,
+                ..
+            } | ExchangeState::CompleteAcknowledge { .. }
+        )
+        .then(|| self.mrp.retrans_deadline())
+        .flatten();
+
+        let expiry = matches!(self.state, ExchangeState::ExchangeRecv { .. })
+            .then(|| self.last_activity + self.timeout);
+
+        [retrans, self.mrp.ack_deadline(), expiry]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Whether the exchange has been waiting for a message from the peer for too long
+    pub(crate) fn is_expired(&self, epoch: Epoch) -> bool {
+        matches!(self.state, ExchangeState::ExchangeRecv { .. })
+            && self.last_activity + self.timeout <= epoch()
     }
 
     pub fn new_ephemeral(session_id: SessionId, reply_to: Option<&Packet<'_>>) -> Self {
@@ -57,13 +112,16 @@ impl ExchangeCtx {
                 },
                 session_id: session_id.clone(),
             },
-            role: if reply_to.is_some() {
-                Role::Responder
+            role: if let Some(rx) = reply_to {
+                Role::complementary(rx.proto.is_initiator())
             } else {
                 Role::Initiator
             },
             mrp: ReliableMessage::new(),
             state: ExchangeState::Active,
+            last_activity: Duration::ZERO,
+            timeout: DEFAULT_EXCHANGE_TIMEOUT,
+            timed_deadline: None,
         }
     }
 
@@ -72,6 +130,7 @@ impl ExchangeCtx {
         session_mgr: &mut SessionMgr,
         reply_to: Option<&Packet<'_>>,
         tx: &mut Packet<'_>,
+        observer: Option<&dyn PacketObserver>,
     ) -> Result<ExchangeCtx, Error> {
         let mut ctx = Self::new_ephemeral(session_id.clone(), reply_to);
 
@@ -94,12 +153,19 @@ impl ExchangeCtx {
         tx.unset_reliable();
 
         if let Some(sess_index) = sess_index {
-            let session = session_mgr.mut_by_index(sess_index).unwrap();
-            ctx.pre_send_sess(session, tx, epoch)?;
+            let session = session_mgr
+                .mut_by_index(sess_index)
+                .ok_or(ErrorCode::NoSession)?;
+            ctx.pre_send_sess(session, tx, epoch, rand, observer)?;
         } else {
             let mut session =
                 Session::new(session_id.peer_addr, session_id.peer_nodeid, epoch, rand);
-            ctx.pre_send_sess(&mut session, tx, epoch)?;
+            ctx.pre_send_sess(&mut session, tx, epoch, rand, observer)?;
+        }
+
+        if let Some(rx) = reply_to {
+            // Reply from the address the peer sent its message to
+            tx.local = rx.local;
         }
 
         Ok(ctx)
@@ -109,8 +175,10 @@ impl ExchangeCtx {
         &mut self,
         session_mgr: &mut SessionMgr,
         tx: &mut Packet,
+        observer: Option<&dyn PacketObserver>,
     ) -> Result<(), Error> {
         let epoch = session_mgr.epoch;
+        let rand = session_mgr.rand;
 
         let sess_index = session_mgr
             .get(
@@ -121,9 +189,25 @@ impl ExchangeCtx {
             )
             .ok_or(ErrorCode::NoSession)?;
 
-        let session = session_mgr.mut_by_index(sess_index).unwrap();
+        let session = session_mgr
+            .mut_by_index(sess_index)
+            .ok_or(ErrorCode::NoSession)?;
 
-        self.pre_send_sess(session, tx, epoch)
+        self.pre_send_sess(session, tx, epoch, rand, observer)
+    }
+
+    /// Whether this is the exchange of a received group message, which cannot be responded to
+    pub(crate) fn is_group_rx(&self, session_mgr: &mut SessionMgr) -> bool {
+        session_mgr
+            .get(
+                self.id.session_id.id,
+                self.id.session_id.peer_addr,
+                self.id.session_id.peer_nodeid,
+                self.id.session_id.is_encrypted,
+            )
+            .and_then(|sess_index| session_mgr.mut_by_index(sess_index))
+            .map(|session| session.is_group() && session.get_peer_node_id().is_some())
+            .unwrap_or(false)
     }
 
     pub(crate) fn pre_send_sess(
@@ -131,6 +215,8 @@ impl ExchangeCtx {
         session: &mut Session,
         tx: &mut Packet,
         epoch: Epoch,
+        rand: Rand,
+        observer: Option<&dyn PacketObserver>,
     ) -> Result<(), Error> {
         tx.proto.exch_id = self.id.id;
         if self.role == Role::Initiator {
@@ -138,7 +224,15 @@ impl ExchangeCtx {
         }
 
         session.pre_send(tx)?;
-        self.mrp.pre_send(tx)?;
+        self.mrp
+            .pre_send(tx, session.retrans_interval(epoch), epoch, rand)?;
+
+        if let Some(observer) = observer {
+            // The last chance to observe the packet before it gets encrypted
+            tx.peer = session.get_peer_addr();
+            observer.observe(PacketDirection::Tx, tx);
+        }
+
         session.send(epoch, tx)
     }
 }
@@ -159,7 +253,7 @@ pub(crate) enum ExchangeState {
         notification: *const Notification,
     },
     ExchangeRecv {
-        _tx: *const Packet<'static>,
+        tx: *const Packet<'static>,
         tx_acknowledged: bool,
         rx: *mut Packet<'static>,
         notification: *const Notification,
@@ -169,15 +263,17 @@ pub(crate) enum ExchangeState {
         notification: *const Notification,
     },
     CompleteAcknowledge {
-        _tx: *const Packet<'static>,
+        tx: *const Packet<'static>,
         notification: *const Notification,
     },
+    /// The peer did not acknowledge the last message of the exchange, even after all
+    /// retransmissions (`TxTimeout`), or did not respond to it in time (`RxTimeout`)
+    Failed(ErrorCode),
     Closed,
 }
 
 pub struct ExchangeCtr<'a> {
     pub(crate) exchange: Exchange<'a>,
-    pub(crate) construction_notification: &'a Notification,
 }
 
 impl<'a> ExchangeCtr<'a> {
@@ -189,8 +285,6 @@ impl<'a> ExchangeCtr<'a> {
     // Should be #[allow(clippy::needless_pass_by_ref_mut)], but this is only in 1.73 which is not released yet
     // rx is actually modified, but via an unsafe `*mut Packet<'static>` and apparently Clippy can't see this
     pub async fn get(mut self, rx: &mut Packet<'_>) -> Result<Exchange<'a>, Error> {
-        let construction_notification = self.construction_notification;
-
         self.exchange.with_ctx_mut(move |exchange, ctx| {
             if !matches!(ctx.state, ExchangeState::Active) {
                 Err(ErrorCode::NoExchange)?;
@@ -202,7 +296,7 @@ impl<'a> ExchangeCtr<'a> {
 
             ctx.state = ExchangeState::Construction { rx, notification };
 
-            construction_notification.signal(());
+            exchange.matter.construction_notification.signal(());
 
             Ok(())
         })?;
@@ -265,7 +359,7 @@ impl<'a> Exchange<'a> {
         F: FnOnce(&mut Session) -> Result<T, Error>,
     {
         self.with_ctx(|_self, ctx| {
-            let mut session_mgr = _self.matter.session_mgr.borrow_mut();
+            let mut session_mgr = _self.matter.session_mgr().borrow_mut();
 
             let sess_index = session_mgr
                 .get(
@@ -276,7 +370,9 @@ impl<'a> Exchange<'a> {
                 )
                 .ok_or(ErrorCode::NoSession)?;
 
-            f(session_mgr.mut_by_index(sess_index).unwrap())
+            f(session_mgr
+                .mut_by_index(sess_index)
+                .ok_or(ErrorCode::NoSession)?)
         })
     }
 
@@ -287,6 +383,29 @@ impl<'a> Exchange<'a> {
         self.with_session_mut(|sess| f(sess))
     }
 
+    /// Sets how long the exchange waits for a message from the peer, before it is closed.
+    /// The default is [`DEFAULT_EXCHANGE_TIMEOUT`].
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.with_ctx_mut(|_, ctx| {
+            ctx.timeout = timeout;
+            Ok(())
+        })
+    }
+
+    /// Opens the timed window of the exchange - on a TimedRequest - which closes at `deadline`.
+    /// The Write or Invoke request following the TimedRequest is only accepted within it.
+    pub fn set_timed_deadline(&mut self, deadline: Duration) -> Result<(), Error> {
+        self.with_ctx_mut(|_, ctx| {
+            ctx.timed_deadline = Some(deadline);
+            Ok(())
+        })
+    }
+
+    /// When the timed window of the exchange closes, if a TimedRequest opened one
+    pub fn timed_deadline(&self) -> Result<Option<Duration>, Error> {
+        self.with_ctx(|_, ctx| Ok(ctx.timed_deadline))
+    }
+
     pub async fn acknowledge(&mut self) -> Result<(), Error> {
         let wait = self.with_ctx_mut(|_self, ctx| {
             if !matches!(ctx.state, ExchangeState::Active) {
@@ -325,8 +444,8 @@ impl<'a> Exchange<'a> {
                 Err(ErrorCode::NoExchange)?;
             }
 
-            let mut session_mgr = _self.matter.session_mgr.borrow_mut();
-            ctx.pre_send(&mut session_mgr, tx)?;
+            let mut session_mgr = _self.matter.session_mgr().borrow_mut();
+            ctx.pre_send(&mut session_mgr, tx, _self.matter.packet_observer.get())?;
 
             ctx.state = ExchangeState::ExchangeSend {
                 tx: tx as *const _,
@@ -340,7 +459,7 @@ impl<'a> Exchange<'a> {
 
         self.notification.wait().await;
 
-        Ok(())
+        self.check_delivered()
     }
 
     pub async fn complete(mut self, tx: &mut Packet<'_>) -> Result<(), Error> {
@@ -350,13 +469,20 @@ impl<'a> Exchange<'a> {
     pub async fn send_complete(&mut self, tx: &mut Packet<'_>) -> Result<(), Error> {
         let tx: &mut Packet<'static> = unsafe { core::mem::transmute(tx) };
 
-        self.with_ctx_mut(|_self, ctx| {
+        let send = self.with_ctx_mut(|_self, ctx| {
             if !matches!(ctx.state, ExchangeState::Active) {
                 Err(ErrorCode::NoExchange)?;
             }
 
-            let mut session_mgr = _self.matter.session_mgr.borrow_mut();
-            ctx.pre_send(&mut session_mgr, tx)?;
+            let mut session_mgr = _self.matter.session_mgr().borrow_mut();
+
+            if ctx.is_group_rx(&mut session_mgr) {
+                // Responses to group messages are suppressed
+                ctx.state = ExchangeState::Closed;
+                return Ok(false);
+            }
+
+            ctx.pre_send(&mut session_mgr, tx, _self.matter.packet_observer.get())?;
 
             ctx.state = ExchangeState::Complete {
                 tx: tx as *const _,
@@ -364,16 +490,18 @@ impl<'a> Exchange<'a> {
             };
             _self.matter.send_notification.signal(());
 
-            Ok(())
+            Ok(true)
         })?;
 
-        self.notification.wait().await;
+        if send {
+            self.notification.wait().await;
+        }
 
-        Ok(())
+        self.check_delivered()
     }
 
     pub(crate) fn get_next_sess_id(&mut self) -> u16 {
-        self.matter.session_mgr.borrow_mut().get_next_sess_id()
+        self.matter.session_mgr().borrow_mut().get_next_sess_id()
     }
 
     pub(crate) async fn clone_session(
@@ -384,7 +512,7 @@ impl<'a> Exchange<'a> {
         loop {
             let result = self
                 .matter
-                .session_mgr
+                .session_mgr()
                 .borrow_mut()
                 .clone_session(clone_data);
 
@@ -397,11 +525,31 @@ impl<'a> Exchange<'a> {
         }
     }
 
+    fn check_delivered(&self) -> Result<(), Error> {
+        let failed = self
+            .matter
+            .exchanges()
+            .borrow()
+            .iter()
+            .flatten()
+            .find(|ctx| ctx.id == self.id)
+            .and_then(|ctx| match ctx.state {
+                ExchangeState::Failed(code) => Some(code),
+                _ => None,
+            });
+
+        if let Some(code) = failed {
+            Err(code)?;
+        }
+
+        Ok(())
+    }
+
     fn with_ctx<F, T>(&self, f: F) -> Result<T, Error>
     where
         F: FnOnce(&Self, &ExchangeCtx) -> Result<T, Error>,
     {
-        let mut exchanges = self.matter.exchanges.borrow_mut();
+        let mut exchanges = self.matter.exchanges().borrow_mut();
 
         let exchange = ExchangeCtx::get(&mut exchanges, &self.id).ok_or(ErrorCode::NoExchange)?; // TODO
 
@@ -412,7 +560,7 @@ impl<'a> Exchange<'a> {
     where
         F: FnOnce(&mut Self, &mut ExchangeCtx) -> Result<T, Error>,
     {
-        let mut exchanges = self.matter.exchanges.borrow_mut();
+        let mut exchanges = self.matter.exchanges().borrow_mut();
 
         let exchange = ExchangeCtx::get(&mut exchanges, &self.id).ok_or(ErrorCode::NoExchange)?; // TODO
 
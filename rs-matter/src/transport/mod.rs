@@ -15,12 +15,34 @@
  *    limitations under the License.
  */
 
+// A malformed packet must never be able to bring down the transport, so the
+// transport code is not allowed to use any of the explicitly panicking macros,
+// nor to unwrap. The few unwraps which cannot fail are allowed one by one,
+// with the reason why
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::unreachable,
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used
+    )
+)]
+
+pub mod btp;
 pub mod core;
 mod dedup;
 pub mod exchange;
 pub mod mrp;
+pub mod msg_ctr;
+pub mod mux;
 pub mod network;
 pub mod packet;
+#[cfg(feature = "std")]
+pub mod pcap;
 pub mod plain_hdr;
 pub mod proto_hdr;
 pub mod session;
+pub mod tcp;
@@ -15,7 +15,7 @@
  *    limitations under the License.
  */
 
-use crate::utils::epoch::Epoch;
+use crate::utils::{epoch::Epoch, rand::Rand};
 use core::time::Duration;
 
 use crate::{error::*, secure_channel, transport::packet::Packet};
@@ -24,21 +24,158 @@ use log::error;
 // 200 ms
 const MRP_STANDALONE_ACK_TIMEOUT: u64 = 200;
 
+/// The maximum number of transmissions of a reliable message, including the initial one
+pub const MRP_MAX_TRANSMISSIONS: usize = 5;
+
+// The defaults of the session parameters of a peer which did not advertise them, in ms
+pub(crate) const DEFAULT_SESSION_IDLE_INTERVAL: u32 = 500;
+pub(crate) const DEFAULT_SESSION_ACTIVE_INTERVAL: u32 = 300;
+pub(crate) const DEFAULT_SESSION_ACTIVE_THRESHOLD: u16 = 4000;
+
+// The maximum value of the idle and active intervals: 1 hour, in ms
+const MAX_SESSION_INTERVAL: u32 = 3_600_000;
+
+/// The MRP parameters of a peer, as advertised by it in its session parameters - see
+/// [`crate::transport::session::SessionParams`]. All values are in milliseconds, and the spec
+/// defaults apply to those which are not advertised.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MrpParams {
+    /// SESSION_IDLE_INTERVAL: the retransmission interval when the peer is idle
+    pub idle_interval: Option<u32>,
+    /// SESSION_ACTIVE_INTERVAL: the retransmission interval when the peer is active
+    pub active_interval: Option<u32>,
+    /// SESSION_ACTIVE_THRESHOLD: how long the peer stays active after its last message
+    pub active_threshold: Option<u16>,
+}
+
+impl MrpParams {
+    pub const fn new() -> Self {
+        Self {
+            idle_interval: None,
+            active_interval: None,
+            active_threshold: None,
+        }
+    }
+
+    pub fn idle_interval(&self) -> Duration {
+        Self::interval(self.idle_interval, DEFAULT_SESSION_IDLE_INTERVAL)
+    }
+
+    pub fn active_interval(&self) -> Duration {
+        Self::interval(self.active_interval, DEFAULT_SESSION_ACTIVE_INTERVAL)
+    }
+
+    pub fn active_threshold(&self) -> Duration {
+        Duration::from_millis(
+            self.active_threshold
+                .unwrap_or(DEFAULT_SESSION_ACTIVE_THRESHOLD) as _,
+        )
+    }
+
+    /// Whether a peer which sent its last message `since_rx` ago is still in active mode
+    pub fn is_active(&self, since_rx: Duration) -> bool {
+        since_rx <= self.active_threshold()
+    }
+
+    /// The base retransmission interval for a peer which sent its last message `since_rx` ago
+    pub fn retrans_interval(&self, since_rx: Duration) -> Duration {
+        if self.is_active(since_rx) {
+            self.active_interval()
+        } else {
+            self.idle_interval()
+        }
+    }
+
+    fn interval(value: Option<u32>, default: u32) -> Duration {
+        Duration::from_millis(value.unwrap_or(default).min(MAX_SESSION_INTERVAL) as _)
+    }
+}
+
+// MRP_BACKOFF_BASE (1.6) and MRP_BACKOFF_MARGIN (1.1), as fractions
+const MRP_BACKOFF_BASE: (u64, u64) = (16, 10);
+const MRP_BACKOFF_MARGIN: (u64, u64) = (11, 10);
+
+// MRP_BACKOFF_JITTER (0.25), as a fraction
+const MRP_BACKOFF_JITTER: (u64, u64) = (25, 100);
+
+// The number of transmissions after which the exponential backoff kicks in
+const MRP_BACKOFF_THRESHOLD: usize = 1;
+
 #[derive(Debug)]
 pub struct RetransEntry {
     // The msg counter that we are waiting to be acknowledged
     msg_ctr: u32,
-    // This will additionally have retransmission count and periods once we implement it
+    // How many times the message was sent so far
+    transmissions: usize,
+    // When should the message be sent again, if it is still not acknowledged
+    next_transmission: Duration,
+    // The base retransmission interval of the peer
+    interval: Duration,
 }
 
 impl RetransEntry {
-    pub fn new(msg_ctr: u32) -> Self {
-        Self { msg_ctr }
+    pub fn new(msg_ctr: u32, interval: Duration, epoch: Epoch, rand: Rand) -> Self {
+        let mut entry = Self {
+            msg_ctr,
+            transmissions: 0,
+            next_transmission: Duration::ZERO,
+            interval,
+        };
+
+        entry.transmitted(epoch, rand);
+
+        entry
     }
 
     pub fn get_msg_ctr(&self) -> u32 {
         self.msg_ctr
     }
+
+    pub fn is_due(&self, epoch: Epoch) -> bool {
+        self.next_transmission <= epoch()
+    }
+
+    /// When the message should be sent again, if it is still not acknowledged
+    pub fn next_transmission(&self) -> Duration {
+        self.next_transmission
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.transmissions >= MRP_MAX_TRANSMISSIONS
+    }
+
+    fn transmitted(&mut self, epoch: Epoch, rand: Rand) {
+        self.next_transmission =
+            epoch() + Self::jitter(Self::backoff(self.interval, self.transmissions), rand);
+        self.transmissions += 1;
+    }
+
+    /// The time to wait for an acknowledgement after the `n`-th transmission of a message (`n` starting from 0),
+    /// with `i` being the base retransmission `interval` of the peer, as per the spec formula
+    /// (sans the random jitter):
+    /// `MRP_BACKOFF_MARGIN * i * MRP_BACKOFF_BASE ^ max(0, n - MRP_BACKOFF_THRESHOLD)`
+    pub fn backoff(interval: Duration, n: usize) -> Duration {
+        let mut ms = interval.as_millis() as u64 * MRP_BACKOFF_MARGIN.0 / MRP_BACKOFF_MARGIN.1;
+
+        for _ in 0..n.saturating_sub(MRP_BACKOFF_THRESHOLD) {
+            ms = ms * MRP_BACKOFF_BASE.0 / MRP_BACKOFF_BASE.1;
+        }
+
+        Duration::from_millis(ms)
+    }
+
+    /// Applies the random jitter of the spec formula to `backoff`:
+    /// `backoff * (1 + random * MRP_BACKOFF_JITTER)`, with `random` in the range [0, 1),
+    /// so that peers which lost messages at the same time do not retransmit them in lockstep
+    pub fn jitter(backoff: Duration, rand: Rand) -> Duration {
+        let mut random = [0; 1];
+        rand(&mut random);
+
+        let ms = backoff.as_millis() as u64;
+        let jitter = ms * MRP_BACKOFF_JITTER.0 * random[0] as u64 / (MRP_BACKOFF_JITTER.1 * 256);
+
+        Duration::from_millis(ms + jitter)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,7 +205,13 @@ impl AckEntry {
     }
 
     pub fn has_timed_out(&self, epoch: Epoch) -> bool {
-        self.ack_timeout > epoch()
+        self.ack_timeout <= epoch()
+    }
+
+    /// When the acknowledgement has to be sent as a standalone one, if it could not be
+    /// piggybacked on a message by then
+    pub fn ack_timeout(&self) -> Duration {
+        self.ack_timeout
     }
 }
 
@@ -99,11 +242,72 @@ impl ReliableMessage {
         }
     }
 
+    /// Whether the message we are waiting an acknowledgement for should be sent again
+    pub fn is_retrans_due(&self, epoch: Epoch) -> bool {
+        self.retrans
+            .as_ref()
+            .map(|entry| entry.is_due(epoch))
+            .unwrap_or(false)
+    }
+
+    /// When the message we are waiting an acknowledgement for is due for a retransmission, if any
+    pub fn retrans_deadline(&self) -> Option<Duration> {
+        self.retrans.as_ref().map(RetransEntry::next_transmission)
+    }
+
+    /// When the pending acknowledgement is due to be sent as a standalone one, if any
+    pub fn ack_deadline(&self) -> Option<Duration> {
+        self.ack.as_ref().map(AckEntry::ack_timeout)
+    }
+
+    /// Registers the retransmission of the message we are waiting an acknowledgement for.
+    ///
+    /// Returns `false` if the message was already sent the maximum number of times, in which
+    /// case the message is given up on and the exchange should be considered failed.
+    pub fn retransmit(&mut self, epoch: Epoch, rand: Rand) -> bool {
+        let Some(entry) = self.retrans.as_mut() else {
+            return false;
+        };
+
+        if entry.is_exhausted() {
+            error!(
+                "Message with counter {} not acknowledged after {} transmissions",
+                entry.get_msg_ctr(),
+                MRP_MAX_TRANSMISSIONS
+            );
+
+            self.retrans = None;
+
+            false
+        } else {
+            entry.transmitted(epoch, rand);
+
+            true
+        }
+    }
+
+    /// Forgets the pending acknowledgement of the message with counter `msg_ctr`, if any,
+    /// so that the peer retransmits a message which was dropped without being processed
+    pub fn cancel_ack(&mut self, msg_ctr: u32) {
+        if self.ack.as_ref().map(AckEntry::get_msg_ctr) == Some(msg_ctr) {
+            self.ack = None;
+        }
+    }
+
     pub fn prepare_ack(_exch_id: u16, proto_tx: &mut Packet) {
         secure_channel::common::create_mrp_standalone_ack(proto_tx);
     }
 
-    pub fn pre_send(&mut self, proto_tx: &mut Packet) -> Result<(), Error> {
+    /// Prepares `proto_tx` for sending, piggybacking any pending acknowledgement on it.
+    /// Reliable messages are then retransmitted based on `interval` - the base retransmission
+    /// interval of the peer (see [`MrpParams::retrans_interval`]) - until acknowledged.
+    pub fn pre_send(
+        &mut self,
+        proto_tx: &mut Packet,
+        interval: Duration,
+        epoch: Epoch,
+        rand: Rand,
+    ) -> Result<(), Error> {
         // Check if any acknowledgements are pending for this exchange,
 
         // if so, piggy back in the encoded header here
@@ -123,7 +327,7 @@ impl ReliableMessage {
             Err(ErrorCode::Invalid)?;
         }
 
-        self.retrans = Some(RetransEntry::new(proto_tx.plain.ctr));
+        self.retrans = Some(RetransEntry::new(proto_tx.plain.ctr, interval, epoch, rand));
         Ok(())
     }
 
@@ -158,3 +362,112 @@ impl ReliableMessage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::transport::packet::Packet;
+    use crate::utils::epoch::dummy_epoch;
+    use crate::utils::rand::dummy_rand;
+
+    use super::{MrpParams, ReliableMessage, RetransEntry, MRP_MAX_TRANSMISSIONS};
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_millis(300);
+
+        assert_eq!(
+            RetransEntry::backoff(interval, 0),
+            Duration::from_millis(330)
+        );
+        assert_eq!(
+            RetransEntry::backoff(interval, 1),
+            Duration::from_millis(330)
+        );
+        assert_eq!(
+            RetransEntry::backoff(interval, 2),
+            Duration::from_millis(528)
+        );
+        assert_eq!(
+            RetransEntry::backoff(interval, 3),
+            Duration::from_millis(844)
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        let backoff = Duration::from_millis(1000);
+
+        // No jitter with the lowest random value, and up to MRP_BACKOFF_JITTER with the highest
+        assert_eq!(RetransEntry::jitter(backoff, |buf| buf.fill(0)), backoff);
+        assert_eq!(
+            RetransEntry::jitter(backoff, |buf| buf.fill(0x80)),
+            Duration::from_millis(1125)
+        );
+        assert_eq!(
+            RetransEntry::jitter(backoff, |buf| buf.fill(0xff)),
+            Duration::from_millis(1249)
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        // The spec defaults
+        let params = MrpParams::new();
+        assert_eq!(params.active_interval(), Duration::from_millis(300));
+        assert_eq!(params.idle_interval(), Duration::from_millis(500));
+        assert_eq!(params.active_threshold(), Duration::from_millis(4000));
+
+        // A sleepy peer
+        let params = MrpParams {
+            idle_interval: Some(30_000),
+            active_interval: Some(1000),
+            active_threshold: Some(2000),
+        };
+
+        assert_eq!(
+            params.retrans_interval(Duration::from_millis(1500)),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            params.retrans_interval(Duration::from_millis(2500)),
+            Duration::from_millis(30_000)
+        );
+        assert!(params.is_active(Duration::from_millis(2000)));
+        assert!(!params.is_active(Duration::from_millis(2001)));
+
+        // Capped to 1 hour
+        let params = MrpParams {
+            idle_interval: Some(u32::MAX),
+            ..MrpParams::new()
+        };
+        assert_eq!(params.idle_interval(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_retransmit() {
+        let mut buf = [0; 64];
+        let mut tx = Packet::new_tx(&mut buf);
+        tx.plain.ctr = 5;
+        tx.set_reliable();
+
+        let mut mrp = ReliableMessage::new();
+        mrp.pre_send(&mut tx, Duration::from_millis(300), dummy_epoch, dummy_rand)
+            .unwrap();
+        assert!(!mrp.is_empty());
+        assert_eq!(mrp.retrans_deadline(), Some(Duration::from_millis(330)));
+        assert_eq!(mrp.ack_deadline(), None);
+
+        // The dummy epoch never advances, so nothing is due yet
+        assert!(!mrp.is_retrans_due(dummy_epoch));
+
+        for _ in 1..MRP_MAX_TRANSMISSIONS {
+            assert!(mrp.retransmit(dummy_epoch, dummy_rand));
+        }
+
+        assert!(!mrp.retransmit(dummy_epoch, dummy_rand));
+        assert!(mrp.is_empty());
+        assert_eq!(mrp.retrans_deadline(), None);
+    }
+}
@@ -0,0 +1,348 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! The message counters which have to survive a reboot.
+//!
+//! Unicast sessions do not need any: their keys - and thus their counters - are gone after
+//! a reboot anyway. Group messages - and ICD Check-In messages - however are encrypted with
+//! long-lived keys, so:
+//! - the counter of the group messages sent by this node must never go back, or the members
+//!   of the group would drop the messages as duplicates (or worse, accept replays of them).
+//!   The same goes for the counter of the Check-In messages sent to the ICD clients.
+//!   Instead of storing the counter on every message, an upper bound of it is stored, moved
+//!   forward by [`MSG_COUNTER_WINDOW`] well before the counter reaches it. After a reboot,
+//!   counting resumes from that bound;
+//! - the highest counter received from each group peer is stored as well, so that replays
+//!   of its messages are not accepted after a reboot. To bound the number of writes, it is only
+//!   stored every [`MSG_COUNTER_WINDOW`] messages, so up to that many of the most recent messages
+//!   of a peer might be accepted again after a reboot.
+//!
+//! The counters are persisted together with the fabrics and the ACLs
+//! (see [`crate::Matter::store_msg_counters`]).
+
+use heapless::Vec;
+
+use crate::{
+    error::{Error, ErrorCode},
+    tlv::{self, FromTLV, TLVList, TLVWriter, TagType, ToTLV},
+    utils::{rand::Rand, writebuf::WriteBuf},
+};
+
+pub use crate::config::{MAX_GROUP_PEERS, MSG_COUNTER_WINDOW};
+
+const MATTER_MSG_CTR_RANGE: u32 = 0x0fffffff;
+
+const TAG_GROUP_CTR_LIMIT: u8 = 1;
+const TAG_PEERS: u8 = 2;
+const TAG_CHECK_IN_CTR_LIMIT: u8 = 3;
+
+/// The highest group message counter received from a peer
+#[derive(Debug, Clone, PartialEq, Eq, ToTLV, FromTLV)]
+struct PeerCtr {
+    fab_idx: u8,
+    node_id: u64,
+    max_ctr: u32,
+}
+
+/// A counter of the messages sent by this node, which never goes back - even across reboots
+struct PersistedCtr {
+    /// The next counter, or `None` if neither loaded, nor initialized yet
+    next: Option<u32>,
+    /// The stored upper bound of `next`
+    limit: u32,
+}
+
+impl PersistedCtr {
+    const fn new() -> Self {
+        Self {
+            next: None,
+            limit: 0,
+        }
+    }
+
+    fn load(&mut self, limit: u32) {
+        self.next = Some(limit);
+        self.limit = limit;
+    }
+
+    /// Returns the next counter, without moving it forward.
+    /// A random counter is picked the first time, unless a stored one was loaded.
+    fn current(&mut self, rand: Rand) -> u32 {
+        *self.next.get_or_insert_with(|| {
+            let mut buf = [0; 4];
+            rand(&mut buf);
+
+            let ctr = (u32::from_be_bytes(buf) & MATTER_MSG_CTR_RANGE) + 1;
+            self.limit = ctr;
+
+            ctr
+        })
+    }
+
+    /// Returns the next counter, and whether its upper bound needs to be stored again.
+    /// A random counter is picked the first time, unless a stored one was loaded.
+    fn next(&mut self, rand: Rand) -> (u32, bool) {
+        let ctr = self.current(rand);
+
+        let mut changed = false;
+
+        if ctr >= self.limit.saturating_sub(MSG_COUNTER_WINDOW / 2) {
+            // Move the bound forward, while there is still room for half a window of messages
+            // until it is persisted
+            self.limit = ctr.saturating_add(MSG_COUNTER_WINDOW);
+            changed = true;
+        }
+
+        self.next = Some(ctr.wrapping_add(1));
+
+        (ctr, changed)
+    }
+}
+
+pub struct MsgCounterMgr {
+    /// The counter of the group messages sent by this node
+    group_ctr: PersistedCtr,
+    /// The counter of the ICD Check-In messages sent by this node
+    check_in_ctr: PersistedCtr,
+    peers: Vec<PeerCtr, MAX_GROUP_PEERS>,
+    changed: bool,
+}
+
+impl MsgCounterMgr {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            group_ctr: PersistedCtr::new(),
+            check_in_ctr: PersistedCtr::new(),
+            peers: Vec::new(),
+            changed: false,
+        }
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVList::new(data).iter().next().ok_or(ErrorCode::Invalid)?;
+
+        let limit = root.find_tag(TAG_GROUP_CTR_LIMIT as _)?.u32()?;
+        let peers = root.find_tag(TAG_PEERS as _)?;
+
+        tlv::from_tlv(&mut self.peers, &peers)?;
+        self.group_ctr.load(limit);
+
+        // Not there, if stored before any Check-In message was sent
+        if let Ok(limit) = root.find_tag(TAG_CHECK_IN_CTR_LIMIT as _) {
+            self.check_in_ctr.load(limit.u32()?);
+        }
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.changed {
+            let mut wb = WriteBuf::new(buf);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            tw.start_struct(TagType::Anonymous)?;
+            tw.u32(TagType::Context(TAG_GROUP_CTR_LIMIT), self.group_ctr.limit)?;
+            self.peers
+                .as_slice()
+                .to_tlv(&mut tw, TagType::Context(TAG_PEERS))?;
+            if self.check_in_ctr.next.is_some() {
+                tw.u32(
+                    TagType::Context(TAG_CHECK_IN_CTR_LIMIT),
+                    self.check_in_ctr.limit,
+                )?;
+            }
+            tw.end_container()?;
+
+            self.changed = false;
+
+            let len = tw.get_tail();
+
+            Ok(Some(&buf[..len]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Returns the counter of the next group message sent by this node.
+    /// A random counter is picked the first time, unless a stored one was loaded.
+    pub fn next_group_ctr(&mut self, rand: Rand) -> u32 {
+        let (ctr, changed) = self.group_ctr.next(rand);
+        self.changed |= changed;
+
+        ctr
+    }
+
+    /// Returns the counter of the next ICD Check-In message sent by this node.
+    /// A random counter is picked the first time, unless a stored one was loaded.
+    pub fn next_check_in_ctr(&mut self, rand: Rand) -> u32 {
+        let (ctr, changed) = self.check_in_ctr.next(rand);
+        self.changed |= changed;
+
+        ctr
+    }
+
+    /// Returns the counter of the next ICD Check-In message sent by this node, without using it
+    /// up - i.e. the ICDCounter reported to the ICD clients when they register
+    pub fn check_in_ctr(&mut self, rand: Rand) -> u32 {
+        self.check_in_ctr.current(rand)
+    }
+
+    /// The highest counter stored for the group messages of a peer, if any
+    pub fn peer_max_ctr(&self, fab_idx: u8, node_id: u64) -> Option<u32> {
+        self.peers
+            .iter()
+            .find(|peer| peer.fab_idx == fab_idx && peer.node_id == node_id)
+            .map(|peer| peer.max_ctr)
+    }
+
+    /// Records the counter of a group message received - and successfully decrypted - from a peer.
+    /// The least recently added peer is forgotten, if there is no room for a new one.
+    pub fn update_peer(&mut self, fab_idx: u8, node_id: u64, ctr: u32) {
+        if let Some(peer) = self
+            .peers
+            .iter_mut()
+            .find(|peer| peer.fab_idx == fab_idx && peer.node_id == node_id)
+        {
+            if ctr >= peer.max_ctr.saturating_add(MSG_COUNTER_WINDOW) {
+                peer.max_ctr = ctr;
+                self.changed = true;
+            }
+        } else {
+            if self.peers.is_full() {
+                self.peers.remove(0);
+            }
+
+            let _ = self.peers.push(PeerCtr {
+                fab_idx,
+                node_id,
+                max_ctr: ctr,
+            });
+
+            self.changed = true;
+        }
+    }
+
+    /// Removes the counters of all peers on the given fabric, e.g. when the fabric is removed
+    pub fn remove_fabric(&mut self, fab_idx: u8) {
+        let len = self.peers.len();
+
+        self.peers.retain(|peer| peer.fab_idx != fab_idx);
+
+        if self.peers.len() != len {
+            self.changed = true;
+        }
+    }
+}
+
+impl Default for MsgCounterMgr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::rand::dummy_rand;
+
+    use super::{MsgCounterMgr, MSG_COUNTER_WINDOW};
+
+    #[test]
+    fn test_group_ctr_survives_reboot() {
+        let mut mgr = MsgCounterMgr::new();
+
+        let first = mgr.next_group_ctr(dummy_rand);
+        assert!(mgr.is_changed());
+
+        for ctr in first + 1..first + 10 {
+            assert_eq!(mgr.next_group_ctr(dummy_rand), ctr);
+        }
+
+        let mut buf = [0; 256];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+        assert!(!mgr.is_changed());
+
+        // After a reboot, counting resumes beyond any counter used before
+        let mut loaded = MsgCounterMgr::new();
+        loaded.load(data).unwrap();
+
+        let ctr = loaded.next_group_ctr(dummy_rand);
+        assert!(ctr > first + 10);
+        assert_eq!(ctr, first + MSG_COUNTER_WINDOW);
+    }
+
+    #[test]
+    fn test_check_in_ctr_survives_reboot() {
+        let mut mgr = MsgCounterMgr::new();
+
+        let group = mgr.next_group_ctr(dummy_rand);
+        let first = mgr.next_check_in_ctr(dummy_rand);
+        assert_eq!(mgr.next_check_in_ctr(dummy_rand), first + 1);
+
+        let mut buf = [0; 256];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+
+        let mut loaded = MsgCounterMgr::new();
+        loaded.load(data).unwrap();
+
+        // Both counters resume independently
+        assert_eq!(
+            loaded.next_check_in_ctr(dummy_rand),
+            first + MSG_COUNTER_WINDOW
+        );
+        assert_eq!(
+            loaded.next_group_ctr(dummy_rand),
+            group + MSG_COUNTER_WINDOW
+        );
+    }
+
+    #[test]
+    fn test_peer_ctr() {
+        let mut mgr = MsgCounterMgr::new();
+
+        assert_eq!(mgr.peer_max_ctr(1, 100), None);
+
+        mgr.update_peer(1, 100, 5);
+        assert_eq!(mgr.peer_max_ctr(1, 100), Some(5));
+
+        // Only stored once per window
+        mgr.update_peer(1, 100, 6);
+        assert_eq!(mgr.peer_max_ctr(1, 100), Some(5));
+        mgr.update_peer(1, 100, 5 + MSG_COUNTER_WINDOW);
+        assert_eq!(mgr.peer_max_ctr(1, 100), Some(5 + MSG_COUNTER_WINDOW));
+
+        mgr.update_peer(2, 100, 7);
+
+        let mut buf = [0; 256];
+        let data = mgr.store(&mut buf).unwrap().unwrap();
+
+        let mut loaded = MsgCounterMgr::new();
+        loaded.load(data).unwrap();
+        assert_eq!(loaded.peer_max_ctr(1, 100), Some(5 + MSG_COUNTER_WINDOW));
+        assert_eq!(loaded.peer_max_ctr(2, 100), Some(7));
+
+        loaded.remove_fabric(1);
+        assert_eq!(loaded.peer_max_ctr(1, 100), None);
+        assert!(loaded.is_changed());
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Running several transports at the same time.
+//!
+//! [`crate::Matter::run`] takes a single [`NetworkSend`] / [`NetworkReceive`] pair.
+//! [`MuxSend`] and [`MuxReceive`] combine the UDP, TCP and BTP transports into such a pair:
+//! - incoming packets are received from whichever transport has one available first. Each
+//!   packet is reported with the [`Address`] variant of its transport, so the sessions
+//!   established over it remember the transport they belong to;
+//! - outgoing packets are routed to the transport matching the [`Address`] variant of their
+//!   peer (i.e. of their session).
+//!
+//! Transports which are not used are replaced with [`NoNetwork`].
+
+use embassy_futures::select::{select3, Either3};
+
+use log::warn;
+
+use crate::error::{Error, ErrorCode};
+
+use super::network::{Address, LocalAddr, NetworkReceive, NetworkSend};
+
+/// A placeholder for a transport which is not in use: it never receives anything and
+/// refuses to send.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoNetwork;
+
+impl NetworkSend for NoNetwork {
+    async fn send_to(&mut self, _data: &[u8], addr: Address) -> Result<(), Error> {
+        warn!(
+            "Dropping packet for {}: no transport for this address",
+            addr
+        );
+
+        Err(ErrorCode::InvalidPeerAddr.into())
+    }
+}
+
+impl NetworkReceive for NoNetwork {
+    async fn wait_available(&mut self) -> Result<(), Error> {
+        core::future::pending().await
+    }
+
+    async fn recv_from(&mut self, _buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+        core::future::pending().await
+    }
+}
+
+/// Routes each outgoing packet to the UDP, TCP or BTP transport, depending on its peer address
+pub struct MuxSend<U, T, B> {
+    udp: U,
+    tcp: T,
+    btp: B,
+}
+
+impl<U, T, B> MuxSend<U, T, B>
+where
+    U: NetworkSend,
+    T: NetworkSend,
+    B: NetworkSend,
+{
+    pub const fn new(udp: U, tcp: T, btp: B) -> Self {
+        Self { udp, tcp, btp }
+    }
+}
+
+impl<U, T, B> NetworkSend for MuxSend<U, T, B>
+where
+    U: NetworkSend,
+    T: NetworkSend,
+    B: NetworkSend,
+{
+    async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
+        match addr {
+            Address::Udp(_) => self.udp.send_to(data, addr).await,
+            Address::Tcp(_) => self.tcp.send_to(data, addr).await,
+            Address::Btp(_) => self.btp.send_to(data, addr).await,
+        }
+    }
+
+    async fn send_from(
+        &mut self,
+        data: &[u8],
+        addr: Address,
+        local: Option<LocalAddr>,
+    ) -> Result<(), Error> {
+        match addr {
+            Address::Udp(_) => self.udp.send_from(data, addr, local).await,
+            Address::Tcp(_) => self.tcp.send_from(data, addr, local).await,
+            Address::Btp(_) => self.btp.send_from(data, addr, local).await,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+    Btp,
+}
+
+/// Receives incoming packets from the UDP, TCP and BTP transports, whichever has one first.
+///
+/// The [`NetworkReceive::wait_available`] futures of the multiplexed transports are dropped
+/// as soon as one of them completes, so they need to be cancel-safe.
+pub struct MuxReceive<U, T, B> {
+    udp: U,
+    tcp: T,
+    btp: B,
+    ready: Option<Transport>,
+}
+
+impl<U, T, B> MuxReceive<U, T, B>
+where
+    U: NetworkReceive,
+    T: NetworkReceive,
+    B: NetworkReceive,
+{
+    pub const fn new(udp: U, tcp: T, btp: B) -> Self {
+        Self {
+            udp,
+            tcp,
+            btp,
+            ready: None,
+        }
+    }
+}
+
+impl<U, T, B> NetworkReceive for MuxReceive<U, T, B>
+where
+    U: NetworkReceive,
+    T: NetworkReceive,
+    B: NetworkReceive,
+{
+    async fn wait_available(&mut self) -> Result<(), Error> {
+        if self.ready.is_none() {
+            let ready = match select3(
+                self.udp.wait_available(),
+                self.tcp.wait_available(),
+                self.btp.wait_available(),
+            )
+            .await
+            {
+                Either3::First(result) => result.map(|_| Transport::Udp),
+                Either3::Second(result) => result.map(|_| Transport::Tcp),
+                Either3::Third(result) => result.map(|_| Transport::Btp),
+            }?;
+
+            self.ready = Some(ready);
+        }
+
+        Ok(())
+    }
+
+    async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+        loop {
+            self.wait_available().await?;
+
+            match self.ready.take() {
+                Some(Transport::Udp) => break self.udp.recv_from(buffer).await,
+                Some(Transport::Tcp) => break self.tcp.recv_from(buffer).await,
+                Some(Transport::Btp) => break self.btp.recv_from(buffer).await,
+                None => continue,
+            }
+        }
+    }
+
+    async fn recv_from_local(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, Address, Option<LocalAddr>), Error> {
+        loop {
+            self.wait_available().await?;
+
+            match self.ready.take() {
+                Some(Transport::Udp) => break self.udp.recv_from_local(buffer).await,
+                Some(Transport::Tcp) => break self.tcp.recv_from_local(buffer).await,
+                Some(Transport::Btp) => break self.btp.recv_from_local(buffer).await,
+                None => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Error, ErrorCode};
+    use crate::transport::network::{
+        Address, BtAddr, Ipv4Addr, NetworkReceive, NetworkSend, SocketAddr,
+    };
+
+    use super::{MuxReceive, MuxSend, NoNetwork};
+
+    /// A transport which records the sent packets and receives from a fixed list
+    #[derive(Default)]
+    struct Network {
+        sent: std::vec::Vec<Address>,
+        received: std::vec::Vec<Address>,
+    }
+
+    impl NetworkSend for &mut Network {
+        async fn send_to(&mut self, _data: &[u8], addr: Address) -> Result<(), Error> {
+            self.sent.push(addr);
+
+            Ok(())
+        }
+    }
+
+    impl NetworkReceive for &mut Network {
+        async fn wait_available(&mut self) -> Result<(), Error> {
+            if self.received.is_empty() {
+                core::future::pending().await
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+            self.wait_available().await?;
+
+            buffer[0] = 1;
+
+            Ok((1, self.received.remove(0)))
+        }
+    }
+
+    fn ip() -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540)
+    }
+
+    #[test]
+    fn test_send_routing() {
+        let mut udp = Network::default();
+        let mut tcp = Network::default();
+
+        {
+            let mut send = MuxSend::new(&mut udp, &mut tcp, NoNetwork);
+
+            embassy_futures::block_on(send.send_to(&[1], Address::Udp(ip()))).unwrap();
+            embassy_futures::block_on(send.send_to(&[1], Address::Tcp(ip()))).unwrap();
+            embassy_futures::block_on(send.send_to(&[1], Address::Udp(ip()))).unwrap();
+
+            // No BTP transport
+            let result =
+                embassy_futures::block_on(send.send_to(&[1], Address::Btp(BtAddr([0; 6]))));
+            assert_eq!(result.unwrap_err().code(), ErrorCode::InvalidPeerAddr);
+        }
+
+        assert_eq!(udp.sent, [Address::Udp(ip()), Address::Udp(ip())]);
+        assert_eq!(tcp.sent, [Address::Tcp(ip())]);
+    }
+
+    #[test]
+    fn test_recv() {
+        let mut udp = Network::default();
+        let mut btp = Network {
+            received: std::vec![Address::Btp(BtAddr([1; 6]))],
+            ..Default::default()
+        };
+
+        let mut recv = MuxReceive::new(&mut udp, NoNetwork, &mut btp);
+        let mut buf = [0; 8];
+
+        let (len, addr) = embassy_futures::block_on(recv.recv_from(&mut buf)).unwrap();
+        assert_eq!(len, 1);
+        assert_eq!(addr, Address::Btp(BtAddr([1; 6])));
+
+        // Nothing more to receive
+        assert!(embassy_futures::poll_once(recv.wait_available()).is_pending());
+    }
+}
@@ -16,17 +16,42 @@
  */
 
 use core::fmt::{Debug, Display};
+use core::future::poll_fn;
 
 #[cfg(not(feature = "std"))]
 pub use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 #[cfg(feature = "std")]
 pub use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
-use crate::error::Error;
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::channel::Channel;
+
+use crate::error::{Error, ErrorCode};
+
+use super::packet::MAX_TX_BUF_SIZE;
+
+/// The address of a Bluetooth LE device
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BtAddr(pub [u8; 6]);
+
+impl Display for BtAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
+        )
+    }
+}
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub enum Address {
     Udp(SocketAddr),
+    /// The remote end of an established TCP connection. Sessions established over TCP
+    /// are bound to that connection, as the peer address is part of the session lookup key.
+    Tcp(SocketAddr),
+    /// The central of a BTP session
+    Btp(BtAddr),
 }
 
 impl Address {
@@ -37,16 +62,47 @@ impl Address {
     pub fn is_reliable(&self) -> bool {
         match self {
             Self::Udp(_) => false,
+            Self::Tcp(_) | Self::Btp(_) => true,
         }
     }
 
-    pub fn unwrap_udp(self) -> SocketAddr {
+    pub fn is_tcp(&self) -> bool {
+        matches!(self, Self::Tcp(_))
+    }
+
+    pub fn udp(&self) -> Option<SocketAddr> {
         match self {
-            Self::Udp(addr) => addr,
+            Self::Udp(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    pub fn tcp(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    pub fn btp(&self) -> Option<BtAddr> {
+        match self {
+            Self::Btp(addr) => Some(*addr),
+            _ => None,
         }
     }
 }
 
+/// The local end of a received UDP packet: the (destination) IP address it was sent to, and
+/// the index of the network interface it was received over, if known.
+///
+/// On hosts with several IPv6 addresses per interface, replies have to be sent from the address
+/// the peer sent its request to, or the peer might drop them as coming from an unknown address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct LocalAddr {
+    pub ip: IpAddr,
+    pub interface: Option<u32>,
+}
+
 impl Default for Address {
     fn default() -> Self {
         Self::new()
@@ -57,6 +113,8 @@ impl Display for Address {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Address::Udp(addr) => write!(f, "UDP {}", addr),
+            Address::Tcp(addr) => write!(f, "TCP {}", addr),
+            Address::Btp(addr) => write!(f, "BTP {}", addr),
         }
     }
 }
@@ -65,12 +123,30 @@ impl Debug for Address {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Address::Udp(addr) => writeln!(f, "{}", addr),
+            Address::Tcp(addr) => writeln!(f, "TCP {}", addr),
+            Address::Btp(addr) => writeln!(f, "BTP {}", addr),
         }
     }
 }
 
 pub trait NetworkSend {
     async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error>;
+
+    /// Same as [`NetworkSend::send_to`], but sends the packet from the `local` address,
+    /// if provided - usually the one the peer sent its last packet to.
+    ///
+    /// Implementations which cannot select the source address (the default one) send
+    /// the packet from whatever address the OS picks.
+    async fn send_from(
+        &mut self,
+        data: &[u8],
+        addr: Address,
+        local: Option<LocalAddr>,
+    ) -> Result<(), Error> {
+        let _ = local;
+
+        self.send_to(data, addr).await
+    }
 }
 
 impl<T> NetworkSend for &mut T
@@ -80,12 +156,34 @@ where
     async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
         (*self).send_to(data, addr).await
     }
+
+    async fn send_from(
+        &mut self,
+        data: &[u8],
+        addr: Address,
+        local: Option<LocalAddr>,
+    ) -> Result<(), Error> {
+        (*self).send_from(data, addr, local).await
+    }
 }
 
 pub trait NetworkReceive {
     async fn wait_available(&mut self) -> Result<(), Error>;
 
     async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error>;
+
+    /// Same as [`NetworkReceive::recv_from`], but also returns the local address the packet
+    /// was received on, if known (e.g. from `IPV6_PKTINFO`).
+    ///
+    /// The default implementation does not know it.
+    async fn recv_from_local(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, Address, Option<LocalAddr>), Error> {
+        let (len, addr) = self.recv_from(buffer).await?;
+
+        Ok((len, addr, None))
+    }
 }
 
 impl<T> NetworkReceive for &mut T
@@ -99,6 +197,149 @@ where
     async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
         (*self).recv_from(buffer).await
     }
+
+    async fn recv_from_local(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, Address, Option<LocalAddr>), Error> {
+        (*self).recv_from_local(buffer).await
+    }
+}
+
+/// A packet in flight over a [`Loopback`] link, together with the address of its sender
+type LoopbackPacket = (Address, heapless::Vec<u8, MAX_TX_BUF_SIZE>);
+
+/// An in-memory link between two ends - e.g. two `Matter` instances, or a device and
+/// a test driver - for running the full stack without sockets.
+///
+/// Each end is given an address of its own. Packets sent by one end are received by the other
+/// one as coming from the address of the sending end, so sessions and exchanges work
+/// exactly as they do over a real network.
+///
+/// Up to `N` packets can be queued in each direction; sending blocks while the queue is full.
+pub struct Loopback<M, const N: usize>
+where
+    M: RawMutex,
+{
+    a: Address,
+    b: Address,
+    a_to_b: Channel<M, LoopbackPacket, N>,
+    b_to_a: Channel<M, LoopbackPacket, N>,
+}
+
+impl<M, const N: usize> Loopback<M, N>
+where
+    M: RawMutex,
+{
+    /// Creates a link between an end with address `a` and an end with address `b`
+    pub const fn new(a: Address, b: Address) -> Self {
+        Self {
+            a,
+            b,
+            a_to_b: Channel::new(),
+            b_to_a: Channel::new(),
+        }
+    }
+
+    /// Returns the send and receive halves of the end with address `a`
+    pub fn a(&self) -> (LoopbackSend<'_, M, N>, LoopbackReceive<'_, M, N>) {
+        (
+            LoopbackSend {
+                local: self.a,
+                peer: self.b,
+                channel: &self.a_to_b,
+            },
+            LoopbackReceive {
+                local: self.a,
+                channel: &self.b_to_a,
+            },
+        )
+    }
+
+    /// Returns the send and receive halves of the end with address `b`
+    pub fn b(&self) -> (LoopbackSend<'_, M, N>, LoopbackReceive<'_, M, N>) {
+        (
+            LoopbackSend {
+                local: self.b,
+                peer: self.a,
+                channel: &self.b_to_a,
+            },
+            LoopbackReceive {
+                local: self.b,
+                channel: &self.a_to_b,
+            },
+        )
+    }
+}
+
+/// The sending half of one end of a [`Loopback`] link
+pub struct LoopbackSend<'a, M, const N: usize>
+where
+    M: RawMutex,
+{
+    local: Address,
+    peer: Address,
+    channel: &'a Channel<M, LoopbackPacket, N>,
+}
+
+impl<'a, M, const N: usize> NetworkSend for LoopbackSend<'a, M, N>
+where
+    M: RawMutex,
+{
+    async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
+        if addr != self.peer {
+            Err(ErrorCode::InvalidPeerAddr)?;
+        }
+
+        let data = heapless::Vec::from_slice(data).map_err(|_| ErrorCode::NoSpace)?;
+
+        self.channel.send((self.local, data)).await;
+
+        Ok(())
+    }
+}
+
+/// The receiving half of one end of a [`Loopback`] link
+pub struct LoopbackReceive<'a, M, const N: usize>
+where
+    M: RawMutex,
+{
+    local: Address,
+    channel: &'a Channel<M, LoopbackPacket, N>,
+}
+
+impl<'a, M, const N: usize> NetworkReceive for LoopbackReceive<'a, M, N>
+where
+    M: RawMutex,
+{
+    async fn wait_available(&mut self) -> Result<(), Error> {
+        poll_fn(|cx| self.channel.poll_ready_to_receive(cx)).await;
+
+        Ok(())
+    }
+
+    async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+        let (addr, data) = self.channel.receive().await;
+
+        let buffer = buffer.get_mut(..data.len()).ok_or(ErrorCode::NoSpace)?;
+        buffer.copy_from_slice(&data);
+
+        Ok((data.len(), addr))
+    }
+
+    async fn recv_from_local(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, Address, Option<LocalAddr>), Error> {
+        let (len, addr) = self.recv_from(buffer).await?;
+
+        let local = self.local.udp().map(|local| LocalAddr {
+            ip: local.ip(),
+            interface: None,
+        });
+
+        Ok((len, addr, local))
+    }
 }
 
 #[cfg(all(feature = "std", feature = "async-io"))]
@@ -115,7 +356,9 @@ mod async_io {
 
     impl NetworkSend for &Async<UdpSocket> {
         async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
-            Async::<UdpSocket>::send_to(self, data, addr.unwrap_udp()).await?;
+            let addr = addr.udp().ok_or(ErrorCode::InvalidPeerAddr)?;
+
+            Async::<UdpSocket>::send_to(self, data, addr).await?;
 
             Ok(())
         }
@@ -143,3 +386,53 @@ mod async_io {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::{block_on, poll_once};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::{Address, Ipv6Addr, LocalAddr, Loopback, NetworkReceive, NetworkSend, SocketAddr};
+
+    #[test]
+    fn test_loopback() {
+        let device = Address::Udp(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 5540));
+        let driver = Address::Udp(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 5541));
+
+        let link = Loopback::<NoopRawMutex, 2>::new(device, driver);
+
+        let (mut device_send, mut device_recv) = link.a();
+        let (mut driver_send, mut driver_recv) = link.b();
+
+        let mut buf = [0; 16];
+
+        // Nothing to receive yet
+        assert!(poll_once(driver_recv.wait_available()).is_pending());
+
+        block_on(device_send.send_to(&[1, 2, 3], driver)).unwrap();
+        block_on(driver_recv.wait_available()).unwrap();
+        let (len, addr) = block_on(driver_recv.recv_from(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[1, 2, 3]);
+        assert_eq!(addr, device);
+
+        // The receiving end knows the address the packet was sent to
+        block_on(driver_send.send_to(&[4, 5], device)).unwrap();
+        let (len, addr, local) = block_on(device_recv.recv_from_local(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[4, 5]);
+        assert_eq!(addr, driver);
+        assert_eq!(
+            local,
+            Some(LocalAddr {
+                ip: Ipv6Addr::LOCALHOST.into(),
+                interface: None,
+            })
+        );
+
+        // Only the other end is reachable
+        assert!(block_on(device_send.send_to(&[1], device)).is_err());
+
+        // The receive buffer is too small
+        block_on(device_send.send_to(&[1, 2, 3], driver)).unwrap();
+        assert!(block_on(driver_recv.recv_from(&mut buf[..2])).is_err());
+    }
+}
@@ -27,14 +27,33 @@ use crate::{
 };
 
 use super::{
-    network::Address,
+    network::{Address, LocalAddr},
     plain_hdr::{self, PlainHdr},
     proto_hdr::{self, ProtoHdr},
 };
 
-pub const MAX_RX_BUF_SIZE: usize = 1583;
+/// The maximum size of a message received over a transport limited by the IPv6 MTU (UDP, BTP)
+pub const MAX_RX_MTU_SIZE: usize = 1583;
+/// The maximum size of a message sent over a transport limited by the IPv6 MTU (UDP, BTP)
+pub const MAX_TX_MTU_SIZE: usize = 1280 - 40/*IPV6 header size*/ - 8/*UDP header size*/;
+
+/// The maximum size of a Large Payload message, which can only be exchanged over TCP
+pub const MAX_LARGE_MSG_SIZE: usize = 64000;
+
+/// The size of the packet buffers. With the `large-payload` feature, the buffers are large enough
+/// for Large Payload messages; the messages of sessions over other transports are still limited
+/// to the MTU (see [`super::session::Session::max_tx_size`]).
+pub const MAX_RX_BUF_SIZE: usize = if cfg!(feature = "large-payload") {
+    MAX_LARGE_MSG_SIZE
+} else {
+    MAX_RX_MTU_SIZE
+};
 pub const MAX_RX_STATUS_BUF_SIZE: usize = 100;
-pub const MAX_TX_BUF_SIZE: usize = 1280 - 40/*IPV6 header size*/ - 8/*UDP header size*/;
+pub const MAX_TX_BUF_SIZE: usize = if cfg!(feature = "large-payload") {
+    MAX_LARGE_MSG_SIZE
+} else {
+    MAX_TX_MTU_SIZE
+};
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum RxState {
@@ -76,6 +95,9 @@ pub struct Packet<'a> {
     pub plain: PlainHdr,
     pub proto: ProtoHdr,
     pub peer: Address,
+    /// The local address a received packet was sent to, or the one a packet to be sent
+    /// should be sent from, if known
+    pub local: Option<LocalAddr>,
     data: Direction<'a>,
 }
 
@@ -87,12 +109,16 @@ impl<'a> Packet<'a> {
             plain: Default::default(),
             proto: Default::default(),
             peer: Address::default(),
+            local: None,
             data: Direction::Rx(ParseBuf::new(buf), RxState::Uninit),
         }
     }
 
     pub fn new_tx(buf: &'a mut [u8]) -> Self {
         let mut wb = WriteBuf::new(buf);
+        // A TX buffer with no room even for the headers is a bug of the caller, which no
+        // peer can trigger
+        #[allow(clippy::unwrap_used)]
         wb.reserve(Packet::HDR_RESERVE).unwrap();
 
         // Reliability on by default
@@ -103,6 +129,7 @@ impl<'a> Packet<'a> {
             plain: Default::default(),
             proto,
             peer: Address::default(),
+            local: None,
             data: Direction::Tx(wb),
         }
     }
@@ -110,11 +137,14 @@ impl<'a> Packet<'a> {
     pub fn reset(&mut self) {
         if let Direction::Tx(wb) = &mut self.data {
             wb.reset();
+            // The same reservation already succeeded when the packet was created
+            #[allow(clippy::unwrap_used)]
             wb.reserve(Packet::HDR_RESERVE).unwrap();
 
             self.plain = Default::default();
             self.proto = Default::default();
             self.peer = Address::default();
+            self.local = None;
 
             self.proto.set_reliable();
         }
@@ -124,6 +154,7 @@ impl<'a> Packet<'a> {
         self.plain = packet.plain.clone();
         self.proto = packet.proto.clone();
         self.peer = packet.peer;
+        self.local = packet.local;
         self.data.load(&packet.data)
     }
 
@@ -185,6 +216,22 @@ impl<'a> Packet<'a> {
         self.proto.proto_opcode = proto_opcode;
     }
 
+    /// Whether the packet is a Secure Channel status report or an Interaction Model
+    /// status response
+    pub fn is_status(&self) -> bool {
+        match self.get_proto_id() {
+            PROTO_ID_SECURE_CHANNEL => {
+                self.get_proto_raw_opcode()
+                    == crate::secure_channel::common::OpCode::StatusReport as u8
+            }
+            PROTO_ID_INTERACTION_MODEL => {
+                self.get_proto_raw_opcode()
+                    == crate::interaction_model::core::OpCode::StatusResponse as u8
+            }
+            _ => false,
+        }
+    }
+
     pub fn set_reliable(&mut self) {
         self.proto.set_reliable()
     }
@@ -0,0 +1,271 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Capturing the messages exchanged by the stack in the pcap format, for inspecting them
+//! in Wireshark.
+//!
+//! ```ignore
+//! let capture = PcapWriter::new(std::fs::File::create("matter.pcap")?)?;
+//! matter.set_packet_observer(Some(Box::leak(Box::new(capture))));
+//! ```
+//!
+//! Every message is written as a raw IP/UDP datagram between the peer and the local
+//! [`MATTER_PORT`] (the local IP address is not known to the stack, so it is left unspecified).
+//! As the messages are captured in plain text, they are rewritten as unsecured messages - with
+//! session ID 0 - so that Wireshark decodes their protocol header and payload instead of
+//! trying to decrypt them.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::warn;
+
+use crate::error::Error;
+use crate::utils::writebuf::WriteBuf;
+use crate::MATTER_PORT;
+
+use super::core::{PacketDirection, PacketObserver};
+use super::network::{Address, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::packet::Packet;
+use super::plain_hdr::{self, SessionType};
+use super::proto_hdr;
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_SNAPLEN: u32 = 65535;
+/// Raw IPv4 or IPv6 packets, without a link-layer header
+const LINKTYPE_RAW: u32 = 101;
+
+const IPV4_HDR_LEN: usize = 20;
+const IPV6_HDR_LEN: usize = 40;
+const UDP_HDR_LEN: usize = 8;
+const IP_PROTO_UDP: u8 = 17;
+
+/// A [`PacketObserver`] writing all messages into `W` as a pcap capture
+pub struct PcapWriter<W>
+where
+    W: Write,
+{
+    writer: RefCell<W>,
+}
+
+impl<W> PcapWriter<W>
+where
+    W: Write,
+{
+    /// Creates the writer, writing the pcap file header right away
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        writer.write_all(&2_u16.to_le_bytes())?;
+        writer.write_all(&4_u16.to_le_bytes())?;
+        // Timezone offset and timestamp accuracy
+        writer.write_all(&0_i32.to_le_bytes())?;
+        writer.write_all(&0_u32.to_le_bytes())?;
+        writer.write_all(&PCAP_SNAPLEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+
+        Ok(Self {
+            writer: RefCell::new(writer),
+        })
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    fn write(&self, direction: PacketDirection, packet: &Packet<'_>) -> Result<(), Error> {
+        let datagram = Self::datagram(direction, packet)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut writer = self.writer.borrow_mut();
+
+        writer.write_all(&(now.as_secs() as u32).to_le_bytes())?;
+        writer.write_all(&now.subsec_micros().to_le_bytes())?;
+        writer.write_all(&(datagram.len() as u32).to_le_bytes())?;
+        writer.write_all(&(datagram.len() as u32).to_le_bytes())?;
+        writer.write_all(&datagram)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn datagram(direction: PacketDirection, packet: &Packet<'_>) -> Result<Vec<u8>, Error> {
+        let payload = packet.as_slice();
+
+        let max_len = plain_hdr::max_plain_hdr_len() + proto_hdr::max_proto_hdr_len();
+
+        let mut message = vec![0; max_len + payload.len()];
+        let mut wb = WriteBuf::new(&mut message);
+
+        let mut plain = packet.plain.clone();
+        plain.sess_id = 0;
+        plain.sess_type = SessionType::None;
+        plain.privacy = false;
+        plain.encode(&mut wb)?;
+        packet.proto.clone().encode(&mut wb)?;
+        wb.append(payload)?;
+
+        let message = wb.as_slice();
+
+        let peer = match packet.peer {
+            Address::Udp(addr) | Address::Tcp(addr) => addr,
+            // Not an IP transport; still captured, but without addresses
+            Address::Btp(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), MATTER_PORT),
+        };
+
+        let local = SocketAddr::new(
+            match peer.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            },
+            MATTER_PORT,
+        );
+
+        let (src, dst) = match direction {
+            PacketDirection::Rx => (peer, local),
+            PacketDirection::Tx => (local, peer),
+        };
+
+        let udp_len = UDP_HDR_LEN + message.len();
+
+        let mut datagram = Vec::with_capacity(IPV6_HDR_LEN + udp_len);
+
+        match (src.ip(), dst.ip()) {
+            (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+                let mut hdr = [0; IPV4_HDR_LEN];
+                hdr[0] = 0x45;
+                hdr[2..4].copy_from_slice(&((IPV4_HDR_LEN + udp_len) as u16).to_be_bytes());
+                hdr[8] = 64;
+                hdr[9] = IP_PROTO_UDP;
+                hdr[12..16].copy_from_slice(&src_ip.octets());
+                hdr[16..20].copy_from_slice(&dst_ip.octets());
+
+                let checksum = ipv4_checksum(&hdr);
+                hdr[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+                datagram.extend_from_slice(&hdr);
+            }
+            (src_ip, dst_ip) => {
+                let mut hdr = [0; IPV6_HDR_LEN];
+                hdr[0] = 0x60;
+                hdr[4..6].copy_from_slice(&(udp_len as u16).to_be_bytes());
+                hdr[6] = IP_PROTO_UDP;
+                hdr[7] = 64;
+                hdr[8..24].copy_from_slice(&to_ipv6(src_ip).octets());
+                hdr[24..40].copy_from_slice(&to_ipv6(dst_ip).octets());
+
+                datagram.extend_from_slice(&hdr);
+            }
+        }
+
+        datagram.extend_from_slice(&src.port().to_be_bytes());
+        datagram.extend_from_slice(&dst.port().to_be_bytes());
+        datagram.extend_from_slice(&(udp_len as u16).to_be_bytes());
+        // No checksum
+        datagram.extend_from_slice(&[0, 0]);
+        datagram.extend_from_slice(message);
+
+        Ok(datagram)
+    }
+}
+
+impl<W> PacketObserver for PcapWriter<W>
+where
+    W: Write,
+{
+    fn observe(&self, direction: PacketDirection, packet: &Packet<'_>) {
+        if let Err(e) = self.write(direction, packet) {
+            warn!("Cannot capture packet: {:?}", e);
+        }
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+fn ipv4_checksum(hdr: &[u8; IPV4_HDR_LEN]) -> u16 {
+    let mut sum = hdr
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum::<u32>();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interaction_model::core::{OpCode, PROTO_ID_INTERACTION_MODEL};
+    use crate::transport::core::{PacketDirection, PacketObserver};
+    use crate::transport::network::{Address, Ipv4Addr, SocketAddr};
+    use crate::transport::packet::Packet;
+    use crate::transport::plain_hdr::SessionType;
+
+    use super::PcapWriter;
+
+    #[test]
+    fn test_capture() {
+        let capture = PcapWriter::new(std::vec::Vec::new()).unwrap();
+
+        let mut buf = [0; 256];
+        let mut tx = Packet::new_tx(&mut buf);
+        tx.plain.sess_type = SessionType::Encrypted;
+        tx.plain.sess_id = 0x1234;
+        tx.set_proto_id(PROTO_ID_INTERACTION_MODEL);
+        tx.set_proto_opcode(OpCode::StatusResponse as _);
+        tx.get_writebuf().unwrap().append(&[0x15, 0x18]).unwrap();
+        tx.peer = Address::Udp(SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 1234));
+
+        capture.observe(PacketDirection::Tx, &tx);
+
+        let data = capture.into_inner();
+
+        // File header
+        assert_eq!(&data[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&data[20..24], &[101, 0, 0, 0]);
+
+        // Record header
+        let len = u32::from_le_bytes([data[32], data[33], data[34], data[35]]) as usize;
+        assert_eq!(data.len(), 24 + 16 + len);
+
+        let datagram = &data[40..];
+
+        // IPv4 to the peer
+        assert_eq!(datagram[0], 0x45);
+        assert_eq!(&datagram[16..20], &[192, 168, 1, 2]);
+
+        // UDP from the Matter port
+        assert_eq!(&datagram[20..22], &5540_u16.to_be_bytes());
+        assert_eq!(&datagram[22..24], &1234_u16.to_be_bytes());
+
+        // The message is rewritten as an unsecured one, with session ID 0
+        let message = &datagram[28..];
+        assert_eq!(&message[1..4], &[0, 0, 0]);
+        assert_eq!(&message[message.len() - 2..], &[0x15, 0x18]);
+    }
+}
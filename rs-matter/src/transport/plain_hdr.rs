@@ -15,6 +15,7 @@
  *    limitations under the License.
  */
 
+use crate::crypto;
 use crate::error::*;
 use crate::utils::parsebuf::ParseBuf;
 use crate::utils::writebuf::WriteBuf;
@@ -26,6 +27,8 @@ pub enum SessionType {
     #[default]
     None,
     Encrypted,
+    /// Encrypted with an operational group key
+    Group,
 }
 
 bitflags! {
@@ -38,29 +41,68 @@ bitflags! {
     }
 }
 
+// The session type is encoded in the lowest 2 bits of the security flags
+const SEC_FLAGS_SESS_TYPE_MASK: u8 = 0x03;
+const SEC_FLAGS_SESS_TYPE_UNICAST: u8 = 0x00;
+const SEC_FLAGS_SESS_TYPE_GROUP: u8 = 0x01;
+// The message header is obfuscated with the privacy key of the session
+const SEC_FLAGS_PRIVACY: u8 = 0x80;
+
+// The flags, the session ID and the security flags are never obfuscated
+const PRIVACY_HDR_START: usize = 4;
+
+const PRIVACY_KEY_INFO: &[u8] = b"PrivacyKey";
+
 // This is the unencrypted message
 #[derive(Debug, Default, Clone)]
 pub struct PlainHdr {
     pub flags: MsgFlags,
     pub sess_type: SessionType,
     pub sess_id: u16,
+    /// Whether the privacy-protected fields of the header - the message counter and
+    /// the node IDs - are obfuscated on the wire
+    pub privacy: bool,
     pub ctr: u32,
-    peer_nodeid: Option<u64>,
+    src_nodeid: Option<u64>,
+    dest_nodeid: Option<u64>,
+    dest_group_id: Option<u16>,
 }
 
 impl PlainHdr {
     pub fn set_dest_u64(&mut self, id: u64) {
+        self.flags.remove(MsgFlags::DSIZ_GROUPCAST_NODEID);
         self.flags |= MsgFlags::DSIZ_UNICAST_NODEID;
-        self.peer_nodeid = Some(id);
+        self.dest_nodeid = Some(id);
+        self.dest_group_id = None;
+    }
+
+    pub fn set_dest_group(&mut self, group_id: u16) {
+        self.flags.remove(MsgFlags::DSIZ_UNICAST_NODEID);
+        self.flags |= MsgFlags::DSIZ_GROUPCAST_NODEID;
+        self.dest_nodeid = None;
+        self.dest_group_id = Some(group_id);
+    }
+
+    pub fn set_src_u64(&mut self, id: u64) {
+        self.flags |= MsgFlags::SRC_ADDR_PRESENT;
+        self.src_nodeid = Some(id);
     }
 
     pub fn get_src_u64(&self) -> Option<u64> {
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
-            self.peer_nodeid
+            self.src_nodeid
         } else {
             None
         }
     }
+
+    pub fn get_dest_u64(&self) -> Option<u64> {
+        self.dest_nodeid
+    }
+
+    pub fn get_dest_group(&self) -> Option<u16> {
+        self.dest_group_id
+    }
 }
 
 impl PlainHdr {
@@ -68,16 +110,41 @@ impl PlainHdr {
     pub fn decode(&mut self, msg: &mut ParseBuf) -> Result<(), Error> {
         self.flags = MsgFlags::from_bits(msg.le_u8()?).ok_or(ErrorCode::Invalid)?;
         self.sess_id = msg.le_u16()?;
-        let _sec_flags = msg.le_u8()?;
-        self.sess_type = if self.sess_id != 0 {
-            SessionType::Encrypted
-        } else {
-            SessionType::None
+        let sec_flags = msg.le_u8()?;
+        self.sess_type = match sec_flags & SEC_FLAGS_SESS_TYPE_MASK {
+            SEC_FLAGS_SESS_TYPE_UNICAST if self.sess_id == 0 => SessionType::None,
+            SEC_FLAGS_SESS_TYPE_UNICAST => SessionType::Encrypted,
+            SEC_FLAGS_SESS_TYPE_GROUP => SessionType::Group,
+            _ => Err(ErrorCode::Invalid)?,
         };
+        self.privacy = sec_flags & SEC_FLAGS_PRIVACY != 0;
         self.ctr = msg.le_u32()?;
 
-        if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
-            self.peer_nodeid = Some(msg.le_u64()?);
+        self.src_nodeid = if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
+            Some(msg.le_u64()?)
+        } else {
+            None
+        };
+
+        self.dest_nodeid = None;
+        self.dest_group_id = None;
+
+        if self.flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+            if self.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+                // Reserved destination size
+                Err(ErrorCode::Invalid)?;
+            }
+
+            self.dest_nodeid = Some(msg.le_u64()?);
+        } else if self.flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+            self.dest_group_id = Some(msg.le_u16()?);
+        }
+
+        if self.sess_type == SessionType::Group
+            && (self.src_nodeid.is_none() || self.dest_group_id.is_none())
+        {
+            // Group messages must identify both their sender and their destination group
+            Err(ErrorCode::Invalid)?;
         }
 
         info!(
@@ -90,17 +157,100 @@ impl PlainHdr {
     pub fn encode(&mut self, resp_buf: &mut WriteBuf) -> Result<(), Error> {
         resp_buf.le_u8(self.flags.bits())?;
         resp_buf.le_u16(self.sess_id)?;
-        resp_buf.le_u8(0)?;
+        let sess_type = if self.sess_type == SessionType::Group {
+            SEC_FLAGS_SESS_TYPE_GROUP
+        } else {
+            SEC_FLAGS_SESS_TYPE_UNICAST
+        };
+        resp_buf.le_u8(if self.privacy {
+            sess_type | SEC_FLAGS_PRIVACY
+        } else {
+            sess_type
+        })?;
         resp_buf.le_u32(self.ctr)?;
-        if let Some(d) = self.peer_nodeid {
+        if let Some(s) = self.get_src_u64() {
+            resp_buf.le_u64(s)?;
+        }
+        if let Some(d) = self.dest_nodeid {
             resp_buf.le_u64(d)?;
+        } else if let Some(g) = self.dest_group_id {
+            resp_buf.le_u16(g)?;
         }
         Ok(())
     }
 
     pub fn is_encrypted(&self) -> bool {
-        self.sess_type == SessionType::Encrypted
+        self.sess_type != SessionType::None
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.sess_type == SessionType::Group
+    }
+}
+
+/// Returns the session ID of the encoded message `msg` if its header is privacy-protected,
+/// i.e. needs to be de-obfuscated with [`privacy_crypt_in_place`] before it can be decoded
+pub fn privacy_sess_id(msg: &[u8]) -> Option<u16> {
+    match msg {
+        [_, id0, id1, sec_flags, ..] if sec_flags & SEC_FLAGS_PRIVACY != 0 => {
+            Some(u16::from_le_bytes([*id0, *id1]))
+        }
+        _ => None,
+    }
+}
+
+/// Derives the privacy key of a session from its encryption key (for sending)
+/// or its decryption key (for receiving)
+pub fn privacy_key(key: &[u8]) -> Result<[u8; crypto::SYMM_KEY_LEN_BYTES], Error> {
+    let mut privacy_key = [0; crypto::SYMM_KEY_LEN_BYTES];
+    crypto::hkdf_sha256(&[], key, PRIVACY_KEY_INFO, &mut privacy_key)
+        .map_err(|_| ErrorCode::NoSpace)?;
+
+    Ok(privacy_key)
+}
+
+/// Obfuscates - or de-obfuscates, as the operation is symmetric - the privacy-protected fields
+/// of the header of `msg`, a complete encrypted message, with `privacy_key`.
+///
+/// As per the spec, this is AES-CTR with the nonce made of the session ID and of the last 11
+/// bytes of the MIC of the message. An outgoing message is obfuscated after being encrypted,
+/// and an incoming one is de-obfuscated before being decrypted.
+pub fn privacy_crypt_in_place(privacy_key: &[u8], msg: &mut [u8]) -> Result<(), Error> {
+    let flags = MsgFlags::from_bits(*msg.first().ok_or(ErrorCode::TruncatedPacket)?)
+        .ok_or(ErrorCode::Invalid)?;
+    let sess_id = msg.get(1..3).ok_or(ErrorCode::TruncatedPacket)?;
+
+    // Message counter
+    let mut end = PRIVACY_HDR_START + 4;
+    if flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
+        end += 8;
+    }
+    if flags.contains(MsgFlags::DSIZ_UNICAST_NODEID) {
+        end += 8;
+    } else if flags.contains(MsgFlags::DSIZ_GROUPCAST_NODEID) {
+        end += 2;
     }
+
+    if msg.len() < end + crypto::AEAD_MIC_LEN_BYTES {
+        Err(ErrorCode::TruncatedPacket)?;
+    }
+
+    let mic = &msg[msg.len() - crypto::AEAD_MIC_LEN_BYTES..];
+
+    let mut nonce = [0; crypto::AEAD_NONCE_LEN_BYTES];
+    nonce[..2].copy_from_slice(&[sess_id[1], sess_id[0]]);
+    nonce[2..].copy_from_slice(&mic[5..]);
+
+    // AES-CTR, as done by AES-CCM - with the same counter blocks - before computing its tag
+    let mut buf = [0; max_plain_hdr_len() + crypto::AEAD_MIC_LEN_BYTES];
+    let hdr = &mut msg[PRIVACY_HDR_START..end];
+    buf[..hdr.len()].copy_from_slice(hdr);
+
+    crypto::encrypt_in_place(privacy_key, &nonce, &[], &mut buf, hdr.len())?;
+
+    hdr.copy_from_slice(&buf[..hdr.len()]);
+
+    Ok(())
 }
 
 pub const fn max_plain_hdr_len() -> usize {
@@ -119,3 +269,130 @@ pub const fn max_plain_hdr_len() -> usize {
     // [optional] destination node ID
         8
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{parsebuf::ParseBuf, writebuf::WriteBuf};
+
+    use super::{MsgFlags, PlainHdr, SessionType};
+
+    #[test]
+    fn test_group_hdr() {
+        let mut hdr = PlainHdr {
+            sess_type: SessionType::Group,
+            sess_id: 0x1234,
+            ctr: 0x0a0b0c0d,
+            ..Default::default()
+        };
+        hdr.set_src_u64(0x1122334455667788);
+        hdr.set_dest_group(0x0101);
+
+        let mut buf = [0; 32];
+        let mut wb = WriteBuf::new(&mut buf);
+        hdr.encode(&mut wb).unwrap();
+
+        assert_eq!(
+            wb.as_slice(),
+            &[
+                0x06, 0x34, 0x12, 0x01, 0x0d, 0x0c, 0x0b, 0x0a, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33,
+                0x22, 0x11, 0x01, 0x01
+            ]
+        );
+
+        let len = wb.as_slice().len();
+        let mut decoded = PlainHdr::default();
+        decoded.decode(&mut ParseBuf::new(&mut buf[..len])).unwrap();
+
+        assert!(decoded.is_group());
+        assert!(decoded.is_encrypted());
+        assert_eq!(decoded.sess_id, 0x1234);
+        assert_eq!(decoded.ctr, 0x0a0b0c0d);
+        assert_eq!(decoded.get_src_u64(), Some(0x1122334455667788));
+        assert_eq!(decoded.get_dest_group(), Some(0x0101));
+        assert_eq!(decoded.get_dest_u64(), None);
+    }
+
+    #[test]
+    fn test_unicast_hdr_with_dest() {
+        let mut hdr = PlainHdr {
+            sess_id: 0,
+            ctr: 1,
+            ..Default::default()
+        };
+        hdr.set_dest_u64(5);
+        assert_eq!(hdr.flags, MsgFlags::DSIZ_UNICAST_NODEID);
+
+        let mut buf = [0; 32];
+        let mut wb = WriteBuf::new(&mut buf);
+        hdr.encode(&mut wb).unwrap();
+        let len = wb.as_slice().len();
+        assert_eq!(len, 16);
+
+        let mut decoded = PlainHdr::default();
+        decoded.decode(&mut ParseBuf::new(&mut buf[..len])).unwrap();
+
+        assert!(!decoded.is_encrypted());
+        assert_eq!(decoded.get_src_u64(), None);
+        assert_eq!(decoded.get_dest_u64(), Some(5));
+    }
+
+    #[test]
+    fn test_privacy() {
+        let mut hdr = PlainHdr {
+            sess_type: SessionType::Encrypted,
+            sess_id: 0x1234,
+            privacy: true,
+            ctr: 0x0a0b0c0d,
+            ..Default::default()
+        };
+        hdr.set_dest_u64(5);
+
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        hdr.encode(&mut wb).unwrap();
+        let hdr_len = wb.as_slice().len();
+        // The encrypted payload and the MIC
+        wb.append(&[0x55; 8]).unwrap();
+        wb.append(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08])
+            .unwrap();
+        wb.append(&[0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10])
+            .unwrap();
+        let len = wb.as_slice().len();
+
+        let plain = buf;
+        let plain = &plain[..len];
+        let msg = &mut buf[..len];
+
+        assert_eq!(super::privacy_sess_id(msg), Some(0x1234));
+
+        let key = super::privacy_key(&[0xaa; 16]).unwrap();
+
+        super::privacy_crypt_in_place(&key, msg).unwrap();
+
+        // Only the message counter and the destination node ID are obfuscated
+        assert_eq!(&msg[..4], &plain[..4]);
+        assert_ne!(&msg[4..hdr_len], &plain[4..hdr_len]);
+        assert_eq!(&msg[hdr_len..], &plain[hdr_len..]);
+
+        super::privacy_crypt_in_place(&key, msg).unwrap();
+        assert_eq!(msg, plain);
+
+        let mut decoded = PlainHdr::default();
+        decoded.decode(&mut ParseBuf::new(msg)).unwrap();
+        assert!(decoded.privacy);
+        assert_eq!(decoded.ctr, 0x0a0b0c0d);
+        assert_eq!(decoded.get_dest_u64(), Some(5));
+
+        // Not privacy-protected
+        assert_eq!(super::privacy_sess_id(&[0x00, 0x34, 0x12, 0x00]), None);
+    }
+
+    #[test]
+    fn test_group_hdr_without_src_is_rejected() {
+        // Group session type, destination group, but no source node ID
+        let mut data = [0x02, 0x34, 0x12, 0x01, 0, 0, 0, 0, 0x01, 0x01];
+        assert!(PlainHdr::default()
+            .decode(&mut ParseBuf::new(&mut data))
+            .is_err());
+    }
+}
@@ -140,27 +140,25 @@ impl ProtoHdr {
 
 impl fmt::Display for ProtoHdr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut flag_str = heapless::String::<16>::new();
-        if self.is_vendor() {
-            flag_str.push_str("V|").unwrap();
-        }
-        if self.is_security_ext() {
-            flag_str.push_str("SX|").unwrap();
-        }
-        if self.is_reliable() {
-            flag_str.push_str("R|").unwrap();
-        }
-        if self.is_ack() {
-            flag_str.push_str("A|").unwrap();
-        }
-        if self.is_initiator() {
-            flag_str.push_str("I|").unwrap();
-        }
         write!(
             f,
-            "ExId: {}, Proto: {}, Opcode: {}, Flags: {}",
-            self.exch_id, self.proto_id, self.proto_opcode, flag_str
-        )
+            "ExId: {}, Proto: {}, Opcode: {}, Flags: ",
+            self.exch_id, self.proto_id, self.proto_opcode
+        )?;
+
+        let flags = [
+            (self.is_vendor(), "V|"),
+            (self.is_security_ext(), "SX|"),
+            (self.is_reliable(), "R|"),
+            (self.is_ack(), "A|"),
+            (self.is_initiator(), "I|"),
+        ];
+
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            f.write_str(flag)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -209,16 +207,14 @@ fn decrypt_in_place(
     key: &[u8],
 ) -> Result<(), Error> {
     // AAD:
-    //    the unencrypted header of this packet
-    let mut aad = [0_u8; crypto::AEAD_AAD_LEN_BYTES];
+    //    the unencrypted header of this packet, which is variable in size
+    //    (e.g. group messages always carry the source node ID and the destination group ID)
+    let mut aad_buf = [0_u8; plain_hdr::max_plain_hdr_len()];
     let parsed_slice = parsebuf.parsed_as_slice();
-    if parsed_slice.len() == aad.len() {
-        // The plain_header is variable sized in length, I wonder if the AAD is fixed at 8, or the variable size.
-        // If so, we need to handle it cleanly here.
-        aad.copy_from_slice(parsed_slice);
-    } else {
-        Err(ErrorCode::InvalidAAD)?;
-    }
+    let aad = aad_buf
+        .get_mut(..parsed_slice.len())
+        .ok_or(ErrorCode::InvalidAAD)?;
+    aad.copy_from_slice(parsed_slice);
 
     // IV:
     //   the specific way for creating IV is in get_iv
@@ -231,7 +227,7 @@ fn decrypt_in_place(
     //println!("IV: {:x?}", iv);
     //println!("Key: {:x?}", key);
 
-    crypto::decrypt_in_place(key, &iv, &aad, cipher_text)?;
+    crypto::decrypt_in_place(key, &iv, aad, cipher_text)?;
     // println!("Plain Text: {:x?}", cipher_text);
     parsebuf.tail(crypto::AEAD_MIC_LEN_BYTES)?;
     Ok(())
@@ -16,23 +16,102 @@
  */
 
 use crate::data_model::sdm::noc::NocData;
+use crate::group_keys::{GroupKey, GroupKeyMgr};
 use crate::utils::epoch::Epoch;
 use crate::utils::rand::Rand;
 use core::fmt;
+use core::ops::{Deref, DerefMut};
 use core::time::Duration;
 
-use crate::{error::*, transport::plain_hdr};
-use log::info;
+use crate::{
+    error::*,
+    tlv::{FromTLV, ToTLV},
+    transport::plain_hdr,
+};
+use log::{error, info, warn};
 
 use super::dedup::RxCtrState;
 use super::exchange::SessionId;
-use super::{network::Address, packet::Packet};
+use super::mrp::{
+    MrpParams, DEFAULT_SESSION_ACTIVE_INTERVAL, DEFAULT_SESSION_ACTIVE_THRESHOLD,
+    DEFAULT_SESSION_IDLE_INTERVAL,
+};
+use super::msg_ctr::MsgCounterMgr;
+use super::{
+    network::{Address, LocalAddr},
+    packet::{Packet, MAX_LARGE_MSG_SIZE, MAX_TX_MTU_SIZE},
+};
 
 pub const MAX_CAT_IDS_PER_NOC: usize = 3;
 pub type NocCatIds = [u32; MAX_CAT_IDS_PER_NOC];
 
 const MATTER_AES128_KEY_SIZE: usize = 16;
 
+/// The revision of the Data Model implemented by this node
+pub const DATA_MODEL_REVISION: u16 = 1;
+
+/// How many paths this node accepts in a single Invoke Request
+pub const MAX_PATHS_PER_INVOKE: u16 = crate::config::MAX_PATHS_PER_INVOKE as _;
+
+/// The session parameters a node advertises while establishing a session, in the
+/// PBKDFParamRequest/PBKDFParamResponse and Sigma1/Sigma2/Sigma2Resume messages.
+/// The parameters which are not advertised take their spec defaults.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, FromTLV, ToTLV)]
+#[tlvargs(start = 1)]
+pub struct SessionParams {
+    /// SESSION_IDLE_INTERVAL, in ms
+    pub idle_interval: Option<u32>,
+    /// SESSION_ACTIVE_INTERVAL, in ms
+    pub active_interval: Option<u32>,
+    /// SESSION_ACTIVE_THRESHOLD, in ms
+    pub active_threshold: Option<u16>,
+    pub data_model_revision: Option<u16>,
+    pub interaction_model_revision: Option<u16>,
+    pub specification_version: Option<u32>,
+    pub max_paths_per_invoke: Option<u16>,
+}
+
+impl SessionParams {
+    pub const fn new() -> Self {
+        Self {
+            idle_interval: None,
+            active_interval: None,
+            active_threshold: None,
+            data_model_revision: None,
+            interaction_model_revision: None,
+            specification_version: None,
+            max_paths_per_invoke: None,
+        }
+    }
+
+    /// The session parameters advertised by this node
+    pub const fn local() -> Self {
+        Self {
+            idle_interval: Some(DEFAULT_SESSION_IDLE_INTERVAL),
+            active_interval: Some(DEFAULT_SESSION_ACTIVE_INTERVAL),
+            active_threshold: Some(DEFAULT_SESSION_ACTIVE_THRESHOLD),
+            data_model_revision: Some(DATA_MODEL_REVISION),
+            interaction_model_revision: None,
+            specification_version: None,
+            max_paths_per_invoke: Some(MAX_PATHS_PER_INVOKE),
+        }
+    }
+
+    /// The MRP parameters, i.e. how to retransmit to the node which advertised these parameters
+    pub fn mrp_params(&self) -> MrpParams {
+        MrpParams {
+            idle_interval: self.idle_interval,
+            active_interval: self.active_interval,
+            active_threshold: self.active_threshold,
+        }
+    }
+
+    /// How many paths the node accepts in a single Invoke Request; 1 if not advertised
+    pub fn max_paths_per_invoke(&self) -> u16 {
+        self.max_paths_per_invoke.unwrap_or(1)
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct CaseDetails {
     pub fab_idx: u8,
@@ -48,10 +127,26 @@ impl CaseDetails {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GroupDetails {
+    pub fab_idx: u8,
+    pub group_id: u16,
+}
+
+impl GroupDetails {
+    pub fn new(fab_idx: u8, group_id: u16) -> Self {
+        Self { fab_idx, group_id }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Default)]
 pub enum SessionMode {
     // The Case session will capture the local fabric index
     Case(CaseDetails),
+    // A group session is either the session of the group messages received from a group member
+    // (the peer node ID is then the member's node ID) or the session used for sending group
+    // messages to the group (without a peer node ID)
+    Group(GroupDetails),
     Pase,
     #[default]
     PlainText,
@@ -59,6 +154,8 @@ pub enum SessionMode {
 
 pub struct Session {
     peer_addr: Address,
+    // The local address the peer sent its last message to, so that replies are sent from it
+    local_addr: Option<LocalAddr>,
     local_nodeid: u64,
     peer_nodeid: Option<u64>,
     // I find the session initiator/responder role getting confused with exchange initiator/responder
@@ -73,6 +170,46 @@ pub struct Session {
     mode: SessionMode,
     data: Option<NocData>,
     last_use: Duration,
+    // When was the last message from the peer received
+    last_rx: Duration,
+    session_params: SessionParams,
+    privacy: bool,
+    large_payload: bool,
+    // Expired sessions accept no new exchanges, and are removed once their exchanges are gone
+    expired: bool,
+}
+
+/// The kind of a session, as reported by [`SessionInfo`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SessionKind {
+    Unsecured,
+    Pase,
+    Case,
+    Group,
+}
+
+/// A snapshot of a session, for diagnostics - see [`crate::Matter::sessions`].
+/// It carries no key material.
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub kind: SessionKind,
+    pub local_sess_id: u16,
+    pub peer_sess_id: u16,
+    pub peer_addr: Address,
+    pub peer_nodeid: Option<u64>,
+    pub fab_idx: Option<u8>,
+    pub group_id: Option<u16>,
+    /// The counter of the next message sent over the session
+    pub tx_msg_ctr: u32,
+    /// The largest counter of the messages received over the session
+    pub rx_msg_ctr: u32,
+    /// When was the session last used, to send or receive
+    pub last_use: Duration,
+    /// When was the last message from the peer received
+    pub last_rx: Duration,
+    /// The session parameters advertised by the peer
+    pub session_params: SessionParams,
+    pub expired: bool,
 }
 
 #[derive(Debug)]
@@ -80,6 +217,8 @@ pub struct CloneData {
     pub dec_key: [u8; MATTER_AES128_KEY_SIZE],
     pub enc_key: [u8; MATTER_AES128_KEY_SIZE],
     pub att_challenge: [u8; MATTER_AES128_KEY_SIZE],
+    /// The session parameters advertised by the peer during the session establishment
+    pub session_params: SessionParams,
     local_sess_id: u16,
     peer_sess_id: u16,
     local_nodeid: u64,
@@ -101,6 +240,7 @@ impl CloneData {
             dec_key: [0; MATTER_AES128_KEY_SIZE],
             enc_key: [0; MATTER_AES128_KEY_SIZE],
             att_challenge: [0; MATTER_AES128_KEY_SIZE],
+            session_params: SessionParams::new(),
             local_nodeid,
             peer_nodeid,
             peer_addr,
@@ -113,10 +253,17 @@ impl CloneData {
 
 const MATTER_MSG_CTR_RANGE: u32 = 0x0fffffff;
 
+/// Once the message counter of a secure unicast session reaches this value, the session is
+/// expired, so that it is closed - and re-established by the peer - before the counter wraps.
+/// The remaining counters are left for the exchanges already running over the session, and for
+/// the CloseSession status report.
+pub const MSG_CTR_EXHAUSTION_THRESHOLD: u32 = u32::MAX - 0x10000;
+
 impl Session {
     pub fn new(peer_addr: Address, peer_nodeid: Option<u64>, epoch: Epoch, rand: Rand) -> Self {
         Self {
             peer_addr,
+            local_addr: None,
             local_nodeid: 0,
             peer_nodeid,
             dec_key: [0; MATTER_AES128_KEY_SIZE],
@@ -129,6 +276,46 @@ impl Session {
             mode: SessionMode::PlainText,
             data: None,
             last_use: epoch(),
+            last_rx: epoch(),
+            session_params: SessionParams::new(),
+            privacy: false,
+            large_payload: Self::supports_large_payload(&peer_addr),
+            expired: false,
+        }
+    }
+
+    /// A new group session, using the operational key of the group for both directions
+    pub fn group(
+        peer_addr: Address,
+        local_nodeid: u64,
+        peer_nodeid: Option<u64>,
+        key: &GroupKey,
+        epoch: Epoch,
+        rand: Rand,
+    ) -> Session {
+        let mut op_key = [0; MATTER_AES128_KEY_SIZE];
+        op_key.copy_from_slice(key.op_key());
+
+        Session {
+            peer_addr,
+            local_addr: None,
+            local_nodeid,
+            peer_nodeid,
+            dec_key: op_key,
+            enc_key: op_key,
+            att_challenge: [0; MATTER_AES128_KEY_SIZE],
+            local_sess_id: key.session_id(),
+            peer_sess_id: key.session_id(),
+            msg_ctr: Self::rand_msg_ctr(rand),
+            rx_ctr_state: RxCtrState::new(0),
+            mode: SessionMode::Group(GroupDetails::new(key.fab_idx, key.group_id)),
+            data: None,
+            last_use: epoch(),
+            last_rx: epoch(),
+            session_params: SessionParams::new(),
+            privacy: false,
+            large_payload: false,
+            expired: false,
         }
     }
 
@@ -136,6 +323,7 @@ impl Session {
     pub fn clone(clone_from: &CloneData, epoch: Epoch, rand: Rand) -> Session {
         Session {
             peer_addr: clone_from.peer_addr,
+            local_addr: None,
             local_nodeid: clone_from.local_nodeid,
             peer_nodeid: Some(clone_from.peer_nodeid),
             dec_key: clone_from.dec_key,
@@ -148,6 +336,11 @@ impl Session {
             mode: clone_from.mode.clone(),
             data: None,
             last_use: epoch(),
+            last_rx: epoch(),
+            session_params: clone_from.session_params,
+            privacy: false,
+            large_payload: Self::supports_large_payload(&clone_from.peer_addr),
+            expired: false,
         }
     }
 
@@ -160,6 +353,50 @@ impl Session {
         }
     }
 
+    /// Whether this is the session with the given local ID, peer and encryption -
+    /// a peer node ID unknown on either side matches any
+    pub fn matches(
+        &self,
+        sess_id: u16,
+        peer_addr: Address,
+        peer_nodeid: Option<u64>,
+        is_encrypted: bool,
+    ) -> bool {
+        let nodeid_matches =
+            self.peer_nodeid.is_none() || peer_nodeid.is_none() || self.peer_nodeid == peer_nodeid;
+
+        self.local_sess_id == sess_id
+            && self.peer_addr == peer_addr
+            && self.is_encrypted() == is_encrypted
+            && nodeid_matches
+    }
+
+    /// A snapshot of the session, for diagnostics
+    pub fn info(&self) -> SessionInfo {
+        let kind = match self.mode {
+            SessionMode::Case(_) => SessionKind::Case,
+            SessionMode::Group(_) => SessionKind::Group,
+            SessionMode::Pase => SessionKind::Pase,
+            SessionMode::PlainText => SessionKind::Unsecured,
+        };
+
+        SessionInfo {
+            kind,
+            local_sess_id: self.local_sess_id,
+            peer_sess_id: self.peer_sess_id,
+            peer_addr: self.peer_addr,
+            peer_nodeid: self.peer_nodeid,
+            fab_idx: self.get_local_fabric_idx(),
+            group_id: self.get_group_id(),
+            tx_msg_ctr: self.msg_ctr,
+            rx_msg_ctr: self.rx_ctr_state.max_ctr(),
+            last_use: self.last_use,
+            last_rx: self.last_rx,
+            session_params: self.session_params,
+            expired: self.expired,
+        }
+    }
+
     pub fn set_noc_data(&mut self, data: NocData) {
         self.data = Some(data);
     }
@@ -195,11 +432,118 @@ impl Session {
 
     pub fn is_encrypted(&self) -> bool {
         match self.mode {
-            SessionMode::Case(_) | SessionMode::Pase => true,
+            SessionMode::Case(_) | SessionMode::Group(_) | SessionMode::Pase => true,
             SessionMode::PlainText => false,
         }
     }
 
+    pub fn is_group(&self) -> bool {
+        matches!(self.mode, SessionMode::Group(_))
+    }
+
+    /// Marks the session as expired, e.g. because its fabric was removed.
+    /// The exchanges already running over it can still complete.
+    pub fn expire(&mut self) {
+        self.expired = true;
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// When was the session last used to send or receive a message
+    pub fn last_use(&self) -> Duration {
+        self.last_use
+    }
+
+    /// Whether Large Payload messages (up to [`MAX_LARGE_MSG_SIZE`] bytes) can be exchanged
+    /// over this session
+    pub fn is_large_payload(&self) -> bool {
+        self.large_payload
+    }
+
+    /// Enables or disables Large Payload messages for this session, as negotiated with the peer.
+    /// Large Payloads can only be enabled for sessions over TCP, and only with the
+    /// `large-payload` feature
+    pub fn set_large_payload(&mut self, large_payload: bool) -> Result<(), Error> {
+        if large_payload && !Self::supports_large_payload(&self.peer_addr) {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        self.large_payload = large_payload;
+
+        Ok(())
+    }
+
+    /// Whether the headers of the messages sent over this session are privacy-protected.
+    /// Privacy is enabled automatically once the peer sends a privacy-protected message
+    pub fn is_privacy(&self) -> bool {
+        self.privacy
+    }
+
+    /// Enables or disables message privacy for the messages sent over this session.
+    /// Only unicast encrypted sessions support message privacy
+    pub fn set_privacy(&mut self, privacy: bool) -> Result<(), Error> {
+        if privacy && (!self.is_encrypted() || self.is_group()) {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        self.privacy = privacy;
+
+        Ok(())
+    }
+
+    /// The session parameters of the peer, as negotiated during the session establishment
+    pub fn session_params(&self) -> &SessionParams {
+        &self.session_params
+    }
+
+    /// Sets the session parameters of the peer, as advertised by it during the session
+    /// establishment
+    pub fn set_session_params(&mut self, session_params: SessionParams) {
+        self.session_params = session_params;
+    }
+
+    /// The MRP parameters of the peer
+    pub fn mrp_params(&self) -> MrpParams {
+        self.session_params.mrp_params()
+    }
+
+    /// When was the last message from the peer received
+    pub fn last_rx(&self) -> Duration {
+        self.last_rx
+    }
+
+    /// Whether the peer is active, i.e. it sent a message within its active threshold.
+    /// Idle peers are assumed to be sleepy, and are thus retried less often.
+    pub fn is_peer_active(&self, epoch: Epoch) -> bool {
+        self.mrp_params()
+            .is_active(epoch().saturating_sub(self.last_rx))
+    }
+
+    /// The base interval for retransmitting messages to the peer: its active interval if it
+    /// is active (see [`Session::is_peer_active`]), its idle interval otherwise
+    pub fn retrans_interval(&self, epoch: Epoch) -> Duration {
+        self.mrp_params()
+            .retrans_interval(epoch().saturating_sub(self.last_rx))
+    }
+
+    /// The maximum size of a message sent over this session
+    pub fn max_tx_size(&self) -> usize {
+        if self.large_payload {
+            MAX_LARGE_MSG_SIZE
+        } else {
+            MAX_TX_MTU_SIZE
+        }
+    }
+
+    pub fn get_group_id(&self) -> Option<u16> {
+        match &self.mode {
+            SessionMode::Group(g) => Some(g.group_id),
+            _ => None,
+        }
+    }
+
     pub fn get_peer_node_id(&self) -> Option<u64> {
         self.peer_nodeid
     }
@@ -214,6 +558,7 @@ impl Session {
     pub fn get_local_fabric_idx(&self) -> Option<u8> {
         match &self.mode {
             SessionMode::Case(a) => Some(a.fab_idx),
+            SessionMode::Group(g) => Some(g.fab_idx),
             _ => None,
         }
     }
@@ -222,22 +567,64 @@ impl Session {
         &self.mode
     }
 
-    pub fn get_msg_ctr(&mut self) -> u32 {
+    /// Sets the counter of the next message sent over this session, e.g. from the global
+    /// counter of the group messages sent by this node
+    pub fn set_msg_ctr(&mut self, ctr: u32) {
+        self.msg_ctr = ctr;
+    }
+
+    /// Takes the counter of the next message sent over this session.
+    ///
+    /// The counters of secure unicast sessions never wrap: the session is expired once the
+    /// counter reaches [`MSG_CTR_EXHAUSTION_THRESHOLD`], and sending fails with
+    /// [`ErrorCode::MsgCtrExhausted`] once no counters are left.
+    pub fn get_msg_ctr(&mut self) -> Result<u32, Error> {
         let ctr = self.msg_ctr;
-        self.msg_ctr += 1;
-        ctr
+
+        if self.is_secure_unicast() {
+            if ctr == u32::MAX {
+                error!("Session {}: message counter exhausted", self.local_sess_id);
+                self.expire();
+
+                Err(ErrorCode::MsgCtrExhausted)?;
+            }
+
+            if ctr >= MSG_CTR_EXHAUSTION_THRESHOLD && !self.expired {
+                warn!(
+                    "Session {}: message counter close to exhaustion, expiring the session",
+                    self.local_sess_id
+                );
+                self.expire();
+            }
+        }
+
+        self.msg_ctr = ctr.wrapping_add(1);
+
+        Ok(ctr)
+    }
+
+    /// Whether this is a secure unicast session whose message counter is close to exhaustion,
+    /// i.e. which already handed out [`MSG_CTR_EXHAUSTION_THRESHOLD`] via [`Self::get_msg_ctr`].
+    /// Such a session has to be closed, so that the peer establishes a new one.
+    pub fn is_msg_ctr_exhausted(&self) -> bool {
+        // `msg_ctr` is the next counter to be handed out
+        self.is_secure_unicast() && self.msg_ctr > MSG_CTR_EXHAUSTION_THRESHOLD
+    }
+
+    fn is_secure_unicast(&self) -> bool {
+        matches!(self.mode, SessionMode::Case(_) | SessionMode::Pase)
     }
 
     pub fn get_dec_key(&self) -> Option<&[u8]> {
         match self.mode {
-            SessionMode::Case(_) | SessionMode::Pase => Some(&self.dec_key),
+            SessionMode::Case(_) | SessionMode::Group(_) | SessionMode::Pase => Some(&self.dec_key),
             SessionMode::PlainText => None,
         }
     }
 
     pub fn get_enc_key(&self) -> Option<&[u8]> {
         match self.mode {
-            SessionMode::Case(_) | SessionMode::Pase => Some(&self.enc_key),
+            SessionMode::Case(_) | SessionMode::Group(_) | SessionMode::Pase => Some(&self.enc_key),
             SessionMode::PlainText => None,
         }
     }
@@ -248,14 +635,41 @@ impl Session {
 
     pub fn recv(&mut self, epoch: Epoch, rx: &mut Packet) -> Result<(), Error> {
         self.last_use = epoch();
+        self.last_rx = self.last_use;
+        if !self.is_group() {
+            if rx.plain.privacy {
+                // Respond to the peer with privacy as well
+                self.privacy = true;
+            }
+
+            if rx.local.is_some() {
+                self.local_addr = rx.local;
+            }
+        }
         rx.proto_decode(self.peer_nodeid.unwrap_or_default(), self.get_dec_key())
     }
 
     pub fn pre_send(&mut self, tx: &mut Packet) -> Result<(), Error> {
         tx.plain.sess_id = self.get_peer_sess_id();
-        tx.plain.ctr = self.get_msg_ctr();
-        if self.is_encrypted() {
+        tx.plain.ctr = self.get_msg_ctr()?;
+        if let SessionMode::Group(g) = &self.mode {
+            if self.peer_nodeid.is_some() {
+                // Nothing is ever sent back to the sender of a group message
+                Err(ErrorCode::InvalidState)?;
+            }
+
+            tx.plain.sess_type = plain_hdr::SessionType::Group;
+            tx.plain.set_src_u64(self.local_nodeid);
+            tx.plain.set_dest_group(g.group_id);
+            // Group messages are never acknowledged
+            tx.unset_reliable();
+        } else if self.is_encrypted() {
             tx.plain.sess_type = plain_hdr::SessionType::Encrypted;
+            tx.plain.privacy = self.privacy;
+        }
+        if self.peer_addr.is_reliable() {
+            // MRP is not used over transports which are reliable already, like TCP
+            tx.unset_reliable();
         }
         Ok(())
     }
@@ -269,7 +683,29 @@ impl Session {
             self.local_nodeid,
             self.mode == SessionMode::PlainText,
             self.get_enc_key(),
-        )
+        )?;
+
+        tx.local = self.local_addr;
+
+        if tx.plain.privacy {
+            let privacy_key = plain_hdr::privacy_key(&self.enc_key)?;
+            plain_hdr::privacy_crypt_in_place(&privacy_key, tx.as_mut_slice())?;
+        }
+
+        if tx.as_slice().len() > self.max_tx_size() {
+            error!(
+                "Message of {} bytes too large for session {}",
+                tx.as_slice().len(),
+                self.local_sess_id
+            );
+            Err(ErrorCode::NoSpace)?;
+        }
+
+        Ok(())
+    }
+
+    fn supports_large_payload(peer_addr: &Address) -> bool {
+        cfg!(feature = "large-payload") && peer_addr.is_tcp()
     }
 
     fn rand_msg_ctr(rand: Rand) -> u32 {
@@ -295,28 +731,98 @@ impl fmt::Display for Session {
     }
 }
 
-pub const MAX_SESSIONS: usize = 16;
+pub const MAX_SESSIONS: usize = crate::config::MAX_SESSIONS;
+
+/// A session which might be evicted, to make room for a new one
+pub struct EvictionCandidate<'a> {
+    /// The index of the session in the session manager
+    pub index: usize,
+    pub session: &'a Session,
+    /// The number of exchanges - including those carrying subscriptions - running over the session
+    pub exchanges: usize,
+    /// Whether the peer of the session is active (see [`Session::is_peer_active`])
+    pub peer_active: bool,
+}
+
+/// Picks the session to be evicted when a new session has to be established,
+/// but all session slots are taken
+pub trait EvictionPolicy {
+    /// Returns the index of the session to be evicted, or `None` if none should be,
+    /// in which case the new session is refused
+    ///
+    /// The candidates are iterated rather than passed as a slice, as their number depends
+    /// on the session capacity of the [`Matter`](crate::Matter) instance.
+    fn select(&self, candidates: &mut dyn Iterator<Item = EvictionCandidate<'_>>) -> Option<usize>;
+}
+
+impl<T> EvictionPolicy for &T
+where
+    T: EvictionPolicy,
+{
+    fn select(&self, candidates: &mut dyn Iterator<Item = EvictionCandidate<'_>>) -> Option<usize> {
+        (*self).select(candidates)
+    }
+}
+
+/// The default eviction policy: the least recently used session is evicted,
+/// preferring the sessions with idle peers
+pub struct LruEviction;
+
+impl EvictionPolicy for LruEviction {
+    fn select(&self, candidates: &mut dyn Iterator<Item = EvictionCandidate<'_>>) -> Option<usize> {
+        candidates
+            .min_by_key(|candidate| (candidate.peer_active, candidate.session.last_use()))
+            .map(|candidate| candidate.index)
+    }
+}
 
-pub struct SessionMgr {
+/// The session manager, with the session slots `S` - `[Option<Session>; N]` for a manager
+/// with room for `N` sessions, which dereferences to `SessionMgr<[Option<Session>]>`, i.e. the
+/// manager of any capacity all methods are implemented on.
+pub struct SessionMgr<S: ?Sized = [Option<Session>]> {
     next_sess_id: u16,
-    sessions: heapless::Vec<Option<Session>, MAX_SESSIONS>,
     pub(crate) epoch: Epoch,
     pub(crate) rand: Rand,
+    sessions: S,
 }
 
-impl SessionMgr {
+impl<const N: usize> SessionMgr<[Option<Session>; N]> {
+    // Only used as the array repeat operand, as `Session` is not `Copy`
+    const EMPTY: Option<Session> = None;
+
     #[inline(always)]
     pub const fn new(epoch: Epoch, rand: Rand) -> Self {
         Self {
-            sessions: heapless::Vec::new(),
             next_sess_id: 1,
             epoch,
             rand,
+            sessions: [Self::EMPTY; N],
         }
     }
+}
+
+impl<const N: usize> Deref for SessionMgr<[Option<Session>; N]> {
+    type Target = SessionMgr;
+
+    fn deref(&self) -> &Self::Target {
+        self
+    }
+}
+
+impl<const N: usize> DerefMut for SessionMgr<[Option<Session>; N]> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self
+    }
+}
+
+impl SessionMgr {
+    /// The maximum number of sessions
+    pub fn capacity(&self) -> usize {
+        self.sessions.len()
+    }
 
     pub fn reset(&mut self) {
-        self.sessions.clear();
+        self.sessions.fill_with(|| None);
         self.next_sess_id = 1;
     }
 
@@ -347,30 +853,47 @@ impl SessionMgr {
         next_sess_id
     }
 
-    pub fn get_session_for_eviction(&self) -> Option<usize> {
-        if self.sessions.len() == MAX_SESSIONS && self.get_empty_slot().is_none() {
-            Some(self.get_lru())
+    /// Returns the session to evict according to `policy`, if all session slots are taken.
+    /// `exchanges` returns the number of exchanges running over the given session.
+    pub fn get_session_for_eviction<F>(
+        &self,
+        policy: &dyn EvictionPolicy,
+        exchanges: F,
+    ) -> Option<usize>
+    where
+        F: Fn(&Session) -> usize,
+    {
+        if self.is_full() {
+            let mut candidates = self
+                .sessions
+                .iter()
+                .enumerate()
+                .filter_map(|(index, session)| {
+                    session.as_ref().map(|session| EvictionCandidate {
+                        index,
+                        session,
+                        exchanges: exchanges(session),
+                        peer_active: session.is_peer_active(self.epoch),
+                    })
+                });
+
+            // All slots are taken, so any session is a candidate
+            policy
+                .select(&mut candidates)
+                .filter(|index| *index < self.sessions.len())
         } else {
             None
         }
     }
 
-    fn get_empty_slot(&self) -> Option<usize> {
-        self.sessions.iter().position(|x| x.is_none())
+    /// Whether all session slots are taken, so that a new session can only be added
+    /// once an existing one is removed (or evicted)
+    pub fn is_full(&self) -> bool {
+        self.get_empty_slot().is_none()
     }
 
-    fn get_lru(&self) -> usize {
-        let mut lru_index = 0;
-        let mut lru_ts = (self.epoch)();
-        for (i, s) in self.sessions.iter().enumerate() {
-            if let Some(s) = s {
-                if s.last_use < lru_ts {
-                    lru_ts = s.last_use;
-                    lru_index = i;
-                }
-            }
-        }
-        lru_index
+    fn get_empty_slot(&self) -> Option<usize> {
+        self.sessions.iter().position(|x| x.is_none())
     }
 
     pub fn add(&mut self, peer_addr: Address, peer_nodeid: Option<u64>) -> Result<usize, Error> {
@@ -384,6 +907,90 @@ impl SessionMgr {
         self.sessions[idx] = None;
     }
 
+    /// Removes all sessions for which `f` returns `true`, returning how many were removed
+    pub fn remove_if<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&Session) -> bool,
+    {
+        let mut removed = 0;
+
+        for session in self.sessions.iter_mut() {
+            if session.as_ref().map(&mut f).unwrap_or(false) {
+                *session = None;
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// Takes the NOC data - pending operational keys and trusted roots - of all sessions
+    pub fn take_noc_data(&mut self) -> impl Iterator<Item = NocData> + '_ {
+        self.sessions
+            .iter_mut()
+            .flatten()
+            .filter_map(Session::take_noc_data)
+    }
+
+    /// Expires all sessions - unicast and group - on the given fabric,
+    /// returning how many were expired
+    pub fn expire_fabric(&mut self, fab_idx: u8) -> usize {
+        let mut expired = 0;
+
+        for session in self.sessions.iter_mut().flatten() {
+            if session.get_local_fabric_idx() == Some(fab_idx) && !session.is_expired() {
+                session.expire();
+                expired += 1;
+            }
+        }
+
+        expired
+    }
+
+    /// Removes the expired sessions, except those for which `in_use` returns `true`,
+    /// returning how many were removed.
+    ///
+    /// Sessions with an exhausted message counter are not removed, as their peers have to be
+    /// notified - see [`SessionMgr::first_exhausted`].
+    pub fn remove_expired<F>(&mut self, in_use: F) -> usize
+    where
+        F: Fn(&Session) -> bool,
+    {
+        let mut removed = 0;
+
+        for session in self.sessions.iter_mut() {
+            if session
+                .as_ref()
+                .map(|s| s.is_expired() && !s.is_msg_ctr_exhausted() && !in_use(s))
+                .unwrap_or(false)
+            {
+                *session = None;
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// The index of the first session with an exhausted message counter, except those for
+    /// which `in_use` returns `true`, if any
+    pub fn first_exhausted<F>(&self, in_use: F) -> Option<usize>
+    where
+        F: Fn(&Session) -> bool,
+    {
+        self.position(|session| session.is_msg_ctr_exhausted() && !in_use(session))
+    }
+
+    /// The index of the first session for which `f` returns `true`, if any
+    pub fn position<F>(&self, mut f: F) -> Option<usize>
+    where
+        F: FnMut(&Session) -> bool,
+    {
+        self.sessions
+            .iter()
+            .position(|session| session.as_ref().map(&mut f).unwrap_or(false))
+    }
+
     /// We could have returned a SessionHandle here. But the borrow checker doesn't support
     /// non-lexical lifetimes. This makes it harder for the caller of this function to take
     /// action in the error return path
@@ -391,13 +998,6 @@ impl SessionMgr {
         if let Some(index) = self.get_empty_slot() {
             self.sessions[index] = Some(session);
             Ok(index)
-        } else if self.sessions.len() < MAX_SESSIONS {
-            self.sessions
-                .push(Some(session))
-                .map_err(|_| ErrorCode::NoSpaceSessions)
-                .unwrap();
-
-            Ok(self.sessions.len() - 1)
         } else {
             Err(ErrorCode::NoSpaceSessions.into())
         }
@@ -415,21 +1015,7 @@ impl SessionMgr {
         peer_nodeid: Option<u64>,
         is_encrypted: bool,
     ) -> Option<usize> {
-        self.sessions.iter().position(|x| {
-            if let Some(x) = x {
-                let mut nodeid_matches = true;
-                if x.peer_nodeid.is_some() && peer_nodeid.is_some() && x.peer_nodeid != peer_nodeid
-                {
-                    nodeid_matches = false;
-                }
-                x.local_sess_id == sess_id
-                    && x.peer_addr == peer_addr
-                    && x.is_encrypted() == is_encrypted
-                    && nodeid_matches
-            } else {
-                false
-            }
-        })
+        self.position(|x| x.matches(sess_id, peer_addr, peer_nodeid, is_encrypted))
     }
 
     pub fn get_or_add(
@@ -460,7 +1046,136 @@ impl SessionMgr {
             rx.plain.is_encrypted(),
         )?;
 
-        let session = self.sessions[sess_index].as_mut().unwrap();
+        self.check_duplicate(sess_index, rx)
+    }
+
+    /// De-obfuscates the header of `rx` - not decoded yet - if it is privacy-protected,
+    /// using the privacy key of its session
+    pub fn privacy_decode(&self, rx: &mut Packet) -> Result<(), Error> {
+        let Some(sess_id) = plain_hdr::privacy_sess_id(rx.as_slice()) else {
+            return Ok(());
+        };
+
+        // TODO: Privacy for group messages, whose session is only known after trial decryption
+        let session = self
+            .sessions
+            .iter()
+            .flatten()
+            .find(|sess| {
+                sess.local_sess_id == sess_id
+                    && sess.peer_addr == rx.peer
+                    && sess.is_encrypted()
+                    && !sess.is_group()
+            })
+            .ok_or(ErrorCode::NoSession)?;
+
+        let privacy_key = plain_hdr::privacy_key(&session.dec_key)?;
+
+        plain_hdr::privacy_crypt_in_place(&privacy_key, rx.as_mut_slice())
+    }
+
+    /// Same as `post_recv`, but for group messages: the group session of the sender is looked up
+    /// (or created) using the operational key of the destination group
+    ///
+    /// The duplicate detection of a new group session starts from the highest counter
+    /// persisted for the sender in `ctrs`, if any, so that replays are not accepted after a reboot
+    pub fn post_recv_group(
+        &mut self,
+        rx: &Packet,
+        keys: &GroupKeyMgr,
+        ctrs: &MsgCounterMgr,
+    ) -> Result<usize, Error> {
+        let src_nodeid = rx.plain.get_src_u64().ok_or(ErrorCode::Invalid)?;
+        let group_id = rx.plain.get_dest_group().ok_or(ErrorCode::Invalid)?;
+
+        // TODO: Trial decryption, when several keys share the same group session ID
+        let key = keys
+            .candidates(rx.plain.sess_id, group_id)
+            .next()
+            .ok_or(ErrorCode::NoSession)?;
+
+        let mode = SessionMode::Group(GroupDetails::new(key.fab_idx, group_id));
+
+        let sess_index = if let Some(index) = self.sessions.iter().position(|x| {
+            x.as_ref()
+                .map(|x| {
+                    x.local_sess_id == key.session_id()
+                        && x.peer_nodeid == Some(src_nodeid)
+                        && x.mode == mode
+                })
+                .unwrap_or(false)
+        }) {
+            index
+        } else {
+            info!("Creating new group session");
+            let mut session =
+                Session::group(rx.peer, 0, Some(src_nodeid), &key, self.epoch, self.rand);
+            if let Some(max_ctr) = ctrs.peer_max_ctr(key.fab_idx, src_nodeid) {
+                session.rx_ctr_state = RxCtrState::new(max_ctr);
+            }
+
+            self.add_session(session)?
+        };
+
+        let session = self.mut_by_index(sess_index).ok_or(ErrorCode::NoSession)?;
+        // Group members are identified by their node ID rather than by their address
+        session.peer_addr = rx.peer;
+
+        self.check_duplicate(sess_index, rx)
+    }
+
+    /// Returns the session used for sending group messages to `peer_addr` - the multicast
+    /// address of the group, creating it if necessary
+    pub fn get_or_add_group_tx(
+        &mut self,
+        peer_addr: Address,
+        local_nodeid: u64,
+        key: &GroupKey,
+    ) -> Result<usize, Error> {
+        let mode = SessionMode::Group(GroupDetails::new(key.fab_idx, key.group_id));
+
+        if let Some(index) = self.sessions.iter().position(|x| {
+            x.as_ref()
+                .map(|x| {
+                    x.local_sess_id == key.session_id()
+                        && x.peer_nodeid.is_none()
+                        && x.peer_addr == peer_addr
+                        && x.mode == mode
+                })
+                .unwrap_or(false)
+        }) {
+            Ok(index)
+        } else {
+            let session = Session::group(peer_addr, local_nodeid, None, key, self.epoch, self.rand);
+            self.add_session(session)
+        }
+    }
+
+    /// All sessions
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter().flatten()
+    }
+
+    /// The most recently used CASE session with the peer `peer_node_id` on fabric `fab_idx`
+    pub fn case_session(&self, fab_idx: u8, peer_node_id: u64) -> Option<SessionId> {
+        self.iter()
+            .filter(|sess| {
+                matches!(sess.get_session_mode(), SessionMode::Case(_))
+                    && !sess.is_expired()
+                    && sess.get_local_fabric_idx() == Some(fab_idx)
+                    && sess.get_peer_node_id() == Some(peer_node_id)
+            })
+            .max_by_key(|sess| sess.last_use())
+            .map(Session::id)
+    }
+
+    /// All group sessions, both of received group messages and of sent ones
+    pub fn group_sessions(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter().flatten().filter(|s| s.is_group())
+    }
+
+    fn check_duplicate(&mut self, sess_index: usize, rx: &Packet) -> Result<usize, Error> {
+        let session = self.mut_by_index(sess_index).ok_or(ErrorCode::NoSession)?;
         let is_encrypted = session.is_encrypted();
         let duplicate = session.rx_ctr_state.recv(rx.plain.ctr, is_encrypted);
         if duplicate {
@@ -494,15 +1209,134 @@ impl fmt::Display for SessionMgr {
 mod tests {
 
     use crate::{
-        transport::network::Address,
+        transport::network::{Address, Ipv4Addr, SocketAddr},
         utils::{epoch::dummy_epoch, rand::dummy_rand},
     };
 
-    use super::SessionMgr;
+    use crate::group_keys::{GroupKey, GroupKeyMgr};
+    use crate::transport::msg_ctr::MsgCounterMgr;
+    use crate::transport::packet::{Packet, MAX_LARGE_MSG_SIZE, MAX_TX_MTU_SIZE};
+    use crate::transport::plain_hdr::SessionType;
+    use crate::{
+        tlv::{get_root_node, FromTLV, TLVWriter, TagType, ToTLV},
+        utils::writebuf::WriteBuf,
+    };
+
+    use core::time::Duration;
+
+    use super::{
+        CaseDetails, CloneData, EvictionCandidate, EvictionPolicy, LruEviction, Session,
+        SessionKind, SessionMgr, SessionMode, SessionParams, MAX_PATHS_PER_INVOKE, MAX_SESSIONS,
+        MSG_CTR_EXHAUSTION_THRESHOLD,
+    };
+
+    #[test]
+    fn test_session_info() {
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
+        sm.add(Address::default(), None).unwrap();
+
+        let mut clone_data = CloneData::new(
+            1,
+            2,
+            3,
+            4,
+            Address::default(),
+            SessionMode::Case(CaseDetails::new(5, &[0; 3])),
+        );
+        clone_data.session_params = SessionParams::local();
+        sm.clone_session(&clone_data).unwrap();
+
+        let infos: std::vec::Vec<_> = sm.iter().map(Session::info).collect();
+        assert_eq!(infos.len(), 2);
+
+        assert_eq!(infos[0].kind, SessionKind::Unsecured);
+        assert_eq!(infos[0].fab_idx, None);
+
+        assert_eq!(infos[1].kind, SessionKind::Case);
+        assert_eq!(infos[1].local_sess_id, 4);
+        assert_eq!(infos[1].peer_sess_id, 3);
+        assert_eq!(infos[1].peer_nodeid, Some(2));
+        assert_eq!(infos[1].fab_idx, Some(5));
+        assert_eq!(infos[1].session_params, SessionParams::local());
+        assert!(!infos[1].expired);
+    }
+
+    #[test]
+    fn test_msg_ctr_exhaustion() {
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
+
+        let clone_data = CloneData::new(
+            1,
+            2,
+            3,
+            4,
+            Address::default(),
+            SessionMode::Case(CaseDetails::new(5, &[0; 3])),
+        );
+        let index = sm.clone_session(&clone_data).unwrap();
+
+        let session = sm.mut_by_index(index).unwrap();
+        session.set_msg_ctr(MSG_CTR_EXHAUSTION_THRESHOLD - 1);
+        session.get_msg_ctr().unwrap();
+        assert!(!session.is_expired());
+        assert!(!session.is_msg_ctr_exhausted());
+
+        // Expired once the threshold is reached, but still usable by its exchanges
+        assert_eq!(session.get_msg_ctr().unwrap(), MSG_CTR_EXHAUSTION_THRESHOLD);
+        assert!(session.is_expired());
+        assert!(session.is_msg_ctr_exhausted());
+
+        // Kept around until its peer is notified
+        assert_eq!(sm.remove_expired(|_| false), 0);
+        assert_eq!(sm.first_exhausted(|_| true), None);
+        assert_eq!(sm.first_exhausted(|_| false), Some(index));
+
+        // Never wraps
+        let session = sm.mut_by_index(index).unwrap();
+        session.set_msg_ctr(u32::MAX);
+        assert!(session.get_msg_ctr().is_err());
+
+        // Unsecured sessions wrap
+        let index = sm.add(Address::default(), None).unwrap();
+        let session = sm.mut_by_index(index).unwrap();
+        session.set_msg_ctr(u32::MAX);
+        assert_eq!(session.get_msg_ctr().unwrap(), u32::MAX);
+        assert_eq!(session.get_msg_ctr().unwrap(), 0);
+        assert!(!session.is_msg_ctr_exhausted());
+    }
+
+    #[test]
+    fn test_session_params_tlv() {
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        let mut tw = TLVWriter::new(&mut wb);
+        SessionParams::local()
+            .to_tlv(&mut tw, TagType::Anonymous)
+            .unwrap();
+        let len = tw.get_tail();
+
+        let params = SessionParams::from_tlv(&get_root_node(&buf[..len]).unwrap()).unwrap();
+        assert_eq!(params, SessionParams::local());
+        assert_eq!(params.max_paths_per_invoke(), MAX_PATHS_PER_INVOKE);
+
+        // Only the active interval advertised; the rest takes the defaults
+        let data = [0x15, 0x25, 0x02, 0xe8, 0x03, 0x18];
+        let params = SessionParams::from_tlv(&get_root_node(&data).unwrap()).unwrap();
+        assert_eq!(params.active_interval, Some(1000));
+        assert_eq!(params.data_model_revision, None);
+        assert_eq!(
+            params.mrp_params().active_interval(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            params.mrp_params().idle_interval(),
+            Duration::from_millis(500)
+        );
+    }
 
     #[test]
     fn test_next_sess_id_doesnt_reuse() {
-        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
         let sess_idx = sm.add(Address::default(), None).unwrap();
         let sess = sm.mut_by_index(sess_idx).unwrap();
         sess.set_local_sess_id(1);
@@ -516,7 +1350,7 @@ mod tests {
 
     #[test]
     fn test_next_sess_id_overflows() {
-        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
         let sess_idx = sm.add(Address::default(), None).unwrap();
         let sess = sm.mut_by_index(sess_idx).unwrap();
         sess.set_local_sess_id(1);
@@ -526,4 +1360,129 @@ mod tests {
         assert_eq!(sm.get_next_sess_id(), 65535);
         assert_eq!(sm.get_next_sess_id(), 2);
     }
+
+    #[test]
+    fn test_eviction_policy() {
+        /// Never evicts sessions with running exchanges
+        struct KeepBusy;
+
+        impl EvictionPolicy for KeepBusy {
+            fn select(
+                &self,
+                candidates: &mut dyn Iterator<Item = EvictionCandidate<'_>>,
+            ) -> Option<usize> {
+                candidates
+                    .filter(|candidate| candidate.exchanges == 0)
+                    .map(|candidate| candidate.index)
+                    .next()
+            }
+        }
+
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
+
+        // No eviction while there are free slots
+        sm.add(Address::default(), None).unwrap();
+        assert_eq!(sm.get_session_for_eviction(&LruEviction, |_| 0), None);
+
+        for _ in 1..MAX_SESSIONS {
+            sm.add(Address::default(), None).unwrap();
+        }
+
+        assert!(sm.get_session_for_eviction(&LruEviction, |_| 0).is_some());
+
+        // Tells the session at index 2 apart from the others
+        sm.mut_by_index(2).unwrap().set_local_sess_id(3);
+
+        assert_eq!(
+            sm.get_session_for_eviction(&KeepBusy, |session| {
+                (session.get_local_sess_id() != 3) as usize
+            }),
+            Some(2)
+        );
+        assert_eq!(sm.get_session_for_eviction(&KeepBusy, |_| 1), None);
+
+        // Sessions with idle peers go first
+        let session = Session::new(Address::default(), None, dummy_epoch, dummy_rand);
+        let candidates = [0, 1, 2].map(|index| EvictionCandidate {
+            index,
+            session: &session,
+            exchanges: 0,
+            peer_active: index != 1,
+        });
+        assert_eq!(LruEviction.select(&mut candidates.into_iter()), Some(1));
+    }
+
+    #[test]
+    fn test_large_payload() {
+        let peer = SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540);
+
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
+
+        let sess_idx = sm.add(Address::Udp(peer), None).unwrap();
+        let sess = sm.mut_by_index(sess_idx).unwrap();
+        assert!(!sess.is_large_payload());
+        assert_eq!(sess.max_tx_size(), MAX_TX_MTU_SIZE);
+        // Only sessions over TCP can use Large Payloads
+        assert!(sess.set_large_payload(true).is_err());
+
+        let sess_idx = sm.add(Address::Tcp(peer), None).unwrap();
+        let sess = sm.mut_by_index(sess_idx).unwrap();
+        assert_eq!(sess.is_large_payload(), cfg!(feature = "large-payload"));
+
+        if cfg!(feature = "large-payload") {
+            assert_eq!(sess.max_tx_size(), MAX_LARGE_MSG_SIZE);
+
+            // The peer might not support Large Payloads
+            sess.set_large_payload(false).unwrap();
+            assert_eq!(sess.max_tx_size(), MAX_TX_MTU_SIZE);
+        }
+    }
+
+    #[test]
+    fn test_group_sessions() {
+        let mut keys = GroupKeyMgr::new();
+        let ctrs = MsgCounterMgr::new();
+        let key = GroupKey::new(1, 0x0101, &[7; 16]).unwrap();
+        keys.add(key.clone()).unwrap();
+
+        let mut sm = SessionMgr::<[_; MAX_SESSIONS]>::new(dummy_epoch, dummy_rand);
+
+        let mut buf = [0; 64];
+        let mut rx = Packet::new_rx(&mut buf);
+        rx.plain.sess_type = SessionType::Group;
+        rx.plain.sess_id = key.session_id();
+        rx.plain.ctr = 10;
+        rx.plain.set_src_u64(0x55);
+        rx.plain.set_dest_group(0x0101);
+
+        let sess_idx = sm.post_recv_group(&rx, &keys, &ctrs).unwrap();
+        let sess = sm.mut_by_index(sess_idx).unwrap();
+        assert_eq!(sess.get_group_id(), Some(0x0101));
+        assert_eq!(sess.get_local_fabric_idx(), Some(1));
+        assert_eq!(sess.get_peer_node_id(), Some(0x55));
+        assert!(sess.is_encrypted());
+
+        // Duplicates are detected
+        assert!(sm.post_recv_group(&rx, &keys, &ctrs).is_err());
+
+        // The same session is used for subsequent messages of the same sender
+        rx.plain.ctr = 11;
+        assert_eq!(sm.post_recv_group(&rx, &keys, &ctrs).unwrap(), sess_idx);
+
+        // Unknown group
+        rx.plain.set_dest_group(0x0102);
+        assert!(sm.post_recv_group(&rx, &keys, &ctrs).is_err());
+
+        let tx_idx = sm
+            .get_or_add_group_tx(Address::default(), 0x66, &key)
+            .unwrap();
+        assert_ne!(tx_idx, sess_idx);
+        assert_eq!(
+            sm.get_or_add_group_tx(Address::default(), 0x66, &key)
+                .unwrap(),
+            tx_idx
+        );
+
+        assert_eq!(sm.group_sessions().count(), 2);
+    }
 }
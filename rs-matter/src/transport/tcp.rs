@@ -0,0 +1,342 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Matter over TCP.
+//!
+//! Over a stream transport every Matter message is prefixed with its length, encoded as
+//! a 32-bit little-endian integer. [`TcpSend`] and [`TcpReceive`] implement this framing on top
+//! of the two halves of an established connection, and expose it as a regular
+//! [`NetworkSend`] / [`NetworkReceive`] pair which can be passed to [`crate::Matter::run`],
+//! possibly multiplexed with the other transports (see [`super::mux`]).
+//!
+//! All messages received over the connection are reported with an [`Address::Tcp`] peer address,
+//! so sessions established over the connection are bound to it.
+
+use log::warn;
+
+use crate::error::{Error, ErrorCode};
+
+use super::network::{Address, NetworkReceive, NetworkSend, SocketAddr};
+
+/// The size of the message length prefix
+pub const MSG_LEN_SIZE: usize = 4;
+
+/// The writing half of a byte stream
+pub trait StreamWrite {
+    /// Writes some of the bytes in `data`, returning how many were written
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Error>;
+}
+
+impl<T> StreamWrite for &mut T
+where
+    T: StreamWrite,
+{
+    async fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        (*self).write(data).await
+    }
+}
+
+/// The reading half of a byte stream
+pub trait StreamRead {
+    /// Reads some bytes into `buf`, returning how many were read.
+    /// Returning 0 means the stream was closed by the peer.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+impl<T> StreamRead for &mut T
+where
+    T: StreamRead,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        (*self).read(buf).await
+    }
+}
+
+pub fn encode_msg_len(len: usize) -> Result<[u8; MSG_LEN_SIZE], Error> {
+    let len: u32 = len.try_into().map_err(|_| ErrorCode::Invalid)?;
+
+    Ok(len.to_le_bytes())
+}
+
+pub fn decode_msg_len(hdr: &[u8; MSG_LEN_SIZE]) -> usize {
+    u32::from_le_bytes(*hdr) as _
+}
+
+async fn write_all<W: StreamWrite>(stream: &mut W, mut data: &[u8]) -> Result<(), Error> {
+    while !data.is_empty() {
+        let len = stream.write(data).await?;
+        if len == 0 {
+            Err(ErrorCode::ConnectionClosed)?;
+        }
+
+        data = &data[len..];
+    }
+
+    Ok(())
+}
+
+async fn read_exact<R: StreamRead>(stream: &mut R, mut buf: &mut [u8]) -> Result<(), Error> {
+    while !buf.is_empty() {
+        let len = stream.read(buf).await?;
+        if len == 0 {
+            Err(ErrorCode::ConnectionClosed)?;
+        }
+
+        buf = &mut buf[len..];
+    }
+
+    Ok(())
+}
+
+/// Sends length-prefixed Matter messages over the writing half of a TCP connection
+pub struct TcpSend<W> {
+    stream: W,
+    peer: SocketAddr,
+}
+
+impl<W> TcpSend<W>
+where
+    W: StreamWrite,
+{
+    pub const fn new(stream: W, peer: SocketAddr) -> Self {
+        Self { stream, peer }
+    }
+
+    pub fn peer(&self) -> Address {
+        Address::Tcp(self.peer)
+    }
+}
+
+impl<W> NetworkSend for TcpSend<W>
+where
+    W: StreamWrite,
+{
+    async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
+        if addr != self.peer() {
+            warn!(
+                "Dropping packet for {}: not the peer of this connection",
+                addr
+            );
+            Err(ErrorCode::InvalidPeerAddr)?;
+        }
+
+        write_all(&mut self.stream, &encode_msg_len(data.len())?).await?;
+        write_all(&mut self.stream, data).await
+    }
+}
+
+/// Receives length-prefixed Matter messages from the reading half of a TCP connection.
+///
+/// [`NetworkReceive::wait_available`] is cancel-safe, so the receiver can be multiplexed with
+/// other transports (see [`super::mux::MuxReceive`]).
+pub struct TcpReceive<R> {
+    stream: R,
+    peer: SocketAddr,
+    hdr: [u8; MSG_LEN_SIZE],
+    hdr_len: usize,
+    msg_len: Option<usize>,
+}
+
+impl<R> TcpReceive<R>
+where
+    R: StreamRead,
+{
+    pub const fn new(stream: R, peer: SocketAddr) -> Self {
+        Self {
+            stream,
+            peer,
+            hdr: [0; MSG_LEN_SIZE],
+            hdr_len: 0,
+            msg_len: None,
+        }
+    }
+
+    pub fn peer(&self) -> Address {
+        Address::Tcp(self.peer)
+    }
+
+    async fn skip(&mut self, buffer: &mut [u8], mut len: usize) -> Result<(), Error> {
+        while len > 0 {
+            let chunk = core::cmp::min(len, buffer.len());
+            read_exact(&mut self.stream, &mut buffer[..chunk]).await?;
+
+            len -= chunk;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R> NetworkReceive for TcpReceive<R>
+where
+    R: StreamRead,
+{
+    async fn wait_available(&mut self) -> Result<(), Error> {
+        while self.msg_len.is_none() {
+            // The length prefix is read into `self`, so that dropping this future halfway
+            // does not lose the framing of the stream
+            while self.hdr_len < MSG_LEN_SIZE {
+                let len = self.stream.read(&mut self.hdr[self.hdr_len..]).await?;
+                if len == 0 {
+                    Err(ErrorCode::ConnectionClosed)?;
+                }
+
+                self.hdr_len += len;
+            }
+
+            self.hdr_len = 0;
+
+            let len = decode_msg_len(&self.hdr);
+            if len > 0 {
+                self.msg_len = Some(len);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+        loop {
+            self.wait_available().await?;
+
+            let Some(len) = self.msg_len.take() else {
+                continue;
+            };
+
+            if len <= buffer.len() {
+                read_exact(&mut self.stream, &mut buffer[..len]).await?;
+
+                break Ok((len, self.peer()));
+            }
+
+            // Skip the message but keep the stream in sync, so that the following messages can still be received
+            warn!(
+                "Dropping a {}-byte message from {}: too large for the RX buffer",
+                len,
+                self.peer()
+            );
+
+            self.skip(buffer, len).await?;
+        }
+    }
+}
+
+#[cfg(all(feature = "std", feature = "async-io"))]
+mod async_io {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use async_io::Async;
+
+    use crate::error::Error;
+
+    use super::{StreamRead, StreamWrite};
+
+    impl StreamWrite for &Async<TcpStream> {
+        async fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+            Ok(self.write_with(|mut stream| stream.write(data)).await?)
+        }
+    }
+
+    impl StreamRead for &Async<TcpStream> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            Ok(self.read_with(|mut stream| stream.read(buf)).await?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::transport::network::{Address, Ipv4Addr, NetworkReceive, NetworkSend, SocketAddr};
+
+    use super::{decode_msg_len, encode_msg_len, StreamRead, StreamWrite, TcpReceive, TcpSend};
+
+    struct Stream {
+        data: std::vec::Vec<u8>,
+        offset: usize,
+        chunk: usize,
+    }
+
+    impl StreamWrite for Stream {
+        async fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+            let len = core::cmp::min(data.len(), self.chunk);
+            self.data.extend_from_slice(&data[..len]);
+
+            Ok(len)
+        }
+    }
+
+    impl StreamRead for Stream {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let len = core::cmp::min(
+                core::cmp::min(buf.len(), self.chunk),
+                self.data.len() - self.offset,
+            );
+            buf[..len].copy_from_slice(&self.data[self.offset..self.offset + len]);
+            self.offset += len;
+
+            Ok(len)
+        }
+    }
+
+    fn peer() -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::new(192, 168, 1, 2).into(), 5540)
+    }
+
+    #[test]
+    fn test_msg_len() {
+        assert_eq!(encode_msg_len(0x0102).unwrap(), [0x02, 0x01, 0, 0]);
+        assert_eq!(decode_msg_len(&[0x02, 0x01, 0, 0]), 0x0102);
+    }
+
+    #[test]
+    fn test_framing() {
+        let mut stream = Stream {
+            data: std::vec::Vec::new(),
+            offset: 0,
+            chunk: 3,
+        };
+
+        {
+            let mut send = TcpSend::new(&mut stream, peer());
+
+            embassy_futures::block_on(send.send_to(&[1, 2, 3, 4, 5], Address::Tcp(peer())))
+                .unwrap();
+            embassy_futures::block_on(send.send_to(&[0; 20], Address::Tcp(peer()))).unwrap();
+            embassy_futures::block_on(send.send_to(&[6, 7], Address::Tcp(peer()))).unwrap();
+
+            // Only the peer of the connection can be addressed
+            assert!(embassy_futures::block_on(send.send_to(&[1], Address::Udp(peer()))).is_err());
+        }
+
+        assert_eq!(&stream.data[..9], &[5, 0, 0, 0, 1, 2, 3, 4, 5]);
+
+        let mut recv = TcpReceive::new(&mut stream, peer());
+        let mut buf = [0; 8];
+
+        let (len, addr) = embassy_futures::block_on(recv.recv_from(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[1, 2, 3, 4, 5]);
+        assert_eq!(addr, Address::Tcp(peer()));
+
+        // The oversized message is skipped
+        let (len, _) = embassy_futures::block_on(recv.recv_from(&mut buf)).unwrap();
+        assert_eq!(&buf[..len], &[6, 7]);
+
+        assert!(embassy_futures::block_on(recv.recv_from(&mut buf)).is_err());
+    }
+}
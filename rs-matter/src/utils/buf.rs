@@ -17,6 +17,7 @@
 
 use core::ops::{Deref, DerefMut};
 
+use embassy_futures::select::select_array;
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::mutex::{Mutex, MutexGuard};
 
@@ -35,7 +36,10 @@ impl<T> BufferAccess for &T
 where
     T: BufferAccess,
 {
-    type Buffer<'a> = T::Buffer<'a> where Self: 'a;
+    type Buffer<'a>
+        = T::Buffer<'a>
+    where
+        Self: 'a;
 
     async fn get(&self) -> Self::Buffer<'_> {
         (*self).get().await
@@ -53,7 +57,10 @@ impl<const N: usize> BufferAccessImpl<N> {
 }
 
 impl<const N: usize> BufferAccess for BufferAccessImpl<N> {
-    type Buffer<'a> = BufferImpl<'a, N> where Self: 'a;
+    type Buffer<'a>
+        = BufferImpl<'a, N>
+    where
+        Self: 'a;
 
     async fn get(&self) -> Self::Buffer<'_> {
         let mut guard = self.0.lock().await;
@@ -64,6 +71,46 @@ impl<const N: usize> BufferAccess for BufferAccessImpl<N> {
     }
 }
 
+/// A pool of `M` buffers of `N` bytes each, where every buffer can be accessed independently
+/// of the others - either by its index, or by awaiting the first buffer which becomes available.
+pub struct BufferPool<const M: usize, const N: usize>([BufferAccessImpl<N>; M]);
+
+impl<const M: usize, const N: usize> BufferPool<M, N> {
+    // Only used as the array repeat operand, so that the pool can be built in a `const fn`
+    // on the MSRV, which has no inline `const { .. }` blocks yet
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: BufferAccessImpl<N> = BufferAccessImpl::new();
+
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self([Self::INIT; M])
+    }
+
+    /// Get access to the buffer with the provided index, if it exists
+    pub fn buffer(&self, index: usize) -> Option<&BufferAccessImpl<N>> {
+        self.0.get(index)
+    }
+}
+
+impl<const M: usize, const N: usize> Default for BufferPool<M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const M: usize, const N: usize> BufferAccess for BufferPool<M, N> {
+    type Buffer<'a>
+        = BufferImpl<'a, N>
+    where
+        Self: 'a;
+
+    async fn get(&self) -> Self::Buffer<'_> {
+        let buffers: [_; M] = core::array::from_fn(|index| self.0[index].get());
+
+        select_array(buffers).await.0
+    }
+}
+
 pub struct BufferImpl<'a, const N: usize>(MutexGuard<'a, NoopRawMutex, heapless::Vec<u8, N>>);
 
 impl<'a, const N: usize> Deref for BufferImpl<'a, N> {
@@ -79,3 +126,36 @@ impl<'a, const N: usize> DerefMut for BufferImpl<'a, N> {
         &mut self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+
+    use embassy_futures::poll_once;
+
+    use super::{BufferAccess, BufferPool};
+
+    #[test]
+    fn test_buffer_pool() {
+        let pool = BufferPool::<2, 16>::new();
+
+        let mut first = embassy_futures::block_on(pool.get());
+        let second = embassy_futures::block_on(pool.get());
+        assert_eq!(first.len(), 16);
+        assert_eq!(second.len(), 16);
+
+        first[0] = 1;
+
+        // Both buffers are taken
+        let mut third = pin!(pool.get());
+        assert!(poll_once(third.as_mut()).is_pending());
+
+        // ... including when accessed by index
+        assert!(poll_once(pin!(pool.buffer(1).unwrap().get())).is_pending());
+
+        drop(second);
+
+        assert!(poll_once(third.as_mut()).is_ready());
+        assert!(pool.buffer(2).is_none());
+    }
+}
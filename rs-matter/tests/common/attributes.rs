@@ -26,7 +26,7 @@ pub fn __assert_attr_report(received: &ReportDataMsg, expected: &[AttrResp], ski
     let mut index = 0;
 
     // We can't use assert_eq because it will also try to match data-version
-    for inv_response in received.attr_reports.as_ref().unwrap().iter() {
+    for inv_response in received.attr_resps() {
         println!("Validating index {}", index);
         match &expected[index] {
             AttrResp::Data(e_d) => match inv_response {
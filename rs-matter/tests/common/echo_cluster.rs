@@ -22,9 +22,9 @@ use num_derive::FromPrimitive;
 use rs_matter::{
     attribute_enum, command_enum,
     data_model::objects::{
-        Access, AttrData, AttrDataEncoder, AttrDataWriter, AttrDetails, AttrType, Attribute,
-        Cluster, CmdDataEncoder, CmdDataWriter, CmdDetails, Dataver, Handler, NonBlockingHandler,
-        Quality, ATTRIBUTE_LIST, FEATURE_MAP,
+        Access, AttrData, AttrDataEncoder, AttrDetails, AttrType, Attribute, Cluster,
+        CmdDataEncoder, CmdDataWriter, CmdDetails, Command, Dataver, Handler, NonBlockingHandler,
+        Quality, ACCEPTED_COMMAND_LIST, ATTRIBUTE_LIST, FEATURE_MAP, GENERATED_COMMAND_LIST,
     },
     error::{Error, ErrorCode},
     interaction_model::messages::ib::{attr_list_write, ListOperation},
@@ -67,6 +67,8 @@ pub const CLUSTER: Cluster<'static> = Cluster {
     attributes: &[
         FEATURE_MAP,
         ATTRIBUTE_LIST,
+        ACCEPTED_COMMAND_LIST,
+        GENERATED_COMMAND_LIST,
         Attribute::new(
             AttributesDiscriminants::Att1 as u16,
             Access::RV,
@@ -93,7 +95,9 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Quality::NONE,
         ),
     ],
-    commands: &[Commands::EchoReq as _],
+    commands: &[Command::new(Commands::EchoReq as _, Access::WO)],
+    generated_commands: &[RespCommands::EchoResp as _],
+    events: &[],
 };
 
 /// This is used in the tests to validate any settings that may have happened
@@ -125,6 +129,9 @@ impl TestChecker {
 
 pub const WRITE_LIST_MAX: usize = 5;
 
+/// The cluster-specific status of an echo request, whose echo does not fit in a byte
+pub const ECHO_OVERFLOW_STATUS: u8 = 0x01;
+
 pub struct EchoCluster {
     pub data_ver: Dataver,
     pub multiplier: u8,
@@ -132,6 +139,8 @@ pub struct EchoCluster {
     pub att2: Cell<u16>,
     pub att_write: Cell<u16>,
     pub att_custom: Cell<u32>,
+    /// When set, the reads of Att1 and the writes of AttWrite fail with `Busy`
+    pub busy: Cell<bool>,
 }
 
 impl EchoCluster {
@@ -143,15 +152,17 @@ impl EchoCluster {
             att2: Cell::new(0x5678),
             att_write: Cell::new(ATTR_WRITE_DEFAULT_VALUE),
             att_custom: Cell::new(ATTR_CUSTOM_VALUE),
+            busy: Cell::new(false),
         }
     }
 
     pub fn read(&self, attr: &AttrDetails, encoder: AttrDataEncoder) -> Result<(), Error> {
-        if let Some(mut writer) = encoder.with_dataver(self.data_ver.get())? {
+        if let Some(writer) = encoder.with_dataver(self.data_ver.get())? {
             if attr.is_system() {
                 CLUSTER.read(attr.attr_id, writer)
             } else {
                 match attr.attr_id.try_into()? {
+                    Attributes::Att1(_) if self.busy.get() => Err(ErrorCode::Busy.into()),
                     Attributes::Att1(codec) => codec.encode(writer, 0x1234),
                     Attributes::Att2(codec) => codec.encode(writer, 0x5678),
                     Attributes::AttWrite(codec) => codec.encode(writer, ATTR_WRITE_DEFAULT_VALUE),
@@ -160,13 +171,7 @@ impl EchoCluster {
                         let tc_handle = TestChecker::get().unwrap();
                         let tc = tc_handle.lock().unwrap();
 
-                        writer.start_array(AttrDataWriter::TAG)?;
-                        for i in tc.write_list.iter().flatten() {
-                            writer.u16(TagType::Anonymous, *i)?;
-                        }
-                        writer.end_container()?;
-
-                        writer.complete()
+                        writer.set_list(tc.write_list.iter().flatten())
                     }
                 }
             }
@@ -176,15 +181,18 @@ impl EchoCluster {
     }
 
     pub fn write(&self, attr: &AttrDetails, data: AttrData) -> Result<(), Error> {
-        let data = data.with_dataver(self.data_ver.get())?;
+        let dataver = self.data_ver.get();
 
         match attr.attr_id.try_into()? {
-            Attributes::Att1(codec) => self.att1.set(codec.decode(data)?),
-            Attributes::Att2(codec) => self.att2.set(codec.decode(data)?),
-            Attributes::AttWrite(codec) => self.att_write.set(codec.decode(data)?),
-            Attributes::AttCustom(codec) => self.att_custom.set(codec.decode(data)?),
+            Attributes::Att1(_) => self.att1.set(data.get(dataver)?),
+            Attributes::Att2(_) => self.att2.set(data.get(dataver)?),
+            Attributes::AttWrite(_) if self.busy.get() => Err(ErrorCode::Busy)?,
+            Attributes::AttWrite(_) => self.att_write.set(data.get(dataver)?),
+            Attributes::AttCustom(_) => self.att_custom.set(data.get(dataver)?),
             Attributes::AttWriteList(_) => {
-                attr_list_write(attr, data, |op, data| self.write_attr_list(&op, data))?
+                attr_list_write(attr, data.with_dataver(dataver)?, |op, data| {
+                    self.write_attr_list(&op, data)
+                })?
             }
         }
 
@@ -205,12 +213,15 @@ impl EchoCluster {
             // with data multiplied by the multiplier
             Commands::EchoReq => {
                 let a = data.u8()?;
+                let echo = a
+                    .checked_mul(self.multiplier)
+                    .ok_or(ErrorCode::ClusterStatus(ECHO_OVERFLOW_STATUS))?;
 
                 let mut writer = encoder.with_command(RespCommands::EchoResp as _)?;
 
                 writer.start_struct(CmdDataWriter::TAG)?;
                 // Echo = input * self.multiplier
-                writer.u8(TagType::Context(0), a * self.multiplier)?;
+                writer.u8(TagType::Context(0), echo)?;
                 writer.end_container()?;
 
                 writer.complete()
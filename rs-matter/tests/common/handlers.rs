@@ -123,6 +123,30 @@ impl<'a> ImEngine<'a> {
         im.handle_commands(&im.handler(), input, expected)
     }
 
+    /// Invokes commands expecting the whole request to be rejected with a status
+    pub fn commands_status(input: &[CmdData], expected: IMStatusCode) {
+        let im = ImEngine::new_default();
+
+        im.add_default_acl();
+
+        let req = InvReq {
+            suppress_response: Some(false),
+            timed_request: Some(false),
+            inv_requests: Some(TLVArray::Slice(input)),
+        };
+
+        let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process(&im.handler(), &[&input], &mut out).unwrap();
+
+        assert_eq!(out[0].action, OpCode::StatusResponse);
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let status_resp = StatusResp::from_tlv(&root).unwrap();
+        assert_eq!(status_resp.status, expected);
+    }
+
     // Helper for handling Invoke Command sequences
     pub fn handle_commands(
         &self,
@@ -206,7 +230,7 @@ impl<'a> ImEngine<'a> {
         delay: u16,
     ) {
         let mut out = heapless::Vec::<_, 2>::new();
-        let write_req = WriteReq::new(false, input);
+        let write_req = WriteReq::new(false, input).set_timed_request(timeout != 0);
 
         self.gen_timed_reqs_output(
             handler,
@@ -234,6 +258,34 @@ impl<'a> ImEngine<'a> {
         }
     }
 
+    // Helper for handling a Read request following a TimedRequest, which is not allowed
+    pub fn handle_timed_read_reqs(
+        &self,
+        handler: &ImEngineHandler,
+        input: &[AttrPath],
+        expected: IMStatusCode,
+        timeout: u16,
+    ) {
+        let mut out = heapless::Vec::<_, 2>::new();
+        let read_req = ReadReq::new(true).set_attr_requests(input);
+
+        self.gen_timed_reqs_output(
+            handler,
+            OpCode::ReadRequest,
+            &read_req,
+            timeout,
+            0,
+            &mut out,
+        );
+
+        let out = &out[out.len() - 1];
+        let root = tlv::get_root_node_struct(&out.data).unwrap();
+
+        assert_eq!(out.action, OpCode::StatusResponse);
+        let status_resp = StatusResp::from_tlv(&root).unwrap();
+        assert_eq!(status_resp.status, expected);
+    }
+
     pub fn timed_commands(
         input: &[CmdData],
         expected: &TimedInvResponse,
@@ -18,8 +18,9 @@
 use crate::common::echo_cluster;
 use core::borrow::Borrow;
 use core::future::pending;
+use core::pin::pin;
 use core::time::Duration;
-use embassy_futures::select::select3;
+use embassy_futures::select::{select3, select4};
 use embassy_sync::{
     blocking_mutex::raw::{NoopRawMutex, RawMutex},
     zerocopy_channel::{Channel, Receiver, Sender},
@@ -31,14 +32,14 @@ use rs_matter::{
         cluster_on_off::{self, OnOffCluster},
         device_types::{DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_ROOT_NODE},
         objects::{
-            AttrData, AttrDataEncoder, AttrDetails, Endpoint, Handler, HandlerCompat, Metadata,
-            Node, NonBlockingHandler, Privilege,
+            AttrData, AttrDataEncoder, AttrDetails, DataModelHandler, Endpoint, Handler,
+            HandlerCompat, Metadata, Node, NonBlockingHandler, Privilege,
         },
         root_endpoint::{self, RootEndpointHandler},
         sdm::{
             admin_commissioning,
             dev_att::{DataType, DevAttDataFetcher},
-            general_commissioning, noc, nw_commissioning,
+            general_commissioning, general_diagnostics, noc, nw_commissioning,
         },
         system_model::{
             access_control,
@@ -52,13 +53,13 @@ use rs_matter::{
     secure_channel::{self, common::PROTO_ID_SECURE_CHANNEL, spake2p::VerifierData},
     tlv::{TLVWriter, TagType, ToTLV},
     transport::{
-        core::PacketBuffers,
+        core::{ExchangeQueue, PacketBuffers},
         network::{Address, Ipv4Addr, NetworkReceive, NetworkSend, SocketAddr, SocketAddrV4},
         packet::{Packet, MAX_RX_BUF_SIZE, MAX_TX_BUF_SIZE},
         session::{CaseDetails, CloneData, NocCatIds, SessionMode},
     },
     utils::select::{EitherUnwrap, Notification},
-    CommissioningData, Matter, MATTER_PORT,
+    CommissioningData, DefaultMatterStorage, Matter, MATTER_PORT,
 };
 
 use super::echo_cluster::EchoCluster;
@@ -99,9 +100,13 @@ const NODE: Node<'static> = Node {
                 admin_commissioning::CLUSTER,
                 noc::CLUSTER,
                 access_control::CLUSTER,
+                general_diagnostics::CLUSTER,
                 echo_cluster::CLUSTER,
             ],
+            client_clusters: &[],
+            tags: &[],
             device_type: DEV_TYPE_ROOT_NODE,
+            parent: None,
         },
         Endpoint {
             id: 1,
@@ -110,7 +115,10 @@ const NODE: Node<'static> = Node {
                 cluster_on_off::CLUSTER,
                 echo_cluster::CLUSTER,
             ],
+            client_clusters: &[],
+            tags: &[],
             device_type: DEV_TYPE_ON_OFF_LIGHT,
+            parent: None,
         },
     ],
 };
@@ -187,7 +195,10 @@ impl<'a> Handler for ImEngineHandler<'a> {
 impl<'a> NonBlockingHandler for ImEngineHandler<'a> {}
 
 impl<'a> Metadata for ImEngineHandler<'a> {
-    type MetadataGuard<'g> = Node<'g> where Self: 'g;
+    type MetadataGuard<'g>
+        = Node<'g>
+    where
+        Self: 'g;
 
     fn lock(&self) -> Self::MetadataGuard<'_> {
         NODE
@@ -196,7 +207,7 @@ impl<'a> Metadata for ImEngineHandler<'a> {
 
 /// An Interaction Model Engine to facilitate easy testing
 pub struct ImEngine<'a> {
-    pub matter: Matter<'a>,
+    pub matter: Matter<'a, DefaultMatterStorage>,
     cat_ids: NocCatIds,
 }
 
@@ -248,6 +259,46 @@ impl<'a> ImEngine<'a> {
         input: &[&ImInput],
         out: &mut heapless::Vec<ImOutput, N>,
     ) -> Result<(), Error> {
+        self.process_with(&HandlerCompat(handler), input, out)
+    }
+
+    /// Processes the input with any data model handler, e.g. with an asynchronous one
+    pub fn process_with<H, const N: usize>(
+        &self,
+        handler: &H,
+        input: &[&ImInput],
+        out: &mut heapless::Vec<ImOutput, N>,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        self.process_stack(handler, input, out, false)
+    }
+
+    /// Like [`Self::process_with`], but runs the stack as separately polled futures - the
+    /// transport RX and TX halves, a responder and the reporter - rather than with `Matter::run`
+    pub fn process_split_with<H, const N: usize>(
+        &self,
+        handler: &H,
+        input: &[&ImInput],
+        out: &mut heapless::Vec<ImOutput, N>,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        self.process_stack(handler, input, out, true)
+    }
+
+    fn process_stack<H, const N: usize>(
+        &self,
+        handler: &H,
+        input: &[&ImInput],
+        out: &mut heapless::Vec<ImOutput, N>,
+        split: bool,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
         self.matter.reset_transport();
 
         let clone_data = CloneData::new(
@@ -261,7 +312,7 @@ impl<'a> ImEngine<'a> {
 
         let sess_idx = self
             .matter
-            .session_mgr
+            .session_mgr()
             .borrow_mut()
             .clone_session(&clone_data)
             .unwrap();
@@ -272,28 +323,26 @@ impl<'a> ImEngine<'a> {
         let mut send_channel = Channel::<NoopRawMutex, _>::new(&mut send_channel_buf);
         let mut recv_channel = Channel::<NoopRawMutex, _>::new(&mut recv_channel_buf);
 
-        let handler = &handler;
-
         let mut msg_ctr = self
             .matter
-            .session_mgr
+            .session_mgr()
             .borrow_mut()
             .mut_by_index(sess_idx)
             .unwrap()
-            .get_msg_ctr();
+            .get_msg_ctr()?;
 
         let resp_notif = Notification::new();
         let resp_notif = &resp_notif;
 
-        let mut buffers = PacketBuffers::new();
-        let buffers = &mut buffers;
+        let buffers = PacketBuffers::new();
+        let buffers = &buffers;
 
         let (send, mut send_dest) = send_channel.split();
         let (mut recv_dest, recv) = recv_channel.split();
 
         embassy_futures::block_on(async move {
             select3(
-                self.matter.run(
+                self.run_stack(
                     NetworkSender(send),
                     NetworkReceiver(recv),
                     buffers,
@@ -302,7 +351,8 @@ impl<'a> ImEngine<'a> {
                         verifier: VerifierData::new_with_pw(123456, *self.matter.borrow()),
                         discriminator: 250,
                     },
-                    &HandlerCompat(handler),
+                    handler,
+                    split,
                 ),
                 async move {
                     let mut acknowledge = false;
@@ -363,6 +413,39 @@ impl<'a> ImEngine<'a> {
         Ok(())
     }
 
+    async fn run_stack<H>(
+        &self,
+        send: NetworkSender<'_>,
+        recv: NetworkReceiver<'_>,
+        buffers: &PacketBuffers,
+        dev_comm: CommissioningData,
+        handler: &H,
+        split: bool,
+    ) -> Result<(), Error>
+    where
+        H: DataModelHandler,
+    {
+        if !split {
+            return self
+                .matter
+                .run(send, recv, buffers, dev_comm, handler)
+                .await;
+        }
+
+        self.matter.start_transport(dev_comm).await?;
+
+        let queue = ExchangeQueue::new();
+
+        let mut rx = pin!(self.matter.transport_rx(recv, &queue));
+        let mut tx = pin!(self.matter.transport_tx(send));
+        let mut responder = pin!(self.matter.responder(0, &queue, handler));
+        let mut reporter = pin!(self.matter.reporter(handler));
+
+        select4(&mut rx, &mut tx, &mut responder, &mut reporter)
+            .await
+            .unwrap()
+    }
+
     async fn send(
         input: &ImInput<'_>,
         sender: &mut Sender<'_, impl RawMutex, heapless::Vec<u8, MAX_RX_BUF_SIZE>>,
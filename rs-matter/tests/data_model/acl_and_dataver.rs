@@ -19,20 +19,25 @@ use rs_matter::{
     acl::{gen_noc_cat, AclEntry, AuthMode, Target},
     data_model::{
         objects::{EncodeValue, Privilege},
+        sdm::general_commissioning as gen_comm,
         system_model::access_control,
     },
     interaction_model::{
         core::IMStatusCode,
-        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus, ClusterPath, DataVersionFilter},
+        messages::ib::{
+            AttrData, AttrPath, AttrResp, AttrStatus, ClusterPath, CmdData, CmdPath, CmdStatus,
+            DataVersionFilter,
+        },
         messages::GenericPath,
     },
     tlv::{ElementType, TLVArray, TLVElement, TLVWriter, TagType},
 };
 
 use crate::{
-    attr_data_path, attr_status,
+    attr_data_path, attr_status, cmd_data,
     common::{
         attributes::*,
+        commands::ExpectedInvResp,
         echo_cluster::{self, ATTR_WRITE_DEFAULT_VALUE},
         im_engine::{ImEngine, IM_ENGINE_PEER_ID},
         init_env_logger,
@@ -371,6 +376,37 @@ fn insufficient_perms_write() {
     );
 }
 
+#[test]
+/// Ensure that an invoke of a command needing more than the granted privilege is rejected
+fn insufficient_perms_invoke() {
+    init_env_logger();
+    let arm_failsafe = CmdPath::new(
+        Some(0),
+        Some(gen_comm::ID),
+        Some(gen_comm::Commands::ArmFailsafe as u32),
+    );
+    let input = &[cmd_data!(arm_failsafe.clone(), 5)];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    // Add ACL to allow our peer with only OPERATE permission, while arming the
+    // fail-safe needs ADMINISTER
+    let mut acl = AclEntry::new(1, Privilege::OPERATE, AuthMode::Case);
+    acl.add_subject(IM_ENGINE_PEER_ID).unwrap();
+    im.matter.acl_mgr.borrow_mut().add(acl).unwrap();
+
+    im.handle_commands(
+        &handler,
+        input,
+        &[ExpectedInvResp::Status(CmdStatus::new(
+            arm_failsafe,
+            IMStatusCode::UnsupportedAccess,
+            0,
+        ))],
+    );
+}
+
 /// Disabling this test as it conflicts with another part of the spec.
 ///
 /// The spec expects that a single write request like DeleteList + AddItem
@@ -552,6 +588,77 @@ fn test_read_data_ver() {
     assert_attr_report(&received, expected_error);
 }
 
+#[test]
+/// A change made outside of an interaction bumps the data version of the cluster, so that
+/// the reads filtered with the previous data version get the changed cluster
+fn test_read_data_ver_attribute_changed() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    // Add ACL to allow our peer with only OPERATE permission
+    let acl = AclEntry::new(1, Privilege::OPERATE, AuthMode::Case);
+    im.matter.acl_mgr.borrow_mut().add(acl).unwrap();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att1 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1)];
+    let expected = &[attr_data_path!(ep0_att1.clone(), ElementType::U16(0x1234))];
+
+    let mut out = heapless::Vec::new();
+    let received = im.gen_read_reqs_output::<1>(&handler, input, None, &mut out);
+    assert_attr_report(&received, expected);
+
+    let data_ver = received
+        .attr_reports
+        .as_ref()
+        .unwrap()
+        .get_index(0)
+        .unwrap_data()
+        .data_ver
+        .unwrap();
+
+    let dataver_filter = [DataVersionFilter {
+        path: ClusterPath {
+            node: None,
+            endpoint: 0,
+            cluster: echo_cluster::ID,
+        },
+        data_ver,
+    }];
+
+    // The echo cluster does not bump its own data version when Att1 changes
+    im.matter.attribute_changed(
+        0,
+        echo_cluster::ID,
+        echo_cluster::AttributesDiscriminants::Att1 as _,
+    );
+
+    let mut out = heapless::Vec::new();
+    let received = im.gen_read_reqs_output::<1>(
+        &handler,
+        input,
+        Some(TLVArray::Slice(&dataver_filter)),
+        &mut out,
+    );
+    assert_attr_report(&received, expected);
+
+    let new_data_ver = received
+        .attr_reports
+        .as_ref()
+        .unwrap()
+        .get_index(0)
+        .unwrap_data()
+        .data_ver
+        .unwrap();
+    assert_eq!(data_ver.wrapping_add(1), new_data_ver);
+    assert_eq!(data_ver, handler.echo_cluster(0).data_ver.get());
+}
+
 #[test]
 /// - Write with the correct data version should go through
 /// - Write with incorrect data version should fail with error
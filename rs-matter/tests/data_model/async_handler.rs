@@ -0,0 +1,149 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        cluster_on_off::{self, OnOffCluster},
+        objects::{
+            AsyncHandler, AttrDataEncoder, AttrDetails, CmdDataEncoder, CmdDetails, EncodeValue,
+            HandlerCompat,
+        },
+    },
+    error::Error,
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{CmdData, CmdPath, CmdStatus},
+            msg::{InvReq, InvResp},
+        },
+    },
+    tlv::{self, FromTLV, TLVArray, TLVElement},
+    transport::exchange::Exchange,
+};
+
+use crate::{
+    cmd_data,
+    common::{
+        commands::{assert_inv_response, ExpectedInvResp},
+        im_engine::{ImEngine, ImInput},
+        init_env_logger,
+    },
+};
+
+/// A relay switched over a (simulated) asynchronous bus
+#[derive(Default)]
+struct Relay {
+    on: Cell<bool>,
+}
+
+impl Relay {
+    async fn switch(&self, on: bool) {
+        embassy_futures::yield_now().await;
+
+        self.on.set(on);
+    }
+}
+
+/// An OnOff cluster handler, which switches the relay when its state changes
+struct RelayOnOff<'a> {
+    cluster: OnOffCluster,
+    relay: &'a Relay,
+}
+
+impl<'a> AsyncHandler for RelayOnOff<'a> {
+    async fn read<'r>(
+        &'r self,
+        attr: &'r AttrDetails<'_>,
+        encoder: AttrDataEncoder<'r, '_, '_>,
+    ) -> Result<(), Error> {
+        self.cluster.read(attr, encoder)
+    }
+
+    async fn invoke<'r>(
+        &'r self,
+        exchange: &'r Exchange<'_>,
+        cmd: &'r CmdDetails<'_>,
+        data: &'r TLVElement<'_>,
+        encoder: CmdDataEncoder<'r, '_, '_>,
+    ) -> Result<(), Error> {
+        self.cluster.invoke(exchange, cmd, data, encoder)?;
+
+        self.relay.switch(self.cluster.get()).await;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_async_handler_invoke() {
+    // An asynchronous OnOff handler chained in front of the synchronous ones awaits the relay
+    // before the command is responded to
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let handler = im.handler();
+    let relay = Relay::default();
+
+    let dm_handler = (
+        HandlerCompat(&handler),
+        HandlerCompat(&handler).chain(
+            1,
+            cluster_on_off::ID,
+            RelayOnOff {
+                cluster: OnOffCluster::new(*im.matter.borrow()),
+                relay: &relay,
+            },
+        ),
+    );
+
+    for expected_on in [true, false] {
+        let path = CmdPath::new(
+            Some(1),
+            Some(cluster_on_off::ID),
+            Some(cluster_on_off::CommandsDiscriminants::Toggle as u32),
+        );
+        let input = &[cmd_data!(path.clone(), 1)];
+
+        let req = InvReq {
+            suppress_response: Some(false),
+            timed_request: Some(false),
+            inv_requests: Some(TLVArray::Slice(input)),
+        };
+        let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let resp = InvResp::from_tlv(&root).unwrap();
+        assert_inv_response(
+            &resp,
+            &[ExpectedInvResp::Status(CmdStatus::new(
+                path,
+                IMStatusCode::Success,
+                0,
+            ))],
+        );
+
+        assert_eq!(relay.on.get(), expected_on);
+    }
+}
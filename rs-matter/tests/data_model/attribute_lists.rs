@@ -18,16 +18,16 @@
 use rs_matter::{
     data_model::objects::EncodeValue,
     interaction_model::{
-        core::IMStatusCode,
+        core::{IMStatusCode, OpCode},
         messages::ib::{AttrData, AttrPath, AttrStatus},
-        messages::GenericPath,
+        messages::{msg::WriteReq, GenericPath},
     },
     tlv::Nullable,
 };
 
 use crate::common::{
     echo_cluster::{self, TestChecker},
-    im_engine::ImEngine,
+    im_engine::{ImEngine, ImInput},
     init_env_logger,
 };
 
@@ -129,7 +129,7 @@ fn attr_list_ops() {
 
     // Test 6: Overwrite Operation - delete whole list
     att_path.list_index = None;
-    let input = &[AttrData::new(None, att_path, delete_all)];
+    let input = &[AttrData::new(None, att_path.clone(), delete_all)];
     let expected = &[AttrStatus::new(&att_data, IMStatusCode::Success, 0)];
 
     ImEngine::write_reqs(input, expected);
@@ -137,4 +137,39 @@ fn attr_list_ops() {
         let tc = tc_handle.lock().unwrap();
         assert_eq!([None, None, None, None, None], tc.write_list);
     }
+
+    // Test 7: Chunked write - the first chunk overwrites the list, the next one appends to it
+    let first_val: [u16; 1] = [30];
+    let next_val: u16 = 31;
+    let first = &[AttrData::new(
+        None,
+        att_path.clone(),
+        EncodeValue::Value(&first_val),
+    )];
+    att_path.list_index = Some(Nullable::Null);
+    let next = &[AttrData::new(None, att_path, EncodeValue::Value(&next_val))];
+
+    let first_req = WriteReq::new(false, first).set_more_chunked(true);
+    let next_req = WriteReq::new(false, next);
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let mut out = heapless::Vec::<_, 2>::new();
+    im.process(
+        &im.handler(),
+        &[
+            &ImInput::new(OpCode::WriteRequest, &first_req),
+            &ImInput::new(OpCode::WriteRequest, &next_req),
+        ],
+        &mut out,
+    )
+    .unwrap();
+
+    assert_eq!(out.len(), 2);
+    assert!(out.iter().all(|o| o.action == OpCode::WriteResponse));
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!([Some(30), Some(31), None, None, None], tc.write_list);
+    }
 }
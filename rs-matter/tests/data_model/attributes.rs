@@ -17,8 +17,8 @@
 
 use rs_matter::{
     data_model::{
-        cluster_on_off,
-        objects::{EncodeValue, GlobalElements},
+        cluster_basic_information, cluster_on_off,
+        objects::{EncodeValue, GlobalElements, HandlerCompat},
     },
     interaction_model::{
         core::IMStatusCode,
@@ -72,6 +72,92 @@ fn test_read_success() {
     ImEngine::read_reqs(input, expected);
 }
 
+#[test]
+fn test_read_busy() {
+    // 2 Attr Read Requests
+    // - first on endpoint 0, att1, whose handler is busy - Busy
+    // - second on endpoint 0, att2 - still read
+    init_env_logger();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att1 as u32),
+    );
+    let ep0_att2 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att2 as u32),
+    );
+    let input = &[AttrPath::new(&ep0_att1), AttrPath::new(&ep0_att2)];
+    let expected = &[
+        attr_status!(&ep0_att1, IMStatusCode::Busy),
+        attr_data_path!(ep0_att2, ElementType::U16(0x5678)),
+    ];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    handler.echo_cluster(0).busy.set(true);
+    im.handle_read_reqs(&handler, input, expected);
+}
+
+#[test]
+fn test_read_tag_compression() {
+    // 3 Attr Read Requests
+    // - first on endpoint 0, att1 - path in full
+    // - second on endpoint 0, att2 - same cluster, so its path is compressed
+    // - third on endpoint 1, att2 - path in full
+    init_env_logger();
+
+    let ep0_att1 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att1 as u32),
+    );
+    let ep0_att2 = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att2 as u32),
+    );
+    let ep1_att2 = GenericPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att2 as u32),
+    );
+    let input = &[
+        AttrPath::new(&ep0_att1),
+        AttrPath::new(&ep0_att2),
+        AttrPath::new(&ep1_att2),
+    ];
+    let expected = &[
+        attr_data_path!(ep0_att1, ElementType::U16(0x1234)),
+        attr_data_path!(ep0_att2, ElementType::U16(0x5678)),
+        attr_data_path!(ep1_att2, ElementType::U16(0x5678)),
+    ];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.matter.set_report_tag_compression(true);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    let received = im.gen_read_reqs_output(&handler, input, None, &mut out);
+
+    let compressed = received
+        .attr_reports
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|resp| resp.path().tag_compression == Some(true))
+        .collect::<heapless::Vec<_, 3>>();
+    assert_eq!(&compressed, &[false, true, false]);
+
+    assert_attr_report(&received, expected);
+}
+
 #[test]
 fn test_read_unsupported_fields() {
     // 6 reads
@@ -179,7 +265,7 @@ fn test_read_wc_endpoint_only_1_has_cluster() {
 fn test_read_wc_endpoint_wc_attribute() {
     // 1 Attr Read Request
     // - wildcard endpoint, wildcard attribute
-    // - 8 responses are expected, 1+3 attributes on endpoint 0, 1+3 on endpoint 1
+    // - 14 responses are expected, 4+3 attributes on endpoint 0, 4+3 on endpoint 1
     init_env_logger();
     let wc_ep_wc_attr = GenericPath::new(None, Some(echo_cluster::ID), None);
     let input = &[AttrPath::new(&wc_ep_wc_attr)];
@@ -189,6 +275,8 @@ fn test_read_wc_endpoint_wc_attribute() {
         &[
             GlobalElements::FeatureMap as u16,
             GlobalElements::AttributeList as u16,
+            GlobalElements::AcceptedCommandList as u16,
+            GlobalElements::GeneratedCommandList as u16,
             echo_cluster::AttributesDiscriminants::Att1 as u16,
             echo_cluster::AttributesDiscriminants::Att2 as u16,
             echo_cluster::AttributesDiscriminants::AttWrite as u16,
@@ -196,6 +284,10 @@ fn test_read_wc_endpoint_wc_attribute() {
         ],
     );
     let attr_list_tlv = attr_list.to_tlv();
+    let accepted_cmds = TLVHolder::new_array(2, &[echo_cluster::Commands::EchoReq as u32]);
+    let accepted_cmds_tlv = accepted_cmds.to_tlv();
+    let generated_cmds = TLVHolder::new_array(2, &[echo_cluster::RespCommands::EchoResp as u32]);
+    let generated_cmds_tlv = generated_cmds.to_tlv();
 
     let expected = &[
         attr_data_path!(
@@ -214,6 +306,22 @@ fn test_read_wc_endpoint_wc_attribute() {
             ),
             attr_list_tlv.get_element_type().clone()
         ),
+        attr_data_path!(
+            GenericPath::new(
+                Some(0),
+                Some(echo_cluster::ID),
+                Some(GlobalElements::AcceptedCommandList as u32),
+            ),
+            accepted_cmds_tlv.get_element_type().clone()
+        ),
+        attr_data_path!(
+            GenericPath::new(
+                Some(0),
+                Some(echo_cluster::ID),
+                Some(GlobalElements::GeneratedCommandList as u32),
+            ),
+            generated_cmds_tlv.get_element_type().clone()
+        ),
         attr_data_path!(
             GenericPath::new(
                 Some(0),
@@ -254,6 +362,22 @@ fn test_read_wc_endpoint_wc_attribute() {
             ),
             attr_list_tlv.get_element_type().clone()
         ),
+        attr_data_path!(
+            GenericPath::new(
+                Some(1),
+                Some(echo_cluster::ID),
+                Some(GlobalElements::AcceptedCommandList as u32),
+            ),
+            accepted_cmds_tlv.get_element_type().clone()
+        ),
+        attr_data_path!(
+            GenericPath::new(
+                Some(1),
+                Some(echo_cluster::ID),
+                Some(GlobalElements::GeneratedCommandList as u32),
+            ),
+            generated_cmds_tlv.get_element_type().clone()
+        ),
         attr_data_path!(
             GenericPath::new(
                 Some(1),
@@ -335,6 +459,166 @@ fn test_write_success() {
     assert_eq!(val1, handler.echo_cluster(1).att_write.get());
 }
 
+#[test]
+fn test_write_invalid_data_type() {
+    // 1 Attr Write Request
+    // - endpoint 0, AttWrite with a boolean instead of an u16
+    init_env_logger();
+    let attr_data0 = |tag, t: &mut TLVWriter| {
+        let _ = t.bool(tag, true);
+    };
+
+    let ep0_att = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::AttWrite as u32),
+    );
+
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&ep0_att),
+        EncodeValue::Closure(&attr_data0),
+    )];
+    let expected = &[AttrStatus::new(&ep0_att, IMStatusCode::InvalidDataType, 0)];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.handle_write_reqs(&handler, input, expected);
+
+    assert_eq!(
+        echo_cluster::ATTR_WRITE_DEFAULT_VALUE,
+        handler.echo_cluster(0).att_write.get()
+    );
+}
+
+#[test]
+fn test_write_constraint_error() {
+    // 1 Attr Write Request
+    // - endpoint 0, NodeLabel longer than its 32 bytes
+    init_env_logger();
+    let attr_data0 = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, &[b'a'; 33]);
+    };
+
+    let ep0_att = GenericPath::new(
+        Some(0),
+        Some(cluster_basic_information::ID),
+        Some(cluster_basic_information::AttributesDiscriminants::NodeLabel as u32),
+    );
+
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&ep0_att),
+        EncodeValue::Closure(&attr_data0),
+    )];
+    let expected = &[AttrStatus::new(&ep0_att, IMStatusCode::ConstraintError, 0)];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.handle_write_reqs(&handler, input, expected);
+}
+
+#[test]
+fn test_write_persisted() {
+    // 1 Attr Write Request
+    // - endpoint 0, NodeLabel, which is non-volatile: its value is restored on another node
+    //   loading the persisted values
+    init_env_logger();
+    let attr_data0 = |tag, t: &mut TLVWriter| {
+        let _ = t.utf8(tag, b"kitchen");
+    };
+
+    let ep0_att = GenericPath::new(
+        Some(0),
+        Some(cluster_basic_information::ID),
+        Some(cluster_basic_information::AttributesDiscriminants::NodeLabel as u32),
+    );
+
+    let input = &[AttrData::new(
+        None,
+        AttrPath::new(&ep0_att),
+        EncodeValue::Closure(&attr_data0),
+    )];
+    let expected = &[AttrStatus::new(&ep0_att, IMStatusCode::Success, 0)];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.handle_write_reqs(&handler, input, expected);
+
+    let mut buf = [0; 256];
+    let data = im.matter.store_nv_attributes(&mut buf).unwrap().unwrap();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    im.matter.load_nv_attributes(data).unwrap();
+    embassy_futures::block_on(im.matter.restore_nv_attributes(&HandlerCompat(&handler))).unwrap();
+
+    let input = &[AttrPath::new(&ep0_att)];
+    let expected = &[attr_data_path!(ep0_att, ElementType::Utf8l(b"kitchen"))];
+    im.handle_read_reqs(&handler, input, expected);
+}
+
+#[test]
+fn test_write_busy() {
+    // 2 Attr Write Requests
+    // - first on endpoint 0, attwrite, whose handler is busy - Busy
+    // - second on endpoint 1, attwrite - still written
+    let val0 = 10;
+    init_env_logger();
+    let attr_data0 = |tag, t: &mut TLVWriter| {
+        let _ = t.u16(tag, val0);
+    };
+
+    let ep0_attwrite = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::AttWrite as u32),
+    );
+    let ep1_attwrite = GenericPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::AttWrite as u32),
+    );
+
+    let input = &[
+        AttrData::new(
+            None,
+            AttrPath::new(&ep0_attwrite),
+            EncodeValue::Closure(&attr_data0),
+        ),
+        AttrData::new(
+            None,
+            AttrPath::new(&ep1_attwrite),
+            EncodeValue::Closure(&attr_data0),
+        ),
+    ];
+    let expected = &[
+        AttrStatus::new(&ep0_attwrite, IMStatusCode::Busy, 0),
+        AttrStatus::new(&ep1_attwrite, IMStatusCode::Success, 0),
+    ];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+    handler.echo_cluster(0).busy.set(true);
+    im.handle_write_reqs(&handler, input, expected);
+
+    assert_eq!(
+        echo_cluster::ATTR_WRITE_DEFAULT_VALUE,
+        handler.echo_cluster(0).att_write.get()
+    );
+    assert_eq!(val0, handler.echo_cluster(1).att_write.get());
+}
+
 #[test]
 fn test_write_wc_endpoint() {
     // 1 Attr Write Request
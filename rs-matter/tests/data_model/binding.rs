@@ -0,0 +1,169 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::RefCell;
+
+use rs_matter::{
+    binding::{Binding, BindingMgr},
+    data_model::{
+        cluster_binding::{self, BindingCluster},
+        cluster_on_off,
+        objects::{DeviceType, EncodeValue, Endpoint, HandlerCompat, Node},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrPath, AttrStatus},
+            msg::{WriteReq, WriteResp},
+            GenericPath,
+        },
+    },
+    tlv::{self, FromTLV, TLVWriter, TagType},
+};
+
+use crate::common::{
+    im_engine::{ImEngine, ImInput},
+    init_env_logger,
+};
+
+const DEV_TYPE_ON_OFF_LIGHT_SWITCH: DeviceType = DeviceType {
+    dtype: 0x0103,
+    drev: 1,
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_ON_OFF_LIGHT_SWITCH,
+            parent: None,
+            clusters: &[descriptor::CLUSTER, cluster_binding::CLUSTER],
+            client_clusters: &[cluster_on_off::ID],
+            tags: &[],
+        },
+    ],
+};
+
+#[test]
+fn test_write_bindings() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let binding_mgr: &RefCell<BindingMgr> = im.matter.borrow();
+    let bindings = BindingCluster::new(binding_mgr, *im.matter.borrow());
+
+    let dm_handler = (
+        NODE,
+        HandlerCompat(
+            root_endpoint::handler(0, &im.matter)
+                .chain(
+                    1,
+                    descriptor::ID,
+                    descriptor::DescriptorCluster::new(*im.matter.borrow()),
+                )
+                .chain(1, cluster_binding::ID, &bindings),
+        ),
+    );
+
+    let path = GenericPath::new(
+        Some(1),
+        Some(cluster_binding::ID),
+        Some(cluster_binding::AttributesDiscriminants::Binding as u32),
+    );
+
+    // A light bound for its OnOff cluster, and a group of lights
+    let valid = |tag: TagType, tw: &mut TLVWriter| {
+        tw.start_array(tag).unwrap();
+        tw.start_struct(TagType::Anonymous).unwrap();
+        tw.u64(TagType::Context(1), 100).unwrap();
+        tw.u16(TagType::Context(3), 2).unwrap();
+        tw.u32(TagType::Context(4), cluster_on_off::ID).unwrap();
+        tw.end_container().unwrap();
+        tw.start_struct(TagType::Anonymous).unwrap();
+        tw.u16(TagType::Context(2), 0x10).unwrap();
+        tw.end_container().unwrap();
+        tw.end_container().unwrap();
+    };
+
+    // A group, with an endpoint
+    let invalid = |tag: TagType, tw: &mut TLVWriter| {
+        tw.start_array(tag).unwrap();
+        tw.start_struct(TagType::Anonymous).unwrap();
+        tw.u16(TagType::Context(2), 0x10).unwrap();
+        tw.u16(TagType::Context(3), 2).unwrap();
+        tw.end_container().unwrap();
+        tw.end_container().unwrap();
+    };
+
+    for (data, expected_status) in [
+        (
+            &valid as &dyn Fn(TagType, &mut TLVWriter),
+            IMStatusCode::Success,
+        ),
+        (&invalid, IMStatusCode::ConstraintError),
+        (&valid, IMStatusCode::Success),
+    ] {
+        let input = &[AttrData::new(
+            None,
+            AttrPath::new(&path),
+            EncodeValue::Closure(data),
+        )];
+
+        let req = WriteReq::new(false, input);
+        let input = ImInput::new(OpCode::WriteRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let resp = WriteResp::from_tlv(&root).unwrap();
+        assert!(resp
+            .write_responses
+            .iter()
+            .eq([AttrStatus::new(&path, expected_status, 0)]));
+    }
+
+    // Both targets were written on the fabric of the accessing administrator, and only the
+    // latest write of the whole list is kept
+    assert_eq!(
+        im.matter.resolve_bindings(1, cluster_on_off::ID).as_slice(),
+        &[
+            Binding::Node {
+                fab_idx: 1,
+                node_id: 100,
+                endpoint: 2,
+                cluster: Some(cluster_on_off::ID),
+            },
+            Binding::Group {
+                fab_idx: 1,
+                group_id: 0x10,
+                cluster: None,
+            },
+        ]
+    );
+    assert_eq!(im.matter.resolve_bindings(1, descriptor::ID).len(), 1);
+
+    let mut buf = [0; 256];
+    assert!(im.matter.store_bindings(&mut buf).unwrap().is_some());
+}
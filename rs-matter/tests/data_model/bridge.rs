@@ -0,0 +1,150 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    data_model::{
+        cluster_bridged_device_basic_information::{
+            self as bridged, BridgedDeviceInfo, BridgedDevicesCluster,
+        },
+        device_types::{DEV_TYPE_AGGREGATOR, DEV_TYPE_ON_OFF_LIGHT},
+        objects::{
+            DataModelHandler, DynamicNode, EncodeValue, Endpoint, HandlerCompat, SharedNode,
+        },
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::ib::{AttrData, AttrPath, AttrResp, AttrStatus},
+        messages::{msg::ReadReq, msg::ReportDataMsg, GenericPath},
+    },
+    tlv::{self, ElementType, FromTLV, TLVElement, TagType},
+};
+
+use crate::{
+    attr_data_path, attr_status,
+    common::{
+        attributes::*,
+        im_engine::{ImEngine, ImInput},
+        init_env_logger,
+    },
+};
+
+const LIGHT: BridgedDeviceInfo<'static> = BridgedDeviceInfo {
+    vid: 0xFFF1,
+    hw_ver: 1,
+    sw_ver: 2,
+    sw_ver_str: "2",
+    serial_no: "zb-0001",
+    vendor_name: "Zigbee Vendor",
+    product_name: "Zigbee Light",
+    unique_id: "00:11:22:33:44:55:66:77",
+};
+
+fn read_reqs<H: DataModelHandler>(
+    im: &ImEngine,
+    handler: &H,
+    input: &[AttrPath],
+    expected: &[AttrResp],
+) {
+    let read_req = ReadReq::new(true).set_attr_requests(input);
+    let input = ImInput::new(OpCode::ReadRequest, &read_req);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    im.process_with(handler, &[&input], &mut out).unwrap();
+
+    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+    let received = ReportDataMsg::from_tlv(&root).unwrap();
+    assert_attr_report(&received, expected);
+}
+
+#[test]
+fn test_bridged_device() {
+    // A bridged light is added under the aggregator endpoint 1, becomes unreachable, and is
+    // removed
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let handler = im.handler();
+
+    let mut node = DynamicNode::<2>::new(0);
+    node.add(Endpoint {
+        id: 1,
+        device_type: DEV_TYPE_AGGREGATOR,
+        parent: None,
+        clusters: &[descriptor::CLUSTER],
+        client_clusters: &[],
+        tags: &[],
+    })
+    .unwrap();
+    let node = SharedNode::new(node);
+
+    let bridged = BridgedDevicesCluster::<1>::new(*im.matter.borrow());
+
+    let dm_handler = (
+        &node,
+        HandlerCompat(&handler).chain_cluster(bridged::ID, HandlerCompat(&bridged)),
+    );
+
+    let light = Endpoint {
+        id: 2,
+        device_type: DEV_TYPE_ON_OFF_LIGHT,
+        parent: None,
+        clusters: &[descriptor::CLUSTER, bridged::CLUSTER],
+        client_clusters: &[],
+        tags: &[],
+    };
+    embassy_futures::block_on(bridged.add_device(&im.matter, &node, 1, light, &LIGHT, true))
+        .unwrap();
+    assert_eq!(bridged.is_reachable(2), Some(true));
+
+    let reachable = GenericPath::new(
+        Some(2),
+        Some(bridged::ID),
+        Some(bridged::AttributesDiscriminants::Reachable as u32),
+    );
+    let unique_id = GenericPath::new(
+        Some(2),
+        Some(bridged::ID),
+        Some(bridged::AttributesDiscriminants::UniqueId as u32),
+    );
+    let input = &[AttrPath::new(&reachable), AttrPath::new(&unique_id)];
+
+    let expected = &[
+        attr_data_path!(reachable, ElementType::True),
+        attr_data_path!(unique_id, ElementType::Utf8l(b"00:11:22:33:44:55:66:77")),
+    ];
+    read_reqs(&im, &dm_handler, input, expected);
+
+    bridged.set_reachable(&im.matter, 2, false).unwrap();
+
+    let expected = &[
+        attr_data_path!(reachable, ElementType::False),
+        attr_data_path!(unique_id, ElementType::Utf8l(b"00:11:22:33:44:55:66:77")),
+    ];
+    read_reqs(&im, &dm_handler, input, expected);
+
+    embassy_futures::block_on(bridged.remove_device(&im.matter, &node, 2)).unwrap();
+    assert_eq!(bridged.is_reachable(2), None);
+
+    let input = &[AttrPath::new(&reachable)];
+    let expected = &[attr_status!(&reachable, IMStatusCode::UnsupportedEndpoint)];
+    read_reqs(&im, &dm_handler, input, expected);
+}
@@ -15,18 +15,35 @@
  *    limitations under the License.
  */
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::{
     cmd_data,
-    common::{commands::*, echo_cluster, im_engine::ImEngine, init_env_logger},
+    common::{
+        commands::*,
+        echo_cluster,
+        im_engine::{ImEngine, ImInput},
+        init_env_logger,
+    },
     echo_req, echo_resp,
 };
 
 use rs_matter::{
-    data_model::{cluster_on_off, objects::EncodeValue},
+    data_model::{
+        cluster_on_off,
+        objects::{EncodeValue, HandlerCompat},
+        sdm::general_diagnostics::{self, TestEventTriggerHandler},
+    },
+    error::{Error, ErrorCode},
     interaction_model::{
-        core::IMStatusCode,
-        messages::ib::{CmdData, CmdPath, CmdStatus},
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{CmdData, CmdPath, CmdStatus},
+            msg::{InvReq, InvResp},
+        },
     },
+    tlv::{self, FromTLV, TLVArray, TLVWriter, TagType},
+    transport::session::MAX_PATHS_PER_INVOKE,
 };
 
 #[test]
@@ -36,19 +53,136 @@ fn test_invoke_cmds_success() {
     // - another on endpoint 1 with data 10
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
+    let expected = &[echo_resp!(0, 10), echo_resp!(1, 30)];
+    ImEngine::commands(input, expected);
+}
+
+#[test]
+fn test_invoke_cmds_split_stack() {
+    // The same 2 echo Requests, processed by the transport RX and TX halves and a responder
+    // polled as separate futures, rather than by `Matter::run`
+    init_env_logger();
+
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
     let expected = &[echo_resp!(0, 10), echo_resp!(1, 30)];
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let handler = im.handler();
+
+    let req = InvReq {
+        suppress_response: Some(false),
+        timed_request: Some(false),
+        inv_requests: Some(TLVArray::Slice(input)),
+    };
+    let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    im.process_split_with(&HandlerCompat(&handler), &[&input], &mut out)
+        .unwrap();
+
+    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+    let resp = InvResp::from_tlv(&root).unwrap();
+    assert_inv_response(&resp, expected);
+}
+
+#[test]
+fn test_invoke_cmds_batch_partial_failure() {
+    // 2 commands in a batch
+    // - echo request on endpoint 0 - success
+    // - command doesn't exist - UnsupportedCommand, with the reference of the command
+    init_env_logger();
+
+    let invalid_command = CmdPath::new(Some(0), Some(echo_cluster::ID), Some(0x1234));
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        cmd_data!(invalid_command.clone(), 5).set_command_ref(2),
+    ];
+    let expected = &[
+        echo_resp!(0, 10),
+        ExpectedInvResp::Status(
+            CmdStatus::new(invalid_command, IMStatusCode::UnsupportedCommand, 0)
+                .set_command_ref(Some(2)),
+        ),
+    ];
+    ImEngine::commands(input, expected);
+}
+
+#[test]
+fn test_invoke_cmds_cluster_status() {
+    // An echo request whose echo overflows fails with the cluster-specific status of the cluster
+    init_env_logger();
+
+    let echo_path = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::EchoReq as u32),
+    );
+    let input = &[echo_req!(0, 200)];
+    let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+        echo_path,
+        IMStatusCode::Failure,
+        echo_cluster::ECHO_OVERFLOW_STATUS as _,
+    ))];
     ImEngine::commands(input, expected);
 }
 
+#[test]
+fn test_invoke_cmds_invalid_batch() {
+    // Batches are rejected as a whole with InvalidAction
+    // - when a command has no reference
+    // - when two commands have the same reference
+    // - when a command has a wildcard path
+    // - when they have more commands than the node accepts
+    init_env_logger();
+
+    let wc_endpoint = CmdPath::new(
+        None,
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::EchoReq as u32),
+    );
+
+    ImEngine::commands_status(
+        &[echo_req!(0, 5).set_command_ref(1), echo_req!(1, 10)],
+        IMStatusCode::InvalidAction,
+    );
+    ImEngine::commands_status(
+        &[
+            echo_req!(0, 5).set_command_ref(1),
+            echo_req!(1, 10).set_command_ref(1),
+        ],
+        IMStatusCode::InvalidAction,
+    );
+    ImEngine::commands_status(
+        &[
+            echo_req!(0, 5).set_command_ref(1),
+            cmd_data!(wc_endpoint, 5).set_command_ref(2),
+        ],
+        IMStatusCode::InvalidAction,
+    );
+
+    let too_many: Vec<_> = (0..MAX_PATHS_PER_INVOKE + 1)
+        .map(|index| echo_req!(0, 5).set_command_ref(index))
+        .collect();
+    ImEngine::commands_status(&too_many, IMStatusCode::InvalidAction);
+}
+
 #[test]
 fn test_invoke_cmds_unsupported_fields() {
-    // 5 commands
+    // 5 commands, each in its own request
     // - endpoint doesn't exist - UnsupportedEndpoint
     // - cluster doesn't exist - UnsupportedCluster
-    // - cluster doesn't exist and endpoint is wildcard - UnsupportedCluster
+    // - cluster doesn't exist and endpoint is wildcard - no response
     // - command doesn't exist - UnsupportedCommand
-    // - command doesn't exist and endpoint is wildcard - UnsupportedCommand
+    // - command doesn't exist and endpoint is wildcard - no response
     init_env_logger();
 
     let invalid_endpoint = CmdPath::new(
@@ -68,32 +202,32 @@ fn test_invoke_cmds_unsupported_fields() {
     );
     let invalid_command = CmdPath::new(Some(0), Some(echo_cluster::ID), Some(0x1234));
     let invalid_command_wc_endpoint = CmdPath::new(None, Some(echo_cluster::ID), Some(0x1234));
-    let input = &[
-        cmd_data!(invalid_endpoint.clone(), 5),
-        cmd_data!(invalid_cluster.clone(), 5),
-        cmd_data!(invalid_cluster_wc_endpoint, 5),
-        cmd_data!(invalid_command.clone(), 5),
-        cmd_data!(invalid_command_wc_endpoint, 5),
-    ];
-
-    let expected = &[
-        ExpectedInvResp::Status(CmdStatus::new(
+    ImEngine::commands(
+        &[cmd_data!(invalid_endpoint.clone(), 5)],
+        &[ExpectedInvResp::Status(CmdStatus::new(
             invalid_endpoint,
             IMStatusCode::UnsupportedEndpoint,
             0,
-        )),
-        ExpectedInvResp::Status(CmdStatus::new(
+        ))],
+    );
+    ImEngine::commands(
+        &[cmd_data!(invalid_cluster.clone(), 5)],
+        &[ExpectedInvResp::Status(CmdStatus::new(
             invalid_cluster,
             IMStatusCode::UnsupportedCluster,
             0,
-        )),
-        ExpectedInvResp::Status(CmdStatus::new(
+        ))],
+    );
+    ImEngine::commands(&[cmd_data!(invalid_cluster_wc_endpoint, 5)], &[]);
+    ImEngine::commands(
+        &[cmd_data!(invalid_command.clone(), 5)],
+        &[ExpectedInvResp::Status(CmdStatus::new(
             invalid_command,
             IMStatusCode::UnsupportedCommand,
             0,
-        )),
-    ];
-    ImEngine::commands(input, expected);
+        ))],
+    );
+    ImEngine::commands(&[cmd_data!(invalid_command_wc_endpoint, 5)], &[]);
 }
 
 #[test]
@@ -135,3 +269,77 @@ fn test_invoke_cmd_wc_endpoint_only_1_has_cluster() {
     ))];
     ImEngine::commands(input, expected);
 }
+
+/// Triggers the test event 0x1234 only
+struct TestTriggers {
+    triggered: AtomicBool,
+}
+
+impl TestEventTriggerHandler for TestTriggers {
+    fn enable_key(&self) -> &[u8; 16] {
+        &[0x5a; 16]
+    }
+
+    fn trigger(&self, trigger: u64) -> Result<(), Error> {
+        if trigger == 0x1234 {
+            self.triggered.store(true, Ordering::SeqCst);
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidCommand.into())
+        }
+    }
+}
+
+static TEST_TRIGGERS: TestTriggers = TestTriggers {
+    triggered: AtomicBool::new(false),
+};
+
+#[test]
+fn test_invoke_test_event_trigger() {
+    // TestEventTrigger commands
+    // - without a trigger handler - ConstraintError
+    // - with a wrong enable key - ConstraintError
+    // - with an unsupported trigger - InvalidCommand
+    // - with a supported trigger - Success
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let handler = im.handler();
+
+    let path = CmdPath::new(
+        Some(0),
+        Some(general_diagnostics::ID),
+        Some(general_diagnostics::Commands::TestEventTrigger as u32),
+    );
+
+    let trigger = |enable_key: [u8; 16], trigger: u64, expected: IMStatusCode| {
+        let data = move |tag: TagType, tw: &mut TLVWriter| {
+            tw.start_struct(tag).unwrap();
+            tw.str8(TagType::Context(0), &enable_key).unwrap();
+            tw.u64(TagType::Context(1), trigger).unwrap();
+            tw.end_container().unwrap();
+        };
+
+        let input = &[CmdData::new(path.clone(), EncodeValue::Closure(&data))];
+        let expected = &[ExpectedInvResp::Status(CmdStatus::new(
+            path.clone(),
+            expected,
+            0,
+        ))];
+        im.handle_commands(&handler, input, expected);
+    };
+
+    trigger([0x5a; 16], 0x1234, IMStatusCode::ConstraintError);
+
+    im.matter
+        .set_test_event_trigger_handler(Some(&TEST_TRIGGERS));
+
+    trigger([0xa5; 16], 0x1234, IMStatusCode::ConstraintError);
+    trigger([0x5a; 16], 0x4321, IMStatusCode::InvalidCommand);
+    assert!(!TEST_TRIGGERS.triggered.load(Ordering::SeqCst));
+
+    trigger([0x5a; 16], 0x1234, IMStatusCode::Success);
+    assert!(TEST_TRIGGERS.triggered.load(Ordering::SeqCst));
+}
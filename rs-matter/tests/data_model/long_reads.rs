@@ -20,8 +20,8 @@ use rs_matter::{
         cluster_basic_information as basic_info, cluster_on_off as onoff,
         objects::{EncodeValue, GlobalElements},
         sdm::{
-            admin_commissioning as adm_comm, general_commissioning as gen_comm, noc,
-            nw_commissioning,
+            admin_commissioning as adm_comm, general_commissioning as gen_comm,
+            general_diagnostics as gen_diag, noc, nw_commissioning,
         },
         system_model::{access_control as acl, descriptor},
     },
@@ -46,12 +46,24 @@ use crate::{
     },
 };
 
-fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
+fn wildcard_read_resp() -> Vec<AttrResp<'static>> {
     // For brevity, we only check the AttrPath, not the actual 'data'
     let dont_care = ElementType::U8(0);
-    let part1 = vec![
+    vec![
         attr_data!(0, 29, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 29, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            29,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            29,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             29,
@@ -63,6 +75,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         attr_data!(0, 29, descriptor::Attributes::ClientList, dont_care.clone()),
         attr_data!(0, 40, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 40, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            40,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            40,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             40,
@@ -125,6 +149,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
         attr_data!(0, 48, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 48, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            48,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            48,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             48,
@@ -151,6 +187,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
         attr_data!(0, 49, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 49, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            49,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            49,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             49,
@@ -195,6 +243,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
         attr_data!(0, 60, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 60, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            60,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            60,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             60,
@@ -213,11 +273,20 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             adm_comm::AttributesDiscriminants::AdminVendorId,
             dont_care.clone()
         ),
-    ];
-
-    let part2 = vec![
         attr_data!(0, 62, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 62, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            62,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            62,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             62,
@@ -244,6 +313,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
         attr_data!(0, 31, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(0, 31, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            31,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            31,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(0, 31, acl::AttributesDiscriminants::Acl, dont_care.clone()),
         attr_data!(
             0,
@@ -269,6 +350,32 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             acl::AttributesDiscriminants::EntriesPerFabric,
             dont_care.clone()
         ),
+        attr_data!(0, 51, GlobalElements::FeatureMap, dont_care.clone()),
+        attr_data!(0, 51, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            0,
+            51,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            51,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            51,
+            gen_diag::AttributesDiscriminants::RebootCount,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            51,
+            gen_diag::AttributesDiscriminants::TestEventTriggersEnabled,
+            dont_care.clone()
+        ),
         attr_data!(0, echo::ID, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(
             0,
@@ -276,6 +383,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             GlobalElements::AttributeList,
             dont_care.clone()
         ),
+        attr_data!(
+            0,
+            echo::ID,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            0,
+            echo::ID,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             0,
             echo::ID,
@@ -296,6 +415,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         ),
         attr_data!(1, 29, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(1, 29, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(
+            1,
+            29,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            29,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             1,
             29,
@@ -307,6 +438,13 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
         attr_data!(1, 29, descriptor::Attributes::ClientList, dont_care.clone()),
         attr_data!(1, 6, GlobalElements::FeatureMap, dont_care.clone()),
         attr_data!(1, 6, GlobalElements::AttributeList, dont_care.clone()),
+        attr_data!(1, 6, GlobalElements::AcceptedCommandList, dont_care.clone()),
+        attr_data!(
+            1,
+            6,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             1,
             6,
@@ -320,6 +458,18 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             GlobalElements::AttributeList,
             dont_care.clone()
         ),
+        attr_data!(
+            1,
+            echo::ID,
+            GlobalElements::AcceptedCommandList,
+            dont_care.clone()
+        ),
+        attr_data!(
+            1,
+            echo::ID,
+            GlobalElements::GeneratedCommandList,
+            dont_care.clone()
+        ),
         attr_data!(
             1,
             echo::ID,
@@ -338,18 +488,34 @@ fn wildcard_read_resp(part: u8) -> Vec<AttrResp<'static>> {
             echo::AttributesDiscriminants::AttCustom,
             dont_care
         ),
-    ];
+    ]
+}
+
+/// Asserts that the chunks of a report carry - in order - all of the `expected` attributes
+fn assert_wildcard_read_chunks(chunks: &[&[u8]], expected: &[AttrResp]) {
+    let mut offset = 0;
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let root = tlv::get_root_node_struct(chunk).unwrap();
+        let report_data = ReportDataMsg::from_tlv(&root).unwrap();
 
-    if part == 1 {
-        part1
-    } else {
-        part2
+        let len = report_data.attr_reports.as_ref().unwrap().iter().count();
+        assert_attr_report_skip_data(&report_data, &expected[offset..offset + len]);
+        offset += len;
+
+        let last = index == chunks.len() - 1;
+        assert_eq!(
+            report_data.more_chunks,
+            if last { None } else { Some(true) }
+        );
     }
+
+    assert_eq!(offset, expected.len());
 }
 
 #[test]
 fn test_long_read_success() {
-    // Read the entire attribute database, which requires 2 reads to complete
+    // Read the entire attribute database, which requires 3 reads to complete
     init_env_logger();
 
     let mut out = heapless::Vec::<_, 3>::new();
@@ -362,46 +528,38 @@ fn test_long_read_success() {
 
     let read_all = [AttrPath::new(&wc_path)];
     let read_req = ReadReq::new(true).set_attr_requests(&read_all);
-    let expected_part1 = wildcard_read_resp(1);
+    let expected = wildcard_read_resp();
 
     let status_report = StatusResp {
         status: IMStatusCode::Success,
     };
-    let expected_part2 = wildcard_read_resp(2);
 
     im.process(
         &handler,
         &[
             &ImInput::new(OpCode::ReadRequest, &read_req),
             &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
         ],
         &mut out,
     )
     .unwrap();
 
-    assert_eq!(out.len(), 2);
-
-    assert_eq!(out[0].action, OpCode::ReportData);
-
-    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
-    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
-    assert_attr_report_skip_data(&report_data, &expected_part1);
-    assert_eq!(report_data.more_chunks, Some(true));
+    assert_eq!(out.len(), 3);
 
-    assert_eq!(out[1].action, OpCode::ReportData);
+    for out in &out {
+        assert_eq!(out.action, OpCode::ReportData);
+    }
 
-    let root = tlv::get_root_node_struct(&out[1].data).unwrap();
-    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
-    assert_attr_report_skip_data(&report_data, &expected_part2);
-    assert_eq!(report_data.more_chunks, None);
+    assert_wildcard_read_chunks(&[&out[0].data, &out[1].data, &out[2].data], &expected);
 }
 
 #[test]
 fn test_long_read_subscription_success() {
-    // Subscribe to the entire attribute database, which requires 2 reads to complete
+    // Subscribe to the entire attribute database, which requires 3 reads to complete
     init_env_logger();
 
-    let mut out = heapless::Vec::<_, 3>::new();
+    let mut out = heapless::Vec::<_, 4>::new();
     let im = ImEngine::new_default();
     let handler = im.handler();
 
@@ -411,12 +569,11 @@ fn test_long_read_subscription_success() {
 
     let read_all = [AttrPath::new(&wc_path)];
     let subs_req = SubscribeReq::new(true, 1, 20).set_attr_requests(&read_all);
-    let expected_part1 = wildcard_read_resp(1);
+    let expected = wildcard_read_resp();
 
     let status_report = StatusResp {
         status: IMStatusCode::Success,
     };
-    let expected_part2 = wildcard_read_resp(2);
 
     im.process(
         &handler,
@@ -424,30 +581,65 @@ fn test_long_read_subscription_success() {
             &ImInput::new(OpCode::SubscribeRequest, &subs_req),
             &ImInput::new(OpCode::StatusResponse, &status_report),
             &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
         ],
         &mut out,
     )
     .unwrap();
 
-    assert_eq!(out.len(), 3);
+    assert_eq!(out.len(), 4);
+
+    for out in &out[..3] {
+        assert_eq!(out.action, OpCode::ReportData);
+    }
+
+    assert_wildcard_read_chunks(&[&out[0].data, &out[1].data, &out[2].data], &expected);
+
+    assert_eq!(out[3].action, OpCode::SubscribeResponse);
+
+    let root = tlv::get_root_node_struct(&out[3].data).unwrap();
+    let subs_resp = SubscribeResp::from_tlv(&root).unwrap();
+    assert_eq!(subs_resp.subs_id, 1);
+    assert_eq!(subs_resp.max_int, 20);
+}
+
+#[test]
+fn test_long_read_subscription_icd_max_int() {
+    // A sleepy node extends the max interval to its idle mode duration
+    init_env_logger();
+
+    let mut out = heapless::Vec::<_, 4>::new();
+    let im = ImEngine::new_default();
+    let handler = im.handler();
 
-    assert_eq!(out[0].action, OpCode::ReportData);
+    im.add_default_acl();
+    im.matter.set_idle_mode_duration(Some(300));
 
-    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
-    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
-    assert_attr_report_skip_data(&report_data, &expected_part1);
-    assert_eq!(report_data.more_chunks, Some(true));
+    let wc_path = GenericPath::new(None, None, None);
 
-    assert_eq!(out[1].action, OpCode::ReportData);
+    let read_all = [AttrPath::new(&wc_path)];
+    let subs_req = SubscribeReq::new(true, 1, 20).set_attr_requests(&read_all);
 
-    let root = tlv::get_root_node_struct(&out[1].data).unwrap();
-    let report_data = ReportDataMsg::from_tlv(&root).unwrap();
-    assert_attr_report_skip_data(&report_data, &expected_part2);
-    assert_eq!(report_data.more_chunks, None);
+    let status_report = StatusResp {
+        status: IMStatusCode::Success,
+    };
 
-    assert_eq!(out[2].action, OpCode::SubscribeResponse);
+    im.process(
+        &handler,
+        &[
+            &ImInput::new(OpCode::SubscribeRequest, &subs_req),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
+            &ImInput::new(OpCode::StatusResponse, &status_report),
+        ],
+        &mut out,
+    )
+    .unwrap();
 
-    let root = tlv::get_root_node_struct(&out[2].data).unwrap();
+    assert_eq!(out.len(), 4);
+    assert_eq!(out[3].action, OpCode::SubscribeResponse);
+
+    let root = tlv::get_root_node_struct(&out[3].data).unwrap();
     let subs_resp = SubscribeResp::from_tlv(&root).unwrap();
-    assert_eq!(subs_resp.subs_id, 1);
+    assert_eq!(subs_resp.max_int, 300);
 }
@@ -0,0 +1,125 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        cluster_on_off,
+        objects::{EncodeValue, HandlerCompat, Middleware, MiddlewareHandler, Operation},
+    },
+    error::{Error, ErrorCode},
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{CmdData, CmdPath, CmdStatus},
+            msg::{InvReq, InvResp},
+        },
+    },
+    tlv::{self, FromTLV, TLVArray},
+};
+
+use crate::{
+    cmd_data,
+    common::{
+        commands::{assert_inv_response, ExpectedInvResp},
+        im_engine::{ImEngine, ImInput},
+        init_env_logger,
+    },
+};
+
+/// A middleware which vetoes the commands of the OnOff cluster while locked, and which counts
+/// the commands completed successfully
+#[derive(Default)]
+struct ChildLock {
+    locked: Cell<bool>,
+    completed: Cell<u32>,
+}
+
+impl Middleware for ChildLock {
+    fn before(&self, op: Operation) -> Result<(), Error> {
+        match op {
+            Operation::Invoke(_, cmd)
+                if cmd.cluster_id == cluster_on_off::ID && self.locked.get() =>
+            {
+                Err(ErrorCode::UnsupportedAccess.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn after(&self, op: Operation, result: &Result<(), Error>) {
+        if matches!(op, Operation::Invoke(..)) && result.is_ok() {
+            self.completed.set(self.completed.get() + 1);
+        }
+    }
+}
+
+#[test]
+fn test_middleware_veto() {
+    // The commands vetoed by the middleware are responded to with its error as their status,
+    // and never reach the handler
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let handler = im.handler();
+    let child_lock = ChildLock::default();
+
+    let dm_handler = (
+        HandlerCompat(&handler),
+        MiddlewareHandler::new(&child_lock, HandlerCompat(&handler)),
+    );
+
+    for (locked, expected_status) in [
+        (true, IMStatusCode::UnsupportedAccess),
+        (false, IMStatusCode::Success),
+    ] {
+        child_lock.locked.set(locked);
+
+        let path = CmdPath::new(
+            Some(1),
+            Some(cluster_on_off::ID),
+            Some(cluster_on_off::CommandsDiscriminants::Toggle as u32),
+        );
+        let input = &[cmd_data!(path.clone(), 1)];
+
+        let req = InvReq {
+            suppress_response: Some(false),
+            timed_request: Some(false),
+            inv_requests: Some(TLVArray::Slice(input)),
+        };
+        let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let resp = InvResp::from_tlv(&root).unwrap();
+        assert_inv_response(
+            &resp,
+            &[ExpectedInvResp::Status(CmdStatus::new(
+                path,
+                expected_status,
+                0,
+            ))],
+        );
+    }
+
+    assert_eq!(child_lock.completed.get(), 1);
+}
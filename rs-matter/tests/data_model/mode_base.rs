@@ -0,0 +1,191 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        cluster_mode_base::{
+            self, ModeCluster, ModeDelegate, ModeOption, ModeTag, DISHWASHER_MODE_CLUSTER,
+            DISHWASHER_MODE_ID, STATUS_INVALID_IN_MODE, STATUS_SUCCESS, STATUS_UNSUPPORTED_MODE,
+            TAG_AUTO, TAG_LOW_ENERGY, TAG_QUICK,
+        },
+        objects::{DeviceType, EncodeValue, Endpoint, HandlerCompat, Node},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::OpCode,
+        messages::{
+            ib::{AttrData, AttrPath, AttrResp, CmdData, CmdPath},
+            msg::{InvReq, InvResp, ReadReq, ReportDataMsg},
+            GenericPath,
+        },
+    },
+    tlv::{self, ElementType, FromTLV, TLVArray, TLVElement, TLVWriter, TagType},
+};
+
+use crate::{
+    attr_data_path,
+    common::{
+        attributes::*,
+        commands::{assert_inv_response, ExpectedInvResp},
+        im_engine::{ImEngine, ImInput},
+        init_env_logger,
+    },
+};
+
+const DEV_TYPE_DISHWASHER: DeviceType = DeviceType {
+    dtype: 0x0075,
+    drev: 1,
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_DISHWASHER,
+            parent: None,
+            clusters: &[descriptor::CLUSTER, DISHWASHER_MODE_CLUSTER],
+            client_clusters: &[],
+            tags: &[],
+        },
+    ],
+};
+
+const MODES: &[ModeOption<'static>] = &[
+    ModeOption::new("Normal", 0, &[ModeTag::new(TAG_AUTO)]),
+    ModeOption::new("Eco", 1, &[ModeTag::new(TAG_LOW_ENERGY)]),
+    ModeOption::new("Express", 2, &[ModeTag::new(TAG_QUICK)]),
+];
+
+/// A dishwasher which cannot switch to the Express mode while washing
+#[derive(Default)]
+struct Dishwasher {
+    washing: Cell<bool>,
+    mode: Cell<u8>,
+}
+
+impl ModeDelegate for Dishwasher {
+    fn change_to_mode(&self, new_mode: u8) -> Result<(), u8> {
+        if new_mode == 2 && self.washing.get() {
+            Err(STATUS_INVALID_IN_MODE)
+        } else {
+            self.mode.set(new_mode);
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_change_to_mode() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let dishwasher = Dishwasher {
+        washing: Cell::new(true),
+        ..Default::default()
+    };
+    let modes = ModeCluster::new(MODES, 0, &dishwasher, *im.matter.borrow());
+
+    let dm_handler = (
+        NODE,
+        HandlerCompat(
+            root_endpoint::handler(0, &im.matter)
+                .chain(
+                    1,
+                    descriptor::ID,
+                    descriptor::DescriptorCluster::new(*im.matter.borrow()),
+                )
+                .chain(1, DISHWASHER_MODE_ID, &modes),
+        ),
+    );
+
+    for (new_mode, expected_status) in [
+        (1, STATUS_SUCCESS),
+        (1, STATUS_SUCCESS),
+        (2, STATUS_INVALID_IN_MODE),
+        (7, STATUS_UNSUPPORTED_MODE),
+    ] {
+        let data = move |tag: TagType, tw: &mut TLVWriter| {
+            tw.start_struct(tag).unwrap();
+            tw.u8(TagType::Context(0), new_mode).unwrap();
+            tw.end_container().unwrap();
+        };
+
+        let input = &[CmdData::new(
+            CmdPath::new(
+                Some(1),
+                Some(DISHWASHER_MODE_ID),
+                Some(cluster_mode_base::Commands::ChangeToMode as u32),
+            ),
+            EncodeValue::Closure(&data),
+        )];
+
+        let req = InvReq {
+            suppress_response: Some(false),
+            timed_request: Some(false),
+            inv_requests: Some(TLVArray::Slice(input)),
+        };
+        let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let resp = InvResp::from_tlv(&root).unwrap();
+        assert_inv_response(
+            &resp,
+            &[ExpectedInvResp::Cmd(
+                CmdPath::new(
+                    Some(1),
+                    Some(DISHWASHER_MODE_ID),
+                    Some(cluster_mode_base::RespCommands::ChangeToModeResponse as u32),
+                ),
+                expected_status,
+            )],
+        );
+    }
+
+    // Only the accepted change reached the dishwasher
+    assert_eq!(dishwasher.mode.get(), 1);
+    assert_eq!(modes.current_mode(), 1);
+
+    let current_mode = GenericPath::new(
+        Some(1),
+        Some(DISHWASHER_MODE_ID),
+        Some(cluster_mode_base::Attributes::CurrentMode as u32),
+    );
+    let attr_paths = [AttrPath::new(&current_mode)];
+    let read_req = ReadReq::new(true).set_attr_requests(&attr_paths);
+    let input = ImInput::new(OpCode::ReadRequest, &read_req);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+    let received = ReportDataMsg::from_tlv(&root).unwrap();
+    assert_attr_report(
+        &received,
+        &[attr_data_path!(current_mode, ElementType::U8(1))],
+    );
+}
@@ -0,0 +1,241 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+
+use rs_matter::{
+    data_model::{
+        cluster_on_off::{self, OnOffCluster, StartUpOnOffEnum, LIGHTING_CLUSTER},
+        objects::{DataModelHandler, DeviceType, EncodeValue, Endpoint, HandlerCompat, Node},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{AttrData, AttrPath, AttrStatus, CmdData, CmdPath, CmdStatus},
+            msg::{InvReq, InvResp, WriteReq, WriteResp},
+            GenericPath,
+        },
+    },
+    tlv::{self, FromTLV, TLVArray, TLVWriter, TagType},
+};
+
+use crate::common::{
+    commands::{assert_inv_response, ExpectedInvResp},
+    im_engine::{ImEngine, ImInput},
+    init_env_logger,
+};
+
+const DEV_TYPE_ON_OFF_LIGHT: DeviceType = DeviceType {
+    dtype: 0x0100,
+    drev: 2,
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DEV_TYPE_ON_OFF_LIGHT,
+            parent: None,
+            clusters: &[descriptor::CLUSTER, LIGHTING_CLUSTER],
+            client_clusters: &[],
+            tags: &[],
+        },
+    ],
+};
+
+/// Invokes an OnWithTimedOff command on the light, expecting it to succeed
+fn on_with_timed_off<H>(im: &ImEngine, handler: &H, control: u8, on_time: u16, off_wait_time: u16)
+where
+    H: DataModelHandler,
+{
+    let data = move |tag: TagType, tw: &mut TLVWriter| {
+        tw.start_struct(tag).unwrap();
+        tw.u8(TagType::Context(0), control).unwrap();
+        tw.u16(TagType::Context(1), on_time).unwrap();
+        tw.u16(TagType::Context(2), off_wait_time).unwrap();
+        tw.end_container().unwrap();
+    };
+
+    invoke(
+        im,
+        handler,
+        cluster_on_off::Commands::OnWithTimedOff as _,
+        &data,
+    );
+}
+
+/// Invokes a command on the light, expecting it to succeed
+fn invoke<H>(im: &ImEngine, handler: &H, cmd: u32, data: &dyn Fn(TagType, &mut TLVWriter))
+where
+    H: DataModelHandler,
+{
+    let path = CmdPath::new(Some(1), Some(cluster_on_off::ID), Some(cmd));
+    let input = &[CmdData::new(path.clone(), EncodeValue::Closure(data))];
+
+    let req = InvReq {
+        suppress_response: Some(false),
+        timed_request: Some(false),
+        inv_requests: Some(TLVArray::Slice(input)),
+    };
+    let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    im.process_with(handler, &[&input], &mut out).unwrap();
+
+    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+    let resp = InvResp::from_tlv(&root).unwrap();
+    assert_inv_response(
+        &resp,
+        &[ExpectedInvResp::Status(CmdStatus::new(
+            path,
+            IMStatusCode::Success,
+            0,
+        ))],
+    );
+}
+
+#[test]
+fn test_timed_on_off() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let on_off = OnOffCluster::new(*im.matter.borrow());
+
+    let dm_handler = (
+        NODE,
+        HandlerCompat(
+            root_endpoint::handler(0, &im.matter)
+                .chain(
+                    1,
+                    descriptor::ID,
+                    descriptor::DescriptorCluster::new(*im.matter.borrow()),
+                )
+                .chain(1, cluster_on_off::ID, &on_off),
+        ),
+    );
+
+    // On for 2/10ths of a second, then off
+    on_with_timed_off(&im, &dm_handler, 0, 2, 5);
+    assert!(on_off.get());
+    assert_eq!((on_off.on_time(), on_off.off_wait_time()), (2, 5));
+
+    assert!(on_off.tick());
+    assert!(on_off.get());
+    assert!(on_off.tick());
+    assert!(!on_off.get());
+    assert_eq!((on_off.on_time(), on_off.off_wait_time()), (0, 0));
+    assert!(!on_off.tick());
+
+    // Turned off early, and guarded against being turned back on with a timed on, until
+    // the shortened off wait time elapses
+    on_with_timed_off(&im, &dm_handler, 0, 10, 5);
+    let empty = |tag: TagType, tw: &mut TLVWriter| {
+        tw.start_struct(tag).unwrap();
+        tw.end_container().unwrap();
+    };
+    invoke(&im, &dm_handler, cluster_on_off::Commands::Off as _, &empty);
+    assert_eq!((on_off.on_time(), on_off.off_wait_time()), (0, 5));
+
+    on_with_timed_off(&im, &dm_handler, 0, 10, 1);
+    assert!(!on_off.get());
+    assert_eq!(on_off.off_wait_time(), 1);
+
+    assert!(on_off.tick());
+    assert_eq!(on_off.off_wait_time(), 0);
+
+    // Only accepted when on
+    on_with_timed_off(&im, &dm_handler, 0x01, 10, 0);
+    assert!(!on_off.get());
+
+    on_with_timed_off(&im, &dm_handler, 0, 10, 0);
+    assert!(on_off.get());
+    assert_eq!(on_off.on_time(), 10);
+}
+
+#[test]
+fn test_start_up_on_off() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let on_off = OnOffCluster::new(*im.matter.borrow());
+
+    let dm_handler = (
+        NODE,
+        HandlerCompat(
+            root_endpoint::handler(0, &im.matter)
+                .chain(
+                    1,
+                    descriptor::ID,
+                    descriptor::DescriptorCluster::new(*im.matter.borrow()),
+                )
+                .chain(1, cluster_on_off::ID, &on_off),
+        ),
+    );
+
+    let path = GenericPath::new(
+        Some(1),
+        Some(cluster_on_off::ID),
+        Some(cluster_on_off::AttributesDiscriminants::StartUpOnOff as u32),
+    );
+
+    for (value, expected_status) in [
+        (StartUpOnOffEnum::Toggle as u8, IMStatusCode::Success),
+        (3, IMStatusCode::ConstraintError),
+    ] {
+        let data = move |tag: TagType, tw: &mut TLVWriter| {
+            tw.u8(tag, value).unwrap();
+        };
+
+        let input = &[AttrData::new(
+            None,
+            AttrPath::new(&path),
+            EncodeValue::Closure(&data),
+        )];
+
+        let req = WriteReq::new(false, input);
+        let input = ImInput::new(OpCode::WriteRequest, &req);
+
+        let mut out = heapless::Vec::<_, 1>::new();
+        im.process_with(&dm_handler, &[&input], &mut out).unwrap();
+
+        let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+        let resp = WriteResp::from_tlv(&root).unwrap();
+        assert!(resp
+            .write_responses
+            .iter()
+            .eq([AttrStatus::new(&path, expected_status, 0)]));
+    }
+
+    assert_eq!(on_off.start_up_on_off(), Some(StartUpOnOffEnum::Toggle));
+
+    // The StartUpOnOff value is persisted, for the light to apply it at startup
+    let mut buf = [0; 256];
+    assert!(im.matter.store_nv_attributes(&mut buf).unwrap().is_some());
+
+    on_off.start_up();
+    assert!(on_off.get());
+    on_off.start_up();
+    assert!(!on_off.get());
+}
@@ -0,0 +1,224 @@
+/*
+ *
+ *    Copyright (c) 2024 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::borrow::Borrow;
+use core::cell::Cell;
+
+use rs_matter::{
+    data_model::{
+        cluster_operational_state::{
+            self as opstate, OperationalStateCluster, OperationalStateDelegate,
+            ERROR_COMMAND_INVALID_IN_STATE, ERROR_NO_ERROR, RVC_OPERATIONAL_STATE_CLUSTER,
+            RVC_OPERATIONAL_STATE_ID, STATES, STATE_PAUSED, STATE_RUNNING, STATE_STOPPED,
+        },
+        objects::{DataModelHandler, DeviceType, EncodeValue, Endpoint, HandlerCompat, Node},
+        root_endpoint,
+        system_model::descriptor,
+    },
+    interaction_model::{
+        core::{IMStatusCode, OpCode},
+        messages::{
+            ib::{self, CmdData, CmdPath},
+            msg::{InvReq, InvResp},
+        },
+    },
+    tlv::{self, FromTLV, TLVArray, TLVWriter, TagType},
+};
+
+use crate::common::{
+    im_engine::{ImEngine, ImInput},
+    init_env_logger,
+};
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::endpoint(0),
+        Endpoint {
+            id: 1,
+            device_type: DeviceType {
+                dtype: 0x007B,
+                drev: 1,
+            },
+            parent: None,
+            clusters: &[
+                descriptor::CLUSTER,
+                opstate::CLUSTER,
+                RVC_OPERATIONAL_STATE_CLUSTER,
+            ],
+            client_clusters: &[],
+            tags: &[],
+        },
+    ],
+};
+
+/// A device which counts the commands reaching it
+#[derive(Default)]
+struct Device {
+    commands: Cell<u32>,
+}
+
+impl Device {
+    fn command(&self) -> Result<(), u8> {
+        self.commands.set(self.commands.get() + 1);
+        Ok(())
+    }
+}
+
+impl OperationalStateDelegate for Device {
+    fn pause(&self) -> Result<(), u8> {
+        self.command()
+    }
+
+    fn resume(&self) -> Result<(), u8> {
+        self.command()
+    }
+
+    fn start(&self) -> Result<(), u8> {
+        self.command()
+    }
+
+    fn stop(&self) -> Result<(), u8> {
+        self.command()
+    }
+}
+
+/// Invokes a command without fields, returning the operational error of the response, or the
+/// status the command was rejected with
+fn invoke<H: DataModelHandler>(
+    im: &ImEngine,
+    handler: &H,
+    cluster_id: u32,
+    command: opstate::Commands,
+) -> Result<u8, IMStatusCode> {
+    let data = |tag: TagType, tw: &mut TLVWriter| {
+        tw.start_struct(tag).unwrap();
+        tw.end_container().unwrap();
+    };
+
+    let input = &[CmdData::new(
+        CmdPath::new(Some(1), Some(cluster_id), Some(command as u32)),
+        EncodeValue::Closure(&data),
+    )];
+
+    let req = InvReq {
+        suppress_response: Some(false),
+        timed_request: Some(false),
+        inv_requests: Some(TLVArray::Slice(input)),
+    };
+    let input = ImInput::new(OpCode::InvokeRequest, &req);
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    im.process_with(handler, &[&input], &mut out).unwrap();
+
+    let root = tlv::get_root_node_struct(&out[0].data).unwrap();
+    let resp = InvResp::from_tlv(&root).unwrap();
+
+    match resp.inv_responses.unwrap().iter().next().unwrap() {
+        ib::InvResp::Cmd(cmd) => match cmd.data {
+            EncodeValue::Tlv(data) => Ok(data
+                .find_tag(0)
+                .and_then(|state| state.find_tag(0))
+                .and_then(|id| id.u8())
+                .unwrap()),
+            _ => panic!("Incorrect CmdDataType"),
+        },
+        ib::InvResp::Status(status) => Err(status.status().status),
+    }
+}
+
+#[test]
+fn test_operational_state_commands() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+
+    let device = Device::default();
+    let opstate = OperationalStateCluster::new(
+        1,
+        opstate::ID,
+        STATES,
+        Some(&["Washing", "Rinsing", "Drying"]),
+        &device,
+        *im.matter.borrow(),
+    );
+    let rvc = OperationalStateCluster::new(
+        1,
+        RVC_OPERATIONAL_STATE_ID,
+        STATES,
+        None,
+        &device,
+        *im.matter.borrow(),
+    );
+
+    let dm_handler = (
+        NODE,
+        HandlerCompat(
+            root_endpoint::handler(0, &im.matter)
+                .chain(
+                    1,
+                    descriptor::ID,
+                    descriptor::DescriptorCluster::new(*im.matter.borrow()),
+                )
+                .chain(1, opstate::ID, &opstate)
+                .chain(1, RVC_OPERATIONAL_STATE_ID, &rvc),
+        ),
+    );
+
+    // A stopped operation can be neither paused nor resumed
+    for command in [opstate::Commands::Pause, opstate::Commands::Resume] {
+        assert_eq!(
+            invoke(&im, &dm_handler, opstate::ID, command),
+            Ok(ERROR_COMMAND_INVALID_IN_STATE)
+        );
+    }
+    assert_eq!(opstate.state(), STATE_STOPPED);
+
+    for (command, expected_state) in [
+        (opstate::Commands::Start, STATE_RUNNING),
+        (opstate::Commands::Pause, STATE_PAUSED),
+        (opstate::Commands::Pause, STATE_PAUSED),
+        (opstate::Commands::Resume, STATE_RUNNING),
+        (opstate::Commands::Stop, STATE_STOPPED),
+    ] {
+        assert_eq!(
+            invoke(&im, &dm_handler, opstate::ID, command),
+            Ok(ERROR_NO_ERROR)
+        );
+        assert_eq!(opstate.state(), expected_state);
+    }
+
+    // Pausing while paused does not reach the device
+    assert_eq!(device.commands.get(), 4);
+
+    // The RVC Operational State cluster is started with the run mode of the robot
+    assert_eq!(
+        invoke(
+            &im,
+            &dm_handler,
+            RVC_OPERATIONAL_STATE_ID,
+            opstate::Commands::Start
+        ),
+        Err(IMStatusCode::UnsupportedCommand)
+    );
+
+    opstate
+        .complete(&im.matter, ERROR_NO_ERROR, Some(3600), None)
+        .unwrap();
+    assert_eq!(opstate.state(), STATE_STOPPED);
+}
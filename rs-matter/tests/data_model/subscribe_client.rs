@@ -0,0 +1,107 @@
+/*
+ *
+ *    Copyright (c) 2023 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use rs_matter::{
+    interaction_model::{
+        client::ReportHandler,
+        core::{IMStatusCode, OpCode},
+        messages::msg::{ReportDataMsg, StatusResp},
+    },
+    tlv::{get_root_node_struct, FromTLV},
+};
+
+use crate::common::{
+    im_engine::{ImEngine, ImInput},
+    init_env_logger,
+};
+
+const SUBSCRIPTION_ID: u32 = 5;
+
+/// Counts the reports of a single subscription
+struct CountingHandler(AtomicU32);
+
+impl ReportHandler for CountingHandler {
+    fn handle(&self, subscription_id: u32, _report: &ReportDataMsg<'_>) -> bool {
+        if subscription_id == SUBSCRIPTION_ID {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+static HANDLER: CountingHandler = CountingHandler(AtomicU32::new(0));
+
+fn report(subscription_id: u32) -> ReportDataMsg<'static> {
+    ReportDataMsg {
+        subscription_id: Some(subscription_id),
+        attr_reports: None,
+        event_reports: None,
+        more_chunks: None,
+        suppress_response: None,
+    }
+}
+
+fn status(data: &[u8]) -> IMStatusCode {
+    StatusResp::from_tlv(&get_root_node_struct(data).unwrap())
+        .unwrap()
+        .status
+}
+
+#[test]
+fn test_client_reports() {
+    init_env_logger();
+
+    let mut out = heapless::Vec::<_, 1>::new();
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    let mut send_report = |subscription_id| {
+        let report = report(subscription_id);
+
+        im.process(
+            &handler,
+            &[&ImInput::new(OpCode::ReportData, &report)],
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(out[0].action, OpCode::StatusResponse);
+        status(&out[0].data)
+    };
+
+    // Without a report handler, the reports of any subscription are rejected
+    assert_eq!(
+        send_report(SUBSCRIPTION_ID),
+        IMStatusCode::InvalidSubscription
+    );
+
+    im.matter.set_report_handler(Some(&HANDLER));
+
+    assert_eq!(send_report(SUBSCRIPTION_ID), IMStatusCode::Success);
+    assert_eq!(HANDLER.0.load(Ordering::SeqCst), 1);
+
+    // The handler does not know of other subscriptions
+    assert_eq!(
+        send_report(SUBSCRIPTION_ID + 1),
+        IMStatusCode::InvalidSubscription
+    );
+    assert_eq!(HANDLER.0.load(Ordering::SeqCst), 1);
+}
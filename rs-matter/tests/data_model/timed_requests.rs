@@ -16,16 +16,17 @@
  */
 
 use rs_matter::{
-    data_model::objects::EncodeValue,
+    data_model::{objects::EncodeValue, sdm::admin_commissioning as adm_comm},
     interaction_model::{
         core::IMStatusCode,
         messages::ib::{AttrData, AttrPath, AttrStatus},
-        messages::{ib::CmdData, ib::CmdPath, GenericPath},
+        messages::{ib::CmdData, ib::CmdPath, ib::CmdStatus, GenericPath},
     },
     tlv::TLVWriter,
 };
 
 use crate::{
+    cmd_data,
     common::{
         commands::*,
         echo_cluster,
@@ -95,7 +96,10 @@ fn test_timed_cmd_success() {
     // A timed request that works
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
     let expected = &[echo_resp!(0, 10), echo_resp!(1, 30)];
     ImEngine::timed_commands(
         input,
@@ -106,12 +110,51 @@ fn test_timed_cmd_success() {
     );
 }
 
+#[test]
+fn test_timed_only_cmd() {
+    // A command that can only be invoked in a timed interaction
+    init_env_logger();
+
+    let revoke_comm = CmdPath::new(
+        Some(0),
+        Some(adm_comm::ID),
+        Some(adm_comm::Commands::RevokeComm as u32),
+    );
+    let input = &[cmd_data!(revoke_comm.clone(), 0)];
+
+    // Rejected outside of a timed interaction
+    ImEngine::commands(
+        input,
+        &[ExpectedInvResp::Status(CmdStatus::new(
+            revoke_comm.clone(),
+            IMStatusCode::NeedsTimedInteraction,
+            0,
+        ))],
+    );
+
+    // Handled in a timed interaction, revoking the commissioning window the engine opens
+    ImEngine::timed_commands(
+        input,
+        &TimedInvResponse::TransactionSuccess(&[ExpectedInvResp::Status(CmdStatus::new(
+            revoke_comm,
+            IMStatusCode::Success,
+            0,
+        ))]),
+        2000,
+        0,
+        true,
+    );
+}
+
 #[test]
 fn test_timed_cmd_timeout() {
     // A timed request that is executed after t imeout
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
     ImEngine::timed_commands(
         input,
         &TimedInvResponse::TransactionError(IMStatusCode::Timeout),
@@ -126,7 +169,10 @@ fn test_timed_cmd_timedout_mismatch() {
     // A timed request with timeout mismatch
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
     ImEngine::timed_commands(
         input,
         &TimedInvResponse::TransactionError(IMStatusCode::TimedRequestMisMatch),
@@ -135,7 +181,10 @@ fn test_timed_cmd_timedout_mismatch() {
         false,
     );
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[
+        echo_req!(0, 5).set_command_ref(1),
+        echo_req!(1, 10).set_command_ref(2),
+    ];
     ImEngine::timed_commands(
         input,
         &TimedInvResponse::TransactionError(IMStatusCode::TimedRequestMisMatch),
@@ -144,3 +193,19 @@ fn test_timed_cmd_timedout_mismatch() {
         true,
     );
 }
+
+#[test]
+fn test_timed_read_invalid() {
+    // Only a Write or an Invoke request may follow a TimedRequest
+    init_env_logger();
+
+    let input = &[AttrPath::new(&GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::Att1 as u32),
+    ))];
+
+    let im = ImEngine::new_default();
+    im.add_default_acl();
+    im.handle_timed_read_reqs(&im.handler(), input, IMStatusCode::InvalidAction, 2000);
+}
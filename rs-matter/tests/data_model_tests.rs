@@ -19,9 +19,17 @@ mod common;
 
 mod data_model {
     mod acl_and_dataver;
+    mod async_handler;
     mod attribute_lists;
     mod attributes;
+    mod binding;
+    mod bridge;
     mod commands;
     mod long_reads;
+    mod middleware;
+    mod mode_base;
+    mod on_off;
+    mod operational_state;
+    mod subscribe_client;
     mod timed_requests;
 }